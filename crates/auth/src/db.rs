@@ -96,6 +96,7 @@ impl AuthDatabase {
 			user.sensitive = SensitiveUserInfoSerialized::None;
 		}
 
+		self.write().context("Failed to write to database")?;
 		Ok(())
 	}
 
@@ -6,19 +6,23 @@ use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 pub use oauth2::basic::{BasicClient, BasicTokenType};
 pub use oauth2::reqwest::async_http_client;
 pub use oauth2::{
-	AuthUrl, ClientId, DeviceAuthorizationUrl, EmptyExtraTokenFields, ErrorResponse, RefreshToken,
-	RequestTokenError, Scope, StandardDeviceAuthorizationResponse, StandardTokenResponse,
-	TokenResponse, TokenUrl,
+	AuthUrl, ClientId, DeviceAuthorizationUrl, DeviceCodeErrorResponseType, EmptyExtraTokenFields,
+	ErrorResponse, RefreshToken, RequestTokenError, Scope, StandardDeviceAuthorizationResponse,
+	StandardTokenResponse, TokenResponse, TokenUrl,
 };
 use reqwest::Response;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::time::Duration;
 
 const DEVICE_CODE_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode";
 const MSA_AUTHORIZE_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/authorize";
 const MSA_TOKEN_URL: &str = "https://login.microsoftonline.com/consumers/oauth2/v2.0/token";
 
+/// The default amount of time to wait for the user to sign in before giving up
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 /// Authenticate a Microsoft user using Microsoft OAuth.
 /// Will authenticate every time and will not use the database.
 pub async fn authenticate_microsoft_user(
@@ -32,8 +36,16 @@ pub async fn authenticate_microsoft_user(
 		.context("Failed to execute authorization and generate login page")?;
 
 	o.display_special_ms_auth(response.verification_uri(), response.user_code().secret());
+	let timeout = DEFAULT_AUTH_TIMEOUT.min(response.expires_in());
+	o.display(
+		MessageContents::Simple(format!(
+			"The code will expire in {} minutes",
+			timeout.as_secs().div_ceil(60)
+		)),
+		MessageLevel::Important,
+	);
 
-	let token = get_microsoft_token(&oauth_client, response)
+	let token = get_microsoft_token(&oauth_client, response, timeout)
 		.await
 		.context("Failed to get Microsoft token")?;
 
@@ -115,22 +127,27 @@ pub async fn generate_login_page(
 /// A TokenResponse from Microsoft OAuth
 pub type MicrosoftToken = StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType>;
 
-/// Get the Microsoft token. Will wait indefinitely until the user has signed in to
-/// Microsoft and authenticated the application
+/// Get the Microsoft token. Will wait until the user has signed in to
+/// Microsoft and authenticated the application, or until the timeout is reached.
+/// The returned future can be safely dropped to cancel authentication
 pub async fn get_microsoft_token(
 	client: &BasicClient,
 	auth_response: StandardDeviceAuthorizationResponse,
+	timeout: Duration,
 ) -> anyhow::Result<MicrosoftToken> {
 	let out = client
 		.exchange_device_access_token(&auth_response)
-		.request_async(
-			async_http_client,
-			|x| async move { std::thread::sleep(x) },
-			None,
-		)
+		.request_async(async_http_client, tokio::time::sleep, Some(timeout))
 		.await;
 
-	out.map_err(decorate_request_token_error)
+	out.map_err(|e| match e {
+		RequestTokenError::ServerResponse(response)
+			if response.error() == &DeviceCodeErrorResponseType::ExpiredToken =>
+		{
+			anyhow!("Authentication timed out. Please try again")
+		}
+		e => decorate_request_token_error(e),
+	})
 }
 
 /// Gets the access token using a refresh token
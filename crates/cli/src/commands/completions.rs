@@ -0,0 +1,270 @@
+use std::collections::BTreeSet;
+use std::io::Write;
+
+use anyhow::Context;
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::Shell;
+use mcvm::config::Config;
+use mcvm::io::paths::Paths;
+use serde_json::Value;
+
+use super::Cli;
+
+/// Kinds of values that can be completed dynamically from the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionKind {
+	/// Instance IDs
+	Instances,
+	/// Profile IDs
+	Profiles,
+	/// IDs of packages configured on instances and profiles
+	Packages,
+}
+
+impl CompletionKind {
+	/// Get the argument name of this kind for the helper command
+	fn arg_name(&self) -> &'static str {
+		match self {
+			Self::Instances => "instances",
+			Self::Profiles => "profiles",
+			Self::Packages => "packages",
+		}
+	}
+}
+
+/// Subcommand paths whose positional arguments are completed dynamically.
+/// Aliases of the top-level commands are added when generating the scripts
+const DYNAMIC_ARGS: &[(&str, CompletionKind)] = &[
+	("launch", CompletionKind::Instances),
+	("instance launch", CompletionKind::Instances),
+	("instance stop", CompletionKind::Instances),
+	("instance exec", CompletionKind::Instances),
+	("instance info", CompletionKind::Instances),
+	("instance update", CompletionKind::Instances),
+	("profile update", CompletionKind::Profiles),
+	("instance delete", CompletionKind::Instances),
+	("instance rm", CompletionKind::Instances),
+	("instance duplicate", CompletionKind::Instances),
+	("instance rename", CompletionKind::Instances),
+	("instance export", CompletionKind::Instances),
+	("instance dir", CompletionKind::Instances),
+	("instance datapack add", CompletionKind::Instances),
+	("instance datapack list", CompletionKind::Instances),
+	("instance datapack remove", CompletionKind::Instances),
+	("instance resourcepack add", CompletionKind::Instances),
+	("instance resourcepack list", CompletionKind::Instances),
+	("instance resourcepack remove", CompletionKind::Instances),
+	("instance shaderpack add", CompletionKind::Instances),
+	("instance shaderpack list", CompletionKind::Instances),
+	("instance shaderpack remove", CompletionKind::Instances),
+	("instance snapshot create", CompletionKind::Instances),
+	("instance snapshot list", CompletionKind::Instances),
+	("instance snapshot restore", CompletionKind::Instances),
+	("instance snapshot remove", CompletionKind::Instances),
+	("package info", CompletionKind::Packages),
+	("package cat", CompletionKind::Packages),
+	("package features", CompletionKind::Packages),
+];
+
+/// Aliases for the top-level commands used in DYNAMIC_ARGS
+const COMMAND_ALIASES: &[(&str, &str)] = &[
+	("instance", "inst"),
+	("package", "pkg"),
+	("profile", "prof"),
+];
+
+/// Print the completion script for a shell
+pub fn print_completions(shell: Shell) -> anyhow::Result<()> {
+	let mut out = Vec::new();
+	clap_complete::generate(shell, &mut Cli::command(), "mcvm", &mut out);
+	match shell {
+		Shell::Bash => out.extend(get_bash_dynamic_script().as_bytes()),
+		Shell::Fish => out.extend(get_fish_dynamic_script().as_bytes()),
+		_ => {}
+	}
+
+	std::io::stdout()
+		.write_all(&out)
+		.context("Failed to write completions")?;
+
+	Ok(())
+}
+
+/// Print the candidates for a dynamically completed argument, one per line.
+/// This reads the config file directly instead of fully loading it, as it runs on every completion
+pub fn print_candidates(kind: CompletionKind) -> anyhow::Result<()> {
+	let paths = Paths::new_no_create().context("Failed to get system paths")?;
+	let path = Config::get_path(&paths);
+	if !path.exists() {
+		return Ok(());
+	}
+	let config: Value =
+		serde_json::from_str(&std::fs::read_to_string(path).context("Failed to read config file")?)
+			.context("Failed to parse config file")?;
+
+	for candidate in get_candidates(&config, kind) {
+		println!("{candidate}");
+	}
+
+	Ok(())
+}
+
+/// Get the candidates for a kind of argument from the raw config
+fn get_candidates(config: &Value, kind: CompletionKind) -> BTreeSet<String> {
+	let get_keys = |field: &str| -> Vec<&Value> {
+		config
+			.get(field)
+			.and_then(|x| x.as_object())
+			.map(|x| x.values().collect())
+			.unwrap_or_default()
+	};
+	let get_ids = |field: &str| -> BTreeSet<String> {
+		config
+			.get(field)
+			.and_then(|x| x.as_object())
+			.map(|x| x.keys().cloned().collect())
+			.unwrap_or_default()
+	};
+
+	match kind {
+		CompletionKind::Instances => get_ids("instances"),
+		CompletionKind::Profiles => get_ids("profiles"),
+		CompletionKind::Packages => {
+			let mut out = BTreeSet::new();
+			for item in get_keys("instances")
+				.into_iter()
+				.chain(get_keys("profiles"))
+			{
+				let Some(packages) = item.get("packages") else {
+					continue;
+				};
+				// Profiles can have packages split by side
+				let lists: Vec<&Value> = match packages {
+					Value::Object(sides) => sides.values().collect(),
+					other => vec![other],
+				};
+				for package in lists.iter().filter_map(|x| x.as_array()).flatten() {
+					let id = match package {
+						Value::String(id) => Some(id.as_str()),
+						Value::Object(package) => package.get("id").and_then(|x| x.as_str()),
+						_ => None,
+					};
+					// Strip any version specifiers
+					if let Some(id) = id {
+						out.insert(get_package_id(id).to_string());
+					}
+				}
+			}
+			out
+		}
+	}
+}
+
+/// Get the plain ID from a package request string
+fn get_package_id(package: &str) -> &str {
+	package.split_once('@').map_or(package, |x| x.0)
+}
+
+/// Get all of the subcommand paths for a dynamic argument, including aliases
+fn get_path_variants(path: &str) -> Vec<String> {
+	let mut out = vec![path.to_string()];
+	for (command, alias) in COMMAND_ALIASES {
+		if let Some(rest) = path.strip_prefix(command) {
+			out.push(format!("{alias}{rest}"));
+		}
+	}
+	out
+}
+
+/// Get the bash script that adds dynamic completions on top of the generated ones
+fn get_bash_dynamic_script() -> String {
+	let mut cases = String::new();
+	for (path, kind) in DYNAMIC_ARGS {
+		let patterns = get_path_variants(path)
+			.into_iter()
+			.map(|x| format!("\"{x}\"*"))
+			.collect::<Vec<_>>()
+			.join("|");
+		cases.push_str(&format!("\t\t{patterns}) kind={} ;;\n", kind.arg_name()));
+	}
+
+	format!(
+		r#"
+_mcvm_dynamic() {{
+	local cur="${{COMP_WORDS[COMP_CWORD]}}"
+	local words=()
+	for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+		[[ "$word" != -* ]] && words+=("$word")
+	done
+	local kind=""
+	case "${{words[*]}}" in
+{cases}	esac
+	if [[ -n "$kind" && "$cur" != -* ]]; then
+		COMPREPLY=($(compgen -W "$(mcvm complete "$kind" 2>/dev/null)" -- "$cur"))
+		return 0
+	fi
+	_mcvm "$@"
+}}
+
+complete -F _mcvm_dynamic -o bashdefault -o default mcvm
+"#
+	)
+}
+
+/// Get the fish script that adds dynamic completions on top of the generated ones
+fn get_fish_dynamic_script() -> String {
+	let mut out = String::from("\n");
+	for (path, kind) in DYNAMIC_ARGS {
+		for variant in get_path_variants(path) {
+			let condition = variant
+				.split(' ')
+				.map(|x| format!("__fish_seen_subcommand_from {x}"))
+				.collect::<Vec<_>>()
+				.join("; and ");
+			out.push_str(&format!(
+				"complete -c mcvm -n \"{condition}\" -f -a \"(mcvm complete {})\"\n",
+				kind.arg_name()
+			));
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_completion_candidates() {
+		let config = serde_json::json!({
+			"instances": {
+				"survival": {
+					"type": "client",
+					"packages": ["sodium", {"id": "lithium"}]
+				},
+				"server": { "type": "server" }
+			},
+			"profiles": {
+				"base": {
+					"packages": {
+						"global": ["fabric-api@0.1.0"],
+						"client": ["sodium"]
+					}
+				}
+			}
+		});
+
+		assert_eq!(
+			get_candidates(&config, CompletionKind::Instances),
+			BTreeSet::from(["server".into(), "survival".into()])
+		);
+		assert_eq!(
+			get_candidates(&config, CompletionKind::Profiles),
+			BTreeSet::from(["base".into()])
+		);
+		assert_eq!(
+			get_candidates(&config, CompletionKind::Packages),
+			BTreeSet::from(["fabric-api".into(), "lithium".into(), "sodium".into()])
+		);
+	}
+}
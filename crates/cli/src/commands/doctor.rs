@@ -0,0 +1,393 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use color_print::cformat;
+use mcvm::config::Config;
+use mcvm::core::io::persistent::PersistentData;
+use mcvm::io::lock::Lockfile;
+use serde::Serialize;
+
+use super::CmdData;
+use crate::output::{print_json, TerminalOutput};
+
+/// The outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CheckStatus {
+	Pass,
+	Warn,
+	Fail,
+}
+
+/// The result of a single check
+#[derive(Debug, Serialize)]
+struct CheckResult {
+	/// The name of the check
+	name: String,
+	status: CheckStatus,
+	/// A description of what was found
+	message: String,
+	/// A suggestion for how to resolve the problem
+	#[serde(skip_serializing_if = "Option::is_none")]
+	remedy: Option<String>,
+	/// Whether the problem was automatically fixed
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	fixed: bool,
+}
+
+impl CheckResult {
+	fn pass(name: impl Into<String>, message: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			status: CheckStatus::Pass,
+			message: message.into(),
+			remedy: None,
+			fixed: false,
+		}
+	}
+
+	fn warn(
+		name: impl Into<String>,
+		message: impl Into<String>,
+		remedy: impl Into<String>,
+	) -> Self {
+		Self {
+			name: name.into(),
+			status: CheckStatus::Warn,
+			message: message.into(),
+			remedy: Some(remedy.into()),
+			fixed: false,
+		}
+	}
+
+	fn fail(
+		name: impl Into<String>,
+		message: impl Into<String>,
+		remedy: impl Into<String>,
+	) -> Self {
+		Self {
+			name: name.into(),
+			status: CheckStatus::Fail,
+			message: message.into(),
+			remedy: Some(remedy.into()),
+			fixed: false,
+		}
+	}
+
+	/// Mark this result as having been fixed
+	fn fixed(mut self, message: impl Into<String>) -> Self {
+		self.status = CheckStatus::Pass;
+		self.message = message.into();
+		self.remedy = None;
+		self.fixed = true;
+		self
+	}
+}
+
+/// Run all of the checks and print a report
+pub async fn run(data: &mut CmdData, fix: bool) -> anyhow::Result<()> {
+	let mut results = Vec::new();
+
+	check_paths(data, &mut results);
+	let config_loaded = check_config(data, &mut results).await;
+	if config_loaded {
+		check_repos(data, &mut results).await;
+		check_user(data, &mut results);
+		check_lockfile(data, fix, &mut results);
+	}
+	check_java(data, fix, &mut results).await;
+
+	if data.output.is_json() {
+		print_json(&results)?;
+	} else {
+		print_report(&results, &data.output);
+	}
+
+	let failed = results
+		.iter()
+		.filter(|x| x.status == CheckStatus::Fail)
+		.count();
+	if failed > 0 {
+		bail!("{failed} checks failed");
+	}
+
+	Ok(())
+}
+
+/// Check that the config can be opened and fully loaded. Returns whether it was loaded
+async fn check_config(data: &mut CmdData, results: &mut Vec<CheckResult>) -> bool {
+	const NAME: &str = "Config";
+	let path = Config::get_path(&data.paths);
+	let remedy = format!(
+		"Fix the config file at {} with `mcvm config edit`, or restore a backup made with `mcvm config backup`",
+		path.display()
+	);
+	if let Err(e) = Config::open(&path) {
+		results.push(CheckResult::fail(NAME, format!("{e:#}"), remedy));
+		return false;
+	}
+	if let Err(e) = data.ensure_config(false).await {
+		results.push(CheckResult::fail(NAME, format!("{e:#}"), remedy));
+		return false;
+	}
+
+	results.push(CheckResult::pass(NAME, "Config file is valid"));
+	true
+}
+
+/// Check that all of the directories used by mcvm are writable
+fn check_paths(data: &CmdData, results: &mut Vec<CheckResult>) {
+	let paths = [
+		&data.paths.data,
+		&data.paths.internal,
+		&data.paths.addons,
+		&data.paths.logs,
+		&data.paths.snapshots,
+		&data.paths.plugins,
+		&data.paths.core.java,
+		&data.paths.core.assets,
+		&data.paths.pkg_cache,
+		&data.paths.project.config_dir().to_path_buf(),
+	];
+
+	let unwritable: Vec<_> = paths.into_iter().filter(|x| !is_writable(x)).collect();
+	if unwritable.is_empty() {
+		results.push(CheckResult::pass("Paths", "All directories are writable"));
+	} else {
+		for path in unwritable {
+			results.push(CheckResult::fail(
+				"Paths",
+				format!("Directory {} is not writable", path.display()),
+				"Make sure that the directory exists and that your user owns it",
+			));
+		}
+	}
+}
+
+/// Checks whether a file can be created in a directory
+fn is_writable(dir: &Path) -> bool {
+	let path = dir.join(".mcvm_doctor");
+	if fs::write(&path, "").is_err() {
+		return false;
+	}
+	let _ = fs::remove_file(path);
+	true
+}
+
+/// Check that each configured package repository can be reached
+async fn check_repos(data: &mut CmdData, results: &mut Vec<CheckResult>) {
+	let config = data.config.get_mut();
+	let client = match config.prefs.make_client() {
+		Ok(client) => client,
+		Err(e) => {
+			results.push(CheckResult::fail(
+				"Repositories",
+				format!("Failed to create HTTP client: {e:#}"),
+				"Check the proxy setting in your preferences",
+			));
+			return;
+		}
+	};
+
+	for repo in config.packages.repos.iter_mut() {
+		let name = format!("Repository '{}'", repo.id);
+		match repo.sync(&data.paths, &client, &mut data.output).await {
+			Ok(..) => results.push(CheckResult::pass(name, "Reachable")),
+			Err(e) => results.push(CheckResult::fail(
+				name,
+				format!("Failed to reach {}: {e:#}", repo.get_location()),
+				"Check your internet connection and the URL of the repository, or remove it from your config",
+			)),
+		}
+	}
+}
+
+/// Check that the default user is logged in
+fn check_user(data: &CmdData, results: &mut Vec<CheckResult>) {
+	const NAME: &str = "Default user";
+	let config = data.config.get();
+	let Some(user) = config.users.get_chosen_user() else {
+		results.push(CheckResult::warn(
+			NAME,
+			"No default user is set",
+			"Add a user with `mcvm user add` and choose it with `mcvm user switch`",
+		));
+		return;
+	};
+
+	if user.is_auth_valid(&data.paths.core) {
+		results.push(CheckResult::pass(
+			NAME,
+			format!("User '{}' has valid authentication", user.get_id()),
+		));
+	} else {
+		results.push(CheckResult::warn(
+			NAME,
+			format!("User '{}' needs to log in again", user.get_id()),
+			format!("Run `mcvm user auth {}` to log in", user.get_id()),
+		));
+	}
+}
+
+/// Check that the lockfile can be read and doesn't have entries for instances that don't exist
+fn check_lockfile(data: &mut CmdData, fix: bool, results: &mut Vec<CheckResult>) {
+	const NAME: &str = "Lockfile";
+	let mut lock = match Lockfile::open(&data.paths, &mut data.output) {
+		Ok(lock) => lock,
+		Err(e) => {
+			results.push(CheckResult::fail(
+				NAME,
+				format!("{e:#}"),
+				format!(
+					"Restore the lockfile from the backup at {}",
+					Lockfile::get_backup_path(&data.paths).display()
+				),
+			));
+			return;
+		}
+	};
+
+	let config = data.config.get();
+	let orphaned: Vec<String> = lock
+		.get_instance_ids()
+		.into_iter()
+		.filter(|x| !config.instances.contains_key(*x))
+		.map(String::from)
+		.collect();
+	if orphaned.is_empty() {
+		results.push(CheckResult::pass(NAME, "No orphaned entries"));
+		return;
+	}
+
+	let result = CheckResult::warn(
+		NAME,
+		format!(
+			"Found entries for instances that no longer exist: {}",
+			orphaned.join(", ")
+		),
+		"Run `mcvm doctor --fix` to remove them",
+	);
+	if !fix {
+		results.push(result);
+		return;
+	}
+
+	for instance in &orphaned {
+		lock.remove_instance(instance);
+	}
+	match lock.finish(&data.paths) {
+		Ok(..) => results.push(result.fixed(format!(
+			"Removed entries for {} instances that no longer exist",
+			orphaned.len()
+		))),
+		Err(e) => results.push(CheckResult::fail(
+			NAME,
+			format!("Failed to write lockfile: {e:#}"),
+			"Make sure that the lockfile is writable",
+		)),
+	}
+}
+
+/// Check that the managed Java installations are still on the disk
+async fn check_java(data: &CmdData, fix: bool, results: &mut Vec<CheckResult>) {
+	const NAME: &str = "Java";
+	let mut persistent = match PersistentData::open(&data.paths.core) {
+		Ok(persistent) => persistent,
+		Err(e) => {
+			results.push(CheckResult::fail(
+				NAME,
+				format!("{e:#}"),
+				format!(
+					"Remove the file at {} so that it can be recreated",
+					PersistentData::get_path(&data.paths.core).display()
+				),
+			));
+			return;
+		}
+	};
+
+	let missing = persistent.get_missing_java_installations();
+	if missing.is_empty() {
+		results.push(CheckResult::pass(
+			NAME,
+			"All managed Java installations are present",
+		));
+		return;
+	}
+
+	for (installation, major_version) in missing {
+		let result = CheckResult::fail(
+			NAME,
+			format!("{installation} Java {major_version} is missing from the disk"),
+			"Run `mcvm doctor --fix` to forget it. It will be reinstalled the next time it is needed",
+		);
+		if !fix {
+			results.push(result);
+			continue;
+		}
+
+		let removed = persistent
+			.remove_java_installation(installation, &major_version)
+			.context("Failed to remove Java installation");
+		match removed {
+			Ok(..) => results.push(result.fixed(format!(
+				"Forgot missing {installation} Java {major_version}"
+			))),
+			Err(e) => results.push(CheckResult::fail(
+				NAME,
+				format!("{e:#}"),
+				"Remove the Java installation manually",
+			)),
+		}
+	}
+
+	if fix {
+		if let Err(e) = persistent.dump(&data.paths.core).await {
+			results.push(CheckResult::fail(
+				NAME,
+				format!("Failed to write persistent data: {e:#}"),
+				"Make sure that the internal data directory is writable",
+			));
+		}
+	}
+}
+
+/// Print the results of the checks
+fn print_report(results: &[CheckResult], o: &TerminalOutput) {
+	for result in results {
+		match result.status {
+			CheckStatus::Pass if result.fixed => o.println(cformat!(
+				"<g>[FIXED]</> <s>{}:</> {}",
+				result.name,
+				result.message
+			)),
+			CheckStatus::Pass => o.println(cformat!(
+				"<g>[PASS]</> <s>{}:</> {}",
+				result.name,
+				result.message
+			)),
+			CheckStatus::Warn => o.println(cformat!(
+				"<y>[WARN]</> <s>{}:</> {}",
+				result.name,
+				result.message
+			)),
+			CheckStatus::Fail => o.println(cformat!(
+				"<r>[FAIL]</> <s>{}:</> {}",
+				result.name,
+				result.message
+			)),
+		}
+		if let Some(remedy) = &result.remedy {
+			o.println(cformat!("       <k!>Suggestion:</> {}", remedy));
+		}
+	}
+
+	let count = |status| results.iter().filter(|x| x.status == status).count();
+	o.println(cformat!(
+		"<s>{} passed, {} warnings, {} failed",
+		count(CheckStatus::Pass),
+		count(CheckStatus::Warn),
+		count(CheckStatus::Fail)
+	));
+}
@@ -1,10 +1,23 @@
 use super::CmdData;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use clap::Subcommand;
-use color_print::cprintln;
+use color_print::{cformat, cprintln};
+use itertools::Itertools;
+use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
+use mcvm::config::{Config, ConfigDeser};
+use mcvm::core::io::persistent::PersistentData;
+use mcvm::io::lock::Lockfile;
+use mcvm::io::paths::Paths;
+use mcvm::io::pid::{is_process_running, read_pidfile};
+use mcvm::shared::id::InstanceID;
+use mcvm::shared::output::MCVMOutput;
+use mcvm::util::{format_size, get_available_space, get_size, move_dir};
+
+use crate::output::{TerminalOutput, HYPHEN_POINT};
 
 use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Subcommand)]
 pub enum FilesSubcommand {
@@ -19,23 +32,382 @@ files in your instances or any other user data."
 		#[arg(short, long)]
 		data: bool,
 	},
+	#[command(
+		about = "Remove certain kinds of cached files",
+		long_about = "Remove certain kinds of cached files so that they are downloaded again
+the next time they are needed. This never touches instances or worlds."
+	)]
+	Clean {
+		/// Whether to remove cached packages and repository indexes
+		#[arg(long)]
+		packages: bool,
+		/// Whether to remove game assets
+		#[arg(long)]
+		assets: bool,
+		/// Whether to remove game libraries
+		#[arg(long)]
+		libraries: bool,
+		/// Whether to remove all Java installations managed by mcvm
+		#[arg(long)]
+		java: bool,
+		/// Whether to remove all of the above
+		#[arg(short, long)]
+		all: bool,
+	},
+	#[command(
+		about = "Remove unused Java installations",
+		long_about = "Remove Java installations managed by mcvm that are no longer used by any instance"
+	)]
+	CleanJava,
+	#[command(
+		about = "Print the directories where mcvm stores its files",
+		long_about = "Print the directories where mcvm stores its files. This will not create them if they don't exist"
+	)]
+	Where {
+		/// Whether to print the paths as a JSON object
+		#[arg(short, long)]
+		json: bool,
+	},
+	#[command(
+		about = "Move all instances to a new directory",
+		long_about = "Move the directories of all instances to a new directory, such as one on another drive,
+and store new instances there from now on. Caches, libraries, and other data stay where they are.
+Instances with a custom game directory only have their instance directory moved."
+	)]
+	RelocateInstances {
+		/// The directory to move the instances to
+		new_dir: PathBuf,
+	},
 }
 
 pub async fn run(subcommand: FilesSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
 	match subcommand {
 		FilesSubcommand::Remove { data: remove_data } => remove(data, remove_data).await,
+		FilesSubcommand::Clean {
+			packages,
+			assets,
+			libraries,
+			java,
+			all,
+		} => {
+			clean(
+				data,
+				packages || all,
+				assets || all,
+				libraries || all,
+				java || all,
+			)
+			.await
+		}
+		FilesSubcommand::CleanJava => clean_java(data).await,
+		FilesSubcommand::Where { json } => print_paths(json),
+		FilesSubcommand::RelocateInstances { new_dir } => relocate_instances(data, new_dir).await,
 	}
 }
 
 pub async fn remove(data: &mut CmdData, remove_data: bool) -> anyhow::Result<()> {
-	cprintln!("<g>Removing cached files...");
+	data.output.println(cformat!("<g>Removing cached files..."));
 	fs::remove_dir_all(data.paths.project.cache_dir())
 		.context("Failed to remove cache directory")?;
 	if remove_data {
-		cprintln!("<g>Removing internal files...");
+		data.output
+			.println(cformat!("<g>Removing internal files..."));
 		fs::remove_dir_all(&data.paths.internal)
 			.context("Failed to remove internal data directory")?;
 	}
 
 	Ok(())
 }
+
+pub async fn clean(
+	data: &mut CmdData,
+	packages: bool,
+	assets: bool,
+	libraries: bool,
+	java: bool,
+) -> anyhow::Result<()> {
+	if !(packages || assets || libraries || java) {
+		bail!("Specify what to clean, or use --all to clean everything");
+	}
+
+	let mut total = 0;
+	if packages {
+		total += clean_dir("packages", &data.paths.pkg_cache, &data.output)?;
+		fs::create_dir_all(&data.paths.pkg_index_cache)
+			.context("Failed to recreate package index cache")?;
+	}
+	if assets {
+		total += clean_dir("assets", &data.paths.core.assets, &data.output)?;
+	}
+	if libraries {
+		total += clean_dir("libraries", &data.paths.core.libraries, &data.output)?;
+	}
+	if java {
+		// Forget the installations first so that they get installed again when they are needed
+		let mut persistent =
+			PersistentData::open(&data.paths.core).context("Failed to open persistent data")?;
+		for (installation, major_version) in persistent.get_unused_java_installations(&[]) {
+			persistent
+				.remove_java_installation(installation, &major_version)
+				.context("Failed to remove Java installation")?;
+		}
+		persistent
+			.dump(&data.paths.core)
+			.await
+			.context("Failed to write persistent data")?;
+		total += clean_dir("Java installations", &data.paths.core.java, &data.output)?;
+	}
+
+	data.output
+		.println(cformat!("<g>Freed <b>{}</> in total", format_size(total)));
+
+	Ok(())
+}
+
+/// Remove the contents of a cache directory and print how much space was freed.
+/// Returns the number of bytes that were freed
+fn clean_dir(name: &str, dir: &Path, o: &TerminalOutput) -> anyhow::Result<u64> {
+	let size = get_size(dir);
+	if dir.exists() {
+		fs::remove_dir_all(dir).with_context(|| format!("Failed to remove {name}"))?;
+	}
+	fs::create_dir_all(dir).with_context(|| format!("Failed to recreate {name} directory"))?;
+	o.println(cformat!(
+		"{}<s>Removed {}</>, freeing {}",
+		HYPHEN_POINT,
+		name,
+		format_size(size)
+	));
+
+	Ok(size)
+}
+
+pub async fn clean_java(data: &mut CmdData) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get();
+
+	let mut persistent =
+		PersistentData::open(&data.paths.core).context("Failed to open persistent data")?;
+
+	// Without a record of which Java every instance uses, installations that are still needed would be removed
+	if config.instances.is_empty() {
+		bail!("No instances are configured, so unused Java installations can't be determined");
+	}
+	let unknown = config
+		.instances
+		.keys()
+		.filter(|id| persistent.get_instance_java(id).is_none())
+		.map(|id| id.to_string())
+		.sorted()
+		.collect_vec();
+	if !unknown.is_empty() {
+		bail!(
+			"Not all instances have recorded which Java installation they use, so unused installations can't be determined. Launch these instances first: {}",
+			unknown.join(", ")
+		);
+	}
+
+	let active = persistent.get_active_java_installations(config.instances.keys().map(|x| &**x));
+	let unused = persistent.get_unused_java_installations(&active);
+	if unused.is_empty() {
+		data.output
+			.println(cformat!("<g>No unused Java installations found"));
+		return Ok(());
+	}
+
+	for (installation, major_version) in unused {
+		data.output.println(cformat!(
+			"{}<s>Removing {} Java {}",
+			HYPHEN_POINT,
+			installation,
+			major_version
+		));
+		persistent
+			.remove_java_installation(installation, &major_version)
+			.context("Failed to remove Java installation")?;
+	}
+	persistent
+		.dump(&data.paths.core)
+		.await
+		.context("Failed to write persistent data")?;
+
+	data.output
+		.println(cformat!("<g>Unused Java installations removed"));
+
+	Ok(())
+}
+
+pub async fn relocate_instances(data: &mut CmdData, new_dir: PathBuf) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get();
+
+	let new_dir = if new_dir.is_absolute() {
+		new_dir
+	} else {
+		std::env::current_dir()
+			.context("Failed to get current directory")?
+			.join(new_dir)
+	};
+	let old_dir = data.paths.instances.clone();
+	if new_dir == old_dir {
+		bail!("Instances are already stored in {}", new_dir.display());
+	}
+	if new_dir.starts_with(&old_dir) {
+		bail!("The new directory can't be inside of the current instances directory");
+	}
+
+	let mut to_move = Vec::new();
+	for id in config.instances.keys().sorted() {
+		if let Some(pid) = read_pidfile(&data.paths, id)? {
+			if is_process_running(pid)? {
+				bail!("Instance '{id}' is currently running. Stop it before moving instances");
+			}
+		}
+		let src = old_dir.join(id.to_string());
+		if !src.exists() {
+			continue;
+		}
+		let dest = new_dir.join(id.to_string());
+		if dest.exists() {
+			bail!("The directory {} already exists", dest.display());
+		}
+		to_move.push((id, src, dest));
+	}
+
+	// Make sure that the instances can be moved before touching any of them
+	fs::create_dir_all(&new_dir).context("Failed to create the new instances directory")?;
+	let test_path = new_dir.join(".mcvm_write_test");
+	fs::write(&test_path, [])
+		.with_context(|| format!("The directory {} is not writable", new_dir.display()))?;
+	fs::remove_file(&test_path).context("Failed to remove write test file")?;
+	let required: u64 = to_move.iter().map(|x| get_size(&x.1)).sum();
+	if let Some(available) = get_available_space(&new_dir) {
+		if available < required {
+			bail!(
+				"Not enough free space to move the instances. {} is needed, but only {} is available",
+				format_size(required),
+				format_size(available)
+			);
+		}
+	}
+
+	for (i, (id, src, dest)) in to_move.iter().enumerate() {
+		data.output
+			.println(cformat!("{}<s>Moving instance <b>{}</>", HYPHEN_POINT, id));
+		if let Err(e) = move_dir(src, dest) {
+			// Put back the instances that were already moved so that nothing is left half done.
+			// A partial copy is already cleaned up, so if this instance's new directory still
+			// exists then it was copied fully and needs to be moved back as well
+			let moved = if dest.exists() {
+				&to_move[..=i]
+			} else {
+				&to_move[..i]
+			};
+			move_instances_back(moved)?;
+			return Err(e).with_context(|| format!("Failed to move instance '{id}'"));
+		}
+	}
+
+	// The config is written first so that it always points to where the instances are.
+	// If anything else fails, everything is rolled back
+	let mut config = data.get_raw_config()?;
+	let previous_dir = config.get_instances_dir();
+	let result = set_instances_dir(&mut config, Some(new_dir.clone()), &data.paths);
+	if let Err(e) = result {
+		move_instances_back(&to_move)?;
+		return Err(e);
+	}
+	if let Err(e) = relocate_instance_references(&data.paths, &old_dir, &new_dir, &mut data.output)
+	{
+		relocate_instance_references(&data.paths, &new_dir, &old_dir, &mut data.output)
+			.context("Failed to restore instance paths after an error")?;
+		set_instances_dir(&mut config, previous_dir, &data.paths)?;
+		move_instances_back(&to_move)?;
+		return Err(e);
+	}
+	data.paths.set_instances_dir(new_dir.clone());
+
+	data.output.println(cformat!(
+		"<g>Moved {} instances to <b>{}</>",
+		to_move.len(),
+		new_dir.display()
+	));
+
+	Ok(())
+}
+
+/// Moves instances from their new directories back to where they were before relocating them
+fn move_instances_back(moved: &[(&InstanceID, PathBuf, PathBuf)]) -> anyhow::Result<()> {
+	for (_, src, dest) in moved {
+		move_dir(dest, src).context("Failed to move instance back after an error")?;
+	}
+
+	Ok(())
+}
+
+/// Sets the instances directory in the config and writes it
+fn set_instances_dir(
+	config: &mut ConfigDeser,
+	dir: Option<PathBuf>,
+	paths: &Paths,
+) -> anyhow::Result<()> {
+	apply_modifications_and_write(
+		config,
+		vec![ConfigModification::SetInstancesDir(dir)],
+		paths,
+	)
+	.context("Failed to set the instances directory in the config")
+}
+
+/// Updates the paths to instance files that are stored in the lockfile
+/// after the instances directory has moved
+fn relocate_instance_references(
+	paths: &Paths,
+	old_dir: &Path,
+	new_dir: &Path,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<()> {
+	let mut lock = Lockfile::open(paths, o).context("Failed to open lockfile")?;
+	lock.relocate_instances(old_dir, new_dir);
+	lock.finish(paths).context("Failed to write lockfile")?;
+
+	Ok(())
+}
+
+/// Prints the paths that mcvm uses. This is called before the command data is set up
+/// so that the directories aren't created just by printing them
+pub fn print_paths(json: bool) -> anyhow::Result<()> {
+	let mut paths = Paths::new_no_create().context("Failed to get system paths")?;
+	Config::apply_instances_dir(&mut paths);
+	let entries = [
+		("data", paths.data.clone()),
+		("internal", paths.internal.clone()),
+		("instances", paths.instances.clone()),
+		("assets", paths.core.assets.clone()),
+		("java", paths.core.java.clone()),
+		("cache", paths.project.cache_dir().to_owned()),
+		("config", paths.project.config_dir().to_owned()),
+		("config_file", Config::get_path(&paths)),
+		("lockfile", Lockfile::get_path(&paths)),
+		("addons", paths.addons.clone()),
+		("pkg_cache", paths.pkg_cache.clone()),
+		("logs", paths.logs.clone()),
+		("plugins", paths.plugins.clone()),
+		("run", paths.run.clone()),
+	];
+
+	if json {
+		let object: serde_json::Map<_, _> = entries
+			.into_iter()
+			.map(|(label, path)| (label.to_string(), path.to_string_lossy().into()))
+			.collect();
+		let out = serde_json::to_string_pretty(&object).context("Failed to serialize paths")?;
+		println!("{out}");
+	} else {
+		for (label, path) in entries {
+			cprintln!("<s>{}:</> {}", label, path.to_string_lossy());
+		}
+	}
+
+	Ok(())
+}
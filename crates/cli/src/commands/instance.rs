@@ -1,22 +1,36 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use clap::Subcommand;
-use color_print::{cprint, cprintln};
+use color_print::cformat;
 use inquire::Select;
 use itertools::Itertools;
+use mcvm::config::instance::validate_instance_id;
+use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
 use mcvm::config::Config;
+use mcvm::core::io::persistent::PersistentData;
+use mcvm::core::user::User;
+use mcvm::instance::create::InstanceDirs;
+use mcvm::instance::pack_files::PackSource;
+use mcvm::instance::transfer::{load_formats, ExportSettings};
 use mcvm::instance::update::InstanceUpdateContext;
 use mcvm::io::lock::Lockfile;
+use mcvm::io::paths::Paths;
+use mcvm::io::pid::{is_process_running, read_pidfile, remove_pidfile, terminate_process};
+use mcvm::net::rcon::{read_rcon_settings, RconClient};
 use mcvm::shared::id::InstanceID;
+use mcvm::util::{format_size, get_size};
 
-use mcvm::instance::launch::LaunchSettings;
+use mcvm::instance::launch::{finish_detached, is_crash, InstanceHandle, LaunchSettings};
+use mcvm::shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm::shared::Side;
-use reqwest::Client;
 
 use super::CmdData;
-use crate::output::{icons_enabled, HYPHEN_POINT, INSTANCE, LOADER, PACKAGE, VERSION};
-use crate::secrets::get_ms_client_id;
+use crate::output::{
+	icons_enabled, print_json, TerminalOutput, HYPHEN_POINT, INSTANCE, LOADER, PACKAGE, VERSION,
+};
 
 #[derive(Debug, Subcommand)]
 pub enum InstanceSubcommand {
@@ -39,11 +53,38 @@ pub enum InstanceSubcommand {
 		/// if you have authenticated at least once
 		#[arg(short, long)]
 		offline: bool,
-		/// The instance to launch, as an instance reference (profile:instance)
-		instance: Option<String>,
+		/// Launch in offline mode as a temporary user with this username instead of the
+		/// chosen user's name. The chosen user must be a Microsoft user that has authenticated at least once
+		#[arg(long, value_name = "USERNAME")]
+		offline_name: Option<String>,
+		/// Whether to detach server instances so that they keep running in the background
+		/// after mcvm exits. Use `instance stop` to stop them
+		#[arg(short, long)]
+		detach: bool,
+		/// The instances to launch, as instance references (profile:instance).
+		/// Multiple instances will be launched together
+		instances: Vec<String>,
+	},
+	#[command(about = "Stop a detached server instance")]
+	Stop {
+		/// The instance to stop
+		instance: String,
+	},
+	#[command(about = "Run a command on a running server instance using RCON")]
+	Exec {
+		/// The instance to run the command on
+		instance: String,
+		/// The command to run, without a leading slash
+		command: String,
+	},
+	#[command(
+		about = "Print useful information about an instance",
+		long_about = "Print the version, loader, Java, memory settings, packages, and game directory of an instance"
+	)]
+	Info {
+		/// The instance to print information about
+		instance: String,
 	},
-	#[command(about = "Print useful information about an instance")]
-	Info { instance: String },
 	Update {
 		/// Whether to force update files that have already been downloaded
 		#[arg(short, long)]
@@ -54,17 +95,230 @@ pub enum InstanceSubcommand {
 		/// Whether to skip updating packages
 		#[arg(short = 'P', long)]
 		skip_packages: bool,
+		/// Whether to snapshot instances before changing their packages, so that a failed
+		/// update can be rolled back
+		#[arg(short, long)]
+		backup: bool,
+		/// Only show what the update would download, install, and remove without changing anything
+		#[arg(long)]
+		dry_run: bool,
+		/// Install exactly the package files recorded in the lockfile instead of resolving packages,
+		/// failing if any of them can't be downloaded or don't match their hashes
+		#[arg(long, conflicts_with_all = ["dry_run", "skip_packages"])]
+		frozen: bool,
+		/// Whether to install packages with elevated permissions without asking
+		#[arg(short, long)]
+		yes: bool,
 		/// Additional instance groups to update
 		#[arg(short, long)]
 		groups: Vec<String>,
 		/// The instances to update
 		instances: Vec<String>,
 	},
+	#[command(about = "Delete an instance and all of its files")]
+	#[clap(alias = "rm")]
+	Delete {
+		/// The instance to delete
+		instance: String,
+		/// Whether to skip the confirmation prompt
+		#[arg(short, long)]
+		yes: bool,
+	},
+	#[command(about = "Create a new instance with the same config as an existing one")]
+	Duplicate {
+		/// The instance to copy
+		source: String,
+		/// The ID of the new instance
+		new_id: String,
+		/// Whether to also copy the files in the game directory, such as worlds and options.
+		/// Packages will be installed fresh on the new instance when it is updated
+		#[arg(short, long)]
+		copy_files: bool,
+	},
+	#[command(about = "Change the ID of an instance, keeping all of its files")]
+	Rename {
+		/// The instance to rename
+		instance: String,
+		/// The new ID of the instance
+		new_id: String,
+	},
+	#[command(about = "Export an instance to a file that can be imported into other launchers")]
+	Export {
+		/// The instance to export
+		instance: String,
+		/// The path to write the exported instance to
+		output: String,
+		/// The transfer format to export with. Formats are provided by plugins
+		#[arg(short, long, default_value = "mmc")]
+		format: String,
+		/// Whether to leave out world saves, which can be very large
+		#[arg(long)]
+		exclude_worlds: bool,
+	},
 	#[command(about = "Print the directory of an instance")]
 	Dir {
 		/// The instance to print the directory of
 		instance: Option<String>,
 	},
+	#[command(about = "Manage datapacks installed on an instance")]
+	#[clap(alias = "dp")]
+	Datapack {
+		/// The datapack subcommand
+		#[command(subcommand)]
+		command: DatapackSubcommand,
+	},
+	#[command(
+		name = "resourcepack",
+		about = "Manage resource packs installed on a client instance"
+	)]
+	#[clap(alias = "rp")]
+	ResourcePack {
+		/// The resource pack subcommand
+		#[command(subcommand)]
+		command: ResourcePackSubcommand,
+	},
+	#[command(
+		name = "shaderpack",
+		about = "Manage shader packs installed on a client instance"
+	)]
+	#[clap(alias = "sp")]
+	ShaderPack {
+		/// The shader pack subcommand
+		#[command(subcommand)]
+		command: ShaderPackSubcommand,
+	},
+	#[command(about = "Manage snapshots of an instance")]
+	Snapshot {
+		/// The snapshot subcommand
+		#[command(subcommand)]
+		command: SnapshotSubcommand,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DatapackSubcommand {
+	#[command(about = "Install a datapack from Modrinth or a local zip file")]
+	Add {
+		/// The instance to install the datapack on
+		instance: String,
+		/// The Modrinth project ID or slug, or the path to a datapack zip
+		datapack: String,
+		/// The worlds to install the datapack in. Defaults to all worlds
+		#[arg(short, long)]
+		world: Vec<String>,
+	},
+	#[command(about = "List the datapacks installed on an instance")]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to remove formatting from the output
+		#[arg(short, long)]
+		raw: bool,
+		/// The instance to list the datapacks of
+		instance: String,
+	},
+	#[command(about = "Remove a datapack from an instance")]
+	#[clap(alias = "rm")]
+	Remove {
+		/// The instance to remove the datapack from
+		instance: String,
+		/// The ID of the datapack to remove
+		datapack: String,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ResourcePackSubcommand {
+	#[command(about = "Install a resource pack from Modrinth or a local zip file")]
+	Add {
+		/// The instance to install the resource pack on
+		instance: String,
+		/// The Modrinth project ID or slug, or the path to a resource pack zip
+		resource_pack: String,
+		/// Whether to also enable the resource pack in the game options
+		#[arg(short, long)]
+		enable: bool,
+	},
+	#[command(about = "List the resource packs installed on an instance")]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to remove formatting from the output
+		#[arg(short, long)]
+		raw: bool,
+		/// The instance to list the resource packs of
+		instance: String,
+	},
+	#[command(about = "Remove a resource pack from an instance")]
+	#[clap(alias = "rm")]
+	Remove {
+		/// The instance to remove the resource pack from
+		instance: String,
+		/// The ID of the resource pack to remove
+		resource_pack: String,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ShaderPackSubcommand {
+	#[command(about = "Install a shader pack from Modrinth or a local zip file")]
+	Add {
+		/// The instance to install the shader pack on
+		instance: String,
+		/// The Modrinth project ID or slug, or the path to a shader pack zip
+		shader_pack: String,
+	},
+	#[command(about = "List the shader packs installed on an instance")]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to remove formatting from the output
+		#[arg(short, long)]
+		raw: bool,
+		/// The instance to list the shader packs of
+		instance: String,
+	},
+	#[command(about = "Remove a shader pack from an instance")]
+	#[clap(alias = "rm")]
+	Remove {
+		/// The instance to remove the shader pack from
+		instance: String,
+		/// The ID of the shader pack to remove
+		shader_pack: String,
+	},
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotSubcommand {
+	#[command(about = "Create a new snapshot of an instance")]
+	Create {
+		/// The instance to snapshot
+		instance: String,
+		/// The ID of the snapshot. Defaults to the current time
+		#[arg(short, long)]
+		name: Option<String>,
+	},
+	#[command(about = "List the snapshots of an instance")]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to remove formatting from the output
+		#[arg(short, long)]
+		raw: bool,
+		/// The instance to list the snapshots of
+		instance: String,
+	},
+	#[command(about = "Restore a snapshot of an instance")]
+	Restore {
+		/// The instance to restore the snapshot on
+		instance: String,
+		/// The ID of the snapshot to restore
+		snapshot: String,
+	},
+	#[command(about = "Remove a snapshot of an instance")]
+	#[clap(alias = "rm")]
+	Remove {
+		/// The instance to remove the snapshot from
+		instance: String,
+		/// The ID of the snapshot to remove
+		snapshot: String,
+	},
 }
 
 pub async fn run(command: InstanceSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
@@ -73,37 +327,99 @@ pub async fn run(command: InstanceSubcommand, data: &mut CmdData) -> anyhow::Res
 		InstanceSubcommand::Launch {
 			user,
 			offline,
-			instance,
-		} => launch(instance, user, offline, data).await,
+			offline_name,
+			detach,
+			instances,
+		} => {
+			launch(
+				instances,
+				user,
+				offline || offline_name.is_some(),
+				offline_name,
+				detach,
+				data,
+			)
+			.await
+		}
+		InstanceSubcommand::Stop { instance } => stop(data, instance).await,
+		InstanceSubcommand::Exec { instance, command } => exec(data, instance, command).await,
 		InstanceSubcommand::Info { instance } => info(data, &instance).await,
 		InstanceSubcommand::Update {
 			force,
 			all,
 			skip_packages,
+			backup,
+			dry_run,
+			frozen,
+			yes,
 			groups,
 			instances,
-		} => update(data, instances, groups, all, force, skip_packages).await,
+		} => {
+			update(
+				data,
+				instances,
+				groups,
+				all,
+				force,
+				skip_packages,
+				backup,
+				dry_run,
+				frozen,
+				yes,
+			)
+			.await
+		}
+		InstanceSubcommand::Delete { instance, yes } => delete(data, instance, yes).await,
+		InstanceSubcommand::Duplicate {
+			source,
+			new_id,
+			copy_files,
+		} => duplicate(data, source, new_id, copy_files).await,
+		InstanceSubcommand::Rename { instance, new_id } => rename(data, instance, new_id).await,
+		InstanceSubcommand::Export {
+			instance,
+			output,
+			format,
+			exclude_worlds,
+		} => export(data, instance, output, format, exclude_worlds).await,
 		InstanceSubcommand::Dir { instance } => dir(data, instance).await,
+		InstanceSubcommand::Datapack { command } => datapack(command, data).await,
+		InstanceSubcommand::ResourcePack { command } => resource_pack(command, data).await,
+		InstanceSubcommand::ShaderPack { command } => shader_pack(command, data).await,
+		InstanceSubcommand::Snapshot { command } => snapshot(command, data).await,
 	}
 }
 
 async fn list(data: &mut CmdData, raw: bool, side: Option<Side>) -> anyhow::Result<()> {
-	data.ensure_config(!raw).await?;
+	let json = data.output.is_json();
+	data.ensure_config(!raw && !json).await?;
 	let config = data.config.get_mut();
 
-	for (id, instance) in config.instances.iter().sorted_by_key(|x| x.0) {
-		if let Some(side) = side {
-			if instance.get_side() != side {
-				continue;
-			}
-		}
+	let instances = config
+		.instances
+		.iter()
+		.sorted_by_key(|x| x.0)
+		.filter(|x| side.is_none() || side == Some(x.1.get_side()));
+
+	if json {
+		let instances: Vec<_> = instances
+			.map(|(id, instance)| {
+				serde_json::json!({
+					"id": id,
+					"side": instance.get_side(),
+				})
+			})
+			.collect();
+		return print_json(&instances);
+	}
 
+	for (id, instance) in instances {
 		if raw {
 			println!("{id}");
 		} else {
 			match instance.get_side() {
-				Side::Client => cprintln!("{}<y!>{}", HYPHEN_POINT, id),
-				Side::Server => cprintln!("{}<c!>{}", HYPHEN_POINT, id),
+				Side::Client => data.output.println(cformat!("{}<y!>{}", HYPHEN_POINT, id)),
+				Side::Server => data.output.println(cformat!("{}<c!>{}", HYPHEN_POINT, id)),
 			}
 		}
 	}
@@ -112,236 +428,1462 @@ async fn list(data: &mut CmdData, raw: bool, side: Option<Side>) -> anyhow::Resu
 }
 
 async fn info(data: &mut CmdData, id: &str) -> anyhow::Result<()> {
-	data.ensure_config(true).await?;
-	let config = data.config.get_mut();
+	let json = data.output.is_json();
+	data.ensure_config(!json).await?;
+	let config = data.config.get();
 
-	fn print_indent() {
-		print!("   ");
+	fn print_indent(o: &TerminalOutput) {
+		o.print("   ");
 	}
 
 	let instance = config
 		.instances
 		.get(id)
 		.with_context(|| format!("Unknown instance '{id}'"))?;
+	let inst_config = instance.get_config();
+	let side = instance.get_side();
+
+	let lock = Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let installed_version = lock.get_instance_version(id);
+	let paper_build = lock.get_instance_paper_build(id);
+	let packages: Vec<_> = instance
+		.get_configured_packages()
+		.iter()
+		.map(|pkg| (pkg.id.clone(), lock.get_package_version(id, &pkg.id)))
+		.collect();
+
+	let dirs = InstanceDirs::new(
+		&data.paths,
+		id,
+		&side,
+		inst_config.game_dir_override.as_deref(),
+	);
+	let game_dir_size = dirs.game_dir.exists().then(|| get_size(&dirs.game_dir));
+
+	let persistent =
+		PersistentData::open(&data.paths.core).context("Failed to open persistent data")?;
+	let java = persistent
+		.get_instance_java(id)
+		.map(|(installation, major_version)| {
+			let path = persistent.get_java_path(installation, major_version);
+			(installation, major_version, path)
+		});
+
+	if json {
+		let packages: Vec<_> = packages
+			.iter()
+			.map(|(id, version)| serde_json::json!({ "id": id, "version": version }))
+			.collect();
+		let out = serde_json::json!({
+			"id": id,
+			"side": side.to_string(),
+			"version": inst_config.version.to_string(),
+			"installed_version": installed_version,
+			"loader": instance.get_loader_name(),
+			"paper_build": paper_build,
+			"java": {
+				"kind": inst_config.launch.java.to_string(),
+				"installation": java.as_ref().map(|x| x.0.to_string()),
+				"major_version": java.as_ref().map(|x| x.1),
+				"path": java.as_ref().and_then(|x| x.2.as_ref()),
+			},
+			"memory": {
+				"min": inst_config.launch.min_mem.as_ref().map(|x| x.to_string()),
+				"max": inst_config.launch.max_mem.as_ref().map(|x| x.to_string()),
+			},
+			"packages": packages,
+			"game_dir": dirs.game_dir,
+			"game_dir_size": game_dir_size,
+		});
+		return print_json(&out);
+	}
 
 	if icons_enabled() {
 		print!("{} ", INSTANCE);
 	}
-	cprintln!("<s><g>Instance <b>{}", id);
-	print_indent();
+	data.output.println(cformat!("<s><g>Instance <b>{}", id));
+	print_indent(&data.output);
 	if icons_enabled() {
 		print!("{} ", VERSION);
 	}
-	cprintln!("<s>Version:</s> <g>{}", instance.get_config().version);
+	data.output
+		.print(cformat!("<s>Version:</s> <g>{}", inst_config.version));
+	match installed_version {
+		Some(version) if version != inst_config.version.to_string() => {
+			data.output.print(cformat!(" <k!>(installed {})", version))
+		}
+		Some(..) => {}
+		None => data.output.print(cformat!(" <k!>(not installed yet)")),
+	}
+	data.output.println("");
 
-	print_indent();
-	cprint!("{}Type: ", HYPHEN_POINT);
-	match instance.get_side() {
-		Side::Client => cprint!("<y!>Client"),
-		Side::Server => cprint!("<c!>Server"),
+	print_indent(&data.output);
+	data.output.print(cformat!("{}Type: ", HYPHEN_POINT));
+	match side {
+		Side::Client => data.output.print(cformat!("<y!>Client")),
+		Side::Server => data.output.print(cformat!("<c!>Server")),
 	}
-	cprintln!();
+	data.output.println("");
 
-	if instance.get_config().modifications.common_modloader() {
-		print_indent();
+	if inst_config.modifications.common_modloader() {
+		print_indent(&data.output);
 		if icons_enabled() {
 			print!("{} ", LOADER);
 		}
-		cprintln!(
+		data.output.println(cformat!(
 			"<s>Modloader:</s> <g>{}",
-			instance
-				.get_config()
-				.modifications
-				.get_modloader(Side::Client)
-		);
+			inst_config.modifications.get_modloader(Side::Client)
+		));
 	} else {
-		print_indent();
+		print_indent(&data.output);
 		if icons_enabled() {
 			print!("{} ", LOADER);
 		}
-		cprintln!(
+		data.output.println(cformat!(
 			"<s>Client:</s> <g>{}",
-			instance.get_config().modifications.client_type
-		);
-		print_indent();
+			inst_config.modifications.client_type
+		));
+		print_indent(&data.output);
 		if icons_enabled() {
 			print!("{} ", LOADER);
 		}
-		cprintln!(
+		data.output.println(cformat!(
 			"<s>Server:</s> <g>{}",
-			instance.get_config().modifications.server_type
-		);
+			inst_config.modifications.server_type
+		));
+	}
+	if let Some(paper_build) = paper_build {
+		print_indent(&data.output);
+		data.output
+			.println(cformat!("{}Paper build: <g>{}", HYPHEN_POINT, paper_build));
+	}
+
+	print_indent(&data.output);
+	data.output.print(cformat!(
+		"{}Java: <g>{}",
+		HYPHEN_POINT,
+		inst_config.launch.java
+	));
+	if let Some((installation, major_version, path)) = &java {
+		data.output
+			.print(cformat!(" <k!>({} Java {}", installation, major_version));
+		if let Some(path) = path {
+			data.output.print(cformat!(" <k!>at {}", path.display()));
+		}
+		data.output.print(cformat!("<k!>)"));
+	}
+	data.output.println("");
+
+	if inst_config.launch.min_mem.is_some() || inst_config.launch.max_mem.is_some() {
+		print_indent(&data.output);
+		data.output.print(cformat!("{}Memory:", HYPHEN_POINT));
+		if let Some(min_mem) = &inst_config.launch.min_mem {
+			data.output.print(cformat!(" <g>min {}", min_mem));
+		}
+		if let Some(max_mem) = &inst_config.launch.max_mem {
+			data.output.print(cformat!(" <g>max {}", max_mem));
+		}
+		data.output.println("");
+	}
+
+	print_indent(&data.output);
+	data.output.print(cformat!(
+		"{}Game directory: <b>{}",
+		HYPHEN_POINT,
+		dirs.game_dir.display()
+	));
+	if let Some(size) = game_dir_size {
+		data.output.print(cformat!(" <k!>({})", format_size(size)));
 	}
+	data.output.println("");
 
-	print_indent();
+	print_indent(&data.output);
 	if icons_enabled() {
 		print!("{} ", PACKAGE);
 	}
-	cprintln!("<s>Packages:");
-	for pkg in instance.get_configured_packages() {
-		print_indent();
-		cprint!("{}", HYPHEN_POINT);
-		cprint!("<b!>{}<g!>", pkg.id);
-		cprintln!();
+	data.output.println(cformat!("<s>Packages:"));
+	for (pkg, version) in packages {
+		print_indent(&data.output);
+		data.output.print(cformat!("{}", HYPHEN_POINT));
+		data.output.print(cformat!("<b!>{}<g!>", pkg));
+		if let Some(version) = version {
+			data.output.print(cformat!(" <k!>{}", version));
+		}
+		data.output.println("");
 	}
 
 	Ok(())
 }
 
 pub async fn launch(
-	instance: Option<String>,
+	instances: Vec<String>,
 	user: Option<String>,
 	offline: bool,
+	offline_user: Option<String>,
+	detach: bool,
 	data: &mut CmdData,
 ) -> anyhow::Result<()> {
 	data.ensure_config(true).await?;
 	let config = data.config.get_mut();
 
-	let instance_id = pick_instance(instance, config).context("Failed to pick instance")?;
+	let instance_ids = if instances.is_empty() {
+		vec![pick_instance(None, config).context("Failed to pick instance")?]
+	} else {
+		instances.into_iter().map(InstanceID::from).collect()
+	};
+
+	if let Some(user) = user {
+		config
+			.users
+			.choose_user(&user)
+			.context("Failed to choose user")?;
+	}
+
+	// Use a temporary unverified user that is not saved in the config. This is only allowed
+	// for people who own the game, as we don't want to be a cracked launcher
+	if let Some(name) = &offline_user {
+		let owner = config
+			.users
+			.get_chosen_user()
+			.context("A user must be chosen to launch with an offline username")?;
+		if !owner.is_microsoft() || !owner.is_auth_valid(&data.paths.core) {
+			bail!(
+				"User '{}' must be a Microsoft user that has authenticated at least once to launch with an offline username",
+				owner.get_id()
+			);
+		}
+		let user = User::new_unverified(name).context("Failed to create offline user")?;
+		let user_id = user.get_id().clone();
+		config.users.add_user(user);
+		config
+			.users
+			.choose_user(&user_id)
+			.context("Failed to choose offline user")?;
+	}
+
+	// Prefix the output of each instance when there are multiple so that they can be told apart
+	let is_multiple = instance_ids.len() > 1;
+
+	let client = config.prefs.make_client()?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let make_settings = |instance_id: &InstanceID, config: &Config| {
+		let output_prefix = if is_multiple {
+			Some(cformat!("<s>[{instance_id}]</> "))
+		} else {
+			None
+		};
+		LaunchSettings {
+			offline_auth: offline,
+			output_prefix,
+			detach,
+			client: client.clone(),
+			download_concurrency: config.prefs.download_concurrency,
+			mirrors: config.prefs.mirrors.clone(),
+		}
+	};
+
+	let mut handles = Vec::new();
+	for instance_id in instance_ids {
+		let launch_settings = make_settings(&instance_id, config);
+		let result = launch_instance(
+			&instance_id,
+			launch_settings,
+			config,
+			&mut lock,
+			&data.paths,
+			&mut data.output,
+		)
+		.await
+		.with_context(|| format!("Failed to launch instance '{instance_id}'"));
+
+		match result {
+			Ok(handle) => {
+				let restart_policy = config
+					.instances
+					.get(&instance_id)
+					.and_then(|x| x.get_restart_policy());
+				handles.push((instance_id, handle, restart_policy));
+			}
+			// Keep launching the other instances if one fails
+			Err(e) if is_multiple => data.output.display(
+				MessageContents::Error(format!("{e:?}")),
+				MessageLevel::Important,
+			),
+			Err(e) => return Err(e),
+		}
+	}
+
+	if handles.is_empty() {
+		bail!("No instances were launched");
+	}
+
+	// Offline players can't join servers that check their accounts
+	if let Some(name) = &offline_user {
+		for (instance_id, ..) in &handles {
+			let is_online_mode = config
+				.instances
+				.get(instance_id)
+				.and_then(|x| x.is_online_mode());
+			if is_online_mode == Some(true) {
+				data.output.display(
+					MessageContents::Warning(format!(
+						"Server instance '{instance_id}' is in online mode, so the offline user '{name}' will not be able to join it. Set the online-mode property to false to allow this"
+					)),
+					MessageLevel::Important,
+				);
+			}
+		}
+	}
+
+	// Detached instances keep running on their own, so we don't wait for them
+	if detach {
+		for (instance_id, handle, restart_policy) in handles {
+			if restart_policy.is_some() {
+				data.output.display(
+					MessageContents::Warning(format!(
+						"Instance '{instance_id}' will not be restarted if it crashes, as it is detached"
+					)),
+					MessageLevel::Important,
+				);
+			}
+			data.output.println(cformat!(
+				"<s>Instance <b>{}</> is running in the background with PID <b>{}",
+				instance_id,
+				handle.get_pid()
+			));
+		}
+		return Ok(());
+	}
+
+	// Wait for all of the instances to exit, restarting the servers that crash. Errors are collected
+	// so that every instance is still waited on and gets its stop hooks run
+	let mut errors = Vec::new();
+	let mut restarts = Vec::new();
+	while !handles.is_empty() || !restarts.is_empty() {
+		let mut i = 0;
+		while i < handles.len() {
+			let status = match handles[i].1.try_wait() {
+				Ok(Some(status)) => status,
+				Ok(None) => {
+					i += 1;
+					continue;
+				}
+				Err(e) => {
+					let (instance_id, handle, _) = handles.remove(i);
+					errors.push(e.context(format!("Failed to wait for instance '{instance_id}'")));
+					if let Err(e) = handle
+						.kill(&config.plugins, &data.paths, &mut data.output)
+						.await
+					{
+						errors.push(e.context(format!("Failed to stop instance '{instance_id}'")));
+					}
+					continue;
+				}
+			};
+			let (instance_id, handle, restart_policy) = handles.remove(i);
+			if let Err(e) = handle
+				.finish(&config.plugins, &data.paths, &mut data.output)
+				.await
+			{
+				errors.push(e.context(format!("Failed to finish instance '{instance_id}'")));
+			}
+			if is_multiple {
+				data.output.println(cformat!(
+					"<s>Instance <b>{}</> exited with {}",
+					instance_id,
+					status
+				));
+			}
+
+			let Some(mut restart_policy) = restart_policy.filter(|_| is_crash(&status)) else {
+				continue;
+			};
+			if let Some(delay) = restart_policy.on_crash(Instant::now()) {
+				data.output.display(
+					MessageContents::Warning(format!(
+						"Instance '{instance_id}' crashed with {status}. Restarting it in {} seconds (restart {} of {})",
+						delay.as_secs(),
+						restart_policy.get_restart_count(),
+						restart_policy.get_max_restarts(),
+					)),
+					MessageLevel::Important,
+				);
+				restarts.push((instance_id, Instant::now() + delay, restart_policy));
+			} else {
+				data.output.display(
+					MessageContents::Error(format!(
+						"Instance '{instance_id}' crashed too many times and will not be restarted"
+					)),
+					MessageLevel::Important,
+				);
+			}
+		}
+
+		// Restart the crashed instances that are done waiting
+		let now = Instant::now();
+		let (ready, waiting): (Vec<_>, Vec<_>) = restarts.into_iter().partition(|x| x.1 <= now);
+		restarts = waiting;
+		for (instance_id, _, restart_policy) in ready {
+			data.output.display(
+				MessageContents::Simple(format!("Restarting instance '{instance_id}'")),
+				MessageLevel::Important,
+			);
+			let launch_settings = make_settings(&instance_id, config);
+			let result = launch_instance(
+				&instance_id,
+				launch_settings,
+				config,
+				&mut lock,
+				&data.paths,
+				&mut data.output,
+			)
+			.await
+			.with_context(|| format!("Failed to restart instance '{instance_id}'"));
+			match result {
+				Ok(handle) => handles.push((instance_id, handle, Some(restart_policy))),
+				Err(e) => errors.push(e),
+			}
+		}
+
+		tokio::time::sleep(Duration::from_millis(100)).await;
+	}
 
+	match errors.len() {
+		0 => Ok(()),
+		1 => Err(errors.remove(0)),
+		_ => bail!(
+			"Multiple errors occurred while running instances:\n{}",
+			errors.iter().map(|e| format!("{e:?}")).join("\n")
+		),
+	}
+}
+
+/// Updates an instance if needed and launches it
+async fn launch_instance(
+	instance_id: &InstanceID,
+	launch_settings: LaunchSettings,
+	config: &mut Config,
+	lock: &mut Lockfile,
+	paths: &Paths,
+	o: &mut TerminalOutput,
+) -> anyhow::Result<InstanceHandle> {
 	let instance = config
 		.instances
-		.get_mut(&instance_id)
+		.get_mut(instance_id)
 		.context("Instance does not exist")?;
 
 	// Perform first update if needed
-	let mut lock = Lockfile::open(&data.paths).context("Failed to open lockfile")?;
-	if !lock.has_instance_done_first_update(&instance_id) {
-		cprintln!("<s>Performing first update of instance profile...");
+	if !lock.has_instance_done_first_update(instance_id) {
+		o.println(cformat!(
+			"<s>Performing first update of instance profile..."
+		));
 
-		let client = Client::new();
+		let client = config.prefs.make_client()?;
 		let mut ctx = InstanceUpdateContext {
 			packages: &mut config.packages,
 			users: &config.users,
 			plugins: &config.plugins,
 			prefs: &config.prefs,
-			paths: &data.paths,
-			lock: &mut lock,
+			paths,
+			lock,
 			client: &client,
-			output: &mut data.output,
+			output: o,
+			accept_permissions: false,
 		};
 
 		instance
-			.update(true, false, &mut ctx)
+			.update(true, false, false, &mut ctx)
 			.await
 			.context("Failed to perform first update for instance")?;
 
 		// Since the update was successful, we can mark the instance as ready
-		lock.update_instance_has_done_first_update(&instance_id);
-		lock.finish(&data.paths)
+		lock.update_instance_has_done_first_update(instance_id);
+		lock.finish(paths)
 			.context("Failed to finish using lockfile")?;
 	}
 
-	if let Some(user) = user {
-		config
-			.users
-			.choose_user(&user)
-			.context("Failed to choose user")?;
-	}
-
-	let launch_settings = LaunchSettings {
-		ms_client_id: get_ms_client_id(),
-		offline_auth: offline,
-	};
 	let instance_handle = instance
 		.launch(
-			&data.paths,
+			paths,
 			&mut config.users,
 			&config.plugins,
 			launch_settings,
-			&mut data.output,
+			o,
 		)
 		.await
 		.context("Instance failed to launch")?;
 
-	instance_handle
-		.wait(&config.plugins, &data.paths, &mut data.output)
-		.context("Failed to wait for instance child process")?;
+	Ok(instance_handle)
+}
+
+async fn stop(data: &mut CmdData, instance: String) -> anyhow::Result<()> {
+	let Some(pid) = read_pidfile(&data.paths, &instance)? else {
+		bail!("Instance '{instance}' is not running in the background");
+	};
+
+	if !is_process_running(pid)? {
+		remove_pidfile(&data.paths, &instance)?;
+		finish_stopped_instance(data, &instance).await?;
+		data.output.println(cformat!(
+			"<s>Instance <b>{}</> was not running. Removed stale pidfile",
+			instance
+		));
+		return Ok(());
+	}
+
+	terminate_process(pid).context("Failed to stop instance process")?;
+	data.output.display(
+		MessageContents::StartProcess(format!("Waiting for instance '{instance}' to shut down")),
+		MessageLevel::Important,
+	);
+
+	// Give the server time to save and shut down gracefully
+	let mut elapsed = Duration::ZERO;
+	while is_process_running(pid)? {
+		if elapsed >= STOP_TIMEOUT {
+			data.output.end_process();
+			bail!(
+				"Instance did not shut down after {} seconds",
+				STOP_TIMEOUT.as_secs()
+			);
+		}
+		tokio::time::sleep(STOP_POLL_INTERVAL).await;
+		elapsed += STOP_POLL_INTERVAL;
+	}
+
+	remove_pidfile(&data.paths, &instance)?;
+	finish_stopped_instance(data, &instance).await?;
+	data.output.display(
+		MessageContents::Success(format!("Instance '{instance}' stopped")),
+		MessageLevel::Important,
+	);
+	data.output.end_process();
 
 	Ok(())
 }
 
-async fn dir(data: &mut CmdData, instance: Option<String>) -> anyhow::Result<()> {
+/// Runs the stop hooks and post-launch command of a detached instance that has stopped
+async fn finish_stopped_instance(data: &mut CmdData, instance: &str) -> anyhow::Result<()> {
 	data.ensure_config(true).await?;
+	let config = data.config.get();
+	finish_detached(instance, &config.plugins, &data.paths, &mut data.output)
+		.await
+		.context("Failed to finish instance")
+}
 
-	let instance = pick_instance(instance, data.config.get()).context("Failed to pick instance")?;
+async fn exec(data: &mut CmdData, instance: String, command: String) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
 	let instance = data
 		.config
 		.get_mut()
 		.instances
-		.get_mut(&instance)
-		.context("Instance does not exist")?;
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+	if instance.get_side() != Side::Server {
+		bail!("Commands can only be run on server instances");
+	}
 	instance.ensure_dirs(&data.paths)?;
 
-	println!("{}", &instance.get_dirs().get().game_dir.to_string_lossy());
+	let settings = read_rcon_settings(&instance.get_dirs().get().game_dir)
+		.context("Failed to get RCON settings")?;
+	if !settings.enabled {
+		bail!("RCON is not enabled for this server. Set `enable-rcon` to true in its server.properties");
+	}
+
+	let mut client = RconClient::connect(settings.port, &settings.password)
+		.context("Failed to connect to server over RCON")?;
+	let response = client.command(&command).context("Failed to run command")?;
+	if !response.is_empty() {
+		println!("{response}");
+	}
 
 	Ok(())
 }
 
-async fn update(
-	data: &mut CmdData,
-	instances: Vec<String>,
-	groups: Vec<String>,
-	all: bool,
-	force: bool,
-	skip_packages: bool,
-) -> anyhow::Result<()> {
-	data.ensure_config(true).await?;
-	let config = data.config.get_mut();
+/// How long to wait for a detached instance to shut down
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+/// How often to check whether a detached instance has shut down
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
-	let mut ids: Vec<InstanceID> = if all {
-		config.instances.keys().cloned().collect()
-	} else {
-		instances.into_iter().map(InstanceID::from).collect()
-	};
+async fn delete(data: &mut CmdData, instance: String, yes: bool) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
 
-	for group in groups {
-		let group = Arc::from(group);
-		let group = config
-			.instance_groups
-			.get(&group)
-			.with_context(|| format!("Instance group '{group}' does not exist"))?;
-		ids.extend(group.clone());
+	if let Some(pid) = read_pidfile(&data.paths, &instance_id)? {
+		if is_process_running(pid)? {
+			bail!("Instance '{instance_id}' is currently running. Stop it before deleting it");
+		}
 	}
 
-	let client = Client::new();
-	let mut lock = Lockfile::open(&data.paths).context("Failed to open lockfile")?;
-	for id in ids {
-		let instance = config
-			.instances
-			.get_mut(&id)
-			.with_context(|| format!("Unknown instance '{id}'"))?;
+	let instance = data
+		.config
+		.get()
+		.instances
+		.get(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+	let inst_dir =
+		InstanceDirs::new(&data.paths, &instance_id, &instance.get_side(), None).inst_dir;
 
-		let mut ctx = InstanceUpdateContext {
-			packages: &mut config.packages,
-			users: &config.users,
-			plugins: &config.plugins,
-			prefs: &config.prefs,
-			paths: &data.paths,
-			lock: &mut lock,
-			client: &client,
-			output: &mut data.output,
-		};
+	if !yes {
+		data.output
+			.println(cformat!("<s>This will permanently delete:"));
+		data.output.println(cformat!(
+			"{}The instance <b>{}</> from the config",
+			HYPHEN_POINT,
+			instance_id
+		));
+		data.output.println(cformat!(
+			"{}The directory <b>{}</> and all of its contents",
+			HYPHEN_POINT,
+			inst_dir.to_string_lossy()
+		));
+		let confirmed = data.output.prompt_yes_no(
+			false,
+			MessageContents::Simple("Are you sure you want to continue?".into()),
+		)?;
+		if !confirmed {
+			data.output.println(cformat!("<s>Cancelled"));
+			return Ok(());
+		}
+	}
 
-		instance
-			.update(!skip_packages, force, &mut ctx)
-			.await
-			.context("Failed to update instance")?;
+	if inst_dir.exists() {
+		std::fs::remove_dir_all(&inst_dir).context("Failed to remove instance directory")?;
 	}
+	remove_pidfile(&data.paths, &instance_id)?;
+
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	lock.remove_instance(&instance_id);
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	let mut persistent =
+		PersistentData::open(&data.paths.core).context("Failed to open persistent data")?;
+	persistent.remove_instance_java(&instance_id);
+	persistent.remove_instance_elevated_approvals(&instance_id);
+	persistent
+		.dump(&data.paths.core)
+		.await
+		.context("Failed to write persistent data")?;
+
+	let mut config = data.get_raw_config()?;
+	apply_modifications_and_write(
+		&mut config,
+		vec![ConfigModification::RemoveInstance(instance_id.clone())],
+		&data.paths,
+	)
+	.context("Failed to remove instance from config")?;
+
+	data.output
+		.println(cformat!("<g>Instance <b>{}</> deleted.", instance_id));
 
 	Ok(())
 }
 
-/// Pick which instance to use
-pub fn pick_instance(instance: Option<String>, config: &Config) -> anyhow::Result<InstanceID> {
-	if let Some(instance) = instance {
-		Ok(instance.into())
-	} else {
-		let options = config.instances.keys().sorted().collect();
-		let selection = Select::new("Choose an instance", options)
-			.prompt()
+async fn duplicate(
+	data: &mut CmdData,
+	source: String,
+	new_id: String,
+	copy_files: bool,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let source = InstanceID::from(source);
+	let new_id = InstanceID::from(new_id);
+
+	let source_instance = data
+		.config
+		.get()
+		.instances
+		.get(&source)
+		.with_context(|| format!("Unknown instance '{source}'"))?;
+	let side = source_instance.get_side();
+	// The duplicate has the same config, so a custom game directory is shared between them
+	// and there is nothing to copy
+	let has_custom_game_dir = source_instance.get_config().game_dir_override.is_some();
+
+	let mut config = data.get_raw_config()?;
+	apply_modifications_and_write(
+		&mut config,
+		vec![ConfigModification::DuplicateInstance {
+			source: source.clone(),
+			new_id: new_id.clone(),
+		}],
+		&data.paths,
+	)
+	.context("Failed to add new instance to config")?;
+
+	if copy_files {
+		let src_dirs = InstanceDirs::new(&data.paths, &source, &side, None);
+		let dest_dirs = InstanceDirs::new(&data.paths, &new_id, &side, None);
+		if !has_custom_game_dir && src_dirs.game_dir.exists() {
+			// Addons are tracked per-instance in the lockfile, so leave them out and let
+			// the new instance install its own copies
+			let lock =
+				Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+			let addon_files = lock.get_instance_addon_files(&source).into_iter().collect();
+			src_dirs
+				.copy_game_dir(&dest_dirs, &addon_files)
+				.context("Failed to copy instance files")?;
+		}
+	}
+
+	data.output.println(cformat!(
+		"<g>Created instance <b>{}</> from <b>{}</>. Update it to install its packages.",
+		new_id,
+		source
+	));
+
+	Ok(())
+}
+
+async fn rename(data: &mut CmdData, instance: String, new_id: String) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let new_id = InstanceID::from(new_id);
+
+	if let Some(pid) = read_pidfile(&data.paths, &instance_id)? {
+		if is_process_running(pid)? {
+			bail!("Instance '{instance_id}' is currently running. Stop it before renaming it");
+		}
+	}
+
+	let config = data.config.get();
+	let side = config
+		.instances
+		.get(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?
+		.get_side();
+	validate_instance_id(&new_id).with_context(|| format!("Invalid instance ID '{new_id}'"))?;
+	if let Some(existing) = config
+		.instances
+		.keys()
+		.find(|x| x.eq_ignore_ascii_case(&new_id) && *x != &instance_id)
+	{
+		bail!("An instance with the ID '{existing}' already exists");
+	}
+
+	let old_dirs = InstanceDirs::new(&data.paths, &instance_id, &side, None);
+	let new_dirs = InstanceDirs::new(&data.paths, &new_id, &side, None);
+	if new_dirs.inst_dir.exists() {
+		bail!(
+			"The directory {} already exists",
+			new_dirs.inst_dir.to_string_lossy()
+		);
+	}
+	// Write the config first so that nothing is moved if that fails. If a later step fails,
+	// the files that were already moved and the old config are put back
+	let config_path = Config::get_path(&data.paths);
+	let old_config = std::fs::read(&config_path).context("Failed to read config file")?;
+	let mut config = data.get_raw_config()?;
+	apply_modifications_and_write(
+		&mut config,
+		vec![ConfigModification::RenameInstance {
+			instance: instance_id.clone(),
+			new_id: new_id.clone(),
+		}],
+		&data.paths,
+	)
+	.context("Failed to rename instance in config")?;
+
+	let mut moved = Vec::new();
+	let result = move_renamed_instance(
+		&old_dirs.inst_dir,
+		&new_dirs.inst_dir,
+		&data.paths.snapshots.join(instance_id.to_string()),
+		&data.paths.snapshots.join(new_id.to_string()),
+		&mut moved,
+	)
+	.and_then(|_| {
+		let mut lock =
+			Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+		lock.rename_instance(
+			&instance_id,
+			&new_id,
+			&old_dirs.inst_dir,
+			&new_dirs.inst_dir,
+		);
+		lock.finish(&data.paths).context("Failed to write lockfile")
+	});
+	if let Err(e) = result {
+		for (from, to) in moved.into_iter().rev() {
+			let _ = std::fs::rename(to, from);
+		}
+		std::fs::write(&config_path, old_config).with_context(|| {
+			format!("Failed to restore the config file after the rename failed: {e:?}")
+		})?;
+		return Err(e);
+	}
+
+	// The process isn't running, so any leftover pidfile is stale
+	remove_pidfile(&data.paths, &instance_id)?;
+
+	// The instance is already renamed, so failing to move its Java usage and package approvals
+	// isn't worth undoing everything for
+	let persistent =
+		PersistentData::open(&data.paths.core).context("Failed to open persistent data");
+	let result = match persistent {
+		Ok(mut persistent) => {
+			persistent.rename_instance_java(&instance_id, &new_id);
+			persistent.rename_instance_elevated_approvals(&instance_id, &new_id);
+			persistent
+				.dump(&data.paths.core)
+				.await
+				.context("Failed to write persistent data")
+		}
+		Err(e) => Err(e),
+	};
+	if let Err(e) = result {
+		data.output.display(
+			MessageContents::Warning(format!("Failed to update Java usage of instance: {e:?}")),
+			MessageLevel::Important,
+		);
+	}
+
+	data.output.println(cformat!(
+		"<g>Renamed instance <b>{}</> to <b>{}</>.",
+		instance_id,
+		new_id
+	));
+
+	Ok(())
+}
+
+/// Move the directory and snapshots of an instance that is being renamed. The moves that
+/// were made are recorded so that they can be undone
+fn move_renamed_instance(
+	old_dir: &Path,
+	new_dir: &Path,
+	old_snapshots: &Path,
+	new_snapshots: &Path,
+	moved: &mut Vec<(PathBuf, PathBuf)>,
+) -> anyhow::Result<()> {
+	if old_dir.exists() {
+		std::fs::rename(old_dir, new_dir).context("Failed to move instance directory")?;
+		moved.push((old_dir.to_owned(), new_dir.to_owned()));
+	}
+	if old_snapshots.exists() {
+		std::fs::rename(old_snapshots, new_snapshots)
+			.context("Failed to move instance snapshots")?;
+		moved.push((old_snapshots.to_owned(), new_snapshots.to_owned()));
+	}
+
+	Ok(())
+}
+
+async fn export(
+	data: &mut CmdData,
+	instance: String,
+	output: String,
+	format: String,
+	exclude_worlds: bool,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+	let instance_id = InstanceID::from(instance);
+
+	let formats = load_formats(&config.plugins, &data.paths, &mut data.output)
+		.context("Failed to load transfer formats")?;
+
+	let instance = config
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let settings = ExportSettings {
+		format,
+		result_path: PathBuf::from(output),
+		exclude_worlds,
+	};
+	instance.export(
+		settings,
+		&formats,
+		&config.plugins,
+		&data.paths,
+		&mut data.output,
+	)?;
+
+	data.output
+		.println(cformat!("<g>Instance <b>{}</> exported.", instance_id));
+
+	Ok(())
+}
+
+async fn dir(data: &mut CmdData, instance: Option<String>) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+
+	let instance = pick_instance(instance, data.config.get()).context("Failed to pick instance")?;
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance)
+		.context("Instance does not exist")?;
+	instance.ensure_dirs(&data.paths)?;
+
+	println!("{}", &instance.get_dirs().get().game_dir.to_string_lossy());
+
+	Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn update(
+	data: &mut CmdData,
+	instances: Vec<String>,
+	groups: Vec<String>,
+	all: bool,
+	force: bool,
+	skip_packages: bool,
+	backup: bool,
+	dry_run: bool,
+	frozen: bool,
+	yes: bool,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+
+	let mut ids: Vec<InstanceID> = if all {
+		config.instances.keys().cloned().collect()
+	} else {
+		instances.into_iter().map(InstanceID::from).collect()
+	};
+
+	for group in groups {
+		let group = Arc::from(group);
+		let group = config
+			.instance_groups
+			.get(&group)
+			.with_context(|| format!("Instance group '{group}' does not exist"))?;
+		ids.extend(group.clone());
+	}
+
+	let client = config.prefs.make_client()?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let mut plans = Vec::new();
+	for id in ids {
+		let instance = config
+			.instances
+			.get_mut(&id)
+			.with_context(|| format!("Unknown instance '{id}'"))?;
+
+		let mut ctx = InstanceUpdateContext {
+			packages: &mut config.packages,
+			users: &config.users,
+			plugins: &config.plugins,
+			prefs: &config.prefs,
+			paths: &data.paths,
+			lock: &mut lock,
+			client: &client,
+			output: &mut data.output,
+			accept_permissions: yes,
+		};
+
+		if dry_run {
+			let plan = instance
+				.plan_update(!skip_packages, force, &mut ctx)
+				.await
+				.context("Failed to plan instance update")?;
+			plans.push(plan);
+		} else if frozen {
+			instance
+				.update_frozen(&mut ctx)
+				.await
+				.context("Failed to update instance from the lockfile")?;
+		} else {
+			instance
+				.update(!skip_packages, force, backup, &mut ctx)
+				.await
+				.context("Failed to update instance")?;
+		}
+	}
+
+	// Print the report at the end so that it isn't mixed up with the progress output
+	if dry_run {
+		if data.output.is_json() {
+			return print_json(&plans);
+		}
+		for plan in plans {
+			plan.display(&mut data.output);
+		}
+	}
+
+	Ok(())
+}
+
+/// Pick which instance to use
+pub fn pick_instance(instance: Option<String>, config: &Config) -> anyhow::Result<InstanceID> {
+	if let Some(instance) = instance {
+		Ok(instance.into())
+	} else {
+		let options = config.instances.keys().sorted().collect();
+		let selection = Select::new("Choose an instance", options)
+			.prompt()
 			.context("Prompt failed")?;
 
 		Ok(selection.to_owned())
 	}
 }
+
+async fn datapack(command: DatapackSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match command {
+		DatapackSubcommand::Add {
+			instance,
+			datapack,
+			world,
+		} => datapack_add(data, instance, datapack, world).await,
+		DatapackSubcommand::List { raw, instance } => datapack_list(data, instance, raw).await,
+		DatapackSubcommand::Remove { instance, datapack } => {
+			datapack_remove(data, instance, datapack).await
+		}
+	}
+}
+
+async fn datapack_add(
+	data: &mut CmdData,
+	instance: String,
+	datapack: String,
+	worlds: Vec<String>,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+	let instance_id = InstanceID::from(instance);
+	let instance = config
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let source = PackSource::parse(&datapack)?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let id = instance
+		.add_datapack(
+			&source,
+			&worlds,
+			&mut lock,
+			&data.paths,
+			&client,
+			&mut data.output,
+		)
+		.await
+		.context("Failed to add datapack")?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Datapack <b>{}</> added to <b>{}</>.",
+		id,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn datapack_list(data: &mut CmdData, instance: String, raw: bool) -> anyhow::Result<()> {
+	data.ensure_config(!raw).await?;
+	let instance_id = InstanceID::from(instance);
+	if !data.config.get().instances.contains_key(&instance_id) {
+		bail!("Unknown instance '{instance_id}'");
+	}
+
+	let lock = Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	for (id, datapack) in lock.get_datapacks(&instance_id) {
+		if raw {
+			println!("{id}");
+		} else if let Some(version) = &datapack.version {
+			data.output
+				.println(cformat!("{}<b>{}</> <k!>{}</>", HYPHEN_POINT, id, version));
+		} else {
+			data.output
+				.println(cformat!("{}<b>{}</>", HYPHEN_POINT, id));
+		}
+	}
+
+	Ok(())
+}
+
+async fn datapack_remove(
+	data: &mut CmdData,
+	instance: String,
+	datapack: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	instance.remove_datapack(&datapack, &mut lock)?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Datapack <b>{}</> removed from <b>{}</>.",
+		datapack,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn resource_pack(command: ResourcePackSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match command {
+		ResourcePackSubcommand::Add {
+			instance,
+			resource_pack,
+			enable,
+		} => resource_pack_add(data, instance, resource_pack, enable).await,
+		ResourcePackSubcommand::List { raw, instance } => {
+			resource_pack_list(data, instance, raw).await
+		}
+		ResourcePackSubcommand::Remove {
+			instance,
+			resource_pack,
+		} => resource_pack_remove(data, instance, resource_pack).await,
+	}
+}
+
+async fn resource_pack_add(
+	data: &mut CmdData,
+	instance: String,
+	resource_pack: String,
+	enable: bool,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+	let instance_id = InstanceID::from(instance);
+	let instance = config
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let source = PackSource::parse(&resource_pack)?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let id = instance
+		.add_resource_pack(
+			&source,
+			enable,
+			&mut lock,
+			&data.paths,
+			&client,
+			&mut data.output,
+		)
+		.await
+		.context("Failed to add resource pack")?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Resource pack <b>{}</> added to <b>{}</>.",
+		id,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn resource_pack_list(data: &mut CmdData, instance: String, raw: bool) -> anyhow::Result<()> {
+	data.ensure_config(!raw).await?;
+	let instance_id = InstanceID::from(instance);
+	if !data.config.get().instances.contains_key(&instance_id) {
+		bail!("Unknown instance '{instance_id}'");
+	}
+
+	let lock = Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	for (id, resource_pack) in lock.get_resource_packs(&instance_id) {
+		if raw {
+			println!("{id}");
+			continue;
+		}
+		let enabled = if resource_pack.enabled {
+			" (enabled)"
+		} else {
+			""
+		};
+		if let Some(version) = &resource_pack.version {
+			data.output.println(cformat!(
+				"{}<b>{}</> <k!>{}</>{}",
+				HYPHEN_POINT,
+				id,
+				version,
+				enabled
+			));
+		} else {
+			data.output
+				.println(cformat!("{}<b>{}</>{}", HYPHEN_POINT, id, enabled));
+		}
+	}
+
+	Ok(())
+}
+
+async fn resource_pack_remove(
+	data: &mut CmdData,
+	instance: String,
+	resource_pack: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	instance.remove_resource_pack(&resource_pack, &mut lock, &data.paths)?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Resource pack <b>{}</> removed from <b>{}</>.",
+		resource_pack,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn shader_pack(command: ShaderPackSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match command {
+		ShaderPackSubcommand::Add {
+			instance,
+			shader_pack,
+		} => shader_pack_add(data, instance, shader_pack).await,
+		ShaderPackSubcommand::List { raw, instance } => shader_pack_list(data, instance, raw).await,
+		ShaderPackSubcommand::Remove {
+			instance,
+			shader_pack,
+		} => shader_pack_remove(data, instance, shader_pack).await,
+	}
+}
+
+async fn shader_pack_add(
+	data: &mut CmdData,
+	instance: String,
+	shader_pack: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+	let instance_id = InstanceID::from(instance);
+	let instance = config
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let source = PackSource::parse(&shader_pack)?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let id = instance
+		.add_shader_pack(&source, &mut lock, &data.paths, &client, &mut data.output)
+		.await
+		.context("Failed to add shader pack")?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Shader pack <b>{}</> added to <b>{}</>.",
+		id,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn shader_pack_list(data: &mut CmdData, instance: String, raw: bool) -> anyhow::Result<()> {
+	data.ensure_config(!raw).await?;
+	let instance_id = InstanceID::from(instance);
+	if !data.config.get().instances.contains_key(&instance_id) {
+		bail!("Unknown instance '{instance_id}'");
+	}
+
+	let lock = Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	for (id, shader_pack) in lock.get_shader_packs(&instance_id) {
+		if raw {
+			println!("{id}");
+		} else if let Some(version) = &shader_pack.version {
+			data.output
+				.println(cformat!("{}<b>{}</> <k!>{}</>", HYPHEN_POINT, id, version));
+		} else {
+			data.output
+				.println(cformat!("{}<b>{}</>", HYPHEN_POINT, id));
+		}
+	}
+
+	Ok(())
+}
+
+async fn shader_pack_remove(
+	data: &mut CmdData,
+	instance: String,
+	shader_pack: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	instance.remove_shader_pack(&shader_pack, &mut lock)?;
+	lock.finish(&data.paths)
+		.context("Failed to write lockfile")?;
+
+	data.output.println(cformat!(
+		"<g>Shader pack <b>{}</> removed from <b>{}</>.",
+		shader_pack,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn snapshot(command: SnapshotSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match command {
+		SnapshotSubcommand::Create { instance, name } => {
+			snapshot_create(data, instance, name).await
+		}
+		SnapshotSubcommand::List { raw, instance } => snapshot_list(data, instance, raw).await,
+		SnapshotSubcommand::Restore { instance, snapshot } => {
+			snapshot_restore(data, instance, snapshot).await
+		}
+		SnapshotSubcommand::Remove { instance, snapshot } => {
+			snapshot_remove(data, instance, snapshot).await
+		}
+	}
+}
+
+async fn snapshot_create(
+	data: &mut CmdData,
+	instance: String,
+	name: Option<String>,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let id = instance
+		.create_snapshot(name, &data.paths, &mut data.output)
+		.context("Failed to create snapshot")?;
+
+	data.output.println(cformat!(
+		"<g>Snapshot <b>{}</> created for <b>{}</>.",
+		id,
+		instance_id
+	));
+
+	Ok(())
+}
+
+async fn snapshot_list(data: &mut CmdData, instance: String, raw: bool) -> anyhow::Result<()> {
+	data.ensure_config(!raw).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get()
+		.instances
+		.get(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	for snapshot in instance.get_snapshots(&data.paths)? {
+		if raw {
+			println!("{}", snapshot.id);
+		} else {
+			data.output.println(cformat!(
+				"{}<b>{}</> <k!>{}</>",
+				HYPHEN_POINT,
+				snapshot.id,
+				snapshot.date
+			));
+		}
+	}
+
+	Ok(())
+}
+
+async fn snapshot_restore(
+	data: &mut CmdData,
+	instance: String,
+	snapshot: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+
+	if let Some(pid) = read_pidfile(&data.paths, &instance_id)? {
+		if is_process_running(pid)? {
+			bail!("Instance '{instance_id}' is currently running. Stop it before restoring a snapshot");
+		}
+	}
+
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	let backup_id = instance
+		.restore_snapshot(&snapshot, &data.paths, &mut data.output)
+		.context("Failed to restore snapshot")?;
+
+	data.output.println(cformat!(
+		"<g>Snapshot <b>{}</> restored to <b>{}</>.",
+		snapshot,
+		instance_id
+	));
+	data.output.println(cformat!(
+		"<s>The previous state was saved as snapshot <b>{}</>.",
+		backup_id
+	));
+
+	Ok(())
+}
+
+async fn snapshot_remove(
+	data: &mut CmdData,
+	instance: String,
+	snapshot: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let instance_id = InstanceID::from(instance);
+	let instance = data
+		.config
+		.get_mut()
+		.instances
+		.get_mut(&instance_id)
+		.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+
+	instance
+		.remove_snapshot(&snapshot, &data.paths)
+		.context("Failed to remove snapshot")?;
+
+	data.output.println(cformat!(
+		"<g>Snapshot <b>{}</> removed from <b>{}</>.",
+		snapshot,
+		instance_id
+	));
+
+	Ok(())
+}
@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Subcommand;
+use color_print::cformat;
+use itertools::Itertools;
+use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
+use mcvm::io::migrate::{self, MigratedInstance, MigrationStatus};
+use mcvm::shared::id::InstanceID;
+
+use super::CmdData;
+use crate::output::{print_json, TerminalOutput, HYPHEN_POINT};
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateSubcommand {
+	#[command(
+		about = "Import instances from Prism Launcher or MultiMC",
+		long_about = "Import all of the instances from a Prism Launcher or MultiMC data directory.
+The Minecraft version and modloader of each instance are read from its settings, and its
+.minecraft directory is copied into a new mcvm instance. Mods are copied as files that
+mcvm does not manage."
+	)]
+	Prism {
+		/// The data directory of Prism Launcher or MultiMC, which contains the instances directory
+		dir: PathBuf,
+	},
+}
+
+pub async fn run(subcommand: MigrateSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match subcommand {
+		MigrateSubcommand::Prism { dir } => prism(data, dir).await,
+	}
+}
+
+async fn prism(data: &mut CmdData, dir: PathBuf) -> anyhow::Result<()> {
+	data.ensure_config(!data.output.is_json()).await?;
+	let config = data.config.get();
+	let existing_ids: Vec<InstanceID> = config.instances.keys().cloned().collect();
+
+	// Copying the game directories can take a while, so it is done on a blocking thread
+	let paths = data.paths.clone();
+	let migrated =
+		tokio::task::spawn_blocking(move || migrate::prism::migrate(&dir, &existing_ids, &paths))
+			.await
+			.context("Failed to run import task")?
+			.context("Failed to import instances")?;
+
+	let modifications = migrated
+		.iter()
+		.filter_map(|x| {
+			Some(ConfigModification::AddInstance(
+				x.id.clone()?,
+				x.config.clone()?,
+			))
+		})
+		.collect_vec();
+	if !modifications.is_empty() {
+		let result = data.get_raw_config().and_then(|mut raw_config| {
+			apply_modifications_and_write(&mut raw_config, modifications, &data.paths)
+		});
+		if let Err(e) = result {
+			// Don't leave copied instance directories behind without any config for them
+			migrate::remove_migrated_instances(&migrated, &data.paths);
+			return Err(e.context("Failed to write imported instances to config"));
+		}
+	}
+
+	if data.output.is_json() {
+		return print_json(&migrated);
+	}
+	print_report(&migrated, &data.output);
+
+	Ok(())
+}
+
+/// Print which instances were imported and which ones need more work
+fn print_report(migrated: &[MigratedInstance], o: &TerminalOutput) {
+	if migrated.is_empty() {
+		o.println(cformat!("<y>No instances were found to import"));
+		return;
+	}
+
+	for instance in migrated {
+		let id = instance.id.as_deref().unwrap_or_default();
+		match &instance.status {
+			MigrationStatus::Clean => {
+				o.println(cformat!(
+					"<g>Imported <b>{}</b> as <b>{}</b>",
+					instance.name,
+					id
+				));
+			}
+			MigrationStatus::NeedsAttention(notes) => {
+				o.println(cformat!(
+					"<y>Imported <b>{}</b> as <b>{}</b>, but it needs attention:",
+					instance.name,
+					id
+				));
+				for note in notes {
+					o.println(cformat!("{}<y>{}", HYPHEN_POINT, note));
+				}
+			}
+			MigrationStatus::Failed(error) => {
+				o.println(cformat!(
+					"<r>Failed to import <b>{}</b>: {}",
+					instance.name,
+					error
+				));
+			}
+		}
+		if instance.unmanaged_mods > 0 {
+			o.println(cformat!(
+				"{}Mods copied as unmanaged files: {}. Add them as packages to have mcvm manage them",
+				HYPHEN_POINT,
+				instance.unmanaged_mods
+			));
+		}
+	}
+
+	let imported = migrated.iter().filter(|x| x.id.is_some()).count();
+	o.println(cformat!(
+		"<s>Imported <b>{}</b> of <b>{}</b> instances",
+		imported,
+		migrated.len()
+	));
+}
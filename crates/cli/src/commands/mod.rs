@@ -1,13 +1,18 @@
+mod completions;
 mod config;
+mod doctor;
 mod files;
 mod instance;
+mod migrate;
 mod package;
 mod plugin;
+mod profile;
 mod user;
+mod version;
 
 use anyhow::{bail, Context};
 use clap::{Parser, Subcommand};
-use color_print::{cformat, cprintln};
+use color_print::cformat;
 
 use mcvm::config::plugin::PluginManager;
 use mcvm::config::{Config, ConfigDeser};
@@ -16,14 +21,18 @@ use mcvm::plugin::hooks::{self, AddTranslations};
 use mcvm::shared::later::Later;
 use mcvm::shared::output::{MCVMOutput, MessageContents, MessageLevel};
 
+use self::completions::CompletionKind;
 use self::config::ConfigSubcommand;
 use self::files::FilesSubcommand;
 use self::instance::InstanceSubcommand;
+use self::migrate::MigrateSubcommand;
 use self::package::PackageSubcommand;
 use self::plugin::PluginSubcommand;
+use self::profile::ProfileSubcommand;
 use self::user::UserSubcommand;
+use self::version::VersionSubcommand;
 
-use super::output::TerminalOutput;
+use super::output::{OutputFormat, TerminalOutput};
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
@@ -33,6 +42,12 @@ pub enum Command {
 		#[command(subcommand)]
 		command: InstanceSubcommand,
 	},
+	#[command(about = "Manage profiles")]
+	#[clap(alias = "prof")]
+	Profile {
+		#[command(subcommand)]
+		command: ProfileSubcommand,
+	},
 	#[command(about = "Manage users and authentication")]
 	User {
 		#[command(subcommand)]
@@ -42,6 +57,10 @@ pub enum Command {
 	Launch {
 		/// The instance to launch
 		instance: Option<String>,
+		/// Launch in offline mode as a temporary user with this username. The chosen user
+		/// must be a Microsoft user that has authenticated at least once
+		#[arg(long, value_name = "USERNAME")]
+		offline_name: Option<String>,
 	},
 	#[command(about = "Manage packages")]
 	#[clap(alias = "pkg")]
@@ -61,13 +80,46 @@ pub enum Command {
 		#[command(subcommand)]
 		command: ConfigSubcommand,
 	},
-	#[command(about = "Print the mcvm version")]
-	Version,
+	#[command(about = "Print the mcvm version or list Minecraft versions")]
+	Version {
+		#[command(subcommand)]
+		command: Option<VersionSubcommand>,
+	},
+	#[command(about = "Import instances from other launchers")]
+	Migrate {
+		#[command(subcommand)]
+		command: MigrateSubcommand,
+	},
 	#[command(about = "Deal with files created by mcvm")]
 	Files {
 		#[command(subcommand)]
 		command: FilesSubcommand,
 	},
+	#[cfg(feature = "schema")]
+	#[command(
+		about = "Print the JSON schema for the config file",
+		long_about = "Print the JSON schema for the config file, which editors can use to complete and validate it"
+	)]
+	Schema {
+		/// A file to write the schema to instead of printing it
+		path: Option<std::path::PathBuf>,
+	},
+	#[command(about = "Check for common problems with your setup")]
+	Doctor {
+		/// Whether to automatically fix problems that are safe to fix
+		#[arg(long)]
+		fix: bool,
+	},
+	#[command(about = "Print a shell completion script")]
+	Completions {
+		/// The shell to print the completion script for
+		shell: clap_complete::Shell,
+	},
+	#[command(hide = true)]
+	Complete {
+		/// The kind of value to print completion candidates for
+		kind: CompletionKind,
+	},
 	#[clap(external_subcommand)]
 	External(Vec<String>),
 }
@@ -80,6 +132,18 @@ pub struct Cli {
 	debug: bool,
 	#[arg(short = 'D', long)]
 	trace: bool,
+	/// Only show important messages, hiding status and progress lines
+	#[arg(short, long, global = true, conflicts_with = "verbose")]
+	quiet: bool,
+	/// Also show debug messages, such as retry notices
+	#[arg(short, long, global = true)]
+	verbose: bool,
+	/// The format to write output in
+	#[arg(long, global = true, value_enum, default_value_t)]
+	output: OutputFormat,
+	/// The maximum number of files to download at the same time, overriding the preference
+	#[arg(long, global = true, value_parser = clap::value_parser!(u16).range(1..))]
+	concurrency: Option<u16>,
 }
 
 /// Run the command line interface
@@ -100,27 +164,75 @@ pub async fn run_cli() -> anyhow::Result<()> {
 	}
 	let cli = cli?;
 
+	// Printing the paths shouldn't create them, so handle it before the command data is set up
+	if let Command::Files {
+		command: FilesSubcommand::Where { json },
+	} = &cli.command
+	{
+		return files::print_paths(*json);
+	}
+
+	// Completions should be fast and not have any side effects
+	match &cli.command {
+		Command::Completions { shell } => return completions::print_completions(*shell),
+		Command::Complete { kind } => return completions::print_candidates(*kind),
+		_ => {}
+	}
+
 	// Prepare the command data
 	let mut data = CmdData::new().await?;
 	let log_level = get_log_level(&cli);
 	data.output.set_log_level(log_level);
+	data.output.set_format(cli.output);
+	data.download_concurrency = cli.concurrency.map(usize::from);
 
 	let res = match cli.command {
 		Command::User { command } => user::run(command, &mut data).await,
-		Command::Launch { instance } => instance::launch(instance, None, false, &mut data).await,
-		Command::Version => {
-			print_version();
+		Command::Launch {
+			instance,
+			offline_name,
+		} => {
+			instance::launch(
+				Vec::from_iter(instance),
+				None,
+				offline_name.is_some(),
+				offline_name,
+				false,
+				&mut data,
+			)
+			.await
+		}
+		Command::Version { command: None } => {
+			print_version(&data.output);
 			Ok(())
 		}
+		Command::Version {
+			command: Some(command),
+		} => version::run(command, &mut data).await,
 		Command::Files { command } => files::run(command, &mut data).await,
+		Command::Migrate { command } => migrate::run(command, &mut data).await,
 		Command::Package { command } => package::run(command, &mut data).await,
 		Command::Instance { command } => instance::run(command, &mut data).await,
+		Command::Profile { command } => profile::run(command, &mut data).await,
 		Command::Plugin { command } => plugin::run(command, &mut data).await,
 		Command::Config { command } => config::run(command, &mut data).await,
+		Command::Doctor { fix } => doctor::run(&mut data, fix).await,
+		#[cfg(feature = "schema")]
+		Command::Schema { path } => print_schema(path),
+		Command::Completions { .. } | Command::Complete { .. } => Ok(()),
 		Command::External(args) => call_plugin_subcommand(args, &mut data).await,
 	};
 
 	if let Err(e) = &res {
+		if data.output.is_json() {
+			let error = serde_json::json!({
+				"error": e.to_string(),
+				"causes": e.chain().skip(1).map(|x| x.to_string()).collect::<Vec<_>>(),
+			});
+			eprintln!("{error}");
+			return res;
+		}
+
 		// Don't use the existing process or section
 		data.output.end_process();
 		data.output.end_section();
@@ -133,14 +245,16 @@ pub async fn run_cli() -> anyhow::Result<()> {
 	res
 }
 
-/// Get the log level based on the debug options
+/// Get the log level based on the verbosity options
 fn get_log_level(cli: &Cli) -> MessageLevel {
 	if cli.trace {
 		MessageLevel::Trace
-	} else if cli.debug {
+	} else if cli.debug || cli.verbose {
 		MessageLevel::Debug
-	} else {
+	} else if cli.quiet {
 		MessageLevel::Important
+	} else {
+		MessageLevel::Extra
 	}
 }
 
@@ -149,18 +263,22 @@ pub struct CmdData {
 	pub paths: Paths,
 	pub config: Later<Config>,
 	pub output: TerminalOutput,
+	/// A download concurrency that overrides the one from the preferences
+	pub download_concurrency: Option<usize>,
 }
 
 impl CmdData {
 	pub async fn new() -> anyhow::Result<Self> {
-		let paths = Paths::new()
+		let mut paths = Paths::new()
 			.await
 			.context("Failed to set up system paths")?;
+		Config::apply_instances_dir(&mut paths);
 		let output = TerminalOutput::new(&paths).context("Failed to set up output")?;
 		Ok(Self {
 			paths,
 			config: Later::new(),
 			output,
+			download_concurrency: None,
 		})
 	}
 
@@ -180,6 +298,9 @@ impl CmdData {
 				)
 				.context("Failed to load config")?,
 			);
+			if let Some(concurrency) = self.download_concurrency {
+				self.config.get_mut().prefs.download_concurrency = Some(concurrency);
+			}
 		}
 
 		// Update the translation map from plugins
@@ -191,7 +312,7 @@ impl CmdData {
 			.context("Failed to get extra translations from plugins")?;
 
 		for result in results {
-			let mut result = result.result(&mut self.output)?;
+			let mut result = result.result_async(&mut self.output).await?;
 			let map = result.remove(&self.config.get().prefs.language);
 			if let Some(map) = map {
 				self.output.set_translation_map(map);
@@ -211,11 +332,25 @@ impl CmdData {
 }
 
 /// Print the mcvm version
-fn print_version() {
+fn print_version(o: &TerminalOutput) {
 	let version = env!("CARGO_PKG_VERSION");
 	let mcvm_version = mcvm::VERSION;
-	cprintln!("CLI version: <g>{}</g>", version);
-	cprintln!("MCVM version: <g>{}</g>", mcvm_version);
+	o.println(cformat!("CLI version: <g>{}</g>", version));
+	o.println(cformat!("MCVM version: <g>{}</g>", mcvm_version));
+}
+
+/// Print the config schema or write it to a file
+#[cfg(feature = "schema")]
+fn print_schema(path: Option<std::path::PathBuf>) -> anyhow::Result<()> {
+	let schema = schemars::schema_for!(ConfigDeser);
+	let schema = serde_json::to_string_pretty(&schema).context("Failed to serialize schema")?;
+	if let Some(path) = path {
+		std::fs::write(path, schema).context("Failed to write schema to file")?;
+	} else {
+		println!("{schema}");
+	}
+
+	Ok(())
 }
 
 /// Call a plugin subcommand
@@ -244,8 +379,20 @@ async fn call_plugin_subcommand(args: Vec<String>, data: &mut CmdData) -> anyhow
 		.call_hook(hooks::Subcommand, &args, &data.paths, &mut data.output)
 		.context("Plugin subcommand failed")?;
 	for result in results {
-		result.result(&mut data.output)?;
+		result.result_async(&mut data.output).await?;
 	}
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use clap::CommandFactory;
+
+	use super::*;
+
+	#[test]
+	fn test_cli_definition() {
+		Cli::command().debug_assert();
+	}
+}
@@ -1,19 +1,28 @@
+use std::path::Path;
 use std::{collections::HashMap, sync::Arc};
 
 use super::CmdData;
 use itertools::Itertools;
-use mcvm::parse::lex::Token;
+use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
+use mcvm::config::package::PackageConfigSource;
+use mcvm::config::Config;
+use mcvm::instance::update::InstanceUpdateContext;
+use mcvm::io::lock::Lockfile;
+use mcvm::parse::highlight::highlight_package_script;
+use mcvm::pkg::repo::SyncResult;
+use mcvm::pkg::validate::validate_package;
+use mcvm::pkg_crate::metadata::PackageMetadata;
 use mcvm::pkg_crate::{PackageContentType, PkgRequest, PkgRequestSource};
 use mcvm::shared::id::{InstanceID, ProfileID};
 use mcvm::shared::util::print::ReplPrinter;
+use mcvm::util::fuzzy_match_score;
 
 use anyhow::{bail, Context};
 use clap::Subcommand;
-use color_print::{cformat, cprint, cprintln};
+use color_print::cformat;
 use mcvm::shared::pkg::PackageID;
-use reqwest::Client;
 
-use crate::output::HYPHEN_POINT;
+use crate::output::{print_json, HYPHEN_POINT};
 
 #[derive(Debug, Subcommand)]
 pub enum PackageSubcommand {
@@ -64,6 +73,48 @@ This package does not need to be installed, it just has to be in the index."
 	},
 	#[command(about = "Browse packages from the remote repositories")]
 	Browse {},
+	#[command(
+		about = "Search for packages in the configured repositories",
+		long_about = "Search for packages in all of the configured repositories. The query is
+fuzzy-matched against package IDs as well as their names and descriptions."
+	)]
+	Search {
+		/// Whether to remove formatting and warnings from the output
+		#[arg(short, long)]
+		raw: bool,
+		/// The search query
+		query: String,
+	},
+	#[command(
+		about = "Enable or disable features of a package on a profile",
+		long_about = "Enable or disable features of a package that is configured on a profile.
+The instances that use the profile are then updated so that the package's addons
+match the new features."
+	)]
+	Features {
+		/// The package to change the features of
+		package: String,
+		/// Features to enable
+		#[arg(short, long)]
+		enable: Vec<String>,
+		/// Features to disable
+		#[arg(short, long)]
+		disable: Vec<String>,
+		/// The profile that the package is configured on
+		#[arg(short, long)]
+		profile: String,
+	},
+	#[command(
+		about = "Check a package for problems",
+		long_about = "Check a package for problems before publishing it. The package can be a path
+to a package file or the ID of a package in the configured repositories. Syntax,
+metadata, and hashes are checked, as well as that the packages it relates to exist
+and that its addon URLs are reachable. Every problem that is found is reported."
+	)]
+	Validate {
+		/// The path or ID of the package to validate
+		package: String,
+	},
 }
 
 #[derive(Debug, Subcommand)]
@@ -90,11 +141,20 @@ pub async fn run(subcommand: PackageSubcommand, data: &mut CmdData) -> anyhow::R
 		PackageSubcommand::Info { package } => info(data, &package).await,
 		PackageSubcommand::Repository { command } => repo(command, data).await,
 		PackageSubcommand::Browse {} => browse(data).await,
+		PackageSubcommand::Search { raw, query } => search(data, &query, raw).await,
+		PackageSubcommand::Features {
+			package,
+			enable,
+			disable,
+			profile,
+		} => features(data, package, enable, disable, profile).await,
+		PackageSubcommand::Validate { package } => validate(data, &package).await,
 	}
 }
 
 async fn list(data: &mut CmdData, raw: bool, instance: Option<String>) -> anyhow::Result<()> {
-	data.ensure_config(!raw).await?;
+	let json = data.output.is_json();
+	data.ensure_config(!raw && !json).await?;
 	let config = data.config.get_mut();
 
 	if let Some(instance_id) = instance {
@@ -103,8 +163,18 @@ async fn list(data: &mut CmdData, raw: bool, instance: Option<String>) -> anyhow
 			.instances
 			.get(&instance_id)
 			.with_context(|| format!("Unknown instance '{instance_id}'"))?;
+		if json {
+			let packages: Vec<_> = instance
+				.get_configured_packages()
+				.iter()
+				.map(|x| &x.id)
+				.sorted()
+				.collect();
+			return print_json(&packages);
+		}
 		if !raw {
-			cprintln!("<s>Packages in instance <b>{}</b>:", instance_id);
+			data.output
+				.println(cformat!("<s>Packages in instance <b>{}</b>:", instance_id));
 		}
 		for pkg in instance
 			.get_configured_packages()
@@ -114,7 +184,8 @@ async fn list(data: &mut CmdData, raw: bool, instance: Option<String>) -> anyhow
 			if raw {
 				println!("{}", pkg.id);
 			} else {
-				cprintln!("{}<b!>{}</>", HYPHEN_POINT, pkg.id);
+				data.output
+					.println(cformat!("{}<b!>{}</>", HYPHEN_POINT, pkg.id));
 			}
 		}
 	} else {
@@ -127,16 +198,30 @@ async fn list(data: &mut CmdData, raw: bool, instance: Option<String>) -> anyhow
 					.push(id.clone());
 			}
 		}
+		if json {
+			let packages: Vec<_> = found_pkgs
+				.iter()
+				.sorted_by_key(|x| x.0)
+				.map(|(pkg, instances)| {
+					serde_json::json!({
+						"id": pkg,
+						"instances": instances.iter().sorted().collect::<Vec<_>>(),
+					})
+				})
+				.collect();
+			return print_json(&packages);
+		}
 		if !raw {
-			cprintln!("<s>Packages:");
+			data.output.println(cformat!("<s>Packages:"));
 		}
 		for (pkg, profiles) in found_pkgs.iter().sorted_by_key(|x| x.0) {
 			if raw {
 				println!("{pkg}");
 			} else {
-				cprintln!("<b!>{}</>", pkg);
+				data.output.println(cformat!("<b!>{}</>", pkg));
 				for profile in profiles.iter().sorted() {
-					cprintln!("{}<k!>{}", HYPHEN_POINT, profile);
+					data.output
+						.println(cformat!("{}<k!>{}", HYPHEN_POINT, profile));
 				}
 			}
 		}
@@ -149,8 +234,8 @@ async fn sync(data: &mut CmdData, filter: Vec<String>) -> anyhow::Result<()> {
 	data.ensure_config(true).await?;
 	let config = data.config.get_mut();
 
-	let mut printer = ReplPrinter::new(true);
-	let client = Client::new();
+	let mut printer = ReplPrinter::new(!data.output.is_quiet());
+	let client = config.prefs.make_client()?;
 	for repo in config.packages.repos.iter_mut() {
 		// Skip repositories not in the filter
 		if !filter.is_empty() && !filter.contains(&repo.id) {
@@ -158,11 +243,24 @@ async fn sync(data: &mut CmdData, filter: Vec<String>) -> anyhow::Result<()> {
 		}
 
 		printer.print(&cformat!("Syncing repository <b>{}</b>...", repo.id));
-		match repo.sync(&data.paths, &client).await {
-			Ok(..) => {
+		match repo.sync(&data.paths, &client, &mut data.output).await {
+			Ok(SyncResult::Updated) => {
 				printer.print(&cformat!("<g>Synced repository <b!>{}</b!>", repo.id));
 			}
+			Ok(SyncResult::UpToDate) => {
+				printer.print(&cformat!(
+					"<g>Repository <b!>{}</b!> is up to date",
+					repo.id
+				));
+			}
 			Err(e) => {
+				// Problems are still shown when the printer is quiet
+				if data.output.is_quiet() {
+					eprintln!(
+						"{}",
+						cformat!("<r>Failed to sync repository <r!>{}</r!>: {}", repo.id, e)
+					);
+				}
 				printer.println(&cformat!("<r>{}", e));
 				printer.print(&cformat!(
 					"<r>Failed to sync repository <r!>{}</r!>",
@@ -171,7 +269,7 @@ async fn sync(data: &mut CmdData, filter: Vec<String>) -> anyhow::Result<()> {
 				continue;
 			}
 		};
-		cprintln!();
+		printer.newline();
 	}
 	printer.print(&cformat!("<s>Updating packages..."));
 	config
@@ -187,11 +285,13 @@ async fn sync(data: &mut CmdData, filter: Vec<String>) -> anyhow::Result<()> {
 			.await
 		{
 			Ok(..) => {}
-			Err(e) => printer.println(&cformat!(
-				"<y>Warning: Package '{}' was invalid:\n{:?}",
-				package,
-				e
-			)),
+			Err(e) => {
+				let message = cformat!("<y>Warning: Package '{}' was invalid:\n{:?}", package, e);
+				if data.output.is_quiet() {
+					eprintln!("{message}");
+				}
+				printer.println(&message);
+			}
 		}
 	}
 
@@ -202,7 +302,7 @@ async fn cat(data: &mut CmdData, id: &str, raw: bool) -> anyhow::Result<()> {
 	data.ensure_config(!raw).await?;
 	let config = data.config.get_mut();
 
-	let client = Client::new();
+	let client = config.prefs.make_client()?;
 
 	let req = Arc::new(PkgRequest::parse(id, PkgRequestSource::UserRequire));
 	let contents = config
@@ -210,7 +310,8 @@ async fn cat(data: &mut CmdData, id: &str, raw: bool) -> anyhow::Result<()> {
 		.load(&req, &data.paths, &client, &mut data.output)
 		.await?;
 	if !raw {
-		cprintln!("<s,b>Contents of package <g>{}</g>:</s,b>", req);
+		data.output
+			.println(cformat!("<s,b>Contents of package <g>{}</g>:</s,b>", req));
 	}
 
 	if raw {
@@ -221,7 +322,7 @@ async fn cat(data: &mut CmdData, id: &str, raw: bool) -> anyhow::Result<()> {
 			.content_type(&req, &data.paths, &client, &mut data.output)
 			.await?;
 		if let PackageContentType::Script = content_type {
-			pretty_print_package_script(&contents)?;
+			print!("{}", highlight_package_script(&contents)?);
 		} else {
 			print!("{contents}");
 		}
@@ -230,88 +331,11 @@ async fn cat(data: &mut CmdData, id: &str, raw: bool) -> anyhow::Result<()> {
 	Ok(())
 }
 
-/// Pretty-print a package script
-fn pretty_print_package_script(contents: &str) -> anyhow::Result<()> {
-	let mut lexed = mcvm::parse::lex::lex(contents)?;
-
-	// Since the windows iterator won't go to the end with the last token on the left
-	// side of the window, because it always makes sure the array is at least 2 elements long,
-	// we pad the end with a none token
-	if let Some(last) = lexed.last().cloned() {
-		lexed.push((Token::None, last.1));
-	}
-
-	let mut last_tok_was_at = false;
-	let mut last_tok_was_curly_or_semi = false;
-	for elem in lexed.windows(2) {
-		if let Some(left) = elem.first() {
-			// If the right does not exist, set it to the end of the string
-			let right_pos = elem
-				.get(1)
-				.map(|x| *x.1.absolute())
-				.unwrap_or(contents.len() - 1);
-
-			let left_pos = *left.1.absolute();
-			if left_pos >= contents.len() || right_pos >= contents.len() {
-				continue;
-			}
-			// A range thing
-			let text = if left_pos == contents.len() - 1 && !contents.is_empty() {
-				&contents[left_pos..]
-			} else {
-				&contents[left_pos..right_pos]
-			};
-			let text = match left.0 {
-				Token::None => String::new(),
-				Token::Whitespace => text.to_string(),
-				Token::Semicolon
-				| Token::Colon
-				| Token::Comma
-				| Token::Pipe
-				| Token::Bang
-				| Token::Square(..)
-				| Token::Paren(..)
-				| Token::Angle(..)
-				| Token::Curly(..) => text.to_string(),
-				Token::At => cformat!("<m><s>{text}"),
-				Token::Variable(..) => cformat!("<c>{text}"),
-				Token::Comment(..) => cformat!("<k!>{text}"),
-				Token::Ident(..) => {
-					if last_tok_was_at {
-						cformat!("<m><s>{text}")
-					} else if last_tok_was_curly_or_semi {
-						cformat!("<b!>{text}")
-					} else {
-						text.to_string()
-					}
-				}
-				Token::Num(..) => cformat!("<y>{text}"),
-				Token::Str(..) => cformat!("<g>{text}"),
-			};
-			print!("{text}");
-
-			// Whitespace can split these tokens apart so we need to make sure it doesn't
-			if !left.0.is_ignored() {
-				last_tok_was_at = false;
-				last_tok_was_curly_or_semi = false;
-			}
-			if let Token::At = left.0 {
-				last_tok_was_at = true;
-			}
-			if let Token::Curly(..) | Token::Semicolon = left.0 {
-				last_tok_was_curly_or_semi = true;
-			}
-		}
-	}
-
-	Ok(())
-}
-
 async fn info(data: &mut CmdData, id: &str) -> anyhow::Result<()> {
 	data.ensure_config(true).await?;
 	let config = data.config.get_mut();
 
-	let client = Client::new();
+	let client = config.prefs.make_client()?;
 
 	let req = Arc::new(PkgRequest::parse(id, PkgRequestSource::UserRequire));
 	let metadata = config
@@ -320,13 +344,15 @@ async fn info(data: &mut CmdData, id: &str) -> anyhow::Result<()> {
 		.await
 		.context("Failed to get metadata from the registry")?;
 	if let Some(name) = &metadata.name {
-		cprintln!("<s><g>Package</g> <b>{}</b>", name);
+		data.output
+			.println(cformat!("<s><g>Package</g> <b>{}</b>", name));
 	} else {
-		cprintln!("<s><g>Package</g> <b>{}</b>", id);
+		data.output
+			.println(cformat!("<s><g>Package</g> <b>{}</b>", id));
 	}
 	if let Some(description) = &metadata.description {
 		if !description.is_empty() {
-			cprintln!("   <s>{}", description);
+			data.output.println(cformat!("   <s>{}", description));
 		}
 	}
 	if let Some(long_description) = &metadata.long_description {
@@ -334,53 +360,61 @@ async fn info(data: &mut CmdData, id: &str) -> anyhow::Result<()> {
 			termimad::print_text(long_description);
 		}
 	}
-	cprintln!("   <s>ID:</s> <g>{}", id);
+	data.output.println(cformat!("   <s>ID:</s> <g>{}", id));
 	if let Some(authors) = &metadata.authors {
 		if !authors.is_empty() {
-			cprintln!("   <s>Authors:</s> <g>{}", authors.join(", "));
+			data.output
+				.println(cformat!("   <s>Authors:</s> <g>{}", authors.join(", ")));
 		}
 	}
 	if let Some(maintainers) = &metadata.package_maintainers {
 		if !maintainers.is_empty() {
-			cprintln!(
+			data.output.println(cformat!(
 				"   <s>Package Maintainers:</s> <g>{}",
 				maintainers.join(", ")
-			);
+			));
 		}
 	}
 	if let Some(website) = &metadata.website {
 		if !website.is_empty() {
-			cprintln!("   <s>Website:</s> <b!>{}", website);
+			data.output
+				.println(cformat!("   <s>Website:</s> <b!>{}", website));
 		}
 	}
 	if let Some(support_link) = &metadata.support_link {
 		if !support_link.is_empty() {
-			cprintln!("   <s>Support Link:</s> <b!>{}", support_link);
+			data.output
+				.println(cformat!("   <s>Support Link:</s> <b!>{}", support_link));
 		}
 	}
 	if let Some(documentation) = &metadata.documentation {
 		if !documentation.is_empty() {
-			cprintln!("   <s>Documentation:</s> <b!>{}", documentation);
+			data.output
+				.println(cformat!("   <s>Documentation:</s> <b!>{}", documentation));
 		}
 	}
 	if let Some(source) = &metadata.source {
 		if !source.is_empty() {
-			cprintln!("   <s>Source:</s> <b!>{}", source);
+			data.output
+				.println(cformat!("   <s>Source:</s> <b!>{}", source));
 		}
 	}
 	if let Some(issues) = &metadata.issues {
 		if !issues.is_empty() {
-			cprintln!("   <s>Issue Tracker:</s> <b!>{}", issues);
+			data.output
+				.println(cformat!("   <s>Issue Tracker:</s> <b!>{}", issues));
 		}
 	}
 	if let Some(community) = &metadata.community {
 		if !community.is_empty() {
-			cprintln!("   <s>Community Link:</s> <b!>{}", community);
+			data.output
+				.println(cformat!("   <s>Community Link:</s> <b!>{}", community));
 		}
 	}
 	if let Some(license) = &metadata.license {
 		if !license.is_empty() {
-			cprintln!("   <s>License:</s> <b!>{}", license);
+			data.output
+				.println(cformat!("   <s>License:</s> <b!>{}", license));
 		}
 	}
 
@@ -395,26 +429,47 @@ async fn repo(subcommand: RepoSubcommand, data: &mut CmdData) -> anyhow::Result<
 }
 
 async fn repo_list(data: &mut CmdData, raw: bool) -> anyhow::Result<()> {
-	data.ensure_config(!raw).await?;
+	let json = data.output.is_json();
+	data.ensure_config(!raw && !json).await?;
 	let config = data.config.get_mut();
 
 	let repos = config.packages.get_repos();
 
+	if json {
+		let repos: Vec<_> = repos
+			.iter()
+			.map(|repo| {
+				serde_json::json!({
+					"id": repo.id,
+					"location": repo.get_location().to_string(),
+					"plugin": repo.get_plugin(),
+				})
+			})
+			.collect();
+		return print_json(&repos);
+	}
+
 	if raw {
 		for repo in repos {
 			println!("{}", repo.id);
 		}
 	} else {
-		cprintln!("<s>Repositories:");
+		data.output.println(cformat!("<s>Repositories:"));
 		for repo in repos {
 			if repo.id == "core" {
-				cprint!("<s><m>{}</></>", repo.id);
+				data.output.print(cformat!("<s><m>{}</></>", repo.id));
 			} else if repo.id == "std" {
-				cprint!("<s><b>{}</></>", repo.id);
+				data.output.print(cformat!("<s><b>{}</></>", repo.id));
 			} else {
-				cprint!("<s>{}</>", repo.id);
+				data.output.print(cformat!("<s>{}</>", repo.id));
 			}
-			cprintln!(" <k!>-</> <m>{}</>", repo.get_location());
+			data.output
+				.print(cformat!(" <k!>-</> <m>{}</>", repo.get_location()));
+			if let Some(plugin) = repo.get_plugin() {
+				data.output
+					.print(cformat!(" <k!>(from plugin {})</>", plugin));
+			}
+			data.output.println("");
 		}
 	}
 
@@ -431,7 +486,7 @@ async fn repo_info(data: &mut CmdData, repo_id: String) -> anyhow::Result<()> {
 	};
 
 	// Get the repo package count and metadata
-	let client = Client::new();
+	let client = config.prefs.make_client()?;
 
 	let pkg_count = repo
 		.get_package_count(&data.paths, &client, &mut data.output)
@@ -452,26 +507,33 @@ async fn repo_info(data: &mut CmdData, repo_id: String) -> anyhow::Result<()> {
 		repo.id.clone()
 	};
 
-	cprint!("<s>Repository </>");
+	data.output.print(cformat!("<s>Repository </>"));
 	if repo.id == "core" {
-		cprint!("<s><m>{}</></>", name);
+		data.output.print(cformat!("<s><m>{}</></>", name));
 	} else if repo.id == "std" {
-		cprint!("<s><b>{}</></>", name);
+		data.output.print(cformat!("<s><b>{}</></>", name));
 	} else {
-		cprint!("<s>{}</>", name);
+		data.output.print(cformat!("<s>{}</>", name));
 	}
-	cprintln!("<s>:</>");
+	data.output.println(cformat!("<s>:</>"));
 
 	// Print info
 	if let Some(description) = &meta.description {
-		cprintln!("   {}", description);
+		data.output.println(cformat!("   {}", description));
+	}
+	data.output.println(cformat!("   <s>ID:</> {}", repo.id));
+	data.output
+		.println(cformat!("   <s>Location:</> <m>{}</>", repo.get_location()));
+	if repo.is_authenticated() {
+		data.output
+			.println(cformat!("   <s>Authentication:</> Bearer token"));
 	}
-	cprintln!("   <s>ID:</> {}", repo.id);
-	cprintln!("   <s>Location:</> <m>{}</>", repo.get_location());
 	if let Some(version) = &meta.mcvm_version {
-		cprintln!("   <s>MCVM Version:</> <c>{}</>", version);
+		data.output
+			.println(cformat!("   <s>MCVM Version:</> <c>{}</>", version));
 	}
-	cprintln!("   <s>Package Count:</> <y>{}</>", pkg_count);
+	data.output
+		.println(cformat!("   <s>Package Count:</> <y>{}</>", pkg_count));
 
 	Ok(())
 }
@@ -480,7 +542,7 @@ async fn browse(data: &mut CmdData) -> anyhow::Result<()> {
 	data.ensure_config(true).await?;
 	let config = data.config.get_mut();
 
-	let client = Client::new();
+	let client = config.prefs.make_client()?;
 	let mut packages = config
 		.packages
 		.get_all_available_packages(&data.paths, &client, &mut data.output)
@@ -502,3 +564,266 @@ async fn browse(data: &mut CmdData) -> anyhow::Result<()> {
 
 	Ok(())
 }
+
+async fn search(data: &mut CmdData, query: &str, raw: bool) -> anyhow::Result<()> {
+	data.ensure_config(!raw).await?;
+	let config = data.config.get_mut();
+
+	let client = config.prefs.make_client()?;
+
+	// Collect the package lists first since getting metadata needs the whole registry
+	let mut repo_packages = Vec::new();
+	for repo in config.packages.repos.iter_mut() {
+		let packages = repo
+			.get_all_packages(&data.paths, &client, &mut data.output)
+			.await
+			.with_context(|| format!("Failed to get packages from repository '{}'", repo.id))?;
+		repo_packages.push((repo.id.clone(), packages));
+	}
+
+	// Metadata lookups are cached so that packages in multiple repositories are only loaded once
+	let mut metadata_cache: HashMap<String, Option<PackageMetadata>> = HashMap::new();
+	for (repo_id, packages) in repo_packages {
+		let mut results = Vec::new();
+		for (id, ..) in packages {
+			let metadata = if let Some(metadata) = metadata_cache.get(&id) {
+				metadata.clone()
+			} else {
+				let req = Arc::new(PkgRequest::parse(&id, PkgRequestSource::Repository));
+				let metadata = config
+					.packages
+					.get_metadata(&req, &data.paths, &client, &mut data.output)
+					.await
+					.ok()
+					.cloned();
+				metadata_cache.insert(id.clone(), metadata.clone());
+				metadata
+			};
+			let PackageMetadata {
+				name, description, ..
+			} = metadata.unwrap_or_default();
+
+			let score = [
+				fuzzy_match_score(query, &id),
+				name.as_ref().and_then(|x| fuzzy_match_score(query, x)),
+				// Descriptions are long enough that only direct matches are useful
+				description
+					.as_ref()
+					.filter(|x| x.to_lowercase().contains(&query.to_lowercase()))
+					.map(|_| 25),
+			]
+			.into_iter()
+			.flatten()
+			.max();
+
+			if let Some(score) = score {
+				results.push((id, name, score));
+			}
+		}
+
+		if results.is_empty() {
+			continue;
+		}
+
+		if !raw {
+			data.output
+				.println(cformat!("<s>Results from repository <b>{}</b>:", repo_id));
+		}
+		for (id, name, score) in results
+			.iter()
+			.sorted_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)))
+		{
+			if raw {
+				println!("{id}");
+			} else if let Some(name) = name {
+				data.output.println(cformat!(
+					"{}<b!>{}</> <k!>({})</> <y>[{}]",
+					HYPHEN_POINT,
+					id,
+					name,
+					score
+				));
+			} else {
+				data.output
+					.println(cformat!("{}<b!>{}</> <y>[{}]", HYPHEN_POINT, id, score));
+			}
+		}
+	}
+
+	Ok(())
+}
+
+async fn features(
+	data: &mut CmdData,
+	package: String,
+	enable: Vec<String>,
+	disable: Vec<String>,
+	profile: String,
+) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let profile_id = ProfileID::from(profile);
+	let mut raw_config = data.get_raw_config()?;
+	let mut package_config = raw_config
+		.get_profile(&profile_id)
+		.with_context(|| format!("Unknown profile '{profile_id}'"))?
+		.packages
+		.iter()
+		.find(|x| x.get_pkg_id().as_ref() == package)
+		.with_context(|| {
+			format!("Package '{package}' is not configured on profile '{profile_id}'")
+		})?
+		.clone();
+
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+	let req = Arc::new(PkgRequest::parse(
+		package_config.get_pkg_id(),
+		PkgRequestSource::UserRequire,
+	));
+	let properties = config
+		.packages
+		.get_properties(&req, &data.paths, &client, &mut data.output)
+		.await
+		.context("Failed to get properties of package")?
+		.clone();
+	package_config.change_features(&enable, &disable, &properties)?;
+	let effective_features = package_config
+		.clone()
+		.to_package_config(Default::default(), PackageConfigSource::Profile)
+		.calculate_features(&properties)?;
+
+	// Remember how the package was configured on each instance so that we only update
+	// the ones that changed
+	let get_instance_features = |config: &Config| {
+		config
+			.instances
+			.iter()
+			.filter_map(|(id, instance)| {
+				let package = instance.get_package_config(&package)?;
+				Some((
+					id.clone(),
+					(package.features.clone(), package.use_default_features),
+				))
+			})
+			.collect::<HashMap<_, _>>()
+	};
+	let old_features = get_instance_features(config);
+
+	apply_modifications_and_write(
+		&mut raw_config,
+		vec![ConfigModification::SetPackage(
+			profile_id.clone(),
+			package_config,
+		)],
+		&data.paths,
+	)
+	.context("Failed to write config")?;
+
+	// Reload the config and resolve packages again on the affected instances
+	data.config.clear();
+	data.ensure_config(false).await?;
+	let config = data.config.get_mut();
+	let changed: Vec<InstanceID> = get_instance_features(config)
+		.into_iter()
+		.filter(|(id, features)| old_features.get(id) != Some(features))
+		.map(|x| x.0)
+		.sorted()
+		.collect();
+
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	for id in changed {
+		let Some(instance) = config.instances.get_mut(&id) else {
+			continue;
+		};
+		let mut ctx = InstanceUpdateContext {
+			packages: &mut config.packages,
+			users: &config.users,
+			plugins: &config.plugins,
+			prefs: &config.prefs,
+			paths: &data.paths,
+			lock: &mut lock,
+			client: &client,
+			output: &mut data.output,
+			accept_permissions: false,
+		};
+		instance
+			.update(true, false, false, &mut ctx)
+			.await
+			.with_context(|| format!("Failed to update instance '{id}'"))?;
+	}
+
+	if data.output.is_json() {
+		return print_json(&effective_features);
+	}
+	data.output.println(cformat!(
+		"<s>Features of package <b>{}</b> on profile <b>{}</b>:",
+		package,
+		profile_id
+	));
+	for feature in effective_features.iter().sorted().dedup() {
+		data.output
+			.println(cformat!("{}<b>{}</>", HYPHEN_POINT, feature));
+	}
+
+	Ok(())
+}
+
+async fn validate(data: &mut CmdData, package: &str) -> anyhow::Result<()> {
+	data.ensure_config(!data.output.is_json()).await?;
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+
+	let path = Path::new(package);
+	let (contents, content_type) = if path.is_file() {
+		let contents = std::fs::read_to_string(path)
+			.with_context(|| format!("Failed to read package file {}", path.display()))?;
+		let content_type = if path.extension().is_some_and(|x| x == "json") {
+			PackageContentType::Declarative
+		} else {
+			PackageContentType::Script
+		};
+		(contents, content_type)
+	} else {
+		let req = Arc::new(PkgRequest::parse(package, PkgRequestSource::UserRequire));
+		let contents = config
+			.packages
+			.load(&req, &data.paths, &client, &mut data.output)
+			.await?;
+		let content_type = config
+			.packages
+			.content_type(&req, &data.paths, &client, &mut data.output)
+			.await?;
+		(contents, content_type)
+	};
+
+	let problems = validate_package(
+		&contents,
+		content_type,
+		&mut config.packages,
+		&data.paths,
+		&client,
+		&mut data.output,
+	)
+	.await;
+
+	if data.output.is_json() {
+		print_json(&problems)?;
+	} else if problems.is_empty() {
+		data.output
+			.println(cformat!("<s,g>Package <b>{}</b> is valid", package));
+	} else {
+		data.output
+			.println(cformat!("<s>Problems with package <b>{}</b>:", package));
+		for problem in &problems {
+			data.output
+				.println(cformat!("{}<r>{}", HYPHEN_POINT, problem));
+		}
+	}
+
+	if !problems.is_empty() {
+		bail!("Package has {} problem(s)", problems.len());
+	}
+
+	Ok(())
+}
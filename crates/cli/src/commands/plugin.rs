@@ -1,6 +1,6 @@
 use anyhow::Context;
 use clap::Subcommand;
-use color_print::cprintln;
+use color_print::cformat;
 use itertools::Itertools;
 
 use super::CmdData;
@@ -35,7 +35,8 @@ async fn list(data: &mut CmdData, raw: bool) -> anyhow::Result<()> {
 		if raw {
 			println!("{}", plugin.get_id());
 		} else {
-			cprintln!("{}<s>{}", HYPHEN_POINT, plugin.get_id());
+			data.output
+				.println(cformat!("{}<s>{}", HYPHEN_POINT, plugin.get_id()));
 		}
 	}
 
@@ -53,18 +54,19 @@ async fn info(data: &mut CmdData, plugin: String) -> anyhow::Result<()> {
 		.find(|x| x.get_id() == &plugin)
 		.context("Plugin does not exist")?;
 
-	cprintln!(
+	data.output.println(cformat!(
 		"<s>Plugin <b>{}</>:",
 		plugin
 			.get_manifest()
 			.name
 			.as_ref()
 			.unwrap_or(plugin.get_id())
-	);
+	));
 	if let Some(description) = &plugin.get_manifest().description {
-		cprintln!("{}", description);
+		data.output.println(cformat!("{}", description));
 	}
-	cprintln!("{}<s>ID:</> {}", HYPHEN_POINT, plugin.get_id());
+	data.output
+		.println(cformat!("{}<s>ID:</> {}", HYPHEN_POINT, plugin.get_id()));
 
 	Ok(())
 }
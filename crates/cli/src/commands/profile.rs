@@ -0,0 +1,391 @@
+use anyhow::{bail, Context};
+use clap::Subcommand;
+use color_print::cformat;
+use itertools::Itertools;
+use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
+use mcvm::config::profile::{ProfileManifest, ProfileSource};
+use mcvm::config::ConfigDeser;
+use mcvm::instance::update::summary::{InstanceState, UpdateSummary};
+use mcvm::instance::update::InstanceUpdateContext;
+use mcvm::io::lock::Lockfile;
+use mcvm::shared::id::{InstanceID, ProfileID};
+use mcvm::shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm::shared::util::validate_identifier;
+
+use super::CmdData;
+use crate::output::{print_json, HYPHEN_POINT};
+
+#[derive(Debug, Subcommand)]
+pub enum ProfileSubcommand {
+	#[command(
+		about = "List all profiles",
+		long_about = "List all of the configured profiles, along with their Minecraft version,
+loader, client and server types, and the number of instances that derive from them."
+	)]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to remove formatting and warnings from the output
+		#[arg(short, long)]
+		raw: bool,
+	},
+	#[command(
+		about = "Update all instances of a profile",
+		long_about = "Update all of the instances that derive from a profile and report what changed,
+such as the Minecraft version, the loader, and the installed packages.
+Instances whose configuration hasn't changed since they were last updated are skipped,
+unless --force is used."
+	)]
+	Update {
+		/// The profile to update
+		profile: Option<String>,
+		/// Whether to update the instances of every profile
+		#[arg(short, long)]
+		all: bool,
+		/// Whether to accept changed profile manifests and packages with elevated permissions without asking
+		#[arg(short, long)]
+		yes: bool,
+		/// Whether to update instances even if they haven't changed
+		#[arg(short, long)]
+		force: bool,
+		/// Install exactly the package files recorded in the lockfile instead of resolving packages,
+		/// failing if any of them can't be downloaded or don't match their hashes
+		#[arg(long, conflicts_with = "force")]
+		frozen: bool,
+	},
+	#[command(
+		about = "Regenerate the lockfile entries of a profile's instances",
+		long_about = "Resolve the packages of all of the instances that derive from a profile again
+and record the results in the lockfile, even if the instances haven't changed.
+The lockfile can then be used to install the same files elsewhere with `profile update --frozen`."
+	)]
+	Lock {
+		/// The profile to lock
+		profile: Option<String>,
+		/// Whether to lock the instances of every profile
+		#[arg(short, long)]
+		all: bool,
+		/// Whether to accept changed profile manifests and packages with elevated permissions without asking
+		#[arg(short, long)]
+		yes: bool,
+	},
+	#[command(
+		about = "Subscribe a profile to a remote manifest",
+		long_about = "Subscribe a profile to a manifest hosted at a URL, which defines the Minecraft version,
+loader, packages, and other configuration of the profile. The profile is created if it
+does not exist, and any local configuration on it is layered on top of the manifest.
+The manifest is pinned by its hash, and `profile update` will download it again and ask
+before accepting any changes."
+	)]
+	Subscribe {
+		/// The profile to subscribe
+		profile: String,
+		/// The URL of the manifest
+		url: String,
+		/// Whether to accept a manifest that runs commands or sets environment variables without asking
+		#[arg(short, long)]
+		yes: bool,
+	},
+}
+
+pub async fn run(subcommand: ProfileSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match subcommand {
+		ProfileSubcommand::List { raw } => list(data, raw).await,
+		ProfileSubcommand::Update {
+			profile,
+			all,
+			yes,
+			force,
+			frozen,
+		} => update(data, profile, all, yes, force, frozen).await,
+		ProfileSubcommand::Lock { profile, all, yes } => {
+			update(data, profile, all, yes, true, false).await
+		}
+		ProfileSubcommand::Subscribe { profile, url, yes } => {
+			subscribe(data, profile, url, yes).await
+		}
+	}
+}
+
+async fn list(data: &mut CmdData, raw: bool) -> anyhow::Result<()> {
+	let raw_config = data.get_raw_config()?;
+	let profiles = raw_config
+		.get_resolved_profiles(&data.paths)
+		.context("Failed to read profiles")?;
+
+	let profiles = profiles.iter().sorted_by_key(|x| x.0).map(|(id, profile)| {
+		let common = &profile.instance.common;
+		let version = common
+			.version
+			.as_ref()
+			.map(|x| x.to_mc_version().to_string());
+		(
+			id,
+			common,
+			version,
+			raw_config.get_profile_instances(id).len(),
+		)
+	});
+
+	if data.output.is_json() {
+		let profiles: Vec<_> = profiles
+			.map(|(id, common, version, instance_count)| {
+				serde_json::json!({
+					"id": id,
+					"version": version,
+					"modloader": common.modloader,
+					"client_type": common.client_type,
+					"server_type": common.server_type,
+					"instances": instance_count,
+				})
+			})
+			.collect();
+		return print_json(&profiles);
+	}
+
+	for (id, common, version, instance_count) in profiles {
+		if raw {
+			println!("{id}");
+			continue;
+		}
+
+		data.output.println(cformat!("<b!>{}</>", id));
+		data.output.println(cformat!(
+			"{}Version: <g>{}",
+			HYPHEN_POINT,
+			version.as_deref().unwrap_or("Unset")
+		));
+		if let Some(modloader) = &common.modloader {
+			data.output
+				.println(cformat!("{}Modloader: <m>{}", HYPHEN_POINT, modloader));
+		}
+		if let Some(client_type) = &common.client_type {
+			data.output
+				.println(cformat!("{}Client type: <y!>{}", HYPHEN_POINT, client_type));
+		}
+		if let Some(server_type) = &common.server_type {
+			data.output
+				.println(cformat!("{}Server type: <c!>{}", HYPHEN_POINT, server_type));
+		}
+		data.output
+			.println(cformat!("{}Instances: <b>{}", HYPHEN_POINT, instance_count));
+	}
+
+	Ok(())
+}
+
+async fn update(
+	data: &mut CmdData,
+	profile: Option<String>,
+	all: bool,
+	yes: bool,
+	force: bool,
+	frozen: bool,
+) -> anyhow::Result<()> {
+	let mut raw_config = data.get_raw_config()?;
+
+	let profiles = if all {
+		raw_config.get_profile_ids()
+	} else if let Some(profile) = profile {
+		let profile = ProfileID::from(profile);
+		if raw_config.get_profile(&profile).is_none() {
+			bail!("Profile '{profile}' does not exist");
+		}
+		vec![profile]
+	} else {
+		bail!("Specify a profile to update, or use --all to update every profile");
+	};
+
+	let ids: Vec<InstanceID> = profiles
+		.iter()
+		.flat_map(|x| raw_config.get_profile_instances(x))
+		.unique()
+		.collect();
+
+	// Download the manifests of subscribed profiles first so that the instances are updated with them.
+	// Frozen updates only use what is already recorded
+	if !frozen {
+		update_manifests(data, &mut raw_config, &profiles, yes).await?;
+	}
+
+	data.ensure_config(true).await?;
+	let config = data.config.get_mut();
+	let client = config.prefs.make_client()?;
+	let mut lock =
+		Lockfile::open(&data.paths, &mut data.output).context("Failed to open lockfile")?;
+	let mut summaries = Vec::new();
+	for id in ids {
+		let instance = config
+			.instances
+			.get_mut(&id)
+			.with_context(|| format!("Unknown instance '{id}'"))?;
+
+		let before = InstanceState::read(&id, &lock);
+		if !force && !frozen && instance.is_unchanged_since_update(&lock) {
+			summaries.push(UpdateSummary::skipped(&id, before));
+			continue;
+		}
+
+		let mut ctx = InstanceUpdateContext {
+			packages: &mut config.packages,
+			users: &config.users,
+			plugins: &config.plugins,
+			prefs: &config.prefs,
+			paths: &data.paths,
+			lock: &mut lock,
+			client: &client,
+			output: &mut data.output,
+			accept_permissions: yes,
+		};
+		if frozen {
+			instance.update_frozen(&mut ctx).await
+		} else {
+			instance.update(true, false, false, &mut ctx).await
+		}
+		.with_context(|| format!("Failed to update instance '{id}'"))?;
+		let after = InstanceState::read(&id, &lock);
+
+		summaries.push(UpdateSummary::new(&id, before, after));
+	}
+
+	// Print the summaries at the end so that they aren't mixed up with the progress output
+	if data.output.is_json() {
+		return print_json(&summaries);
+	}
+	for summary in summaries {
+		summary.display(&mut data.output);
+	}
+
+	Ok(())
+}
+
+/// Download the manifests of subscribed profiles again and ask to accept the ones that changed
+async fn update_manifests(
+	data: &mut CmdData,
+	raw_config: &mut ConfigDeser,
+	profiles: &[ProfileID],
+	yes: bool,
+) -> anyhow::Result<()> {
+	let client = raw_config.make_client()?;
+	let mut modifications = Vec::new();
+	let mut manifests = Vec::new();
+	for profile in profiles {
+		let Some(source) = raw_config
+			.get_profile(profile)
+			.and_then(|x| x.source.clone())
+		else {
+			continue;
+		};
+
+		let downloaded = ProfileManifest::download(&source.url, &client)
+			.await
+			.with_context(|| format!("Failed to update manifest for profile '{profile}'"))?;
+		if !downloaded.sha256.eq_ignore_ascii_case(&source.sha256) {
+			data.output.display(
+				MessageContents::Warning(format!(
+					"The manifest for profile '{profile}' has changed (previous hash {}, new hash {})",
+					source.sha256, downloaded.sha256
+				)),
+				MessageLevel::Important,
+			);
+			show_launch_commands(data, &downloaded.manifest);
+			let accepted = yes
+				|| data.output.prompt_yes_no(
+					false,
+					MessageContents::Simple("Accept the new manifest?".into()),
+				)?;
+			if !accepted {
+				continue;
+			}
+			modifications.push(ConfigModification::SetProfileSource(
+				profile.clone(),
+				ProfileSource {
+					url: source.url,
+					sha256: downloaded.sha256.clone(),
+				},
+			));
+		}
+
+		manifests.push((profile, downloaded));
+	}
+
+	// The config is written first so that the cached manifests never have a newer
+	// hash than the one that is pinned. A manifest that failed to be cached will
+	// just be downloaded again on the next update
+	if !modifications.is_empty() {
+		apply_modifications_and_write(raw_config, modifications, &data.paths)
+			.context("Failed to write modified config")?;
+	}
+	for (profile, downloaded) in manifests {
+		downloaded.write_cache(profile, &data.paths)?;
+	}
+
+	Ok(())
+}
+
+async fn subscribe(
+	data: &mut CmdData,
+	profile: String,
+	url: String,
+	yes: bool,
+) -> anyhow::Result<()> {
+	validate_identifier(&profile).with_context(|| format!("Invalid profile ID '{profile}'"))?;
+	let profile = ProfileID::from(profile);
+
+	let mut raw_config = data.get_raw_config()?;
+	let client = raw_config.make_client()?;
+	let downloaded = ProfileManifest::download(&url, &client).await?;
+	if show_launch_commands(data, &downloaded.manifest) && !yes {
+		let accepted = data.output.prompt_yes_no(
+			false,
+			MessageContents::Simple("Subscribe to this manifest?".into()),
+		)?;
+		if !accepted {
+			bail!("Subscription to the manifest was cancelled");
+		}
+	}
+
+	let source = ProfileSource {
+		url,
+		sha256: downloaded.sha256.clone(),
+	};
+	apply_modifications_and_write(
+		&mut raw_config,
+		vec![ConfigModification::SetProfileSource(
+			profile.clone(),
+			source,
+		)],
+		&data.paths,
+	)
+	.context("Failed to write modified config")?;
+	downloaded.write_cache(&profile, &data.paths)?;
+
+	data.output.println(cformat!(
+		"<g>Profile <b>{}</> subscribed to manifest with hash <b>{}</>",
+		profile,
+		downloaded.sha256
+	));
+
+	Ok(())
+}
+
+/// Show the commands that a profile manifest runs and the environment variables that it sets when
+/// launching. Returns whether there were any
+fn show_launch_commands(data: &mut CmdData, manifest: &ProfileManifest) -> bool {
+	let commands = manifest.get_launch_commands();
+	if commands.is_empty() {
+		return false;
+	}
+
+	data.output.display(
+		MessageContents::Warning("When launching, this manifest will:".into()),
+		MessageLevel::Important,
+	);
+	for command in commands {
+		data.output.display(
+			MessageContents::ListItem(Box::new(MessageContents::Simple(command))),
+			MessageLevel::Important,
+		);
+	}
+
+	true
+}
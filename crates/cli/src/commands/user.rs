@@ -1,14 +1,14 @@
 use super::CmdData;
-use crate::output::{icons_enabled, HYPHEN_POINT, STAR};
+use crate::output::{icons_enabled, print_json, HYPHEN_POINT, STAR};
 use anyhow::{bail, Context};
 use itertools::Itertools;
 use mcvm::config::modifications::{apply_modifications_and_write, ConfigModification};
 use mcvm::config::user::{UserConfig, UserVariant};
 use mcvm::core::user::UserKind;
+use mcvm::shared::output::{MCVMOutput, MessageContents};
 
 use clap::Subcommand;
-use color_print::{cprint, cprintln};
-use reqwest::Client;
+use color_print::cformat;
 
 #[derive(Debug, Subcommand)]
 pub enum UserSubcommand {
@@ -41,6 +41,23 @@ pub enum UserSubcommand {
 	},
 	#[command(about = "Add new users to your config")]
 	Add {},
+	#[command(about = "Change the default user")]
+	Switch {
+		/// The user to switch to
+		user: String,
+	},
+	#[command(
+		about = "Remove a user",
+		long_about = "Remove a user from your config, along with all of their stored authentication"
+	)]
+	#[clap(alias = "rm")]
+	Remove {
+		/// The user to remove
+		user: String,
+		/// Whether to skip the confirmation prompt when removing the default user
+		#[arg(short, long)]
+		yes: bool,
+	},
 }
 
 pub async fn run(subcommand: UserSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
@@ -51,38 +68,68 @@ pub async fn run(subcommand: UserSubcommand, data: &mut CmdData) -> anyhow::Resu
 		UserSubcommand::Auth { user } => auth(data, user).await,
 		UserSubcommand::Logout { user } => logout(data, user).await,
 		UserSubcommand::Add {} => add(data).await,
+		UserSubcommand::Switch { user } => switch(data, user).await,
+		UserSubcommand::Remove { user, yes } => remove(data, user, yes).await,
 	}
 }
 
 async fn list(data: &mut CmdData, raw: bool) -> anyhow::Result<()> {
-	data.ensure_config(!raw).await?;
+	let json = data.output.is_json();
+	data.ensure_config(!raw && !json).await?;
 	let config = data.config.get();
+	let chosen_id = config.users.get_chosen_user().map(|x| x.get_id());
+
+	if json {
+		let users: Vec<_> = config
+			.users
+			.iter_users()
+			.sorted_by_key(|x| x.0)
+			.map(|(id, user)| {
+				let kind = match user.get_kind() {
+					UserKind::Microsoft { .. } => "microsoft",
+					UserKind::Demo => "demo",
+					UserKind::Unverified => "unverified",
+					UserKind::Unknown(other) => other,
+				};
+				serde_json::json!({
+					"id": id,
+					"kind": kind,
+					"default": Some(id) == chosen_id,
+					"logged_in": user.is_auth_valid(&data.paths.core),
+				})
+			})
+			.collect();
+		return print_json(&users);
+	}
 
 	if !raw {
-		cprintln!("<s>Users:");
+		data.output.println(cformat!("<s>Users:"));
 	}
 	for (id, user) in config.users.iter_users().sorted_by_key(|x| x.0) {
-		cprint!("{}", HYPHEN_POINT);
 		if raw {
 			println!("{id}");
 		} else {
+			data.output.print(cformat!("{}", HYPHEN_POINT));
 			match user.get_kind() {
-				UserKind::Microsoft { .. } => {
-					cprint!("<s><g>{}</g>", id)
+				UserKind::Microsoft { .. } => data.output.print(cformat!("<s><g>{}</g>", id)),
+				UserKind::Demo => data.output.print(cformat!("<s><c!>{}</c!>", id)),
+				UserKind::Unverified => data.output.print(cformat!("<s><y!>{}</y!>", id)),
+				UserKind::Unknown(other) => {
+					data.output
+						.print(cformat!("<s><k!>({}) {}</k!>", other, id))
 				}
-				UserKind::Demo => cprint!("<s><c!>{}</c!>", id),
-				UserKind::Unknown(other) => cprint!("<s><k!>({other}) {}</k!>", id),
 			}
-			if let Some(chosen) = config.users.get_chosen_user() {
-				if chosen.get_id() == id {
-					if icons_enabled() {
-						cprint!("<y> {}", STAR);
-					} else {
-						cprint!("<s> (Default)");
-					}
+			if Some(id) == chosen_id {
+				if icons_enabled() {
+					data.output.print(cformat!("<y> {}", STAR));
+				} else {
+					data.output.print(cformat!("<s> (Default)"));
 				}
 			}
-			println!();
+			if !user.is_auth_valid(&data.paths.core) {
+				data.output.print(cformat!("<k!> (Logged out)"));
+			}
+			data.output.println("");
 		}
 	}
 
@@ -97,22 +144,46 @@ async fn status(data: &mut CmdData) -> anyhow::Result<()> {
 		Some(user) => {
 			let user_valid = user.is_auth_valid(&data.paths.core);
 			if user_valid {
-				cprint!("<g>Logged in as ");
+				data.output.print(cformat!("<g>Logged in as "));
 			} else {
-				cprint!("<g>User chosen as ");
+				data.output.print(cformat!("<g>User chosen as "));
 			}
 			match user.get_kind() {
-				UserKind::Microsoft { .. } => cprint!("<s,g!>{}", user.get_id()),
-				UserKind::Demo => cprint!("<s,c!>{}", user.get_id()),
-				UserKind::Unknown(other) => cprint!("<s,k!>({other}) {}", user.get_id()),
+				UserKind::Microsoft { .. } => {
+					data.output.print(cformat!("<s,g!>{}", user.get_id()))
+				}
+				UserKind::Demo => data.output.print(cformat!("<s,c!>{}", user.get_id())),
+				UserKind::Unverified => data.output.print(cformat!("<s,y!>{}", user.get_id())),
+				UserKind::Unknown(other) => {
+					data.output
+						.print(cformat!("<s,k!>({}) {}", other, user.get_id()))
+				}
 			}
 
 			if !user_valid {
-				cprint!(" - <r>Currently logged out");
+				data.output.print(cformat!(" - <r>Currently logged out"));
 			}
-			cprintln!();
+			data.output.println("");
+		}
+		None => data.output.println(cformat!("<r>No user chosen")),
+	}
+
+	// Show the other accounts that can be switched to without signing in
+	let chosen_id = config.users.get_chosen_user().map(|x| x.get_id());
+	let others: Vec<_> = config
+		.users
+		.list_authenticated(&data.paths.core)
+		.into_iter()
+		.filter(|x| x.is_microsoft() && Some(x.get_id()) != chosen_id)
+		.map(|x| x.get_id())
+		.sorted()
+		.collect();
+	if !others.is_empty() {
+		data.output.println(cformat!("<s>Other logged in users:"));
+		for id in others {
+			data.output
+				.println(cformat!("{}<s,g!>{}", HYPHEN_POINT, id));
 		}
-		None => cprintln!("<r>No user chosen"),
 	}
 
 	Ok(())
@@ -143,7 +214,7 @@ async fn auth(data: &mut CmdData, user: Option<String>) -> anyhow::Result<()> {
 		config.users.choose_user(&user)?;
 	}
 
-	let client = Client::new();
+	let client = config.prefs.make_client()?;
 	config
 		.users
 		.authenticate(&data.paths.core, &client, &mut data.output)
@@ -190,7 +261,74 @@ async fn add(data: &mut CmdData) -> anyhow::Result<()> {
 	)
 	.context("Failed to write modified config")?;
 
-	cprintln!("<g>User added.");
+	data.output.println(cformat!("<g>User added."));
+
+	Ok(())
+}
+
+async fn switch(data: &mut CmdData, user: String) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get();
+	let Some(user_ref) = config.users.get_user(&user) else {
+		bail!("User '{user}' does not exist");
+	};
+	let is_logged_in = user_ref.is_auth_valid(&data.paths.core);
+
+	let mut config = data.get_raw_config()?;
+	apply_modifications_and_write(
+		&mut config,
+		vec![ConfigModification::SetDefaultUser(user.clone())],
+		&data.paths,
+	)
+	.context("Failed to write modified config")?;
+
+	data.output
+		.println(cformat!("<g>Switched to user <b>{}</b>.", user));
+	if !is_logged_in {
+		data.output.println(cformat!("<y>This user is not logged in, so you will have to sign in the next time they are used."));
+	}
+
+	Ok(())
+}
+
+async fn remove(data: &mut CmdData, user: String, yes: bool) -> anyhow::Result<()> {
+	data.ensure_config(true).await?;
+	let config = data.config.get();
+	let Some(user_ref) = config.users.get_user(&user) else {
+		bail!("User '{user}' does not exist");
+	};
+	let is_chosen = config
+		.users
+		.get_chosen_user()
+		.is_some_and(|x| x.get_id() == user_ref.get_id());
+
+	if is_chosen && !yes {
+		let confirmed = data.output.prompt_yes_no(
+			false,
+			MessageContents::Simple(format!(
+				"User '{user}' is your default user. Are you sure you want to remove them?"
+			)),
+		)?;
+		if !confirmed {
+			data.output.println(cformat!("<s>Cancelled"));
+			return Ok(());
+		}
+	}
+
+	user_ref
+		.forget(&data.paths.core)
+		.context("Failed to remove stored authentication for user")?;
+
+	let mut config = data.get_raw_config()?;
+	apply_modifications_and_write(
+		&mut config,
+		vec![ConfigModification::RemoveUser(user.clone())],
+		&data.paths,
+	)
+	.context("Failed to write modified config")?;
+
+	data.output
+		.println(cformat!("<g>User <b>{}</b> removed.", user));
 
 	Ok(())
 }
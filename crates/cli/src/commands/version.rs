@@ -0,0 +1,112 @@
+use anyhow::Context;
+use clap::Subcommand;
+use color_print::cformat;
+use mcvm::core::net::game_files::version_manifest::{VersionFilter, VersionType};
+use mcvm::core::{ConfigBuilder, MCVMCore};
+use mcvm::plugin::hooks::AddVersions;
+
+use super::CmdData;
+use crate::output::{print_json, HYPHEN_POINT};
+
+#[derive(Debug, Subcommand)]
+pub enum VersionSubcommand {
+	#[command(
+		about = "List available Minecraft versions",
+		long_about = "List the Minecraft versions that can be installed, from newest to oldest"
+	)]
+	#[clap(alias = "ls")]
+	List {
+		/// Whether to only list releases
+		#[arg(short, long)]
+		releases: bool,
+		/// Whether to only list snapshots
+		#[arg(short, long)]
+		snapshots: bool,
+		/// Only list this version and the ones that came after it
+		#[arg(long)]
+		since: Option<String>,
+	},
+}
+
+pub async fn run(subcommand: VersionSubcommand, data: &mut CmdData) -> anyhow::Result<()> {
+	match subcommand {
+		VersionSubcommand::List {
+			releases,
+			snapshots,
+			since,
+		} => {
+			let filter = VersionFilter {
+				releases,
+				snapshots,
+				since,
+			};
+			list(data, filter).await
+		}
+	}
+}
+
+async fn list(data: &mut CmdData, filter: VersionFilter) -> anyhow::Result<()> {
+	let json = data.output.is_json();
+	data.ensure_config(!json).await?;
+	let config = data.config.get();
+
+	let core_config = ConfigBuilder::new()
+		.mirrors(config.prefs.mirrors.clone())
+		.build();
+	let mut core = MCVMCore::with_config(core_config).context("Failed to initialize core")?;
+	core.set_client(config.prefs.make_client()?);
+
+	// Add extra versions to manifest from plugins
+	let results = config
+		.plugins
+		.call_hook(AddVersions, &(), &data.paths, &mut data.output)
+		.context("Failed to call add_versions hook")?;
+	for result in results {
+		let result = result.result_async(&mut data.output).await?;
+		core.add_additional_versions(result);
+	}
+
+	let manifest = core
+		.get_version_manifest(&mut data.output)
+		.await
+		.context("Failed to get version manifest")?;
+	let versions = manifest.manifest.filter_versions(&filter)?;
+
+	if json {
+		let versions: Vec<_> = versions
+			.into_iter()
+			.map(|x| {
+				serde_json::json!({
+					"id": x.id,
+					"type": x.ty,
+					"release_time": x.release_time,
+				})
+			})
+			.collect();
+		return print_json(&versions);
+	}
+
+	for version in versions {
+		let ty = match version.ty {
+			VersionType::Release => "release",
+			VersionType::Snapshot => "snapshot",
+			VersionType::OldAlpha => "old alpha",
+			VersionType::OldBeta => "old beta",
+		};
+		// Only show the date part of the release time
+		let date = version
+			.release_time
+			.as_deref()
+			.and_then(|x| x.get(..10))
+			.unwrap_or("unknown date");
+		data.output.println(cformat!(
+			"{}<g>{}</> <k!>({}, {})",
+			HYPHEN_POINT,
+			version.id,
+			ty,
+			date
+		));
+	}
+
+	Ok(())
+}
@@ -1,17 +1,21 @@
-use std::io::Write;
+use std::fmt::Display;
+use std::io::{IsTerminal, Write};
 use std::{fs::File, path::PathBuf};
 
 use anyhow::Context;
+use clap::ValueEnum;
 use color_print::{cformat, cstr};
 use inquire::{Confirm, Password};
 use mcvm::io::paths::Paths;
 use mcvm::pkg_crate::{PkgRequest, PkgRequestSource};
 use mcvm::shared::lang::translate::{TranslationKey, TranslationMap};
 use mcvm::shared::output::{
-	default_special_ms_auth, MCVMOutput, Message, MessageContents, MessageLevel,
+	default_special_ms_auth, MCVMOutput, Message, MessageContents, MessageLevel, UpdatePhase,
+	UpdatePhaseEvent,
 };
 use mcvm::shared::util::print::ReplPrinter;
 use mcvm::shared::util::utc_timestamp;
+use serde::Serialize;
 
 /// A nice colored bullet point for terminal output
 pub const HYPHEN_POINT: &str = cstr!("<k!> - </k!>");
@@ -27,6 +31,42 @@ pub const VERSION: &str = "\u{1F4C5}";
 /// A loader icon
 pub const LOADER: &str = "\u{1F4E5}";
 
+/// The format that the CLI writes its output in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+	/// Human-readable, colored text
+	#[default]
+	Text,
+	/// Machine-readable JSON. Messages are written as one JSON object per line,
+	/// and listing commands print a single JSON document
+	Json,
+}
+
+/// A message written to stdout in the JSON output format
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+	level: MessageLevel,
+	#[serde(flatten)]
+	contents: JsonMessageContents<'a>,
+}
+
+/// The contents of a JSON message
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonMessageContents<'a> {
+	/// Plain text
+	Text(&'a str),
+	/// A structured message
+	Message(&'a MessageContents),
+	/// An event for a phase of updating an instance
+	UpdatePhase {
+		/// The phase that the event is for
+		phase: UpdatePhase,
+		/// The event
+		event: UpdatePhaseEvent,
+	},
+}
+
 /// Terminal MCVMOutput
 pub struct TerminalOutput {
 	printer: ReplPrinter,
@@ -36,12 +76,19 @@ pub struct TerminalOutput {
 	log_file: File,
 	latest_log_file: File,
 	translation_map: Option<TranslationMap>,
+	/// Whether stdout is an interactive terminal that lines can be replaced on
+	is_terminal: bool,
+	format: OutputFormat,
 }
 
 impl MCVMOutput for TerminalOutput {
 	fn display_text(&mut self, text: String, level: MessageLevel) {
 		let _ = self.log_message(&text, level);
-		self.display_text_impl(text, level);
+		if self.is_json() {
+			self.display_json(JsonMessageContents::Text(&text), level);
+			return;
+		}
+		self.display_text_impl(text, level, false);
 	}
 
 	fn display_message(&mut self, message: Message) {
@@ -49,19 +96,58 @@ impl MCVMOutput for TerminalOutput {
 			&Self::format_message_log(message.contents.clone()),
 			message.level,
 		);
-		self.display_text_impl(self.format_message(message.contents), message.level);
+		if self.is_json() {
+			if !message.contents.is_unfinished_progress() {
+				self.display_json(
+					JsonMessageContents::Message(&message.contents),
+					message.level,
+				);
+			}
+			return;
+		}
+		// Progress bars can't be redrawn in place when the output is piped, so only show the final state
+		if !self.is_terminal && message.contents.is_unfinished_progress() {
+			return;
+		}
+		let is_problem = matches!(
+			message.contents,
+			MessageContents::Warning(..) | MessageContents::Error(..)
+		);
+		self.display_text_impl(
+			self.format_message(message.contents),
+			message.level,
+			is_problem,
+		);
+	}
+
+	fn display_update_phase(&mut self, phase: UpdatePhase, event: UpdatePhaseEvent) {
+		// The text output already shows the messages for each phase
+		if self.is_json() && !event.is_unfinished_progress() {
+			self.display_json(
+				JsonMessageContents::UpdatePhase { phase, event },
+				MessageLevel::Important,
+			);
+		}
 	}
 
 	fn start_process(&mut self) {
+		if self.is_json() {
+			return;
+		}
 		if self.in_process {
-			self.printer.newline();
+			if !self.is_quiet() {
+				self.printer.newline();
+			}
 		} else {
 			self.in_process = true;
 		}
 	}
 
 	fn end_process(&mut self) {
-		if self.in_process {
+		if self.is_json() {
+			return;
+		}
+		if self.in_process && !self.is_quiet() {
 			self.printer.newline();
 		}
 		self.in_process = false;
@@ -129,23 +215,79 @@ impl TerminalOutput {
 			.context("Failed to open latest.txt log file")?;
 		Ok(Self {
 			printer: ReplPrinter::new(true),
-			level: MessageLevel::Important,
+			level: MessageLevel::Extra,
 			in_process: false,
 			indent_level: 0,
 			log_file: file,
 			latest_log_file: latest_file,
 			translation_map: None,
+			is_terminal: std::io::stdout().is_terminal(),
+			format: OutputFormat::Text,
 		})
 	}
 
-	/// Display text
-	fn display_text_impl(&mut self, text: String, level: MessageLevel) {
+	/// Set the format of the output
+	pub fn set_format(&mut self, format: OutputFormat) {
+		self.format = format;
+	}
+
+	/// Whether the output is in the JSON format
+	pub fn is_json(&self) -> bool {
+		self.format == OutputFormat::Json
+	}
+
+	/// Print text from a command that isn't a message, like the entries of a list. In the JSON
+	/// format, it is written to stderr instead so that stdout only contains JSON
+	pub fn print(&self, text: impl Display) {
+		if self.is_json() {
+			eprint!("{text}");
+		} else {
+			print!("{text}");
+		}
+	}
+
+	/// Print a line of text from a command that isn't a message, in the same way as `print`
+	pub fn println(&self, text: impl Display) {
+		if self.is_json() {
+			eprintln!("{text}");
+		} else {
+			println!("{text}");
+		}
+	}
+
+	/// Write a message as a line of JSON
+	fn display_json(&mut self, contents: JsonMessageContents, level: MessageLevel) {
+		if !level.at_least(&self.level) {
+			return;
+		}
+
+		let message = JsonMessage { level, contents };
+		if let Ok(text) = serde_json::to_string(&message) {
+			println!("{text}");
+		}
+	}
+
+	/// Whether only important messages are shown
+	pub fn is_quiet(&self) -> bool {
+		matches!(self.level, MessageLevel::Important)
+	}
+
+	/// Display text. Persistent text is kept on its own line even in quiet mode
+	fn display_text_impl(&mut self, text: String, level: MessageLevel, persistent: bool) {
 		if !level.at_least(&self.level) {
 			return;
 		}
 
 		if self.in_process {
-			self.printer.print(&text);
+			// The lines of a process replace each other, so they are hidden when quiet
+			if self.is_quiet() {
+				if persistent {
+					self.printer.print(&text);
+					self.printer.newline();
+				}
+			} else {
+				self.printer.print(&text);
+			}
 		} else {
 			self.printer.print(&text);
 			self.printer.newline();
@@ -312,7 +454,11 @@ struct ProgressBarSettings {
 
 /// Creates a nice looking progress bar and returns the full and empty parts
 fn progress_bar_parts(current: u32, total: u32, settings: ProgressBarSettings) -> (String, String) {
-	let progress = (current as f32) / (total as f32);
+	let progress = if total == 0 {
+		1.0
+	} else {
+		((current as f32) / (total as f32)).min(1.0)
+	};
 	let full_count = (progress * (settings.len as f32)) as u8;
 	let empty_count = settings.len - full_count;
 	let mut full_bar = settings.full.repeat(full_count.into());
@@ -335,6 +481,14 @@ fn add_period(string: String) -> String {
 	}
 }
 
+/// Print the result of a command as a single JSON document
+pub fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+	let text = serde_json::to_string_pretty(value).context("Failed to serialize output")?;
+	println!("{text}");
+
+	Ok(())
+}
+
 /// Get whether icons are enabled
 pub fn icons_enabled() -> bool {
 	let out = std::env::var("MCVM_CLI_ICONS").unwrap_or("0".into());
@@ -1,15 +0,0 @@
-use mcvm::core::auth_crate::mc::ClientId;
-
-/// Get the Microsoft client ID
-pub fn get_ms_client_id() -> ClientId {
-	ClientId::new(get_raw_ms_client_id().to_string())
-}
-
-const fn get_raw_ms_client_id() -> &'static str {
-	if let Some(id) = option_env!("MCVM_MS_CLIENT_ID") {
-		id
-	} else {
-		// Please don't use my client ID :)
-		"402abc71-43fb-45c1-b230-e7fc9d4485fe"
-	}
-}
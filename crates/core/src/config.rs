@@ -1,5 +1,7 @@
 use mcvm_auth::mc::ClientId;
 
+use crate::net::download::get_transfer_limit;
+use crate::net::game_files::mirror::Mirrors;
 use crate::util::secrets::get_ms_client_id;
 
 macro_rules! builder_method {
@@ -20,6 +22,8 @@ pub struct Configuration {
 	pub(crate) force_reinstall: bool,
 	/// Whether to allow offline installs
 	pub(crate) allow_offline: bool,
+	/// Whether to check the hashes of existing files to find ones that need to be updated
+	pub(crate) verify_hashes: bool,
 	/// Whether to censor user credentials in output messages and logs
 	pub(crate) censor_secrets: bool,
 	/// Whether to use file copies instead of hardlinks. Useful if you
@@ -27,6 +31,16 @@ pub struct Configuration {
 	pub(crate) disable_hardlinks: bool,
 	/// Launcher branding
 	pub(crate) branding: BrandingProperties,
+	/// A proxy URL to send requests through, overriding the proxy environment variables
+	pub(crate) proxy: Option<String>,
+	/// A custom User-Agent to send with requests instead of the default one
+	pub(crate) user_agent: Option<String>,
+	/// The maximum number of files to download at the same time
+	pub(crate) download_concurrency: usize,
+	/// Mirrors to download game files from instead of the official servers
+	pub(crate) mirrors: Mirrors,
+	/// A version of the game that is expected to be installed already
+	pub(crate) installed_version: Option<String>,
 }
 
 impl Default for Configuration {
@@ -42,9 +56,15 @@ impl Configuration {
 			ms_client_id: get_ms_client_id(),
 			force_reinstall: false,
 			allow_offline: false,
+			verify_hashes: false,
 			censor_secrets: true,
 			disable_hardlinks: false,
 			branding: BrandingProperties::default(),
+			proxy: None,
+			user_agent: None,
+			download_concurrency: get_transfer_limit(),
+			mirrors: Mirrors::default(),
+			installed_version: None,
 		}
 	}
 
@@ -86,6 +106,12 @@ impl ConfigBuilder {
 
 	builder_method!(allow_offline, bool, "Set whether to allow offline installs");
 
+	builder_method!(
+		verify_hashes,
+		bool,
+		"Set whether to check the hashes of existing files when updating. Forced updates always check them"
+	);
+
 	builder_method!(
 		censor_secrets,
 		bool,
@@ -99,6 +125,36 @@ impl ConfigBuilder {
 	);
 
 	builder_method!(branding, BrandingProperties, "Set the branding properties");
+
+	builder_method!(
+		proxy,
+		Option<String>,
+		"Set a proxy URL to send requests through"
+	);
+
+	builder_method!(
+		user_agent,
+		Option<String>,
+		"Set a custom User-Agent to send with requests"
+	);
+
+	builder_method!(
+		download_concurrency,
+		usize,
+		"Set the maximum number of files to download at the same time. Must be greater than zero"
+	);
+
+	builder_method!(
+		mirrors,
+		Mirrors,
+		"Set mirrors to download game files from instead of the official servers"
+	);
+
+	builder_method!(
+		installed_version,
+		Option<String>,
+		"Set a version that is expected to be installed already. When offline installs are allowed and its files are present, the cached version manifest is used instead of downloading it again"
+	);
 }
 
 impl Default for ConfigBuilder {
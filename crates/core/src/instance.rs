@@ -1,7 +1,10 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context};
-use mcvm_shared::output::MCVMOutput;
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent,
+};
+use mcvm_shared::translate;
 use mcvm_shared::Side;
 
 use crate::config::BrandingProperties;
@@ -11,10 +14,10 @@ use crate::io::java::classpath::Classpath;
 use crate::io::java::install::{JavaInstallParameters, JavaInstallation};
 use crate::io::persistent::PersistentData;
 use crate::io::update::UpdateManager;
-use crate::launch::{LaunchConfiguration, LaunchParameters};
+use crate::launch::{should_use_log_config, LaunchConfiguration, LaunchError, LaunchParameters};
 use crate::net::game_files::client_meta::ClientMeta;
 use crate::net::game_files::version_manifest::VersionManifestAndList;
-use crate::net::game_files::{game_jar, libraries};
+use crate::net::game_files::{game_jar, libraries, log_config};
 use crate::user::UserManager;
 use crate::util::versions::VersionName;
 use crate::version::{ClientAssetsAndLibraries, ClientAssetsAndLibsParameters};
@@ -48,17 +51,42 @@ impl<'params> Instance<'params> {
 		}
 
 		// Install Java
-		let java_vers = &params.client_meta.java_info.major_version;
+		let java_info = &params.client_meta.java_info;
+		let java_vers = &config
+			.launch
+			.java_version
+			.unwrap_or(java_info.major_version);
+		if java_vers.0 < java_info.major_version.0 {
+			o.display(
+				MessageContents::Warning(translate!(
+					o,
+					JavaVersionOverrideTooLow,
+					"version" = &java_vers.to_string(),
+					"required" = &java_info.major_version.to_string()
+				)),
+				MessageLevel::Important,
+			);
+		}
+		// The runtime component from the game only applies to the major version it asks for
+		let component = if *java_vers == java_info.major_version {
+			java_info.component.as_deref()
+		} else {
+			None
+		};
 		let java_params = JavaInstallParameters {
 			paths: params.paths,
 			update_manager: params.update_manager,
 			persistent: params.persistent,
 			req_client: params.req_client,
+			component,
+			arch: config.launch.get_java_arch(),
 		};
+		o.display_update_phase(UpdatePhase::Java, UpdatePhaseEvent::Start);
 		let java =
 			JavaInstallation::install(config.launch.java.clone(), *java_vers, java_params, o)
 				.await
 				.context("Failed to install or update Java")?;
+		o.display_update_phase(UpdatePhase::Java, UpdatePhaseEvent::End);
 
 		let is_valid = java
 			.verify()
@@ -67,12 +95,22 @@ impl<'params> Instance<'params> {
 			bail!("Java installation is invalid");
 		}
 
+		let java_usage_key = config
+			.id
+			.clone()
+			.unwrap_or_else(|| config.path.to_string_lossy().to_string());
+		params.persistent.update_instance_java(
+			&java_usage_key,
+			java.get_managed_installation(),
+			&java.get_managed_key(),
+		);
 		params.persistent.dump(params.paths).await?;
 
 		// Get the game jar
 		let mut jar_path = if let Some(jar_path) = &config.jar_path {
 			jar_path.clone()
 		} else {
+			o.display_update_phase(UpdatePhase::GameJar, UpdatePhaseEvent::Start);
 			game_jar::get(
 				config.side.get_side(),
 				params.client_meta,
@@ -84,6 +122,7 @@ impl<'params> Instance<'params> {
 			)
 			.await
 			.context("Failed to get the game JAR file")?;
+			o.display_update_phase(UpdatePhase::GameJar, UpdatePhaseEvent::End);
 
 			crate::io::minecraft::game_jar::get_path(
 				config.side.get_side(),
@@ -102,7 +141,10 @@ impl<'params> Instance<'params> {
 			// Don't hardlink if it's already in the right place
 			if new_jar_path != jar_path {
 				// Update the hardlink
-				if params.update_manager.should_update_file(&new_jar_path) {
+				if params
+					.update_manager
+					.should_update_file(&new_jar_path, None)
+				{
 					if new_jar_path.exists() {
 						tokio::fs::remove_file(&new_jar_path)
 							.await
@@ -133,21 +175,38 @@ impl<'params> Instance<'params> {
 				version: params.version,
 				paths: params.paths,
 				req_client: params.req_client,
-				version_manifest: params.version_manifest,
 				update_manager: params.update_manager,
+				arch: config.launch.get_java_arch(),
 			};
 			params
 				.client_assets_and_libs
 				.load(sub_params, o)
 				.await
 				.context("Failed to load client assets and libraries")?;
+
+			let version_list = &params.version_manifest.list;
+			if should_use_log_config(&config.launch, params.version, version_list) {
+				log_config::get(
+					params.client_meta,
+					params.version,
+					params.paths,
+					params.update_manager,
+					params.req_client,
+				)
+				.await
+				.context("Failed to get the logging config")?;
+			}
 		}
 
 		// Classpath
 		let mut classpath = Classpath::new();
 		if let Side::Client = config.side.get_side() {
-			let lib_classpath = libraries::get_classpath(params.client_meta, params.paths)
-				.context("Failed to extract classpath from game library list")?;
+			let lib_classpath = libraries::get_classpath(
+				params.client_meta,
+				params.paths,
+				config.launch.get_java_arch(),
+			)
+			.context("Failed to extract classpath from game library list")?;
 			classpath.extend(lib_classpath);
 		}
 		for lib in &config.additional_libs {
@@ -187,12 +246,17 @@ impl<'params> Instance<'params> {
 		})
 	}
 
-	/// Launch the instance and block until the process is finished
-	pub async fn launch(&mut self, o: &mut impl MCVMOutput) -> anyhow::Result<()> {
+	/// Launch the instance and block until the process is finished.
+	/// Returns an error if the game exits with a non-zero exit code
+	pub async fn launch(&mut self, o: &mut impl MCVMOutput) -> Result<(), LaunchError> {
 		let mut handle = self.launch_with_handle(o).await?;
-		handle
+		let status = handle
 			.wait()
 			.context("Failed to wait for instance process")?;
+		if !status.success() {
+			return Err(LaunchError::NonZeroExit(status.code().unwrap_or(-1)));
+		}
+
 		Ok(())
 	}
 
@@ -200,7 +264,13 @@ impl<'params> Instance<'params> {
 	pub async fn launch_with_handle(
 		&mut self,
 		o: &mut impl MCVMOutput,
-	) -> anyhow::Result<InstanceHandle> {
+	) -> Result<InstanceHandle, LaunchError> {
+		if !self.jar_path.exists() {
+			return Err(LaunchError::VersionNotInstalled(
+				self.params.version.to_string(),
+			));
+		}
+
 		let params = LaunchParameters {
 			version: self.params.version,
 			version_manifest: self.params.version_manifest,
@@ -217,10 +287,7 @@ impl<'params> Instance<'params> {
 			censor_secrets: self.params.censor_secrets,
 			branding: self.params.branding,
 		};
-		let handle = crate::launch::launch(params, o)
-			.await
-			.context("Failed to run launch routine")?;
-		Ok(handle)
+		crate::launch::launch(params, o).await
 	}
 
 	/// Get the JAR path of the instance
@@ -235,6 +302,9 @@ pub struct InstanceConfiguration {
 	pub side: InstanceKind,
 	/// The directory where the instance will be stored and launched from
 	pub path: PathBuf,
+	/// A unique ID for the instance, used to remember which managed Java installation it uses.
+	/// If this is not set, the path is used instead
+	pub id: Option<String>,
 	/// Launch options for the instance
 	pub launch: LaunchConfiguration,
 	/// JAR path override. If this is set, the default JAR file will not be downloaded
@@ -253,6 +323,7 @@ impl InstanceConfiguration {
 		Self {
 			side,
 			path,
+			id: None,
 			launch: LaunchConfiguration::new(),
 			jar_path: None,
 			main_class: None,
@@ -279,6 +350,12 @@ impl InstanceConfigBuilder {
 		self.config
 	}
 
+	/// Set the unique ID of the instance
+	pub fn id(mut self, id: String) -> Self {
+		self.config.id = Some(id);
+		self
+	}
+
 	/// Set the launch options for the instance
 	pub fn launch_config(mut self, launch_config: LaunchConfiguration) -> Self {
 		self.config.launch = launch_config;
@@ -14,6 +14,9 @@ pub struct Paths {
 	pub data: PathBuf,
 	/// Holds internal data
 	pub internal: PathBuf,
+	/// Holds the directories of instances. This isn't used by core itself,
+	/// but is passed on to plugins
+	pub instances: PathBuf,
 	/// Holds game assets
 	pub assets: PathBuf,
 	/// Holds game libraries
@@ -30,6 +33,8 @@ pub struct Paths {
 	pub launch_logs: PathBuf,
 	/// Used for runtime info like PIDs
 	pub run: PathBuf,
+	/// Holds cached responses from web APIs
+	pub api_cache: PathBuf,
 }
 
 impl Paths {
@@ -49,12 +54,14 @@ impl Paths {
 		std::fs::create_dir_all(self.project.config_dir())?;
 		std::fs::create_dir_all(&self.internal)?;
 		std::fs::create_dir_all(&self.assets)?;
+		std::fs::create_dir_all(&self.libraries)?;
 		std::fs::create_dir_all(&self.java)?;
 		std::fs::create_dir_all(&self.jars)?;
 		std::fs::create_dir_all(&self.auth)?;
 		std::fs::create_dir_all(&self.logs)?;
 		std::fs::create_dir_all(&self.launch_logs)?;
 		std::fs::create_dir_all(&self.run)?;
+		std::fs::create_dir_all(&self.api_cache)?;
 		Ok(())
 	}
 
@@ -66,6 +73,7 @@ impl Paths {
 
 		let data = project.data_dir().to_owned();
 		let internal = data.join("internal");
+		let instances = data.join("instances");
 		let assets = internal.join("assets");
 		let libraries = internal.join("libraries");
 		let java = internal.join("java");
@@ -73,16 +81,19 @@ impl Paths {
 		let auth = internal.join("auth");
 		let logs = data.join("logs");
 		let launch_logs = logs.join("launch");
+		// Not every platform has a runtime directory, so fall back to one in our data
 		let run = project
 			.runtime_dir()
 			.map(|x| x.to_path_buf())
-			.unwrap_or(internal.join("run"));
+			.unwrap_or_else(|| internal.join("run"));
+		let api_cache = project.cache_dir().join("api");
 
 		Ok(Paths {
 			base,
 			project,
 			data,
 			internal,
+			instances,
 			assets,
 			libraries,
 			java,
@@ -91,6 +102,7 @@ impl Paths {
 			logs,
 			launch_logs,
 			run,
+			api_cache,
 		})
 	}
 }
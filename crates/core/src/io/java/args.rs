@@ -25,18 +25,35 @@ impl MemoryNum {
 	}
 
 	/// Converts into the equivalent amount in bytes
-	pub fn to_bytes(&self) -> u32 {
+	pub fn to_bytes(&self) -> u64 {
 		match self {
-			Self::B(n) => *n,
-			Self::Kb(n) => *n * 1024,
-			Self::Mb(n) => *n * 1024 * 1024,
-			Self::Gb(n) => *n * 1024 * 1024 * 1024,
+			Self::B(n) => *n as u64,
+			Self::Kb(n) => *n as u64 * 1024,
+			Self::Mb(n) => *n as u64 * 1024 * 1024,
+			Self::Gb(n) => *n as u64 * 1024 * 1024 * 1024,
+		}
+	}
+
+	/// Creates a MemoryNum from a number of bytes, using the largest unit that represents it exactly
+	pub fn from_bytes(bytes: u64) -> Self {
+		const KB: u64 = 1024;
+		const MB: u64 = KB * 1024;
+		const GB: u64 = MB * 1024;
+
+		if bytes != 0 && bytes % GB == 0 {
+			Self::Gb((bytes / GB) as u32)
+		} else if bytes != 0 && bytes % MB == 0 {
+			Self::Mb((bytes / MB) as u32)
+		} else if bytes != 0 && bytes % KB == 0 {
+			Self::Kb((bytes / KB) as u32)
+		} else {
+			Self::B(bytes.min(u32::MAX as u64) as u32)
 		}
 	}
 
 	/// Averages two amounts of memory
 	pub fn avg(left: Self, right: Self) -> Self {
-		Self::B((left.to_bytes() + right.to_bytes()) / 2)
+		Self::from_bytes((left.to_bytes() + right.to_bytes()) / 2)
 	}
 }
 
@@ -75,6 +92,65 @@ impl MemoryArg {
 	}
 }
 
+/// Presets for JVM arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgsPreset {
+	/// No preset
+	None,
+	/// Aikar's G1GC tuning flags
+	Aikar,
+}
+
+impl ArgsPreset {
+	/// Parse a string into an ArgsPreset
+	pub fn parse(string: &str) -> Option<Self> {
+		match string {
+			"none" => Some(Self::None),
+			"aikars" | "akairs" => Some(Self::Aikar),
+			_ => None,
+		}
+	}
+
+	/// Generate the JVM arguments for this preset, using the maximum heap size if it is set
+	pub fn generate_args(&self, max_mem: Option<&MemoryNum>) -> Vec<String> {
+		match self {
+			Self::None => Vec::new(),
+			Self::Aikar => {
+				// Use the values for large heaps when more than 12G is allocated
+				let large_heap = max_mem.is_some_and(|x| x.to_bytes() > 12 * 1024 * 1024 * 1024);
+				let (new_size, max_new_size, region_size, reserve, heap_occupancy) = if large_heap {
+					(40, 50, "16M", 15, 20)
+				} else {
+					(30, 40, "8M", 20, 15)
+				};
+
+				vec![
+					"-XX:+UseG1GC".into(),
+					"-XX:+ParallelRefProcEnabled".into(),
+					"-XX:MaxGCPauseMillis=200".into(),
+					"-XX:+UnlockExperimentalVMOptions".into(),
+					"-XX:+DisableExplicitGC".into(),
+					"-XX:+AlwaysPreTouch".into(),
+					format!("-XX:G1NewSizePercent={new_size}"),
+					format!("-XX:G1MaxNewSizePercent={max_new_size}"),
+					format!("-XX:G1HeapRegionSize={region_size}"),
+					format!("-XX:G1ReservePercent={reserve}"),
+					"-XX:G1HeapWastePercent=5".into(),
+					"-XX:G1MixedGCCountTarget=4".into(),
+					format!("-XX:InitiatingHeapOccupancyPercent={heap_occupancy}"),
+					"-XX:G1MixedGCLiveThresholdPercent=90".into(),
+					"-XX:G1RSetUpdatingPauseTimePercent=5".into(),
+					"-XX:SurvivorRatio=32".into(),
+					"-XX:+PerfDisableSharedMem".into(),
+					"-XX:MaxTenuringThreshold=1".into(),
+					"-Dusing.aikars.flags=https://mcflags.emc.gs".into(),
+					"-Daikars.new.flags=true".into(),
+				]
+			}
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -89,6 +165,17 @@ mod tests {
 		assert_eq!(MemoryNum::parse(""), None);
 	}
 
+	#[test]
+	fn test_mem_from_bytes() {
+		assert_eq!(MemoryNum::from_bytes(2358), MemoryNum::B(2358));
+		assert_eq!(MemoryNum::from_bytes(4096), MemoryNum::Kb(4));
+		assert_eq!(MemoryNum::from_bytes(512 * 1024 * 1024), MemoryNum::Mb(512));
+		assert_eq!(
+			MemoryNum::from_bytes(8 * 1024 * 1024 * 1024),
+			MemoryNum::Gb(8)
+		);
+	}
+
 	#[test]
 	fn test_mem_arg_output() {
 		assert_eq!(
@@ -100,4 +187,41 @@ mod tests {
 			"-Xms128".to_string()
 		);
 	}
+
+	#[test]
+	fn test_aikar_preset() {
+		assert_eq!(ArgsPreset::parse("aikars"), Some(ArgsPreset::Aikar));
+		assert_eq!(ArgsPreset::parse("foo"), None);
+
+		let args = ArgsPreset::Aikar.generate_args(Some(&MemoryNum::Gb(10)));
+		assert_eq!(
+			args,
+			vec![
+				"-XX:+UseG1GC",
+				"-XX:+ParallelRefProcEnabled",
+				"-XX:MaxGCPauseMillis=200",
+				"-XX:+UnlockExperimentalVMOptions",
+				"-XX:+DisableExplicitGC",
+				"-XX:+AlwaysPreTouch",
+				"-XX:G1NewSizePercent=30",
+				"-XX:G1MaxNewSizePercent=40",
+				"-XX:G1HeapRegionSize=8M",
+				"-XX:G1ReservePercent=20",
+				"-XX:G1HeapWastePercent=5",
+				"-XX:G1MixedGCCountTarget=4",
+				"-XX:InitiatingHeapOccupancyPercent=15",
+				"-XX:G1MixedGCLiveThresholdPercent=90",
+				"-XX:G1RSetUpdatingPauseTimePercent=5",
+				"-XX:SurvivorRatio=32",
+				"-XX:+PerfDisableSharedMem",
+				"-XX:MaxTenuringThreshold=1",
+				"-Dusing.aikars.flags=https://mcflags.emc.gs",
+				"-Daikars.new.flags=true",
+			]
+		);
+
+		let args = ArgsPreset::Aikar.generate_args(Some(&MemoryNum::Gb(16)));
+		assert!(args.contains(&"-XX:G1HeapRegionSize=16M".to_string()));
+		assert!(args.contains(&"-XX:G1NewSizePercent=40".to_string()));
+	}
 }
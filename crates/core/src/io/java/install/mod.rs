@@ -1,24 +1,29 @@
 /// System Java installation
 mod system;
 
+use std::fmt::Display;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek};
 
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::PermissionsExt;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{bail, Context};
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm_shared::translate;
 use tar::Archive;
+use tokio::{sync::Semaphore, task::JoinSet};
 use zip::ZipArchive;
 
 use crate::io::files::{self, paths::Paths};
 use crate::io::persistent::{PersistentData, PersistentDataJavaInstallation};
 use crate::io::update::UpdateManager;
+use crate::net::game_files::verify;
+use crate::net::java::mojang::FileEntry;
 use crate::net::{self, download};
-use mcvm_shared::util::preferred_archive_extension;
+use mcvm_shared::util::{preferred_archive_extension, ARCH_STRING};
 
 use super::JavaMajorVersion;
 
@@ -37,6 +42,8 @@ pub enum JavaInstallationKind {
 	Zulu,
 	/// GraalVM
 	GraalVM,
+	/// The runtime that Mojang distributes for the game version
+	Mojang,
 	/// A user-specified installation
 	Custom {
 		/// The path to the installation. The JVM must live at
@@ -45,6 +52,20 @@ pub enum JavaInstallationKind {
 	},
 }
 
+impl Display for JavaInstallationKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Auto => write!(f, "auto"),
+			Self::System => write!(f, "system"),
+			Self::Adoptium => write!(f, "adoptium"),
+			Self::Zulu => write!(f, "zulu"),
+			Self::GraalVM => write!(f, "graalvm"),
+			Self::Mojang => write!(f, "mojang"),
+			Self::Custom { path } => write!(f, "{}", path.display()),
+		}
+	}
+}
+
 impl JavaInstallationKind {
 	/// Parse a string into a JavaKind
 	pub fn parse(string: &str) -> Self {
@@ -54,11 +75,69 @@ impl JavaInstallationKind {
 			"adoptium" => Self::Adoptium,
 			"zulu" => Self::Zulu,
 			"graalvm" => Self::GraalVM,
+			"mojang" => Self::Mojang,
 			path => Self::Custom {
 				path: PathBuf::from(path),
 			},
 		}
 	}
+
+	/// Get the managed installation that would have to be downloaded to use this kind of Java
+	/// with a major version, or None if nothing would be downloaded. This does not check for
+	/// updates to installations that are already present. The component is the Mojang runtime
+	/// component that the game version asks for, if any, and the arch is the architecture to install for
+	pub fn get_missing_installation(
+		&self,
+		major_version: &JavaMajorVersion,
+		component: Option<&str>,
+		arch: &str,
+		persistent: &PersistentData,
+	) -> Option<PersistentDataJavaInstallation> {
+		let vers_str = major_version.to_string();
+		let key = net::java::get_arch_key(&vers_str, arch);
+		let is_missing = |installation| persistent.get_java_path(installation, &key).is_none();
+		match self {
+			Self::Auto => {
+				let is_native_arch = arch == ARCH_STRING;
+				let managed = if is_native_arch {
+					vec![
+						PersistentDataJavaInstallation::Adoptium,
+						PersistentDataJavaInstallation::GraalVM,
+						PersistentDataJavaInstallation::Zulu,
+					]
+				} else {
+					vec![
+						PersistentDataJavaInstallation::Adoptium,
+						PersistentDataJavaInstallation::Zulu,
+					]
+				};
+				if (is_native_arch && system::install(&vers_str).is_ok())
+					|| !managed.into_iter().all(is_missing)
+				{
+					None
+				} else {
+					Some(PersistentDataJavaInstallation::Adoptium)
+				}
+			}
+			Self::Adoptium => {
+				Some(PersistentDataJavaInstallation::Adoptium).filter(|x| is_missing(*x))
+			}
+			Self::Zulu => Some(PersistentDataJavaInstallation::Zulu).filter(|x| is_missing(*x)),
+			Self::GraalVM => {
+				Some(PersistentDataJavaInstallation::GraalVM).filter(|x| is_missing(*x))
+			}
+			Self::Mojang => {
+				let component = get_mojang_component(component, &vers_str).ok();
+				let is_missing = component.map_or(true, |component| {
+					persistent
+						.get_java_path(PersistentDataJavaInstallation::Mojang, &component)
+						.is_none()
+				});
+				Some(PersistentDataJavaInstallation::Mojang).filter(|_| is_missing)
+			}
+			Self::System | Self::Custom { .. } => None,
+		}
+	}
 }
 
 /// A Java installation used to launch the game
@@ -68,6 +147,10 @@ pub struct JavaInstallation {
 	major_version: JavaMajorVersion,
 	/// The path to the directory where the installation is, which will be filled when it is installed
 	path: PathBuf,
+	/// The managed installation flavor that this installation comes from, if any
+	managed: Option<PersistentDataJavaInstallation>,
+	/// The key that the managed installation is stored under in the persistent data
+	managed_key: String,
 }
 
 impl JavaInstallation {
@@ -85,13 +168,26 @@ impl JavaInstallation {
 		);
 
 		let vers_str = major_version.to_string();
+		let is_native_arch = params.arch == ARCH_STRING;
+		if !is_native_arch
+			&& matches!(
+				kind,
+				JavaInstallationKind::GraalVM | JavaInstallationKind::Mojang
+			) {
+			bail!(
+				"{kind} Java can't be installed for the {} architecture. Use Adoptium or Zulu instead",
+				params.arch
+			);
+		}
+		let arch_key = net::java::get_arch_key(&vers_str, params.arch);
 
 		let path = match &kind {
-			JavaInstallationKind::Auto => install_auto(&vers_str, params, o).await?,
+			JavaInstallationKind::Auto => install_auto(&vers_str, &mut params, o).await?,
 			JavaInstallationKind::System => system::install(&vers_str)?,
 			JavaInstallationKind::Adoptium => install_adoptium(&vers_str, &mut params, o).await?,
 			JavaInstallationKind::Zulu => install_zulu(&vers_str, &mut params, o).await?,
 			JavaInstallationKind::GraalVM => install_graalvm(&vers_str, &mut params, o).await?,
+			JavaInstallationKind::Mojang => install_mojang(&vers_str, &mut params, o).await?,
 			JavaInstallationKind::Custom { path } => path.clone(),
 		};
 
@@ -102,9 +198,33 @@ impl JavaInstallation {
 
 		o.end_process();
 
+		let managed = match &kind {
+			JavaInstallationKind::Adoptium => Some(PersistentDataJavaInstallation::Adoptium),
+			JavaInstallationKind::Zulu => Some(PersistentDataJavaInstallation::Zulu),
+			JavaInstallationKind::GraalVM => Some(PersistentDataJavaInstallation::GraalVM),
+			JavaInstallationKind::Mojang => Some(PersistentDataJavaInstallation::Mojang),
+			// Figure out which flavor was picked, if any
+			JavaInstallationKind::Auto => [
+				PersistentDataJavaInstallation::Adoptium,
+				PersistentDataJavaInstallation::GraalVM,
+				PersistentDataJavaInstallation::Zulu,
+			]
+			.into_iter()
+			.find(|x| params.persistent.get_java_path(*x, &arch_key).as_ref() == Some(&path)),
+			JavaInstallationKind::System | JavaInstallationKind::Custom { .. } => None,
+		};
+
+		let managed_key = if let JavaInstallationKind::Mojang = &kind {
+			get_mojang_component(params.component, &vers_str)?
+		} else {
+			arch_key
+		};
+
 		let out = Self {
 			major_version,
 			path,
+			managed,
+			managed_key,
 		};
 
 		Ok(out)
@@ -120,6 +240,18 @@ impl JavaInstallation {
 		&self.path
 	}
 
+	/// Get the managed installation flavor that this installation comes from, if any
+	pub fn get_managed_installation(&self) -> Option<PersistentDataJavaInstallation> {
+		self.managed
+	}
+
+	/// Get the key that the managed installation is stored under in the persistent data.
+	/// This is the runtime component for Mojang installations and the major version, along with the
+	/// architecture if it isn't the native one, otherwise
+	pub(crate) fn get_managed_key(&self) -> String {
+		self.managed_key.clone()
+	}
+
 	/// Get the path to the JVM.
 	pub fn get_jvm_path(&self) -> PathBuf {
 		#[cfg(target_family = "windows")]
@@ -158,26 +290,36 @@ pub(crate) struct JavaInstallParameters<'a> {
 	pub update_manager: &'a mut UpdateManager,
 	pub persistent: &'a mut PersistentData,
 	pub req_client: &'a reqwest::Client,
+	/// The Mojang runtime component that the game version asks for, if any
+	pub component: Option<&'a str>,
+	/// The architecture to install Java for
+	pub arch: &'a str,
 }
 
 async fn install_auto(
 	major_version: &str,
-	mut params: JavaInstallParameters<'_>,
+	params: &mut JavaInstallParameters<'_>,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<PathBuf> {
-	let out = system::install(major_version);
-	if let Ok(out) = out {
-		return Ok(out);
+	// The system installation and GraalVM can only be used for the native architecture
+	let is_native_arch = params.arch == ARCH_STRING;
+	if is_native_arch {
+		let out = system::install(major_version);
+		if let Ok(out) = out {
+			return Ok(out);
+		}
 	}
-	let out = install_adoptium(major_version, &mut params, o).await;
+	let out = install_adoptium(major_version, params, o).await;
 	if let Ok(out) = out {
 		return Ok(out);
 	}
-	let out = install_graalvm(major_version, &mut params, o).await;
-	if let Ok(out) = out {
-		return Ok(out);
+	if is_native_arch {
+		let out = install_graalvm(major_version, params, o).await;
+		if let Ok(out) = out {
+			return Ok(out);
+		}
 	}
-	let out = install_zulu(major_version, &mut params, o).await;
+	let out = install_zulu(major_version, params, o).await;
 	if let Ok(out) = out {
 		return Ok(out);
 	}
@@ -190,10 +332,10 @@ async fn install_adoptium(
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<PathBuf> {
 	if params.update_manager.allow_offline {
-		if let Some(directory) = params
-			.persistent
-			.get_java_path(PersistentDataJavaInstallation::Adoptium, major_version)
-		{
+		if let Some(directory) = params.persistent.get_java_path(
+			PersistentDataJavaInstallation::Adoptium,
+			&net::java::get_arch_key(major_version, params.arch),
+		) {
 			Ok(directory)
 		} else {
 			update_adoptium(major_version, params, o)
@@ -213,10 +355,10 @@ async fn install_zulu(
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<PathBuf> {
 	if params.update_manager.allow_offline {
-		if let Some(directory) = params
-			.persistent
-			.get_java_path(PersistentDataJavaInstallation::Zulu, major_version)
-		{
+		if let Some(directory) = params.persistent.get_java_path(
+			PersistentDataJavaInstallation::Zulu,
+			&net::java::get_arch_key(major_version, params.arch),
+		) {
 			Ok(directory)
 		} else {
 			update_zulu(major_version, params, o)
@@ -253,17 +395,72 @@ async fn install_graalvm(
 	}
 }
 
+async fn install_mojang(
+	major_version: &str,
+	params: &mut JavaInstallParameters<'_>,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<PathBuf> {
+	if params.update_manager.allow_offline {
+		let component = get_mojang_component(params.component, major_version)?;
+		if let Some(directory) = params
+			.persistent
+			.get_java_path(PersistentDataJavaInstallation::Mojang, &component)
+		{
+			return Ok(net::java::mojang::get_home_dir(&directory));
+		}
+	}
+	update_mojang(major_version, params, o)
+		.await
+		.context("Failed to update Mojang Java")
+}
+
+/// Get the Mojang runtime component to use, falling back to the default one for the major
+/// version when the game version doesn't specify one
+fn get_mojang_component(component: Option<&str>, major_version: &str) -> anyhow::Result<String> {
+	component
+		.or_else(|| net::java::mojang::default_component(major_version))
+		.map(String::from)
+		.with_context(|| format!("Mojang does not distribute a runtime for Java {major_version}"))
+}
+
+/// Get the directory to install a managed flavor of Java into.
+/// Installations for other architectures are kept separate from the native ones
+fn get_out_dir(flavor: &str, params: &JavaInstallParameters<'_>) -> PathBuf {
+	let dir = params.paths.java.join(flavor);
+	if params.arch == ARCH_STRING {
+		dir
+	} else {
+		dir.join(params.arch)
+	}
+}
+
+/// Get the max age for cached Java API responses. When offline installs are allowed,
+/// the cache will be used no matter how old it is
+fn get_api_cache_age(params: &JavaInstallParameters<'_>) -> Option<std::time::Duration> {
+	if params.update_manager.allow_offline {
+		None
+	} else {
+		Some(net::java::JAVA_API_CACHE_AGE)
+	}
+}
+
 /// Updates Adoptium and returns the path to the installation
 async fn update_adoptium(
 	major_version: &str,
 	params: &mut JavaInstallParameters<'_>,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<PathBuf> {
-	let out_dir = params.paths.java.join("adoptium");
-	files::create_dir(&out_dir)?;
-	let version = net::java::adoptium::get_latest(major_version, params.req_client)
-		.await
-		.context("Failed to obtain Adoptium information")?;
+	let out_dir = get_out_dir("adoptium", params);
+	std::fs::create_dir_all(&out_dir).context("Failed to create Java directory")?;
+	let version = net::java::adoptium::get_latest(
+		major_version,
+		params.arch,
+		get_api_cache_age(params),
+		params.paths,
+		params.req_client,
+	)
+	.await
+	.context("Failed to obtain Adoptium information")?;
 
 	let release_name = version.release_name.clone();
 	let mut extracted_bin_name = release_name.clone();
@@ -274,7 +471,7 @@ async fn update_adoptium(
 		.persistent
 		.update_java_installation(
 			PersistentDataJavaInstallation::Adoptium,
-			major_version,
+			&net::java::get_arch_key(major_version, params.arch),
 			&release_name,
 			&extracted_bin_dir,
 		)
@@ -299,21 +496,7 @@ async fn update_adoptium(
 		)),
 		MessageLevel::Important,
 	);
-	download::file(bin_url, &arc_path, params.req_client)
-		.await
-		.context("Failed to download JRE binaries")?;
-
-	// Extraction
-	o.display(
-		MessageContents::StartProcess(translate!(o, StartExtractingJava)),
-		MessageLevel::Important,
-	);
-	extract_archive_file(&arc_path, &out_dir).context("Failed to extract")?;
-	o.display(
-		MessageContents::StartProcess(translate!(o, StartRemovingJavaArchive)),
-		MessageLevel::Important,
-	);
-	std::fs::remove_file(arc_path).context("Failed to remove archive")?;
+	download_and_extract_archive(&bin_url, &arc_path, &out_dir, params.req_client, o).await?;
 
 	o.display(
 		MessageContents::Success(translate!(o, FinishJavaInstallation)),
@@ -329,12 +512,18 @@ async fn update_zulu(
 	params: &mut JavaInstallParameters<'_>,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<PathBuf> {
-	let out_dir = params.paths.java.join("zulu");
-	files::create_dir(&out_dir)?;
-
-	let package = net::java::zulu::get_latest(major_version, params.req_client)
-		.await
-		.context("Failed to get the latest Zulu version")?;
+	let out_dir = get_out_dir("zulu", params);
+	std::fs::create_dir_all(&out_dir).context("Failed to create Java directory")?;
+
+	let package = net::java::zulu::get_latest(
+		major_version,
+		params.arch,
+		get_api_cache_age(params),
+		params.paths,
+		params.req_client,
+	)
+	.await
+	.context("Failed to get the latest Zulu version")?;
 
 	let extracted_dir = out_dir.join(net::java::zulu::extract_dir_name(&package.name));
 
@@ -342,7 +531,7 @@ async fn update_zulu(
 		.persistent
 		.update_java_installation(
 			PersistentDataJavaInstallation::Zulu,
-			major_version,
+			&net::java::get_arch_key(major_version, params.arch),
 			&package.name,
 			&extracted_dir,
 		)
@@ -359,21 +548,14 @@ async fn update_zulu(
 		MessageContents::StartProcess(translate!(o, DownloadingZulu, "version" = &package.name)),
 		MessageLevel::Important,
 	);
-	download::file(&package.download_url, &arc_path, params.req_client)
-		.await
-		.context("Failed to download JRE binaries")?;
-
-	// Extraction
-	o.display(
-		MessageContents::StartProcess(translate!(o, StartExtractingJava)),
-		MessageLevel::Important,
-	);
-	extract_archive_file(&arc_path, &out_dir).context("Failed to extract")?;
-	o.display(
-		MessageContents::StartProcess(translate!(o, StartRemovingJavaArchive)),
-		MessageLevel::Important,
-	);
-	std::fs::remove_file(arc_path).context("Failed to remove archive")?;
+	download_and_extract_archive(
+		&package.download_url,
+		&arc_path,
+		&out_dir,
+		params.req_client,
+		o,
+	)
+	.await?;
 
 	o.display(
 		MessageContents::Success(translate!(o, FinishJavaInstallation)),
@@ -435,6 +617,186 @@ async fn update_graalvm(
 	Ok(extracted_dir)
 }
 
+/// Updates a Mojang runtime and returns the path to the installation
+async fn update_mojang(
+	major_version: &str,
+	params: &mut JavaInstallParameters<'_>,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<PathBuf> {
+	let component = get_mojang_component(params.component, major_version)?;
+	let out_dir = params.paths.java.join("mojang").join(&component);
+
+	let runtime = net::java::mojang::get_latest(
+		&component,
+		get_api_cache_age(params),
+		params.paths,
+		params.req_client,
+	)
+	.await
+	.context("Failed to get the latest Mojang runtime version")?;
+
+	let home_dir = net::java::mojang::get_home_dir(&out_dir);
+
+	if !params
+		.persistent
+		.update_java_installation(
+			PersistentDataJavaInstallation::Mojang,
+			&component,
+			&runtime.version.name,
+			&out_dir,
+		)
+		.context("Failed to update Java in lockfile")?
+	{
+		return Ok(home_dir);
+	}
+
+	params.persistent.dump(params.paths).await?;
+
+	o.display(
+		MessageContents::StartProcess(translate!(
+			o,
+			DownloadingMojangJava,
+			"component" = &component,
+			"version" = &runtime.version.name
+		)),
+		MessageLevel::Important,
+	);
+
+	let manifest = net::java::mojang::get_file_manifest(&runtime, params.req_client).await?;
+	download_mojang_files(manifest, &out_dir, params)
+		.await
+		.context("Failed to download runtime files")?;
+
+	o.display(
+		MessageContents::Success(translate!(o, FinishJavaInstallation)),
+		MessageLevel::Important,
+	);
+
+	Ok(home_dir)
+}
+
+/// Downloads all of the files in a Mojang runtime manifest into a directory
+async fn download_mojang_files(
+	manifest: net::java::mojang::FileManifest,
+	out_dir: &Path,
+	params: &JavaInstallParameters<'_>,
+) -> anyhow::Result<()> {
+	std::fs::create_dir_all(out_dir).context("Failed to create runtime directory")?;
+
+	let mut join = JoinSet::new();
+	// Used to limit the number of open file descriptors
+	let sem = Arc::new(Semaphore::new(params.update_manager.download_concurrency));
+	let mut links = Vec::new();
+	for (path, entry) in manifest.files {
+		let path = get_mojang_file_path(out_dir, &path)?;
+		match entry {
+			FileEntry::Directory => {
+				std::fs::create_dir_all(&path).context("Failed to create directory")?
+			}
+			FileEntry::Link { target } => links.push((path, target)),
+			FileEntry::File {
+				executable,
+				downloads,
+			} => {
+				let client = params.req_client.clone();
+				let sem = sem.clone();
+				join.spawn(async move {
+					let _permit = sem.acquire().await;
+					if !verify::is_file_intact(&path, &downloads.raw.sha1) {
+						files::create_leading_dirs_async(&path).await?;
+						let bytes = download::bytes(&downloads.raw.url, &client)
+							.await
+							.context("Failed to download file")?;
+						verify::verify_sha1(&bytes, &downloads.raw.sha1)
+							.with_context(|| format!("Failed to verify file {}", path.display()))?;
+						tokio::fs::write(&path, bytes)
+							.await
+							.context("Failed to write file")?;
+					}
+					#[cfg(target_family = "unix")]
+					if executable {
+						std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+							.context("Failed to make file executable")?;
+					}
+					#[cfg(not(target_family = "unix"))]
+					let _ = executable;
+					Ok::<(), anyhow::Error>(())
+				});
+			}
+		}
+	}
+
+	while let Some(result) = join.join_next().await {
+		result??;
+	}
+
+	// Links are only needed on Unix, where the runtimes use them for things like legal files
+	#[cfg(target_family = "unix")]
+	for (path, target) in links {
+		if path.symlink_metadata().is_ok() {
+			std::fs::remove_file(&path).context("Failed to remove existing link")?;
+		}
+		std::os::unix::fs::symlink(target, &path).context("Failed to create link")?;
+	}
+	#[cfg(not(target_family = "unix"))]
+	let _ = links;
+
+	Ok(())
+}
+
+/// Gets the path that a file from a Mojang runtime manifest is installed to. Paths that
+/// would escape the runtime directory are rejected
+fn get_mojang_file_path(out_dir: &Path, path: &str) -> anyhow::Result<PathBuf> {
+	let relative = Path::new(path);
+	let is_contained = relative
+		.components()
+		.all(|x| matches!(x, Component::Normal(..) | Component::CurDir));
+	if !is_contained {
+		bail!("Runtime file path '{path}' is outside of the runtime directory");
+	}
+
+	Ok(out_dir.join(relative))
+}
+
+/// Downloads the JRE archive and extracts it. On Windows the archive is a zip, which needs
+/// to be seekable, so it is downloaded to a file first. Otherwise, the tar.gz is extracted
+/// as it is downloaded so that the archive never has to be stored on disk
+async fn download_and_extract_archive(
+	url: &str,
+	arc_path: &Path,
+	out_dir: &Path,
+	client: &reqwest::Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<()> {
+	if cfg!(windows) {
+		download::file(url, arc_path, client)
+			.await
+			.context("Failed to download JRE binaries")?;
+
+		o.display(
+			MessageContents::StartProcess(translate!(o, StartExtractingJava)),
+			MessageLevel::Important,
+		);
+		let result = extract_archive_file(arc_path, out_dir).context("Failed to extract");
+		// Remove the archive even if extraction failed, as it is not used again
+		o.display(
+			MessageContents::StartProcess(translate!(o, StartRemovingJavaArchive)),
+			MessageLevel::Important,
+		);
+		std::fs::remove_file(arc_path).context("Failed to remove archive")?;
+		result?;
+	} else {
+		let out_dir = out_dir.to_owned();
+		download::stream(url, client, move |reader| {
+			extract_tar_gz(BufReader::new(reader), &out_dir)
+		})
+		.await
+		.context("Failed to download and extract JRE binaries")?;
+	}
+
+	Ok(())
+}
+
 /// Extracts the archive file
 fn extract_archive_file(arc_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
 	let file = File::open(arc_path).context("Failed to read archive file")?;
@@ -447,43 +809,72 @@ fn extract_archive_file(arc_path: &Path, out_dir: &Path) -> anyhow::Result<()> {
 
 /// Extracts the JRE archive (either a tar or a zip) and also returns the internal extraction directory name
 fn extract_archive<R: Read + Seek>(reader: R, out_dir: &Path) -> anyhow::Result<String> {
-	let dir_name = if cfg!(windows) {
-		let mut archive = ZipArchive::new(reader).context("Failed to open zip archive")?;
+	if cfg!(windows) {
+		extract_zip(reader, out_dir)
+	} else {
+		extract_tar_gz(reader, out_dir)
+	}
+}
 
-		let dir_name = archive
-			.file_names()
-			.next()
-			.context("Missing archive internal directory")?
-			.to_string();
+/// Extracts a zip JRE archive and returns the internal extraction directory name
+fn extract_zip<R: Read + Seek>(reader: R, out_dir: &Path) -> anyhow::Result<String> {
+	let mut archive = ZipArchive::new(reader).context("Failed to open zip archive")?;
 
-		archive
-			.extract(out_dir)
-			.context("Failed to extract zip file")?;
+	let dir_name = archive
+		.file_names()
+		.next()
+		.context("Missing archive internal directory")?
+		.to_string();
 
-		dir_name
-	} else {
-		let mut decoder =
-			libflate::gzip::Decoder::new(reader).context("Failed to decode tar.gz")?;
-		// Get the archive twice because of archive shenanigans
-		let mut arc = Archive::new(&mut decoder);
-
-		// Wow
-		let dir_name = arc
-			.entries()
-			.context("Failed to get Tar entries")?
-			.next()
-			.context("Missing archive internal directory")?
-			.context("Failed to get entry")?
-			.path()
-			.context("Failed to get entry path name")?
-			.to_string_lossy()
-			.to_string();
-
-		let mut arc = Archive::new(&mut decoder);
-		arc.unpack(out_dir).context("Failed to unarchive tar")?;
-
-		dir_name
-	};
+	archive
+		.extract(out_dir)
+		.context("Failed to extract zip file")?;
 
 	Ok(dir_name)
 }
+
+/// Extracts a tar.gz JRE archive and returns the internal extraction directory name.
+/// The archive is read in one pass, so the reader does not need to be seekable
+fn extract_tar_gz<R: Read>(reader: R, out_dir: &Path) -> anyhow::Result<String> {
+	let mut decoder = libflate::gzip::Decoder::new(reader).context("Failed to decode tar.gz")?;
+	// Get the archive twice because of archive shenanigans
+	let mut arc = Archive::new(&mut decoder);
+
+	// Wow
+	let dir_name = arc
+		.entries()
+		.context("Failed to get Tar entries")?
+		.next()
+		.context("Missing archive internal directory")?
+		.context("Failed to get entry")?
+		.path()
+		.context("Failed to get entry path name")?
+		.to_string_lossy()
+		.to_string();
+
+	let mut arc = Archive::new(&mut decoder);
+	arc.unpack(out_dir).context("Failed to unarchive tar")?;
+
+	Ok(dir_name)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_mojang_file_path() {
+		let out_dir = Path::new("runtime");
+		assert_eq!(
+			get_mojang_file_path(out_dir, "bin/java").unwrap(),
+			out_dir.join("bin").join("java")
+		);
+		assert_eq!(
+			get_mojang_file_path(out_dir, "legal/java.base/LICENSE").unwrap(),
+			out_dir.join("legal/java.base/LICENSE")
+		);
+		assert!(get_mojang_file_path(out_dir, "../bin/java").is_err());
+		assert!(get_mojang_file_path(out_dir, "bin/../../java").is_err());
+		assert!(get_mojang_file_path(out_dir, "/usr/bin/java").is_err());
+	}
+}
@@ -2,6 +2,7 @@ use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
+use directories::BaseDirs;
 
 /// Installs the system java installation
 pub fn install(major_version: &str) -> anyhow::Result<PathBuf> {
@@ -36,6 +37,9 @@ fn get_system_java_installation(#[allow(unused_variables)] major_version: &str)
 			return Some(path);
 		}
 	}
+	if let Some(path) = scan_home(major_version) {
+		return Some(path);
+	}
 
 	None
 }
@@ -66,15 +70,19 @@ fn scan_linux(major_version: &str) -> Option<PathBuf> {
 	// Flatpak
 	scan!(&PathBuf::from("/app/jdk"), major_version);
 
-	if let Ok(home) = std::env::var("HOME") {
-		let home = PathBuf::from(home);
-		// IntelliJ
-		scan!(&home.join(".jdks"), major_version);
-		// SDKMan
-		scan!(&home.join(".sdkman/candidates/java"), major_version);
-		// Gradle
-		scan!(&home.join(".gradle/jdks"), major_version);
-	}
+	None
+}
+
+/// Scan for Java installed by tools in the user's home directory
+fn scan_home(major_version: &str) -> Option<PathBuf> {
+	let base_dirs = BaseDirs::new()?;
+	let home = base_dirs.home_dir();
+	// IntelliJ
+	scan!(&home.join(".jdks"), major_version);
+	// SDKMan
+	scan!(&home.join(".sdkman/candidates/java"), major_version);
+	// Gradle
+	scan!(&home.join(".gradle/jdks"), major_version);
 
 	None
 }
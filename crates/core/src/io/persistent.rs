@@ -19,6 +19,41 @@ pub struct PersistentData {
 struct PersistentDataContents {
 	java: PersistentDataJava,
 	versions: HashMap<String, PersistentDataVersionInfo>,
+	/// Map of instance IDs to the managed Java installation they last used
+	instance_java: HashMap<String, PersistentDataInstanceJava>,
+	/// Map of instance IDs to the packages that the user allowed to run with elevated permissions
+	/// on them. These are kept here instead of the lockfile so that they can't come from a lockfile
+	/// that someone else made
+	elevated_approvals: HashMap<String, HashMap<String, PersistentDataElevatedApproval>>,
+}
+
+/// A record of the user allowing a package to run with elevated permissions. The approval
+/// only applies while the package script and the privileged operations stay the same
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct PersistentDataElevatedApproval {
+	/// The SHA-256 hash of the package script
+	script_hash: String,
+	/// The privileged operations of the package, sorted
+	operations: Vec<String>,
+}
+
+impl PersistentDataElevatedApproval {
+	/// Create a new PersistentDataElevatedApproval
+	fn new(script_hash: &str, operations: &[String]) -> Self {
+		let mut operations = operations.to_vec();
+		operations.sort();
+		Self {
+			script_hash: script_hash.into(),
+			operations,
+		}
+	}
+}
+
+/// The managed Java installation used by an instance
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct PersistentDataInstanceJava {
+	installation: PersistentDataJavaInstallation,
+	major_version: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -39,16 +74,22 @@ struct PersistentDataJava {
 	adoptium: HashMap<String, PersistentDataJavaVersion>,
 	zulu: HashMap<String, PersistentDataJavaVersion>,
 	graalvm: HashMap<String, PersistentDataJavaVersion>,
+	/// Mojang runtimes are keyed by their component name instead of the major version
+	mojang: HashMap<String, PersistentDataJavaVersion>,
 }
 
-/// Used as a function argument
-pub(crate) enum PersistentDataJavaInstallation {
+/// A flavor of Java installation that is managed by mcvm
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PersistentDataJavaInstallation {
 	/// Adoptium Java
 	Adoptium,
 	/// Zulu Java
 	Zulu,
 	/// GraalVM Java
 	GraalVM,
+	/// Java runtimes distributed by Mojang
+	Mojang,
 }
 
 impl PersistentDataContents {
@@ -95,6 +136,7 @@ impl PersistentData {
 			PersistentDataJavaInstallation::Adoptium => &mut self.contents.java.adoptium,
 			PersistentDataJavaInstallation::Zulu => &mut self.contents.java.zulu,
 			PersistentDataJavaInstallation::GraalVM => &mut self.contents.java.graalvm,
+			PersistentDataJavaInstallation::Mojang => &mut self.contents.java.mojang,
 		};
 		let path_str = path.to_string_lossy().to_string();
 		if let Some(current_version) = installation.get_mut(major_version) {
@@ -124,17 +166,315 @@ impl PersistentData {
 	}
 
 	/// Gets the path to a Java installation
-	pub(crate) fn get_java_path(
+	pub fn get_java_path(
 		&self,
 		installation: PersistentDataJavaInstallation,
 		version: &str,
 	) -> Option<PathBuf> {
-		let installation = match installation {
+		let version = self.get_java_versions(installation).get(version)?;
+		Some(PathBuf::from(version.path.clone()))
+	}
+
+	/// Updates the managed Java installation that an instance uses
+	pub(crate) fn update_instance_java(
+		&mut self,
+		instance: &str,
+		installation: Option<PersistentDataJavaInstallation>,
+		major_version: &str,
+	) {
+		if let Some(installation) = installation {
+			self.contents.instance_java.insert(
+				instance.to_string(),
+				PersistentDataInstanceJava {
+					installation,
+					major_version: major_version.to_string(),
+				},
+			);
+		} else {
+			self.contents.instance_java.remove(instance);
+		}
+	}
+
+	/// Removes the recorded Java usage of an instance, such as when it is deleted
+	pub fn remove_instance_java(&mut self, instance: &str) {
+		self.contents.instance_java.remove(instance);
+	}
+
+	/// Moves the recorded Java usage of an instance to a new ID after it is renamed
+	pub fn rename_instance_java(&mut self, instance: &str, new_id: &str) {
+		if let Some(java) = self.contents.instance_java.remove(instance) {
+			self.contents.instance_java.insert(new_id.to_string(), java);
+		}
+	}
+
+	/// Checks if the user has already allowed a package on an instance to run with elevated
+	/// permissions, with the same script and privileged operations
+	pub fn is_elevated_package_approved(
+		&self,
+		instance: &str,
+		package: &str,
+		script_hash: &str,
+		operations: &[String],
+	) -> bool {
+		self.contents
+			.elevated_approvals
+			.get(instance)
+			.and_then(|x| x.get(package))
+			.is_some_and(|x| *x == PersistentDataElevatedApproval::new(script_hash, operations))
+	}
+
+	/// Records that the user allowed a package on an instance to run with elevated permissions
+	pub fn approve_elevated_package(
+		&mut self,
+		instance: &str,
+		package: &str,
+		script_hash: &str,
+		operations: &[String],
+	) {
+		self.contents
+			.elevated_approvals
+			.entry(instance.into())
+			.or_default()
+			.insert(
+				package.into(),
+				PersistentDataElevatedApproval::new(script_hash, operations),
+			);
+	}
+
+	/// Removes the elevated package approvals of an instance, such as when it is deleted
+	pub fn remove_instance_elevated_approvals(&mut self, instance: &str) {
+		self.contents.elevated_approvals.remove(instance);
+	}
+
+	/// Moves the elevated package approvals of an instance to a new ID after it is renamed
+	pub fn rename_instance_elevated_approvals(&mut self, instance: &str, new_id: &str) {
+		if let Some(approvals) = self.contents.elevated_approvals.remove(instance) {
+			self.contents
+				.elevated_approvals
+				.insert(new_id.to_string(), approvals);
+		}
+	}
+
+	/// Gets the managed Java installation that an instance last used, along with its major version
+	pub fn get_instance_java(
+		&self,
+		instance: &str,
+	) -> Option<(PersistentDataJavaInstallation, &str)> {
+		let java = self.contents.instance_java.get(instance)?;
+		Some((java.installation, &java.major_version))
+	}
+
+	/// Gets the managed Java installations that are used by the given instances
+	pub fn get_active_java_installations<'a>(
+		&self,
+		instances: impl IntoIterator<Item = &'a str>,
+	) -> Vec<(PersistentDataJavaInstallation, String)> {
+		let mut out = Vec::new();
+		for instance in instances {
+			if let Some(java) = self.contents.instance_java.get(instance) {
+				let entry = (java.installation, java.major_version.clone());
+				if !out.contains(&entry) {
+					out.push(entry);
+				}
+			}
+		}
+
+		out
+	}
+
+	/// Gets all of the installed Java installations that are not in the active set
+	pub fn get_unused_java_installations(
+		&self,
+		active: &[(PersistentDataJavaInstallation, String)],
+	) -> Vec<(PersistentDataJavaInstallation, String)> {
+		let mut out = Vec::new();
+		for installation in [
+			PersistentDataJavaInstallation::Adoptium,
+			PersistentDataJavaInstallation::Zulu,
+			PersistentDataJavaInstallation::GraalVM,
+			PersistentDataJavaInstallation::Mojang,
+		] {
+			for major_version in self.get_java_versions(installation).keys() {
+				let is_active = active
+					.iter()
+					.any(|(x, y)| *x == installation && y == major_version);
+				if !is_active {
+					out.push((installation, major_version.clone()));
+				}
+			}
+		}
+		// Sort by the numeric major version, keeping versions that aren't numbers at the end
+		out.sort_by_cached_key(|x| (x.1.parse::<u32>().unwrap_or(u32::MAX), x.1.clone()));
+
+		out
+	}
+
+	/// Gets the Java installations that are recorded but no longer exist on disk
+	pub fn get_missing_java_installations(&self) -> Vec<(PersistentDataJavaInstallation, String)> {
+		let mut out = Vec::new();
+		for installation in [
+			PersistentDataJavaInstallation::Adoptium,
+			PersistentDataJavaInstallation::Zulu,
+			PersistentDataJavaInstallation::GraalVM,
+			PersistentDataJavaInstallation::Mojang,
+		] {
+			for (major_version, version) in self.get_java_versions(installation) {
+				if !PathBuf::from(&version.path).exists() {
+					out.push((installation, major_version.clone()));
+				}
+			}
+		}
+		// Sort by the numeric major version, keeping versions that aren't numbers at the end
+		out.sort_by_cached_key(|x| (x.1.parse::<u32>().unwrap_or(u32::MAX), x.1.clone()));
+
+		out
+	}
+
+	/// Removes a Java installation from the disk and the persistent data
+	pub fn remove_java_installation(
+		&mut self,
+		installation: PersistentDataJavaInstallation,
+		major_version: &str,
+	) -> anyhow::Result<()> {
+		let versions = match installation {
+			PersistentDataJavaInstallation::Adoptium => &mut self.contents.java.adoptium,
+			PersistentDataJavaInstallation::Zulu => &mut self.contents.java.zulu,
+			PersistentDataJavaInstallation::GraalVM => &mut self.contents.java.graalvm,
+			PersistentDataJavaInstallation::Mojang => &mut self.contents.java.mojang,
+		};
+		if let Some(version) = versions.remove(major_version) {
+			let path = PathBuf::from(&version.path);
+			if path.exists() {
+				fs::remove_dir_all(path).context("Failed to remove Java installation")?;
+			}
+		}
+		self.contents
+			.instance_java
+			.retain(|_, x| !(x.installation == installation && x.major_version == major_version));
+
+		Ok(())
+	}
+
+	/// Gets the map of major versions for an installation
+	fn get_java_versions(
+		&self,
+		installation: PersistentDataJavaInstallation,
+	) -> &HashMap<String, PersistentDataJavaVersion> {
+		match installation {
 			PersistentDataJavaInstallation::Adoptium => &self.contents.java.adoptium,
 			PersistentDataJavaInstallation::Zulu => &self.contents.java.zulu,
 			PersistentDataJavaInstallation::GraalVM => &self.contents.java.graalvm,
+			PersistentDataJavaInstallation::Mojang => &self.contents.java.mojang,
+		}
+	}
+}
+
+impl std::fmt::Display for PersistentDataJavaInstallation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				Self::Adoptium => "adoptium",
+				Self::Zulu => "zulu",
+				Self::GraalVM => "graalvm",
+				Self::Mojang => "mojang",
+			}
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn java_version(path: &str) -> PersistentDataJavaVersion {
+		PersistentDataJavaVersion {
+			version: "1".into(),
+			path: path.into(),
+		}
+	}
+
+	#[test]
+	fn test_unused_java_installations() {
+		let mut data = PersistentData {
+			contents: PersistentDataContents::default(),
 		};
-		let version = installation.get(version)?;
-		Some(PathBuf::from(version.path.clone()))
+		data.contents
+			.java
+			.adoptium
+			.insert("17".into(), java_version("/java/adoptium17"));
+		data.contents
+			.java
+			.adoptium
+			.insert("8".into(), java_version("/java/adoptium8"));
+		data.contents
+			.java
+			.zulu
+			.insert("17".into(), java_version("/java/zulu17"));
+
+		// Two instances share the same installation
+		data.update_instance_java("a", Some(PersistentDataJavaInstallation::Adoptium), "17");
+		data.update_instance_java("b", Some(PersistentDataJavaInstallation::Adoptium), "17");
+
+		let active = data.get_active_java_installations(["b"]);
+		assert_eq!(
+			active,
+			vec![(PersistentDataJavaInstallation::Adoptium, "17".to_string())]
+		);
+		let unused = data.get_unused_java_installations(&active);
+		assert_eq!(
+			unused,
+			vec![
+				(PersistentDataJavaInstallation::Adoptium, "8".to_string()),
+				(PersistentDataJavaInstallation::Zulu, "17".to_string()),
+			]
+		);
+	}
+
+	#[test]
+	fn test_instance_java_rename_and_remove() {
+		let mut data = PersistentData {
+			contents: PersistentDataContents::default(),
+		};
+		data.update_instance_java("a", Some(PersistentDataJavaInstallation::Zulu), "21");
+
+		data.rename_instance_java("a", "b");
+		assert_eq!(data.get_instance_java("a"), None);
+		assert_eq!(
+			data.get_instance_java("b"),
+			Some((PersistentDataJavaInstallation::Zulu, "21"))
+		);
+
+		data.remove_instance_java("b");
+		assert_eq!(data.get_instance_java("b"), None);
+		assert!(data.get_active_java_installations(["b"]).is_empty());
+	}
+
+	#[test]
+	fn test_elevated_package_approval() {
+		let mut data = PersistentData {
+			contents: PersistentDataContents::default(),
+		};
+		let operations = vec![
+			"Run the command 'b'".to_string(),
+			"Run the command 'a'".into(),
+		];
+		assert!(!data.is_elevated_package_approved("inst", "pkg", "hash", &operations));
+
+		data.approve_elevated_package("inst", "pkg", "hash", &operations);
+		let mut reordered = operations.clone();
+		reordered.reverse();
+		assert!(data.is_elevated_package_approved("inst", "pkg", "hash", &reordered));
+		assert!(!data.is_elevated_package_approved("inst", "pkg", "other", &operations));
+		assert!(!data.is_elevated_package_approved("inst", "pkg", "hash", &operations[1..]));
+		assert!(!data.is_elevated_package_approved("other", "pkg", "hash", &operations));
+
+		data.rename_instance_elevated_approvals("inst", "renamed");
+		assert!(!data.is_elevated_package_approved("inst", "pkg", "hash", &operations));
+		assert!(data.is_elevated_package_approved("renamed", "pkg", "hash", &operations));
+
+		data.remove_instance_elevated_approvals("renamed");
+		assert!(!data.is_elevated_package_approved("renamed", "pkg", "hash", &operations));
 	}
 }
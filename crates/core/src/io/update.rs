@@ -1,6 +1,10 @@
 use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
+use crate::net::download::get_transfer_limit;
+use crate::net::game_files::mirror::Mirrors;
+use crate::net::game_files::verify;
+
 /// Manager for when we are updating profile files.
 /// It will keep track of files we have already downloaded, manage task requirements, etc
 #[derive(Debug)]
@@ -9,6 +13,14 @@ pub struct UpdateManager {
 	pub(crate) force: bool,
 	/// Whether we will prioritize local files instead of remote ones
 	pub(crate) allow_offline: bool,
+	/// Whether to check the hashes of existing files
+	pub(crate) verify_hashes: bool,
+	/// The maximum number of files to download at the same time
+	pub(crate) download_concurrency: usize,
+	/// Mirrors to download game files from instead of the official servers
+	pub(crate) mirrors: Mirrors,
+	/// A version of the game that is expected to be installed already
+	pub(crate) installed_version: Option<String>,
 	/// File paths that are added when they have been updated by other functions
 	files: HashSet<PathBuf>,
 }
@@ -19,6 +31,10 @@ impl UpdateManager {
 		Self {
 			force,
 			allow_offline,
+			verify_hashes: false,
+			download_concurrency: get_transfer_limit(),
+			mirrors: Mirrors::default(),
+			installed_version: None,
 			files: HashSet::new(),
 		}
 	}
@@ -38,12 +54,22 @@ impl UpdateManager {
 		self.add_files(result.files_updated);
 	}
 
-	/// Whether a file needs to be updated
-	pub fn should_update_file(&self, file: &Path) -> bool {
-		if self.force {
-			!self.files.contains(file) || !file.exists()
+	/// Whether a file needs to be updated. On forced updates, or when hashes are verified,
+	/// an existing file with a known SHA-1 hash is only updated if its contents don't match.
+	/// Otherwise, existing files are only updated when updates are forced
+	pub fn should_update_file(&self, file: &Path, expected_sha1: Option<&str>) -> bool {
+		if !file.exists() {
+			return true;
+		}
+		// Files that were already updated don't need to be checked again
+		if self.files.contains(file) {
+			return false;
+		}
+		let expected_sha1 = expected_sha1.filter(|_| self.force || self.verify_hashes);
+		if let Some(expected_sha1) = expected_sha1 {
+			!verify::is_file_intact(file, expected_sha1)
 		} else {
-			!file.exists()
+			self.force
 		}
 	}
 
@@ -53,6 +79,16 @@ impl UpdateManager {
 		self.allow_offline
 	}
 
+	/// Gets the maximum number of files to download at the same time
+	pub fn download_concurrency(&self) -> usize {
+		self.download_concurrency
+	}
+
+	/// Gets the mirrors to download game files from
+	pub fn mirrors(&self) -> &Mirrors {
+		&self.mirrors
+	}
+
 	/// Gets whether the manager forces the reinstallation of files
 	/// even if they are already installed
 	pub fn force_reinstall(&self) -> bool {
@@ -78,3 +114,39 @@ impl UpdateMethodResult {
 		self.files_updated.extend(other.files_updated);
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_should_update_file() {
+		let dir = std::env::temp_dir().join("mcvm_test_should_update_file");
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("file.txt");
+		std::fs::write(&path, "hello").unwrap();
+		// SHA-1 of "hello"
+		let hash = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+
+		// Normal updates only check that the file exists
+		let mut manager = UpdateManager::new(false, false);
+		assert!(!manager.should_update_file(&path, None));
+		assert!(!manager.should_update_file(&path, Some(hash)));
+		assert!(!manager.should_update_file(&path, Some("0000")));
+		assert!(manager.should_update_file(&dir.join("missing.txt"), Some(hash)));
+
+		manager.verify_hashes = true;
+		assert!(!manager.should_update_file(&path, None));
+		assert!(!manager.should_update_file(&path, Some(hash)));
+		assert!(manager.should_update_file(&path, Some("0000")));
+
+		// Forced updates only skip files with a matching hash
+		let mut manager = UpdateManager::new(true, false);
+		assert!(manager.should_update_file(&path, None));
+		assert!(!manager.should_update_file(&path, Some(hash)));
+		manager.add_file(path.clone());
+		assert!(!manager.should_update_file(&path, None));
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,5 +1,5 @@
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
-use mcvm_shared::util::{ARCH_STRING, OS_STRING};
+use mcvm_shared::util::OS_STRING;
 use mcvm_shared::versions::VersionPattern;
 
 use crate::instance::{InstanceKind, WindowResolution};
@@ -8,6 +8,8 @@ use crate::launch::{LaunchParameters, QuickPlayType};
 use crate::io::files::paths::Paths;
 use crate::net::game_files::assets::get_virtual_dir_path;
 use crate::net::game_files::client_meta::args::ArgumentItem;
+use crate::net::game_files::client_meta::conditions::RuleFeatures;
+use crate::net::game_files::libraries::get_natives_dir;
 use crate::user::UserKind;
 
 /// Process an argument for the client from the client meta
@@ -24,73 +26,31 @@ pub(crate) fn process_arg(arg: &ArgumentItem, params: &LaunchParameters) -> Vec<
 			}
 		}
 		ArgumentItem::Conditional(arg) => {
+			let is_demo_user = params
+				.users
+				.get_chosen_user()
+				.is_some_and(|x| matches!(x.kind, UserKind::Demo));
+			let has_custom_resolution = window.resolution.is_some();
 			for rule in &arg.rules {
-				let allowed = rule.action.is_allowed();
-
-				if let Some(os_name) = &rule.os.name {
-					if allowed != (OS_STRING == os_name.to_string()) {
-						return vec![];
-					}
-				}
-				if let Some(os_arch) = &rule.os.arch {
-					if allowed != (ARCH_STRING == os_arch.to_string()) {
-						return vec![];
-					}
-				}
-
-				if let Some(has_custom_resolution) = &rule.features.has_custom_resolution {
-					if *has_custom_resolution && window.resolution.is_none() {
-						return vec![];
-					}
-				}
-				if let Some(is_demo_user) = &rule.features.is_demo_user {
-					if *is_demo_user {
-						let use_demo = match params.users.get_chosen_user() {
-							Some(user) => matches!(user.kind, UserKind::Demo),
-							None => false,
-						};
-						if !use_demo {
-							return vec![];
-						}
-					}
-				}
-				if let Some(quick_play_support) = &rule.features.has_quick_play_support {
-					if *quick_play_support {
-						let uses_quick_play =
-							!matches!(params.launch_config.quick_play, QuickPlayType::None);
-						if !uses_quick_play {
-							return vec![];
-						}
-					}
-				}
-				if let Some(quick_play_singleplayer) = &rule.features.is_quick_play_singleplayer {
-					if *quick_play_singleplayer {
-						let uses_quick_play =
-							!matches!(params.launch_config.quick_play, QuickPlayType::World { .. });
-						if !uses_quick_play {
-							return vec![];
-						}
-					}
-				}
-				if let Some(quick_play_multiplayer) = &rule.features.is_quick_play_multiplayer {
-					if *quick_play_multiplayer {
-						let uses_quick_play = !matches!(
-							params.launch_config.quick_play,
-							QuickPlayType::Server { .. }
-						);
-						if !uses_quick_play {
-							return vec![];
-						}
-					}
-				}
-				if let Some(quick_play_realms) = &rule.features.is_quick_play_realms {
-					if *quick_play_realms {
-						let uses_quick_play =
-							!matches!(params.launch_config.quick_play, QuickPlayType::Realm { .. });
-						if !uses_quick_play {
-							return vec![];
-						}
-					}
+				let os_matches = rule
+					.os
+					.name
+					.as_ref()
+					.map_or(true, |x| OS_STRING == x.to_string())
+					&& rule.os.arch.as_ref().map_or(true, |x| {
+						params.launch_config.get_java_arch() == x.to_string()
+					});
+				let features_match = features_match(
+					&rule.features,
+					is_demo_user,
+					has_custom_resolution,
+					&params.launch_config.quick_play,
+				);
+				if !rule
+					.action
+					.is_allowed_with_condition(os_matches && features_match)
+				{
+					return vec![];
 				}
 			}
 
@@ -103,6 +63,32 @@ pub(crate) fn process_arg(arg: &ArgumentItem, params: &LaunchParameters) -> Vec<
 	out
 }
 
+/// Checks whether the features that an argument rule checks for all match the launch
+fn features_match(
+	features: &RuleFeatures,
+	is_demo_user: bool,
+	has_custom_resolution: bool,
+	quick_play: &QuickPlayType,
+) -> bool {
+	let matches = |feature: Option<bool>, value: bool| feature.map_or(true, |x| x == value);
+
+	matches(features.is_demo_user, is_demo_user)
+		&& matches(features.has_custom_resolution, has_custom_resolution)
+		&& matches(
+			features.has_quick_play_support,
+			!matches!(quick_play, QuickPlayType::None),
+		) && matches(
+		features.is_quick_play_singleplayer,
+		matches!(quick_play, QuickPlayType::World { .. }),
+	) && matches(
+		features.is_quick_play_multiplayer,
+		matches!(quick_play, QuickPlayType::Server { .. }),
+	) && matches(
+		features.is_quick_play_realms,
+		matches!(quick_play, QuickPlayType::Realm { .. }),
+	)
+}
+
 /// Process a simple string argument
 pub(crate) fn process_simple_arg(arg: &str, params: &LaunchParameters) -> Option<String> {
 	replace_arg_placeholders(arg, params)
@@ -131,13 +117,12 @@ pub(crate) fn replace_arg_placeholders(arg: &str, params: &LaunchParameters) ->
 	out = out.replace(placeholder!("classpath"), &params.classpath.get_str());
 	out = out.replace(
 		placeholder!("natives_directory"),
-		params
-			.paths
-			.internal
-			.join("versions")
-			.join(params.version.to_string())
-			.join("natives")
-			.to_str()?,
+		get_natives_dir(
+			params.paths,
+			params.version,
+			params.launch_config.get_java_arch(),
+		)
+		.to_str()?,
 	);
 	out = out.replace(placeholder!("version_name"), params.version);
 	out = out.replace(placeholder!("version_type"), "mcvm");
@@ -146,7 +131,7 @@ pub(crate) fn replace_arg_placeholders(arg: &str, params: &LaunchParameters) ->
 	out = out.replace(placeholder!("assets_index_name"), params.version);
 	out = out.replace(
 		placeholder!("game_assets"),
-		get_virtual_dir_path(params.paths).to_str()?,
+		get_virtual_dir_path(params.paths, &params.client_meta.assets_version).to_str()?,
 	);
 
 	out = out.replace(placeholder!("clientid"), "mcvm");
@@ -199,6 +184,7 @@ pub(crate) fn replace_arg_placeholders(arg: &str, params: &LaunchParameters) ->
 			// User type
 			let user_type = match user.get_kind() {
 				UserKind::Microsoft { .. } => "msa",
+				UserKind::Unverified => "legacy",
 				_ => "msa",
 			};
 			out = out.replace(placeholder!("user_type"), user_type);
@@ -303,3 +289,131 @@ pub fn fill_logging_path_arg(arg: String, version: &str, paths: &Paths) -> Optio
 	let path = crate::net::game_files::log_config::get_path(version, paths);
 	Some(arg.replace(placeholder!("path"), path.to_str()?))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::net::game_files::client_meta::conditions::Rule;
+
+	/// Parse the features of a rule object like the ones in the client meta
+	fn parse_features(rule: &str) -> RuleFeatures {
+		serde_json::from_str::<Rule>(rule).unwrap().features
+	}
+
+	#[test]
+	fn test_quick_play_support_feature() {
+		let features =
+			parse_features(r#"{"action": "allow", "features": {"has_quick_plays_support": true}}"#);
+		assert!(!features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::None
+		));
+		assert!(features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::World {
+				world: "New World".into()
+			}
+		));
+	}
+
+	#[test]
+	fn test_quick_play_singleplayer_feature() {
+		let features = parse_features(
+			r#"{"action": "allow", "features": {"is_quick_play_singleplayer": true}}"#,
+		);
+		assert!(features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::World {
+				world: "New World".into()
+			}
+		));
+		assert!(!features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::Realm {
+				realm: "1234".into()
+			}
+		));
+		assert!(!features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::None
+		));
+	}
+
+	#[test]
+	fn test_quick_play_multiplayer_feature() {
+		let features = parse_features(
+			r#"{"action": "allow", "features": {"is_quick_play_multiplayer": true}}"#,
+		);
+		assert!(features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::Server {
+				server: "example.com".into(),
+				port: None
+			}
+		));
+		assert!(!features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::World {
+				world: "New World".into()
+			}
+		));
+	}
+
+	#[test]
+	fn test_quick_play_realms_feature() {
+		let features =
+			parse_features(r#"{"action": "allow", "features": {"is_quick_play_realms": true}}"#);
+		assert!(features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::Realm {
+				realm: "1234".into()
+			}
+		));
+		assert!(!features_match(
+			&features,
+			false,
+			false,
+			&QuickPlayType::Server {
+				server: "example.com".into(),
+				port: Some(25565)
+			}
+		));
+	}
+
+	#[test]
+	fn test_other_features() {
+		let features = parse_features(
+			r#"{"action": "allow", "features": {"is_demo_user": true, "has_custom_resolution": true}}"#,
+		);
+		assert!(features_match(&features, true, true, &QuickPlayType::None));
+		assert!(!features_match(
+			&features,
+			false,
+			true,
+			&QuickPlayType::None
+		));
+		assert!(!features_match(
+			&features,
+			true,
+			false,
+			&QuickPlayType::None
+		));
+	}
+}
@@ -1,19 +1,21 @@
 /// Client arguments
 mod args;
 
-use anyhow::{anyhow, bail, Context};
+use anyhow::{anyhow, Context};
 
 use std::collections::HashMap;
 
-#[cfg(target_os = "linux")]
 use mcvm_shared::versions::VersionPattern;
 use mcvm_shared::{output::MCVMOutput, skip_none};
 
 pub use args::create_quick_play_args;
 
+use crate::net::game_files::assets;
 use crate::net::game_files::client_meta::args::Arguments;
+use crate::net::game_files::libraries::get_natives_dir;
+use crate::user::User;
 
-use super::{process::LaunchProcessProperties, LaunchParameters};
+use super::{process::LaunchProcessProperties, LaunchConfiguration, LaunchError, LaunchParameters};
 
 /// Create launch properties for the client
 pub(crate) async fn get_launch_props(
@@ -22,7 +24,7 @@ pub(crate) async fn get_launch_props(
 ) -> anyhow::Result<LaunchProcessProperties> {
 	// Ensure a user is picked
 	if !params.users.is_user_chosen() {
-		bail!("No user chosen");
+		return Err(LaunchError::AuthRequired(anyhow!("No user chosen")).into());
 	}
 
 	// Ensure the user is authenticated
@@ -30,18 +32,35 @@ pub(crate) async fn get_launch_props(
 		.users
 		.authenticate(params.paths, params.req_client, o)
 		.await
-		.context("Failed to authenticate user")?;
+		.map_err(|e| LaunchError::AuthRequired(e.context("Failed to authenticate user")))?;
+
+	// Older versions look for their assets in the game directory
+	assets::setup_resources_dir(
+		params.paths,
+		params.version,
+		&params.client_meta.assets_version,
+		params.launch_dir,
+	)
+	.await
+	.context("Failed to set up resources directory")?;
 
 	// Build up arguments
 	let mut jvm_args = Vec::new();
 	let mut game_args = Vec::new();
 
-	if params.launch_config.use_log4j_config {
+	let version_list = &params.version_manifest.list;
+	if should_use_log_config(params.launch_config, params.version, version_list) {
 		let logging_arg = params.client_meta.logging.client.argument.clone();
 		let logging_arg = args::fill_logging_path_arg(logging_arg, params.version, params.paths)
 			.ok_or(anyhow!("Failed to convert logging path to a string"))?;
 		jvm_args.push(logging_arg);
 	}
+	add_log4j_mitigation_arg(
+		&mut jvm_args,
+		params.launch_config,
+		params.version,
+		version_list,
+	);
 
 	match &params.client_meta.arguments {
 		Arguments::New(args) => {
@@ -60,14 +79,13 @@ pub(crate) async fn get_launch_props(
 		Arguments::Old(args) => {
 			jvm_args.push(format!(
 				"-Djava.library.path={}",
-				params
-					.paths
-					.internal
-					.join("versions")
-					.join(params.version.to_string())
-					.join("natives")
-					.to_str()
-					.context("Failed to convert natives directory to a string")?
+				get_natives_dir(
+					params.paths,
+					params.version,
+					params.launch_config.get_java_arch()
+				)
+				.to_str()
+				.context("Failed to convert natives directory to a string")?
 			));
 			jvm_args.push("-cp".into());
 			jvm_args.push(params.classpath.get_str());
@@ -78,6 +96,8 @@ pub(crate) async fn get_launch_props(
 		}
 	}
 
+	add_demo_arg(&mut game_args, params.users.get_chosen_user());
+
 	let env_vars =
 		get_additional_environment_variables(params.version, &params.version_manifest.list);
 
@@ -89,6 +109,49 @@ pub(crate) async fn get_launch_props(
 	Ok(props)
 }
 
+/// Makes sure that demo users launch the game in demo mode. Newer versions add the argument
+/// through the is_demo_user rule in the client meta, but older versions don't have that rule
+fn add_demo_arg(game_args: &mut Vec<String>, user: Option<&User>) {
+	let is_demo = user.is_some_and(|x| x.is_demo());
+	if is_demo && !game_args.iter().any(|x| x == "--demo") {
+		game_args.push("--demo".into());
+	}
+}
+
+/// The JVM argument that disables message lookups in Log4J, which is the mitigation
+/// for Log4Shell on the versions of Log4J that support it
+const LOG4J_NO_LOOKUPS_ARG: &str = "-Dlog4j2.formatMsgNoLookups=true";
+
+/// Checks whether a version uses a Log4J that is vulnerable to Log4Shell (CVE-2021-44228).
+/// This affects every release from 1.7.2 until it was fixed in 1.18.1
+pub(crate) fn is_log4j_vulnerable(version: &str, version_list: &[String]) -> bool {
+	VersionPattern::Range("1.7.2".into(), "1.18".into()).matches_single(version, version_list)
+}
+
+/// Checks whether the logging config from the client meta should be used. Mojang patched the
+/// configs of vulnerable versions, which is the only mitigation for the ones that use a Log4J
+/// too old to support disabling lookups, so they always use it unless patching is disabled
+pub(crate) fn should_use_log_config(
+	launch_config: &LaunchConfiguration,
+	version: &str,
+	version_list: &[String],
+) -> bool {
+	launch_config.use_log4j_config
+		|| (launch_config.patch_log4j && is_log4j_vulnerable(version, version_list))
+}
+
+/// Disables Log4J message lookups for versions that are vulnerable to Log4Shell
+fn add_log4j_mitigation_arg(
+	jvm_args: &mut Vec<String>,
+	launch_config: &LaunchConfiguration,
+	version: &str,
+	version_list: &[String],
+) {
+	if launch_config.patch_log4j && is_log4j_vulnerable(version, version_list) {
+		jvm_args.push(LOG4J_NO_LOOKUPS_ARG.into());
+	}
+}
+
 /// Get additional environment variables for the client
 fn get_additional_environment_variables(
 	version: &str,
@@ -115,3 +178,59 @@ fn get_additional_environment_variables(
 
 	env_vars
 }
+
+#[cfg(test)]
+mod tests {
+	use crate::user::UserKind;
+
+	use super::*;
+
+	#[test]
+	fn test_demo_arg() {
+		let base = vec!["--username".to_string(), "Steve".to_string()];
+		let demo = User::new(UserKind::Demo, "demo".into());
+		let microsoft = User::new(UserKind::Microsoft { xbox_uid: None }, "ms".into());
+
+		let mut args = base.clone();
+		add_demo_arg(&mut args, Some(&demo));
+		assert_eq!(args.last().map(String::as_str), Some("--demo"));
+
+		// The argument shouldn't be duplicated if the client meta already added it
+		add_demo_arg(&mut args, Some(&demo));
+		assert_eq!(args.iter().filter(|x| *x == "--demo").count(), 1);
+
+		let mut args = base.clone();
+		add_demo_arg(&mut args, Some(&microsoft));
+		assert_eq!(args, base);
+
+		let mut args = base.clone();
+		add_demo_arg(&mut args, None);
+		assert_eq!(args, base);
+	}
+
+	#[test]
+	fn test_log4j_mitigation() {
+		let versions: Vec<String> = ["1.6.4", "1.7.2", "1.12.2", "1.17.1", "1.18", "1.18.1"]
+			.into_iter()
+			.map(String::from)
+			.collect();
+		let mut config = LaunchConfiguration::new();
+
+		let mut args = Vec::new();
+		add_log4j_mitigation_arg(&mut args, &config, "1.17.1", &versions);
+		assert_eq!(args, vec![LOG4J_NO_LOOKUPS_ARG.to_string()]);
+		assert!(should_use_log_config(&config, "1.12.2", &versions));
+
+		let mut args = Vec::new();
+		add_log4j_mitigation_arg(&mut args, &config, "1.18.1", &versions);
+		add_log4j_mitigation_arg(&mut args, &config, "1.6.4", &versions);
+		assert!(args.is_empty());
+		assert!(!should_use_log_config(&config, "1.18.1", &versions));
+
+		config.patch_log4j = false;
+		let mut args = Vec::new();
+		add_log4j_mitigation_arg(&mut args, &config, "1.17.1", &versions);
+		assert!(args.is_empty());
+		assert!(!should_use_log_config(&config, "1.12.2", &versions));
+	}
+}
@@ -1,13 +1,24 @@
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use mcvm_shared::util::ARCH_STRING;
 
 use crate::io::java::args::MemoryNum;
 use crate::io::java::install::JavaInstallationKind;
+use crate::io::java::JavaMajorVersion;
 
 /// Options for launching an instance
 #[derive(Debug)]
 pub struct LaunchConfiguration {
 	/// Java kind
 	pub java: JavaInstallationKind,
+	/// A major Java version to install and use instead of the one that the game version asks for
+	pub java_version: Option<JavaMajorVersion>,
+	/// An architecture, in the form of ARCH_STRING, to download Java and select native libraries for
+	/// instead of the one that this was built for. Useful for running x86_64 Java under Rosetta
+	pub java_arch: Option<String>,
 	/// JVM arguments
 	pub jvm_args: Vec<String>,
 	/// Game arguments
@@ -24,13 +35,35 @@ pub struct LaunchConfiguration {
 	pub quick_play: QuickPlayType,
 	/// Whether or not to use the Log4J configuration
 	pub use_log4j_config: bool,
+	/// Whether to mitigate the Log4Shell vulnerability when launching affected client versions
+	pub patch_log4j: bool,
+	/// A file to write the output of the game process to, in addition to the terminal.
+	/// The previous log file will be rotated when the game is launched
+	pub log_file: Option<PathBuf>,
+	/// The maximum number of rotated log files to keep
+	pub max_log_files: usize,
+	/// A prefix to put before every line of the game output in the terminal
+	pub output_prefix: Option<String>,
+	/// Whether to detach the game process from the launcher so that it keeps running
+	/// after the launcher exits. Output will only be written to the log file
+	pub detach: bool,
+	/// Whether to broadcast the output of the game so that it can be received live
+	/// from the InstanceHandle. Has no effect on detached processes
+	pub stream_output: bool,
+	/// A function to modify the generated launch arguments right before the game is launched
+	pub args_modifier: Option<LaunchArgsModifier>,
 }
 
+/// The default maximum number of rotated log files to keep
+pub const DEFAULT_MAX_LOG_FILES: usize = 5;
+
 impl LaunchConfiguration {
 	/// Create a new LaunchConfiguration with default settings
 	pub fn new() -> Self {
 		Self {
 			java: JavaInstallationKind::Auto,
+			java_version: None,
+			java_arch: None,
 			jvm_args: Vec::new(),
 			game_args: Vec::new(),
 			min_mem: None,
@@ -39,9 +72,21 @@ impl LaunchConfiguration {
 			wrappers: Vec::new(),
 			quick_play: QuickPlayType::None,
 			use_log4j_config: false,
+			patch_log4j: true,
+			log_file: None,
+			max_log_files: DEFAULT_MAX_LOG_FILES,
+			output_prefix: None,
+			detach: false,
+			stream_output: false,
+			args_modifier: None,
 		}
 	}
 
+	/// Get the architecture to run Java with, which is the override if there is one
+	pub fn get_java_arch(&self) -> &str {
+		self.java_arch.as_deref().unwrap_or(ARCH_STRING)
+	}
+
 	/// Get a builder for the configuration
 	pub fn builder() -> LaunchConfigBuilder {
 		LaunchConfigBuilder::new()
@@ -78,6 +123,18 @@ impl LaunchConfigBuilder {
 		self
 	}
 
+	/// Set the major Java version to use instead of the one that the game version asks for
+	pub fn java_version(mut self, java_version: JavaMajorVersion) -> Self {
+		self.config.java_version = Some(java_version);
+		self
+	}
+
+	/// Set the architecture to download Java and select native libraries for
+	pub fn java_arch(mut self, java_arch: String) -> Self {
+		self.config.java_arch = Some(java_arch);
+		self
+	}
+
 	/// Set additional JVM arguments to use
 	pub fn jvm_args(mut self, jvm_args: Vec<String>) -> Self {
 		self.config.jvm_args = jvm_args;
@@ -125,6 +182,48 @@ impl LaunchConfigBuilder {
 		self.config.use_log4j_config = use_log4j_config;
 		self
 	}
+
+	/// Set whether to mitigate the Log4Shell vulnerability for affected versions
+	pub fn patch_log4j(mut self, patch_log4j: bool) -> Self {
+		self.config.patch_log4j = patch_log4j;
+		self
+	}
+
+	/// Set the file to log the game output to
+	pub fn log_file(mut self, log_file: PathBuf) -> Self {
+		self.config.log_file = Some(log_file);
+		self
+	}
+
+	/// Set the maximum number of rotated log files to keep
+	pub fn max_log_files(mut self, max_log_files: usize) -> Self {
+		self.config.max_log_files = max_log_files;
+		self
+	}
+
+	/// Set a prefix to put before every line of the game output in the terminal
+	pub fn output_prefix(mut self, output_prefix: String) -> Self {
+		self.config.output_prefix = Some(output_prefix);
+		self
+	}
+
+	/// Set whether to detach the game process from the launcher
+	pub fn detach(mut self, detach: bool) -> Self {
+		self.config.detach = detach;
+		self
+	}
+
+	/// Set whether to broadcast the output of the game so that it can be received live
+	pub fn stream_output(mut self, stream_output: bool) -> Self {
+		self.config.stream_output = stream_output;
+		self
+	}
+
+	/// Set a function to modify the generated launch arguments
+	pub fn args_modifier(mut self, args_modifier: LaunchArgsModifier) -> Self {
+		self.config.args_modifier = Some(args_modifier);
+		self
+	}
 }
 
 impl Default for LaunchConfigBuilder {
@@ -133,6 +232,26 @@ impl Default for LaunchConfigBuilder {
 	}
 }
 
+/// The arguments that the game process is launched with
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchArgs {
+	/// Arguments for the JVM
+	pub jvm_args: Vec<String>,
+	/// Arguments for the game
+	pub game_args: Vec<String>,
+}
+
+/// A function that modifies the launch arguments. Arguments that are essential for launching,
+/// such as the classpath, must not be removed
+#[derive(Clone)]
+pub struct LaunchArgsModifier(pub Arc<dyn Fn(LaunchArgs) -> anyhow::Result<LaunchArgs>>);
+
+impl Debug for LaunchArgsModifier {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "LaunchArgsModifier")
+	}
+}
+
 /// A wrapper command that can be used to
 /// enclose the normal launch command in another
 /// program.
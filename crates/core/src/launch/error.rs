@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// An error that prevented an instance from launching or running successfully.
+/// These can be matched on to react to each kind of failure
+#[derive(Error, Debug)]
+pub enum LaunchError {
+	/// The Java executable used to launch the instance does not exist
+	#[error("Java executable '{}' does not exist", .0.display())]
+	JavaMissing(PathBuf),
+	/// No user was chosen, or the chosen user could not be authenticated
+	#[error("User authentication is required")]
+	AuthRequired(#[source] anyhow::Error),
+	/// The files for the version have not been installed
+	#[error("Version {0} is not installed")]
+	VersionNotInstalled(String),
+	/// The game process could not be started
+	#[error("Failed to spawn game process")]
+	ProcessSpawn(#[source] std::io::Error),
+	/// The game exited with a non-zero exit code, or -1 if it was terminated by a signal
+	#[error("Game exited with code {0}")]
+	NonZeroExit(i32),
+	/// Any other error
+	#[error(transparent)]
+	Other(anyhow::Error),
+}
+
+impl From<anyhow::Error> for LaunchError {
+	/// Recovers a LaunchError that was passed through an anyhow::Error,
+	/// or wraps the error as another error
+	fn from(value: anyhow::Error) -> Self {
+		match value.downcast::<LaunchError>() {
+			Ok(err) => err,
+			Err(err) => Self::Other(err),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use anyhow::Context;
+
+	use super::*;
+
+	#[test]
+	fn test_launch_error_from_anyhow() {
+		let err = anyhow::Error::new(LaunchError::NonZeroExit(1));
+		assert!(matches!(
+			LaunchError::from(err),
+			LaunchError::NonZeroExit(1)
+		));
+
+		let err: anyhow::Result<()> = Err(LaunchError::VersionNotInstalled("1.20".into()).into());
+		let err = err.context("Failed to launch").unwrap_err();
+		assert!(matches!(
+			LaunchError::from(err),
+			LaunchError::VersionNotInstalled(..)
+		));
+
+		let err = anyhow::anyhow!("Something else");
+		assert!(matches!(LaunchError::from(err), LaunchError::Other(..)));
+	}
+}
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::Child;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+/// Prefix for the filenames of rotated log files
+const ROTATED_LOG_PREFIX: &str = "mcvm-";
+/// Extension for log files
+const LOG_EXTENSION: &str = ".log";
+
+/// How many lines of output are buffered for each receiver before old lines are dropped
+const OUTPUT_CHANNEL_CAPACITY: usize = 1024;
+
+/// A line of output from the game process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+	/// The stream that the line was written to
+	pub stream: OutputStream,
+	/// The contents of the line, without the trailing newline
+	pub line: String,
+}
+
+/// An output stream of the game process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+	/// Standard output
+	Stdout,
+	/// Standard error
+	Stderr,
+}
+
+/// Creates the channel that the output of the game is broadcast on
+pub(crate) fn output_channel() -> (Sender<OutputLine>, Receiver<OutputLine>) {
+	broadcast::channel(OUTPUT_CHANNEL_CAPACITY)
+}
+
+/// Moves the previous log file out of the way so that a new one can be written,
+/// and removes old log files so that at most `max_logs` rotated logs are kept
+pub(crate) fn rotate_log_files(path: &Path, max_logs: usize) -> anyhow::Result<()> {
+	let Some(dir) = path.parent() else {
+		return Ok(());
+	};
+	std::fs::create_dir_all(dir).context("Failed to create log directory")?;
+
+	if path.exists() {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_millis();
+		let rotated_path = dir.join(format!("{ROTATED_LOG_PREFIX}{timestamp}{LOG_EXTENSION}"));
+		std::fs::rename(path, rotated_path).context("Failed to rotate previous log file")?;
+	}
+
+	// Find all of the rotated logs and remove the oldest ones
+	let mut rotated = Vec::new();
+	for entry in dir.read_dir().context("Failed to read log directory")? {
+		let entry = entry?;
+		let file_name = entry.file_name().to_string_lossy().to_string();
+		let Some(timestamp) = file_name
+			.strip_prefix(ROTATED_LOG_PREFIX)
+			.and_then(|x| x.strip_suffix(LOG_EXTENSION))
+			.and_then(|x| x.parse::<u128>().ok())
+		else {
+			continue;
+		};
+		rotated.push((timestamp, entry.path()));
+	}
+	rotated.sort_by_key(|x| std::cmp::Reverse(x.0));
+	for (_, path) in rotated.into_iter().skip(max_logs) {
+		std::fs::remove_file(path).context("Failed to remove old log file")?;
+	}
+
+	Ok(())
+}
+
+/// Copies the stdout and stderr of a child process to the terminal and optionally a log file
+/// and an output channel. If a prefix is given, each line written to the terminal will be prefixed with it.
+/// The child must have been spawned with piped stdout and stderr
+pub(crate) fn tee_output(
+	child: &mut Child,
+	path: Option<&Path>,
+	prefix: Option<&str>,
+	channel: Option<Sender<OutputLine>>,
+) -> anyhow::Result<Vec<JoinHandle<()>>> {
+	let file = if let Some(path) = path {
+		Some(File::create(path).context("Failed to create log file")?)
+	} else {
+		None
+	};
+	let file = Arc::new(Mutex::new(file));
+	let prefix = prefix.map(|x| x.as_bytes().to_vec());
+
+	let mut out = Vec::new();
+	if let Some(stdout) = child.stdout.take() {
+		out.push(spawn_tee_thread(
+			stdout,
+			std::io::stdout(),
+			file.clone(),
+			prefix.clone(),
+			channel.clone().map(|x| (x, OutputStream::Stdout)),
+		));
+	}
+	if let Some(stderr) = child.stderr.take() {
+		out.push(spawn_tee_thread(
+			stderr,
+			std::io::stderr(),
+			file,
+			prefix,
+			channel.map(|x| (x, OutputStream::Stderr)),
+		));
+	}
+
+	Ok(out)
+}
+
+/// Spawns a thread that copies lines from a reader to a terminal writer, a shared file,
+/// and an output channel. The reader is always read until the end, even if nobody is
+/// receiving from the channel, so that the process never blocks on a full pipe
+fn spawn_tee_thread(
+	reader: impl Read + Send + 'static,
+	mut terminal: impl Write + Send + 'static,
+	file: Arc<Mutex<Option<File>>>,
+	prefix: Option<Vec<u8>>,
+	channel: Option<(Sender<OutputLine>, OutputStream)>,
+) -> JoinHandle<()> {
+	std::thread::spawn(move || {
+		let mut reader = BufReader::new(reader);
+		let mut line = Vec::new();
+		loop {
+			line.clear();
+			match reader.read_until(b'\n', &mut line) {
+				Ok(0) | Err(..) => break,
+				Ok(..) => {}
+			}
+			if let Some(prefix) = &prefix {
+				let _ = terminal.write_all(prefix);
+			}
+			let _ = terminal.write_all(&line);
+			let _ = terminal.flush();
+			if let Ok(mut file) = file.lock() {
+				if let Some(file) = file.as_mut() {
+					let _ = file.write_all(&line);
+				}
+			}
+			if let Some((sender, stream)) = &channel {
+				let text = String::from_utf8_lossy(&line);
+				let text = text.trim_end_matches(['\n', '\r']).to_string();
+				// Sending only fails when there are no receivers, which is fine
+				let _ = sender.send(OutputLine {
+					stream: *stream,
+					line: text,
+				});
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_output_streaming() {
+		let (sender, mut receiver) = output_channel();
+		let reader = std::io::Cursor::new(b"Starting game\r\nCrashed!\n".to_vec());
+		let thread = spawn_tee_thread(
+			reader,
+			std::io::sink(),
+			Arc::new(Mutex::new(None)),
+			None,
+			Some((sender, OutputStream::Stderr)),
+		);
+		thread.join().unwrap();
+
+		let line = receiver.try_recv().unwrap();
+		assert_eq!(line.line, "Starting game");
+		assert_eq!(line.stream, OutputStream::Stderr);
+		assert_eq!(receiver.try_recv().unwrap().line, "Crashed!");
+		assert!(receiver.try_recv().is_err());
+	}
+
+	#[test]
+	fn test_log_rotation() {
+		let dir = std::env::temp_dir().join("mcvm_test_log_rotation");
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		let latest = dir.join("mcvm-latest.log");
+		for i in 0..5 {
+			std::fs::write(dir.join(format!("mcvm-{i}.log")), "").unwrap();
+		}
+		std::fs::write(&latest, "").unwrap();
+
+		rotate_log_files(&latest, 3).unwrap();
+		assert!(!latest.exists());
+		let count = dir.read_dir().unwrap().count();
+		assert_eq!(count, 3);
+		// The oldest logs should be the ones removed
+		assert!(!dir.join("mcvm-0.log").exists());
+		assert!(dir.join("mcvm-4.log").exists());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
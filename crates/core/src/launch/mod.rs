@@ -2,18 +2,26 @@
 mod client;
 /// Configuration for launch settings
 mod configuration;
+/// Structured errors for launching
+mod error;
+/// Logging of game output to files
+mod log;
 /// Actual launching of the game process
 mod process;
 /// Server-specific launch functionality
 mod server;
 
 use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use mcvm_shared::output::MCVMOutput;
 use mcvm_shared::Side;
+use tokio::sync::broadcast::Receiver;
 
 use self::client::create_quick_play_args;
+pub(crate) use self::client::should_use_log_config;
 use self::process::{launch_game_process, LaunchGameProcessParameters};
 use crate::config::BrandingProperties;
 use crate::instance::InstanceKind;
@@ -27,17 +35,24 @@ use crate::user::UserManager;
 use crate::util::versions::VersionName;
 
 pub use self::configuration::{
-	LaunchConfigBuilder, LaunchConfiguration, QuickPlayType, WrapperCommand,
+	LaunchArgs, LaunchArgsModifier, LaunchConfigBuilder, LaunchConfiguration, QuickPlayType,
+	WrapperCommand, DEFAULT_MAX_LOG_FILES,
 };
 
+pub use self::error::LaunchError;
+pub use self::log::{OutputLine, OutputStream};
 pub use self::process::launch_process;
 pub use self::process::{LaunchProcessParameters, LaunchProcessProperties};
 
 pub(crate) async fn launch(
 	mut params: LaunchParameters<'_>,
 	o: &mut impl MCVMOutput,
-) -> anyhow::Result<InstanceHandle> {
+) -> Result<InstanceHandle, LaunchError> {
 	let command = params.java.get_jvm_path();
+	if !command.is_file() {
+		return Err(LaunchError::JavaMissing(command));
+	}
+
 	// Get side-specific launch properties
 	let props = match params.side.get_side() {
 		Side::Client => self::client::get_launch_props(&mut params, o).await,
@@ -63,9 +78,9 @@ pub(crate) async fn launch(
 		censor_secrets: params.censor_secrets,
 	};
 
-	let child = launch_game_process(proc_params, o).context("Failed to launch game process")?;
+	let process = launch_game_process(proc_params, o).context("Failed to launch game process")?;
 
-	let handle = InstanceHandle::new(child);
+	let handle = InstanceHandle::new(process.child, process.log_threads, process.output);
 	Ok(handle)
 }
 
@@ -140,28 +155,130 @@ impl LaunchConfiguration {
 	}
 }
 
+/// How long to give the game to shut down after asking it to before killing it
+pub const KILL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+/// How often to check if the process has exited when waiting with a timeout
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Handle for an instance after launching it. You must make sure to use
 /// .wait() so that the child process is awaited.
 #[derive(Debug)]
 pub struct InstanceHandle {
 	/// The child process for the launched instance
 	process: std::process::Child,
+	/// Threads that are copying the process output to a log file
+	log_threads: Vec<JoinHandle<()>>,
+	/// Receiver for the output of the process, if it is being streamed
+	output: Option<Receiver<OutputLine>>,
 }
 
 impl InstanceHandle {
 	/// Construct a new InstanceHandle
-	fn new(process: std::process::Child) -> Self {
-		Self { process }
+	fn new(
+		process: std::process::Child,
+		log_threads: Vec<JoinHandle<()>>,
+		output: Option<Receiver<OutputLine>>,
+	) -> Self {
+		Self {
+			process,
+			log_threads,
+			output,
+		}
+	}
+
+	/// Subscribes to the lines of output from the game as they are written.
+	/// Returns None if output streaming was not enabled in the launch configuration.
+	/// The first subscriber receives all of the output since the game was launched,
+	/// and later subscribers receive output from the time that the previous one subscribed.
+	/// The output is read even when nobody is subscribed, so the game won't block on it
+	pub fn subscribe(&mut self) -> Option<Receiver<OutputLine>> {
+		let output = self.output.as_mut()?;
+		let next = output.resubscribe();
+		Some(std::mem::replace(output, next))
 	}
 
 	/// Waits for the process to complete
 	pub fn wait(&mut self) -> std::io::Result<std::process::ExitStatus> {
-		self.process.wait()
+		let status = self.process.wait()?;
+		// Make sure all of the output has been written
+		for thread in std::mem::take(&mut self.log_threads) {
+			let _ = thread.join();
+		}
+
+		Ok(status)
 	}
 
-	/// Kills the process early
-	pub fn kill(&mut self) -> std::io::Result<()> {
-		self.process.kill()
+	/// Checks if the process has exited without blocking, returning its exit status if it has
+	pub fn try_wait(&mut self) -> std::io::Result<Option<std::process::ExitStatus>> {
+		let status = self.process.try_wait()?;
+		if status.is_some() {
+			for thread in std::mem::take(&mut self.log_threads) {
+				let _ = thread.join();
+			}
+		}
+
+		Ok(status)
+	}
+
+	/// Waits for the process to complete for at most the given amount of time.
+	/// Returns the exit status if the process exited in time
+	pub fn wait_timeout(
+		&mut self,
+		timeout: Duration,
+	) -> std::io::Result<Option<std::process::ExitStatus>> {
+		let start = Instant::now();
+		loop {
+			if let Some(status) = self.try_wait()? {
+				return Ok(Some(status));
+			}
+			let elapsed = start.elapsed();
+			if elapsed >= timeout {
+				return Ok(None);
+			}
+			std::thread::sleep(WAIT_POLL_INTERVAL.min(timeout - elapsed));
+		}
+	}
+
+	/// Stops the process early. On Unix the process is first asked to shut down
+	/// gracefully, and is killed if it hasn't exited after a grace period.
+	/// On Windows the process is terminated immediately
+	pub fn kill(&mut self) -> std::io::Result<std::process::ExitStatus> {
+		self.kill_with_grace_period(KILL_GRACE_PERIOD)
+	}
+
+	/// Stops the process early, waiting for the given grace period for it to shut down
+	/// gracefully before killing it. The grace period is not used on Windows
+	pub fn kill_with_grace_period(
+		&mut self,
+		grace_period: Duration,
+	) -> std::io::Result<std::process::ExitStatus> {
+		#[cfg(target_family = "unix")]
+		{
+			let status = std::process::Command::new("kill")
+				.args(["-TERM", &self.process.id().to_string()])
+				.stderr(std::process::Stdio::null())
+				.status()?;
+			// If the signal couldn't be sent, just move on to killing it
+			if status.success() {
+				if let Some(status) = self.wait_timeout(grace_period)? {
+					return Ok(status);
+				}
+			}
+		}
+		#[cfg(not(target_family = "unix"))]
+		let _ = grace_period;
+
+		// Killing a process that has already exited fails, so check first
+		if let Some(status) = self.try_wait()? {
+			return Ok(status);
+		}
+		self.process.kill()?;
+		self.wait()
+	}
+
+	/// Gets the process ID of the game
+	pub fn get_pid(&self) -> u32 {
+		self.process.id()
 	}
 
 	/// Gets the internal child process for the game, consuming the
@@ -170,3 +287,26 @@ impl InstanceHandle {
 		self.process
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(target_family = "unix")]
+	fn test_wait_timeout_and_kill() {
+		let child = std::process::Command::new("sleep")
+			.arg("30")
+			.spawn()
+			.unwrap();
+		let mut handle = InstanceHandle::new(child, Vec::new(), None);
+		let status = handle.wait_timeout(Duration::from_millis(100)).unwrap();
+		assert!(status.is_none());
+
+		// sleep exits on SIGTERM, so this shouldn't have to wait for the grace period
+		let start = Instant::now();
+		let status = handle.kill().unwrap();
+		assert!(!status.success());
+		assert!(start.elapsed() < KILL_GRACE_PERIOD);
+	}
+}
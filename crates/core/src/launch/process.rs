@@ -1,24 +1,38 @@
 use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::fs::File;
 use std::path::Path;
-use std::process::{Child, Command};
+use std::process::{Child, Command, Stdio};
+use std::thread::JoinHandle;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use mcvm_auth::mc::AccessToken;
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm_shared::translate;
+use tokio::sync::broadcast::Receiver;
 
 use crate::instance::InstanceKind;
 use crate::util::versions::VersionName;
 use crate::WrapperCommand;
 
-use super::LaunchConfiguration;
+use super::log::{output_channel, rotate_log_files, tee_output, OutputLine};
+use super::{LaunchArgs, LaunchConfiguration, LaunchError};
+
+/// A game process that was just launched
+pub(crate) struct LaunchedProcess {
+	/// The child process
+	pub child: Child,
+	/// Threads that are logging the output of the process
+	pub log_threads: Vec<JoinHandle<()>>,
+	/// Receiver for the output of the process, if it is being streamed
+	pub output: Option<Receiver<OutputLine>>,
+}
 
 /// Launch the game process
 pub(crate) fn launch_game_process(
 	mut params: LaunchGameProcessParameters<'_>,
 	o: &mut impl MCVMOutput,
-) -> anyhow::Result<std::process::Child> {
+) -> anyhow::Result<LaunchedProcess> {
 	// Modify the parameters based on game-specific properties
 
 	// Prepend generated game args to the beginning
@@ -31,6 +45,20 @@ pub(crate) fn launch_game_process(
 	);
 	params.props.game_args.extend(previous_game_args);
 
+	// Let the user of the library change the arguments
+	if let Some(modifier) = &params.launch_config.args_modifier {
+		let original = LaunchArgs {
+			jvm_args: std::mem::take(&mut params.props.jvm_args),
+			game_args: std::mem::take(&mut params.props.game_args),
+		};
+		let modified =
+			(modifier.0)(original.clone()).context("Failed to modify launch arguments")?;
+		validate_modified_args(&original, &modified)
+			.context("Modified launch arguments are invalid")?;
+		params.props.jvm_args = modified.jvm_args;
+		params.props.game_args = modified.game_args;
+	}
+
 	// Create the parameters for the process
 	let proc_params = LaunchProcessParameters {
 		command: params.command,
@@ -51,10 +79,74 @@ pub(crate) fn launch_game_process(
 
 	output_launch_command(&cmd, params.user_access_token, params.censor_secrets, o)?;
 
+	// Set up logging to a file
+	let log_file = params.launch_config.log_file.as_deref();
+	let output_prefix = params.launch_config.output_prefix.as_deref();
+	if let Some(log_file) = log_file {
+		rotate_log_files(log_file, params.launch_config.max_log_files)
+			.context("Failed to rotate log files")?;
+	}
+
+	// Detached processes can't write to our terminal, so send their output straight to the log file
+	if params.launch_config.detach {
+		detach_command(&mut cmd, log_file).context("Failed to set up detached process")?;
+		let child = cmd.spawn().map_err(LaunchError::ProcessSpawn)?;
+		return Ok(LaunchedProcess {
+			child,
+			log_threads: Vec::new(),
+			output: None,
+		});
+	}
+
+	let stream_output = params.launch_config.stream_output;
+	let capture_output = log_file.is_some() || output_prefix.is_some() || stream_output;
+	if capture_output {
+		cmd.stdout(Stdio::piped());
+		cmd.stderr(Stdio::piped());
+	}
+
 	// Spawn
-	let child = cmd.spawn().context("Failed to spawn child process")?;
+	let mut child = cmd.spawn().map_err(LaunchError::ProcessSpawn)?;
+
+	let (sender, receiver) = if stream_output {
+		let (sender, receiver) = output_channel();
+		(Some(sender), Some(receiver))
+	} else {
+		(None, None)
+	};
+	let log_threads = if capture_output {
+		tee_output(&mut child, log_file, output_prefix, sender)
+			.context("Failed to start logging game output")?
+	} else {
+		Vec::new()
+	};
 
-	Ok(child)
+	Ok(LaunchedProcess {
+		child,
+		log_threads,
+		output: receiver,
+	})
+}
+
+/// Makes sure that arguments essential for launching were not removed when modifying the launch arguments.
+/// The main class is passed separately from the arguments, so it can't be removed
+fn validate_modified_args(original: &LaunchArgs, modified: &LaunchArgs) -> anyhow::Result<()> {
+	let classpath = original
+		.jvm_args
+		.windows(2)
+		.find(|x| x[0] == "-cp")
+		.map(|x| &x[1]);
+	if let Some(classpath) = classpath {
+		if !modified
+			.jvm_args
+			.windows(2)
+			.any(|x| x[0] == "-cp" && &x[1] == classpath)
+		{
+			bail!("The classpath argument was removed or changed");
+		}
+	}
+
+	Ok(())
 }
 
 /// Launch a generic process with the core's config system
@@ -86,6 +178,40 @@ pub fn get_process_launch_command(params: LaunchProcessParameters<'_>) -> anyhow
 	Ok(cmd)
 }
 
+/// Sets up a command to run separately from the launcher process, so that it
+/// is not stopped when the launcher exits or the terminal is closed
+fn detach_command(cmd: &mut Command, log_file: Option<&Path>) -> anyhow::Result<()> {
+	cmd.stdin(Stdio::null());
+	if let Some(log_file) = log_file {
+		let file = File::create(log_file).context("Failed to create log file")?;
+		let file2 = file
+			.try_clone()
+			.context("Failed to clone log file handle")?;
+		cmd.stdout(file);
+		cmd.stderr(file2);
+	} else {
+		cmd.stdout(Stdio::null());
+		cmd.stderr(Stdio::null());
+	}
+
+	// Put the process in its own process group so that it doesn't receive
+	// signals sent to the terminal, such as Ctrl+C
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::process::CommandExt;
+		cmd.process_group(0);
+	}
+	#[cfg(target_os = "windows")]
+	{
+		use std::os::windows::process::CommandExt;
+		const DETACHED_PROCESS: u32 = 0x00000008;
+		const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+		cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+	}
+
+	Ok(())
+}
+
 /// Display the launch command in our own way,
 /// censoring any credentials if needed
 fn output_launch_command(
@@ -241,4 +367,21 @@ mod tests {
 		assert_eq!(args.next(), Some(OsStr::new("hello")));
 		assert_eq!(args.next(), Some(OsStr::new("run")));
 	}
+
+	#[test]
+	fn test_modified_args_validation() {
+		let original = LaunchArgs {
+			jvm_args: vec!["-cp".into(), "a.jar:b.jar".into()],
+			game_args: vec!["--demo".into()],
+		};
+		let mut modified = original.clone();
+		modified
+			.jvm_args
+			.insert(0, "-javaagent:profiler.jar".into());
+		modified.game_args.clear();
+		assert!(validate_modified_args(&original, &modified).is_ok());
+
+		modified.jvm_args.truncate(2);
+		assert!(validate_modified_args(&original, &modified).is_err());
+	}
 }
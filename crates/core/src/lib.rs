@@ -27,11 +27,12 @@ pub mod util;
 /// Installable versions of the game
 pub mod version;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use io::java::install::{JavaInstallParameters, JavaInstallation, JavaInstallationKind};
 use io::java::JavaMajorVersion;
 use io::{persistent::PersistentData, update::UpdateManager};
 use mcvm_shared::output::{self, MCVMOutput};
+use mcvm_shared::util::ARCH_STRING;
 use mcvm_shared::versions::VersionInfo;
 use net::game_files::version_manifest::{make_version_list, VersionEntry, VersionManifestAndList};
 use user::UserManager;
@@ -44,7 +45,9 @@ use version::{
 pub use config::{ConfigBuilder, Configuration};
 pub use instance::{ClientWindowConfig, Instance, InstanceConfiguration, InstanceKind};
 pub use io::files::paths::Paths;
-pub use launch::{InstanceHandle, QuickPlayType, WrapperCommand};
+pub use launch::{
+	InstanceHandle, LaunchError, OutputLine, OutputStream, QuickPlayType, WrapperCommand,
+};
 
 /// Wrapper around all usage of `mcvm_core`
 pub struct MCVMCore {
@@ -70,13 +73,26 @@ impl MCVMCore {
 
 	/// Construct a new core with set configuration and paths
 	pub fn with_config_and_paths(config: Configuration, paths: Paths) -> anyhow::Result<Self> {
+		if config.download_concurrency == 0 {
+			bail!("Download concurrency must be greater than zero");
+		}
 		let persistent =
 			PersistentData::open(&paths).context("Failed to open persistent data file")?;
+		let req_client = crate::net::download::make_client(
+			config.proxy.as_deref(),
+			config.user_agent.as_deref(),
+		)
+		.context("Failed to create HTTP client")?;
+		let mut update_manager = UpdateManager::new(config.force_reinstall, config.allow_offline);
+		update_manager.verify_hashes = config.verify_hashes;
+		update_manager.download_concurrency = config.download_concurrency;
+		update_manager.mirrors = config.mirrors.clone();
+		update_manager.installed_version = config.installed_version.clone();
 		let out = Self {
 			paths,
-			req_client: reqwest::Client::new(),
+			req_client,
 			persistent,
-			update_manager: UpdateManager::new(config.force_reinstall, config.allow_offline),
+			update_manager,
 			versions: VersionRegistry::new(),
 			users: UserManager::new(config.ms_client_id.clone()),
 			config,
@@ -141,7 +157,7 @@ impl MCVMCore {
 		&mut self,
 		version: &MinecraftVersion,
 		o: &mut impl MCVMOutput,
-	) -> anyhow::Result<InstalledVersion> {
+	) -> anyhow::Result<InstalledVersion<'_, '_>> {
 		self.get_version_manifest(o)
 			.await
 			.context("Failed to ensure version manifest exists")?;
@@ -201,6 +217,8 @@ impl MCVMCore {
 			update_manager: &mut self.update_manager,
 			persistent: &mut self.persistent,
 			req_client: &self.req_client,
+			component: None,
+			arch: ARCH_STRING,
 		};
 		let java = JavaInstallation::install(kind, major_version, java_params, o)
 			.await
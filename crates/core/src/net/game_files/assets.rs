@@ -3,9 +3,10 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::Context;
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent,
+};
 use mcvm_shared::translate;
-use mcvm_shared::versions::VersionPattern;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tokio::{sync::Semaphore, task::JoinSet};
@@ -13,16 +14,48 @@ use tokio::{sync::Semaphore, task::JoinSet};
 use crate::io::files::{self, paths::Paths};
 use crate::io::update::{UpdateManager, UpdateMethodResult};
 use crate::io::{json_from_file, json_to_file};
-use crate::net::download::{self, get_transfer_limit};
+use crate::net::download;
 use crate::util::versions::VersionName;
 
 use super::client_meta::ClientMeta;
+use super::mirror::{self, MirrorKind, ASSETS_URL};
 
 /// Structure for the assets index
 #[derive(Deserialize, Serialize)]
 pub struct AssetIndex {
 	/// The map of asset resource locations to index entries
 	pub objects: HashMap<String, IndexEntry>,
+	/// Whether the assets need to be stored by name in the virtual directory
+	#[serde(default)]
+	#[serde(rename = "virtual")]
+	pub is_virtual: bool,
+	/// Whether the assets need to be stored by name in the resources directory of the game
+	#[serde(default)]
+	pub map_to_resources: bool,
+}
+
+impl AssetIndex {
+	/// Get the layout that the game expects the assets in this index to be in
+	pub fn get_layout(&self) -> AssetLayout {
+		if self.map_to_resources {
+			AssetLayout::Resources
+		} else if self.is_virtual {
+			AssetLayout::Virtual
+		} else {
+			AssetLayout::Objects
+		}
+	}
+}
+
+/// Where the game looks for its assets, which depends on how old the version is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AssetLayout {
+	/// Assets are looked up by hash in the objects directory
+	Objects,
+	/// Assets are looked up by name in the virtual directory
+	Virtual,
+	/// Assets are looked up by name in the resources directory inside the game directory
+	Resources,
 }
 
 /// A single asset in the index
@@ -47,11 +80,11 @@ pub async fn get(
 	client_meta: &ClientMeta,
 	paths: &Paths,
 	version: &VersionName,
-	version_list: &[String],
 	manager: &UpdateManager,
 	client: &Client,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<UpdateMethodResult> {
+	o.display_update_phase(UpdatePhase::Assets, UpdatePhaseEvent::Start);
 	let mut out = UpdateMethodResult::new();
 	let version_string = version.to_string();
 	let indexes_dir = paths.assets.join("indexes");
@@ -60,9 +93,8 @@ pub async fn get(
 	let index_path = indexes_dir.join(version_string + ".json");
 	let index_url = &client_meta.asset_index.url;
 
-	let (objects_dir, virtual_dir) = create_dirs(paths, version, version_list)
-		.await
-		.context("Failed to create directories for assets")?;
+	let objects_dir = paths.assets.join("objects");
+	files::create_dir(&objects_dir).context("Failed to create directory for assets")?;
 
 	let index = match download_index(index_url, &index_path, manager, client, false).await {
 		Ok(val) => val,
@@ -85,29 +117,47 @@ pub async fn get(
 		}
 	};
 
+	// Older versions need the assets to be stored by name instead of by hash
+	let virtual_dir = match index.get_layout() {
+		AssetLayout::Objects => None,
+		AssetLayout::Virtual | AssetLayout::Resources => {
+			Some(get_virtual_dir_path(paths, &client_meta.assets_version))
+		}
+	};
+
 	struct AssetData {
 		name: String,
 		url: String,
 		path: PathBuf,
 		virtual_path: Option<PathBuf>,
+		hash: String,
 		size: usize,
 	}
 
 	let mut assets_to_download = Vec::new();
 	for (name, asset) in index.objects {
 		let hash_path = asset.get_hash_path();
-		let url = format!("https://resources.download.minecraft.net/{hash_path}");
+		let url = format!("{ASSETS_URL}/{hash_path}");
 
 		let path = objects_dir.join(&hash_path);
-		let virtual_path = virtual_dir.as_ref().map(|x| x.join(&hash_path));
-		if !manager.should_update_file(&path) {
+		let virtual_path = virtual_dir.as_ref().map(|x| x.join(&name));
+		// Hashing every asset on every update would be too slow, so the hashes are only checked
+		// when updates are forced. JSON assets are minified when they are written, so their hash won't match
+		let expected_hash =
+			(manager.force_reinstall() && !name.ends_with(".json")).then_some(asset.hash.as_str());
+		if !manager.should_update_file(&path, expected_hash)
+			&& !is_asset_truncated(&name, &asset, &path)
+		{
+			// The object is already there, but it may still need to be put in the virtual directory
 			if let Some(virtual_path) = &virtual_path {
-				if !manager.should_update_file(virtual_path) {
-					continue;
+				if manager.should_update_file(virtual_path, None) {
+					files::create_leading_dirs(virtual_path)?;
+					link_or_copy(&path, virtual_path)
+						.await
+						.context("Failed to link virtual asset")?;
 				}
-			} else {
-				continue;
 			}
+			continue;
 		}
 
 		out.files_updated.insert(path.clone());
@@ -120,6 +170,7 @@ pub async fn get(
 			url,
 			path,
 			virtual_path,
+			hash: asset.hash,
 			size: asset.size,
 		};
 		assets_to_download.push(data);
@@ -143,15 +194,29 @@ pub async fn get(
 
 	let mut join = JoinSet::new();
 	// Used to limit the number of open file descriptors
-	let sem = Arc::new(Semaphore::new(get_transfer_limit()));
+	let sem = Arc::new(Semaphore::new(manager.download_concurrency));
 	for asset in assets_to_download {
 		let client = client.clone();
 		let sem = sem.clone();
+		let mirrors = manager.mirrors().clone();
 		let fut = async move {
 			let _permit = sem.acquire().await;
-			let response = download::bytes(asset.url, &client)
-				.await
-				.context("Failed to download asset")?;
+			let download = || {
+				mirror::download_game_file(
+					&asset.url,
+					MirrorKind::Assets,
+					&mirrors,
+					Some(&asset.hash),
+					&client,
+				)
+			};
+			// Download the asset again once in case it was corrupted
+			let response = match download().await {
+				Ok(response) => response,
+				Err(..) => download()
+					.await
+					.with_context(|| format!("Failed to download asset {}", asset.name))?,
+			};
 
 			// Write JSON as minified to save storage space
 			if asset.name.ends_with(".json") {
@@ -166,9 +231,9 @@ pub async fn get(
 			}
 
 			if let Some(virtual_path) = asset.virtual_path {
-				files::update_hardlink_async(&asset.path, &virtual_path)
+				link_or_copy(&asset.path, &virtual_path)
 					.await
-					.context("Failed to hardlink virtual asset")?;
+					.context("Failed to link virtual asset")?;
 			}
 			Ok::<String, anyhow::Error>(asset.name)
 		};
@@ -176,6 +241,13 @@ pub async fn get(
 	}
 
 	if count > 0 {
+		o.display_update_phase(
+			UpdatePhase::Assets,
+			UpdatePhaseEvent::Progress {
+				current: 0,
+				total: count as u32,
+			},
+		);
 		o.display(
 			MessageContents::Associated(
 				Box::new(MessageContents::Progress {
@@ -191,6 +263,13 @@ pub async fn get(
 	while let Some(asset) = join.join_next().await {
 		let name = asset??;
 		num_done += 1;
+		o.display_update_phase(
+			UpdatePhase::Assets,
+			UpdatePhaseEvent::Progress {
+				current: num_done,
+				total: count as u32,
+			},
+		);
 		o.display(
 			MessageContents::Associated(
 				Box::new(MessageContents::Progress {
@@ -211,11 +290,26 @@ pub async fn get(
 		MessageContents::Success(translate!(o, FinishDownloadingAssets)),
 		MessageLevel::Important,
 	);
+	o.display_update_phase(UpdatePhase::Assets, UpdatePhaseEvent::End);
 	o.end_process();
 
 	Ok(out)
 }
 
+/// Checks whether an asset that already exists has a different size than the one in the index,
+/// which happens when a previous download was interrupted. This only needs to stat the file,
+/// so it is cheap enough to run on every asset
+fn is_asset_truncated(name: &str, entry: &IndexEntry, path: &Path) -> bool {
+	// JSON assets are minified when they are written, so their size won't match
+	if name.ends_with(".json") {
+		return false;
+	}
+	match path.metadata() {
+		Ok(meta) => meta.len() != entry.size as u64,
+		Err(..) => false,
+	}
+}
+
 /// Downloads the asset index which contains all of the assets that need to be downloaded
 async fn download_index(
 	url: &str,
@@ -239,25 +333,113 @@ async fn download_index(
 	Ok(index)
 }
 
-/// Create the directories needed to store assets
-async fn create_dirs(
+/// Get the directory where assets are stored by name for older versions that use
+/// the virtual or resources layouts
+pub fn get_virtual_dir_path(paths: &Paths, assets_id: &str) -> PathBuf {
+	paths.assets.join("virtual").join(assets_id)
+}
+
+/// Read the layout of the assets for a version from its downloaded asset index
+pub fn read_layout(paths: &Paths, version: &str) -> anyhow::Result<AssetLayout> {
+	let index_path = paths.assets.join("indexes").join(format!("{version}.json"));
+	let index: AssetIndex =
+		json_from_file(index_path).context("Failed to read asset index contents from file")?;
+	Ok(index.get_layout())
+}
+
+/// Sets up the resources directory in the game directory of an instance for versions
+/// that use the resources asset layout. Does nothing for versions that use any other layout
+pub async fn setup_resources_dir(
 	paths: &Paths,
-	version: &VersionName,
-	version_list: &[String],
-) -> anyhow::Result<(PathBuf, Option<PathBuf>)> {
-	let objects_dir = paths.assets.join("objects");
-	files::create_dir(&objects_dir)?;
-	// Apparently this directory name is used for older game versions
-	let virtual_dir =
-		if VersionPattern::Before("13w48b".into()).matches_single(version, version_list) {
-			Some(get_virtual_dir_path(paths))
-		} else {
-			None
-		};
-	Ok((objects_dir, virtual_dir))
+	version: &str,
+	assets_id: &str,
+	game_dir: &Path,
+) -> anyhow::Result<()> {
+	let virtual_dir = get_virtual_dir_path(paths, assets_id);
+	// Only the legacy layouts create the virtual directory, so we can skip reading the index
+	if !virtual_dir.exists() || read_layout(paths, version)? != AssetLayout::Resources {
+		return Ok(());
+	}
+
+	let resources_dir = game_dir.join("resources");
+	let mut dirs = vec![virtual_dir.clone()];
+	while let Some(dir) = dirs.pop() {
+		let entries = std::fs::read_dir(&dir).context("Failed to read virtual assets directory")?;
+		for entry in entries {
+			let path = entry?.path();
+			if path.is_dir() {
+				dirs.push(path);
+				continue;
+			}
+			let rel = path.strip_prefix(&virtual_dir)?;
+			let dest = resources_dir.join(rel);
+			if dest.exists() {
+				continue;
+			}
+			files::create_leading_dirs(&dest)?;
+			link_or_copy(&path, &dest)
+				.await
+				.with_context(|| format!("Failed to set up resource {}", rel.display()))?;
+		}
+	}
+
+	Ok(())
 }
 
-/// Get the virtual assets directory path
-pub fn get_virtual_dir_path(paths: &Paths) -> PathBuf {
-	paths.assets.join("virtual").join("legacy")
+/// Hardlinks an asset to another path, copying it instead if hardlinking is not possible,
+/// such as when the paths are on different filesystems
+async fn link_or_copy(path: &Path, link: &Path) -> std::io::Result<()> {
+	if files::update_hardlink_async(path, link).await.is_err() {
+		tokio::fs::copy(path, link).await?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A trimmed down version of the pre-1.6 asset index
+	const PRE_1_6_INDEX: &str = r#"{
+		"map_to_resources": true,
+		"objects": {
+			"sound/step/grass1.ogg": {
+				"hash": "227ab99bf7c6cf0b2002e0f7957d0ff7e5cb0c96",
+				"size": 5543
+			},
+			"music/calm1.ogg": {
+				"hash": "4d6a8d3ed3fea2cc1b5b4e7d3b7cd04b4f6d3ab8",
+				"size": 2418046
+			}
+		}
+	}"#;
+
+	/// A trimmed down version of the legacy asset index
+	const LEGACY_INDEX: &str = r#"{
+		"virtual": true,
+		"objects": {
+			"icons/icon_16x16.png": {
+				"hash": "bdf48ef6b5d0d23bbb02e17d04865216179f510a",
+				"size": 3665
+			}
+		}
+	}"#;
+
+	#[test]
+	fn test_legacy_index_layout() {
+		let index: AssetIndex = serde_json::from_str(PRE_1_6_INDEX).unwrap();
+		assert_eq!(index.get_layout(), AssetLayout::Resources);
+		assert_eq!(index.objects.len(), 2);
+		assert_eq!(
+			index.objects["sound/step/grass1.ogg"].get_hash_path(),
+			"22/227ab99bf7c6cf0b2002e0f7957d0ff7e5cb0c96"
+		);
+
+		let index: AssetIndex = serde_json::from_str(LEGACY_INDEX).unwrap();
+		assert_eq!(index.get_layout(), AssetLayout::Virtual);
+
+		let index: AssetIndex = serde_json::from_str(r#"{"objects": {}}"#).unwrap();
+		assert_eq!(index.get_layout(), AssetLayout::Objects);
+	}
 }
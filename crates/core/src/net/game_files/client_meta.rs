@@ -1,11 +1,13 @@
 use std::io::{Cursor, Read};
+use std::path::PathBuf;
 
 use anyhow::{bail, Context};
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel, UpdatePhase};
 use mcvm_shared::translate;
 use mcvm_shared::util::DeserListOrSingle;
 use reqwest::Client;
 use serde::Deserialize;
+use serde_json::Value;
 use zip::ZipArchive;
 
 use crate::io::files::{self, paths::Paths};
@@ -65,6 +67,9 @@ pub struct Downloads {
 pub struct DownloadInfo {
 	/// The URL to the file
 	pub url: String,
+	/// The SHA-1 hash of the file, if it is known
+	#[serde(default)]
+	pub sha1: Option<String>,
 }
 
 /// Information about Java for this version
@@ -73,6 +78,9 @@ pub struct JavaInfo {
 	/// The Java major version to use
 	#[serde(rename = "majorVersion")]
 	pub major_version: JavaMajorVersion,
+	/// The Java runtime component that Mojang distributes for this version
+	#[serde(default)]
+	pub component: Option<String>,
 }
 
 /// Information about logging for this version
@@ -180,6 +188,9 @@ pub mod libraries {
 		pub path: String,
 		/// URL to download the artifact from
 		pub url: String,
+		/// The SHA-1 hash of the artifact, if it is known
+		#[serde(default)]
+		pub sha1: Option<String>,
 	}
 
 	/// Extraction rules for a library
@@ -189,6 +200,14 @@ pub mod libraries {
 		/// Files to exclude from the extraction
 		pub exclude: Vec<String>,
 	}
+
+	impl ExtractionRules {
+		/// Checks if a path inside of an archive is excluded. Exclusions are prefixes,
+		/// so that a directory like `META-INF/` excludes everything inside of it
+		pub fn is_excluded(&self, path: &str) -> bool {
+			self.exclude.iter().any(|x| path.starts_with(x.as_str()))
+		}
+	}
 }
 
 /// Facilities for conditions in the meta
@@ -315,7 +334,11 @@ pub mod conditions {
 	}
 }
 
-/// Gets the specific client info JSON file for a Minecraft version
+/// The maximum number of parents that a client meta can inherit from, to catch cycles
+const MAX_INHERITANCE_DEPTH: usize = 8;
+
+/// Gets the specific client info JSON file for a Minecraft version. If the client meta
+/// inherits from another version, like the ones that modloaders create, it is merged with its parents
 pub async fn get(
 	version: &str,
 	version_manifest: &VersionManifest,
@@ -324,6 +347,33 @@ pub async fn get(
 	client: &Client,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<ClientMeta> {
+	let mut meta = get_contents(version, version_manifest, paths, manager, client, o).await?;
+	let mut depth = 0;
+	while let Some(parent) = get_parent(&meta) {
+		depth += 1;
+		if depth > MAX_INHERITANCE_DEPTH {
+			bail!(
+				"Client meta for version {version} has too many parents, or inherits from itself"
+			);
+		}
+		let parent_meta = get_contents(&parent, version_manifest, paths, manager, client, o)
+			.await
+			.with_context(|| format!("Failed to get client meta for parent version {parent}"))?;
+		meta = merge_inherited(parent_meta, meta);
+	}
+
+	serde_json::from_value(meta).context("Failed to parse client meta")
+}
+
+/// Gets the contents of the client meta for a version, without resolving inheritance
+async fn get_contents(
+	version: &str,
+	version_manifest: &VersionManifest,
+	paths: &Paths,
+	manager: &UpdateManager,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Value> {
 	let version_string = version.to_owned();
 
 	let entry = version_manifest
@@ -334,18 +384,27 @@ pub async fn get(
 		bail!("Minecraft version does not exist or was not found in the manifest");
 	};
 
-	let client_meta_name: String = version_string.clone() + ".json";
-	let version_dir = paths.internal.join("versions").join(version_string);
-	files::create_dir(&version_dir).context("Failed to create versions directory")?;
-	let path = version_dir.join(client_meta_name);
+	let path = get_path(&version_string, paths);
+	if let Some(version_dir) = path.parent() {
+		files::create_dir(version_dir).context("Failed to create versions directory")?;
+	}
 
 	let meta = if manager.allow_offline && path.exists() {
 		json_from_file(path).context("Failed to read client meta contents from file")?
 	} else {
-		let mut download = ProgressiveDownload::bytes(&entry.url, client).await?;
+		// The cached copy would have been used if it existed, so there is nothing to fall back to
+		let error_context = if manager.allow_offline {
+			"Failed to download client meta and no cached copy is available"
+		} else {
+			"Failed to download client meta"
+		};
+		let mut download = ProgressiveDownload::bytes(&entry.url, client)
+			.await
+			.context(error_context)?;
 
 		while !download.is_finished() {
-			download.poll_download().await?;
+			download.poll_download().await.context(error_context)?;
+			o.display_update_phase(UpdatePhase::ClientJson, download.get_phase_progress());
 			o.display(
 				MessageContents::Associated(
 					Box::new(download.get_progress()),
@@ -395,3 +454,192 @@ pub async fn get(
 
 	Ok(meta)
 }
+
+/// Gets the path to the cached client meta of a version
+pub(crate) fn get_path(version: &str, paths: &Paths) -> PathBuf {
+	paths
+		.internal
+		.join("versions")
+		.join(version)
+		.join(format!("{version}.json"))
+}
+
+/// Gets the version that a client meta inherits from
+fn get_parent(meta: &Value) -> Option<String> {
+	meta.get("inheritsFrom")?.as_str().map(str::to_string)
+}
+
+/// Merges a client meta on top of the one that it inherits from. Libraries of the child come
+/// first and replace the same libraries in the parent, and new-style arguments are appended to
+/// the ones from the parent. Other fields from the child override the ones from the parent.
+/// The result inherits from whatever the parent inherits from.
+fn merge_inherited(parent: Value, child: Value) -> Value {
+	let (Value::Object(mut out), Value::Object(mut child)) = (parent, child) else {
+		return Value::Null;
+	};
+	child.remove("inheritsFrom");
+
+	// Libraries
+	let child_libraries = child.remove("libraries");
+	if let Some(Value::Array(mut libraries)) = child_libraries {
+		let keys: Vec<_> = libraries.iter().filter_map(get_library_key).collect();
+		if let Some(Value::Array(parent_libraries)) = out.remove("libraries") {
+			libraries.extend(
+				parent_libraries
+					.into_iter()
+					.filter(|x| get_library_key(x).map_or(true, |key| !keys.contains(&key))),
+			);
+		}
+		out.insert("libraries".into(), Value::Array(libraries));
+	}
+
+	// Arguments
+	if let Some(Value::Object(child_args)) = child.remove("arguments") {
+		match out.get_mut("arguments") {
+			Some(Value::Object(args)) => {
+				for (key, value) in child_args {
+					match (args.get_mut(&key), value) {
+						(Some(Value::Array(args)), Value::Array(value)) => args.extend(value),
+						(_, value) => {
+							args.insert(key, value);
+						}
+					}
+				}
+			}
+			_ => {
+				out.remove("minecraftArguments");
+				out.insert("arguments".into(), Value::Object(child_args));
+			}
+		}
+	}
+	// Old-style arguments are a single string with all of the arguments, so they replace the parent's
+	if let Some(args) = child.remove("minecraftArguments") {
+		out.remove("arguments");
+		out.insert("minecraftArguments".into(), args);
+	}
+
+	for (key, value) in child {
+		merge_value(out.entry(key).or_insert(Value::Null), value);
+	}
+
+	Value::Object(out)
+}
+
+/// Deep-merges a JSON value on top of another one
+fn merge_value(base: &mut Value, value: Value) {
+	match (base, value) {
+		(Value::Object(base), Value::Object(value)) => {
+			for (key, value) in value {
+				merge_value(base.entry(key).or_insert(Value::Null), value);
+			}
+		}
+		(base, value) => *base = value,
+	}
+}
+
+/// Gets the key that identifies a library without its version, such as `org.ow2.asm:asm`
+fn get_library_key(library: &Value) -> Option<String> {
+	let name = library.get("name")?.as_str()?;
+	let mut parts: Vec<_> = name.split(':').collect();
+	// Remove the version, keeping the classifier if there is one
+	if parts.len() > 2 {
+		parts.remove(2);
+	}
+
+	Some(parts.join(":"))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn test_merge_inherited() {
+		let download = json!({ "url": "https://example.com/file" });
+		let parent = json!({
+			"id": "1.20.1",
+			"arguments": {
+				"game": ["--username", "${auth_player_name}"],
+				"jvm": ["-Djava.library.path=${natives_directory}"]
+			},
+			"assetIndex": { "url": "https://example.com/assets.json" },
+			"assets": "5",
+			"downloads": { "client": download, "server": download },
+			"javaVersion": { "majorVersion": 17 },
+			"libraries": [
+				{ "name": "org.ow2.asm:asm:9.3" },
+				{ "name": "com.mojang:brigadier:1.1.8" }
+			],
+			"mainClass": "net.minecraft.client.main.Main",
+			"logging": { "client": { "argument": "-Dlog4j.configurationFile=${path}", "file": download } }
+		});
+		let child = json!({
+			"id": "1.20.1-forge",
+			"inheritsFrom": "1.20.1",
+			"arguments": {
+				"game": ["--launchTarget", "forgeclient"]
+			},
+			"libraries": [
+				{ "name": "org.ow2.asm:asm:9.5" },
+				{ "name": "net.minecraftforge:fmlloader:1.20.1-47.1.0" }
+			],
+			"mainClass": "cpw.mods.bootstraplauncher.BootstrapLauncher",
+			"javaVersion": { "component": "java-runtime-gamma" }
+		});
+
+		let merged = merge_inherited(parent, child);
+		assert_eq!(get_parent(&merged), None);
+		assert_eq!(merged["id"], "1.20.1-forge");
+		assert_eq!(merged["assets"], "5");
+		assert_eq!(merged["javaVersion"]["majorVersion"], 17);
+		assert_eq!(merged["javaVersion"]["component"], "java-runtime-gamma");
+
+		let meta: ClientMeta = serde_json::from_value(merged).unwrap();
+		assert_eq!(
+			meta.main_class,
+			"cpw.mods.bootstraplauncher.BootstrapLauncher"
+		);
+		let libraries: Vec<_> = meta.libraries.iter().map(|x| x.name.as_str()).collect();
+		assert_eq!(
+			libraries,
+			vec![
+				"org.ow2.asm:asm:9.5",
+				"net.minecraftforge:fmlloader:1.20.1-47.1.0",
+				"com.mojang:brigadier:1.1.8"
+			]
+		);
+		let args::Arguments::New(args) = meta.arguments else {
+			panic!("Arguments should be in the new format");
+		};
+		let game_args: Vec<_> = args
+			.game
+			.iter()
+			.filter_map(|x| match x {
+				args::ArgumentItem::Simple(arg) => Some(arg.as_str()),
+				_ => None,
+			})
+			.collect();
+		assert_eq!(
+			game_args,
+			vec![
+				"--username",
+				"${auth_player_name}",
+				"--launchTarget",
+				"forgeclient"
+			]
+		);
+		assert_eq!(args.jvm.len(), 1);
+
+		// Old-style arguments replace the ones from the parent
+		let merged = merge_inherited(
+			json!({ "minecraftArguments": "--username ${auth_player_name}" }),
+			json!({ "minecraftArguments": "--username ${auth_player_name} --tweakClass foo" }),
+		);
+		assert_eq!(
+			merged["minecraftArguments"],
+			"--username ${auth_player_name} --tweakClass foo"
+		);
+	}
+}
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent,
+};
 use mcvm_shared::translate;
 use reqwest::Client;
 use tokio::{sync::Semaphore, task::JoinSet};
@@ -13,43 +15,41 @@ use zip::ZipArchive;
 use crate::io::files::{self, paths::Paths};
 use crate::io::java::classpath::Classpath;
 use crate::io::update::{UpdateManager, UpdateMethodResult};
-use crate::net::download::{self, get_transfer_limit};
 use mcvm_shared::skip_none;
 use mcvm_shared::util;
 
 use super::client_meta::libraries::ExtractionRules;
 use super::client_meta::{libraries::Library, ClientMeta};
+use super::mirror::{self, MirrorKind};
 
-/// Downloads base client libraries.
+/// Downloads base client libraries, with the native libraries for an architecture.
 /// Returns a set of files to be added to the update manager.
 pub async fn get(
 	client_meta: &ClientMeta,
 	paths: &Paths,
 	version: &str,
+	arch: &str,
 	manager: &UpdateManager,
 	client: &Client,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<UpdateMethodResult> {
+	o.display_update_phase(UpdatePhase::Libraries, UpdatePhaseEvent::Start);
 	let mut out = UpdateMethodResult::new();
 	let libraries_path = paths.internal.join("libraries");
 	files::create_dir(&libraries_path)?;
-	let natives_path = paths
-		.internal
-		.join("versions")
-		.join(version)
-		.join("natives");
-	files::create_dir(&natives_path)?;
+	let natives_path = get_natives_dir(paths, version, arch);
+	std::fs::create_dir_all(&natives_path).context("Failed to create natives directory")?;
 	let natives_jars_path = paths.internal.join("natives");
 
 	let mut natives = Vec::new();
 
-	let libraries = get_list(client_meta);
+	let libraries = get_list(client_meta, arch);
 
 	let mut libs_to_download = Vec::new();
 
 	for lib in libraries {
 		if !lib.natives.is_empty() {
-			let key = skip_none!(get_natives_classifier_key(&lib.natives));
+			let key = skip_none!(get_natives_classifier_key(&lib.natives, arch));
 
 			let classifier = lib
 				.downloads
@@ -60,7 +60,7 @@ pub async fn get(
 			let path = natives_jars_path.join(classifier.path.clone());
 
 			natives.push((path.clone(), &lib.name, &lib.extract));
-			if !manager.should_update_file(&path) {
+			if !manager.should_update_file(&path, classifier.sha1.as_deref()) {
 				continue;
 			}
 			libs_to_download.push((lib.name.clone(), classifier.clone(), path));
@@ -68,7 +68,7 @@ pub async fn get(
 		}
 		if let Some(artifact) = &lib.downloads.artifact {
 			let path = libraries_path.join(artifact.path.clone());
-			if !manager.should_update_file(&path) {
+			if !manager.should_update_file(&path, artifact.sha1.as_deref()) {
 				continue;
 			}
 			libs_to_download.push((lib.name.clone(), artifact.clone(), path));
@@ -92,19 +92,26 @@ pub async fn get(
 
 	let mut join = JoinSet::new();
 	// Used to limit the number of open file descriptors
-	let sem = Arc::new(Semaphore::new(get_transfer_limit()));
+	let sem = Arc::new(Semaphore::new(manager.download_concurrency));
 	for (name, library, path) in libs_to_download {
 		let client = client.clone();
 		let sem = sem.clone();
 		let path_clone = path.clone();
+		let mirrors = manager.mirrors().clone();
 		let fut = async move {
 			files::create_leading_dirs_async(&path_clone).await?;
 
 			let _permit = sem.acquire().await;
 
-			let response = download::bytes(library.url, &client)
-				.await
-				.context("Failed to download library")?;
+			let response = mirror::download_game_file(
+				&library.url,
+				MirrorKind::Libraries,
+				&mirrors,
+				library.sha1.as_deref(),
+				&client,
+			)
+			.await
+			.with_context(|| format!("Failed to download library {name}"))?;
 			tokio::fs::write(&path_clone, response)
 				.await
 				.context("Failed to write library file")?;
@@ -116,6 +123,13 @@ pub async fn get(
 	}
 
 	if count > 0 {
+		o.display_update_phase(
+			UpdatePhase::Libraries,
+			UpdatePhaseEvent::Progress {
+				current: 0,
+				total: count as u32,
+			},
+		);
 		o.display(
 			MessageContents::Associated(
 				Box::new(MessageContents::Progress {
@@ -131,6 +145,13 @@ pub async fn get(
 	while let Some(lib) = join.join_next().await {
 		let name = lib??;
 		num_done += 1;
+		o.display_update_phase(
+			UpdatePhase::Libraries,
+			UpdatePhaseEvent::Progress {
+				current: num_done,
+				total: count as u32,
+			},
+		);
 		o.display(
 			MessageContents::Associated(
 				Box::new(MessageContents::Progress {
@@ -147,35 +168,52 @@ pub async fn get(
 		);
 	}
 
-	for (path, name, extract) in natives {
-		o.display(
-			MessageContents::StartProcess(translate!(o, StartExtractingNative, "lib" = name)),
-			MessageLevel::Debug,
-		);
-		let natives_result = extract_native(&path, &natives_path, extract, manager, o)
-			.with_context(|| format!("Failed to extract native library {name}"))?;
-		out.merge(natives_result);
+	// The natives of a version don't change, so they only need to be extracted again when forced
+	let marker_path = natives_path.join(NATIVES_MARKER);
+	if manager.force || !marker_path.exists() {
+		// Clear out old natives so that files which are no longer in the libraries don't stick around.
+		// This is allowed to fail, since natives that are in use by a running game can't be removed on some systems
+		if manager.force {
+			let _ = std::fs::remove_dir_all(&natives_path);
+			std::fs::create_dir_all(&natives_path).context("Failed to create natives directory")?;
+		}
+		for (path, name, extract) in natives {
+			o.display(
+				MessageContents::StartProcess(translate!(o, StartExtractingNative, "lib" = name)),
+				MessageLevel::Debug,
+			);
+			let natives_result = extract_native(&path, &natives_path, extract, manager, o)
+				.with_context(|| format!("Failed to extract native library {name}"))?;
+			out.merge(natives_result);
+		}
+		// Only mark the directory once everything is extracted so that an interrupted extraction is retried
+		std::fs::write(&marker_path, "").context("Failed to write natives marker file")?;
 	}
 
 	o.display(
 		MessageContents::Success(translate!(o, FinishDownloadingLibraries)),
 		MessageLevel::Important,
 	);
+	o.display_update_phase(UpdatePhase::Libraries, UpdatePhaseEvent::End);
 	o.end_process();
 
 	Ok(out)
 }
 
-/// Gets the classpath from Minecraft libraries
-pub fn get_classpath(client_meta: &ClientMeta, paths: &Paths) -> anyhow::Result<Classpath> {
+/// Gets the classpath from Minecraft libraries, with the native libraries for an architecture
+pub fn get_classpath(
+	client_meta: &ClientMeta,
+	paths: &Paths,
+	arch: &str,
+) -> anyhow::Result<Classpath> {
 	let natives_jars_path = paths.internal.join("natives");
 	let libraries_path = paths.internal.join("libraries");
 
 	let mut classpath = Classpath::new();
-	let libraries = get_list(client_meta);
+	let libraries = get_list(client_meta, arch);
 	for lib in libraries {
 		if !lib.natives.is_empty() {
-			let key = skip_none!(get_natives_classifier_key(&lib.natives));
+			let key = skip_none!(get_natives_classifier_key(&lib.natives, arch));
 
 			let classifier = lib
 				.downloads
@@ -197,18 +235,32 @@ pub fn get_classpath(client_meta: &ClientMeta, paths: &Paths) -> anyhow::Result<
 	Ok(classpath)
 }
 
+/// A file that is put in the natives directory once all of the natives have been extracted
+const NATIVES_MARKER: &str = ".extracted";
+
+/// Get the directory that the native libraries of a version are extracted to.
+/// Each architecture other than the native one gets its own directory
+pub fn get_natives_dir(paths: &Paths, version: &str, arch: &str) -> PathBuf {
+	let dir = paths.internal.join("versions").join(version);
+	if arch == util::ARCH_STRING {
+		dir.join("natives")
+	} else {
+		dir.join(format!("natives-{arch}"))
+	}
+}
+
 /// Get the key for the natives classifier
-fn get_natives_classifier_key(classifiers: &HashMap<String, String>) -> Option<String> {
+fn get_natives_classifier_key(classifiers: &HashMap<String, String>, arch: &str) -> Option<String> {
 	let key = classifiers
 		.get(&format!("natives-{}", util::OS_STRING))
 		.or_else(|| classifiers.get(util::OS_STRING))?;
-	let key = key.replace("${arch}", util::TARGET_BITS_STR);
+	let key = key.replace("${arch}", util::get_arch_bits(arch));
 
 	Some(key)
 }
 
-/// Checks the rules of a game library to see if it should be installed
-fn is_allowed(lib: &Library) -> bool {
+/// Checks the rules of a game library to see if it should be installed on an architecture
+fn is_allowed(lib: &Library, arch: &str) -> bool {
 	for rule in &lib.rules {
 		let allowed = rule.action.is_allowed();
 		if let Some(os_name) = &rule.os.name {
@@ -217,7 +269,7 @@ fn is_allowed(lib: &Library) -> bool {
 			}
 		}
 		if let Some(os_arch) = &rule.os.arch {
-			if allowed != (os_arch.to_string() == util::ARCH_STRING) {
+			if allowed != (os_arch.to_string() == arch) {
 				return false;
 			}
 		}
@@ -226,7 +278,8 @@ fn is_allowed(lib: &Library) -> bool {
 	true
 }
 
-/// Extract the files of a native library into the natives directory.
+/// Extract the files of a native library into the natives directory, skipping the
+/// ones that are excluded by its extraction rules
 fn extract_native(
 	path: &Path,
 	natives_dir: &Path,
@@ -239,44 +292,88 @@ fn extract_native(
 	let mut zip = ZipArchive::new(file).context("Failed to unarchive native")?;
 	for i in 0..zip.len() {
 		let mut file = zip.by_index(i)?;
+		if file.is_dir() || extraction_rules.is_excluded(file.name()) {
+			continue;
+		}
 		let rel_path = file
 			.enclosed_name()
 			.context("Invalid compressed file path")?;
-		if let Some(rel_path_str) = rel_path.to_str() {
-			if extraction_rules.exclude.iter().any(|x| x == rel_path_str) {
-				continue;
-			}
-		}
-		if let Some(extension) = rel_path.extension() {
-			match extension.to_str() {
-				Some("so" | "dylib" | "dll") => {
-					let out_path = natives_dir.join(rel_path);
-					if !manager.should_update_file(&out_path) {
-						continue;
-					}
-					let mut out_file =
-						File::create(&out_path).context("Failed to open output file for native")?;
-					std::io::copy(&mut file, &mut out_file)
-						.context("Failed to copy compressed file")?;
-					o.display(
-						MessageContents::Simple(translate!(
-							o,
-							ExtractedNativeFile,
-							"file" = &out_path.to_string_lossy()
-						)),
-						MessageLevel::Debug,
-					);
-					out.files_updated.insert(out_path);
-				}
-				_ => continue,
-			}
+
+		let out_path = natives_dir.join(rel_path);
+		if !manager.should_update_file(&out_path, None) {
+			continue;
 		}
+		files::create_leading_dirs(&out_path).context("Failed to create directories for native")?;
+		let mut out_file =
+			File::create(&out_path).context("Failed to open output file for native")?;
+		std::io::copy(&mut file, &mut out_file).context("Failed to copy compressed file")?;
+		o.display(
+			MessageContents::Simple(translate!(
+				o,
+				ExtractedNativeFile,
+				"file" = &out_path.to_string_lossy()
+			)),
+			MessageLevel::Debug,
+		);
+		out.files_updated.insert(out_path);
 	}
 
 	Ok(out)
 }
 
-/// Gets the list of allowed libraries from the client meta
-pub fn get_list(client_meta: &ClientMeta) -> impl Iterator<Item = &Library> {
-	client_meta.libraries.iter().filter(|lib| is_allowed(lib))
+/// Gets the list of libraries from the client meta that are allowed on an architecture
+pub fn get_list<'a>(
+	client_meta: &'a ClientMeta,
+	arch: &'a str,
+) -> impl Iterator<Item = &'a Library> {
+	client_meta
+		.libraries
+		.iter()
+		.filter(move |lib| is_allowed(lib, arch))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use mcvm_shared::output::NoOp;
+
+	use super::*;
+
+	#[test]
+	fn test_extract_native() {
+		let dir = std::env::temp_dir().join("mcvm_test_extract_native");
+		let _ = std::fs::remove_dir_all(&dir);
+		let natives_dir = dir.join("natives");
+		std::fs::create_dir_all(&natives_dir).unwrap();
+
+		let jar_path = dir.join("native.jar");
+		let mut zip = zip::ZipWriter::new(File::create(&jar_path).unwrap());
+		let options = zip::write::SimpleFileOptions::default();
+		for file in [
+			"liblwjgl.so",
+			"META-INF/MANIFEST.MF",
+			"linux/x64/libglfw.so",
+		] {
+			zip.start_file(file, options).unwrap();
+			zip.write_all(b"native").unwrap();
+		}
+		zip.finish().unwrap();
+
+		let rules = ExtractionRules {
+			exclude: vec!["META-INF/".into()],
+		};
+		let manager = UpdateManager::new(false, false);
+		let result = extract_native(&jar_path, &natives_dir, &rules, &manager, &mut NoOp).unwrap();
+		assert_eq!(result.files_updated.len(), 2);
+		assert!(natives_dir.join("liblwjgl.so").exists());
+		assert!(natives_dir.join("linux/x64/libglfw.so").exists());
+		assert!(!natives_dir.join("META-INF").exists());
+
+		// Files that were already extracted are skipped
+		let result = extract_native(&jar_path, &natives_dir, &rules, &manager, &mut NoOp).unwrap();
+		assert!(result.files_updated.is_empty());
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
 }
@@ -0,0 +1,162 @@
+use anyhow::Context;
+use reqwest::Client;
+
+use crate::net::download;
+
+use super::verify;
+
+/// The official URL that assets are downloaded from
+pub const ASSETS_URL: &str = "https://resources.download.minecraft.net";
+/// The official URL that libraries are downloaded from
+pub const LIBRARIES_URL: &str = "https://libraries.minecraft.net";
+/// The official URL that the version manifest is downloaded from
+pub const VERSION_MANIFEST_URL: &str = "https://piston-meta.mojang.com";
+
+/// Mirrors to download game files from instead of the official Mojang servers.
+/// Each mirror is a base URL that replaces the official one, keeping the rest of the path
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mirrors {
+	/// The mirror for game assets
+	pub assets: Option<String>,
+	/// The mirror for game libraries
+	pub libraries: Option<String>,
+	/// The mirror for the version manifest. This mirror is fully trusted, as the manifest
+	/// is what provides the hashes that files from the other mirrors are checked against
+	pub version_manifest: Option<String>,
+}
+
+/// The different kinds of files that can be downloaded from a mirror
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorKind {
+	/// Game assets
+	Assets,
+	/// Game libraries
+	Libraries,
+	/// The version manifest
+	VersionManifest,
+}
+
+impl MirrorKind {
+	/// Get the official URL that this kind of file is downloaded from
+	pub fn official_url(&self) -> &'static str {
+		match self {
+			Self::Assets => ASSETS_URL,
+			Self::Libraries => LIBRARIES_URL,
+			Self::VersionManifest => VERSION_MANIFEST_URL,
+		}
+	}
+}
+
+impl Mirrors {
+	/// Get the configured mirror for a kind of file
+	pub fn get(&self, kind: MirrorKind) -> Option<&str> {
+		match kind {
+			MirrorKind::Assets => self.assets.as_deref(),
+			MirrorKind::Libraries => self.libraries.as_deref(),
+			MirrorKind::VersionManifest => self.version_manifest.as_deref(),
+		}
+	}
+
+	/// Get the URL to download a file from its mirror. Returns None if there is no mirror
+	/// for this kind of file, or if the URL isn't from the official server
+	pub fn rewrite_url(&self, url: &str, kind: MirrorKind) -> Option<String> {
+		let mirror = self.get(kind)?;
+		let path = url.strip_prefix(kind.official_url())?;
+		if !path.starts_with('/') {
+			return None;
+		}
+
+		Some(format!("{}{path}", mirror.trim_end_matches('/')))
+	}
+}
+
+/// Downloads a game file, trying its mirror first if one is configured. When the SHA-1 hash
+/// from Mojang is known, the download is checked against it. If the mirror fails or gives
+/// a file with the wrong hash, the file is downloaded from the official URL instead
+pub async fn download_game_file(
+	url: &str,
+	kind: MirrorKind,
+	mirrors: &Mirrors,
+	expected_sha1: Option<&str>,
+	client: &Client,
+) -> anyhow::Result<bytes::Bytes> {
+	if let Some(mirror_url) = mirrors.rewrite_url(url, kind) {
+		if let Ok(bytes) = download_verified(&mirror_url, expected_sha1, client).await {
+			return Ok(bytes);
+		}
+	}
+
+	download_verified(url, expected_sha1, client).await
+}
+
+/// Downloads a file and checks it against its hash, if it is known
+async fn download_verified(
+	url: &str,
+	expected_sha1: Option<&str>,
+	client: &Client,
+) -> anyhow::Result<bytes::Bytes> {
+	let bytes = download::bytes_retrying(url, client)
+		.await
+		.with_context(|| format!("Failed to download from {url}"))?;
+	if let Some(expected_sha1) = expected_sha1 {
+		verify::verify_sha1(&bytes, expected_sha1)
+			.with_context(|| format!("File downloaded from {url} is invalid"))?;
+	}
+
+	Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rewrite_url() {
+		let mirrors = Mirrors {
+			assets: Some("https://mirror.example.com/assets/".into()),
+			libraries: Some("https://mirror.example.com/maven".into()),
+			version_manifest: None,
+		};
+
+		assert_eq!(
+			mirrors
+				.rewrite_url(
+					"https://resources.download.minecraft.net/ab/abcdef",
+					MirrorKind::Assets
+				)
+				.as_deref(),
+			Some("https://mirror.example.com/assets/ab/abcdef")
+		);
+		assert_eq!(
+			mirrors
+				.rewrite_url(
+					"https://libraries.minecraft.net/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar",
+					MirrorKind::Libraries
+				)
+				.as_deref(),
+			Some("https://mirror.example.com/maven/com/mojang/brigadier/1.0.18/brigadier-1.0.18.jar")
+		);
+		// Libraries from other servers are left alone
+		assert_eq!(
+			mirrors.rewrite_url(
+				"https://maven.fabricmc.net/net/fabricmc/fabric-loader.jar",
+				MirrorKind::Libraries
+			),
+			None
+		);
+		assert_eq!(
+			mirrors.rewrite_url(
+				"https://libraries.minecraft.net.example.com/foo.jar",
+				MirrorKind::Libraries
+			),
+			None
+		);
+		assert_eq!(
+			mirrors.rewrite_url(
+				"https://piston-meta.mojang.com/mc/game/version_manifest_v2.json",
+				MirrorKind::VersionManifest
+			),
+			None
+		);
+	}
+}
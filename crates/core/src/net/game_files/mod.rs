@@ -4,6 +4,10 @@ pub mod assets;
 pub mod client_meta;
 /// Downloading game Java libraries
 pub mod libraries;
+/// Downloading game files from mirrors of the official servers
+pub mod mirror;
+/// Verifying downloaded game files against their published hashes
+pub mod verify;
 /// Downloading and using the version manifest
 pub mod version_manifest;
 
@@ -19,13 +23,19 @@ use super::download;
 
 /// Downloading the game JAR file
 pub mod game_jar {
-	use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel, OutputProcess};
+	use std::path::Path;
+
+	use anyhow::Context;
+	use mcvm_shared::output::{
+		MCVMOutput, MessageContents, MessageLevel, OutputProcess, UpdatePhase,
+	};
 
 	use self::download::ProgressiveDownload;
 
 	use super::{client_meta::ClientMeta, *};
 
-	/// Downloads the vanilla game JAR file
+	/// Downloads the vanilla game JAR file. The JAR is checked against the hash in the client meta
+	/// and downloaded again once if it doesn't match
 	pub async fn get(
 		side: Side,
 		client_meta: &ClientMeta,
@@ -37,7 +47,12 @@ pub mod game_jar {
 	) -> anyhow::Result<()> {
 		let side_str = side.to_string();
 		let path = crate::io::minecraft::game_jar::get_path(side, version, None, paths);
-		if !manager.should_update_file(&path) {
+		let download = match side {
+			Side::Client => &client_meta.downloads.client,
+			Side::Server => &client_meta.downloads.server,
+		};
+		let expected_hash = download.sha1.as_deref();
+		if !manager.should_update_file(&path, expected_hash) {
 			return Ok(());
 		}
 
@@ -48,21 +63,21 @@ pub mod game_jar {
 			MessageLevel::Important,
 		);
 
-		let download = match side {
-			Side::Client => &client_meta.downloads.client,
-			Side::Server => &client_meta.downloads.server,
-		};
-
-		let mut download = ProgressiveDownload::file(&download.url, path, client).await?;
-		while !download.is_finished() {
-			download.poll_download().await?;
-			process.0.display(
-				MessageContents::Associated(
-					Box::new(download.get_progress()),
-					Box::new(MessageContents::Simple(download_message.clone())),
-				),
-				MessageLevel::Important,
-			);
+		download_jar(&download.url, &path, &download_message, client, process.0).await?;
+		if let Some(expected_hash) = expected_hash {
+			if verify::verify_file_sha1(&path, expected_hash).is_err() {
+				process.0.display(
+					MessageContents::Warning(translate!(
+						process.0,
+						GameFileHashMismatch,
+						"file" = &format!("{side_str}.jar")
+					)),
+					MessageLevel::Important,
+				);
+				download_jar(&download.url, &path, &download_message, client, process.0).await?;
+				verify::verify_file_sha1(&path, expected_hash)
+					.with_context(|| format!("Downloaded {side_str} JAR is corrupted"))?;
+			}
 		}
 
 		let side_str = cap_first_letter(&side_str);
@@ -78,6 +93,30 @@ pub mod game_jar {
 
 		Ok(())
 	}
+
+	/// Downloads the JAR file to a path while displaying the progress
+	async fn download_jar(
+		url: &str,
+		path: &Path,
+		message: &str,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
+		let mut download = ProgressiveDownload::file(url, path, client).await?;
+		while !download.is_finished() {
+			download.poll_download().await?;
+			o.display_update_phase(UpdatePhase::GameJar, download.get_phase_progress());
+			o.display(
+				MessageContents::Associated(
+					Box::new(download.get_progress()),
+					Box::new(MessageContents::Simple(message.to_string())),
+				),
+				MessageLevel::Important,
+			);
+		}
+
+		Ok(())
+	}
 }
 
 /// Downloading and using the logging config file
@@ -95,12 +134,13 @@ pub mod log_config {
 		client: &Client,
 	) -> anyhow::Result<()> {
 		let path = get_path(version, paths);
+		let file = &client_meta.logging.client.file;
 
-		if !manager.should_update_file(&path) {
+		if !manager.should_update_file(&path, file.sha1.as_deref()) {
 			return Ok(());
 		}
 
-		let url = &client_meta.logging.client.file.url;
+		let url = &file.url;
 		download::file(url, &path, client).await?;
 
 		Ok(())
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use sha1::{Digest, Sha1};
+
+/// Get the hex-encoded SHA-1 hash of some data
+pub fn get_sha1(contents: &[u8]) -> String {
+	hex::encode(Sha1::digest(contents))
+}
+
+/// Checks that the SHA-1 hash of some data matches the expected one
+pub fn verify_sha1(contents: &[u8], expected: &str) -> anyhow::Result<()> {
+	let hash = get_sha1(contents);
+	if !hash.eq_ignore_ascii_case(expected) {
+		bail!("Hash mismatch: expected {expected} but got {hash}");
+	}
+
+	Ok(())
+}
+
+/// Checks that the SHA-1 hash of a file matches the expected one
+pub fn verify_file_sha1(path: &Path, expected: &str) -> anyhow::Result<()> {
+	let contents = std::fs::read(path).context("Failed to read file")?;
+	verify_sha1(&contents, expected)
+}
+
+/// Whether a file exists and has the expected SHA-1 hash. Used to skip
+/// downloading files again that are already intact
+pub fn is_file_intact(path: &Path, expected: &str) -> bool {
+	path.exists() && verify_file_sha1(path, expected).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_verify_sha1() {
+		// SHA-1 of "hello"
+		let hash = "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d";
+		verify_sha1(b"hello", hash).unwrap();
+		verify_sha1(b"hello", &hash.to_ascii_uppercase()).unwrap();
+		let err = verify_sha1(b"world", hash).unwrap_err();
+		assert!(err.to_string().contains(hash));
+	}
+}
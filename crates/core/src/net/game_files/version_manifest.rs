@@ -1,15 +1,21 @@
 use anyhow::Context;
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent,
+};
 use mcvm_shared::translate;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 use crate::io::files::{self, paths::Paths};
+use crate::io::minecraft::game_jar;
 use crate::io::update::UpdateManager;
 use crate::io::{json_from_file, json_to_file};
 use crate::net::download::ProgressiveDownload;
 use crate::util::versions::VersionName;
 
+use super::client_meta;
+use super::mirror::{MirrorKind, VERSION_MANIFEST_URL};
+
 /// JSON format for the version manifest that contains all available Minecraft versions
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct VersionManifest {
@@ -19,6 +25,56 @@ pub struct VersionManifest {
 	pub versions: Vec<VersionEntry>,
 }
 
+impl VersionManifest {
+	/// Check whether a version in the manifest is a development snapshot
+	pub fn is_snapshot(&self, version: &str) -> bool {
+		self.versions
+			.iter()
+			.any(|x| x.id == version && matches!(x.ty, VersionType::Snapshot))
+	}
+
+	/// Get the versions that match a filter, from newest to oldest
+	pub fn filter_versions(&self, filter: &VersionFilter) -> anyhow::Result<Vec<&VersionEntry>> {
+		let versions = if let Some(since) = &filter.since {
+			let position = self
+				.versions
+				.iter()
+				.position(|x| &x.id == since)
+				.with_context(|| format!("Version '{since}' does not exist"))?;
+			&self.versions[..=position]
+		} else {
+			&self.versions[..]
+		};
+
+		let all_types = !filter.releases && !filter.snapshots;
+		let out = versions
+			.iter()
+			.filter(|x| {
+				all_types
+					|| match x.ty {
+						VersionType::Release => filter.releases,
+						VersionType::Snapshot => filter.snapshots,
+						VersionType::OldAlpha | VersionType::OldBeta => false,
+					}
+			})
+			.collect();
+
+		Ok(out)
+	}
+}
+
+/// Filter for the versions in the version manifest
+#[derive(Debug, Default, Clone)]
+pub struct VersionFilter {
+	/// Whether to include releases. If neither releases nor snapshots are included,
+	/// every type of version is
+	pub releases: bool,
+	/// Whether to include snapshots
+	pub snapshots: bool,
+	/// Only include this version and the ones that came after it
+	pub since: Option<String>,
+}
+
 /// Entry for a version in the version manifest
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct VersionEntry {
@@ -33,6 +89,11 @@ pub struct VersionEntry {
 	/// Whether the client meta needs to be unzipped first
 	#[serde(default)]
 	pub is_zipped: bool,
+	/// When the version was released
+	#[serde(rename = "releaseTime")]
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub release_time: Option<String>,
 }
 
 /// Type of a version in the version manifest
@@ -98,6 +159,7 @@ pub async fn get_with_output(
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<VersionManifest> {
 	o.start_process();
+	o.display_update_phase(UpdatePhase::Manifest, UpdatePhaseEvent::Start);
 	o.display(
 		MessageContents::StartProcess("Obtaining version manifest".into()),
 		MessageLevel::Important,
@@ -111,12 +173,15 @@ pub async fn get_with_output(
 		MessageContents::Success("Version manifest obtained".into()),
 		MessageLevel::Important,
 	);
+	o.display_update_phase(UpdatePhase::Manifest, UpdatePhaseEvent::End);
 	o.end_process();
 
 	Ok(manifest)
 }
 
-/// Obtain the version manifest contents
+/// Obtain the version manifest contents. When offline installs are allowed, the cached copy is used
+/// if the installed version is already present. Otherwise, the manifest is still downloaded so that
+/// new versions show up, but the cached copy is used if that fails
 async fn get_contents(
 	paths: &Paths,
 	manager: &UpdateManager,
@@ -127,18 +192,94 @@ async fn get_contents(
 	let mut path = paths.internal.join("versions");
 	files::create_dir(&path)?;
 	path.push("manifest.json");
-	if manager.allow_offline && !force && path.exists() {
+
+	// Launching a version that is already installed doesn't need anything new from the
+	// manifest, so the cached copy is used instead of downloading it every time
+	if manager.allow_offline
+		&& !force
+		&& path.exists()
+		&& is_installed_version_present(paths, manager)
+	{
 		return json_from_file(path).context("Failed to read manifest contents from file");
 	}
 
-	let mut download = ProgressiveDownload::bytes(
-		"https://piston-meta.mojang.com/mc/game/version_manifest_v2.json",
-		client,
-	)
-	.await?;
+	let manifest = match download_contents(manager, client, o).await {
+		Ok(manifest) => manifest,
+		Err(e) => {
+			if !manager.allow_offline || force {
+				return Err(e);
+			}
+			if !path.exists() {
+				return Err(e.context(
+					"Failed to download version manifest and no cached copy is available",
+				));
+			}
+
+			o.display(
+				MessageContents::Notice(translate!(o, UsingCachedVersionManifest)),
+				MessageLevel::Important,
+			);
+			return json_from_file(path).context("Failed to read manifest contents from file");
+		}
+	};
+
+	json_to_file(path, &manifest).context("Failed to write manifest to a file")?;
+
+	Ok(manifest)
+}
+
+/// Checks if the client meta and game jar of the version that the manager expects to be
+/// installed are already present
+fn is_installed_version_present(paths: &Paths, manager: &UpdateManager) -> bool {
+	let Some(version) = &manager.installed_version else {
+		return false;
+	};
+
+	client_meta::get_path(version, paths).exists()
+		&& game_jar::get_existing_path(version, paths).is_ok()
+}
+
+/// Download the version manifest contents, from the configured mirror if there is one.
+/// The official server is used instead if the mirror fails
+async fn download_contents(
+	manager: &UpdateManager,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<VersionManifest> {
+	let url = format!("{VERSION_MANIFEST_URL}/mc/game/version_manifest_v2.json");
+	if let Some(mirror_url) = manager
+		.mirrors()
+		.rewrite_url(&url, MirrorKind::VersionManifest)
+	{
+		match download_contents_from(&mirror_url, client, o).await {
+			Ok(manifest) => return Ok(manifest),
+			Err(e) => {
+				o.display(
+					MessageContents::Warning(translate!(o, VersionManifestMirrorFailed)),
+					MessageLevel::Important,
+				);
+				o.display(
+					MessageContents::Error(format!("{e:?}")),
+					MessageLevel::Debug,
+				);
+			}
+		}
+	}
+
+	download_contents_from(&url, client, o).await
+}
+
+/// Download the version manifest contents from a URL
+async fn download_contents_from(
+	url: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<VersionManifest> {
+	let mut download = ProgressiveDownload::bytes(url, client).await?;
 
 	while !download.is_finished() {
 		download.poll_download().await?;
+		o.display_update_phase(UpdatePhase::Manifest, download.get_phase_progress());
 		o.display(
 			MessageContents::Associated(
 				Box::new(download.get_progress()),
@@ -150,11 +291,8 @@ async fn get_contents(
 			MessageLevel::Important,
 		);
 	}
-	let manifest = download.finish_json()?;
 
-	json_to_file(path, &manifest).context("Failed to write manifest to a file")?;
-
-	Ok(manifest)
+	download.finish_json()
 }
 
 /// Make an ordered list of versions from the manifest to use for matching
@@ -192,3 +330,65 @@ impl VersionManifestAndList {
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(id: &str, ty: VersionType) -> VersionEntry {
+		VersionEntry {
+			id: id.into(),
+			ty,
+			url: String::new(),
+			is_zipped: false,
+			release_time: None,
+		}
+	}
+
+	#[test]
+	fn test_filter_versions() {
+		let manifest = VersionManifest {
+			latest: LatestVersions {
+				release: "1.21".into(),
+				snapshot: "24w14a".into(),
+			},
+			versions: vec![
+				entry("1.21", VersionType::Release),
+				entry("24w14a", VersionType::Snapshot),
+				entry("1.20.6", VersionType::Release),
+				entry("b1.7.3", VersionType::OldBeta),
+			],
+		};
+		let ids = |filter| {
+			manifest
+				.filter_versions(&filter)
+				.unwrap()
+				.into_iter()
+				.map(|x| x.id.clone())
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(ids(VersionFilter::default()).len(), 4);
+		assert_eq!(
+			ids(VersionFilter {
+				releases: true,
+				..Default::default()
+			}),
+			vec!["1.21", "1.20.6"]
+		);
+		assert_eq!(
+			ids(VersionFilter {
+				snapshots: true,
+				since: Some("1.20.6".into()),
+				..Default::default()
+			}),
+			vec!["24w14a"]
+		);
+		assert!(manifest
+			.filter_versions(&VersionFilter {
+				since: Some("foo".into()),
+				..Default::default()
+			})
+			.is_err());
+	}
+}
@@ -1,9 +1,28 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::io::files::paths::Paths;
 use crate::net::download;
+use mcvm_shared::output::NoOp;
 use mcvm_shared::util::{ARCH_STRING, OS_STRING, PREFERRED_ARCHIVE};
 
 use anyhow::{anyhow, Context};
 use reqwest::Client;
 
+/// How long the cached Java API responses are considered up to date for
+pub const JAVA_API_CACHE_AGE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Get the key that installations and cached API responses for a major version are stored under.
+/// This is just the major version for the native architecture, so that other architectures don't
+/// replace its installations
+pub fn get_arch_key(major_version: &str, arch: &str) -> String {
+	if arch == ARCH_STRING {
+		major_version.to_string()
+	} else {
+		format!("{major_version}-{arch}")
+	}
+}
+
 /// Downloading Adoptium JDK
 pub mod adoptium {
 	use anyhow::bail;
@@ -11,12 +30,29 @@ pub mod adoptium {
 
 	use super::*;
 
-	/// Gets the newest Adoptium binaries download for a major Java version
-	pub async fn get_latest(major_version: &str, client: &Client) -> anyhow::Result<PackageFormat> {
-		let url = json_url(major_version);
-		let mut manifest = download::json::<Vec<PackageFormat>>(&url, client)
-			.await
-			.context("Failed to download manifest of Adoptium versions")?;
+	/// Gets the newest Adoptium binaries download for a major Java version and architecture.
+	/// The API response is cached for the max age, or forever if it is None
+	pub async fn get_latest(
+		major_version: &str,
+		arch: &str,
+		max_age: Option<Duration>,
+		paths: &Paths,
+		client: &Client,
+	) -> anyhow::Result<PackageFormat> {
+		let url = json_url(major_version, arch);
+		let cache_path = paths.api_cache.join(format!(
+			"adoptium_{}.json",
+			get_arch_key(major_version, arch)
+		));
+		let mut manifest = download::json_cached::<Vec<PackageFormat>>(
+			&url,
+			&cache_path,
+			max_age,
+			client,
+			&mut NoOp,
+		)
+		.await
+		.context("Failed to download manifest of Adoptium versions")?;
 		if manifest.is_empty() {
 			bail!("A valid installation was not found");
 		}
@@ -26,10 +62,10 @@ pub mod adoptium {
 	}
 
 	/// Gets the URL to the JSON file for a major Java version
-	fn json_url(major_version: &str) -> String {
+	fn json_url(major_version: &str, arch: &str) -> String {
 		format!(
 			"https://api.adoptium.net/v3/assets/latest/{major_version}/hotspot?image_type=jre&vendor=eclipse&architecture={}&os={}",
-			get_arch_arg(),
+			get_arch_arg(arch),
 			get_os_arg(),
 		)
 	}
@@ -44,11 +80,11 @@ pub mod adoptium {
 	}
 
 	/// Get the arch argument for the API
-	fn get_arch_arg() -> &'static str {
-		if cfg!(target_arch = "x86_64") {
+	fn get_arch_arg(arch: &str) -> &str {
+		if arch == "x86_64" {
 			"x64"
 		} else {
-			ARCH_STRING
+			arch
 		}
 	}
 
@@ -83,12 +119,28 @@ pub mod zulu {
 	use mcvm_shared::util::preferred_archive_extension;
 	use serde::Deserialize;
 
-	/// Gets the newest Zulu package for a major Java version
-	pub async fn get_latest(major_version: &str, client: &Client) -> anyhow::Result<PackageFormat> {
-		let url = json_url(major_version);
-		let manifest = download::json::<Vec<PackageFormat>>(&url, client)
-			.await
-			.context("Failed to download manifest of Zulu versions")?;
+	/// Gets the newest Zulu package for a major Java version and architecture.
+	/// The API response is cached for the max age, or forever if it is None
+	pub async fn get_latest(
+		major_version: &str,
+		arch: &str,
+		max_age: Option<Duration>,
+		paths: &Paths,
+		client: &Client,
+	) -> anyhow::Result<PackageFormat> {
+		let url = json_url(major_version, arch);
+		let cache_path = paths
+			.api_cache
+			.join(format!("zulu_{}.json", get_arch_key(major_version, arch)));
+		let manifest = download::json_cached::<Vec<PackageFormat>>(
+			&url,
+			&cache_path,
+			max_age,
+			client,
+			&mut NoOp,
+		)
+		.await
+		.context("Failed to download manifest of Zulu versions")?;
 		let package = manifest
 			.first()
 			.ok_or(anyhow!("A valid installation was not found"))?;
@@ -97,9 +149,9 @@ pub mod zulu {
 	}
 
 	/// Gets the URL to the JSON file for a major Java version
-	fn json_url(major_version: &str) -> String {
+	fn json_url(major_version: &str, arch: &str) -> String {
 		format!(
-			"https://api.azul.com/metadata/v1/zulu/packages/?java_version={major_version}&os={OS_STRING}&arch={ARCH_STRING}&archive_type={PREFERRED_ARCHIVE}&java_package_type=jre&latest=true&java_package_features=headfull&release_status=ga&availability_types=CA&certifications=tck&page=1&page_size=100"
+			"https://api.azul.com/metadata/v1/zulu/packages/?java_version={major_version}&os={OS_STRING}&arch={arch}&archive_type={PREFERRED_ARCHIVE}&java_package_type=jre&latest=true&java_package_features=headfull&release_status=ga&availability_types=CA&certifications=tck&page=1&page_size=100"
 		)
 	}
 
@@ -152,3 +204,258 @@ pub mod graalvm {
 		)
 	}
 }
+
+/// Downloading the Java runtimes that Mojang distributes for the game
+pub mod mojang {
+	use std::collections::HashMap;
+
+	use anyhow::bail;
+	use serde::Deserialize;
+
+	use super::*;
+
+	/// URL to the manifest of Java runtimes for every platform
+	const ALL_MANIFEST_URL: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+	/// Gets the newest version of a Java runtime component for this platform.
+	/// The manifest is cached for the max age, or forever if it is None
+	pub async fn get_latest(
+		component: &str,
+		max_age: Option<Duration>,
+		paths: &Paths,
+		client: &Client,
+	) -> anyhow::Result<RuntimeEntry> {
+		let Some(platform) = get_platform_key() else {
+			bail!("Mojang does not distribute Java runtimes for this platform");
+		};
+		let cache_path = paths.api_cache.join("mojang_java.json");
+		let manifest = download::json_cached::<AllManifest>(
+			ALL_MANIFEST_URL,
+			&cache_path,
+			max_age,
+			client,
+			&mut NoOp,
+		)
+		.await
+		.context("Failed to download manifest of Mojang Java runtimes")?;
+
+		select_runtime(manifest, platform, component)
+	}
+
+	/// Selects the runtime for a platform and component from the manifest of all runtimes
+	fn select_runtime(
+		mut manifest: AllManifest,
+		platform: &str,
+		component: &str,
+	) -> anyhow::Result<RuntimeEntry> {
+		let mut entries = manifest
+			.remove(platform)
+			.and_then(|mut x| x.remove(component))
+			.unwrap_or_default();
+		if entries.is_empty() {
+			bail!("Java runtime '{component}' is not available for this platform");
+		}
+
+		Ok(entries.swap_remove(0))
+	}
+
+	/// Downloads the manifest of files for a runtime
+	pub async fn get_file_manifest(
+		runtime: &RuntimeEntry,
+		client: &Client,
+	) -> anyhow::Result<FileManifest> {
+		download::json(&runtime.manifest.url, client)
+			.await
+			.context("Failed to download file manifest of Java runtime")
+	}
+
+	/// Gets the default runtime component for a major Java version, for when
+	/// the game doesn't specify one
+	pub fn default_component(major_version: &str) -> Option<&'static str> {
+		match major_version {
+			"8" => Some("jre-legacy"),
+			"16" => Some("java-runtime-alpha"),
+			"17" => Some("java-runtime-gamma"),
+			"21" => Some("java-runtime-delta"),
+			_ => None,
+		}
+	}
+
+	/// Gets the path to the Java home inside of an installed runtime directory
+	pub fn get_home_dir(dir: &Path) -> PathBuf {
+		if cfg!(target_os = "macos") {
+			dir.join("jre.bundle/Contents/Home")
+		} else {
+			dir.to_path_buf()
+		}
+	}
+
+	/// Gets the key for this platform in the manifest
+	fn get_platform_key() -> Option<&'static str> {
+		let key = if cfg!(target_os = "linux") {
+			if cfg!(target_arch = "x86_64") {
+				"linux"
+			} else if cfg!(target_arch = "x86") {
+				"linux-i386"
+			} else {
+				return None;
+			}
+		} else if cfg!(target_os = "macos") {
+			if cfg!(target_arch = "aarch64") {
+				"mac-os-arm64"
+			} else {
+				"mac-os"
+			}
+		} else if cfg!(target_os = "windows") {
+			if cfg!(target_arch = "x86_64") {
+				"windows-x64"
+			} else if cfg!(target_arch = "x86") {
+				"windows-x86"
+			} else if cfg!(target_arch = "aarch64") {
+				"windows-arm64"
+			} else {
+				return None;
+			}
+		} else {
+			return None;
+		};
+
+		Some(key)
+	}
+
+	/// Map of platforms to maps of components to their available runtimes
+	type AllManifest = HashMap<String, HashMap<String, Vec<RuntimeEntry>>>;
+
+	/// A single runtime in the manifest
+	#[derive(Deserialize, Debug)]
+	pub struct RuntimeEntry {
+		/// The manifest of files for this runtime
+		pub manifest: DownloadInfo,
+		/// Version information for this runtime
+		pub version: RuntimeVersion,
+	}
+
+	/// Version information for a runtime
+	#[derive(Deserialize, Debug)]
+	pub struct RuntimeVersion {
+		/// The name of the version
+		pub name: String,
+	}
+
+	/// Manifest of the files in a runtime
+	#[derive(Deserialize, Debug)]
+	pub struct FileManifest {
+		/// Map of paths relative to the runtime directory to the files
+		pub files: HashMap<String, FileEntry>,
+	}
+
+	/// A single file in a runtime
+	#[derive(Deserialize, Debug)]
+	#[serde(tag = "type", rename_all = "snake_case")]
+	pub enum FileEntry {
+		/// A directory that has to be created
+		Directory,
+		/// A file to download
+		File {
+			/// Whether the file needs to be executable
+			#[serde(default)]
+			executable: bool,
+			/// The downloads for this file
+			downloads: FileDownloads,
+		},
+		/// A symbolic link to another file
+		Link {
+			/// The path the link points to, relative to the link
+			target: String,
+		},
+	}
+
+	/// The available downloads for a file
+	#[derive(Deserialize, Debug)]
+	pub struct FileDownloads {
+		/// The uncompressed download
+		pub raw: DownloadInfo,
+	}
+
+	/// Information about a download
+	#[derive(Deserialize, Debug)]
+	pub struct DownloadInfo {
+		/// The SHA-1 hash of the download
+		pub sha1: String,
+		/// The size of the download in bytes
+		pub size: u64,
+		/// The URL to the download
+		pub url: String,
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		#[test]
+		fn test_default_component() {
+			assert_eq!(default_component("17"), Some("java-runtime-gamma"));
+			assert_eq!(default_component("8"), Some("jre-legacy"));
+			assert_eq!(default_component("11"), None);
+		}
+
+		#[test]
+		fn test_select_runtime() {
+			let manifest = r#"{
+				"linux": {
+					"java-runtime-gamma": [{
+						"manifest": {"sha1": "abc", "size": 10, "url": "https://example.com/gamma.json"},
+						"version": {"name": "17.0.8"}
+					}],
+					"jre-legacy": []
+				},
+				"windows-x64": {
+					"java-runtime-gamma": [{
+						"manifest": {"sha1": "def", "size": 10, "url": "https://example.com/windows.json"},
+						"version": {"name": "17.0.3"}
+					}]
+				}
+			}"#;
+			let parse = || serde_json::from_str::<AllManifest>(manifest).unwrap();
+
+			let runtime = select_runtime(parse(), "linux", "java-runtime-gamma").unwrap();
+			assert_eq!(runtime.version.name, "17.0.8");
+			assert_eq!(runtime.manifest.url, "https://example.com/gamma.json");
+			let runtime = select_runtime(parse(), "windows-x64", "java-runtime-gamma").unwrap();
+			assert_eq!(runtime.version.name, "17.0.3");
+
+			assert!(select_runtime(parse(), "linux", "jre-legacy").is_err());
+			assert!(select_runtime(parse(), "linux", "java-runtime-delta").is_err());
+			assert!(select_runtime(parse(), "mac-os", "java-runtime-gamma").is_err());
+		}
+
+		#[test]
+		fn test_file_manifest_parse() {
+			let manifest = r#"{
+				"files": {
+					"bin": {"type": "directory"},
+					"bin/java": {
+						"type": "file",
+						"executable": true,
+						"downloads": {
+							"raw": {"sha1": "abc", "size": 10, "url": "https://example.com/java"}
+						}
+					},
+					"lib/link": {"type": "link", "target": "../bin/java"}
+				}
+			}"#;
+			let manifest: FileManifest = serde_json::from_str(manifest).unwrap();
+			assert!(matches!(manifest.files["bin"], FileEntry::Directory));
+			assert!(matches!(
+				manifest.files["bin/java"],
+				FileEntry::File {
+					executable: true,
+					..
+				}
+			));
+			assert!(
+				matches!(&manifest.files["lib/link"], FileEntry::Link { target } if target == "../bin/java")
+			);
+		}
+	}
+}
@@ -9,7 +9,7 @@ use mcvm_auth::db::{AuthDatabase, DatabaseUser, SensitiveUserInfo};
 use mcvm_auth::mc::Keypair;
 use mcvm_auth::mc::{
 	self as auth, authenticate_microsoft_user, authenticate_microsoft_user_from_token, AccessToken,
-	ClientId, RefreshToken,
+	ClientId, MicrosoftAuthResult, RefreshToken,
 };
 
 use super::{CustomAuthFunction, User, UserKind};
@@ -48,7 +48,7 @@ impl User {
 					*xbox_uid = user_data.xbox_uid;
 				}
 			}
-			UserKind::Demo => {}
+			UserKind::Demo | UserKind::Unverified => {}
 			UserKind::Unknown(other) => {
 				if let Some(func) = params.custom_auth_fn {
 					o.display(
@@ -86,9 +86,10 @@ impl User {
 					return false;
 				};
 
-				db.get_valid_user(&self.id).is_some()
+				db.get_valid_user(&self.id)
+					.is_some_and(|user| user.is_logged_in())
 			}
-			UserKind::Demo => true,
+			UserKind::Demo | UserKind::Unverified => true,
 			UserKind::Unknown(..) => true,
 		}
 	}
@@ -97,7 +98,7 @@ impl User {
 	pub fn is_authenticated(&self) -> bool {
 		match &self.kind {
 			UserKind::Microsoft { .. } => self.access_token.is_some() && self.uuid.is_some(),
-			UserKind::Demo => true,
+			UserKind::Demo | UserKind::Unverified => true,
 			UserKind::Unknown(..) => true,
 		}
 	}
@@ -144,6 +145,16 @@ impl User {
 
 		Ok(())
 	}
+
+	/// Removes all of this user's data from the auth database, including their passkey
+	pub fn forget(&self, paths: &Paths) -> anyhow::Result<()> {
+		let mut db =
+			AuthDatabase::open(&paths.auth).context("Failed to open authentication database")?;
+		db.remove_user(&self.id)
+			.context("Failed to remove user from database")?;
+
+		Ok(())
+	}
 }
 
 /// Data for a Microsoft user
@@ -169,43 +180,127 @@ async fn update_microsoft_user_auth(
 			.await;
 	}
 
-	// Check the authentication DB
-	let user_data = if let Some((db_user, sensitive)) =
-		get_full_user(&db, user_id, o).context("Failed to get full user from database")?
-	{
-		let refresh_token = RefreshToken::new(
-			sensitive
-				.refresh_token
-				.expect("Refresh token should be present in a full valid user"),
-		);
-		// Get the access token using the refresh token
-		let oauth_client =
-			auth::create_client(params.client_id).context("Failed to create OAuth client")?;
-		let token = auth::refresh_microsoft_token(&oauth_client, &refresh_token)
-			.await
-			.context("Failed to get refreshed token")?;
+	// Try to use the refresh token stored in the authentication DB first
+	let full_user = get_full_user(&db, user_id, o)
+		.context("Failed to get full user from database")?
+		.map(|(_, sensitive)| sensitive);
+	if let Some(sensitive) = full_user {
+		match refresh_microsoft_user(&sensitive, &params, o).await {
+			Ok(token) => {
+				let profile = get_owned_profile(&token.access_token.0, params.req_client).await?;
+
+				// Microsoft may rotate the refresh token, so we need to store the new one
+				if let Some(new_refresh_token) = token.refresh_token {
+					let new_refresh_token = new_refresh_token.secret().clone();
+					if sensitive.refresh_token.as_ref() != Some(&new_refresh_token) {
+						store_refresh_token(&mut db, user_id, sensitive.clone(), new_refresh_token)
+							.context("Failed to store refreshed token")?;
+					}
+				}
 
-		let token = authenticate_microsoft_user_from_token(token, params.req_client, o)
-			.await
-			.context("Failed to authenticate with refreshed token")?;
-
-		MicrosoftUserData {
-			access_token: AccessToken(token.access_token.0.clone()),
-			profile: MinecraftUserProfile {
-				name: db_user.username.clone(),
-				uuid: db_user.uuid.clone(),
-				skins: Vec::new(),
-				capes: Vec::new(),
-			},
-			xbox_uid: sensitive.xbox_uid.clone(),
-			keypair: sensitive.keypair.clone(),
+				// Keep the stored username up to date in case it was changed
+				if let Some(db_user) = db.get_user_mut(user_id) {
+					if db_user.username != profile.name {
+						db_user.username = profile.name.clone();
+						db.write()
+							.context("Failed to write to authentication database")?;
+					}
+				}
+
+				return Ok(MicrosoftUserData {
+					access_token: token.access_token,
+					profile,
+					xbox_uid: sensitive.xbox_uid,
+					keypair: sensitive.keypair,
+				});
+			}
+			Err(e) => {
+				o.display(
+					MessageContents::Warning(
+						"Failed to refresh authentication. Please sign in again".into(),
+					),
+					MessageLevel::Important,
+				);
+				o.display(
+					MessageContents::Error(format!("{e:?}")),
+					MessageLevel::Debug,
+				);
+			}
 		}
-	} else {
-		// Authenticate with the server again
-		reauth_microsoft_user(user_id, &mut db, params.client_id, params.req_client, o).await?
+	}
+
+	// Authenticate with the server again
+	reauth_microsoft_user(user_id, &mut db, params.client_id, params.req_client, o).await
+}
+
+/// Gets new authentication for a Microsoft user using their stored refresh token
+async fn refresh_microsoft_user(
+	sensitive: &SensitiveUserInfo,
+	params: &AuthParameters<'_>,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<MicrosoftAuthResult> {
+	let refresh_token = RefreshToken::new(
+		sensitive
+			.refresh_token
+			.clone()
+			.expect("Refresh token should be present in a full valid user"),
+	);
+	// Get the access token using the refresh token
+	let oauth_client =
+		auth::create_client(params.client_id.clone()).context("Failed to create OAuth client")?;
+	let token = auth::refresh_microsoft_token(&oauth_client, &refresh_token)
+		.await
+		.context("Failed to get refreshed token")?;
+
+	authenticate_microsoft_user_from_token(token, params.req_client, o)
+		.await
+		.context("Failed to authenticate with refreshed token")
+}
+
+/// Gets the Minecraft profile of an account, making sure that it owns the game
+async fn get_owned_profile(
+	access_token: &str,
+	client: &reqwest::Client,
+) -> anyhow::Result<MinecraftUserProfile> {
+	let ownership_task = async {
+		auth::account_owns_game(access_token, client)
+			.await
+			.context("Failed to check for game ownership")
 	};
+	let profile_task = async {
+		crate::net::minecraft::get_user_profile(access_token, client)
+			.await
+			.context("Failed to get Microsoft user profile")
+	};
+	// Accounts without the game don't have a profile, so check ownership first for a better error
+	let (owns_game, profile) = tokio::join!(ownership_task, profile_task);
+	if !owns_game? {
+		bail!("This account does not own Minecraft. Make sure that you signed in with the Microsoft account that bought the game, or use a demo user instead");
+	}
+
+	profile
+}
+
+/// Replaces the stored refresh token of a user in the database
+fn store_refresh_token(
+	db: &mut AuthDatabase,
+	user_id: &str,
+	sensitive: SensitiveUserInfo,
+	refresh_token: String,
+) -> anyhow::Result<()> {
+	let Some(user) = db.get_user_mut(user_id) else {
+		return Ok(());
+	};
+	let sensitive = SensitiveUserInfo {
+		refresh_token: Some(refresh_token),
+		..sensitive
+	};
+	user.set_sensitive_info(sensitive)
+		.context("Failed to update sensitive user info")?;
+	db.write()
+		.context("Failed to write to authentication database")?;
 
-	Ok(user_data)
+	Ok(())
 }
 
 async fn reauth_microsoft_user(
@@ -219,29 +314,7 @@ async fn reauth_microsoft_user(
 		.await
 		.context("Failed to authenticate user")?;
 
-	let ownership_task = {
-		let client = client.clone();
-		let token = auth_result.access_token.0.clone();
-		async move {
-			let owns_game = auth::account_owns_game(&token, &client)
-				.await
-				.context("Failed to check for game ownership")?;
-
-			Ok::<bool, anyhow::Error>(owns_game)
-		}
-	};
-
-	let profile_task = {
-		let client = client.clone();
-		let token = auth_result.access_token.0.clone();
-		async move {
-			let profile = crate::net::minecraft::get_user_profile(&token, &client)
-				.await
-				.context("Failed to get Microsoft user profile")?;
-
-			Ok::<MinecraftUserProfile, anyhow::Error>(profile)
-		}
-	};
+	let profile_task = get_owned_profile(&auth_result.access_token.0, client);
 
 	let certificate_task = {
 		let client = client.clone();
@@ -255,12 +328,7 @@ async fn reauth_microsoft_user(
 		}
 	};
 
-	let (owns_game, profile, certificate) =
-		tokio::try_join!(ownership_task, profile_task, certificate_task)?;
-
-	if !owns_game {
-		bail!("Specified account does not own Minecraft");
-	}
+	let (profile, certificate) = tokio::try_join!(profile_task, certificate_task)?;
 
 	// Calculate expiration time
 	let expiration_time = mcvm_auth::db::calculate_expiration_date();
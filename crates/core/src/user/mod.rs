@@ -5,7 +5,7 @@ pub mod uuid;
 
 use std::{collections::HashMap, ops::Deref, sync::Arc};
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use mcvm_auth::mc::{AccessToken, ClientId, Keypair};
 use mcvm_shared::output::MCVMOutput;
 use reqwest::Client;
@@ -44,6 +44,9 @@ pub enum UserKind {
 	},
 	/// A demo user
 	Demo,
+	/// An offline user with a chosen username that is never authenticated,
+	/// like the players on an offline mode server
+	Unverified,
 	/// An unknown user kind
 	Unknown(String),
 }
@@ -61,6 +64,19 @@ impl User {
 		}
 	}
 
+	/// Create a new unverified user with a username. It will have the same offline UUID that
+	/// the game would give to that username, and no access token
+	pub fn new_unverified(name: &str) -> anyhow::Result<Self> {
+		if !validate_username(&UserKind::Unverified, name) {
+			bail!("Invalid username '{name}'");
+		}
+		let mut out = Self::new(UserKind::Unverified, name.into());
+		out.name = Some(name.to_string());
+		out.uuid = Some(self::uuid::make_offline_uuid(name));
+
+		Ok(out)
+	}
+
 	/// Get the ID of this user
 	pub fn get_id(&self) -> &UserID {
 		&self.id
@@ -81,6 +97,11 @@ impl User {
 		matches!(self.kind, UserKind::Demo)
 	}
 
+	/// Checks if this user is an unverified user
+	pub fn is_unverified(&self) -> bool {
+		matches!(self.kind, UserKind::Unverified)
+	}
+
 	/// Gets the kind of this user
 	pub fn get_kind(&self) -> &UserKind {
 		&self.kind
@@ -143,6 +164,8 @@ pub struct UserManager {
 	users: HashMap<UserID, User>,
 	/// The MS client ID
 	ms_client_id: ClientId,
+	/// Function that gets the MS client ID when it is needed, overriding the stored one
+	ms_client_id_fn: Option<ClientIdFunction>,
 	/// Whether the manager has been set as offline for authentication
 	offline: bool,
 	/// Custom auth function for plugin injection
@@ -165,6 +188,7 @@ impl UserManager {
 			state: AuthState::Offline,
 			users: HashMap::new(),
 			ms_client_id,
+			ms_client_id_fn: None,
 			offline: false,
 			custom_auth_fn: None,
 		}
@@ -202,6 +226,15 @@ impl UserManager {
 		self.users.iter()
 	}
 
+	/// Lists the users that have valid stored authentication, which can be chosen
+	/// and used without having to sign in again
+	pub fn list_authenticated(&self, paths: &Paths) -> Vec<&User> {
+		self.users
+			.values()
+			.filter(|user| user.is_auth_valid(paths))
+			.collect()
+	}
+
 	/// Remove a user with an ID. Will unchoose the user if it is chosen.
 	pub fn remove_user(&mut self, user_id: &str) {
 		let is_chosen = if let Some(chosen) = self.get_chosen_user() {
@@ -274,7 +307,10 @@ impl UserManager {
 					paths,
 					force: false,
 					offline: self.offline,
-					client_id: self.ms_client_id.clone(),
+					client_id: match &self.ms_client_id_fn {
+						Some(func) => func().context("Failed to get the Microsoft client ID")?,
+						None => self.ms_client_id.clone(),
+					},
 					custom_auth_fn: self.custom_auth_fn.clone(),
 				};
 				user.authenticate(params, o).await?;
@@ -290,9 +326,19 @@ impl UserManager {
 	}
 
 	/// Adds users from another UserManager, and copies it's authentication state
+	/// and client ID function
 	pub fn steal_users(&mut self, other: &Self) {
 		self.users.extend(other.users.clone());
 		self.state = other.state.clone();
+		if let Some(func) = &other.ms_client_id_fn {
+			self.ms_client_id_fn = Some(func.clone());
+		}
+	}
+
+	/// Set a function that gets the MS client ID the first time that it is needed for
+	/// authentication, instead of using the one that the manager was created with
+	pub fn set_ms_client_id_function(&mut self, func: ClientIdFunction) {
+		self.ms_client_id_fn = Some(func);
 	}
 
 	/// Set whether the UserManager is offline. When offline, authentication won't use remote servers
@@ -307,6 +353,9 @@ impl UserManager {
 	}
 }
 
+/// Function that gets the MS client ID
+pub type ClientIdFunction = Arc<dyn Fn() -> anyhow::Result<ClientId>>;
+
 /// Function for custom authentication handling
 pub type CustomAuthFunction =
 	Arc<dyn Fn(&str, &str) -> anyhow::Result<Option<MinecraftUserProfile>>>;
@@ -367,4 +416,19 @@ mod tests {
 		assert!(!users.is_user_chosen());
 		assert!(!users.user_exists("foo"));
 	}
+
+	#[test]
+	fn test_unverified_user() {
+		let user = User::new_unverified("Notch").unwrap();
+		assert!(user.is_unverified());
+		assert_eq!(user.get_name().map(String::as_str), Some("Notch"));
+		assert_eq!(
+			user.get_uuid().map(String::as_str),
+			Some("b50ad385-829d-3141-a216-7e7d7539ba7f")
+		);
+		assert!(user.get_access_token().is_none());
+		assert!(user.is_authenticated());
+
+		assert!(User::new_unverified("not a name").is_err());
+	}
 }
@@ -1,4 +1,5 @@
 use anyhow::ensure;
+use md5::{Digest, Md5};
 
 /// Converts a hexadecimal uuid to the hyphenated form
 pub fn hyphenate_uuid(uuid: &str) -> anyhow::Result<String> {
@@ -14,6 +15,17 @@ pub fn hyphenate_uuid(uuid: &str) -> anyhow::Result<String> {
 	Ok(out)
 }
 
+/// Creates the UUID that the game gives to a player with a username in offline mode.
+/// This is a name-based (version 3) UUID of the name prefixed with `OfflinePlayer:`
+pub fn make_offline_uuid(name: &str) -> String {
+	let mut hash: [u8; 16] = Md5::digest(format!("OfflinePlayer:{name}")).into();
+	// Set the version and variant bits
+	hash[6] = (hash[6] & 0x0f) | 0x30;
+	hash[8] = (hash[8] & 0x3f) | 0x80;
+
+	hyphenate_uuid(&hex::encode(hash)).expect("Hash should be the correct length")
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -26,4 +38,12 @@ mod tests {
 			"8b447756-e180-44d3-bfbf-dbc8df722db4".to_string()
 		);
 	}
+
+	#[test]
+	fn test_offline_uuid() {
+		assert_eq!(
+			make_offline_uuid("Notch"),
+			"b50ad385-829d-3141-a216-7e7d7539ba7f"
+		);
+	}
 }
@@ -11,7 +11,7 @@ use crate::net::game_files::version_manifest::VersionManifest;
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub enum MinecraftLatestVersion {
-	#[serde(rename = "latest")]
+	#[serde(rename = "latest", alias = "latest_release")]
 	/// A release version of Minecraft
 	Release,
 	#[serde(rename = "latest_snapshot")]
@@ -54,6 +54,12 @@ pub enum MinecraftVersion {
 }
 
 impl MinecraftVersion {
+	/// Whether this version is resolved from the latest versions in the manifest,
+	/// and could change between updates
+	pub fn is_latest(&self) -> bool {
+		matches!(self, Self::Latest | Self::LatestSnapshot)
+	}
+
 	/// Get the correct version from the version manifest
 	pub fn get_version(&self, manifest: &VersionManifest) -> anyhow::Result<VersionName> {
 		match self {
@@ -106,6 +112,13 @@ mod tests {
 			MinecraftVersionDeser::Latest(MinecraftLatestVersion::Release)
 		);
 
+		assert_eq!(
+			serde_json::from_str::<Test>(r#"{"version": "latest_release"}"#)
+				.unwrap()
+				.version,
+			MinecraftVersionDeser::Latest(MinecraftLatestVersion::Release)
+		);
+
 		assert_eq!(
 			serde_json::from_str::<Test>(r#"{"version": "latest_snapshot"}"#)
 				.unwrap()
@@ -4,7 +4,8 @@ use std::sync::Arc;
 use anyhow::Context;
 use mcvm_shared::later::Later;
 use mcvm_shared::output::MCVMOutput;
-use mcvm_shared::output::{MessageContents, MessageLevel};
+use mcvm_shared::output::{MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent};
+use mcvm_shared::util::ARCH_STRING;
 use mcvm_shared::versions::VersionInfo;
 
 use crate::config::BrandingProperties;
@@ -51,7 +52,7 @@ impl<'inner, 'params> InstalledVersion<'inner, 'params> {
 		&mut self,
 		config: InstanceConfiguration,
 		o: &mut impl MCVMOutput,
-	) -> anyhow::Result<Instance> {
+	) -> anyhow::Result<Instance<'_>> {
 		let params = InstanceParameters {
 			version: &self.inner.version,
 			version_manifest: &self.inner.version_manifest,
@@ -85,8 +86,8 @@ impl<'inner, 'params> InstalledVersion<'inner, 'params> {
 			version: &self.inner.version,
 			paths: self.params.paths,
 			req_client: self.params.req_client,
-			version_manifest: &self.inner.version_manifest,
 			update_manager: self.params.update_manager,
+			arch: ARCH_STRING,
 		};
 		self.inner.client_assets_and_libs.load(params, o).await
 	}
@@ -109,6 +110,7 @@ impl InstalledVersionInner {
 	) -> anyhow::Result<Self> {
 		// Get the client meta
 		o.start_process();
+		o.display_update_phase(UpdatePhase::ClientJson, UpdatePhaseEvent::Start);
 		o.display(
 			MessageContents::StartProcess("Obtaining client metadata".into()),
 			MessageLevel::Important,
@@ -129,6 +131,7 @@ impl InstalledVersionInner {
 			MessageContents::Success("Client meta obtained".into()),
 			MessageLevel::Important,
 		);
+		o.display_update_phase(UpdatePhase::ClientJson, UpdatePhaseEvent::End);
 		o.end_process();
 
 		Ok(Self {
@@ -258,12 +261,15 @@ pub(crate) struct LoadVersionManifestParameters<'a> {
 /// Data for client assets and libraries that are only
 /// loaded when a client needs them
 pub(crate) struct ClientAssetsAndLibraries {
-	loaded: bool,
+	/// The architectures that libraries have been loaded for. Assets are loaded with the first one
+	loaded_archs: Vec<String>,
 }
 
 impl ClientAssetsAndLibraries {
 	pub fn new() -> Self {
-		Self { loaded: false }
+		Self {
+			loaded_archs: Vec::new(),
+		}
 	}
 
 	pub async fn load(
@@ -271,14 +277,17 @@ impl ClientAssetsAndLibraries {
 		params: ClientAssetsAndLibsParameters<'_>,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<()> {
-		if self.loaded {
+		if self.loaded_archs.iter().any(|x| x == params.arch) {
 			return Ok(());
 		}
+		if !self.loaded_archs.is_empty() {
+			return self.load_libraries(params, o).await;
+		}
+
 		let result = assets::get(
 			params.client_meta,
 			params.paths,
 			params.version,
-			&params.version_manifest.list,
 			params.update_manager,
 			params.req_client,
 			o,
@@ -287,10 +296,19 @@ impl ClientAssetsAndLibraries {
 		.context("Failed to get game assets")?;
 		params.update_manager.add_result(result);
 
+		self.load_libraries(params, o).await
+	}
+
+	async fn load_libraries(
+		&mut self,
+		params: ClientAssetsAndLibsParameters<'_>,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
 		let result = libraries::get(
 			params.client_meta,
 			params.paths,
 			params.version,
+			params.arch,
 			params.update_manager,
 			params.req_client,
 			o,
@@ -299,7 +317,7 @@ impl ClientAssetsAndLibraries {
 		.context("Failed to get game libraries")?;
 		params.update_manager.add_result(result);
 
-		self.loaded = true;
+		self.loaded_archs.push(params.arch.to_string());
 		Ok(())
 	}
 }
@@ -310,6 +328,7 @@ pub(crate) struct ClientAssetsAndLibsParameters<'a> {
 	pub version: &'a VersionName,
 	pub paths: &'a Paths,
 	pub req_client: &'a reqwest::Client,
-	pub version_manifest: &'a VersionManifestAndList,
 	pub update_manager: &'a mut UpdateManager,
+	/// The architecture to get native libraries for
+	pub arch: &'a str,
 }
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::path::PathBuf;
 
 use anyhow::{anyhow, Context};
 use mcvm_core::io::java::classpath::Classpath;
@@ -131,6 +132,13 @@ pub struct MainLibrary {
 	maven: String,
 }
 
+impl MainLibrary {
+	/// Get the version of this library from its Maven coordinates
+	pub fn get_version(&self) -> Option<String> {
+		MavenLibraryParts::parse_from_str(&self.maven).map(|x| x.version)
+	}
+}
+
 /// The struct of libraries for different sides
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Libraries {
@@ -167,6 +175,15 @@ impl MainClass {
 	}
 }
 
+/// Get the path where the Fabric/Quilt metadata for a Minecraft version is cached
+pub fn get_meta_path(version: &str, mode: &Mode, paths: &Paths) -> PathBuf {
+	let mode_lowercase = mode.to_string().to_lowercase();
+	paths
+		.internal
+		.join("fabric_quilt")
+		.join(format!("meta_{mode_lowercase}_{version}.json"))
+}
+
 /// Get the Fabric/Quilt metadata file
 pub async fn get_meta(
 	version: &str,
@@ -179,11 +196,7 @@ pub async fn get_meta(
 		Mode::Fabric => format!("https://meta.fabricmc.net/v2/versions/loader/{version}"),
 		Mode::Quilt => format!("https://meta.quiltmc.org/v3/versions/loader/{version}"),
 	};
-	let mode_lowercase = mode.to_string().to_lowercase();
-	let path = paths
-		.internal
-		.join("fabric_quilt")
-		.join(format!("meta_{mode_lowercase}_{version}.json"));
+	let path = get_meta_path(version, mode, paths);
 	files::create_leading_dirs_async(&path)
 		.await
 		.context("Failed to create parent directories for Fabric/Quilt meta")?;
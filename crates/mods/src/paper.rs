@@ -1,8 +1,10 @@
+use std::time::Duration;
 use std::{fmt::Display, path::PathBuf};
 
 use anyhow::{anyhow, bail, Context};
 use mcvm_core::{net::download, MCVMCore};
-use mcvm_shared::{output::MCVMOutput, versions::VersionInfo, Side};
+use mcvm_shared::output::{MCVMOutput, NoOp};
+use mcvm_shared::{versions::VersionInfo, Side};
 use reqwest::Client;
 use serde::Deserialize;
 
@@ -61,13 +63,23 @@ pub async fn install_from_core(
 		bail!("Velocity is a proxy and cannot be used in the install_from_core function");
 	}
 
-	let build_num = get_newest_build(mode, &version_info.version, core.get_client())
-		.await
-		.context(format!("Failed to get newest {mode} build"))?;
-	let jar_file_name =
-		get_jar_file_name(mode, &version_info.version, build_num, core.get_client())
-			.await
-			.context(format!("Failed to get the API name of the {mode} JAR file"))?;
+	let build_num = get_newest_build(
+		mode,
+		&version_info.version,
+		core.get_paths(),
+		core.get_client(),
+	)
+	.await
+	.context(format!("Failed to get newest {mode} build"))?;
+	let jar_file_name = get_jar_file_name(
+		mode,
+		&version_info.version,
+		build_num,
+		core.get_paths(),
+		core.get_client(),
+	)
+	.await
+	.context(format!("Failed to get the API name of the {mode} JAR file"))?;
 	download_server_jar(
 		mode,
 		&version_info.version,
@@ -87,13 +99,13 @@ pub async fn install_from_core(
 
 /// Install Velocity, returning the path to the JAR file and the main class
 pub async fn install_velocity(paths: &Paths, client: &Client) -> anyhow::Result<(PathBuf, String)> {
-	let version = get_newest_version(Mode::Velocity, client)
+	let version = get_newest_version(Mode::Velocity, paths, client)
 		.await
 		.context("Failed to get newest Velocity version")?;
-	let build_num = get_newest_build(Mode::Velocity, &version, client)
+	let build_num = get_newest_build(Mode::Velocity, &version, paths, client)
 		.await
 		.context("Failed to get newest Velocity build version")?;
-	let file_name = get_jar_file_name(Mode::Velocity, &version, build_num, client)
+	let file_name = get_jar_file_name(Mode::Velocity, &version, build_num, paths, client)
 		.await
 		.context("Failed to get Velocity build file name")?;
 
@@ -115,9 +127,15 @@ pub async fn install_velocity(paths: &Paths, client: &Client) -> anyhow::Result<
 }
 
 /// Get the newest version of a PaperMC project
-pub async fn get_newest_version(mode: Mode, client: &Client) -> anyhow::Result<String> {
+pub async fn get_newest_version(
+	mode: Mode,
+	paths: &Paths,
+	client: &Client,
+) -> anyhow::Result<String> {
 	let url = format!("https://api.papermc.io/v2/projects/{}", mode.to_str(),);
-	let resp: ProjectInfoResponse = download::json(url, client).await?;
+	let cache_path = paths.api_cache.join(format!("{}.json", mode.to_str()));
+	let resp: ProjectInfoResponse =
+		download::json_cached(url, &cache_path, Some(Duration::ZERO), client, &mut NoOp).await?;
 
 	let version = resp
 		.versions
@@ -133,12 +151,21 @@ struct ProjectInfoResponse {
 }
 
 /// Get the newest build number of a PaperMC project version
-pub async fn get_newest_build(mode: Mode, version: &str, client: &Client) -> anyhow::Result<u16> {
+pub async fn get_newest_build(
+	mode: Mode,
+	version: &str,
+	paths: &Paths,
+	client: &Client,
+) -> anyhow::Result<u16> {
 	let url = format!(
 		"https://api.papermc.io/v2/projects/{}/versions/{version}",
 		mode.to_str(),
 	);
-	let resp: VersionInfoResponse = download::json(url, client).await?;
+	let cache_path = paths
+		.api_cache
+		.join(format!("{}_{version}.json", mode.to_str()));
+	let resp: VersionInfoResponse =
+		download::json_cached(url, &cache_path, Some(Duration::ZERO), client, &mut NoOp).await?;
 
 	let build = resp
 		.builds
@@ -161,6 +188,7 @@ pub async fn get_jar_file_name(
 	mode: Mode,
 	version: &str,
 	build_num: u16,
+	paths: &Paths,
 	client: &Client,
 ) -> anyhow::Result<String> {
 	let num_str = build_num.to_string();
@@ -168,7 +196,12 @@ pub async fn get_jar_file_name(
 		"https://api.papermc.io/v2/projects/{}/versions/{version}/builds/{num_str}",
 		mode.to_str(),
 	);
-	let resp: BuildInfoResponse = download::json(url, client).await?;
+	// Builds never change once they are published, so this cache doesn't need to expire
+	let cache_path = paths
+		.api_cache
+		.join(format!("{}_{version}_{num_str}.json", mode.to_str()));
+	let resp: BuildInfoResponse =
+		download::json_cached(url, &cache_path, None, client, &mut NoOp).await?;
 
 	Ok(resp.downloads.application.name)
 }
@@ -1,15 +1,41 @@
 use std::fs::File;
-use std::io::{BufWriter, Cursor, Write};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use anyhow::{ensure, Context};
-use mcvm_shared::output::MessageContents;
-use reqwest::{IntoUrl, Url};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel, UpdatePhaseEvent};
+use mcvm_shared::translate;
+use reqwest::header::{
+	HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RETRY_AFTER,
+};
+use reqwest::{IntoUrl, NoProxy, Proxy, RequestBuilder, StatusCode, Url};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 
 /// Re-export of reqwest::Client for users of this download module
 pub use reqwest::Client;
 
+/// Creates a client to use for all requests. An explicit proxy URL can be given, which overrides
+/// the HTTP_PROXY and HTTPS_PROXY environment variables. Hosts in NO_PROXY will always bypass the proxy.
+/// The client should be created once and shared so that connections can be pooled.
+/// A custom User-Agent can be given, which otherwise defaults to the one from `make_user_agent`
+pub fn make_client(proxy: Option<&str>, user_agent: Option<&str>) -> anyhow::Result<Client> {
+	let user_agent = match user_agent {
+		Some(user_agent) => user_agent.to_string(),
+		None => make_user_agent(env!("CARGO_PKG_VERSION")),
+	};
+	let mut builder = Client::builder().user_agent(user_agent);
+	// reqwest already uses the proxy environment variables, so they only need to be
+	// overridden when a proxy is configured explicitly
+	if let Some(proxy) = proxy {
+		let proxy = Proxy::all(proxy).context("Invalid proxy URL")?;
+		builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+	}
+
+	builder.build().context("Failed to create HTTP client")
+}
+
 /// Sensible open file descriptor limit for asynchronous transfers
 #[cfg(target_os = "windows")]
 const FD_SENSIBLE_LIMIT: usize = 128;
@@ -17,26 +43,38 @@ const FD_SENSIBLE_LIMIT: usize = 128;
 #[cfg(not(target_os = "windows"))]
 const FD_SENSIBLE_LIMIT: usize = 128;
 
-/// Get the sensible limit for asynchronous transfers
+/// Get the sensible limit for asynchronous transfers. Invalid or zero values
+/// of the override environment variable are ignored
 pub fn get_transfer_limit() -> usize {
-	if let Ok(env) = std::env::var("MCVM_TRANSFER_LIMIT") {
-		env.parse().unwrap_or_default()
-	} else {
-		FD_SENSIBLE_LIMIT
-	}
+	std::env::var("MCVM_TRANSFER_LIMIT")
+		.ok()
+		.and_then(|x| x.parse().ok())
+		.filter(|x| *x > 0)
+		.unwrap_or(FD_SENSIBLE_LIMIT)
 }
 
-/// The User-Agent header for requests
-fn user_agent() -> String {
-	let version = env!("CARGO_PKG_VERSION");
-	format!("mcvm_core_{version}")
+/// Creates the default User-Agent to send with requests, for the given version of MCVM.
+/// It includes a link to the repository as contact information, which APIs like Modrinth ask for
+pub fn make_user_agent(version: &str) -> String {
+	format!("mcvm/{version} (+{})", env!("CARGO_PKG_REPOSITORY"))
 }
 
 /// Downloads data from a remote location
 pub async fn download(url: impl IntoUrl, client: &Client) -> anyhow::Result<reqwest::Response> {
-	let resp = client
-		.get(url)
-		.header("User-Agent", user_agent())
+	download_with_token(url, None, client).await
+}
+
+/// Downloads data from a remote location, sending a bearer token for authentication if one is given
+pub async fn download_with_token(
+	url: impl IntoUrl,
+	bearer_token: Option<&str>,
+	client: &Client,
+) -> anyhow::Result<reqwest::Response> {
+	let mut req = client.get(url);
+	if let Some(token) = bearer_token {
+		req = req.bearer_auth(token);
+	}
+	let resp = req
 		.send()
 		.await
 		.context("Failed to send request")?
@@ -46,6 +84,104 @@ pub async fn download(url: impl IntoUrl, client: &Client) -> anyhow::Result<reqw
 	Ok(resp)
 }
 
+/// The maximum number of times that a rate-limited request is retried
+const MAX_RATE_LIMIT_RETRIES: u32 = 5;
+/// The longest time to wait before retrying a rate-limited request
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
+/// Downloads data from a remote location. If the server responds that too many requests
+/// have been made, the request is retried after waiting for the rate limit to reset
+pub async fn download_rate_limited(
+	url: impl IntoUrl,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<reqwest::Response> {
+	send_rate_limited(url, &CacheValidators::default(), None, client, o).await
+}
+
+/// Sends a request with the given cache validators, retrying if the request is rate-limited.
+/// The response may have a Not Modified status if the validators are still valid
+async fn send_rate_limited(
+	url: impl IntoUrl,
+	validators: &CacheValidators,
+	bearer_token: Option<&str>,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<reqwest::Response> {
+	let url = url.into_url().context("Invalid URL")?;
+	let mut attempt = 0;
+	loop {
+		let mut req = client.get(url.clone());
+		if let Some(token) = bearer_token {
+			req = req.bearer_auth(token);
+		}
+		let resp = validators
+			.add_to_request(req)
+			.send()
+			.await
+			.context("Failed to send request")?;
+
+		if resp.status() == StatusCode::TOO_MANY_REQUESTS && attempt < MAX_RATE_LIMIT_RETRIES {
+			let wait = get_rate_limit_wait(resp.headers(), attempt);
+			o.display(
+				MessageContents::Simple(translate!(
+					o,
+					RateLimited,
+					"host" = url.host_str().unwrap_or_default(),
+					"seconds" = &wait.as_secs().to_string()
+				)),
+				MessageLevel::Debug,
+			);
+			tokio::time::sleep(wait).await;
+			attempt += 1;
+			continue;
+		}
+
+		return resp.error_for_status().context("Server reported an error");
+	}
+}
+
+/// Get how long to wait before retrying a rate-limited request. The Retry-After and
+/// X-Ratelimit-Reset headers are used if the server sent them, and otherwise the wait
+/// grows exponentially with each attempt
+fn get_rate_limit_wait(headers: &HeaderMap, attempt: u32) -> Duration {
+	let from_headers = [RETRY_AFTER.as_str(), "x-ratelimit-reset"]
+		.into_iter()
+		.find_map(|x| headers.get(x)?.to_str().ok()?.trim().parse().ok())
+		.map(Duration::from_secs);
+	let wait = from_headers.unwrap_or_else(|| Duration::from_secs(1 << attempt));
+
+	wait.min(MAX_RATE_LIMIT_WAIT)
+}
+
+/// Downloads and returns text, retrying if the request is rate-limited
+pub async fn text_rate_limited(
+	url: impl IntoUrl,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<String> {
+	download_rate_limited(url, client, o)
+		.await
+		.context("Failed to download")?
+		.text()
+		.await
+		.context("Failed to convert download to text")
+}
+
+/// Downloads and deserializes the contents into JSON, retrying if the request is rate-limited
+pub async fn json_rate_limited<T: DeserializeOwned>(
+	url: impl IntoUrl,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<T> {
+	download_rate_limited(url, client, o)
+		.await
+		.context("Failed to download JSON data")?
+		.json()
+		.await
+		.context("Failed to parse JSON")
+}
+
 /// Downloads and returns text
 pub async fn text(url: impl IntoUrl, client: &Client) -> anyhow::Result<String> {
 	let text = download(url, client)
@@ -70,6 +206,76 @@ pub async fn bytes(url: impl IntoUrl, client: &Client) -> anyhow::Result<bytes::
 	Ok(bytes)
 }
 
+/// Checks that a URL can be downloaded from without downloading its contents. A HEAD request
+/// is sent, and a GET request is sent instead if the server doesn't allow HEAD requests
+pub async fn check_url_reachable(url: impl IntoUrl, client: &Client) -> anyhow::Result<()> {
+	let url = url.into_url().context("Invalid URL")?;
+	let resp = client
+		.head(url.clone())
+		.send()
+		.await
+		.context("Failed to send request")?;
+	let resp = if resp.status() == StatusCode::METHOD_NOT_ALLOWED {
+		// The body is never read, so only the headers are downloaded
+		client
+			.get(url)
+			.send()
+			.await
+			.context("Failed to send request")?
+	} else {
+		resp
+	};
+	resp.error_for_status()
+		.context("Server reported an error")?;
+
+	Ok(())
+}
+
+/// The maximum number of times that a download is retried after a transient error
+const MAX_TRANSIENT_RETRIES: u32 = 3;
+
+/// Downloads and returns bytes. Downloads that fail because of a connection problem
+/// or an error on the server are retried a few times, waiting longer after each attempt
+pub async fn bytes_retrying(url: impl IntoUrl, client: &Client) -> anyhow::Result<bytes::Bytes> {
+	let url = url.into_url().context("Invalid URL")?;
+	let mut attempt = 0;
+	loop {
+		match try_bytes(url.clone(), client).await {
+			Err(e) if attempt < MAX_TRANSIENT_RETRIES && is_transient_error(&e) => {
+				tokio::time::sleep(Duration::from_millis(500 << attempt)).await;
+				attempt += 1;
+			}
+			result => return result.context("Failed to download"),
+		}
+	}
+}
+
+/// Downloads bytes once, without any retries
+async fn try_bytes(url: Url, client: &Client) -> Result<bytes::Bytes, reqwest::Error> {
+	client
+		.get(url)
+		.send()
+		.await?
+		.error_for_status()?
+		.bytes()
+		.await
+}
+
+/// Whether a request error is likely to go away if the request is made again
+fn is_transient_error(e: &reqwest::Error) -> bool {
+	if let Some(status) = e.status() {
+		return is_transient_status(status);
+	}
+	e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+}
+
+/// Whether an error status from a server is likely to go away if the request is made again
+fn is_transient_status(status: StatusCode) -> bool {
+	status.is_server_error()
+		|| status == StatusCode::TOO_MANY_REQUESTS
+		|| status == StatusCode::REQUEST_TIMEOUT
+}
+
 /// Downloads and puts the contents in a file
 pub async fn file(
 	url: impl IntoUrl,
@@ -89,6 +295,140 @@ pub async fn file(
 	Ok(())
 }
 
+/// The number of downloaded chunks that can be waiting to be read by a stream reader
+const STREAM_CHANNEL_SIZE: usize = 16;
+
+/// Downloads data and reads it with a blocking function as it arrives, without storing the
+/// whole download in memory or on disk. The function is run on a blocking thread and is given
+/// a reader for the response body. If the function stops early, the rest of the download is cancelled
+pub async fn stream<T: Send + 'static>(
+	url: impl IntoUrl,
+	client: &Client,
+	f: impl FnOnce(StreamReader) -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+	let mut resp = download(url, client).await?;
+
+	let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_CHANNEL_SIZE);
+	let reader = StreamReader {
+		receiver,
+		current: bytes::Bytes::new(),
+	};
+	let task = tokio::task::spawn_blocking(move || f(reader));
+
+	loop {
+		let chunk = match resp.chunk().await {
+			Ok(Some(chunk)) => Ok(chunk),
+			Ok(None) => break,
+			Err(e) => Err(std::io::Error::other(e)),
+		};
+		let failed = chunk.is_err();
+		// If the reader is gone then the function has already finished
+		if sender.send(chunk).await.is_err() || failed {
+			break;
+		}
+	}
+	// Dropping the sender tells the reader that the download has ended
+	std::mem::drop(sender);
+
+	task.await.context("Failed to join reading task")?
+}
+
+/// A blocking reader for the body of a download that is being streamed
+pub struct StreamReader {
+	receiver: tokio::sync::mpsc::Receiver<std::io::Result<bytes::Bytes>>,
+	current: bytes::Bytes,
+}
+
+impl Read for StreamReader {
+	fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		while self.current.is_empty() {
+			match self.receiver.blocking_recv() {
+				Some(chunk) => self.current = chunk?,
+				None => return Ok(0),
+			}
+		}
+
+		let len = buf.len().min(self.current.len());
+		buf[..len].copy_from_slice(&self.current.split_to(len));
+		Ok(len)
+	}
+}
+
+/// Values from a previous response that can be sent with a request to check
+/// if the remote contents have changed since then
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct CacheValidators {
+	/// The ETag header of the response
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub etag: Option<String>,
+	/// The Last-Modified header of the response
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+	/// Get the validators from the headers of a response
+	fn from_headers(headers: &HeaderMap) -> Self {
+		let get = |name| {
+			headers
+				.get(name)
+				.and_then(|x| x.to_str().ok())
+				.map(|x| x.to_string())
+		};
+		Self {
+			etag: get(ETAG),
+			last_modified: get(LAST_MODIFIED),
+		}
+	}
+
+	/// Add the conditional request headers for these validators to a request
+	fn add_to_request(&self, mut req: RequestBuilder) -> RequestBuilder {
+		if let Some(etag) = &self.etag {
+			req = req.header(IF_NONE_MATCH, etag);
+		}
+		if let Some(last_modified) = &self.last_modified {
+			req = req.header(IF_MODIFIED_SINCE, last_modified);
+		}
+		req
+	}
+}
+
+/// Downloads bytes only if they have changed since the response that the validators came from.
+/// Returns None if the server reports that the contents haven't been modified. Otherwise,
+/// returns the contents along with the validators to use for the next request.
+/// A bearer token can be given to authenticate with the server. Rate-limited requests are retried
+pub async fn bytes_if_modified(
+	url: impl IntoUrl,
+	validators: &CacheValidators,
+	bearer_token: Option<&str>,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Option<(bytes::Bytes, CacheValidators)>> {
+	let resp = send_rate_limited(url, validators, bearer_token, client, o).await?;
+	if resp.status() == StatusCode::NOT_MODIFIED {
+		return Ok(None);
+	}
+
+	let validators = CacheValidators::from_headers(resp.headers());
+	let bytes = resp
+		.bytes()
+		.await
+		.context("Failed to convert download to raw bytes")?;
+
+	Ok(Some((bytes, validators)))
+}
+
+/// Checks if a download failed because the server rejected the credentials, or lack of them,
+/// that were sent with the request
+pub fn is_unauthorized(error: &anyhow::Error) -> bool {
+	error.chain().any(|x| {
+		x.downcast_ref::<reqwest::Error>()
+			.and_then(|x| x.status())
+			.is_some_and(|x| x == StatusCode::UNAUTHORIZED || x == StatusCode::FORBIDDEN)
+	})
+}
+
 /// Downloads and deserializes the contents into JSON
 pub async fn json<T: DeserializeOwned>(url: impl IntoUrl, client: &Client) -> anyhow::Result<T> {
 	download(url, client)
@@ -99,6 +439,103 @@ pub async fn json<T: DeserializeOwned>(url: impl IntoUrl, client: &Client) -> an
 		.context("Failed to parse JSON")
 }
 
+/// Downloads and deserializes JSON, using a copy cached on disk at `cache_path`. The cache is
+/// used without any request while it is younger than `max_age`, and forever if `max_age` is None,
+/// which is useful when working offline. Once it is older, the ETag and Last-Modified headers that
+/// are stored next to the cache are used to make a conditional request, so that the contents are only
+/// downloaded again if they changed. The cache will still be used if the request fails.
+/// Rate-limited requests are retried
+pub async fn json_cached<T: DeserializeOwned>(
+	url: impl IntoUrl,
+	cache_path: &Path,
+	max_age: Option<Duration>,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<T> {
+	if is_cache_fresh(cache_path, max_age) {
+		if let Ok(value) = read_json_cache(cache_path) {
+			return Ok(value);
+		}
+	}
+
+	let url = url.into_url().context("Invalid URL")?;
+	let validators_path = get_validators_path(cache_path);
+	// The validators are useless without the cached contents that they describe
+	let validators = if cache_path.exists() {
+		read_json_cache(&validators_path).unwrap_or_default()
+	} else {
+		CacheValidators::default()
+	};
+
+	let result = match bytes_if_modified(url.clone(), &validators, None, client, o).await {
+		Ok(result) => result,
+		Err(e) => {
+			// Fall back to an outdated cache instead of failing entirely
+			return read_json_cache(cache_path)
+				.map_err(|_| e)
+				.context("Failed to download JSON data");
+		}
+	};
+	if result.is_none() {
+		if let Ok(value) = read_json_cache(cache_path) {
+			// Restart the max age now that the cache is known to be up to date
+			let _ = File::options()
+				.append(true)
+				.open(cache_path)
+				.and_then(|x| x.set_modified(SystemTime::now()));
+			return Ok(value);
+		}
+	}
+
+	// Download the contents again if they changed or the cached copy is invalid
+	let (bytes, validators) = match result {
+		Some(result) => result,
+		None => bytes_if_modified(url, &CacheValidators::default(), None, client, o)
+			.await?
+			.context("Server did not return the contents")?,
+	};
+	let value = serde_json::from_slice(&bytes).context("Failed to parse JSON")?;
+
+	if let Some(parent) = cache_path.parent() {
+		std::fs::create_dir_all(parent).context("Failed to create cache directory")?;
+	}
+	std::fs::write(cache_path, &bytes).context("Failed to write JSON to cache")?;
+	let validators =
+		serde_json::to_vec(&validators).context("Failed to serialize cache validators")?;
+	std::fs::write(&validators_path, validators).context("Failed to write cache validators")?;
+
+	Ok(value)
+}
+
+/// Get the path to the file that stores the validators for a cached response
+fn get_validators_path(cache_path: &Path) -> PathBuf {
+	let mut file_name = cache_path.file_name().unwrap_or_default().to_owned();
+	file_name.push(".validators");
+	cache_path.with_file_name(file_name)
+}
+
+/// Checks if a cached file exists and is younger than the max age
+fn is_cache_fresh(path: &Path, max_age: Option<Duration>) -> bool {
+	let Ok(meta) = path.metadata() else {
+		return false;
+	};
+	let Some(max_age) = max_age else {
+		return true;
+	};
+	let Ok(modified) = meta.modified() else {
+		return false;
+	};
+	SystemTime::now()
+		.duration_since(modified)
+		.is_ok_and(|age| age < max_age)
+}
+
+/// Reads and deserializes a cached JSON file
+fn read_json_cache<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+	let file = File::open(path).context("Failed to open cached JSON")?;
+	serde_json::from_reader(BufReader::new(file)).context("Failed to parse cached JSON")
+}
+
 /// A persistent single download that can be used to track progress
 pub struct ProgressiveDownload<W: Write> {
 	response: reqwest::Response,
@@ -132,9 +569,21 @@ impl<W: Write> ProgressiveDownload<W> {
 
 	/// Get the progress message corresponding to this download
 	pub fn get_progress(&self) -> MessageContents {
+		let (current, total) = self.get_progress_amounts();
+		MessageContents::Progress { current, total }
+	}
+
+	/// Get the progress of this download as an event for the update phase that it is part of
+	pub fn get_phase_progress(&self) -> UpdatePhaseEvent {
+		let (current, total) = self.get_progress_amounts();
+		UpdatePhaseEvent::Progress { current, total }
+	}
+
+	/// Get the current and total amounts for the progress of this download
+	fn get_progress_amounts(&self) -> (u32, u32) {
 		let current = (self.get_downloaded() / 2) as u32;
 		let total = (self.get_total_length() / 2) as u32;
-		MessageContents::Progress { current, total }
+		(current, total)
 	}
 
 	/// Poll the download
@@ -215,3 +664,90 @@ pub fn validate_url(url: &str) -> anyhow::Result<()> {
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_cache_validators_from_headers() {
+		let mut headers = HeaderMap::new();
+		headers.insert(ETAG, "\"abc123\"".parse().unwrap());
+		let validators = CacheValidators::from_headers(&headers);
+		assert_eq!(validators.etag.as_deref(), Some("\"abc123\""));
+		assert_eq!(validators.last_modified, None);
+	}
+
+	#[test]
+	fn test_validators_path() {
+		assert_eq!(
+			get_validators_path(Path::new("cache/paper.json")),
+			PathBuf::from("cache/paper.json.validators")
+		);
+	}
+
+	#[test]
+	fn test_rate_limit_wait() {
+		let mut headers = HeaderMap::new();
+		assert_eq!(get_rate_limit_wait(&headers, 0), Duration::from_secs(1));
+		assert_eq!(get_rate_limit_wait(&headers, 3), Duration::from_secs(8));
+		assert_eq!(get_rate_limit_wait(&headers, 10), MAX_RATE_LIMIT_WAIT);
+
+		headers.insert("x-ratelimit-reset", "12".parse().unwrap());
+		assert_eq!(get_rate_limit_wait(&headers, 3), Duration::from_secs(12));
+		headers.insert(RETRY_AFTER, "5".parse().unwrap());
+		assert_eq!(get_rate_limit_wait(&headers, 3), Duration::from_secs(5));
+		headers.insert(RETRY_AFTER, "3600".parse().unwrap());
+		assert_eq!(get_rate_limit_wait(&headers, 0), MAX_RATE_LIMIT_WAIT);
+	}
+
+	#[test]
+	fn test_stream_reader() {
+		let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_CHANNEL_SIZE);
+		sender.try_send(Ok(bytes::Bytes::from("hello "))).unwrap();
+		sender.try_send(Ok(bytes::Bytes::new())).unwrap();
+		sender.try_send(Ok(bytes::Bytes::from("world"))).unwrap();
+		std::mem::drop(sender);
+		let mut reader = StreamReader {
+			receiver,
+			current: bytes::Bytes::new(),
+		};
+
+		let mut buf = [0; 4];
+		assert_eq!(reader.read(&mut buf).unwrap(), 4);
+		assert_eq!(&buf, b"hell");
+		let mut rest = String::new();
+		reader.read_to_string(&mut rest).unwrap();
+		assert_eq!(rest, "o world");
+	}
+
+	#[test]
+	fn test_explicit_proxy() {
+		assert!(make_client(Some("http://localhost:8080"), None).is_ok());
+	}
+
+	#[test]
+	fn test_user_agent() {
+		assert_eq!(
+			make_user_agent("1.0.0"),
+			"mcvm/1.0.0 (+https://github.com/CarbonSmasher/mcvm)"
+		);
+		assert!(make_client(None, Some("my-launcher/2.0 (me@example.com)")).is_ok());
+	}
+
+	#[test]
+	fn test_cache_freshness() {
+		let path = std::env::temp_dir().join("mcvm_test_cache_freshness.json");
+		let _ = std::fs::remove_file(&path);
+		assert!(!is_cache_fresh(&path, None));
+
+		std::fs::write(&path, "[1, 2, 3]").unwrap();
+		assert!(is_cache_fresh(&path, None));
+		assert!(is_cache_fresh(&path, Some(Duration::from_secs(3600))));
+		assert!(!is_cache_fresh(&path, Some(Duration::ZERO)));
+		let value: Vec<u8> = read_json_cache(&path).unwrap();
+		assert_eq!(value, vec![1, 2, 3]);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Duration;
+
 use crate::download;
 use anyhow::{anyhow, Context};
+use futures_util::stream::{self, StreamExt};
 use mcvm_shared::modifications::{Modloader, ServerType};
+use mcvm_shared::output::{MCVMOutput, MessageBuffer};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
@@ -66,18 +72,56 @@ pub enum ProjectType {
 }
 
 /// Get a project from the API
-pub async fn get_project(project_id: &str, client: &Client) -> anyhow::Result<Project> {
+pub async fn get_project(
+	project_id: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Project> {
 	let url = format_get_project_url(project_id);
-	let out = download::json(url, client)
+	let out = download::json_rate_limited(url, client, o)
 		.await
 		.context("Failed to download Modrinth project")?;
 	Ok(out)
 }
 
+/// Get a project from the API, reusing a copy cached in the given directory
+/// if the API reports that it hasn't changed
+pub async fn get_project_cached(
+	project_id: &str,
+	cache_dir: &Path,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Project> {
+	let url = format_get_project_url(project_id);
+	let cache_path = cache_dir.join(format!("project_{project_id}.json"));
+	download::json_cached(url, &cache_path, Some(Duration::ZERO), client, o)
+		.await
+		.context("Failed to download Modrinth project")
+}
+
+/// Get all of the versions of a project from the API, reusing a copy cached in the given
+/// directory if the API reports that they haven't changed
+pub async fn get_project_versions_cached(
+	project_id: &str,
+	cache_dir: &Path,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Vec<Version>> {
+	let url = format!("https://api.modrinth.com/v2/project/{project_id}/version");
+	let cache_path = cache_dir.join(format!("project_{project_id}_versions.json"));
+	download::json_cached(url, &cache_path, Some(Duration::ZERO), client, o)
+		.await
+		.context("Failed to download Modrinth project versions")
+}
+
 /// Get the raw response of a project from the API
-pub async fn get_project_raw(project_id: &str, client: &Client) -> anyhow::Result<String> {
+pub async fn get_project_raw(
+	project_id: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<String> {
 	let url = format_get_project_url(project_id);
-	let out = download::text(url, client)
+	let out = download::text_rate_limited(url, client, o)
 		.await
 		.context("Failed to download Modrinth project")?;
 	Ok(out)
@@ -92,12 +136,13 @@ fn format_get_project_url(project_id: &str) -> String {
 pub async fn get_multiple_projects(
 	projects: &[String],
 	client: &Client,
+	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<Vec<Project>> {
 	// Use the multiple-projects API endpoint as it's faster
 	let param = serde_json::to_string(projects)
 		.context("Failed to convert project list to API parameter")?;
 	let url = format!("https://api.modrinth.com/v2/projects?ids={param}");
-	download::json(url, client).await
+	download::json_rate_limited(url, client, o).await
 }
 
 /// Release channel for a Modrinth project version
@@ -189,11 +234,20 @@ pub enum KnownLoader {
 impl Loader {
 	/// Checks if this loader matches an mcvm modloader
 	pub fn matches_modloader(&self, modloader: Modloader) -> bool {
-		match modloader {
-			Modloader::Forge => matches!(self, Self::Known(KnownLoader::Forge)),
-			Modloader::Fabric => matches!(self, Self::Known(KnownLoader::Fabric)),
-			Modloader::Quilt => matches!(self, Self::Known(KnownLoader::Quilt)),
-			_ => true,
+		self.get_modloader_preference(&modloader).is_some()
+	}
+
+	/// Gets how preferred mods for this loader are on an mcvm modloader, where lower is better.
+	/// Returns None if the modloader can't load mods for this loader
+	pub fn get_modloader_preference(&self, modloader: &Modloader) -> Option<u8> {
+		match (modloader, self) {
+			(Modloader::Forge, Self::Known(KnownLoader::Forge)) => Some(0),
+			(Modloader::Fabric, Self::Known(KnownLoader::Fabric)) => Some(0),
+			(Modloader::Quilt, Self::Known(KnownLoader::Quilt)) => Some(0),
+			// Quilt can run Fabric mods, but native Quilt mods are better
+			(Modloader::Quilt, Self::Known(KnownLoader::Fabric)) => Some(1),
+			(Modloader::Forge | Modloader::Fabric | Modloader::Quilt, _) => None,
+			_ => Some(0),
 		}
 	}
 
@@ -204,7 +258,8 @@ impl Loader {
 				self,
 				Self::Known(
 					KnownLoader::Paper
-						| KnownLoader::Bukkit | KnownLoader::Spigot
+						| KnownLoader::Bukkit
+						| KnownLoader::Spigot
 						| KnownLoader::Sponge
 				)
 			),
@@ -214,6 +269,15 @@ impl Loader {
 }
 
 impl Version {
+	/// Gets how preferred this version is on an mcvm modloader, using the best of its loaders.
+	/// Returns None if the version doesn't work on the modloader
+	pub fn get_modloader_preference(&self, modloader: &Modloader) -> Option<u8> {
+		self.loaders
+			.iter()
+			.filter_map(|x| x.get_modloader_preference(modloader))
+			.min()
+	}
+
 	/// Returns the primary file download for this version
 	pub fn get_primary_download(&self) -> anyhow::Result<&Download> {
 		let primary = self.files.iter().find(|x| x.primary);
@@ -227,19 +291,45 @@ impl Version {
 	}
 }
 
+/// Select the best version for an mcvm modloader. Versions made for the modloader itself are picked
+/// over ones that only work through compatibility, like Fabric mods on Quilt. Out of the equally
+/// preferred versions, the newest one is picked
+pub fn select_version_for_modloader<'a>(
+	versions: impl IntoIterator<Item = &'a Version>,
+	modloader: &Modloader,
+) -> Option<&'a Version> {
+	versions
+		.into_iter()
+		.filter_map(|x| Some((x, x.get_modloader_preference(modloader)?)))
+		.min_by(|(a, a_pref), (b, b_pref)| {
+			a_pref
+				.cmp(b_pref)
+				.then_with(|| b.date_published.cmp(&a.date_published))
+		})
+		.map(|x| x.0)
+}
+
 /// Get a Modrinth project version
-pub async fn get_version(version_id: &str, client: &Client) -> anyhow::Result<Version> {
+pub async fn get_version(
+	version_id: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Version> {
 	let url = format_get_version_url(version_id);
-	let out = download::json(url, client)
+	let out = download::json_rate_limited(url, client, o)
 		.await
 		.context("Failed to download Modrinth version")?;
 	Ok(out)
 }
 
 /// Get the raw response of a version from the API
-pub async fn get_version_raw(version_id: &str, client: &Client) -> anyhow::Result<String> {
+pub async fn get_version_raw(
+	version_id: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<String> {
 	let url = format_get_version_url(version_id);
-	let out = download::text(url, client)
+	let out = download::text_rate_limited(url, client, o)
 		.await
 		.context("Failed to download Modrinth version")?;
 	Ok(out)
@@ -250,16 +340,71 @@ fn format_get_version_url(version_id: &str) -> String {
 	format!("https://api.modrinth.com/v2/version/{version_id}")
 }
 
-/// Get multiple Modrinth project versions
+/// The maximum number of version IDs to request at once. Any more than this
+/// and the URL becomes too long for the API to handle
+const MAX_VERSIONS_PER_REQUEST: usize = 215;
+
+/// Get multiple Modrinth project versions. The versions are requested in batches,
+/// with at most `concurrency` requests in flight at once (defaulting to the transfer limit).
+/// The returned versions are in the same order as the input IDs
 pub async fn get_multiple_versions(
+	versions: &[String],
+	concurrency: Option<usize>,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Vec<Version>> {
+	let concurrency = concurrency.unwrap_or_else(download::get_transfer_limit);
+
+	let tasks: Vec<_> = versions
+		.chunks(MAX_VERSIONS_PER_REQUEST)
+		.map(|chunk| get_versions_batch_buffered(chunk.to_vec(), client.clone()))
+		.collect();
+	let mut tasks = stream::iter(tasks).buffer_unordered(concurrency.max(1));
+
+	let mut out = Vec::with_capacity(versions.len());
+	while let Some((result, messages)) = tasks.next().await {
+		messages.replay(o);
+		out.extend(result?);
+	}
+
+	sort_versions_by_ids(&mut out, versions);
+
+	Ok(out)
+}
+
+/// Get a batch of versions, buffering the output messages. Messages can't be given to the
+/// output while multiple requests are running, so they are stored until the request is done
+async fn get_versions_batch_buffered(
+	versions: Vec<String>,
+	client: Client,
+) -> (anyhow::Result<Vec<Version>>, MessageBuffer) {
+	let mut messages = MessageBuffer::default();
+	let result = get_versions_batch(&versions, &client, &mut messages).await;
+	(result, messages)
+}
+
+/// Get a batch of versions using the multiple-versions API endpoint
+async fn get_versions_batch(
 	versions: &[String],
 	client: &Client,
+	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<Vec<Version>> {
-	// Use the multiple-versions API endpoint as it's faster
 	let param = serde_json::to_string(versions)
 		.context("Failed to convert version list to API parameter")?;
 	let url = format!("https://api.modrinth.com/v2/versions?ids={param}");
-	download::json(url, client).await
+	download::json_rate_limited(url, client, o)
+		.await
+		.with_context(|| format!("Failed to get Modrinth versions {}", versions.join(", ")))
+}
+
+/// Sort versions so that they are in the same order as the list of IDs they were requested with
+fn sort_versions_by_ids(versions: &mut [Version], ids: &[String]) {
+	let positions: HashMap<&str, usize> = ids
+		.iter()
+		.enumerate()
+		.map(|(i, id)| (id.as_str(), i))
+		.collect();
+	versions.sort_by_key(|x| positions.get(x.id.as_str()).copied().unwrap_or(usize::MAX));
 }
 
 /// A file download from the Modrinth API
@@ -336,21 +481,26 @@ pub enum SideSupport {
 }
 
 /// Get the team members of a project
-pub async fn get_project_team(project_id: &str, client: &Client) -> anyhow::Result<Vec<Member>> {
+pub async fn get_project_team(
+	project_id: &str,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<Vec<Member>> {
 	let url = format!("https://api.modrinth.com/v2/project/{project_id}/members");
-	download::json(url, client).await
+	download::json_rate_limited(url, client, o).await
 }
 
 /// Get multiple Modrinth teams
 pub async fn get_multiple_teams(
 	teams: &[String],
 	client: &Client,
+	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<Vec<Vec<Member>>> {
 	// Use the multiple-teams API endpoint as it's faster
 	let param =
 		serde_json::to_string(teams).context("Failed to convert team list to API parameter")?;
 	let url = format!("https://api.modrinth.com/v2/teams?ids={param}");
-	download::json(url, client).await
+	download::json_rate_limited(url, client, o).await
 }
 
 /// A member of a project team
@@ -370,3 +520,70 @@ pub struct User {
 	/// The user's username
 	pub username: String,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(id: &str, loaders: &[&str], date_published: &str) -> Version {
+		serde_json::from_value(serde_json::json!({
+			"id": id,
+			"project_id": "project",
+			"name": id,
+			"version_number": id,
+			"version_type": "release",
+			"loaders": loaders,
+			"files": [],
+			"game_versions": ["1.20.1"],
+			"dependencies": [],
+			"featured": false,
+			"date_published": date_published,
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_fabric_version_on_quilt() {
+		let fabric = version("fabric", &["fabric"], "2024-01-01T00:00:00Z");
+		assert_eq!(fabric.get_modloader_preference(&Modloader::Quilt), Some(1));
+		assert_eq!(fabric.get_modloader_preference(&Modloader::Fabric), Some(0));
+		assert_eq!(fabric.get_modloader_preference(&Modloader::Forge), None);
+
+		// Quilt mods don't work on Fabric
+		let quilt = version("quilt", &["quilt"], "2024-01-01T00:00:00Z");
+		assert_eq!(quilt.get_modloader_preference(&Modloader::Fabric), None);
+	}
+
+	#[test]
+	fn test_select_version_for_modloader() {
+		let versions = vec![
+			version("old_quilt", &["quilt"], "2023-01-01T00:00:00Z"),
+			version("new_fabric", &["fabric"], "2024-01-01T00:00:00Z"),
+			version("forge", &["forge"], "2024-06-01T00:00:00Z"),
+		];
+		let selected = select_version_for_modloader(&versions, &Modloader::Quilt).unwrap();
+		assert_eq!(selected.id, "old_quilt");
+		let selected = select_version_for_modloader(&versions, &Modloader::Fabric).unwrap();
+		assert_eq!(selected.id, "new_fabric");
+
+		// Fall back to Fabric when there is no Quilt version
+		let selected = select_version_for_modloader(&versions[1..], &Modloader::Quilt).unwrap();
+		assert_eq!(selected.id, "new_fabric");
+		assert!(select_version_for_modloader(&versions[2..], &Modloader::Quilt).is_none());
+	}
+
+	#[test]
+	fn test_sort_versions_by_ids() {
+		let ids: Vec<String> = ["c", "a", "b"].into_iter().map(String::from).collect();
+		let mut versions = vec![
+			version("a", &[], "2024-01-01T00:00:00Z"),
+			version("unknown", &[], "2024-01-01T00:00:00Z"),
+			version("b", &[], "2024-01-01T00:00:00Z"),
+			version("c", &[], "2024-01-01T00:00:00Z"),
+		];
+		sort_versions_by_ids(&mut versions, &ids);
+		let sorted: Vec<_> = versions.iter().map(|x| x.id.as_str()).collect();
+		// Versions that weren't requested are put at the end
+		assert_eq!(sorted, ["c", "a", "b", "unknown"]);
+	}
+}
@@ -106,10 +106,27 @@ fn convert_mouse_sensitivity(sensitivity: i16) -> f32 {
 	(sensitivity as f32) / 2.0 / 100.0
 }
 
+/// Converts a language code to the casing used by the game version.
+/// Languages were changed from `en_US` to `en_us` in 16w32a
+fn convert_language(lang: &str, after_16w32a: bool) -> String {
+	if after_16w32a {
+		lang.to_lowercase()
+	} else if let Some((language, region)) = lang.split_once('_') {
+		format!("{}_{}", language.to_lowercase(), region.to_uppercase())
+	} else {
+		lang.to_string()
+	}
+}
+
 macro_rules! match_keybind {
 	($out:ident, $option:expr, $key:expr, $before_1_13:expr) => {
 		if let Some(value) = $option {
-			$out.insert($key.into(), value.get_keycode($before_1_13));
+			if let Some(keycode) = value
+				.get_keycode($before_1_13)
+				.with_context(|| format!("Invalid keybind for {}", $key))?
+			{
+				$out.insert($key.into(), keycode);
+			}
 		}
 	};
 
@@ -134,6 +151,7 @@ pub fn create_keys(
 	let after_13w47a = VersionPattern::After("13w47a".into()).matches_info(version_info);
 	let after_14w25a = VersionPattern::After("14w25a".into()).matches_info(version_info);
 	let after_14w28a = VersionPattern::After("14w28a".into()).matches_info(version_info);
+	let after_16w32a = VersionPattern::After("16w32a".into()).matches_info(version_info);
 	let after_17w06a = VersionPattern::After("17w06a".into()).matches_info(version_info);
 	let after_17w47a = VersionPattern::After("17w47a".into()).matches_info(version_info);
 	let after_18w15a = VersionPattern::After("18w15a".into()).matches_info(version_info);
@@ -205,7 +223,7 @@ pub fn create_keys(
 	match_key!(out, options.video.render_distance, "renderDistance");
 	match_key!(out, options.video.simulation_distance, "simulationDistance", after_21w38a);
 	match_key!(out, options.video.entity_distance_scaling, "entityDistanceScaling");
-	match_key!(out, options.video.gui_scale, "guiScale");
+	match_key_int!(out, &options.video.gui_scale, "guiScale");
 	match_key_int!(out, &options.video.particles, "particles");
 	match_key!(out, options.video.max_fps, "maxFps");
 	match_key_int!(out, &options.difficulty, "difficulty");
@@ -239,7 +257,9 @@ pub fn create_keys(
 	if let Some(value) = &options.resource_packs {
 		out.insert("resourcePacks".into(), write_resource_packs(value));
 	}
-	match_key!(out, &options.language, "lang");
+	if let Some(value) = &options.language {
+		out.insert("lang".into(), convert_language(value, after_16w32a));
+	}
 	match_key_int!(out, &options.chat.visibility, "chatVisibility");
 	match_key!(out, options.chat.opacity, "chatOpacity");
 	match_key!(out, options.chat.line_spacing, "chatLineSpacing");
@@ -411,4 +431,11 @@ mod tests {
 		};
 		create_keys(&options.client.unwrap(), &info).unwrap();
 	}
+
+	#[test]
+	fn test_convert_language() {
+		assert_eq!(convert_language("en_US", true), "en_us");
+		assert_eq!(convert_language("en_us", false), "en_US");
+		assert_eq!(convert_language("lol", false), "lol");
+	}
 }
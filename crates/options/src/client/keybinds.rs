@@ -1,10 +1,13 @@
 use std::fmt::Display;
 
+use anyhow::bail;
 use mcvm_shared::util::ToInt;
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::read::EnumOrString;
+
 /// Version-agnostic keybinds
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Copy)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -416,3 +419,51 @@ impl Keybind {
 		}
 	}
 }
+
+impl EnumOrString<Keybind> {
+	/// Returns either the key string or key code based on the minecraft version.
+	/// Raw key identifiers like `key.keyboard.a` only exist after 1.13, so they
+	/// will return None on older versions
+	pub fn get_keycode(&self, before_1_13: bool) -> anyhow::Result<Option<String>> {
+		match self {
+			Self::Enum(keybind) => Ok(Some(keybind.get_keycode(before_1_13))),
+			Self::String(identifier) => {
+				if !identifier.starts_with("key.keyboard.") && !identifier.starts_with("key.mouse.")
+				{
+					bail!("Invalid keybind '{identifier}'");
+				}
+				if before_1_13 {
+					Ok(None)
+				} else {
+					Ok(Some(identifier.clone()))
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_keybind_identifiers() {
+		let keybind: EnumOrString<Keybind> = serde_json::from_str("\"a\"").unwrap();
+		assert_eq!(keybind, EnumOrString::Enum(Keybind::A));
+		assert_eq!(
+			keybind.get_keycode(false).unwrap(),
+			Some("key.keyboard.a".into())
+		);
+
+		let keybind: EnumOrString<Keybind> =
+			serde_json::from_str("\"key.keyboard.keypad.add\"").unwrap();
+		assert_eq!(
+			keybind.get_keycode(false).unwrap(),
+			Some("key.keyboard.keypad.add".into())
+		);
+		assert_eq!(keybind.get_keycode(true).unwrap(), None);
+
+		let keybind: EnumOrString<Keybind> = serde_json::from_str("\"not_a_key\"").unwrap();
+		assert!(keybind.get_keycode(false).is_err());
+	}
+}
@@ -4,7 +4,7 @@ mod file;
 mod keybinds;
 
 pub use file::create_keys;
-pub use file::write_options_txt;
+pub use file::{read_options_txt, write_options_txt};
 
 use std::{collections::HashMap, fmt::Display};
 
@@ -13,7 +13,7 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use self::keybinds::Keybind;
-use super::read::EnumOrNumber;
+use super::read::{EnumOrNumber, EnumOrString};
 
 // I do not want to document all of these
 pub use deser::*;
@@ -121,87 +121,87 @@ pub mod deser {
 	#[serde(default)]
 	pub struct KeyOptions {
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub attack: Option<Keybind>,
+		pub attack: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub r#use: Option<Keybind>,
+		pub r#use: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub forward: Option<Keybind>,
+		pub forward: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub left: Option<Keybind>,
+		pub left: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub back: Option<Keybind>,
+		pub back: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub right: Option<Keybind>,
+		pub right: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub jump: Option<Keybind>,
+		pub jump: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub sneak: Option<Keybind>,
+		pub sneak: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub sprint: Option<Keybind>,
+		pub sprint: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub drop: Option<Keybind>,
+		pub drop: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub inventory: Option<Keybind>,
+		pub inventory: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub chat: Option<Keybind>,
+		pub chat: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub playerlist: Option<Keybind>,
+		pub playerlist: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub pick_item: Option<Keybind>,
+		pub pick_item: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub command: Option<Keybind>,
+		pub command: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub social_interactions: Option<Keybind>,
+		pub social_interactions: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub screenshot: Option<Keybind>,
+		pub screenshot: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub toggle_perspective: Option<Keybind>,
+		pub toggle_perspective: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub smooth_camera: Option<Keybind>,
+		pub smooth_camera: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub fullscreen: Option<Keybind>,
+		pub fullscreen: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub spectator_outlines: Option<Keybind>,
+		pub spectator_outlines: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub swap_offhand: Option<Keybind>,
+		pub swap_offhand: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub save_toolbar: Option<Keybind>,
+		pub save_toolbar: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub load_toolbar: Option<Keybind>,
+		pub load_toolbar: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub advancements: Option<Keybind>,
+		pub advancements: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_1: Option<Keybind>,
+		pub hotbar_1: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_2: Option<Keybind>,
+		pub hotbar_2: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_3: Option<Keybind>,
+		pub hotbar_3: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_4: Option<Keybind>,
+		pub hotbar_4: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_5: Option<Keybind>,
+		pub hotbar_5: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_6: Option<Keybind>,
+		pub hotbar_6: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_7: Option<Keybind>,
+		pub hotbar_7: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_8: Option<Keybind>,
+		pub hotbar_8: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub hotbar_9: Option<Keybind>,
+		pub hotbar_9: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub boss_mode: Option<Keybind>,
+		pub boss_mode: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub decrease_view: Option<Keybind>,
+		pub decrease_view: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub increase_view: Option<Keybind>,
+		pub increase_view: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub stream_commercial: Option<Keybind>,
+		pub stream_commercial: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub stream_pause_unpause: Option<Keybind>,
+		pub stream_pause_unpause: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub stream_start_stop: Option<Keybind>,
+		pub stream_start_stop: Option<EnumOrString<Keybind>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub stream_toggle_microphone: Option<Keybind>,
+		pub stream_toggle_microphone: Option<EnumOrString<Keybind>>,
 	}
 
 	#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
@@ -275,7 +275,7 @@ pub mod deser {
 		#[serde(skip_serializing_if = "Option::is_none")]
 		pub entity_distance_scaling: Option<f32>,
 		#[serde(skip_serializing_if = "Option::is_none")]
-		pub gui_scale: Option<u8>,
+		pub gui_scale: Option<EnumOrNumber<GuiScale>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
 		pub particles: Option<EnumOrNumber<ParticlesMode>>,
 		#[serde(skip_serializing_if = "Option::is_none")]
@@ -407,6 +407,22 @@ pub mod deser {
 		}
 	}
 
+	#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+	#[cfg_attr(feature = "schema", derive(JsonSchema))]
+	#[serde(rename_all = "snake_case")]
+	pub enum GuiScale {
+		Auto,
+		Small,
+		Normal,
+		Large,
+	}
+
+	impl ToInt for GuiScale {
+		fn to_int(&self) -> i32 {
+			self.clone() as i32
+		}
+	}
+
 	#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 	#[cfg_attr(feature = "schema", derive(JsonSchema))]
 	#[serde(rename_all = "snake_case")]
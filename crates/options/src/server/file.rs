@@ -5,7 +5,6 @@ use std::{collections::HashMap, io::Write, path::Path};
 use anyhow::Context;
 use itertools::Itertools;
 
-use crate::read::read_options_file;
 use crate::{match_key, match_key_int};
 use mcvm_shared::util::ToInt;
 use mcvm_shared::versions::{VersionInfo, VersionPattern};
@@ -14,41 +13,140 @@ use super::ServerOptions;
 
 const SEP: char = '=';
 
-/// Write server.properties to a file
+/// Write server.properties to a file, merging the keys over the existing file.
+/// Comments, key order, and keys that we don't manage are preserved
 pub fn write_server_properties(
 	options: HashMap<String, String>,
 	path: &Path,
 ) -> anyhow::Result<()> {
-	let options = merge_server_properties(path, options).context("Failed to merge properties")?;
+	let mut properties = if path.exists() {
+		ServerProperties::open(path).context("Failed to open existing server.properties")?
+	} else {
+		ServerProperties::new()
+	};
+	properties.merge(options);
+
 	let file = File::create(path).context("Failed to open file")?;
 	let mut file = BufWriter::new(file);
-	for (key, value) in options.iter().sorted_by_key(|x| x.0) {
-		write_key(key, value, &mut file)
-			.with_context(|| format!("Failed to write line for option {key} with value {value}"))?;
-	}
+	properties
+		.write(&mut file)
+		.context("Failed to write properties")?;
 
 	Ok(())
 }
 
-/// Collect a hashmap from an existing server.properties file so we can compare with it
-fn read_server_properties(path: &Path) -> anyhow::Result<HashMap<String, String>> {
-	if path.exists() {
-		let contents = std::fs::read_to_string(path).context("Failed to read options.txt")?;
-		read_options_file(&contents, SEP)
-	} else {
-		Ok(HashMap::new())
+/// The contents of a server.properties file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ServerProperties {
+	lines: Vec<PropertiesLine>,
+}
+
+/// A single line in a server.properties file
+#[derive(Debug, Clone, PartialEq)]
+enum PropertiesLine {
+	/// A key and its escaped value
+	Entry(String, String),
+	/// A comment, blank line, or anything else we don't understand
+	Other(String),
+}
+
+impl ServerProperties {
+	/// Create a new empty ServerProperties
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Read ServerProperties from a file
+	pub fn open(path: &Path) -> anyhow::Result<Self> {
+		let contents = std::fs::read_to_string(path).context("Failed to read server.properties")?;
+		Ok(Self::parse(&contents))
+	}
+
+	/// Parse the contents of a server.properties file
+	pub fn parse(contents: &str) -> Self {
+		let lines = contents
+			.lines()
+			.map(|line| {
+				if line.starts_with('#') {
+					return PropertiesLine::Other(line.into());
+				}
+				match line.split_once(SEP) {
+					Some((key, value)) => PropertiesLine::Entry(key.into(), value.into()),
+					None => PropertiesLine::Other(line.into()),
+				}
+			})
+			.collect();
+
+		Self { lines }
+	}
+
+	/// Get the value of a key, without any escaping
+	pub fn get(&self, key: &str) -> Option<String> {
+		self.lines.iter().find_map(|x| match x {
+			PropertiesLine::Entry(k, v) if k.trim() == key => Some(unescape_value(v)),
+			_ => None,
+		})
+	}
+
+	/// Set the value of a key, replacing it in place if it already exists
+	/// and adding it to the end if it doesn't
+	pub fn set(&mut self, key: &str, value: &str) {
+		let value = escape_colons(value);
+		for line in &mut self.lines {
+			if let PropertiesLine::Entry(k, v) = line {
+				if k == key {
+					*v = value;
+					return;
+				}
+			}
+		}
+		self.lines.push(PropertiesLine::Entry(key.into(), value));
+	}
+
+	/// Merge keys over the existing ones. New keys are added in sorted order
+	pub fn merge(&mut self, keys: HashMap<String, String>) {
+		for (key, value) in keys.iter().sorted_by_key(|x| x.0) {
+			self.set(key, value);
+		}
+	}
+
+	/// Write the properties to a writer
+	pub fn write<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+		for line in &self.lines {
+			match line {
+				PropertiesLine::Entry(key, value) => {
+					write_key(key, value, writer).with_context(|| {
+						format!("Failed to write line for option {key} with value {value}")
+					})?
+				}
+				PropertiesLine::Other(line) => writeln!(writer, "{line}")?,
+			}
+		}
+
+		Ok(())
 	}
 }
 
-/// Merge keys with an existing file
-fn merge_server_properties(
-	path: &Path,
-	keys: HashMap<String, String>,
-) -> anyhow::Result<HashMap<String, String>> {
-	let mut file_keys =
-		read_server_properties(path).context("Failed to open options file for merging")?;
-	file_keys.extend(keys);
-	Ok(file_keys)
+/// Remove the escaping from a value in the properties file, such as `\:`, `\=`, and `\\`
+fn unescape_value(value: &str) -> String {
+	let mut out = String::with_capacity(value.len());
+	let mut chars = value.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			out.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('t') => out.push('\t'),
+			Some('n') => out.push('\n'),
+			Some('r') => out.push('\r'),
+			Some('f') => out.push('\x0c'),
+			Some(c) => out.push(c),
+			None => {}
+		}
+	}
+
+	out
 }
 
 /// Escape any unescaped colons. These will not work in the server.properties file
@@ -58,9 +156,9 @@ fn escape_colons(string: &str) -> String {
 	out.replace(':', "\\:")
 }
 
-/// Write a server options key to a writer
+/// Write a server options key with an already escaped value to a writer
 fn write_key<W: Write>(key: &str, value: &str, writer: &mut W) -> anyhow::Result<()> {
-	writeln!(writer, "{key}={}", escape_colons(value))?;
+	writeln!(writer, "{key}{SEP}{value}")?;
 
 	Ok(())
 }
@@ -187,4 +285,32 @@ mod tests {
 		};
 		create_keys(&options.server.unwrap(), &info).unwrap();
 	}
+
+	#[test]
+	fn test_properties_merge() {
+		let contents = "#Minecraft server properties\n#Some date\nmotd=Hello\nplugin-key=foo\nlevel-type=minecraft\\:flat\n";
+		let mut properties = ServerProperties::parse(contents);
+		assert_eq!(properties.get("level-type"), Some("minecraft:flat".into()));
+
+		let mut keys = HashMap::new();
+		keys.insert("motd".to_string(), "World".to_string());
+		keys.insert("max-players".to_string(), "5".to_string());
+		properties.merge(keys);
+
+		let mut out = Vec::new();
+		properties.write(&mut out).unwrap();
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			"#Minecraft server properties\n#Some date\nmotd=World\nplugin-key=foo\nlevel-type=minecraft\\:flat\nmax-players=5\n"
+		);
+	}
+
+	#[test]
+	fn test_properties_get_unescape() {
+		let contents = "motd=a\\=b\\:c\\\\d\\te\nrcon.password=x\\\\\\=y\n";
+		let properties = ServerProperties::parse(contents);
+		assert_eq!(properties.get("motd"), Some("a=b:c\\d\te".into()));
+		assert_eq!(properties.get("rcon.password"), Some("x\\=y".into()));
+		assert_eq!(properties.get("missing"), None);
+	}
 }
@@ -6,6 +6,7 @@ use mcvm_shared::util::{DefaultExt, ToInt};
 pub use file::create_keys;
 pub use file::get_world_name;
 pub use file::write_server_properties;
+pub use file::ServerProperties;
 
 use std::collections::HashMap;
 use std::fmt::Display;
@@ -0,0 +1,118 @@
+use color_print::cformat;
+
+use crate::lex::{lex, TextPos, Token};
+
+/// Syntax-highlight a package script, returning the script with terminal color codes added
+pub fn highlight_package_script(contents: &str) -> anyhow::Result<String> {
+	let mut lexed = lex(contents)?;
+
+	// Token positions are in characters, so we need to find the byte offset of each one to slice the text
+	let mut offsets: Vec<usize> = contents.char_indices().map(|x| x.0).collect();
+	let char_count = offsets.len();
+	offsets.push(contents.len());
+
+	// Since the windows iterator won't go to the end with the last token on the left
+	// side of the window, because it always makes sure the array is at least 2 elements long,
+	// we pad the end with a none token that sits at the end of the string
+	if let Some(last) = lexed.last() {
+		let end = TextPos::new(*last.1.row(), *last.1.col(), char_count);
+		lexed.push((Token::None, end));
+	}
+
+	let mut out = String::with_capacity(contents.len());
+	let mut last_tok_was_at = false;
+	let mut last_tok_was_curly_or_semi = false;
+	for elem in lexed.windows(2) {
+		let [left, right] = elem else {
+			continue;
+		};
+		let left_pos = (*left.1.absolute()).min(char_count);
+		let right_pos = (*right.1.absolute()).min(char_count);
+		if left_pos >= right_pos {
+			continue;
+		}
+		let text = &contents[offsets[left_pos]..offsets[right_pos]];
+
+		let text = match left.0 {
+			Token::None => String::new(),
+			Token::Whitespace => text.to_string(),
+			Token::Semicolon
+			| Token::Colon
+			| Token::Comma
+			| Token::Pipe
+			| Token::Bang
+			| Token::Square(..)
+			| Token::Paren(..)
+			| Token::Angle(..)
+			| Token::Curly(..) => text.to_string(),
+			Token::At => cformat!("<m><s>{text}"),
+			Token::Variable(..) => cformat!("<c>{text}"),
+			Token::Comment(..) => cformat!("<k!>{text}"),
+			Token::Ident(..) => {
+				if last_tok_was_at {
+					cformat!("<m><s>{text}")
+				} else if last_tok_was_curly_or_semi {
+					cformat!("<b!>{text}")
+				} else {
+					text.to_string()
+				}
+			}
+			Token::Num(..) => cformat!("<y>{text}"),
+			Token::Str(..) => cformat!("<g>{text}"),
+		};
+		out.push_str(&text);
+
+		// Whitespace can split these tokens apart so we need to make sure it doesn't
+		if !left.0.is_ignored() {
+			last_tok_was_at = false;
+			last_tok_was_curly_or_semi = false;
+		}
+		if let Token::At = left.0 {
+			last_tok_was_at = true;
+		}
+		if let Token::Curly(..) | Token::Semicolon = left.0 {
+			last_tok_was_curly_or_semi = true;
+		}
+	}
+
+	Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_highlight_directives_and_variables() {
+		let highlighted = highlight_package_script("@install {\n\tfinish;\n}").unwrap();
+		let expected = [
+			cformat!("<m><s>@"),
+			cformat!("<m><s>install"),
+			" {\n\t".into(),
+			cformat!("<b!>finish"),
+			";\n}".into(),
+		]
+		.concat();
+		assert_eq!(highlighted, expected);
+
+		let highlighted = highlight_package_script("require $foo;").unwrap();
+		assert!(highlighted.contains(&cformat!("<c>$foo")));
+	}
+
+	#[test]
+	fn test_highlight_last_token() {
+		// The last token is padded so that it is not dropped, even when it is longer than one character
+		assert_eq!(
+			highlight_package_script("\"abc\"").unwrap(),
+			cformat!("<g>\"abc\"")
+		);
+		assert_eq!(highlight_package_script("}").unwrap(), "}");
+		assert_eq!(highlight_package_script("").unwrap(), "");
+
+		// Multi-byte characters should not break the slicing
+		assert_eq!(
+			highlight_package_script("\"héllo\";").unwrap(),
+			cformat!("<g>\"héllo\"") + ";"
+		);
+	}
+}
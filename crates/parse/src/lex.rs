@@ -1,7 +1,7 @@
 use std::fmt::{Debug, Display};
 
 use crate::unexpected_token;
-use anyhow::bail;
+use anyhow::anyhow;
 
 /// Create a list of tokens from package text contents that we will
 /// then use for parsing
@@ -125,19 +125,18 @@ pub fn lex(text: &str) -> anyhow::Result<Vec<(Token, TextPos)>> {
 					if allowed {
 						name.push(c);
 					} else {
-						repeat = true;
-						tokens.push((tok, tok_start_pos.clone()));
-						tok = Token::None;
-					}
-				}
-				Token::Whitespace => {
-					if !is_whitespace(c) {
 						repeat = true;
 						tokens.push((tok, tok_start_pos.clone()));
 						tok_start_pos = pos.clone();
 						tok = Token::None;
 					}
 				}
+				Token::Whitespace if !is_whitespace(c) => {
+					repeat = true;
+					tokens.push((tok, tok_start_pos.clone()));
+					tok_start_pos = pos.clone();
+					tok = Token::None;
+				}
 				Token::Ident(name) => {
 					if is_ident(c, false) {
 						name.push(c);
@@ -154,7 +153,8 @@ pub fn lex(text: &str) -> anyhow::Result<Vec<(Token, TextPos)>> {
 					} else {
 						repeat = true;
 						if num_str == "-" {
-							bail!("Invalid number '{num_str}', {pos}");
+							let error = anyhow!("Invalid number '{num_str}', {pos}");
+							return Err(PosError::new(error, pos).into());
 						}
 						*num = num_str.parse().expect("Number contains invalid characters");
 						tokens.push((tok, tok_start_pos.clone()));
@@ -319,6 +319,47 @@ impl TextPos {
 /// Token and TextPos
 pub type TokenAndPos = (Token, TextPos);
 
+/// An error from lexing or parsing with the position in the text where it happened.
+/// Errors from lex and parse can be downcast to this to find out where they are
+#[derive(Debug)]
+pub struct PosError {
+	error: anyhow::Error,
+	pos: TextPos,
+}
+
+impl PosError {
+	/// Create a new PosError
+	pub fn new(error: anyhow::Error, pos: TextPos) -> Self {
+		Self { error, pos }
+	}
+
+	/// Get the position of the error
+	pub fn pos(&self) -> &TextPos {
+		&self.pos
+	}
+
+	/// Attach a position to an error, unless it already has one
+	pub fn attach(error: anyhow::Error, pos: TextPos) -> anyhow::Error {
+		if error.downcast_ref::<Self>().is_some() {
+			error
+		} else {
+			Self::new(error, pos).into()
+		}
+	}
+}
+
+impl Display for PosError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.error)
+	}
+}
+
+impl std::error::Error for PosError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		self.error.source()
+	}
+}
+
 /// What action to perform after lexing a string character
 #[derive(Debug, PartialEq)]
 enum StrLexResult {
@@ -8,6 +8,8 @@
 
 /// Parsing for conditions, used in if instructions
 pub mod conditions;
+/// Syntax highlighting for package scripts
+pub mod highlight;
 /// Parsing for most instructions, with the exception of a few complex ones
 pub mod instruction;
 /// Token generation from a string, which is passed into the parser
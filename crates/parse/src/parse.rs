@@ -9,7 +9,7 @@ use crate::routine::RESERVED_ROUTINES;
 use super::conditions::Condition;
 use super::conditions::ConditionKind;
 use super::instruction::{parse_arg, InstrKind, Instruction};
-use super::lex::{lex, reduce_tokens, Side, Token, TokenAndPos};
+use super::lex::{lex, reduce_tokens, PosError, Side, TextPos, Token, TokenAndPos};
 use super::vars::Value;
 use mcvm_shared::addon::AddonKind;
 
@@ -21,18 +21,35 @@ const DEFAULT_ROUTINE: &str = "__default__";
 #[macro_export]
 macro_rules! unexpected_token {
 	($tok:expr, $pos:expr) => {
-		bail!("Unexpected token {} {}", $tok.as_string(), $pos.clone())
+		return Err($crate::lex::PosError::new(
+			anyhow::anyhow!("Unexpected token {} {}", $tok.as_string(), $pos.clone()),
+			$crate::lex::TextPos::clone(&$pos),
+		)
+		.into())
 	};
 }
 
-/// Parse a list of tokens
+/// Parse a list of tokens. Errors from a token can be downcast to a PosError to find where they are
 pub fn parse<'a>(tokens: impl Iterator<Item = &'a TokenAndPos>) -> anyhow::Result<Parsed> {
+	let mut current_pos = None;
+	parse_tokens(tokens, &mut current_pos).map_err(|e| match current_pos {
+		Some(pos) => PosError::attach(e, pos),
+		None => e,
+	})
+}
+
+/// Parse a list of tokens, keeping track of the position of the token that is being parsed
+fn parse_tokens<'a>(
+	tokens: impl Iterator<Item = &'a TokenAndPos>,
+	current_pos: &mut Option<TextPos>,
+) -> anyhow::Result<Parsed> {
 	let tokens = reduce_tokens(tokens);
 
 	let mut prs = ParseData::new();
 	// Whether or not a block just ended
 	let mut block_just_ended = false;
 	for (tok, pos) in tokens {
+		*current_pos = Some(pos.clone());
 		let mut instr_to_push = None;
 		let mut mode_to_set = None;
 		let mut block_to_set = None;
@@ -264,7 +281,7 @@ pub fn parse<'a>(tokens: impl Iterator<Item = &'a TokenAndPos>) -> anyhow::Resul
 								_ => {
 									bail!(
 										"Unknown key {} for 'addon' instruction {}",
-										name.to_string(),
+										name,
 										pos.clone()
 									);
 								}
@@ -431,6 +448,8 @@ pub fn parse<'a>(tokens: impl Iterator<Item = &'a TokenAndPos>) -> anyhow::Resul
 		}
 	}
 
+	*current_pos = None;
+
 	// Check for recursion
 	check_recursion(&prs.parsed)?;
 
@@ -113,6 +113,13 @@ pub trait PackageEvaluator<'a> {
 		pkg: &ArcPkgReq,
 		common_input: &Self::CommonInput,
 	) -> anyhow::Result<&'b PackageProperties>;
+
+	/// Whether packages that conflict with others should still be resolved, so that the
+	/// conflicts can be reported as warnings instead of errors
+	fn allow_conflicts(&self, input: &Self::EvalInput<'a>) -> bool {
+		let _ = input;
+		false
+	}
 }
 
 /// Trait for a user-configured package
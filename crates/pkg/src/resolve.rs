@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use anyhow::{bail, Context};
 use itertools::Itertools;
-use mcvm_shared::pkg::{ArcPkgReq, PackageID};
+use mcvm_shared::pkg::ArcPkgReq;
 
 use crate::properties::PackageProperties;
 use crate::{ConfiguredPackage, PackageEvalRelationsResult, PackageEvaluator};
@@ -17,10 +17,13 @@ pub async fn resolve<'a, E: PackageEvaluator<'a>>(
 	constant_eval_input: E::EvalInput<'a>,
 	common_input: &E::CommonInput,
 ) -> anyhow::Result<ResolutionResult> {
+	let allow_conflicts = evaluator.allow_conflicts(&constant_eval_input);
 	let mut resolver = Resolver {
 		tasks: VecDeque::new(),
 		constraints: Vec::new(),
 		constant_input: constant_eval_input,
+		allow_conflicts,
+		conflicts: Vec::new(),
 	};
 
 	// Create the initial EvalPackage from the installed packages
@@ -60,30 +63,31 @@ pub async fn resolve<'a, E: PackageEvaluator<'a>>(
 					});
 				}
 			}
-			ConstraintKind::Extend(package) => {
-				if !resolver.is_required(package) {
-					let source = package.source.get_source();
-					if let Some(source) = source {
-						bail!(
-							"The package '{}' extends the functionality of the package '{}', which is not installed.",
-							source.debug_sources(),
-							package
-						);
-					} else {
-						bail!(
-							"A package extends the functionality of the package '{}', which is not installed.",
-							package
-						);
-					}
+			ConstraintKind::Extend(package) if !resolver.is_required(package) => {
+				let source = package.source.get_source();
+				if let Some(source) = source {
+					bail!(
+						"The package '{}' extends the functionality of the package '{}', which is not installed.",
+						source.debug_sources(),
+						package
+					);
+				} else {
+					bail!(
+						"A package extends the functionality of the package '{}', which is not installed.",
+						package
+					);
 				}
 			}
 			_ => {}
 		}
 	}
 
+	let conflicts = std::mem::take(&mut resolver.conflicts);
+
 	let out = ResolutionResult {
 		packages: resolver.collect_packages(),
 		unfulfilled_recommendations,
+		conflicts,
 	};
 
 	Ok(out)
@@ -95,6 +99,18 @@ pub struct ResolutionResult {
 	pub packages: Vec<ArcPkgReq>,
 	/// Package recommendations that were not satisfied
 	pub unfulfilled_recommendations: Vec<RecommendedPackage>,
+	/// Conflicts between packages that were both required. Unless the evaluator allows
+	/// conflicts, the packages that were refused were not resolved any further
+	pub conflicts: Vec<PackageConflict>,
+}
+
+/// A conflict between two packages that were both required
+#[derive(Debug, PartialEq, Eq)]
+pub struct PackageConflict {
+	/// The package that declared the conflict
+	pub package: ArcPkgReq,
+	/// The package that it conflicts with
+	pub conflict: ArcPkgReq,
 }
 
 /// Recommended package that has a PkgRequest instead of a String
@@ -138,9 +154,9 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 	resolver: &mut Resolver<'a, E>,
 ) -> anyhow::Result<()> {
 	// Make sure that this package fits the constraints as well
-	resolver
-		.check_constraints(&package)
-		.context("Package did not fit existing constraints")?;
+	if !resolver.check_constraints(&package) {
+		return Ok(());
+	}
 
 	// Get the correct EvalInput
 	let properties = evaluator
@@ -154,20 +170,23 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 		.await
 		.context("Failed to evaluate package")?;
 
+	let mut is_conflicting = false;
 	for conflict in result.get_conflicts().iter().sorted() {
 		let req = Arc::new(PkgRequest::parse(
 			conflict,
 			PkgRequestSource::Refused(package.clone()),
 		));
-		if resolver.is_required(&req) {
-			bail!(
-				"Package '{}' is incompatible with this package.",
-				req.debug_sources()
-			);
-		}
+		let is_required = resolver.is_required(&req);
 		resolver.constraints.push(Constraint {
-			kind: ConstraintKind::Refuse(req),
+			kind: ConstraintKind::Refuse(req.clone()),
 		});
+		if is_required && !resolver.check_constraints(&req) {
+			is_conflicting = true;
+		}
+	}
+	// This package is incompatible with one that is already required, so its relations shouldn't be followed
+	if is_conflicting {
+		return Ok(());
 	}
 
 	for dep in result.get_deps().iter().flatten().sorted() {
@@ -175,10 +194,13 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 			&dep.value,
 			PkgRequestSource::Dependency(package.clone()),
 		));
+		check_cycle(&package, &req)?;
 		if dep.explicit && !resolver.is_user_required(&req) {
 			bail!("Package '{req}' has been explicitly required by this package. This means it must be required by the user in their config.");
 		}
-		resolver.check_constraints(&req)?;
+		if !resolver.check_constraints(&req) {
+			continue;
+		}
 		if !resolver.is_required(&req) {
 			resolver.constraints.push(Constraint {
 				kind: ConstraintKind::Require(req.clone()),
@@ -195,7 +217,10 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 			bundled,
 			PkgRequestSource::Bundled(package.clone()),
 		));
-		resolver.check_constraints(&req)?;
+		check_cycle(&package, &req)?;
+		if !resolver.check_constraints(&req) {
+			continue;
+		}
 		resolver.remove_require_constraint(&req);
 		resolver.constraints.push(Constraint {
 			kind: ConstraintKind::Bundle(req.clone()),
@@ -227,6 +252,7 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 			extension,
 			PkgRequestSource::Dependency(package.clone()),
 		));
+		check_cycle(&package, &req)?;
 		resolver.constraints.push(Constraint {
 			kind: ConstraintKind::Extend(req),
 		});
@@ -245,6 +271,28 @@ async fn resolve_eval_package<'a, E: PackageEvaluator<'a>>(
 	Ok(())
 }
 
+/// Creates an error if requesting a package from another package would create a cycle
+/// in the chain of packages that led to it
+fn check_cycle(package: &ArcPkgReq, req: &PkgRequest) -> anyhow::Result<()> {
+	// Walk up the sources of the package, which act as the resolution stack
+	let mut stack = vec![package.id.clone()];
+	let mut current = package.source.get_source();
+	while stack.last() != Some(&req.id) {
+		let Some(source) = current else {
+			return Ok(());
+		};
+		stack.push(source.id.clone());
+		current = source.source.get_source();
+	}
+
+	let path = stack
+		.iter()
+		.rev()
+		.chain(std::iter::once(&req.id))
+		.join(" -> ");
+	bail!("Circular package relationship detected: {path}");
+}
+
 /// Overrides the EvalInput for a package with config
 fn override_eval_input<'a, E: PackageEvaluator<'a>>(
 	properties: &PackageProperties,
@@ -267,6 +315,10 @@ struct Resolver<'a, E: PackageEvaluator<'a>> {
 	tasks: VecDeque<Task<'a, E>>,
 	constraints: Vec<Constraint>,
 	constant_input: E::EvalInput<'a>,
+	/// Whether to keep resolving packages that conflict with others
+	allow_conflicts: bool,
+	/// Conflicts that were found so far
+	conflicts: Vec<PackageConflict>,
 }
 
 impl<'a, E> Resolver<'a, E>
@@ -308,43 +360,52 @@ where
 		}
 	}
 
-	fn is_refused_fn(constraint: &Constraint, req: ArcPkgReq) -> bool {
+	fn is_refused_fn(constraint: &Constraint, req: &ArcPkgReq) -> bool {
 		matches!(
 			&constraint.kind,
-			ConstraintKind::Refuse(dest) if *dest == req
+			ConstraintKind::Refuse(dest) if dest == req
 		)
 	}
 
 	/// Whether a package has been refused by an existing constraint
 	pub fn is_refused(&self, req: &ArcPkgReq) -> bool {
-		self.constraints
-			.iter()
-			.any(|x| Self::is_refused_fn(x, req.clone()))
+		self.constraints.iter().any(|x| Self::is_refused_fn(x, req))
 	}
 
 	/// Get all refusers of this package
-	pub fn get_refusers(&self, req: &ArcPkgReq) -> Vec<PackageID> {
+	pub fn get_refusers(&self, req: &ArcPkgReq) -> Vec<ArcPkgReq> {
 		self.constraints
 			.iter()
-			.filter_map(|x| {
-				if let ConstraintKind::Refuse(dest) = &x.kind {
-					if dest == req {
-						Some(
-							dest.source
-								.get_source()
-								.map(|source| source.id.clone())
-								.unwrap_or("User-refused".into()),
-						)
-					} else {
-						None
-					}
-				} else {
-					None
-				}
+			.filter_map(|x| match &x.kind {
+				ConstraintKind::Refuse(dest) if dest == req => match &dest.source {
+					PkgRequestSource::Refused(source) => Some(source.clone()),
+					_ => None,
+				},
+				_ => None,
 			})
 			.collect()
 	}
 
+	/// Checks that a package is not disallowed by the existing constraints, recording a conflict
+	/// with each package that refuses it. Returns false if the package should not be resolved any further
+	pub fn check_constraints(&mut self, req: &ArcPkgReq) -> bool {
+		if !self.is_refused(req) {
+			return true;
+		}
+
+		for refuser in self.get_refusers(req) {
+			let conflict = PackageConflict {
+				package: refuser,
+				conflict: req.clone(),
+			};
+			if !self.conflicts.contains(&conflict) {
+				self.conflicts.push(conflict);
+			}
+		}
+
+		self.allow_conflicts
+	}
+
 	/// Whether a compat constraint exists
 	pub fn compat_exists(&self, package: ArcPkgReq, compat_package: ArcPkgReq) -> bool {
 		self.constraints.iter().any(|x| {
@@ -355,19 +416,6 @@ where
 		})
 	}
 
-	/// Creates an error if this package is disallowed in the constraints
-	pub fn check_constraints(&self, req: &ArcPkgReq) -> anyhow::Result<()> {
-		if self.is_refused(req) {
-			let refusers = self.get_refusers(req);
-			bail!(
-				"Package '{req}' is incompatible with existing packages {}",
-				refusers.join(", ")
-			);
-		}
-
-		Ok(())
-	}
-
 	/// Checks compat constraints to see if new constraints are needed
 	pub fn check_compats(&mut self) {
 		let mut constraints_to_add = Vec::new();
@@ -432,3 +480,255 @@ enum Task<'a, E: PackageEvaluator<'a>> {
 fn package_context_error_message(package: &PkgRequest) -> String {
 	format!("In package '{}'", package.debug_sources())
 }
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use async_trait::async_trait;
+	use mcvm_shared::pkg::PackageID;
+
+	use super::*;
+	use crate::declarative::{deserialize_declarative_package, DeclarativePackage};
+	use crate::{RecommendedPackage as RecommendedPackageEntry, RequiredPackage};
+
+	/// Evaluator that resolves the relations of a set of declarative packages
+	struct TestEvaluator {
+		packages: HashMap<String, DeclarativePackage>,
+		allow_conflicts: bool,
+	}
+
+	impl TestEvaluator {
+		fn new(packages: &[(&str, &str)]) -> Self {
+			let packages = packages
+				.iter()
+				.map(|(id, contents)| {
+					(
+						id.to_string(),
+						deserialize_declarative_package(contents).unwrap(),
+					)
+				})
+				.collect();
+			Self {
+				packages,
+				allow_conflicts: false,
+			}
+		}
+
+		fn get(&self, pkg: &PkgRequest) -> anyhow::Result<&DeclarativePackage> {
+			self.packages
+				.get(pkg.id.as_ref())
+				.with_context(|| format!("Package '{pkg}' does not exist"))
+		}
+	}
+
+	struct TestRelations(DeclarativePackage);
+
+	impl PackageEvalRelationsResult for TestRelations {
+		fn get_deps(&self) -> Vec<Vec<RequiredPackage>> {
+			let relations = &self.0.relations;
+			let deps = relations.dependencies.iter().map(|x| (x, false));
+			let explicit = relations.explicit_dependencies.iter().map(|x| (x, true));
+			deps.chain(explicit)
+				.map(|(value, explicit)| {
+					vec![RequiredPackage {
+						value: value.as_str().into(),
+						explicit,
+					}]
+				})
+				.collect()
+		}
+
+		fn get_conflicts(&self) -> Vec<PackageID> {
+			self.0
+				.relations
+				.conflicts
+				.iter()
+				.map(|x| x.as_str().into())
+				.collect()
+		}
+
+		fn get_recommendations(&self) -> Vec<RecommendedPackageEntry> {
+			self.0.relations.recommendations.iter().cloned().collect()
+		}
+
+		fn get_bundled(&self) -> Vec<PackageID> {
+			self.0
+				.relations
+				.bundled
+				.iter()
+				.map(|x| x.as_str().into())
+				.collect()
+		}
+
+		fn get_compats(&self) -> Vec<(PackageID, PackageID)> {
+			self.0
+				.relations
+				.compats
+				.iter()
+				.map(|(x, y)| (x.as_str().into(), y.as_str().into()))
+				.collect()
+		}
+
+		fn get_extensions(&self) -> Vec<PackageID> {
+			self.0
+				.relations
+				.extensions
+				.iter()
+				.map(|x| x.as_str().into())
+				.collect()
+		}
+	}
+
+	#[derive(Clone)]
+	struct TestConfiguredPackage(ArcPkgReq);
+
+	impl ConfiguredPackage for TestConfiguredPackage {
+		type EvalInput<'a> = ();
+
+		fn get_package(&self) -> ArcPkgReq {
+			self.0.clone()
+		}
+
+		fn override_configured_package_input(
+			&self,
+			_properties: &PackageProperties,
+			_input: &mut Self::EvalInput<'_>,
+		) -> anyhow::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[async_trait]
+	impl<'a> PackageEvaluator<'a> for TestEvaluator {
+		type CommonInput = ();
+		type EvalInput<'b> = ();
+		type EvalRelationsResult<'b> = TestRelations;
+		type ConfiguredPackage = TestConfiguredPackage;
+
+		async fn eval_package_relations(
+			&mut self,
+			pkg: &ArcPkgReq,
+			_input: &Self::EvalInput<'a>,
+			_common_input: &Self::CommonInput,
+		) -> anyhow::Result<Self::EvalRelationsResult<'a>> {
+			Ok(TestRelations(self.get(pkg)?.clone()))
+		}
+
+		async fn get_package_properties<'b>(
+			&'b mut self,
+			pkg: &ArcPkgReq,
+			_common_input: &Self::CommonInput,
+		) -> anyhow::Result<&'b PackageProperties> {
+			Ok(&self.get(pkg)?.properties)
+		}
+
+		fn allow_conflicts(&self, _input: &Self::EvalInput<'a>) -> bool {
+			self.allow_conflicts
+		}
+	}
+
+	async fn resolve_test(
+		packages: &[(&str, &str)],
+		required: &[&str],
+	) -> anyhow::Result<ResolutionResult> {
+		resolve_test_with(TestEvaluator::new(packages), required).await
+	}
+
+	async fn resolve_test_with(
+		evaluator: TestEvaluator,
+		required: &[&str],
+	) -> anyhow::Result<ResolutionResult> {
+		let required: Vec<_> = required
+			.iter()
+			.map(|x| {
+				TestConfiguredPackage(Arc::new(PkgRequest::parse(
+					x,
+					PkgRequestSource::UserRequire,
+				)))
+			})
+			.collect();
+		resolve(&required, evaluator, (), &()).await
+	}
+
+	#[tokio::test]
+	async fn test_resolve_dependency_cycle() {
+		let packages = [
+			("a", r#"{ "relations": { "dependencies": "b" } }"#),
+			("b", r#"{ "relations": { "dependencies": "c" } }"#),
+			("c", r#"{ "relations": { "dependencies": "a" } }"#),
+		];
+		let err = resolve_test(&packages, &["a"]).await.err().unwrap();
+		assert!(format!("{err:?}").contains("a -> b -> c -> a"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_bundled_cycle() {
+		let packages = [
+			("a", r#"{ "relations": { "bundled": "b" } }"#),
+			("b", r#"{ "relations": { "bundled": "a" } }"#),
+		];
+		let err = resolve_test(&packages, &["a"]).await.err().unwrap();
+		assert!(format!("{err:?}").contains("a -> b -> a"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_conflicts() {
+		let packages = [
+			("a", r#"{ "relations": { "dependencies": "c" } }"#),
+			("b", r#"{ "relations": { "conflicts": "c" } }"#),
+			("c", r#"{}"#),
+		];
+		let result = resolve_test(&packages, &["a", "b"]).await.unwrap();
+		assert_eq!(result.conflicts.len(), 1);
+		assert_eq!(result.conflicts[0].package.id.as_ref(), "b");
+		assert_eq!(result.conflicts[0].conflict.id.as_ref(), "c");
+	}
+
+	#[tokio::test]
+	async fn test_resolve_conflicts_stop_branch() {
+		let packages = [
+			("a", r#"{ "relations": { "dependencies": "c" } }"#),
+			("b", r#"{ "relations": { "conflicts": "c" } }"#),
+			("c", r#"{ "relations": { "dependencies": "d" } }"#),
+			("d", r#"{}"#),
+		];
+		let is_resolved = |result: &ResolutionResult, id: &str| {
+			result.packages.iter().any(|x| x.id.as_ref() == id)
+		};
+
+		// The refused package shouldn't be resolved any further
+		let result = resolve_test(&packages, &["a", "b"]).await.unwrap();
+		assert_eq!(result.conflicts.len(), 1);
+		assert!(!is_resolved(&result, "d"));
+
+		let mut evaluator = TestEvaluator::new(&packages);
+		evaluator.allow_conflicts = true;
+		let result = resolve_test_with(evaluator, &["a", "b"]).await.unwrap();
+		assert_eq!(result.conflicts.len(), 1);
+		assert!(is_resolved(&result, "d"));
+
+		// Conflicts with packages that are required after they were refused are found too
+		let packages = [
+			("b", r#"{ "relations": { "conflicts": "c" } }"#),
+			("c", r#"{ "relations": { "dependencies": "d" } }"#),
+			("d", r#"{}"#),
+			("e", r#"{ "relations": { "dependencies": "c" } }"#),
+		];
+		let result = resolve_test(&packages, &["b", "e"]).await.unwrap();
+		assert_eq!(result.conflicts.len(), 1);
+		assert_eq!(result.conflicts[0].package.id.as_ref(), "b");
+		assert!(!is_resolved(&result, "d"));
+	}
+
+	#[tokio::test]
+	async fn test_resolve_no_cycle() {
+		let packages = [
+			("a", r#"{ "relations": { "dependencies": ["b", "c"] } }"#),
+			("b", r#"{ "relations": { "dependencies": "c" } }"#),
+			("c", r#"{}"#),
+		];
+		let result = resolve_test(&packages, &["a"]).await.unwrap();
+		assert_eq!(result.packages.len(), 3);
+	}
+}
@@ -9,7 +9,9 @@ use anyhow::Context;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use crate::hooks::{Hook, CONFIG_DIR_ENV, CUSTOM_CONFIG_ENV, DATA_DIR_ENV, PLUGIN_STATE_ENV};
+use crate::hooks::{
+	Hook, CONFIG_DIR_ENV, CUSTOM_CONFIG_ENV, DATA_DIR_ENV, INSTANCES_DIR_ENV, PLUGIN_STATE_ENV,
+};
 use crate::output::OutputAction;
 
 use self::output::PluginOutput;
@@ -82,6 +84,7 @@ impl CustomPlugin {
 		ModifyInstanceConfig
 	);
 	hook_interface!(add_versions, "add_versions", AddVersions);
+	hook_interface!(add_repositories, "add_repositories", AddRepositories);
 	hook_interface!(on_instance_setup, "on_instance_setup", OnInstanceSetup);
 	hook_interface!(on_instance_launch, "on_instance_launch", OnInstanceLaunch);
 	hook_interface!(
@@ -90,6 +93,7 @@ impl CustomPlugin {
 		WhileInstanceLaunch
 	);
 	hook_interface!(on_instance_stop, "on_instance_stop", OnInstanceStop);
+	hook_interface!(modify_launch_args, "modify_launch_args", ModifyLaunchArgs);
 	hook_interface!(
 		custom_package_instruction,
 		"custom_package_instruction",
@@ -188,6 +192,11 @@ impl<'ctx, H: Hook> HookContext<'ctx, H> {
 		get_env_path(CONFIG_DIR_ENV).context("Failed to get directory from environment variable")
 	}
 
+	/// Get the path to the directory where the directories of instances are stored
+	pub fn get_instances_dir(&self) -> anyhow::Result<PathBuf> {
+		get_env_path(INSTANCES_DIR_ENV).context("Failed to get directory from environment variable")
+	}
+
 	/// Get the persistent plugin state, kept the same for this entire hook handler,
 	/// along with a default state
 	pub fn get_persistent_state(
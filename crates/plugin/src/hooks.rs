@@ -1,18 +1,23 @@
-use std::io::{BufRead, BufReader};
+use std::future::Future;
 use std::ops::Deref;
 use std::path::Path;
-use std::process::{Child, ChildStdout, Command};
+use std::process::ExitStatus;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use anyhow::{anyhow, bail, Context};
+use anyhow::{anyhow, bail, ensure, Context};
 use mcvm_core::net::minecraft::MinecraftUserProfile;
 use mcvm_core::{net::game_files::version_manifest::VersionEntry, Paths};
 use mcvm_pkg::script_eval::AddonInstructionData;
 use mcvm_pkg::{RecommendedPackage, RequiredPackage};
 use mcvm_shared::lang::translate::LanguageMap;
+use mcvm_shared::modifications::Modloader;
 use mcvm_shared::pkg::PackageID;
 use mcvm_shared::{output::MCVMOutput, versions::VersionInfo, Side};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::time::Instant;
 
 use crate::output::OutputAction;
 
@@ -22,6 +27,8 @@ pub static CUSTOM_CONFIG_ENV: &str = "MCVM_CUSTOM_CONFIG";
 pub static DATA_DIR_ENV: &str = "MCVM_DATA_DIR";
 /// The environment variable for the config directory passed to a hook
 pub static CONFIG_DIR_ENV: &str = "MCVM_CONFIG_DIR";
+/// The environment variable for the instances directory passed to a hook
+pub static INSTANCES_DIR_ENV: &str = "MCVM_INSTANCES_DIR";
 /// The environment variable for the plugin state passed to a hook
 pub static PLUGIN_STATE_ENV: &str = "MCVM_PLUGIN_STATE";
 /// The environment variable for the version of MCVM
@@ -47,7 +54,7 @@ pub trait Hook {
 		false
 	}
 
-	/// Call the hook using the specified program
+	/// Call the hook using the specified program. This has to be called from inside of an async runtime
 	#[allow(clippy::too_many_arguments)]
 	fn call(
 		&self,
@@ -61,6 +68,7 @@ pub trait Hook {
 		paths: &Paths,
 		mcvm_version: Option<&str>,
 		plugin_id: &str,
+		timeout: Option<Duration>,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<HookHandle<Self>>
 	where
@@ -79,6 +87,7 @@ pub trait Hook {
 		}
 		cmd.env(DATA_DIR_ENV, &paths.data);
 		cmd.env(CONFIG_DIR_ENV, paths.project.config_dir());
+		cmd.env(INSTANCES_DIR_ENV, &paths.instances);
 		if let Some(mcvm_version) = mcvm_version {
 			cmd.env(MCVM_VERSION_ENV, mcvm_version);
 		}
@@ -96,7 +105,8 @@ pub trait Hook {
 		}
 
 		if Self::get_takes_over() {
-			cmd.spawn()?.wait()?;
+			let mut child = cmd.spawn()?;
+			block_on(child.wait())??;
 
 			Ok(HookHandle::constant(
 				Self::Result::default(),
@@ -106,15 +116,13 @@ pub trait Hook {
 			cmd.stdout(std::process::Stdio::piped());
 
 			let mut child = cmd.spawn()?;
-
 			let stdout = child.stdout.take().unwrap();
-			let stdout_reader = BufReader::new(stdout);
 
 			let handle = HookHandle {
 				inner: HookHandleInner::Process {
 					child,
-					stdout: stdout_reader,
-					line_buf: String::new(),
+					lines: Box::new(BufReader::new(stdout).lines()),
+					deadline: timeout.map(|x| Instant::now() + x),
 					result: None,
 				},
 				plugin_state: Some(state),
@@ -152,95 +160,115 @@ impl<H: Hook> HookHandle<H> {
 		&self.plugin_id
 	}
 
-	/// Poll the handle, returning true if the handle is ready
+	/// Poll the handle, returning true if the handle is ready. This blocks until the hook outputs
+	/// something, so use poll_async instead from async code
 	pub fn poll(&mut self, o: &mut impl MCVMOutput) -> anyhow::Result<bool> {
-		match &mut self.inner {
-			HookHandleInner::Process {
-				line_buf,
-				stdout,
-				result,
-				..
-			} => {
-				line_buf.clear();
-				let result_len = stdout.read_line(line_buf)?;
-				// EoF
-				if result_len == 0 {
-					return Ok(true);
-				}
-				let line = line_buf.trim_end_matches("\r\n").trim_end_matches('\n');
-
-				let action = OutputAction::deserialize(line, self.use_base64)
-					.context("Failed to deserialize plugin action")?;
-				match action {
-					OutputAction::SetResult(new_result) => {
-						*result = Some(
-							serde_json::from_str(&new_result)
-								.context("Failed to deserialize hook result")?,
-						);
-					}
-					OutputAction::SetState(new_state) => {
-						let state = self
-							.plugin_state
-							.as_mut()
-							.context("Hook handle does not have a reference to persistent state")?;
-						let mut lock = state.lock().map_err(|x| anyhow!("{x}"))?;
-						*lock = new_state;
-					}
-					OutputAction::Text(text, level) => {
-						o.display_text(text, level);
-					}
-					OutputAction::Message(message) => {
-						o.display_message(message);
-					}
-					OutputAction::StartProcess => {
-						o.start_process();
-					}
-					OutputAction::EndProcess => {
-						o.end_process();
-					}
-					OutputAction::StartSection => {
-						o.start_section();
-					}
-					OutputAction::EndSection => {
-						o.end_section();
-					}
-				}
+		if let HookHandleInner::Constant(..) = &self.inner {
+			return Ok(true);
+		}
+		block_on(self.poll_async(o))?
+	}
 
-				Ok(false)
+	/// Poll the handle without blocking the async runtime, returning true if the handle is ready
+	pub async fn poll_async(&mut self, o: &mut impl MCVMOutput) -> anyhow::Result<bool> {
+		let HookHandleInner::Process {
+			child,
+			lines,
+			deadline,
+			..
+		} = &mut self.inner
+		else {
+			return Ok(true);
+		};
+
+		let line = if let Some(deadline) = deadline {
+			match tokio::time::timeout_at(*deadline, lines.next_line()).await {
+				Ok(line) => line,
+				Err(..) => {
+					let _ = child.start_kill();
+					bail!("Hook timed out and was stopped");
+				}
 			}
-			HookHandleInner::Constant(..) => Ok(true),
-		}
+		} else {
+			lines.next_line().await
+		};
+
+		self.handle_line(line.transpose(), o)
 	}
 
-	/// Get the result of the hook by waiting for it
-	pub fn result(mut self, o: &mut impl MCVMOutput) -> anyhow::Result<H::Result> {
-		if let HookHandleInner::Process { .. } = &self.inner {
-			loop {
-				let result = self.poll(o)?;
-				if result {
-					break;
+	/// Handle a line of output from the hook process, returning true if the process has ended
+	fn handle_line(
+		&mut self,
+		line: Option<std::io::Result<String>>,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<bool> {
+		// EoF
+		let Some(line) = line else {
+			return Ok(true);
+		};
+		let line = line.context("Failed to read output from hook")?;
+
+		let action = OutputAction::deserialize(&line, self.use_base64)
+			.context("Failed to deserialize plugin action")?;
+		match action {
+			OutputAction::SetResult(new_result) => {
+				if let HookHandleInner::Process { result, .. } = &mut self.inner {
+					*result = Some(
+						serde_json::from_str(&new_result)
+							.context("Failed to deserialize hook result")?,
+					);
 				}
 			}
+			OutputAction::SetState(new_state) => {
+				let state = self
+					.plugin_state
+					.as_mut()
+					.context("Hook handle does not have a reference to persistent state")?;
+				let mut lock = state.lock().map_err(|x| anyhow!("{x}"))?;
+				*lock = new_state;
+			}
+			OutputAction::Text(text, level) => {
+				o.display_text(text, level);
+			}
+			OutputAction::Message(message) => {
+				o.display_message(message);
+			}
+			OutputAction::StartProcess => {
+				o.start_process();
+			}
+			OutputAction::EndProcess => {
+				o.end_process();
+			}
+			OutputAction::StartSection => {
+				o.start_section();
+			}
+			OutputAction::EndSection => {
+				o.end_section();
+			}
 		}
 
+		Ok(false)
+	}
+
+	/// Get the result of the hook by waiting for it. Use result_async instead from async code
+	pub fn result(self, o: &mut impl MCVMOutput) -> anyhow::Result<H::Result> {
+		match self.inner {
+			HookHandleInner::Constant(result) => Ok(result),
+			HookHandleInner::Process { .. } => block_on(self.result_async(o))?,
+		}
+	}
+
+	/// Get the result of the hook by waiting for it without blocking the async runtime
+	pub async fn result_async(mut self, o: &mut impl MCVMOutput) -> anyhow::Result<H::Result> {
+		while !self.poll_async(o).await? {}
+
 		match self.inner {
 			HookHandleInner::Constant(result) => Ok(result),
 			HookHandleInner::Process {
 				mut child, result, ..
 			} => {
-				let cmd_result = child.wait()?;
-
-				if !cmd_result.success() {
-					if let Some(exit_code) = cmd_result.code() {
-						bail!("Hook returned a non-zero exit code of {}", exit_code);
-					} else {
-						bail!("Hook returned a non-zero exit code");
-					}
-				}
-
-				let result = result.context("Plugin hook did not return a result")?;
-
-				Ok(result)
+				let cmd_result = child.wait().await.context("Failed to wait for hook")?;
+				check_hook_result(cmd_result, result)
 			}
 		}
 	}
@@ -253,7 +281,7 @@ impl<H: Hook> HookHandle<H> {
 			HookHandleInner::Process {
 				mut child, result, ..
 			} => {
-				child.kill()?;
+				child.start_kill()?;
 
 				Ok(result)
 			}
@@ -266,14 +294,42 @@ enum HookHandleInner<H: Hook> {
 	/// Result is coming from a running process
 	Process {
 		child: Child,
-		line_buf: String,
-		stdout: BufReader<ChildStdout>,
+		/// Lines of output from the process
+		lines: Box<Lines<BufReader<ChildStdout>>>,
+		/// The time at which the process will be stopped if it hasn't finished
+		deadline: Option<Instant>,
 		result: Option<H::Result>,
 	},
 	/// Result is a constant, either from a constant hook or a takeover hook
 	Constant(H::Result),
 }
 
+/// Check the exit status of a finished hook process and get its result
+fn check_hook_result<R>(status: ExitStatus, result: Option<R>) -> anyhow::Result<R> {
+	if !status.success() {
+		if let Some(exit_code) = status.code() {
+			bail!("Hook returned a non-zero exit code of {}", exit_code);
+		} else {
+			bail!("Hook returned a non-zero exit code");
+		}
+	}
+
+	result.context("Plugin hook did not return a result")
+}
+
+/// Wait for a future from synchronous code. Hook processes are driven by the async runtime,
+/// so this has to be inside of a multi-threaded one that can keep running while we block
+fn block_on<F: Future>(f: F) -> anyhow::Result<F::Output> {
+	let handle = tokio::runtime::Handle::try_current()
+		.context("Hooks must be run inside of an async runtime")?;
+	ensure!(
+		handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread,
+		"Hooks can only be waited on synchronously inside of a multi-threaded async runtime"
+	);
+
+	Ok(tokio::task::block_in_place(|| handle.block_on(f)))
+}
+
 macro_rules! def_hook {
 	($struct:ident, $name:literal, $desc:literal, $arg:ty, $res:ty, $($extra:tt)*) => {
 		#[doc = $desc]
@@ -337,6 +393,26 @@ def_hook!(
 	Vec<VersionEntry>,
 );
 
+def_hook!(
+	AddRepositories,
+	"add_repositories",
+	"Hook for adding extra package repositories",
+	(),
+	Vec<PluginRepository>,
+);
+
+/// A package repository provided by a plugin
+#[derive(Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct PluginRepository {
+	/// The ID of the repository
+	pub id: String,
+	/// The URL to the repository
+	pub url: String,
+	/// Whether to place this repository before all other repositories instead of after them
+	pub high_priority: bool,
+}
+
 def_hook!(
 	OnInstanceSetup,
 	"on_instance_setup",
@@ -385,7 +461,7 @@ def_hook!(
 	(),
 );
 
-/// Argument for the OnInstanceLaunch and WhileInstanceLaunch hooks
+/// Argument for the OnInstanceLaunch, WhileInstanceLaunch, and OnInstanceStop hooks
 #[derive(Serialize, Deserialize, Default)]
 #[serde(default)]
 pub struct InstanceLaunchArg {
@@ -403,6 +479,42 @@ pub struct InstanceLaunchArg {
 	pub custom_config: serde_json::Map<String, serde_json::Value>,
 	/// The PID of the instance process
 	pub pid: Option<u32>,
+	/// The exit code of the instance process. Only set for the OnInstanceStop hook,
+	/// and only if the process exited normally instead of being killed
+	pub exit_code: Option<i32>,
+}
+
+def_hook!(
+	ModifyLaunchArgs,
+	"modify_launch_args",
+	"Hook for modifying the arguments that an instance is launched with",
+	ModifyLaunchArgsArg,
+	ModifyLaunchArgsResult,
+);
+
+/// Argument for the ModifyLaunchArgs hook
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ModifyLaunchArgsArg {
+	/// The ID of the instance
+	pub id: String,
+	/// The side of the instance
+	pub side: Option<Side>,
+	/// The current arguments for the JVM
+	pub jvm_args: Vec<String>,
+	/// The current arguments for the game
+	pub game_args: Vec<String>,
+}
+
+/// Result from the ModifyLaunchArgs hook. Arguments that are set will replace the current ones,
+/// and ones that are left out will stay the same
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ModifyLaunchArgsResult {
+	/// The new arguments for the JVM
+	pub jvm_args: Option<Vec<String>>,
+	/// The new arguments for the game
+	pub game_args: Option<Vec<String>>,
 }
 
 def_hook!(
@@ -549,4 +661,70 @@ pub struct ExportInstanceArg {
 	pub game_dir: String,
 	/// The desired path for the resulting instance
 	pub result_path: String,
+	/// The resolved Minecraft version of the instance
+	pub minecraft_version: Option<String>,
+	/// The modloader of the instance
+	pub modloader: Option<Modloader>,
+	/// The version of the modloader, if it is known
+	pub modloader_version: Option<String>,
+	/// Whether to leave out world saves from the export
+	pub exclude_worlds: bool,
+}
+
+#[cfg(test)]
+mod tests {
+	use mcvm_shared::output::NoOp;
+
+	use super::*;
+
+	#[tokio::test(flavor = "multi_thread")]
+	#[cfg(target_family = "unix")]
+	async fn test_hook_timeout() {
+		let paths = Paths::new_no_create().unwrap();
+		let handle = OnLoad
+			.call(
+				"sh",
+				&(),
+				&["-c".into(), "sleep 5".into()],
+				None,
+				true,
+				None,
+				Arc::new(Mutex::new(serde_json::Value::Null)),
+				&paths,
+				None,
+				"test",
+				Some(Duration::from_millis(100)),
+				&mut NoOp,
+			)
+			.unwrap();
+		let start = Instant::now();
+		assert!(handle.result(&mut NoOp).is_err());
+		assert!(start.elapsed() < Duration::from_secs(5));
+	}
+
+	#[tokio::test]
+	#[cfg(target_family = "unix")]
+	async fn test_hook_result_async() {
+		let paths = Paths::new_no_create().unwrap();
+		let output = OutputAction::SetResult("null".into())
+			.serialize(true)
+			.unwrap();
+		let handle = OnLoad
+			.call(
+				"sh",
+				&(),
+				&["-c".into(), format!("echo '{output}'")],
+				None,
+				true,
+				None,
+				Arc::new(Mutex::new(serde_json::Value::Null)),
+				&paths,
+				None,
+				"test",
+				Some(Duration::from_secs(5)),
+				&mut NoOp,
+			)
+			.unwrap();
+		assert!(handle.result_async(&mut NoOp).await.is_ok());
+	}
 }
@@ -18,6 +18,8 @@ pub mod hooks;
 pub mod output;
 /// Plugins
 pub mod plugin;
+/// Running hook handlers that are WebAssembly modules
+mod wasm;
 
 /// A manager for plugins that is used to call their hooks
 #[derive(Debug)]
@@ -85,6 +87,28 @@ impl PluginManager {
 		Ok(out)
 	}
 
+	/// Call a plugin hook on each plugin in order, where the result from each plugin
+	/// is used to update the argument passed to the next one. Returns the final argument
+	pub fn call_hook_chained<H: Hook>(
+		&self,
+		hook: H,
+		mut arg: H::Arg,
+		update: impl Fn(&mut H::Arg, H::Result),
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<H::Arg> {
+		for plugin in &self.plugins {
+			let result = plugin
+				.call_hook(&hook, &arg, paths, self.mcvm_version, o)
+				.context("Plugin hook failed")?;
+			if let Some(result) = result {
+				update(&mut arg, result.result(o)?);
+			}
+		}
+
+		Ok(arg)
+	}
+
 	/// Call a plugin hook on the manager on a specific plugin
 	pub fn call_hook_on_plugin<H: Hook>(
 		&self,
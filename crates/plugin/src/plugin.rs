@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use mcvm_core::Paths;
 use mcvm_shared::output::MCVMOutput;
 use serde::{Deserialize, Deserializer};
+use wasmtime::Module;
 
 use crate::hooks::{Hook, HookHandle};
 
@@ -24,6 +26,10 @@ pub struct Plugin {
 	working_dir: Option<PathBuf>,
 	/// The persistent state of the plugin
 	state: Arc<Mutex<serde_json::Value>>,
+	/// The maximum amount of time that hooks can run for
+	hook_timeout: Option<Duration>,
+	/// WebAssembly modules that have already been compiled, by their path
+	wasm_modules: Mutex<HashMap<PathBuf, Module>>,
 }
 
 impl Plugin {
@@ -35,6 +41,8 @@ impl Plugin {
 			custom_config: None,
 			working_dir: None,
 			state: Arc::new(Mutex::new(serde_json::Value::Null)),
+			hook_timeout: None,
+			wasm_modules: Mutex::new(HashMap::new()),
 		}
 	}
 
@@ -73,6 +81,7 @@ impl Plugin {
 					paths,
 					mcvm_version,
 					&self.id,
+					self.hook_timeout,
 					o,
 				)
 				.map(Some),
@@ -88,9 +97,49 @@ impl Plugin {
 					.context("Failed to deserialize native hook result")?;
 				Ok(Some(HookHandle::constant(result, self.id.clone())))
 			}
+			HookHandler::Wasm { module } => {
+				let arg = serde_json::to_string(arg)
+					.context("Failed to serialize WebAssembly hook argument")?;
+				let result = self.call_wasm_hook(module, hook.get_name(), &arg)?;
+				let result = serde_json::from_str(&result)
+					.context("Failed to deserialize WebAssembly hook result")?;
+				Ok(Some(HookHandle::constant(result, self.id.clone())))
+			}
 		}
 	}
 
+	/// Call the function for a hook in a WebAssembly module, compiling the module
+	/// if it hasn't been used yet
+	fn call_wasm_hook(&self, module: &Path, hook: &str, arg: &str) -> anyhow::Result<String> {
+		let path = if let Some(working_dir) = &self.working_dir {
+			working_dir.join(module)
+		} else {
+			module.to_owned()
+		};
+		let module = {
+			let mut modules = self
+				.wasm_modules
+				.lock()
+				.map_err(|_| anyhow!("Lock poisoned"))?;
+			if let Some(module) = modules.get(&path) {
+				module.clone()
+			} else {
+				let module = crate::wasm::load_module(&path)
+					.with_context(|| format!("Failed to load module {}", path.display()))?;
+				modules.insert(path.clone(), module.clone());
+				module
+			}
+		};
+
+		crate::wasm::call_hook(&module, hook, arg, self.hook_timeout).with_context(|| {
+			format!(
+				"WebAssembly hook handler {} of plugin '{}' failed",
+				path.display(),
+				self.id
+			)
+		})
+	}
+
 	/// Set the custom config of the plugin
 	pub fn set_custom_config(&mut self, config: serde_json::Value) -> anyhow::Result<()> {
 		let serialized =
@@ -103,6 +152,11 @@ impl Plugin {
 	pub fn set_working_dir(&mut self, dir: PathBuf) {
 		self.working_dir = Some(dir);
 	}
+
+	/// Set the maximum amount of time that hooks can run for before they are stopped
+	pub fn set_hook_timeout(&mut self, timeout: Duration) {
+		self.hook_timeout = Some(timeout);
+	}
 }
 
 /// Configuration for a plugin
@@ -150,6 +204,12 @@ pub enum HookHandler {
 		/// The constant result
 		constant: serde_json::Value,
 	},
+	/// Handle this hook by calling the function with the hook's name that is
+	/// exported from a WebAssembly module
+	Wasm {
+		/// The path to the module, relative to the plugin's working directory
+		module: PathBuf,
+	},
 	/// Handle this hook with a native function call
 	Native {
 		/// The function to handle the hook
@@ -175,3 +235,63 @@ where
 
 /// Type for native plugin hook handlers
 pub type NativeHookHandler = Arc<dyn Fn(String) -> anyhow::Result<String> + Send + Sync + 'static>;
+
+#[cfg(test)]
+mod tests {
+	use mcvm_shared::output::NoOp;
+
+	use super::*;
+	use crate::hooks::ModifyInstanceConfig;
+
+	#[test]
+	fn test_wasm_handler_parsing() {
+		let manifest: PluginManifest =
+			serde_json::from_str(r#"{"hooks": {"on_load": {"module": "plugin.wasm"}}}"#).unwrap();
+		assert!(matches!(
+			manifest.hooks.get("on_load"),
+			Some(HookHandler::Wasm { module }) if module == Path::new("plugin.wasm")
+		));
+	}
+
+	#[test]
+	fn test_wasm_handler() {
+		let dir = std::env::temp_dir().join("mcvm_test_wasm_handler");
+		std::fs::create_dir_all(&dir).unwrap();
+		let module = wat::parse_str(
+			r#"
+			(module
+				(memory (export "memory") 1)
+				(data (i32.const 0) "{\"additional_jvm_args\":[\"-Xmx2G\"]}")
+				(func (export "mcvm_alloc") (param i32) (result i32)
+					i32.const 1024)
+				(func (export "modify_instance_config") (param i32 i32) (result i64)
+					i64.const 34))
+			"#,
+		)
+		.unwrap();
+		std::fs::write(dir.join("plugin.wasm"), module).unwrap();
+
+		let manifest = serde_json::from_str(
+			r#"{"hooks": {"modify_instance_config": {"module": "plugin.wasm"}}}"#,
+		)
+		.unwrap();
+		let mut plugin = Plugin::new("test".into(), manifest);
+		plugin.set_working_dir(dir.clone());
+		let paths = Paths::new_no_create().unwrap();
+		let result = plugin
+			.call_hook(
+				&ModifyInstanceConfig,
+				&serde_json::Map::new(),
+				&paths,
+				None,
+				&mut NoOp,
+			)
+			.unwrap()
+			.unwrap()
+			.result(&mut NoOp)
+			.unwrap();
+		assert_eq!(result.additional_jvm_args, vec!["-Xmx2G".to_string()]);
+
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
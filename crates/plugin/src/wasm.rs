@@ -0,0 +1,145 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::Context;
+use wasmtime::{Config, Engine, Instance, Module, Store};
+
+/// The function that modules export to allocate memory for the hook argument.
+/// It takes the length of the argument and returns a pointer to the allocated memory
+const ALLOC_FUNCTION: &str = "mcvm_alloc";
+/// The memory that modules export for the argument and result to be passed through
+const MEMORY_EXPORT: &str = "memory";
+/// How often the engine's epoch is increased, which is the precision of hook timeouts
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Get the engine shared by all WebAssembly hook handlers. The first call starts a thread
+/// that ticks the epoch of the engine, so that hooks can be interrupted when they time out
+fn get_engine() -> anyhow::Result<&'static Engine> {
+	static ENGINE: OnceLock<Engine> = OnceLock::new();
+	if let Some(engine) = ENGINE.get() {
+		return Ok(engine);
+	}
+
+	let mut config = Config::new();
+	config.epoch_interruption(true);
+	let engine = Engine::new(&config).context("Failed to create WebAssembly engine")?;
+	let mut started = false;
+	let engine = ENGINE.get_or_init(|| {
+		started = true;
+		engine
+	});
+	if started {
+		let ticker = engine.clone();
+		std::thread::spawn(move || loop {
+			std::thread::sleep(EPOCH_TICK);
+			ticker.increment_epoch();
+		});
+	}
+
+	Ok(engine)
+}
+
+/// Load and compile a WebAssembly module from a file
+pub(crate) fn load_module(path: &Path) -> anyhow::Result<Module> {
+	Module::from_file(get_engine()?, path).context("Failed to load WebAssembly module")
+}
+
+/// Call the function exported by a module for a hook. The JSON argument is written into memory
+/// allocated by the module, and the function is called with its pointer and length. The function
+/// returns the pointer to the JSON result in the upper 32 bits and its length in the lower 32 bits
+pub(crate) fn call_hook(
+	module: &Module,
+	hook: &str,
+	arg: &str,
+	timeout: Option<Duration>,
+) -> anyhow::Result<String> {
+	let mut store = Store::new(module.engine(), ());
+	let ticks = match timeout {
+		Some(timeout) => (timeout.as_millis() / EPOCH_TICK.as_millis()) as u64 + 1,
+		None => u64::MAX / 2,
+	};
+	store.set_epoch_deadline(ticks);
+
+	let instance =
+		Instance::new(&mut store, module, &[]).context("Failed to instantiate module")?;
+	let memory = instance
+		.get_memory(&mut store, MEMORY_EXPORT)
+		.with_context(|| format!("Module does not export '{MEMORY_EXPORT}'"))?;
+	let alloc = instance
+		.get_typed_func::<i32, i32>(&mut store, ALLOC_FUNCTION)
+		.with_context(|| format!("Module does not export a valid '{ALLOC_FUNCTION}' function"))?;
+	let function = instance
+		.get_typed_func::<(i32, i32), i64>(&mut store, hook)
+		.with_context(|| format!("Module does not export a valid '{hook}' function"))?;
+
+	let arg_len = i32::try_from(arg.len()).context("Hook argument is too large")?;
+	let arg_ptr = alloc
+		.call(&mut store, arg_len)
+		.context("Failed to allocate memory for the hook argument")?;
+	memory
+		.write(&mut store, arg_ptr as u32 as usize, arg.as_bytes())
+		.context("Memory allocated for the hook argument is out of bounds")?;
+
+	let result = function
+		.call(&mut store, (arg_ptr, arg_len))
+		.with_context(|| format!("Hook function '{hook}' failed or timed out"))?;
+	let result = result as u64;
+	let result_ptr = (result >> 32) as usize;
+	let result_len = (result & u32::MAX as u64) as usize;
+	let mut out = vec![0; result_len];
+	memory
+		.read(&store, result_ptr, &mut out)
+		.context("Hook result is out of bounds")?;
+
+	String::from_utf8(out).context("Hook result is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A module that returns a fixed result and allocates the argument after it
+	const TEST_MODULE: &str = r#"
+		(module
+			(memory (export "memory") 1)
+			(data (i32.const 0) "{\"additional_jvm_args\":[\"-Xmx2G\"]}")
+			(func (export "mcvm_alloc") (param i32) (result i32)
+				i32.const 1024)
+			(func (export "modify_instance_config") (param i32 i32) (result i64)
+				i64.const 34)
+			(func (export "loop_forever") (param i32 i32) (result i64)
+				(loop (br 0))
+				i64.const 0))
+	"#;
+
+	fn load_test_module() -> Module {
+		let bytes = wat::parse_str(TEST_MODULE).unwrap();
+		Module::new(get_engine().unwrap(), bytes).unwrap()
+	}
+
+	#[test]
+	fn test_call_hook() {
+		let module = load_test_module();
+		let result = call_hook(&module, "modify_instance_config", "{}", None).unwrap();
+		assert_eq!(result, r#"{"additional_jvm_args":["-Xmx2G"]}"#);
+	}
+
+	#[test]
+	fn test_missing_hook_function() {
+		let module = load_test_module();
+		assert!(call_hook(&module, "on_load", "null", None).is_err());
+	}
+
+	#[test]
+	fn test_hook_timeout() {
+		let module = load_test_module();
+		let result = call_hook(
+			&module,
+			"loop_forever",
+			"null",
+			Some(Duration::from_millis(50)),
+		);
+		assert!(result.is_err());
+	}
+}
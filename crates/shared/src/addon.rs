@@ -34,7 +34,9 @@ pub enum AddonKind {
 	Mod,
 	/// A server plugin that modifies game behavior
 	Plugin,
-	/// A graphics shader that needs to be loaded by a shader modification
+	/// A graphics shader pack that needs to be loaded by a shader modification
+	/// like Iris or OptiFine
+	#[serde(alias = "shader_pack")]
 	Shader,
 	/// A Minecraft datapack
 	Datapack,
@@ -47,7 +49,7 @@ impl AddonKind {
 			"resource_pack" => Some(Self::ResourcePack),
 			"mod" => Some(Self::Mod),
 			"plugin" => Some(Self::Plugin),
-			"shader" => Some(Self::Shader),
+			"shader" | "shader_pack" => Some(Self::Shader),
 			"datapack" => Some(Self::Datapack),
 			_ => None,
 		}
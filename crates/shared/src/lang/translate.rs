@@ -48,16 +48,19 @@ define_translations! {
 	FinishUpdatingPackages, "When finishing updating packages on a profile", "All packages installed";
 	StartUpdatingProfileVersion, "When starting to update a profile's version", "Updating profile version";
 	FinishUpdatingProfileVersion, "When finishing updating a profile's version", "Profile version updated";
+	NoCompatibleLoaderApi, "When there is no version of the Fabric API or QSL for a Minecraft version", "No version of %api supports Minecraft %version, so it will not be installed";
 	StartUpdatingProxy, "When starting to update a proxy", "Checking for proxy updates";
 	FinishUpdatingProxy, "When finishing updating a proxy", "Proxy updated";
 	StartRunningCommands, "When starting to run package commands", "Running commands";
 	FinishRunningCommands, "When finishing running package commands", "Finished running commands";
 	StartUpdatingInstance, "When starting to update an instance", "Updating instance %inst";
 	PreparingLaunch, "When preparing to launch the game", "Preparing to launch";
+	SnapshotLaunchWarning, "When launching a development snapshot of the game", "%version is a development snapshot. Many mods and plugins do not support snapshots";
 	Launch, "When launching the game", "Launching!";
 	CoreRepoName, "Name of the core repo", "Core";
 	CoreRepoDescription, "Description of the core repo", "The built-in set of packages";
 	RepoVersionWarning, "Warning when a remote repo version is too high", "Minimum MCVM version for repository %repo is higher than current installation";
+	CorruptLockfileWarning, "When the lockfile is corrupt and the backup is loaded instead", "The lockfile could not be read and was loaded from the backup instead: %error";
 	OverwriteAddonFilePrompt, "Prompt when an addon file would be overwriten", "The existing file '%file' has the same path as an addon. Overwrite it?";
 	CustomInstructionsWarning, "When a package uses unrecognized custom instructions", "Package uses custom instructions that MCVM does not recognize";
 	Redownloading, "When something is being redownloaded", "Redownloading";
@@ -75,17 +78,24 @@ define_translations! {
 	NoUsers, "When no users are available", "No users are available";
 	ModificationNotSupported, "When a game modification can't be installed by MCVM", "%mod installation is currently unimplemented by mcvm. You will be expected to install it yourself for the time being";
 	EmptyProfile, "When a profile has no instances", "Profile '%profile' does not have any instances";
+	RateLimited, "When a request was rate-limited and will be retried after waiting", "Rate limited by %host, retrying in %seconds seconds";
+	ProfileManifestNotDownloaded, "When the manifest of a subscribed profile has not been downloaded yet", "The manifest for profile '%profile' has not been downloaded yet. Run `mcvm profile update %profile` to download it";
 	StartDownloadingVersionManifest, "When starting to download the version manifest", "Downloading version manifest";
+	UsingCachedVersionManifest, "When the version manifest can't be downloaded and a cached copy is used instead", "Using cached version manifest (offline)";
+	VersionManifestMirrorFailed, "When the version manifest can't be downloaded from the configured mirror and the official server is used instead", "Failed to download version manifest from mirror, using the official server instead";
 	StartDownloadingGameJar, "When starting to download the game jar", "Downloading %side jar";
 	FinishDownloadingGameJar, "When finishing downloading the game jar", "%side jar downloaded";
+	GameFileHashMismatch, "When a downloaded game file doesn't match its expected hash and will be downloaded again", "%file did not match its expected hash. Redownloading";
 	StartCheckingForJavaUpdates, "When starting to check for Java updates", "Checking for Java updates";
 	FinishCheckingForJavaUpdates, "When finishing checking for Java updates", "Java updated";
+	JavaVersionOverrideTooLow, "When the Java version override is lower than the version the game needs", "Java %version is lower than Java %required, which this version of the game needs. The game will likely fail to launch";
 	FinishJavaInstallation, "When finishing installing Java", "Java installation finished";
 	StartExtractingJava, "When starting to extract the JRE", "Extracting JRE";
 	StartRemovingJavaArchive, "When starting to remove the Java archive", "Removing archive";
 	DownloadingGraalVM, "When starting to download GraalVM", "Downloading GraalVM";
 	DownloadingZulu, "When starting to download Zulu", "Downloading Azul Zulu JRE version %version";
 	DownloadingAdoptium, "When starting to download Adoptium", "Downloading Adoptium Temurin JRE version %version";
+	DownloadingMojangJava, "When starting to download a Java runtime from Mojang", "Downloading Mojang Java runtime %component version %version";
 	StartUpdatingClient, "When starting to update a client", "Updating client '%id'";
 	StartUpdatingServer, "When starting to update a server", "Updating server '%id'";
 	PasskeyAccepted, "When finishing decrypting with a passkey", "Passkey accepted";
@@ -49,6 +49,13 @@ pub trait MCVMOutput {
 		self.prompt_password(message)
 	}
 
+	/// Report an event for a phase of updating an instance. This is sent alongside the
+	/// normal messages, so that implementations like GUIs can show which phase is running
+	/// and how far along it is without parsing text
+	fn display_update_phase(&mut self, phase: UpdatePhase, event: UpdatePhaseEvent) {
+		let _ = (phase, event);
+	}
+
 	/// Get the translation for the specified key
 	fn translate(&self, key: TranslationKey) -> &str {
 		key.get_default()
@@ -155,6 +162,65 @@ impl MessageContents {
 			MessageContents::Progress { current, total } => format!("{current}/{total}"),
 		}
 	}
+
+	/// Checks if this message is a progress update that hasn't finished yet, either on its
+	/// own or as the item of an associated message. Outputs that can't replace previous lines,
+	/// such as logs or pipes, can use this to skip intermediate updates
+	pub fn is_unfinished_progress(&self) -> bool {
+		match self {
+			MessageContents::Progress { current, total } => current < total,
+			MessageContents::Associated(item, ..) => item.is_unfinished_progress(),
+			_ => false,
+		}
+	}
+}
+
+/// A phase of updating an instance
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdatePhase {
+	/// Getting the version manifest
+	Manifest,
+	/// Getting the client JSON with the metadata of the version
+	ClientJson,
+	/// Downloading game assets
+	Assets,
+	/// Downloading game libraries
+	Libraries,
+	/// Installing Java
+	Java,
+	/// Downloading the game JAR
+	GameJar,
+	/// Installing Fabric or Quilt
+	FabricQuilt,
+	/// Writing game options
+	Options,
+	/// Installing packages
+	Packages,
+}
+
+/// An event for a phase of updating an instance
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdatePhaseEvent {
+	/// The phase started
+	Start,
+	/// Some of the work of the phase was done
+	Progress {
+		/// The current amount completed
+		current: u32,
+		/// The total amount that needs to be completed
+		total: u32,
+	},
+	/// The phase finished successfully
+	End,
+}
+
+impl UpdatePhaseEvent {
+	/// Checks if this event is a progress update that hasn't finished yet
+	pub fn is_unfinished_progress(&self) -> bool {
+		matches!(self, Self::Progress { current, total } if current < total)
+	}
 }
 
 /// The level of logging that a message has
@@ -194,6 +260,33 @@ impl MCVMOutput for NoOp {
 	fn display_text(&mut self, _text: String, _level: MessageLevel) {}
 }
 
+/// MCVMOutput that stores messages so that they can be displayed later,
+/// such as when multiple tasks are running at once
+#[derive(Default)]
+pub struct MessageBuffer(Vec<Message>);
+
+impl MessageBuffer {
+	/// Display all of the stored messages to another output
+	pub fn replay(self, o: &mut impl MCVMOutput) {
+		for message in self.0 {
+			o.display_message(message);
+		}
+	}
+}
+
+impl MCVMOutput for MessageBuffer {
+	fn display_text(&mut self, text: String, level: MessageLevel) {
+		self.display_message(Message {
+			contents: MessageContents::Simple(text),
+			level,
+		});
+	}
+
+	fn display_message(&mut self, message: Message) {
+		self.0.push(message);
+	}
+}
+
 /// MCVMOutput with simple terminal printing
 pub struct Simple(pub MessageLevel);
 
@@ -240,4 +333,32 @@ mod tests {
 		assert!(MessageLevel::Debug.at_least(&MessageLevel::Debug));
 		assert!(!MessageLevel::Debug.at_least(&MessageLevel::Extra));
 	}
+
+	#[test]
+	fn test_unfinished_progress() {
+		let progress = MessageContents::Progress {
+			current: 3,
+			total: 5,
+		};
+		assert!(progress.is_unfinished_progress());
+		let associated = MessageContents::Associated(
+			Box::new(progress),
+			Box::new(MessageContents::Simple("Downloading".into())),
+		);
+		assert!(associated.is_unfinished_progress());
+
+		let finished = MessageContents::Progress {
+			current: 5,
+			total: 5,
+		};
+		assert!(!finished.is_unfinished_progress());
+		assert!(!MessageContents::Simple("Done".into()).is_unfinished_progress());
+
+		assert!(UpdatePhaseEvent::Progress {
+			current: 3,
+			total: 5
+		}
+		.is_unfinished_progress());
+		assert!(!UpdatePhaseEvent::End.is_unfinished_progress());
+	}
 }
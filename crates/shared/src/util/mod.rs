@@ -68,6 +68,26 @@ cfg_match! {
 	}
 }
 
+/// Parse the name of an architecture into the form used by ARCH_STRING,
+/// accepting common aliases like `x64` and `arm64`
+pub fn parse_arch(arch: &str) -> Option<&'static str> {
+	match arch {
+		"x86_64" | "x64" | "amd64" => Some("x86_64"),
+		"aarch64" | "arm64" => Some("aarch64"),
+		"x86" | "i386" | "i686" => Some("x86"),
+		"arm" => Some("arm"),
+		_ => None,
+	}
+}
+
+/// Get the pointer width of an architecture from ARCH_STRING or parse_arch
+pub fn get_arch_bits(arch: &str) -> &'static str {
+	match arch {
+		"x86" | "arm" | "riscv32" | "mips" | "powerpc" => "32",
+		_ => "64",
+	}
+}
+
 /// Adds a dot to the preferred archive name
 pub fn preferred_archive_extension() -> String {
 	format!(".{PREFERRED_ARCHIVE}")
@@ -212,24 +232,39 @@ pub fn yes_no(string: &str) -> Option<bool> {
 
 /// Checks if a string is a valid identifier
 pub fn is_valid_identifier(id: &str) -> bool {
+	validate_identifier(id).is_ok()
+}
+
+/// Checks that a string is a valid identifier, with an error describing why it is not
+pub fn validate_identifier(id: &str) -> anyhow::Result<()> {
+	validate_identifier_with_punctuation(id, &['_', '-', '.'])
+}
+
+/// Checks that a string is a valid identifier that is allowed to contain
+/// the given punctuation characters, with an error describing why it is not
+pub fn validate_identifier_with_punctuation(
+	id: &str,
+	allowed_punctuation: &[char],
+) -> anyhow::Result<()> {
+	if id.is_empty() {
+		anyhow::bail!("Identifiers cannot be empty");
+	}
+
 	for c in id.chars() {
 		if !c.is_ascii() {
-			return false;
+			anyhow::bail!("Identifiers cannot contain the non-ASCII character '{c}'");
 		}
 
-		if c.is_ascii_punctuation() {
-			match c {
-				'_' | '-' | '.' => {}
-				_ => return false,
-			}
+		if c.is_ascii_punctuation() && !allowed_punctuation.contains(&c) {
+			anyhow::bail!("Identifiers cannot contain the character '{c}'");
 		}
 
-		if c.is_ascii_whitespace() {
-			return false;
+		if c.is_ascii_whitespace() || c.is_ascii_control() {
+			anyhow::bail!("Identifiers cannot contain whitespace or control characters");
 		}
 	}
 
-	true
+	Ok(())
 }
 
 /// Utility enum for deserialization that lets you do a list that can be one item
@@ -388,6 +423,18 @@ mod tests {
 		assert!(!is_valid_identifier("hello*world"));
 		assert!(!is_valid_identifier("hello\nworld"));
 		assert!(!is_valid_identifier("hello world"));
+		assert!(!is_valid_identifier(""));
+		assert!(!is_valid_identifier("héllo"));
+	}
+
+	#[test]
+	fn test_arch_parsing() {
+		assert_eq!(parse_arch("x64"), Some("x86_64"));
+		assert_eq!(parse_arch("arm64"), Some("aarch64"));
+		assert_eq!(parse_arch("aarch64"), Some("aarch64"));
+		assert_eq!(parse_arch("sparc"), None);
+		assert_eq!(get_arch_bits("x86_64"), "64");
+		assert_eq!(get_arch_bits("x86"), "32");
 	}
 
 	#[test]
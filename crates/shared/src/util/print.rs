@@ -63,7 +63,7 @@ impl ReplPrinter {
 		let written = get_terminal_width(text) + self.options.indent_str.chars().count();
 
 		// Clear leftover characters from the last print
-		let clear_count = self.chars_written.checked_sub(written).unwrap_or_default();
+		let clear_count = self.chars_written.saturating_sub(written);
 		let _ = write!(self.stdout, "{}", " ".repeat(clear_count));
 
 		self.chars_written = written;
@@ -72,6 +72,10 @@ impl ReplPrinter {
 
 	/// Print text on a new line
 	pub fn println(&mut self, text: &str) {
+		if !self.options.verbose {
+			return;
+		}
+
 		self.chars_written = 0;
 		let _ = writeln!(self.stdout);
 		self.print(text);
@@ -94,6 +94,7 @@ pub struct Index {
 	/// The config for the backups
 	pub config: Config,
 	/// The instance ID for this index
+	#[allow(dead_code)]
 	pub inst_id: String,
 }
 
@@ -342,7 +343,7 @@ pub fn get_backup_directory(base_dir: &Path, inst_id: &str) -> PathBuf {
 /// Generates a random backup ID
 pub fn generate_random_id() -> String {
 	let mut rng = rand::thread_rng();
-	let num = rng.gen_range(0..std::u64::MAX);
+	let num = rng.gen_range(0..u64::MAX);
 	format!("{num:x}")
 }
 
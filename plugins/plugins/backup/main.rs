@@ -162,7 +162,7 @@ fn create(
 
 	let mut index = get_index(ctx, instance)?;
 
-	let inst_dir = ctx.get_data_dir()?.join("instances").join(instance);
+	let inst_dir = ctx.get_instances_dir()?.join(instance);
 
 	index.create_backup(BackupSource::User, Some(group), &inst_dir)?;
 
@@ -201,7 +201,7 @@ fn restore(
 
 	let index = get_index(ctx, instance)?;
 
-	let inst_dir = ctx.get_data_dir()?.join("instances").join(instance);
+	let inst_dir = ctx.get_instances_dir()?.join(instance);
 
 	index.restore_backup(group, backup, &inst_dir)?;
 	index.finish()?;
@@ -248,7 +248,7 @@ fn get_backup_config<H: Hook>(instance: &str, ctx: &HookContext<'_, H>) -> anyho
 	let config = ctx.get_custom_config().unwrap_or("{}");
 	let mut config: HashMap<String, Config> =
 		serde_json::from_str(config).context("Failed to deserialize custom config")?;
-	let config = config.remove(&instance.to_string()).unwrap_or_default();
+	let config = config.remove(instance).unwrap_or_default();
 	Ok(config)
 }
 
@@ -5,7 +5,8 @@ use std::sync::Arc;
 use std::{cmp::Reverse, collections::HashMap};
 
 use iso8601_timestamp::Timestamp;
-use mcvm_core::net::download::Client;
+use mcvm::shared::output::NoOp;
+use mcvm_core::net::download::make_client;
 use mcvm_net::modrinth::Version;
 use serde::{Deserialize, Serialize};
 use serde_json::{ser::PrettyFormatter, Serializer};
@@ -47,7 +48,11 @@ pub struct BatchedPackageConfig {
 }
 
 /// Generate a lot of packages
-pub async fn batched_gen(mut config: BatchedConfig, filter: Vec<String>) {
+pub async fn batched_gen(
+	mut config: BatchedConfig,
+	filter: Vec<String>,
+	download_concurrency: Option<usize>,
+) {
 	// Read config dir for additional packages
 	if let Some(config_dir) = config.config_dir {
 		let config_dir = PathBuf::from(config_dir);
@@ -75,7 +80,7 @@ pub async fn batched_gen(mut config: BatchedConfig, filter: Vec<String>) {
 		config.packages.extend(additional_pkgs);
 	}
 
-	let client = Client::new();
+	let client = make_client(None, None).expect("Failed to create client");
 
 	println!("Requesting API...");
 
@@ -93,13 +98,12 @@ pub async fn batched_gen(mut config: BatchedConfig, filter: Vec<String>) {
 		})
 		.map(|x| x.id.clone())
 		.collect();
-	let modrinth_projects = mcvm_net::modrinth::get_multiple_projects(&modrinth_ids, &client)
-		.await
-		.expect("Failed to get Modrinth projects");
+	let modrinth_projects =
+		mcvm_net::modrinth::get_multiple_projects(&modrinth_ids, &client, &mut NoOp)
+			.await
+			.expect("Failed to get Modrinth projects");
 
-	// Collect Modrinth project versions. We have to batch these into multiple requests because there becomes
-	// just too many parameters for the URL to handle
-	let batch_limit = 215;
+	// Collect Modrinth project versions
 	let modrinth_version_ids: Vec<_> = modrinth_projects
 		.iter()
 		.flat_map(|x| x.versions.iter().cloned())
@@ -111,23 +115,22 @@ pub async fn batched_gen(mut config: BatchedConfig, filter: Vec<String>) {
 		);
 	}
 
-	let chunks = modrinth_version_ids.chunks(batch_limit);
-
 	let modrinth_versions = Arc::new(Mutex::new(Vec::new()));
 	let mut tasks = JoinSet::new();
-	for chunk in chunks {
-		let chunk = chunk.to_vec();
-		let client = client.clone();
-		let modrinth_versions = modrinth_versions.clone();
-		let task = async move {
-			let versions = mcvm_net::modrinth::get_multiple_versions(&chunk, &client)
-				.await
-				.expect("Failed to get Modrinth versions");
-			let mut lock = modrinth_versions.lock().await;
-			lock.extend(versions);
-		};
-		tasks.spawn(task);
-	}
+	let versions_client = client.clone();
+	let versions_out = modrinth_versions.clone();
+	tasks.spawn(async move {
+		let versions = mcvm_net::modrinth::get_multiple_versions(
+			&modrinth_version_ids,
+			download_concurrency,
+			&versions_client,
+			&mut NoOp,
+		)
+		.await
+		.expect("Failed to get Modrinth versions");
+		let mut lock = versions_out.lock().await;
+		lock.extend(versions);
+	});
 
 	// Download Smithed packs at the same time
 	let smithed_packs = Arc::new(Mutex::new(Vec::new()));
@@ -157,9 +160,10 @@ pub async fn batched_gen(mut config: BatchedConfig, filter: Vec<String>) {
 		let client = client.clone();
 		let modrinth_teams = modrinth_teams.clone();
 		let task = async move {
-			let teams = mcvm_net::modrinth::get_multiple_teams(&modrinth_team_ids, &client)
-				.await
-				.expect("Failed to get Modrinth teams");
+			let teams =
+				mcvm_net::modrinth::get_multiple_teams(&modrinth_team_ids, &client, &mut NoOp)
+					.await
+					.expect("Failed to get Modrinth teams");
 			let mut lock = modrinth_teams.lock().await;
 			*lock = teams;
 		};
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::stdout;
+use std::path::Path;
 
 use clap::Parser;
 use mcvm_plugin::api::CustomPlugin;
@@ -17,11 +18,15 @@ pub mod smithed;
 
 fn main() -> anyhow::Result<()> {
 	let mut plugin = CustomPlugin::new("stats")?;
-	plugin.subcommand(|_, args| {
+	plugin.subcommand(|ctx, args| {
 		let Some(subcommand) = args.first() else {
 			return Ok(());
 		};
 		let subcommand = subcommand.to_owned();
+		let download_concurrency = ctx
+			.get_config_dir()
+			.ok()
+			.and_then(|x| get_download_concurrency(&x));
 
 		// Trick the parser to give it the right bin name
 		let it = std::iter::once(format!("mcvm {subcommand}")).chain(args.into_iter().skip(1));
@@ -36,14 +41,14 @@ fn main() -> anyhow::Result<()> {
 					)
 					.expect("Failed to deserialize config")
 				});
-				gen(cli.source, config, &cli.id).await;
+				gen(cli.source, config, &cli.id, download_concurrency).await;
 			} else if subcommand == "gen-pkg-batched" {
 				let cli = GenPkgBatched::parse_from(it);
 				let config = serde_json::from_reader(
 					File::open(cli.config_path).expect("Failed to open config file"),
 				)
 				.expect("Failed to deserialize config");
-				batched::batched_gen(config, cli.filter).await;
+				batched::batched_gen(config, cli.filter, download_concurrency).await;
 			}
 
 			Ok::<(), anyhow::Error>(())
@@ -55,6 +60,16 @@ fn main() -> anyhow::Result<()> {
 	Ok(())
 }
 
+/// Read the maximum number of files to download at the same time from the user's preferences
+fn get_download_concurrency(config_dir: &Path) -> Option<usize> {
+	let path = config_dir.join("mcvm.json");
+	if !path.exists() {
+		return None;
+	}
+	let config = mcvm::config::Config::open(&path).ok()?;
+	config.get_download_concurrency()
+}
+
 #[derive(Parser)]
 struct GenPkg {
 	/// Path to configuration for the package generation
@@ -115,7 +130,12 @@ impl PackageGenerationConfig {
 }
 
 /// Generates a package from a source and config
-pub async fn gen(source: PackageSource, config: Option<PackageGenerationConfig>, id: &str) {
+pub async fn gen(
+	source: PackageSource,
+	config: Option<PackageGenerationConfig>,
+	id: &str,
+	download_concurrency: Option<usize>,
+) {
 	let config = config.unwrap_or_default();
 	let mut pkg = match source {
 		PackageSource::Smithed => {
@@ -128,6 +148,7 @@ pub async fn gen(source: PackageSource, config: Option<PackageGenerationConfig>,
 				&config.force_extensions,
 				config.make_fabriclike.unwrap_or_default(),
 				config.make_forgelike.unwrap_or_default(),
+				download_concurrency,
 			)
 			.await
 		}
@@ -8,7 +8,8 @@ use mcvm::pkg_crate::metadata::PackageMetadata;
 use mcvm::pkg_crate::properties::PackageProperties;
 use mcvm::pkg_crate::RecommendedPackage;
 use mcvm::shared::addon::AddonKind;
-use mcvm::shared::modifications::{ModloaderMatch, PluginLoaderMatch};
+use mcvm::shared::modifications::{Modloader, ModloaderMatch, PluginLoaderMatch};
+use mcvm::shared::output::NoOp;
 use mcvm::shared::pkg::PackageStability;
 use mcvm::shared::util::DeserListOrSingle;
 use mcvm::shared::versions::VersionPattern;
@@ -25,17 +26,24 @@ pub async fn gen(
 	force_extensions: &[String],
 	make_fabriclike: bool,
 	make_forgelike: bool,
+	download_concurrency: Option<usize>,
 ) -> DeclarativePackage {
-	let client = mcvm_core::net::download::Client::new();
-	let project = modrinth::get_project(id, &client)
+	let client =
+		mcvm_core::net::download::make_client(None, None).expect("Failed to create client");
+	let project = modrinth::get_project(id, &client, &mut NoOp)
 		.await
 		.expect("Failed to get Modrinth project");
 
-	let versions = modrinth::get_multiple_versions(&project.versions, &client)
-		.await
-		.expect("Failed to get Modrinth project versions");
+	let versions = modrinth::get_multiple_versions(
+		&project.versions,
+		download_concurrency,
+		&client,
+		&mut NoOp,
+	)
+	.await
+	.expect("Failed to get Modrinth project versions");
 
-	let members = modrinth::get_project_team(id, &client)
+	let members = modrinth::get_project_team(id, &client, &mut NoOp)
 		.await
 		.expect("Failed to get project team members from Modrinth");
 
@@ -162,11 +170,9 @@ pub async fn gen_raw(
 		for loader in &version.loaders {
 			match loader {
 				Loader::Known(loader) => match loader {
-					KnownLoader::Fabric => modloaders.push(if make_fabriclike {
-						ModloaderMatch::FabricLike
-					} else {
-						ModloaderMatch::Fabric
-					}),
+					KnownLoader::Fabric => {
+						modloaders.push(get_fabric_match(version, versions, make_fabriclike))
+					}
 					KnownLoader::Quilt => modloaders.push(ModloaderMatch::Quilt),
 					KnownLoader::Forge => modloaders.push(if make_forgelike {
 						ModloaderMatch::ForgeLike
@@ -269,6 +275,35 @@ pub async fn gen_raw(
 	}
 }
 
+/// Gets the modloader match for the Fabric loader of a version. Quilt can run Fabric mods, but
+/// native Quilt builds are preferred. A Fabric build is only made to match Quilt when it would be
+/// selected for Quilt on one of its game versions, because there is no native Quilt build for it
+fn get_fabric_match(
+	version: &Version,
+	versions: &[Version],
+	make_fabriclike: bool,
+) -> ModloaderMatch {
+	if make_fabriclike {
+		return ModloaderMatch::FabricLike;
+	}
+	if version.get_modloader_preference(&Modloader::Quilt) != Some(1) {
+		return ModloaderMatch::Fabric;
+	}
+
+	let is_quilt_fallback = version.game_versions.iter().any(|game_version| {
+		let candidates = versions
+			.iter()
+			.filter(|x| x.game_versions.contains(game_version));
+		modrinth::select_version_for_modloader(candidates, &Modloader::Quilt)
+			.is_some_and(|x| x.get_modloader_preference(&Modloader::Quilt) == Some(1))
+	});
+	if is_quilt_fallback {
+		ModloaderMatch::FabricLike
+	} else {
+		ModloaderMatch::Fabric
+	}
+}
+
 /// Gets the list of supported sides from the project
 fn get_supported_sides(project: &Project) -> Vec<Side> {
 	let mut out = Vec::with_capacity(2);
@@ -280,3 +315,65 @@ fn get_supported_sides(project: &Project) -> Vec<Side> {
 	}
 	out
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn version(loaders: &[&str], game_version: &str) -> Version {
+		serde_json::from_value(serde_json::json!({
+			"id": format!("{}-{game_version}", loaders.join("-")),
+			"project_id": "project",
+			"name": "version",
+			"version_number": "1.0.0",
+			"version_type": "release",
+			"loaders": loaders,
+			"files": [],
+			"game_versions": [game_version],
+			"dependencies": [],
+			"featured": false,
+			"date_published": "2024-01-01T00:00:00Z",
+		}))
+		.unwrap()
+	}
+
+	#[test]
+	fn test_fabric_match() {
+		let fabric = version(&["fabric"], "1.20.1");
+		let versions = vec![fabric.clone()];
+		assert_eq!(
+			get_fabric_match(&fabric, &versions, false),
+			ModloaderMatch::FabricLike
+		);
+		// Native Quilt builds get their own match
+		let both = version(&["fabric", "quilt"], "1.20.1");
+		let versions = vec![both.clone()];
+		assert_eq!(
+			get_fabric_match(&both, &versions, false),
+			ModloaderMatch::Fabric
+		);
+		assert_eq!(
+			get_fabric_match(&both, &versions, true),
+			ModloaderMatch::FabricLike
+		);
+	}
+
+	#[test]
+	fn test_fabric_match_prefers_quilt() {
+		let fabric = version(&["fabric"], "1.20.1");
+		let quilt = version(&["quilt"], "1.20.1");
+		let old_fabric = version(&["fabric"], "1.19.4");
+		let versions = vec![fabric.clone(), quilt, old_fabric.clone()];
+
+		// There is a native Quilt build for 1.20.1, so the Fabric one shouldn't be used on Quilt
+		assert_eq!(
+			get_fabric_match(&fabric, &versions, false),
+			ModloaderMatch::Fabric
+		);
+		// But Quilt falls back to the Fabric build for 1.19.4
+		assert_eq!(
+			get_fabric_match(&old_fabric, &versions, false),
+			ModloaderMatch::FabricLike
+		);
+	}
+}
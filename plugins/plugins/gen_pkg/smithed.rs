@@ -9,7 +9,7 @@ use mcvm::pkg_crate::properties::PackageProperties;
 use mcvm::shared::addon::AddonKind;
 use mcvm::shared::util::DeserListOrSingle;
 use mcvm::shared::versions::VersionPattern;
-use mcvm_core::net::download::Client;
+use mcvm_core::net::download::make_client;
 
 use mcvm_net::smithed::Pack;
 
@@ -18,9 +18,12 @@ pub async fn gen(
 	relation_substitutions: HashMap<String, String>,
 	force_extensions: &[String],
 ) -> DeclarativePackage {
-	let pack = mcvm_net::smithed::get_pack(id, &Client::new())
-		.await
-		.expect("Failed to get pack");
+	let pack = mcvm_net::smithed::get_pack(
+		id,
+		&make_client(None, None).expect("Failed to create client"),
+	)
+	.await
+	.expect("Failed to get pack");
 
 	gen_raw(pack, relation_substitutions, force_extensions).await
 }
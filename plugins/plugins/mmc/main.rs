@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use mcvm_plugin::api::CustomPlugin;
+use mcvm_plugin::hooks::{
+	ExportInstanceArg, InstanceTransferFeatureSupport, InstanceTransferFormat,
+	InstanceTransferFormatDirection,
+};
+use mcvm_shared::modifications::Modloader;
+use mcvm_shared::Side;
+use serde::Serialize;
+use zip::ZipWriter;
+
+/// The ID of the transfer format
+const FORMAT_ID: &str = "mmc";
+/// The name of the game directory inside of the exported instance
+const GAME_DIR: &str = ".minecraft";
+/// The name of the directory that client worlds are stored in
+const SAVES_DIR: &str = "saves";
+
+fn main() -> anyhow::Result<()> {
+	let mut plugin = CustomPlugin::new("mmc")?;
+	plugin.add_instance_transfer_format(|_, _| {
+		Ok(InstanceTransferFormat {
+			id: FORMAT_ID.into(),
+			import: None,
+			export: Some(InstanceTransferFormatDirection {
+				modloader: InstanceTransferFeatureSupport::Supported,
+				mods: InstanceTransferFeatureSupport::Supported,
+				launch_settings: InstanceTransferFeatureSupport::PluginUnsupported,
+			}),
+		})
+	})?;
+	plugin.export_instance(|_, arg| {
+		if arg.format != FORMAT_ID {
+			return Ok(());
+		}
+		export(&arg).context("Failed to export instance")
+	})?;
+
+	Ok(())
+}
+
+/// Export an instance to a zip file
+fn export(arg: &ExportInstanceArg) -> anyhow::Result<()> {
+	if arg.side != Some(Side::Client) {
+		bail!("The MultiMC format only supports client instances");
+	}
+	let Some(minecraft_version) = &arg.minecraft_version else {
+		bail!("The Minecraft version of the instance is not known");
+	};
+
+	let file = File::create(&arg.result_path).context("Failed to create export file")?;
+	let mut arc = ZipWriter::new(BufWriter::new(file));
+	let options = zip::write::FileOptions::<()>::default()
+		.compression_method(zip::CompressionMethod::Deflated);
+
+	let pack = create_pack(
+		minecraft_version,
+		arg.modloader.as_ref(),
+		arg.modloader_version.as_deref(),
+	)?;
+	arc.start_file("mmc-pack.json", options)?;
+	serde_json::to_writer_pretty(&mut arc, &pack).context("Failed to write mmc-pack.json")?;
+
+	let name = arg.name.as_deref().unwrap_or(&arg.id);
+	arc.start_file("instance.cfg", options)?;
+	write!(&mut arc, "{}", create_instance_cfg(name)).context("Failed to write instance.cfg")?;
+
+	let game_dir = PathBuf::from(&arg.game_dir);
+	let mut files = Vec::new();
+	collect_files(&game_dir, &game_dir, arg.exclude_worlds, &mut files)?;
+	for (path, rel) in files {
+		let zip_path = format!("{GAME_DIR}/{}", rel.join("/"));
+		arc.start_file(zip_path, options)?;
+		let mut file = File::open(&path)
+			.with_context(|| format!("Failed to open file {}", path.to_string_lossy()))?;
+		std::io::copy(&mut file, &mut arc).context("Failed to copy file to archive")?;
+	}
+
+	arc.finish().context("Failed to finish writing archive")?;
+
+	Ok(())
+}
+
+/// Recursively collect the files in the game directory, along with their
+/// path components relative to the game directory
+fn collect_files(
+	dir: &Path,
+	game_dir: &Path,
+	exclude_worlds: bool,
+	out: &mut Vec<(PathBuf, Vec<String>)>,
+) -> anyhow::Result<()> {
+	for entry in dir.read_dir().context("Failed to read directory")? {
+		let entry = entry?;
+		let path = entry.path();
+		let rel = path.strip_prefix(game_dir)?;
+		if exclude_worlds && rel == Path::new(SAVES_DIR) {
+			continue;
+		}
+		if entry.file_type()?.is_dir() {
+			collect_files(&path, game_dir, exclude_worlds, out)?;
+		} else {
+			let components = rel
+				.components()
+				.map(|x| x.as_os_str().to_string_lossy().to_string())
+				.collect();
+			out.push((path, components));
+		}
+	}
+
+	Ok(())
+}
+
+/// Create the contents of the mmc-pack.json file
+fn create_pack(
+	minecraft_version: &str,
+	modloader: Option<&Modloader>,
+	modloader_version: Option<&str>,
+) -> anyhow::Result<Pack> {
+	let mut components = vec![Component {
+		uid: "net.minecraft".into(),
+		version: Some(minecraft_version.into()),
+		important: Some(true),
+	}];
+
+	if let Some(modloader) = modloader {
+		let uid = match modloader {
+			Modloader::Vanilla => None,
+			Modloader::Fabric => Some("net.fabricmc.fabric-loader"),
+			Modloader::Quilt => Some("org.quiltmc.quilt-loader"),
+			Modloader::Forge => Some("net.minecraftforge"),
+			Modloader::NeoForged => Some("net.neoforged"),
+			Modloader::LiteLoader => Some("com.mumfrey.liteloader"),
+			other => bail!("The modloader '{other}' is not supported by the MultiMC format"),
+		};
+		if let Some(uid) = uid {
+			components.push(Component {
+				uid: uid.into(),
+				version: modloader_version.map(|x| x.into()),
+				important: None,
+			});
+		}
+	}
+
+	Ok(Pack {
+		components,
+		format_version: 1,
+	})
+}
+
+/// Create the contents of the instance.cfg file
+fn create_instance_cfg(name: &str) -> String {
+	format!("[General]\nConfigVersion=1.2\nInstanceType=OneSix\nname={name}\n")
+}
+
+/// The mmc-pack.json file
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Pack {
+	components: Vec<Component>,
+	format_version: u8,
+}
+
+/// A single component in the pack, such as the game or a modloader
+#[derive(Serialize)]
+struct Component {
+	uid: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	version: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	important: Option<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_pack_components() {
+		let pack = create_pack("1.20.1", Some(&Modloader::Fabric), Some("0.15.11")).unwrap();
+		assert_eq!(pack.components.len(), 2);
+		assert_eq!(pack.components[0].uid, "net.minecraft");
+		assert_eq!(pack.components[1].uid, "net.fabricmc.fabric-loader");
+		assert_eq!(pack.components[1].version.as_deref(), Some("0.15.11"));
+
+		let pack = create_pack("1.20.1", Some(&Modloader::Vanilla), None).unwrap();
+		assert_eq!(pack.components.len(), 1);
+
+		assert!(create_pack("1.20.1", Some(&Modloader::Rift), None).is_err());
+	}
+}
@@ -1,6 +1,7 @@
 use anyhow::Context;
 use clap::Parser;
-use mcvm_core::net::download::Client;
+use mcvm::shared::output::NoOp;
+use mcvm_core::net::download::make_client;
 use mcvm_plugin::api::CustomPlugin;
 
 fn main() -> anyhow::Result<()> {
@@ -51,9 +52,9 @@ enum Subcommand {
 }
 
 async fn get_modrinth_project(project: String) -> anyhow::Result<()> {
-	let client = Client::new();
+	let client = make_client(None, None)?;
 
-	let project = mcvm_net::modrinth::get_project_raw(&project, &client)
+	let project = mcvm_net::modrinth::get_project_raw(&project, &client, &mut NoOp)
 		.await
 		.context("Failed to get project")?;
 	let project_pretty = mcvm::core::util::json::format_json(&project);
@@ -70,9 +71,9 @@ async fn get_modrinth_project(project: String) -> anyhow::Result<()> {
 }
 
 async fn get_modrinth_version(version: String) -> anyhow::Result<()> {
-	let client = Client::new();
+	let client = make_client(None, None)?;
 
-	let version = mcvm_net::modrinth::get_version_raw(&version, &client)
+	let version = mcvm_net::modrinth::get_version_raw(&version, &client, &mut NoOp)
 		.await
 		.context("Failed to get project version")?;
 	let version_pretty = mcvm::core::util::json::format_json(&version);
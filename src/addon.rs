@@ -3,12 +3,14 @@ use mcvm_shared::addon::{Addon, AddonKind};
 use mcvm_shared::pkg::PackageAddonOptionalHashes;
 use reqwest::Client;
 
+use crate::io::lock::Lockfile;
 use crate::io::paths::Paths;
 use crate::util::hash::{get_best_hash, hash_file_with_best_hash};
 use mcvm_core::io::files::{create_leading_dirs, update_hardlink};
 use mcvm_core::net::download;
 use mcvm_shared::modifications::{Modloader, ServerType};
 
+use std::fmt::Display;
 use std::future::Future;
 use std::path::{Path, PathBuf};
 
@@ -39,6 +41,11 @@ impl AddonExt for Addon {
 	}
 
 	fn get_path(&self, paths: &Paths, instance_id: &str) -> PathBuf {
+		// Addons with known hashes are shared between all instances that use them
+		if let Some(key) = get_addon_store_key(&self.hashes) {
+			return get_addon_store_dir(paths).join(key);
+		}
+
 		let pkg_dir = self.get_dir(paths).join(self.pkg_id.to_string());
 		if let Some(version) = &self.version {
 			pkg_dir.join(self.id.clone()).join(version)
@@ -68,8 +75,44 @@ impl AddonExt for Addon {
 	}
 
 	fn should_update(&self, paths: &Paths, instance_id: &str) -> bool {
-		self.version.is_none() || !self.get_path(paths, instance_id).exists()
+		// The contents of a stored addon with a hash can never change
+		let is_immutable = self.version.is_some() || get_addon_store_key(&self.hashes).is_some();
+		!is_immutable || !self.get_path(paths, instance_id).exists()
+	}
+}
+
+/// Get the directory where addons with known hashes are stored by their hash
+pub fn get_addon_store_dir(paths: &Paths) -> PathBuf {
+	paths.addons.join("store")
+}
+
+/// Get the key that an addon is stored under in the addon store, which is its best hash.
+/// Hashes that aren't valid hex of the right length are ignored, since the key is used as a filename
+pub fn get_addon_store_key(hashes: &PackageAddonOptionalHashes) -> Option<String> {
+	let get_key = |hash: &Option<String>, len: usize| {
+		hash.as_ref()
+			.map(|x| x.to_ascii_lowercase())
+			.filter(|x| x.len() == len && x.bytes().all(|x| x.is_ascii_hexdigit()))
+	};
+	get_key(&hashes.sha512, 128).or_else(|| get_key(&hashes.sha256, 64))
+}
+
+/// Removes addons from the addon store that are no longer used by any instance in the lockfile
+pub fn remove_unused_stored_addons(paths: &Paths, lock: &Lockfile) -> anyhow::Result<()> {
+	let dir = get_addon_store_dir(paths);
+	if !dir.exists() {
+		return Ok(());
 	}
+
+	let used = lock.get_stored_addon_keys();
+	for entry in dir.read_dir().context("Failed to read addon store")? {
+		let entry = entry?;
+		if !used.contains(entry.file_name().to_string_lossy().as_ref()) {
+			std::fs::remove_file(entry.path()).context("Failed to remove unused stored addon")?;
+		}
+	}
+
+	Ok(())
 }
 
 /// Gets the formulaic filename for an addon in the instance, meant to reduce name clashes
@@ -91,6 +134,15 @@ pub enum AddonLocation {
 	Local(PathBuf),
 }
 
+impl Display for AddonLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Remote(url) => write!(f, "{url}"),
+			Self::Local(path) => write!(f, "{}", path.display()),
+		}
+	}
+}
+
 /// A request for an addon file that will be fulfilled later
 #[derive(Debug, Clone)]
 pub struct AddonRequest {
@@ -111,6 +163,11 @@ impl AddonRequest {
 		self.addon.get_unique_id(instance_id)
 	}
 
+	/// Get where this addon is located
+	pub fn get_location(&self) -> &AddonLocation {
+		&self.location
+	}
+
 	/// Get the addon and store it
 	pub async fn acquire(
 		&self,
@@ -139,24 +196,30 @@ impl AddonRequest {
 		let client = client.clone();
 		let hashes = self.addon.hashes.clone();
 		let task = async move {
+			// Get the file at a temporary path first so that other instances never
+			// link to a stored addon that hasn't been checked yet
+			let mut temp_file_name = path.file_name().unwrap_or_default().to_owned();
+			temp_file_name.push(".part");
+			let temp_path = path.with_file_name(temp_file_name);
 			match location {
 				AddonLocation::Remote(url) => {
-					download::file(url, &path, &client)
+					download::file(url, &temp_path, &client)
 						.await
 						.context("Failed to download addon")?;
 				}
 				AddonLocation::Local(actual_path) => {
-					update_hardlink(&actual_path, &path)
+					update_hardlink(&actual_path, &temp_path)
 						.context("Failed to hardlink local addon")?;
 				}
 			}
 
-			let result = Self::check_hashes_impl(hashes, &path);
+			let result = Self::check_hashes_impl(hashes, &temp_path);
 			// Remove the addon file if it fails the checksum
 			if result.is_err() {
-				std::fs::remove_file(path).context("Failed to remove stored addon file")?;
+				std::fs::remove_file(temp_path).context("Failed to remove stored addon file")?;
+				return result;
 			}
-			result?;
+			std::fs::rename(temp_path, path).context("Failed to move addon into place")?;
 
 			Ok(())
 		};
@@ -166,7 +229,7 @@ impl AddonRequest {
 
 	/// Check the addon's hashes. The stored addon file must exist at this time
 	pub fn check_hashes(&self, path: &Path) -> anyhow::Result<()> {
-		Self::check_hashes_impl(self.addon.hashes.clone(), path)
+		check_addon_hashes(&self.addon, path)
 	}
 
 	/// Implementation for hash checking
@@ -185,6 +248,11 @@ impl AddonRequest {
 	}
 }
 
+/// Check that a stored addon file matches the hashes of the addon
+pub fn check_addon_hashes(addon: &Addon, path: &Path) -> anyhow::Result<()> {
+	AddonRequest::check_hashes_impl(addon.hashes.clone(), path)
+}
+
 /// Checks if the modloader and plugin loader are compatible with each other
 pub fn game_modifications_compatible(modloader: &Modloader, plugin_loader: &ServerType) -> bool {
 	matches!(
@@ -227,4 +295,36 @@ mod tests {
 		};
 		assert_eq!(addon.split_filename(), ("FooBar", ".baz.jar"));
 	}
+
+	#[test]
+	fn test_addon_store_key() {
+		let mut hashes = PackageAddonOptionalHashes::default();
+		assert_eq!(get_addon_store_key(&hashes), None);
+		hashes.sha256 = Some("ABC123".repeat(10) + "ABCD");
+		assert_eq!(
+			get_addon_store_key(&hashes),
+			Some("abc123".repeat(10) + "abcd")
+		);
+		hashes.sha512 = Some("def456".repeat(21) + "de");
+		assert_eq!(
+			get_addon_store_key(&hashes),
+			Some("def456".repeat(21) + "de")
+		);
+	}
+
+	#[test]
+	fn test_invalid_addon_store_key() {
+		let mut hashes = PackageAddonOptionalHashes {
+			sha256: Some("abc123".into()),
+			sha512: None,
+		};
+		assert_eq!(get_addon_store_key(&hashes), None);
+		hashes.sha256 = Some(format!("../../{}", "a".repeat(58)));
+		assert_eq!(get_addon_store_key(&hashes), None);
+
+		// An invalid SHA-512 hash falls back to the SHA-256 one
+		hashes.sha256 = Some("a".repeat(64));
+		hashes.sha512 = Some("../".repeat(42) + "ab");
+		assert_eq!(get_addon_store_key(&hashes), Some("a".repeat(64)));
+	}
 }
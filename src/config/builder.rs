@@ -51,7 +51,7 @@ impl ConfigBuilder {
 	}
 
 	/// Create a UserBuilder
-	pub fn user(&mut self, id: String, kind: UserBuilderKind) -> UserBuilder {
+	pub fn user(&mut self, id: String, kind: UserBuilderKind) -> UserBuilder<'_> {
 		UserBuilder::with_parent(id, kind, Some(self))
 	}
 
@@ -182,6 +182,7 @@ impl<'parent> InstanceBuilder<'parent> {
 			name: None,
 			common: Default::default(),
 			window: Default::default(),
+			properties: Default::default(),
 		};
 
 		Self { id, config, parent }
@@ -246,6 +247,13 @@ impl<'parent> InstanceBuilder<'parent> {
 		self
 	}
 
+	/// Set a property in the server.properties file of the instance
+	pub fn server_property(&mut self, key: String, value: String) -> &mut Self {
+		self.config.properties.insert(key, value);
+
+		self
+	}
+
 	/// Set the datapack folder of the instance
 	pub fn datapack_folder(&mut self, folder: String) -> &mut Self {
 		self.config.common.datapack_folder = Some(folder);
@@ -1,21 +1,29 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
-use anyhow::{bail, ensure, Context};
-use mcvm_core::io::java::args::MemoryNum;
+use anyhow::{anyhow, bail, ensure, Context};
+use itertools::Itertools;
+use mcvm_core::io::java::args::{ArgsPreset, MemoryNum};
 use mcvm_core::io::java::install::JavaInstallationKind;
+use mcvm_core::io::java::JavaMajorVersion;
+use mcvm_core::launch::DEFAULT_MAX_LOG_FILES;
 use mcvm_core::util::versions::MinecraftVersionDeser;
 use mcvm_plugin::hooks::ModifyInstanceConfig;
 use mcvm_shared::id::{InstanceID, ProfileID};
 use mcvm_shared::modifications::{ClientType, Modloader, ServerType};
-use mcvm_shared::output::MCVMOutput;
-use mcvm_shared::pkg::PackageStability;
-use mcvm_shared::util::{merge_options, DefaultExt};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::pkg::{PackageID, PackageStability};
+use mcvm_shared::util::{
+	merge_options, parse_arch, validate_identifier_with_punctuation, DefaultExt, ARCH_STRING,
+};
 use mcvm_shared::Side;
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sysinfo::System;
 
-use crate::instance::launch::{LaunchOptions, WrapperCommand};
+use crate::instance::launch::{LaunchOptions, WrapperCommand, DEFAULT_MAX_RESTARTS};
+use crate::instance::snapshot;
 use crate::instance::{InstKind, Instance, InstanceStoredConfig};
 use crate::io::paths::Paths;
 
@@ -24,7 +32,7 @@ use super::plugin::PluginManager;
 use super::profile::{GameModifications, ProfileConfig};
 
 /// Configuration for an instance
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct InstanceConfig {
 	/// The type or side of this instance
@@ -40,6 +48,10 @@ pub struct InstanceConfig {
 	#[serde(default)]
 	#[serde(skip_serializing_if = "DefaultExt::is_default")]
 	pub window: ClientWindowConfig,
+	/// Properties to set in the server.properties file of a server, taking precedence over generated ones
+	#[serde(default)]
+	#[serde(skip_serializing_if = "HashMap::is_empty")]
+	pub properties: HashMap<String, String>,
 }
 
 /// Common full instance config for both client and server
@@ -64,6 +76,10 @@ pub struct CommonInstanceConfig {
 	#[serde(default)]
 	#[serde(skip_serializing_if = "DefaultExt::is_default")]
 	pub server_type: Option<ServerType>,
+	/// Whether to automatically install the Fabric API or Quilt Standard Libraries
+	/// when using Fabric or Quilt
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub include_loader_api: Option<bool>,
 	/// Default stability setting of packages on this instance
 	#[serde(default)]
 	#[serde(skip_serializing_if = "DefaultExt::is_default")]
@@ -74,6 +90,12 @@ pub struct CommonInstanceConfig {
 	/// The folder for global datapacks to be installed to
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub datapack_folder: Option<String>,
+	/// A custom game directory to launch the instance in, instead of the one inside the instance directory
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub game_dir: Option<String>,
+	/// Configuration for snapshots
+	#[serde(skip_serializing_if = "DefaultExt::is_default")]
+	pub snapshots: snapshot::Config,
 	/// Packages for this instance
 	#[serde(skip_serializing_if = "Vec::is_empty")]
 	pub packages: Vec<PackageConfigDeser>,
@@ -91,9 +113,12 @@ impl CommonInstanceConfig {
 		self.modloader = other.modloader.or(self.modloader.clone());
 		self.client_type = other.client_type.or(self.client_type.clone());
 		self.server_type = other.server_type.or(self.server_type.clone());
+		self.include_loader_api = other.include_loader_api.or(self.include_loader_api);
 		self.package_stability = other.package_stability.or(self.package_stability);
 		self.launch.merge(other.launch);
 		self.datapack_folder = other.datapack_folder.or(self.datapack_folder.clone());
+		self.game_dir = other.game_dir.or(self.game_dir.clone());
+		self.snapshots.merge(other.snapshots);
 		self.packages.extend(other.packages);
 		mcvm_core::util::json::merge_objects(&mut self.plugin_config, other.plugin_config);
 
@@ -113,11 +138,17 @@ pub enum Args {
 }
 
 impl Args {
-	/// Parse the arguments into a vector
+	/// Parse the arguments into a vector. Strings are split like a shell would,
+	/// respecting quotes and escaped spaces. On Windows, backslashes are left alone
+	/// so that paths are not mangled
 	pub fn parse(&self) -> Vec<String> {
 		match self {
 			Self::List(vec) => vec.clone(),
-			Self::String(string) => string.split(' ').map(|string| string.to_string()).collect(),
+			Self::String(string) if cfg!(windows) => split_windows_args(string),
+			Self::String(string) => shlex::split(string).unwrap_or_else(|| {
+				// Unbalanced quotes can't be tokenized, so fall back to splitting on whitespace
+				string.split_whitespace().map(String::from).collect()
+			}),
 		}
 	}
 
@@ -135,6 +166,73 @@ impl Default for Args {
 	}
 }
 
+/// Split a string of arguments on whitespace, keeping whitespace inside double quotes.
+/// Unlike shell-style splitting, backslashes have no special meaning, since they are
+/// the path separator on Windows
+fn split_windows_args(string: &str) -> Vec<String> {
+	let mut out = Vec::new();
+	let mut current = String::new();
+	// Whether we are inside an argument, so that empty quotes still produce one
+	let mut in_arg = false;
+	let mut in_quotes = false;
+	for c in string.chars() {
+		match c {
+			'"' => {
+				in_quotes = !in_quotes;
+				in_arg = true;
+			}
+			c if c.is_whitespace() && !in_quotes => {
+				if in_arg {
+					out.push(std::mem::take(&mut current));
+					in_arg = false;
+				}
+			}
+			c => {
+				current.push(c);
+				in_arg = true;
+			}
+		}
+	}
+	if in_arg {
+		out.push(current);
+	}
+
+	out
+}
+
+/// Expand environment variables and a leading tilde in a launch option. Variables are looked up
+/// in the given map first and then in the environment of mcvm. Unknown variables are an error
+/// when strict, and are left untouched otherwise
+fn expand_vars(
+	string: &str,
+	vars: &HashMap<String, String>,
+	strict: bool,
+) -> anyhow::Result<String> {
+	let home_dir =
+		|| directories::BaseDirs::new().map(|x| x.home_dir().to_string_lossy().to_string());
+	let context = |var: &str| -> anyhow::Result<Option<String>> {
+		if let Some(value) = vars.get(var) {
+			return Ok(Some(value.clone()));
+		}
+		match std::env::var(var) {
+			Ok(value) => Ok(Some(value)),
+			Err(..) if strict => bail!("Variable is not set"),
+			Err(..) => Ok(None),
+		}
+	};
+	let out = shellexpand::full_with_context(string, home_dir, context)
+		.map_err(|e| anyhow!("Failed to expand variable '{}': {}", e.var_name, e.cause))?;
+
+	Ok(out.to_string())
+}
+
+/// Deserialize Args and parse them into a list of arguments
+pub(crate) fn deserialize_args<'de, D: serde::Deserializer<'de>>(
+	deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+	Args::deserialize(deserializer).map(|x| x.parse())
+}
+
 /// Arguments for the process when launching
 #[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -157,6 +255,13 @@ pub enum LaunchMemory {
 	/// No memory arguments
 	#[default]
 	None,
+	/// Automatically pick memory based on the amount of memory on the system
+	#[serde(
+		deserialize_with = "deserialize_auto_memory",
+		serialize_with = "serialize_auto_memory"
+	)]
+	#[cfg_attr(feature = "schema", schemars(with = "AutoMemory"))]
+	Auto,
 	/// A single memory argument shared for both
 	Single(String),
 	/// Different memory arguments for both
@@ -168,6 +273,45 @@ pub enum LaunchMemory {
 	},
 }
 
+/// Used to deserialize the "auto" string for LaunchMemory::Auto
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+enum AutoMemory {
+	Auto,
+}
+
+fn deserialize_auto_memory<'de, D: serde::Deserializer<'de>>(
+	deserializer: D,
+) -> Result<(), D::Error> {
+	AutoMemory::deserialize(deserializer).map(|_| ())
+}
+
+fn serialize_auto_memory<S: serde::Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+	AutoMemory::Auto.serialize(serializer)
+}
+
+/// The maximum amount of memory in MiB that will be automatically allocated
+const AUTO_MEMORY_CEILING: u64 = 8 * 1024;
+/// The amount of memory in MiB that will be left free for the rest of the system
+const AUTO_MEMORY_HEADROOM: u64 = 1024;
+/// The minimum amount of memory in MiB that will be automatically allocated
+const AUTO_MEMORY_FLOOR: u64 = 512;
+
+/// Picks the minimum and maximum memory for the game based on the total system memory in bytes
+fn get_auto_memory(total_memory: u64) -> (MemoryNum, MemoryNum) {
+	let total_mb = total_memory / 1024 / 1024;
+	let max = (total_mb / 2)
+		.min(total_mb.saturating_sub(AUTO_MEMORY_HEADROOM))
+		.clamp(AUTO_MEMORY_FLOOR, AUTO_MEMORY_CEILING);
+	let min = max / 4;
+
+	(
+		MemoryNum::from_bytes(min * 1024 * 1024),
+		MemoryNum::from_bytes(max * 1024 * 1024),
+	)
+}
+
 fn default_java() -> String {
 	"auto".into()
 }
@@ -177,7 +321,7 @@ fn default_flags_preset() -> String {
 }
 
 /// Options for the Minecraft QuickPlay feature
-#[derive(Deserialize, Serialize, Debug, PartialEq, Default, Clone)]
+#[derive(Serialize, Debug, PartialEq, Default, Clone)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
@@ -204,6 +348,33 @@ pub enum QuickPlay {
 	None,
 }
 
+impl<'de> Deserialize<'de> for QuickPlay {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		/// Mirror of QuickPlay that rejects fields from other variants.
+		/// The None variant has to be an empty struct, as unit variants ignore extra fields
+		#[derive(Deserialize)]
+		#[serde(tag = "type")]
+		#[serde(rename_all = "snake_case")]
+		#[serde(deny_unknown_fields)]
+		enum Strict {
+			World { world: String },
+			Server { server: String, port: Option<u16> },
+			Realm { realm: String },
+			None {},
+		}
+
+		Ok(match Strict::deserialize(deserializer)? {
+			Strict::World { world } => Self::World { world },
+			Strict::Server { server, port } => Self::Server { server, port },
+			Strict::Realm { realm } => Self::Realm { realm },
+			Strict::None {} => Self::None,
+		})
+	}
+}
+
 /// Configuration for the launching of the game
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
@@ -219,6 +390,15 @@ pub struct LaunchConfig {
 	/// The java installation to use
 	#[serde(default = "default_java")]
 	pub java: String,
+	/// A major Java version to use instead of the one that the game version asks for
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub java_version: Option<String>,
+	/// An architecture to download Java and select native libraries for instead of the
+	/// one of this system, such as `x64` to run old versions under Rosetta
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub java_arch: Option<String>,
 	/// The preset for flags
 	#[serde(default = "default_flags_preset")]
 	pub preset: String,
@@ -230,6 +410,14 @@ pub struct LaunchConfig {
 	#[serde(default)]
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub wrapper: Option<WrapperCommand>,
+	/// A command to run before the game is launched
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pre_launch: Option<String>,
+	/// A command to run after the game exits
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub post_launch: Option<String>,
 	/// QuickPlay options
 	#[serde(default)]
 	#[serde(skip_serializing_if = "DefaultExt::is_default")]
@@ -238,20 +426,50 @@ pub struct LaunchConfig {
 	#[serde(default)]
 	#[serde(skip_serializing_if = "DefaultExt::is_default")]
 	pub use_log4j_config: bool,
+	/// Whether to mitigate the Log4Shell vulnerability when launching affected client versions
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub patch_log4j: Option<bool>,
+	/// The maximum number of old game output logs to keep
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_log_files: Option<usize>,
+	/// Whether to fail when an environment variable referenced in the launch options is not set,
+	/// instead of leaving the reference untouched
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub strict_expansion: Option<bool>,
+	/// Whether to restart a server automatically when it crashes
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub restart_on_crash: Option<bool>,
+	/// The maximum number of times to restart a crashed server in a short period before giving up
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_restarts: Option<u32>,
 }
 
 impl LaunchConfig {
 	/// Parse and finalize this LaunchConfig into LaunchOptions
-	pub fn to_options(self) -> anyhow::Result<LaunchOptions> {
-		let min_mem = match &self.memory {
-			LaunchMemory::None => None,
-			LaunchMemory::Single(string) => MemoryNum::parse(string),
-			LaunchMemory::Both { min, .. } => MemoryNum::parse(min),
-		};
-		let max_mem = match &self.memory {
-			LaunchMemory::None => None,
-			LaunchMemory::Single(string) => MemoryNum::parse(string),
-			LaunchMemory::Both { max, .. } => MemoryNum::parse(max),
+	pub fn to_options(self, o: &mut impl MCVMOutput) -> anyhow::Result<LaunchOptions> {
+		let (min_mem, max_mem) = match &self.memory {
+			LaunchMemory::None => (None, None),
+			LaunchMemory::Auto => {
+				let mut system = System::new();
+				system.refresh_memory();
+				let (min, max) = get_auto_memory(system.total_memory());
+				if max.to_bytes() < 1024 * 1024 * 1024 {
+					o.display(
+						MessageContents::Warning(format!(
+							"Only {max} of memory could be automatically allocated to the game"
+						)),
+						MessageLevel::Important,
+					);
+				}
+				(Some(min), Some(max))
+			}
+			LaunchMemory::Single(string) => (MemoryNum::parse(string), MemoryNum::parse(string)),
+			LaunchMemory::Both { min, max } => (MemoryNum::parse(min), MemoryNum::parse(max)),
 		};
 		if let Some(min_mem) = &min_mem {
 			if let Some(max_mem) = &max_mem {
@@ -261,16 +479,78 @@ impl LaunchConfig {
 				);
 			}
 		}
+		// Presets that aren't known here are generated by the args plugin instead
+		let mut jvm_args = ArgsPreset::parse(&self.preset)
+			.map(|x| x.generate_args(max_mem.as_ref()))
+			.unwrap_or_default();
+
+		// Expand variables, letting the other options reference the configured environment
+		let strict = self.strict_expansion.unwrap_or(false);
+		let mut env = HashMap::with_capacity(self.env.len());
+		for (key, value) in &self.env {
+			let value = expand_vars(value, &HashMap::new(), strict)
+				.with_context(|| format!("Failed to expand environment variable '{key}'"))?;
+			env.insert(key.clone(), value);
+		}
+		let expand_all = |args: Vec<String>| -> anyhow::Result<Vec<String>> {
+			args.iter().map(|x| expand_vars(x, &env, strict)).collect()
+		};
+		jvm_args
+			.extend(expand_all(self.args.jvm.parse()).context("Failed to expand JVM arguments")?);
+		let game_args =
+			expand_all(self.args.game.parse()).context("Failed to expand game arguments")?;
+		let wrapper = match self.wrapper {
+			Some(wrapper) => Some(WrapperCommand {
+				cmd: expand_vars(&wrapper.cmd, &env, strict)
+					.context("Failed to expand wrapper command")?,
+				args: expand_all(wrapper.args).context("Failed to expand wrapper arguments")?,
+			}),
+			None => None,
+		};
+
+		let java_version = match &self.java_version {
+			Some(version) => Some(
+				JavaMajorVersion::parse(version)
+					.with_context(|| format!("Invalid Java major version '{version}'"))?,
+			),
+			None => None,
+		};
+
+		let java_arch = match &self.java_arch {
+			Some(arch) => {
+				let parsed = parse_arch(arch)
+					.with_context(|| format!("Unknown Java architecture '{arch}'"))?;
+				if parsed != ARCH_STRING {
+					o.display(
+						MessageContents::Warning(format!(
+							"Java will be run as {parsed} on this {ARCH_STRING} system. On Apple Silicon, this requires Rosetta to be installed"
+						)),
+						MessageLevel::Important,
+					);
+				}
+				Some(parsed.to_string())
+			}
+			None => None,
+		};
+
 		Ok(LaunchOptions {
-			jvm_args: self.args.jvm.parse(),
-			game_args: self.args.game.parse(),
+			jvm_args,
+			game_args,
 			min_mem,
 			max_mem,
 			java: JavaInstallationKind::parse(&self.java),
-			env: self.env,
-			wrapper: self.wrapper,
+			java_version,
+			java_arch,
+			env,
+			wrapper,
+			pre_launch: self.pre_launch,
+			post_launch: self.post_launch,
 			quick_play: self.quick_play,
 			use_log4j_config: self.use_log4j_config,
+			patch_log4j: self.patch_log4j.unwrap_or(true),
+			max_log_files: self.max_log_files.unwrap_or(DEFAULT_MAX_LOG_FILES),
+			restart_on_crash: self.restart_on_crash.unwrap_or(false),
+			max_restarts: self.max_restarts.unwrap_or(DEFAULT_MAX_RESTARTS),
 		})
 	}
 
@@ -282,6 +562,12 @@ impl LaunchConfig {
 			self.memory = other.memory;
 		}
 		self.java = other.java;
+		if other.java_version.is_some() {
+			self.java_version = other.java_version;
+		}
+		if other.java_arch.is_some() {
+			self.java_arch = other.java_arch;
+		}
 		if other.preset != "none" {
 			self.preset = other.preset;
 		}
@@ -289,9 +575,30 @@ impl LaunchConfig {
 		if other.wrapper.is_some() {
 			self.wrapper = other.wrapper;
 		}
+		if other.pre_launch.is_some() {
+			self.pre_launch = other.pre_launch;
+		}
+		if other.post_launch.is_some() {
+			self.post_launch = other.post_launch;
+		}
 		if !matches!(other.quick_play, QuickPlay::None) {
 			self.quick_play = other.quick_play;
 		}
+		if other.patch_log4j.is_some() {
+			self.patch_log4j = other.patch_log4j;
+		}
+		if other.max_log_files.is_some() {
+			self.max_log_files = other.max_log_files;
+		}
+		if other.strict_expansion.is_some() {
+			self.strict_expansion = other.strict_expansion;
+		}
+		if other.restart_on_crash.is_some() {
+			self.restart_on_crash = other.restart_on_crash;
+		}
+		if other.max_restarts.is_some() {
+			self.max_restarts = other.max_restarts;
+		}
 
 		self
 	}
@@ -306,11 +613,20 @@ impl Default for LaunchConfig {
 			},
 			memory: LaunchMemory::default(),
 			java: default_java(),
+			java_version: None,
+			java_arch: None,
 			preset: default_flags_preset(),
 			env: HashMap::new(),
 			wrapper: None,
+			pre_launch: None,
+			post_launch: None,
 			quick_play: QuickPlay::default(),
 			use_log4j_config: false,
+			patch_log4j: None,
+			max_log_files: None,
+			strict_expansion: None,
+			restart_on_crash: None,
+			max_restarts: None,
 		}
 	}
 }
@@ -355,6 +671,7 @@ pub fn merge_instance_configs(
 	out.name = config.name.or(out.name);
 	out.side = config.side.or(out.side);
 	out.window.merge(config.window);
+	out.properties.extend(config.properties);
 
 	Ok(out)
 }
@@ -368,8 +685,9 @@ pub fn read_instance_config(
 	paths: &Paths,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<Instance> {
-	if !is_valid_instance_id(&id) {
-		bail!("Invalid instance ID '{}'", id.to_string());
+	validate_instance_id(&id).with_context(|| format!("Invalid instance ID '{id}'"))?;
+	for package in &config.common.packages {
+		package.validate()?;
 	}
 
 	// Get the parent profile if it is specified
@@ -391,20 +709,23 @@ pub fn read_instance_config(
 
 	let side = config.side.context("Instance type was not specified")?;
 
-	// Consolidate all of the package configs into the instance package config list
-	let packages = consolidate_package_configs(profile, &config, side);
-
-	let kind = match side {
-		Side::Client => InstKind::client(config.window),
-		Side::Server => InstKind::server(),
-	};
-
 	let game_modifications = GameModifications::new(
 		config.common.modloader.clone().unwrap_or_default(),
 		config.common.client_type.clone().unwrap_or_default(),
 		config.common.server_type.clone().unwrap_or_default(),
 	);
 
+	// Consolidate all of the package configs into the instance package config list
+	let packages = consolidate_package_configs(profile, &config, side, &game_modifications);
+
+	let kind = match side {
+		Side::Client => InstKind::client(config.window),
+		Side::Server => {
+			check_server_properties(&config.properties, o);
+			InstKind::server(config.properties)
+		}
+	};
+
 	let version = config
 		.common
 		.version
@@ -413,6 +734,8 @@ pub fn read_instance_config(
 		.to_mc_version();
 
 	// Apply plugins
+	resolve_args_preset(&mut config.common.launch, &mut config.common.plugin_config)
+		.context("Failed to resolve JVM args preset")?;
 	let results = plugins
 		.call_hook(ModifyInstanceConfig, &config.common.plugin_config, paths, o)
 		.context("Failed to apply plugin instance modifications")?;
@@ -426,12 +749,22 @@ pub fn read_instance_config(
 			.merge(Args::List(result.additional_jvm_args));
 	}
 
+	let game_dir_override = config
+		.common
+		.game_dir
+		.as_deref()
+		.map(parse_game_dir)
+		.transpose()
+		.context("Invalid game directory")?;
+
 	let stored_config = InstanceStoredConfig {
 		name: config.name,
 		version,
 		modifications: game_modifications,
-		launch: config.common.launch.to_options()?,
+		launch: config.common.launch.to_options(o)?,
 		datapack_folder: config.common.datapack_folder,
+		game_dir_override,
+		snapshots: config.common.snapshots,
 		packages,
 		package_stability: config.common.package_stability.unwrap_or_default(),
 		plugin_config: config.common.plugin_config,
@@ -442,34 +775,148 @@ pub fn read_instance_config(
 	Ok(instance)
 }
 
-/// Checks if an instance ID is valid
-pub fn is_valid_instance_id(id: &str) -> bool {
-	for c in id.chars() {
-		if !c.is_ascii() {
-			return false;
-		}
+/// Parse a custom game directory, expanding a leading ~. The directory must be an absolute path,
+/// as a relative one would depend on the directory that mcvm is run from
+fn parse_game_dir(game_dir: &str) -> anyhow::Result<PathBuf> {
+	let path = PathBuf::from(shellexpand::tilde(game_dir).as_ref());
+	ensure!(
+		path.is_absolute(),
+		"Game directory '{game_dir}' must be an absolute path"
+	);
+
+	Ok(path)
+}
+
+/// JVM flags presets that are generated by the args plugin instead of MCVM
+const PLUGIN_ARGS_PRESETS: [&str; 2] = ["krusic", "obydux"];
 
-		if c.is_ascii_punctuation() {
-			match c {
-				'_' | '-' | '.' | ':' => {}
-				_ => return false,
+/// Decide whether a JVM flags preset is generated here or by the args plugin, so that
+/// the same preset is never applied twice. Errors if a preset is not known by either
+fn resolve_args_preset(
+	launch: &mut LaunchConfig,
+	plugin_config: &mut serde_json::Map<String, serde_json::Value>,
+) -> anyhow::Result<()> {
+	let is_known =
+		|preset: &str| ArgsPreset::parse(preset).is_some() || PLUGIN_ARGS_PRESETS.contains(&preset);
+
+	let plugin_preset = plugin_config.get("args_preset").and_then(|x| x.as_str());
+	if let Some(plugin_preset) = plugin_preset {
+		ensure!(
+			is_known(plugin_preset),
+			"Unknown args preset '{plugin_preset}'"
+		);
+		if ArgsPreset::parse(plugin_preset).is_some() {
+			if launch.preset == "none" {
+				launch.preset = plugin_preset.to_string();
 			}
+			plugin_config.remove("args_preset");
+		}
+	}
+
+	ensure!(
+		is_known(&launch.preset),
+		"Unknown launch preset '{}'",
+		launch.preset
+	);
+	if ArgsPreset::parse(&launch.preset).is_none() {
+		plugin_config
+			.entry("args_preset")
+			.or_insert_with(|| launch.preset.clone().into());
+	}
+
+	Ok(())
+}
+
+/// Warn about server properties with values that the server would reject
+pub fn check_server_properties(properties: &HashMap<String, String>, o: &mut impl MCVMOutput) {
+	for (key, value) in properties.iter().sorted_by_key(|x| x.0) {
+		if let Some(expected) = get_server_property_problem(key, value) {
+			o.display(
+				MessageContents::Warning(format!(
+					"Server property '{key}' has the value '{value}', but it should be {expected}"
+				)),
+				MessageLevel::Important,
+			);
+		}
+	}
+}
+
+/// Check the value of a server property, returning a description of what the value
+/// should be if it is invalid
+fn get_server_property_problem(key: &str, value: &str) -> Option<&'static str> {
+	let (valid, expected) = match key {
+		"server-port" | "rcon.port" | "query.port" => (
+			value.parse::<u16>().is_ok(),
+			"a port number from 0 to 65535",
+		),
+		"max-players" | "view-distance" | "simulation-distance" | "spawn-protection" => {
+			(value.parse::<u32>().is_ok(), "a whole number")
+		}
+		"difficulty" => (
+			matches!(
+				value,
+				"peaceful" | "easy" | "normal" | "hard" | "0" | "1" | "2" | "3"
+			),
+			"one of peaceful, easy, normal, or hard",
+		),
+		"gamemode" => (
+			matches!(
+				value,
+				"survival" | "creative" | "adventure" | "spectator" | "0" | "1" | "2" | "3"
+			),
+			"one of survival, creative, adventure, or spectator",
+		),
+		"online-mode" | "pvp" | "hardcore" | "white-list" | "enforce-whitelist" | "enable-rcon"
+		| "enable-query" | "allow-flight" | "allow-nether" => {
+			(matches!(value, "true" | "false"), "true or false")
 		}
+		_ => return None,
+	};
+
+	if valid {
+		None
+	} else {
+		Some(expected)
+	}
+}
 
-		if c.is_ascii_whitespace() {
-			return false;
+/// Checks if an instance ID is valid
+pub fn is_valid_instance_id(id: &str) -> bool {
+	validate_instance_id(id).is_ok()
+}
+
+/// Checks that an instance ID is valid, with an error describing why it is not
+pub fn validate_instance_id(id: &str) -> anyhow::Result<()> {
+	validate_identifier_with_punctuation(id, &['_', '-', '.', ':'])
+}
+
+/// Finds a pair of instance IDs that only differ by case. Instance IDs are used as directory names,
+/// so these would share the same directory on case-insensitive filesystems
+pub fn find_case_insensitive_collision<'a>(
+	ids: impl IntoIterator<Item = &'a str>,
+) -> Option<(&'a str, &'a str)> {
+	let mut ids: Vec<_> = ids.into_iter().collect();
+	ids.sort();
+	let mut seen = HashMap::with_capacity(ids.len());
+	for id in ids {
+		if let Some(existing) = seen.insert(id.to_ascii_lowercase(), id) {
+			return Some((existing, id));
 		}
 	}
 
-	true
+	None
 }
 
+/// The core package that installs the Fabric API or QSL for an instance's modloader
+const LOADER_API_PACKAGE: &str = "fabriclike-api";
+
 /// Combines all of the package configs from global, profile, and instance together into
 /// the configurations for just one instance
 fn consolidate_package_configs(
 	profile: Option<&ProfileConfig>,
 	instance: &InstanceConfig,
 	side: Side,
+	game_modifications: &GameModifications,
 ) -> Vec<PackageConfig> {
 	let stability = instance.common.package_stability.unwrap_or_default();
 	// We use a map so that we can override packages from more general sources
@@ -496,6 +943,21 @@ fn consolidate_package_configs(
 		map.insert(pkg.id.clone(), pkg);
 	}
 
+	// The loader API is requested like any other package so that it is shared with
+	// the packages that depend on it and tracked in the lockfile
+	let uses_loader_api = matches!(
+		game_modifications.get_modloader(side),
+		Modloader::Fabric | Modloader::Quilt
+	);
+	if instance.common.include_loader_api.unwrap_or_default() && uses_loader_api {
+		let id = PackageID::from(LOADER_API_PACKAGE);
+		map.entry(id.clone()).or_insert_with(|| PackageConfig {
+			source: PackageConfigSource::LoaderApi,
+			stability,
+			..PackageConfig::from_id(id)
+		});
+	}
+
 	let mut out = Vec::new();
 	for pkg in map.values() {
 		out.push(pkg.clone());
@@ -508,31 +970,280 @@ fn consolidate_package_configs(
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_resolve_args_preset() {
+		let mut launch = LaunchConfig::default();
+		let mut plugin_config = serde_json::Map::new();
+		plugin_config.insert("args_preset".into(), "aikars".into());
+		resolve_args_preset(&mut launch, &mut plugin_config).unwrap();
+		assert_eq!(launch.preset, "aikars");
+		assert!(plugin_config.get("args_preset").is_none());
+
+		let mut launch = LaunchConfig {
+			preset: "krusic".into(),
+			..Default::default()
+		};
+		let mut plugin_config = serde_json::Map::new();
+		resolve_args_preset(&mut launch, &mut plugin_config).unwrap();
+		assert_eq!(plugin_config.get("args_preset"), Some(&"krusic".into()));
+		assert!(launch.to_options(&mut mcvm_shared::output::NoOp).is_ok());
+
+		let mut launch = LaunchConfig {
+			preset: "foo".into(),
+			..Default::default()
+		};
+		assert!(resolve_args_preset(&mut launch, &mut serde_json::Map::new()).is_err());
+
+		let mut launch = LaunchConfig::default();
+		let mut plugin_config = serde_json::Map::new();
+		plugin_config.insert("args_preset".into(), "foo".into());
+		assert!(resolve_args_preset(&mut launch, &mut plugin_config).is_err());
+	}
+
+	#[test]
+	fn test_loader_api_package() {
+		let mut config = InstanceConfig::default();
+		config.common.include_loader_api = Some(true);
+		let fabric = GameModifications::new(
+			Modloader::Fabric,
+			ClientType::default(),
+			ServerType::default(),
+		);
+		let packages = consolidate_package_configs(None, &config, Side::Client, &fabric);
+		assert!(packages
+			.iter()
+			.any(|x| x.id == PackageID::from(LOADER_API_PACKAGE)));
+
+		let vanilla = GameModifications::new(
+			Modloader::Vanilla,
+			ClientType::default(),
+			ServerType::default(),
+		);
+		let packages = consolidate_package_configs(None, &config, Side::Client, &vanilla);
+		assert!(packages.is_empty());
+	}
+
+	#[test]
+	fn test_auto_memory() {
+		const GB: u64 = 1024 * 1024 * 1024;
+		assert_eq!(
+			get_auto_memory(16 * GB),
+			(MemoryNum::Gb(2), MemoryNum::Gb(8))
+		);
+		assert_eq!(
+			get_auto_memory(64 * GB),
+			(MemoryNum::Gb(2), MemoryNum::Gb(8))
+		);
+		assert_eq!(
+			get_auto_memory(4 * GB),
+			(MemoryNum::Mb(512), MemoryNum::Gb(2))
+		);
+		// Leave headroom for the OS on small systems
+		assert_eq!(
+			get_auto_memory(GB + GB / 2),
+			(MemoryNum::Mb(128), MemoryNum::Mb(512))
+		);
+	}
+
+	#[test]
+	fn test_launch_memory_deser() {
+		assert_eq!(
+			serde_json::from_str::<LaunchMemory>("\"auto\"").unwrap(),
+			LaunchMemory::Auto
+		);
+		assert_eq!(
+			serde_json::from_str::<LaunchMemory>("\"4G\"").unwrap(),
+			LaunchMemory::Single("4G".into())
+		);
+		assert_eq!(
+			serde_json::to_string(&LaunchMemory::Auto).unwrap(),
+			"\"auto\""
+		);
+	}
+
+	#[test]
+	#[cfg(not(windows))]
+	fn test_args_parse() {
+		let args = Args::String("-Xss1M -Dsome.prop=\"a b\" 'c d'".into());
+		assert_eq!(args.parse(), vec!["-Xss1M", "-Dsome.prop=a b", "c d"]);
+
+		let args = Args::String("--dir /path/with\\ space  --demo".into());
+		assert_eq!(args.parse(), vec!["--dir", "/path/with space", "--demo"]);
+
+		assert!(Args::String(String::new()).parse().is_empty());
+		assert!(Args::String("  ".into()).parse().is_empty());
+
+		// Unbalanced quotes fall back to splitting on whitespace
+		let args = Args::String("--name \"foo bar".into());
+		assert_eq!(args.parse(), vec!["--name", "\"foo", "bar"]);
+	}
+
+	#[test]
+	fn test_split_windows_args() {
+		assert_eq!(
+			split_windows_args(r#"--dir "C:\Program Files\Java" -Xss1M"#),
+			vec!["--dir", r"C:\Program Files\Java", "-Xss1M"]
+		);
+		assert_eq!(
+			split_windows_args(r#"-Dsome.prop="a b"  C:\games\mc"#),
+			vec!["-Dsome.prop=a b", r"C:\games\mc"]
+		);
+		assert_eq!(split_windows_args(r#"a "" b"#), vec!["a", "", "b"]);
+		assert!(split_windows_args("  ").is_empty());
+	}
+
+	#[test]
+	fn test_wrapper_args_deser() {
+		let wrapper: WrapperCommand =
+			serde_json::from_str(r#"{"cmd": "prime-run", "args": "--path \"/my games\""}"#)
+				.unwrap();
+		assert_eq!(wrapper.args, vec!["--path", "/my games"]);
+
+		let wrapper: WrapperCommand =
+			serde_json::from_str(r#"{"cmd": "prime-run", "args": ["a b"]}"#).unwrap();
+		assert_eq!(wrapper.args, vec!["a b"]);
+	}
+
+	#[test]
+	fn test_expand_vars() {
+		let vars = HashMap::from([("GAME_DIR".to_string(), "/games".to_string())]);
+		assert_eq!(
+			expand_vars("${GAME_DIR}/mods", &vars, true).unwrap(),
+			"/games/mods"
+		);
+		assert_eq!(
+			expand_vars("$MCVM_SURELY_UNSET_VAR/x", &vars, false).unwrap(),
+			"$MCVM_SURELY_UNSET_VAR/x"
+		);
+		assert!(expand_vars("$MCVM_SURELY_UNSET_VAR/x", &vars, true).is_err());
+		assert_eq!(expand_vars("no vars", &vars, true).unwrap(), "no vars");
+	}
+
+	#[test]
+	fn test_java_version_override() {
+		let mut o = mcvm_shared::output::NoOp;
+		let config = LaunchConfig {
+			java_version: Some("21".into()),
+			..Default::default()
+		};
+		let options = config.to_options(&mut o).unwrap();
+		assert_eq!(options.java_version, Some(JavaMajorVersion(21)));
+
+		let config = LaunchConfig {
+			java_version: Some("latest".into()),
+			..Default::default()
+		};
+		assert!(config.to_options(&mut o).is_err());
+	}
+
+	#[test]
+	fn test_java_arch_override() {
+		let mut o = mcvm_shared::output::NoOp;
+		let config = LaunchConfig {
+			java_arch: Some("x64".into()),
+			..Default::default()
+		};
+		let options = config.to_options(&mut o).unwrap();
+		assert_eq!(options.java_arch.as_deref(), Some("x86_64"));
+
+		let config = LaunchConfig {
+			java_arch: Some("sparc".into()),
+			..Default::default()
+		};
+		assert!(config.to_options(&mut o).is_err());
+	}
+
+	#[test]
+	fn test_parse_game_dir() {
+		let absolute = if cfg!(windows) {
+			"C:\\Games\\minecraft"
+		} else {
+			"/games/minecraft"
+		};
+		assert_eq!(parse_game_dir(absolute).unwrap(), PathBuf::from(absolute));
+		assert!(parse_game_dir("~/minecraft").unwrap().is_absolute());
+		assert!(parse_game_dir("minecraft").is_err());
+		assert!(parse_game_dir("./minecraft").is_err());
+		assert!(parse_game_dir("../minecraft").is_err());
+	}
+
+	#[test]
+	fn test_instance_id_validation() {
+		assert!(validate_instance_id("survival-1.20").is_ok());
+		assert!(validate_instance_id("plugin:instance").is_ok());
+		assert!(validate_instance_id("").is_err());
+		assert!(validate_instance_id("my instance!").is_err());
+
+		assert_eq!(
+			find_case_insensitive_collision(["foo", "bar", "Foo"]),
+			Some(("Foo", "foo"))
+		);
+		assert_eq!(find_case_insensitive_collision(["foo", "bar"]), None);
+	}
+
 	#[test]
 	fn test_quickplay_deser() {
-		#[derive(Deserialize)]
+		#[derive(Deserialize, Debug)]
 		struct Test {
 			quick_play: QuickPlay,
 		}
 
-		let test = serde_json::from_str::<Test>(
-			r#"{
-			"quick_play": {
-				"type": "server",
-				"server": "localhost",
-				"port": 25565,
-				"world": "test",
-				"realm": "my_realm"
-			}	
-		}"#,
-		)
-		.unwrap();
+		let parse = |quick_play: &str| {
+			serde_json::from_str::<Test>(&format!(r#"{{"quick_play": {quick_play}}}"#))
+				.map(|x| x.quick_play)
+		};
+
 		assert_eq!(
-			test.quick_play,
+			parse(r#"{"type": "server", "server": "localhost", "port": 25565}"#).unwrap(),
 			QuickPlay::Server {
 				server: "localhost".into(),
 				port: Some(25565)
 			}
 		);
+		assert_eq!(
+			parse(r#"{"type": "server", "server": "localhost"}"#).unwrap(),
+			QuickPlay::Server {
+				server: "localhost".into(),
+				port: None
+			}
+		);
+		assert_eq!(
+			parse(r#"{"type": "world", "world": "test"}"#).unwrap(),
+			QuickPlay::World {
+				world: "test".into()
+			}
+		);
+		assert_eq!(
+			parse(r#"{"type": "realm", "realm": "my_realm"}"#).unwrap(),
+			QuickPlay::Realm {
+				realm: "my_realm".into()
+			}
+		);
+		assert_eq!(parse(r#"{"type": "none"}"#).unwrap(), QuickPlay::None);
+
+		// Fields that belong to other variants are an error
+		parse(
+			r#"{
+			"type": "server",
+			"server": "localhost",
+			"port": 25565,
+			"world": "test",
+			"realm": "my_realm"
+		}"#,
+		)
+		.unwrap_err();
+		parse(r#"{"type": "world", "world": "test", "port": 25565}"#).unwrap_err();
+		parse(r#"{"type": "none", "realm": "my_realm"}"#).unwrap_err();
+	}
+
+	#[test]
+	fn test_server_property_problems() {
+		assert!(get_server_property_problem("server-port", "25565").is_none());
+		assert!(get_server_property_problem("server-port", "70000").is_some());
+		assert!(get_server_property_problem("difficulty", "hard").is_none());
+		assert!(get_server_property_problem("difficulty", "impossible").is_some());
+		assert!(get_server_property_problem("pvp", "yes").is_some());
+		// Unknown keys are written without checking
+		assert!(get_server_property_problem("level-seed", "anything").is_none());
 	}
 }
@@ -13,22 +13,26 @@ pub mod plugin;
 pub mod preferences;
 /// Configuring profiles
 pub mod profile;
+/// Secret values that can be read from the environment or from commands
+pub mod secret;
 /// Configuring users
 pub mod user;
 
-use self::instance::{read_instance_config, InstanceConfig};
+use self::instance::{find_case_insensitive_collision, read_instance_config, InstanceConfig};
 use self::plugin::PluginManager;
-use self::preferences::PrefDeser;
-use self::profile::ProfileConfig;
+use self::preferences::{add_plugin_repos, PrefDeser};
+use self::profile::{ProfileConfig, ProfileManifest};
 use self::user::UserConfig;
 use anyhow::{bail, Context};
 use mcvm_core::auth_crate::mc::ClientId;
 use mcvm_core::io::{json_from_file, json_to_file_pretty};
+use mcvm_core::net::download::Client;
 use mcvm_core::user::UserManager;
+use mcvm_core::util::secrets::get_ms_client_id;
 use mcvm_shared::id::{InstanceID, ProfileID};
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm_shared::translate;
-use mcvm_shared::util::is_valid_identifier;
+use mcvm_shared::util::validate_identifier;
 use preferences::ConfigPreferences;
 use profile::consolidate_profile_configs;
 #[cfg(feature = "schema")]
@@ -76,6 +80,89 @@ pub struct ConfigDeser {
 	preferences: PrefDeser,
 }
 
+impl ConfigDeser {
+	/// Get the configuration of a profile
+	pub fn get_profile(&self, profile: &ProfileID) -> Option<&ProfileConfig> {
+		self.profiles.get(profile)
+	}
+
+	/// Create the HTTP client to use for requests, respecting the configured proxy
+	pub fn make_client(&self) -> anyhow::Result<Client> {
+		let (prefs, _) =
+			ConfigPreferences::read(&self.preferences).context("Failed to read preferences")?;
+		prefs.make_client()
+	}
+
+	/// Get the configured maximum number of files to download at the same time, if it is set
+	pub fn get_download_concurrency(&self) -> Option<usize> {
+		self.preferences.download_concurrency
+	}
+
+	/// Get the configured instances directory, if it is not the default one
+	pub fn get_instances_dir(&self) -> Option<PathBuf> {
+		self.preferences.get_instances_dir()
+	}
+
+	/// Get the IDs of all of the profiles, sorted
+	pub fn get_profile_ids(&self) -> Vec<ProfileID> {
+		let mut out: Vec<_> = self.profiles.keys().cloned().collect();
+		out.sort();
+		out
+	}
+
+	/// Get the full configuration of every profile, with the cached manifests of subscribed
+	/// profiles applied and the configuration of parent profiles merged in
+	pub fn get_resolved_profiles(
+		&self,
+		paths: &Paths,
+	) -> anyhow::Result<HashMap<ProfileID, ProfileConfig>> {
+		let mut profiles = self.profiles.clone();
+		for (profile_id, profile) in &mut profiles {
+			if let Some(source) = &profile.source {
+				let manifest = ProfileManifest::read_cached(profile_id, source, paths)
+					.with_context(|| {
+						format!("Failed to read manifest for profile '{profile_id}'")
+					})?;
+				if let Some(manifest) = manifest {
+					*profile = manifest.apply(profile.clone())?;
+				}
+			}
+		}
+
+		consolidate_profile_configs(profiles).context("Failed to merge profiles")
+	}
+
+	/// Get the IDs of the instances that derive from a profile, either directly or
+	/// through other profiles, sorted
+	pub fn get_profile_instances(&self, profile: &ProfileID) -> Vec<InstanceID> {
+		let mut out: Vec<_> = self
+			.instances
+			.iter()
+			.filter(|(_, instance)| {
+				let mut parent = instance.common.from.clone();
+				// Limit the depth in case there is a cycle
+				for _ in 0..=self.profiles.len() {
+					let Some(id) = parent else {
+						return false;
+					};
+					let id = ProfileID::from(id);
+					if &id == profile {
+						return true;
+					}
+					parent = self
+						.profiles
+						.get(&id)
+						.and_then(|x| x.instance.common.from.clone());
+				}
+				false
+			})
+			.map(|x| x.0.clone())
+			.collect();
+		out.sort();
+		out
+	}
+}
+
 impl Config {
 	/// Get the config path
 	pub fn get_path(paths: &Paths) -> PathBuf {
@@ -93,6 +180,22 @@ impl Config {
 		}
 	}
 
+	/// Read the instances directory from the config file and set it on the paths, so that
+	/// instance directories are correct before the rest of the config is loaded. Nothing is
+	/// changed if the config file doesn't exist or is invalid, as that is reported when it is loaded
+	pub fn apply_instances_dir(paths: &mut Paths) {
+		let path = Self::get_path(paths);
+		if !path.exists() {
+			return;
+		}
+		let Ok(config) = json_from_file::<ConfigDeser>(path) else {
+			return;
+		};
+		if let Some(dir) = config.preferences.get_instances_dir() {
+			paths.set_instances_dir(dir);
+		}
+	}
+
 	/// Create the default config at the specified path if it does not exist
 	pub fn create_default(path: &Path) -> anyhow::Result<()> {
 		if !path.exists() {
@@ -112,19 +215,28 @@ impl Config {
 		paths: &Paths,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<Self> {
-		let mut users = UserManager::new(ClientId::new("".into()));
+		let mut users = UserManager::new(get_ms_client_id());
 		let mut instances = HashMap::with_capacity(config.instances.len());
 		// Preferences
-		let (prefs, repositories) =
+		let (prefs, mut repositories) =
 			ConfigPreferences::read(&config.preferences).context("Failed to read preferences")?;
+		// The configured client ID is only resolved once it is needed for authentication
+		if let Some(client_id) = prefs.ms_client_id.clone() {
+			users.set_ms_client_id_function(Arc::new(move || {
+				let client_id = client_id
+					.get()
+					.context("Failed to get the configured Microsoft client ID")?;
+				Ok(ClientId::new(client_id.to_string()))
+			}));
+		}
+		add_plugin_repos(&mut repositories, &plugins, paths, o)
+			.context("Failed to add repositories from plugins")?;
 
 		let packages = PkgRegistry::new(repositories, prefs.package_caching_strategy.clone());
 
 		// Users
 		for (user_id, user_config) in config.users.iter() {
-			if !is_valid_identifier(user_id) {
-				bail!("Invalid user ID '{user_id}'");
-			}
+			validate_identifier(user_id).with_context(|| format!("Invalid user ID '{user_id}'"))?;
 			let user = user_config.to_user(user_id);
 			// Disabled until we can verify game ownership.
 			// We don't want to be a cracked launcher.
@@ -155,9 +267,37 @@ impl Config {
 			);
 		}
 
-		// Consolidate profiles
-		let profiles =
-			consolidate_profile_configs(config.profiles).context("Failed to merge profiles")?;
+		// Check IDs before they are used
+		for (profile_id, profile) in &config.profiles {
+			validate_identifier(profile_id)
+				.with_context(|| format!("Invalid profile ID '{profile_id}'"))?;
+			let is_manifest_missing = profile.source.is_some()
+				&& !ProfileManifest::get_cache_path(profile_id, paths).exists();
+			if is_manifest_missing && show_warnings {
+				o.display(
+					MessageContents::Warning(translate!(
+						o,
+						ProfileManifestNotDownloaded,
+						"profile" = profile_id.as_ref()
+					)),
+					MessageLevel::Important,
+				);
+			}
+		}
+		if let Some((first, second)) =
+			find_case_insensitive_collision(config.instances.keys().map(|x| x.as_ref()))
+		{
+			bail!("Instance IDs '{first}' and '{second}' only differ by case, which would make them share a directory on some systems");
+		}
+
+		// Apply the manifests of subscribed profiles and consolidate profiles
+		let profiles = config.get_resolved_profiles(paths)?;
+		for (profile_id, profile) in &profiles {
+			profile
+				.packages
+				.validate()
+				.with_context(|| format!("Invalid packages in profile '{profile_id}'"))?;
+		}
 
 		// Instances
 		for (instance_id, instance_config) in config.instances {
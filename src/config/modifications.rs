@@ -1,14 +1,15 @@
 #![allow(dead_code)]
 use std::fs::File;
+use std::path::PathBuf;
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 
 use crate::io::paths::Paths;
 use mcvm_shared::id::{InstanceID, ProfileID};
 
-use super::instance::InstanceConfig;
+use super::instance::{validate_instance_id, InstanceConfig};
 use super::package::PackageConfigDeser;
-use super::profile::ProfileConfig;
+use super::profile::{ProfileConfig, ProfileSource};
 use super::user::UserConfig;
 use super::{Config, ConfigDeser};
 
@@ -16,12 +17,38 @@ use super::{Config, ConfigDeser};
 pub enum ConfigModification {
 	/// Adds a new user
 	AddUser(String, UserConfig),
+	/// Sets the default user
+	SetDefaultUser(String),
+	/// Removes a user, unsetting it as the default user if it is
+	RemoveUser(String),
 	/// Adds a new profile
 	AddProfile(ProfileID, ProfileConfig),
+	/// Subscribes a profile to a remote manifest, creating the profile if it does not exist
+	SetProfileSource(ProfileID, ProfileSource),
 	/// Adds a new instance
 	AddInstance(InstanceID, InstanceConfig),
+	/// Removes an instance and any references to it in instance groups
+	RemoveInstance(InstanceID),
+	/// Copies the config of an existing instance to a new instance ID
+	DuplicateInstance {
+		/// The instance to copy from
+		source: InstanceID,
+		/// The ID of the new instance
+		new_id: InstanceID,
+	},
+	/// Changes the ID of an existing instance
+	RenameInstance {
+		/// The instance to rename
+		instance: InstanceID,
+		/// The new ID of the instance
+		new_id: InstanceID,
+	},
 	/// Adds a new package to a profile
 	AddPackage(ProfileID, PackageConfigDeser),
+	/// Replaces the configuration of a package that is already on a profile
+	SetPackage(ProfileID, PackageConfigDeser),
+	/// Sets the directory that instances are stored in, or resets it to the default
+	SetInstancesDir(Option<PathBuf>),
 }
 
 /// Applies modifications to the config
@@ -34,12 +61,77 @@ pub fn apply_modifications(
 			ConfigModification::AddUser(id, user) => {
 				config.users.insert(id, user);
 			}
+			ConfigModification::SetDefaultUser(id) => {
+				if !config.users.contains_key(&id) {
+					bail!("Unknown user '{id}'");
+				}
+				config.default_user = Some(id);
+			}
+			ConfigModification::RemoveUser(id) => {
+				if config.users.remove(&id).is_none() {
+					bail!("Unknown user '{id}'");
+				}
+				if config.default_user.as_ref() == Some(&id) {
+					config.default_user = None;
+				}
+			}
 			ConfigModification::AddProfile(id, profile) => {
 				config.profiles.insert(id, profile);
 			}
+			ConfigModification::SetProfileSource(id, source) => {
+				config.profiles.entry(id).or_default().source = Some(source);
+			}
 			ConfigModification::AddInstance(instance_id, instance) => {
 				config.instances.insert(instance_id, instance);
 			}
+			ConfigModification::RemoveInstance(instance_id) => {
+				if config.instances.remove(&instance_id).is_none() {
+					bail!("Unknown instance '{instance_id}'");
+				}
+				for group in config.instance_groups.values_mut() {
+					group.retain(|x| x != &instance_id);
+				}
+			}
+			ConfigModification::DuplicateInstance { source, new_id } => {
+				validate_instance_id(&new_id)
+					.with_context(|| format!("Invalid instance ID '{new_id}'"))?;
+				if let Some(existing) = config
+					.instances
+					.keys()
+					.find(|x| x.eq_ignore_ascii_case(&new_id))
+				{
+					bail!("An instance with the ID '{existing}' already exists");
+				}
+				let instance = config
+					.instances
+					.get(&source)
+					.ok_or(anyhow!("Unknown instance '{source}'"))?
+					.clone();
+				config.instances.insert(new_id, instance);
+			}
+			ConfigModification::RenameInstance { instance, new_id } => {
+				validate_instance_id(&new_id)
+					.with_context(|| format!("Invalid instance ID '{new_id}'"))?;
+				if let Some(existing) = config
+					.instances
+					.keys()
+					.find(|x| x.eq_ignore_ascii_case(&new_id) && *x != &instance)
+				{
+					bail!("An instance with the ID '{existing}' already exists");
+				}
+				let config_instance = config
+					.instances
+					.remove(&instance)
+					.ok_or(anyhow!("Unknown instance '{instance}'"))?;
+				config.instances.insert(new_id.clone(), config_instance);
+				for group in config.instance_groups.values_mut() {
+					for id in group.iter_mut() {
+						if id == &instance {
+							*id = new_id.clone();
+						}
+					}
+				}
+			}
 			ConfigModification::AddPackage(profile_id, package) => {
 				let profile = config
 					.profiles
@@ -47,6 +139,27 @@ pub fn apply_modifications(
 					.ok_or(anyhow!("Unknown profile '{profile_id}'"))?;
 				profile.packages.add_global_package(package);
 			}
+			ConfigModification::SetPackage(profile_id, package) => {
+				let profile = config
+					.profiles
+					.get_mut(&profile_id)
+					.ok_or(anyhow!("Unknown profile '{profile_id}'"))?;
+				let id = package.get_pkg_id();
+				let mut found = false;
+				// The package can be in more than one of the side lists
+				for existing in profile.packages.iter_mut() {
+					if existing.get_pkg_id() == id {
+						*existing = package.clone();
+						found = true;
+					}
+				}
+				if !found {
+					bail!("Package '{id}' is not configured on profile '{profile_id}'");
+				}
+			}
+			ConfigModification::SetInstancesDir(dir) => {
+				config.preferences.instances_dir = dir.map(|x| x.to_string_lossy().to_string());
+			}
 		};
 	}
 	Ok(())
@@ -85,4 +198,148 @@ mod tests {
 		apply_modifications(&mut config, modifications).unwrap();
 		assert!(config.users.contains_key("bob"));
 	}
+
+	#[test]
+	fn test_default_user_modification() {
+		let mut config = ConfigDeser::default();
+
+		let user_config = UserConfig {
+			variant: UserVariant::Demo {},
+		};
+
+		let modifications = vec![
+			ConfigModification::AddUser("bob".into(), user_config),
+			ConfigModification::SetDefaultUser("bob".into()),
+		];
+		apply_modifications(&mut config, modifications).unwrap();
+		assert_eq!(config.default_user.as_deref(), Some("bob"));
+
+		let modifications = vec![ConfigModification::SetDefaultUser("alice".into())];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+	}
+
+	#[test]
+	fn test_remove_user_modification() {
+		let mut config = ConfigDeser::default();
+
+		let user_config = UserConfig {
+			variant: UserVariant::Demo {},
+		};
+		let modifications = vec![
+			ConfigModification::AddUser("bob".into(), user_config),
+			ConfigModification::SetDefaultUser("bob".into()),
+			ConfigModification::RemoveUser("bob".into()),
+		];
+		apply_modifications(&mut config, modifications).unwrap();
+		assert!(!config.users.contains_key("bob"));
+		assert_eq!(config.default_user, None);
+
+		let modifications = vec![ConfigModification::RemoveUser("bob".into())];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+	}
+
+	#[test]
+	fn test_remove_instance_modification() {
+		let mut config = ConfigDeser::default();
+
+		let instance = InstanceConfig {
+			side: Some(mcvm_shared::Side::Client),
+			name: None,
+			common: Default::default(),
+			window: Default::default(),
+			properties: Default::default(),
+		};
+		let id = InstanceID::from("foo");
+		config
+			.instance_groups
+			.insert("group".into(), vec![id.clone()]);
+
+		let modifications = vec![
+			ConfigModification::AddInstance(id.clone(), instance),
+			ConfigModification::RemoveInstance(id.clone()),
+		];
+		apply_modifications(&mut config, modifications).unwrap();
+		assert!(!config.instances.contains_key(&id));
+		assert!(config.instance_groups["group"].is_empty());
+
+		let modifications = vec![ConfigModification::RemoveInstance(id)];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+	}
+
+	#[test]
+	fn test_duplicate_instance_modification() {
+		let mut config = ConfigDeser::default();
+
+		let instance = InstanceConfig {
+			side: Some(mcvm_shared::Side::Server),
+			name: None,
+			common: Default::default(),
+			window: Default::default(),
+			properties: Default::default(),
+		};
+		let modifications = vec![
+			ConfigModification::AddInstance("foo".into(), instance),
+			ConfigModification::DuplicateInstance {
+				source: "foo".into(),
+				new_id: "bar".into(),
+			},
+		];
+		apply_modifications(&mut config, modifications).unwrap();
+		assert_eq!(
+			config.instances[&InstanceID::from("bar")].side,
+			Some(mcvm_shared::Side::Server)
+		);
+
+		let modifications = vec![ConfigModification::DuplicateInstance {
+			source: "foo".into(),
+			new_id: "bar".into(),
+		}];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+	}
+
+	#[test]
+	fn test_rename_instance_modification() {
+		let mut config = ConfigDeser::default();
+
+		let instance = InstanceConfig {
+			side: Some(mcvm_shared::Side::Client),
+			name: None,
+			common: Default::default(),
+			window: Default::default(),
+			properties: Default::default(),
+		};
+		let old_id = InstanceID::from("foo");
+		let new_id = InstanceID::from("bar");
+		config
+			.instance_groups
+			.insert("group".into(), vec![old_id.clone()]);
+
+		let modifications = vec![
+			ConfigModification::AddInstance(old_id.clone(), instance.clone()),
+			ConfigModification::RenameInstance {
+				instance: old_id.clone(),
+				new_id: new_id.clone(),
+			},
+		];
+		apply_modifications(&mut config, modifications).unwrap();
+		assert!(!config.instances.contains_key(&old_id));
+		assert!(config.instances.contains_key(&new_id));
+		assert_eq!(config.instance_groups["group"], vec![new_id.clone()]);
+
+		// The new ID must not already exist
+		let modifications = vec![
+			ConfigModification::AddInstance(old_id.clone(), instance),
+			ConfigModification::RenameInstance {
+				instance: old_id.clone(),
+				new_id: new_id.clone(),
+			},
+		];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+
+		let modifications = vec![ConfigModification::RenameInstance {
+			instance: old_id,
+			new_id: "invalid id!".into(),
+		}];
+		assert!(apply_modifications(&mut config, modifications).is_err());
+	}
 }
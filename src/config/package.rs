@@ -2,10 +2,12 @@ use std::borrow::Cow;
 use std::fmt::Display;
 use std::sync::Arc;
 
-use anyhow::{bail, ensure};
+use anyhow::{bail, ensure, Context};
 use mcvm_pkg::properties::PackageProperties;
-use mcvm_shared::pkg::{is_valid_package_id, ArcPkgReq, PackageID, PackageStability};
-use mcvm_shared::util::is_valid_identifier;
+use mcvm_shared::pkg::{
+	is_valid_package_id, ArcPkgReq, PackageID, PackageStability, MAX_PACKAGE_ID_LENGTH,
+};
+use mcvm_shared::util::validate_identifier;
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -14,7 +16,7 @@ use crate::pkg::eval::EvalPermissions;
 use mcvm_pkg::{PkgRequest, PkgRequestSource};
 
 /// Stored configuration for a package
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PackageConfig {
 	/// The ID of the pcakage
 	pub id: PackageID,
@@ -80,12 +82,15 @@ impl PackageConfig {
 }
 
 /// Where a package was configured from
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PackageConfigSource {
 	/// Configured for one profile
 	Profile,
 	/// Configured for one instance
 	Instance,
+	/// Requested implicitly by the include_loader_api option
+	LoaderApi,
 }
 
 /// Different representations for the configuration of a package in deserialization
@@ -208,26 +213,115 @@ impl PackageConfigDeser {
 	}
 
 	/// Get the  worlds of the config
-	pub fn get_worlds(&self) -> Cow<[String]> {
+	pub fn get_worlds(&self) -> Cow<'_, [String]> {
 		match &self {
 			Self::Basic(..) => Cow::Owned(Vec::new()),
 			Self::Full(cfg) => Cow::Borrowed(&cfg.worlds),
 		}
 	}
 
+	/// Enable and disable features of the package, making sure that they exist in the package's
+	/// properties. Disabling a default feature turns off the default features and enables the
+	/// rest of them explicitly
+	pub fn change_features(
+		&mut self,
+		enable: &[String],
+		disable: &[String],
+		properties: &PackageProperties,
+	) -> anyhow::Result<()> {
+		let id = self.get_pkg_id();
+		let allowed_features = properties.features.as_deref().unwrap_or_default();
+		for feature in enable.iter().chain(disable) {
+			ensure!(
+				allowed_features.contains(feature),
+				"Package '{id}' does not have the feature '{feature}'"
+			);
+		}
+
+		let mut features = self.get_features();
+		let mut use_default_features = self.get_use_default_features();
+		let default_features = properties.default_features.as_deref().unwrap_or_default();
+		if use_default_features && disable.iter().any(|x| default_features.contains(x)) {
+			use_default_features = false;
+			features.extend(default_features.iter().cloned());
+		}
+		features.extend(enable.iter().cloned());
+		features.retain(|x| !disable.contains(x));
+		features.sort();
+		features.dedup();
+
+		let config = match self {
+			Self::Basic(id) => {
+				*self = Self::Full(FullPackageConfig {
+					id: id.clone(),
+					features: Vec::new(),
+					use_default_features: use_default_features_default(),
+					permissions: EvalPermissions::default(),
+					stability: None,
+					worlds: Vec::new(),
+				});
+				let Self::Full(config) = self else {
+					unreachable!()
+				};
+				config
+			}
+			Self::Full(config) => config,
+		};
+		config.features = features;
+		config.use_default_features = use_default_features;
+
+		Ok(())
+	}
+
 	/// Validate this config
 	pub fn validate(&self) -> anyhow::Result<()> {
 		let id = self.get_pkg_id();
+		validate_identifier(&id).with_context(|| format!("Invalid package ID '{id}'"))?;
 		if !is_valid_package_id(&id) {
-			bail!("Invalid package ID '{id}'");
+			bail!(
+				"Invalid package ID '{id}': Package IDs must be lowercase, cannot contain '_' or '.', and can be at most {MAX_PACKAGE_ID_LENGTH} characters long"
+			);
 		}
 
 		for feature in self.get_features() {
-			if !is_valid_identifier(&feature) {
-				bail!("Invalid string '{feature}'");
-			}
+			validate_identifier(&feature)
+				.with_context(|| format!("Invalid feature '{feature}' for package '{id}'"))?;
 		}
 
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_change_features() {
+		let properties = PackageProperties {
+			features: Some(vec!["a".into(), "b".into(), "c".into()]),
+			default_features: Some(vec!["a".into(), "b".into()]),
+			..Default::default()
+		};
+
+		let mut config = PackageConfigDeser::Basic("pkg".into());
+		config
+			.change_features(&["c".into()], &[], &properties)
+			.unwrap();
+		assert_eq!(config.get_features(), vec!["c".to_string()]);
+		assert!(config.get_use_default_features());
+
+		config
+			.change_features(&[], &["a".into()], &properties)
+			.unwrap();
+		assert_eq!(
+			config.get_features(),
+			vec!["b".to_string(), "c".to_string()]
+		);
+		assert!(!config.get_use_default_features());
+
+		assert!(config
+			.change_features(&["d".into()], &[], &properties)
+			.is_err());
+	}
+}
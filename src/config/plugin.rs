@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, MutexGuard};
+use std::time::Duration;
 
 use crate::io::paths::Paths;
 use anyhow::{anyhow, Context};
@@ -30,6 +31,8 @@ pub struct PluginConfig {
 	pub id: String,
 	/// The custom config for the plugin
 	pub custom_config: Option<serde_json::Value>,
+	/// The maximum number of seconds that a hook from this plugin can run for
+	pub hook_timeout: Option<u64>,
 }
 
 /// Deserialized format for a plugin configuration
@@ -47,6 +50,10 @@ pub enum PluginConfigDeser {
 		#[serde(default)]
 		#[serde(rename = "config")]
 		custom_config: Option<serde_json::Value>,
+		/// The maximum number of seconds that a hook from this plugin can run for
+		#[serde(default)]
+		#[serde(skip_serializing_if = "Option::is_none")]
+		hook_timeout: Option<u64>,
 	},
 }
 
@@ -61,7 +68,16 @@ impl PluginConfigDeser {
 			Self::Full { custom_config, .. } => custom_config.clone(),
 		};
 
-		PluginConfig { id, custom_config }
+		let hook_timeout = match self {
+			Self::Simple(..) => None,
+			Self::Full { hook_timeout, .. } => *hook_timeout,
+		};
+
+		PluginConfig {
+			id,
+			custom_config,
+			hook_timeout,
+		}
 	}
 }
 
@@ -144,6 +160,7 @@ impl PluginManager {
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<()> {
 		let custom_config = plugin.custom_config.clone();
+		let hook_timeout = plugin.hook_timeout;
 		let id = plugin.id.clone();
 		let mut inner = self.inner.lock().map_err(|x| anyhow!("{x}"))?;
 		inner.configs.push(plugin);
@@ -154,6 +171,9 @@ impl PluginManager {
 		if let Some(plugin_dir) = plugin_dir {
 			plugin.set_working_dir(plugin_dir.to_owned());
 		}
+		if let Some(hook_timeout) = hook_timeout {
+			plugin.set_hook_timeout(Duration::from_secs(hook_timeout));
+		}
 
 		inner.manager.add_plugin(plugin, &paths.core, o)?;
 
@@ -194,6 +214,21 @@ impl PluginManager {
 		inner.manager.call_hook(hook, arg, &paths.core, o)
 	}
 
+	/// Call a plugin hook on each plugin in order, passing the result of each to the next one
+	pub fn call_hook_chained<H: Hook>(
+		&self,
+		hook: H,
+		arg: H::Arg,
+		update: impl Fn(&mut H::Arg, H::Result),
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<H::Arg> {
+		let inner = self.inner.lock().map_err(|x| anyhow!("{x}"))?;
+		inner
+			.manager
+			.call_hook_chained(hook, arg, update, &paths.core, o)
+	}
+
 	/// Call a plugin hook on a specific plugin
 	pub fn call_hook_on_plugin<H: Hook>(
 		&self,
@@ -210,7 +245,7 @@ impl PluginManager {
 	}
 
 	/// Get a lock for the inner mutex
-	pub fn get_lock(&self) -> anyhow::Result<MutexGuard<PluginManagerInner>> {
+	pub fn get_lock(&self) -> anyhow::Result<MutexGuard<'_, PluginManagerInner>> {
 		let inner = self.inner.lock().map_err(|x| anyhow!("{x}"))?;
 		Ok(inner)
 	}
@@ -1,12 +1,18 @@
 use std::collections::HashSet;
 use std::path::PathBuf;
 
+use crate::io::paths::Paths;
 use crate::pkg::reg::CachingStrategy;
-use crate::pkg::repo::{PkgRepo, PkgRepoLocation};
-use mcvm_core::net::download::validate_url;
+use crate::pkg::repo::{PkgRepo, PkgRepoLocation, RepoToken};
+use super::plugin::PluginManager;
+use super::secret::{LazySecret, SecretSource};
+use mcvm_core::net::download::{make_client, make_user_agent, validate_url, Client};
+use mcvm_core::net::game_files::mirror::Mirrors;
 
 use anyhow::{bail, Context};
+use mcvm_plugin::hooks::AddRepositories;
 use mcvm_shared::lang::Language;
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -18,6 +24,20 @@ pub struct ConfigPreferences {
 	pub package_caching_strategy: CachingStrategy,
 	/// The global language
 	pub language: Language,
+	/// Whether to only warn about conflicting packages instead of failing
+	pub allow_package_conflicts: bool,
+	/// A proxy URL to send requests through
+	pub proxy: Option<String>,
+	/// A custom User-Agent to send with requests
+	pub user_agent: Option<String>,
+	/// The maximum number of files to download at the same time, overriding the default
+	pub download_concurrency: Option<usize>,
+	/// A Microsoft client ID to use instead of the built-in one
+	pub ms_client_id: Option<LazySecret>,
+	/// A directory to store instances in instead of the default one
+	pub instances_dir: Option<PathBuf>,
+	/// Mirrors to download game files from instead of the official servers
+	pub mirrors: Mirrors,
 }
 
 /// Deserialization struct for user preferences
@@ -31,6 +51,35 @@ pub struct PrefDeser {
 	pub package_caching_strategy: CachingStrategy,
 	/// The user's configured language
 	pub language: Language,
+	/// Whether to only warn about conflicting packages instead of failing
+	pub allow_package_conflicts: bool,
+	/// A proxy URL to send requests through
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub proxy: Option<String>,
+	/// A custom User-Agent to send with requests
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub user_agent: Option<String>,
+	/// The maximum number of files to download at the same time
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub download_concurrency: Option<usize>,
+	/// A Microsoft client ID to use instead of the built-in one. Can be a reference to an
+	/// environment variable like `${MY_CLIENT_ID}` or to a command like `${cmd:pass show client-id}`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub ms_client_id: Option<String>,
+	/// A directory to store instances in instead of the default one. Caches and other data
+	/// stay in the default location
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub instances_dir: Option<String>,
+	/// Mirrors to download game files from instead of the official Mojang servers
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub mirrors: Option<MirrorsDeser>,
+}
+
+impl PrefDeser {
+	/// Get the configured instances directory
+	pub fn get_instances_dir(&self) -> Option<PathBuf> {
+		self.instances_dir.as_ref().map(PathBuf::from)
+	}
 }
 
 /// Deserialization struct for a package repo
@@ -48,6 +97,32 @@ pub struct RepoDeser {
 	/// Whether to disable the repo and not add it to the list
 	#[serde(default)]
 	pub disable: bool,
+	/// Authentication for a private repository
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub auth: Option<RepoAuth>,
+}
+
+/// Authentication for a package repository
+#[derive(Deserialize, Serialize, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct RepoAuth {
+	/// The bearer token to send to the repository. Can be a reference to an environment
+	/// variable like `${MY_TOKEN}` or to a command like `${cmd:pass show my-token}`
+	pub token: String,
+}
+
+impl RepoAuth {
+	/// Get the token. Tokens that refer to an environment variable or a command
+	/// are only resolved when they are first used
+	pub fn get_token(&self) -> anyhow::Result<RepoToken> {
+		if self.token.is_empty() {
+			bail!("Token is empty");
+		}
+
+		Ok(RepoToken::from_secret(LazySecret::new(
+			SecretSource::parse(&self.token),
+		)))
+	}
 }
 
 /// Deserialization struct for all configured package repositories
@@ -67,6 +142,45 @@ pub struct RepositoriesDeser {
 	pub enable_std: bool,
 }
 
+/// Deserialization struct for game file mirrors. Each mirror is a base URL
+/// that replaces the one of the official server
+#[derive(Deserialize, Serialize, Default, Clone)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(default)]
+pub struct MirrorsDeser {
+	/// The mirror for game assets
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub assets: Option<String>,
+	/// The mirror for game libraries
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub libraries: Option<String>,
+	/// The mirror for the version manifest. This mirror is fully trusted, as the manifest
+	/// is what provides the hashes that files from the other mirrors are checked against
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version_manifest: Option<String>,
+}
+
+impl MirrorsDeser {
+	/// Validate the mirror URLs and convert them to the format used by the core
+	pub fn to_mirrors(&self) -> anyhow::Result<Mirrors> {
+		for (kind, url) in [
+			("assets", &self.assets),
+			("libraries", &self.libraries),
+			("version manifest", &self.version_manifest),
+		] {
+			if let Some(url) = url {
+				validate_url(url).with_context(|| format!("Invalid {kind} mirror URL '{url}'"))?;
+			}
+		}
+
+		Ok(Mirrors {
+			assets: self.assets.clone(),
+			libraries: self.libraries.clone(),
+			version_manifest: self.version_manifest.clone(),
+		})
+	}
+}
+
 impl Default for RepositoriesDeser {
 	fn default() -> Self {
 		Self {
@@ -98,6 +212,39 @@ impl ConfigPreferences {
 			}
 		}
 
+		if let Some(proxy) = &prefs.proxy {
+			validate_url(proxy).with_context(|| format!("Invalid proxy URL '{proxy}'"))?;
+		}
+
+		if let Some(user_agent) = &prefs.user_agent {
+			if user_agent.trim().is_empty() {
+				bail!("User agent cannot be empty");
+			}
+		}
+
+		if prefs.download_concurrency == Some(0) {
+			bail!("Download concurrency must be greater than zero");
+		}
+
+		if prefs.ms_client_id.as_ref().is_some_and(|x| x.is_empty()) {
+			bail!("Microsoft client ID cannot be empty");
+		}
+
+		let instances_dir = prefs.get_instances_dir();
+		if let Some(dir) = &instances_dir {
+			if !dir.is_absolute() {
+				bail!(
+					"Instances directory '{}' must be an absolute path",
+					dir.display()
+				);
+			}
+		}
+
+		let mirrors = match &prefs.mirrors {
+			Some(mirrors) => mirrors.to_mirrors()?,
+			None => Mirrors::default(),
+		};
+
 		// Check for duplicate IDs
 		let mut existing = HashSet::new();
 		for repo in &repositories {
@@ -111,12 +258,82 @@ impl ConfigPreferences {
 			Self {
 				package_caching_strategy: prefs.package_caching_strategy.clone(),
 				language: prefs.language,
+				allow_package_conflicts: prefs.allow_package_conflicts,
+				proxy: prefs.proxy.clone(),
+				user_agent: prefs.user_agent.clone(),
+				download_concurrency: prefs.download_concurrency,
+				ms_client_id: prefs
+					.ms_client_id
+					.as_deref()
+					.map(|x| LazySecret::new(SecretSource::parse(x))),
+				instances_dir,
+				mirrors,
 			},
 			repositories,
 		))
 	}
 }
 
+impl ConfigPreferences {
+	/// Create the HTTP client to use for requests, respecting the configured proxy and User-Agent
+	pub fn make_client(&self) -> anyhow::Result<Client> {
+		make_client(self.proxy.as_deref(), Some(&self.get_user_agent()))
+	}
+
+	/// Get the User-Agent to send with requests, falling back to one with the version of MCVM
+	pub fn get_user_agent(&self) -> String {
+		self.user_agent
+			.clone()
+			.unwrap_or_else(|| make_user_agent(crate::VERSION))
+	}
+}
+
+/// Add the repositories provided by plugins to the list. Plugin repositories are placed after
+/// all of the configured ones, unless they are high priority, in which case they come first
+pub fn add_plugin_repos(
+	repos: &mut Vec<PkgRepo>,
+	plugins: &PluginManager,
+	paths: &Paths,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<()> {
+	let results = plugins
+		.call_hook(AddRepositories, &(), paths, o)
+		.context("Failed to call add_repositories hook")?;
+	let mut high_priority = Vec::new();
+	for result in results {
+		let plugin_id = result.get_id().clone();
+		for repo in result.result(o)? {
+			if repos.iter().chain(&high_priority).any(|x| x.id == repo.id) {
+				o.display(
+					MessageContents::Warning(format!(
+						"Repository '{}' from plugin '{plugin_id}' was not added because a repository with that ID already exists",
+						repo.id
+					)),
+					MessageLevel::Important,
+				);
+				continue;
+			}
+			validate_url(&repo.url).with_context(|| {
+				format!(
+					"Invalid url '{}' in package repository '{}' from plugin '{plugin_id}'",
+					repo.url, repo.id
+				)
+			})?;
+
+			let pkg_repo =
+				PkgRepo::from_plugin(&repo.id, PkgRepoLocation::Remote(repo.url), &plugin_id);
+			if repo.high_priority {
+				high_priority.push(pkg_repo);
+			} else {
+				repos.push(pkg_repo);
+			}
+		}
+	}
+	repos.splice(0..0, high_priority);
+
+	Ok(())
+}
+
 /// Add a repo to the list
 fn add_repo(repos: &mut Vec<PkgRepo>, repo: &RepoDeser) -> anyhow::Result<()> {
 	let location = if let Some(url) = &repo.url {
@@ -125,10 +342,23 @@ fn add_repo(repos: &mut Vec<PkgRepo>, repo: &RepoDeser) -> anyhow::Result<()> {
 		})?;
 		PkgRepoLocation::Remote(url.clone())
 	} else if let Some(path) = &repo.path {
+		if repo.auth.is_some() {
+			bail!(
+				"Authentication is only supported for remote repositories, but repository {} is local",
+				repo.id
+			);
+		}
 		PkgRepoLocation::Local(PathBuf::from(path))
 	} else {
 		bail!("Niether path nor URL was set for repository {}", repo.id);
 	};
-	repos.push(PkgRepo::new(&repo.id, location));
+	let mut pkg_repo = PkgRepo::new(&repo.id, location);
+	if let Some(auth) = &repo.auth {
+		let token = auth
+			.get_token()
+			.with_context(|| format!("Failed to get token for repository {}", repo.id))?;
+		pkg_repo = pkg_repo.with_token(token);
+	}
+	repos.push(pkg_repo);
 	Ok(())
 }
@@ -1,18 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
+use anyhow::{bail, Context};
+use itertools::Itertools;
+use mcvm_core::net::download;
 use mcvm_shared::id::ProfileID;
 use mcvm_shared::Side;
+use reqwest::Client;
 #[cfg(feature = "schema")]
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use mcvm_shared::modifications::{ClientType, Modloader, Proxy, ServerType};
 
+use crate::io::paths::Paths;
+
 use super::instance::{merge_instance_configs, InstanceConfig};
 use super::package::PackageConfigDeser;
 
 /// Configuration for a profile
-#[derive(Deserialize, Serialize, Clone)]
+#[derive(Deserialize, Serialize, Clone, Default)]
 #[cfg_attr(feature = "schema", derive(JsonSchema))]
 pub struct ProfileConfig {
 	/// The configuration for the instance
@@ -21,6 +29,190 @@ pub struct ProfileConfig {
 	/// Package configuration
 	#[serde(default)]
 	pub packages: ProfilePackageConfiguration,
+	/// A remote manifest that this profile is subscribed to. The rest of the
+	/// profile config is layered on top of it
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub source: Option<ProfileSource>,
+}
+
+impl ProfileConfig {
+	/// Download a profile manifest from a URL and create a profile from it,
+	/// subscribed to that URL and pinned to the hash of the manifest
+	pub async fn from_manifest_url(url: &str, client: &Client) -> anyhow::Result<Self> {
+		let downloaded = ProfileManifest::download(url, client).await?;
+		Ok(Self {
+			instance: downloaded.manifest.instance,
+			packages: downloaded.manifest.packages,
+			source: Some(ProfileSource {
+				url: url.to_string(),
+				sha256: downloaded.sha256,
+			}),
+		})
+	}
+}
+
+/// A remote profile manifest that a profile is subscribed to
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+pub struct ProfileSource {
+	/// The URL to download the manifest from
+	pub url: String,
+	/// The SHA-256 hash of the last accepted version of the manifest
+	pub sha256: String,
+}
+
+/// A profile manifest, which is hosted at a URL and fully defines a profile.
+/// This is the same as a profile config, without the ability to derive from
+/// other profiles or subscribe to other manifests
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProfileManifest {
+	/// The configuration for the instance
+	#[serde(flatten)]
+	pub instance: InstanceConfig,
+	/// Package configuration
+	#[serde(default)]
+	pub packages: ProfilePackageConfiguration,
+}
+
+/// A profile manifest that was downloaded, along with its raw contents and hash
+pub struct DownloadedProfileManifest {
+	/// The parsed manifest
+	pub manifest: ProfileManifest,
+	/// The raw contents of the manifest
+	pub contents: Vec<u8>,
+	/// The SHA-256 hash of the contents as a hex string
+	pub sha256: String,
+}
+
+impl ProfileManifest {
+	/// Parse and validate a manifest from its raw contents
+	pub fn parse(contents: &[u8]) -> anyhow::Result<Self> {
+		let manifest: Self =
+			serde_json::from_slice(contents).context("Failed to deserialize profile manifest")?;
+		if manifest.instance.common.from.is_some() {
+			bail!("Profile manifests cannot derive from other profiles");
+		}
+		manifest
+			.packages
+			.validate()
+			.context("Invalid packages in profile manifest")?;
+
+		Ok(manifest)
+	}
+
+	/// Download a manifest from a URL
+	pub async fn download(url: &str, client: &Client) -> anyhow::Result<DownloadedProfileManifest> {
+		download::validate_url(url).context("Invalid profile manifest URL")?;
+		let contents = download::bytes(url, client)
+			.await
+			.context("Failed to download profile manifest")?
+			.to_vec();
+		let manifest = Self::parse(&contents)?;
+		let sha256 = hash_manifest(&contents);
+
+		Ok(DownloadedProfileManifest {
+			manifest,
+			contents,
+			sha256,
+		})
+	}
+
+	/// Get the path to the cached manifest of a profile
+	pub fn get_cache_path(profile: &ProfileID, paths: &Paths) -> PathBuf {
+		paths
+			.internal
+			.join("profile_manifests")
+			.join(format!("{profile}.json"))
+	}
+
+	/// Read the cached manifest of a subscribed profile, checking it against the pinned hash.
+	/// Returns None if the manifest has not been downloaded yet
+	pub fn read_cached(
+		profile: &ProfileID,
+		source: &ProfileSource,
+		paths: &Paths,
+	) -> anyhow::Result<Option<Self>> {
+		let path = Self::get_cache_path(profile, paths);
+		if !path.exists() {
+			return Ok(None);
+		}
+		let contents = std::fs::read(path).context("Failed to read cached profile manifest")?;
+		let actual = hash_manifest(&contents);
+		if !actual.eq_ignore_ascii_case(&source.sha256) {
+			bail!("The manifest for profile '{profile}' does not match its pinned hash (expected {}, got {actual}). It may have been tampered with. Run `mcvm profile update {profile}` to download it again", source.sha256);
+		}
+
+		Self::parse(&contents).map(Some)
+	}
+
+	/// Describe the commands that this manifest runs and the environment variables it sets
+	/// when launching, so that they can be shown to the user before the manifest is accepted
+	pub fn get_launch_commands(&self) -> Vec<String> {
+		let launch = &self.instance.common.launch;
+		let mut out = Vec::new();
+		if let Some(command) = &launch.pre_launch {
+			out.push(format!("Run the command '{command}' before launching"));
+		}
+		if let Some(command) = &launch.post_launch {
+			out.push(format!("Run the command '{command}' after the game exits"));
+		}
+		if let Some(wrapper) = &launch.wrapper {
+			let command = std::iter::once(&wrapper.cmd)
+				.chain(&wrapper.args)
+				.join(" ");
+			out.push(format!("Launch the game through the command '{command}'"));
+		}
+		for (key, value) in launch.env.iter().sorted_by_key(|x| x.0) {
+			out.push(format!("Set the environment variable {key}={value}"));
+		}
+
+		out
+	}
+
+	/// Layer a local profile config on top of this manifest. Local packages replace
+	/// the packages from the manifest with the same ID
+	pub fn apply(self, local: ProfileConfig) -> anyhow::Result<ProfileConfig> {
+		let instance = merge_instance_configs(&self.instance, local.instance)?;
+
+		let local_ids: HashSet<_> = local.packages.iter().map(|x| x.get_pkg_id()).collect();
+		let mut packages = self.packages;
+		packages.retain(|x| !local_ids.contains(&x.get_pkg_id()));
+		for pkg in local.packages.iter_global() {
+			packages.add_global_package(pkg.clone());
+		}
+		for pkg in local.packages.iter_side(Side::Client) {
+			packages.add_client_package(pkg.clone());
+		}
+		for pkg in local.packages.iter_side(Side::Server) {
+			packages.add_server_package(pkg.clone());
+		}
+
+		Ok(ProfileConfig {
+			instance,
+			packages,
+			source: local.source,
+		})
+	}
+}
+
+impl DownloadedProfileManifest {
+	/// Write the manifest to the cache for a profile
+	pub fn write_cache(&self, profile: &ProfileID, paths: &Paths) -> anyhow::Result<()> {
+		let path = ProfileManifest::get_cache_path(profile, paths);
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)
+				.context("Failed to create profile manifest cache directory")?;
+		}
+		std::fs::write(path, &self.contents).context("Failed to write cached profile manifest")?;
+
+		Ok(())
+	}
+}
+
+/// Compute the SHA-256 hash of a manifest's contents as a hex string
+fn hash_manifest(contents: &[u8]) -> String {
+	hex::encode(Sha256::digest(contents))
 }
 
 /// Different representations of package configuration on a profile
@@ -85,6 +277,23 @@ impl ProfilePackageConfiguration {
 		}
 	}
 
+	/// Iterate mutably over all of the packages
+	pub fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut PackageConfigDeser> + 'a> {
+		match self {
+			Self::Simple(global) => Box::new(global.iter_mut()),
+			Self::Full {
+				global,
+				client,
+				server,
+			} => Box::new(
+				global
+					.iter_mut()
+					.chain(client.iter_mut())
+					.chain(server.iter_mut()),
+			),
+		}
+	}
+
 	/// Iterate over the global package list
 	pub fn iter_global(&self) -> impl Iterator<Item = &PackageConfigDeser> {
 		match &self {
@@ -105,6 +314,22 @@ impl ProfilePackageConfiguration {
 		}
 	}
 
+	/// Only keep the packages that match a predicate
+	pub fn retain(&mut self, mut f: impl FnMut(&PackageConfigDeser) -> bool) {
+		match self {
+			Self::Simple(global) => global.retain(f),
+			Self::Full {
+				global,
+				client,
+				server,
+			} => {
+				global.retain(&mut f);
+				client.retain(&mut f);
+				server.retain(&mut f);
+			}
+		}
+	}
+
 	/// Adds a package to the global list
 	pub fn add_global_package(&mut self, pkg: PackageConfigDeser) {
 		match self {
@@ -182,7 +407,7 @@ pub fn consolidate_profile_configs(
 }
 
 /// Game modifications
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct GameModifications {
 	modloader: Modloader,
 	/// Type of the client
@@ -272,3 +497,70 @@ pub fn can_install_proxy(proxy: Proxy) -> bool {
 	// TODO: Support Velocity
 	matches!(proxy, Proxy::None)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use mcvm_core::util::versions::MinecraftVersionDeser;
+
+	#[test]
+	fn test_manifest_layering() {
+		let manifest = ProfileManifest::parse(
+			br#"{
+				"version": "1.20.1",
+				"modloader": "fabric",
+				"packages": ["sodium", "lithium"]
+			}"#,
+		)
+		.unwrap();
+		let local: ProfileConfig = serde_json::from_value(serde_json::json!({
+			"version": "1.20.4",
+			"packages": [{ "id": "sodium", "stability": "latest" }, "iris"],
+			"source": { "url": "https://example.com/pack.json", "sha256": "abc" }
+		}))
+		.unwrap();
+
+		let profile = manifest.apply(local).unwrap();
+		assert_eq!(
+			profile.instance.common.version,
+			Some(MinecraftVersionDeser::Version("1.20.4".into()))
+		);
+		assert_eq!(profile.instance.common.modloader, Some(Modloader::Fabric));
+		let ids: Vec<_> = profile.packages.iter().map(|x| x.get_pkg_id()).collect();
+		assert_eq!(ids, ["lithium".into(), "sodium".into(), "iris".into()]);
+		assert!(profile.source.is_some());
+	}
+
+	#[test]
+	fn test_manifest_launch_commands() {
+		let manifest = ProfileManifest::parse(
+			br#"{
+				"version": "1.20.1",
+				"launch": {
+					"pre_launch": "./setup.sh",
+					"wrapper": { "cmd": "gamemoderun", "args": ["--flag"] },
+					"env": { "B": "2", "A": "1" }
+				}
+			}"#,
+		)
+		.unwrap();
+		assert_eq!(
+			manifest.get_launch_commands(),
+			vec![
+				"Run the command './setup.sh' before launching",
+				"Launch the game through the command 'gamemoderun --flag'",
+				"Set the environment variable A=1",
+				"Set the environment variable B=2",
+			]
+		);
+
+		let manifest = ProfileManifest::parse(br#"{ "version": "1.20.1" }"#).unwrap();
+		assert!(manifest.get_launch_commands().is_empty());
+	}
+
+	#[test]
+	fn test_manifest_cannot_derive() {
+		assert!(ProfileManifest::parse(br#"{ "from": "other" }"#).is_err());
+	}
+}
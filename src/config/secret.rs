@@ -0,0 +1,190 @@
+use std::fmt::Debug;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context};
+
+/// Where a secret value in the config comes from. Secrets can be written directly, or as a
+/// reference to an environment variable like `${MY_TOKEN}` or to a command like
+/// `${cmd:pass show mcvm/token}`, which is run to get the value from its output
+#[derive(Clone, PartialEq, Eq)]
+pub enum SecretSource {
+	/// The value itself
+	Plain(String),
+	/// The name of an environment variable to read the value from
+	Env(String),
+	/// A shell command that prints the value
+	Command(String),
+}
+
+impl SecretSource {
+	/// Parse a secret from a config string
+	pub fn parse(string: &str) -> Self {
+		let Some(reference) = get_reference(string) else {
+			return Self::Plain(string.to_string());
+		};
+		match reference.strip_prefix("cmd:") {
+			Some(command) => Self::Command(command.to_string()),
+			None => Self::Env(reference.to_string()),
+		}
+	}
+
+	/// Get the value of the secret, reading the environment variable or running the command
+	/// that it refers to. Fails if the value is empty
+	pub fn resolve(&self) -> anyhow::Result<String> {
+		let value = match self {
+			Self::Plain(value) => value.clone(),
+			Self::Env(var) => std::env::var(var)
+				.with_context(|| format!("Environment variable '{var}' is not set"))?,
+			Self::Command(command) => run_secret_command(command)?,
+		};
+		if value.is_empty() {
+			bail!("Secret is empty");
+		}
+
+		Ok(value)
+	}
+}
+
+impl Debug for SecretSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::Plain(..) => write!(f, "Plain(***)"),
+			Self::Env(var) => write!(f, "Env({var})"),
+			Self::Command(command) => write!(f, "Command({command})"),
+		}
+	}
+}
+
+/// A secret that is only resolved the first time that it is used, so that commands aren't run
+/// unless the value is actually needed. Clones share the resolved value
+#[derive(Clone)]
+pub struct LazySecret {
+	source: SecretSource,
+	value: Arc<Mutex<Option<Arc<str>>>>,
+}
+
+impl LazySecret {
+	/// Create a new LazySecret that will be resolved from a source
+	pub fn new(source: SecretSource) -> Self {
+		Self {
+			source,
+			value: Arc::new(Mutex::new(None)),
+		}
+	}
+
+	/// Get the value of the secret, resolving it if it hasn't been yet
+	pub fn get(&self) -> anyhow::Result<Arc<str>> {
+		// Hold the lock while resolving so that the command is only run once
+		let mut value = self
+			.value
+			.lock()
+			.map_err(|_| anyhow::anyhow!("Secret lock was poisoned"))?;
+		if let Some(value) = &*value {
+			return Ok(value.clone());
+		}
+
+		let resolved: Arc<str> = self.source.resolve()?.into();
+		*value = Some(resolved.clone());
+		Ok(resolved)
+	}
+}
+
+impl Debug for LazySecret {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "LazySecret({:?})", self.source)
+	}
+}
+
+/// Get the contents of a string like `${...}`
+fn get_reference(string: &str) -> Option<&str> {
+	string
+		.strip_prefix("${")
+		.and_then(|x| x.strip_suffix('}'))
+		.filter(|x| !x.is_empty())
+}
+
+/// Runs a command with the system shell and gets its output as a secret. The command can
+/// still prompt the user, since only its standard output is captured
+fn run_secret_command(command: &str) -> anyhow::Result<String> {
+	if command.trim().is_empty() {
+		bail!("Secret command is empty");
+	}
+
+	#[cfg(target_family = "windows")]
+	let mut cmd = {
+		let mut cmd = Command::new("cmd");
+		cmd.arg("/C");
+		cmd
+	};
+	#[cfg(not(target_family = "windows"))]
+	let mut cmd = {
+		let mut cmd = Command::new("sh");
+		cmd.arg("-c");
+		cmd
+	};
+	cmd.arg(command);
+	cmd.stdin(Stdio::inherit());
+	cmd.stderr(Stdio::inherit());
+	cmd.stdout(Stdio::piped());
+
+	let output = cmd
+		.output()
+		.with_context(|| format!("Failed to run secret command '{command}'"))?;
+	// The output is left out of errors so that the secret doesn't end up in logs
+	if !output.status.success() {
+		bail!("Secret command '{command}' exited with {}", output.status);
+	}
+	let output =
+		String::from_utf8(output.stdout).context("Secret command output is not valid UTF-8")?;
+	let output = output.trim();
+	if output.is_empty() {
+		bail!("Secret command '{command}' did not output anything");
+	}
+
+	Ok(output.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_secret_parsing() {
+		assert_eq!(
+			SecretSource::parse("${MY_TOKEN}"),
+			SecretSource::Env("MY_TOKEN".into())
+		);
+		assert_eq!(
+			SecretSource::parse("plain-token"),
+			SecretSource::Plain("plain-token".into())
+		);
+		assert_eq!(
+			SecretSource::parse("${}"),
+			SecretSource::Plain("${}".into())
+		);
+		assert_eq!(
+			SecretSource::parse("prefix${MY_TOKEN}"),
+			SecretSource::Plain("prefix${MY_TOKEN}".into())
+		);
+		assert_eq!(
+			SecretSource::parse("${cmd:pass show mcvm}"),
+			SecretSource::Command("pass show mcvm".into())
+		);
+		assert_eq!(
+			format!("{:?}", SecretSource::parse("hunter2")),
+			"Plain(***)"
+		);
+	}
+
+	#[cfg(target_family = "unix")]
+	#[test]
+	fn test_secret_command() {
+		let secret = LazySecret::new(SecretSource::parse("${cmd:echo hunter2}"));
+		assert_eq!(&*secret.get().unwrap(), "hunter2");
+		assert!(SecretSource::parse("${cmd:true}").resolve().is_err());
+		assert!(SecretSource::parse("${cmd:echo hunter2; exit 1}")
+			.resolve()
+			.is_err());
+	}
+}
@@ -69,36 +69,44 @@ impl Instance {
 					vec![]
 				}
 			}
-			AddonKind::Datapack => {
-				if let Some(datapack_folder) = &self.config.datapack_folder {
-					vec![game_dir.join(datapack_folder)]
-				} else {
-					match &self.kind {
-						InstKind::Client { .. } => {
-							game_dir
-								.join("saves")
-								.read_dir()
-								.context("Failed to read saves directory")?
-								.filter_map(|world| {
-									let world = world.ok()?;
-									let path = world.path();
-									// Filter worlds not in the list
-									if !selected_worlds.is_empty() {
-										let dir_name = path.file_name()?.to_string_lossy();
-										if !selected_worlds.iter().any(|x| x == dir_name.as_ref()) {
-											return None;
-										}
-									}
-									Some(path.join("datapacks"))
-								})
-								.collect()
-						}
-						InstKind::Server { world_name, .. } => {
-							let world_dir = world_name.as_deref().unwrap_or("world");
-							vec![game_dir.join(world_dir).join("datapacks")]
+			AddonKind::Datapack => self.get_datapack_dirs(selected_worlds, paths)?,
+		})
+	}
+
+	/// Get the directories on this instance to put datapacks in. On clients, datapacks are
+	/// put in every world unless worlds are selected
+	pub fn get_datapack_dirs(
+		&mut self,
+		selected_worlds: &[String],
+		paths: &Paths,
+	) -> anyhow::Result<Vec<PathBuf>> {
+		self.ensure_dirs(paths)?;
+		let game_dir = &self.dirs.get().game_dir;
+		if let Some(datapack_folder) = &self.config.datapack_folder {
+			return Ok(vec![game_dir.join(datapack_folder)]);
+		}
+
+		Ok(match &self.kind {
+			InstKind::Client { .. } => game_dir
+				.join("saves")
+				.read_dir()
+				.context("Failed to read saves directory")?
+				.filter_map(|world| {
+					let world = world.ok()?;
+					let path = world.path();
+					// Filter worlds not in the list
+					if !selected_worlds.is_empty() {
+						let dir_name = path.file_name()?.to_string_lossy();
+						if !selected_worlds.iter().any(|x| x == dir_name.as_ref()) {
+							return None;
 						}
 					}
-				}
+					Some(path.join("datapacks"))
+				})
+				.collect(),
+			InstKind::Server { world_name, .. } => {
+				let world_dir = world_name.as_deref().unwrap_or("world");
+				vec![game_dir.join(world_dir).join("datapacks")]
 			}
 		})
 	}
@@ -120,8 +128,10 @@ impl Instance {
 		if link.exists() {
 			std::fs::remove_file(&link).context("Failed to remove instance addon file")?;
 		}
-		mcvm_core::io::files::update_hardlink(&addon_path, &link)
-			.context("Failed to create hard link")?;
+		// Hardlinks can't be made across filesystems, so copy the file in that case
+		if mcvm_core::io::files::update_hardlink(&addon_path, &link).is_err() {
+			std::fs::copy(&addon_path, &link).context("Failed to copy addon file")?;
+		}
 		Ok(())
 	}
 
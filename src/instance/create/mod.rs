@@ -5,21 +5,27 @@ mod server;
 
 use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use mcvm_core::instance::WindowResolution;
 use mcvm_core::io::java::classpath::Classpath;
 use mcvm_core::io::json_to_file;
-use mcvm_core::launch::LaunchConfiguration;
+use mcvm_core::launch::{LaunchArgs, LaunchArgsModifier, LaunchConfiguration};
 use mcvm_core::user::uuid::hyphenate_uuid;
 use mcvm_core::user::{User, UserManager};
 use mcvm_core::version::InstalledVersion;
 use mcvm_core::QuickPlayType;
 use mcvm_mods::fabric_quilt;
-use mcvm_plugin::hooks::{OnInstanceSetup, OnInstanceSetupArg};
+use mcvm_options::server::write_server_properties;
+use mcvm_plugin::hooks::{
+	ModifyLaunchArgs, ModifyLaunchArgsArg, OnInstanceSetup, OnInstanceSetupArg,
+};
 use mcvm_shared::modifications::Modloader;
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, NoOp, UpdatePhase, UpdatePhaseEvent,
+};
 use mcvm_shared::translate;
 use mcvm_shared::Side;
 use reqwest::Client;
@@ -67,9 +73,9 @@ impl Instance {
 	}
 
 	/// Create the data for the instance.
-	pub async fn create<'core>(
+	pub async fn create(
 		&mut self,
-		manager: &'core mut UpdateManager,
+		manager: &mut UpdateManager,
 		plugins: &PluginManager,
 		paths: &Paths,
 		users: &UserManager,
@@ -104,7 +110,8 @@ impl Instance {
 			}
 		}?;
 
-		// Run plugin setup hooks
+		// Run plugin setup hooks, which is where game options are written
+		o.display_update_phase(UpdatePhase::Options, UpdatePhaseEvent::Start);
 		self.ensure_dirs(paths)?;
 		let arg = OnInstanceSetupArg {
 			id: self.id.to_string(),
@@ -117,16 +124,26 @@ impl Instance {
 			.call_hook(OnInstanceSetup, &arg, paths, o)
 			.context("Failed to call instance setup hook")?;
 		for result in results {
-			result.result(o)?;
+			result.result_async(o).await?;
 		}
 
+		// Write the configured server properties over the ones from the plugins
+		if let InstKind::Server { properties, .. } = &self.kind {
+			if !properties.is_empty() {
+				let path = self.dirs.get().game_dir.join("server.properties");
+				write_server_properties(properties.clone(), &path)
+					.context("Failed to write server.properties")?;
+			}
+		}
+		o.display_update_phase(UpdatePhase::Options, UpdatePhaseEvent::End);
+
 		// Make the core instance
 		let mut version = manager
 			.get_core_version(o)
 			.await
 			.context("Failed to get manager version")?;
 
-		self.create_core_instance(&mut version, paths, o)
+		self.create_core_instance(&mut version, paths, plugins, None, false, o)
 			.await
 			.context("Failed to create core instance")?;
 		o.end_section();
@@ -136,18 +153,28 @@ impl Instance {
 
 	/// Ensure the directories are set and exist
 	pub fn ensure_dirs(&mut self, paths: &Paths) -> anyhow::Result<()> {
-		self.dirs
-			.ensure_full(|| InstanceDirs::new(paths, &self.id, &self.kind.to_side()));
+		self.dirs.ensure_full(|| {
+			InstanceDirs::new(
+				paths,
+				&self.id,
+				&self.kind.to_side(),
+				self.config.game_dir_override.as_deref(),
+			)
+		});
 		self.dirs.get().ensure_exist()?;
 
 		Ok(())
 	}
 
-	/// Create the core instance
+	/// Create the core instance. The output prefix is put before every line of the game output.
+	/// A detached instance will keep running after the launcher exits
 	pub(super) async fn create_core_instance<'core>(
 		&mut self,
 		version: &'core mut InstalledVersion<'core, 'core>,
 		paths: &Paths,
+		plugins: &PluginManager,
+		output_prefix: Option<String>,
+		detach: bool,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<mcvm_core::Instance<'core>> {
 		self.ensure_dirs(paths)?;
@@ -181,6 +208,8 @@ impl Instance {
 			});
 		let launch_config = LaunchConfiguration {
 			java: self.config.launch.java.clone(),
+			java_version: self.config.launch.java_version,
+			java_arch: self.config.launch.java_arch.clone(),
 			jvm_args: self.config.launch.jvm_args.clone(),
 			game_args: self.config.launch.game_args.clone(),
 			min_mem: self.config.launch.min_mem.clone(),
@@ -189,10 +218,24 @@ impl Instance {
 			wrappers: Vec::from_iter(wrapper),
 			quick_play,
 			use_log4j_config: self.config.launch.use_log4j_config,
+			patch_log4j: self.config.launch.patch_log4j,
+			log_file: Some(
+				self.dirs
+					.get()
+					.inst_dir
+					.join("logs")
+					.join("mcvm-latest.log"),
+			),
+			max_log_files: self.config.launch.max_log_files,
+			output_prefix,
+			detach,
+			stream_output: false,
+			args_modifier: Some(self.get_launch_args_modifier(plugins, paths)),
 		};
 		let config = mcvm_core::InstanceConfiguration {
 			side,
 			path: self.dirs.get().game_dir.clone(),
+			id: Some(self.id.to_string()),
 			launch: launch_config,
 			jar_path: self.modification_data.jar_path_override.clone(),
 			main_class: self.modification_data.main_class_override.clone(),
@@ -205,6 +248,48 @@ impl Instance {
 		Ok(inst)
 	}
 
+	/// Get the function that lets plugins modify the launch arguments of this instance
+	fn get_launch_args_modifier(
+		&self,
+		plugins: &PluginManager,
+		paths: &Paths,
+	) -> LaunchArgsModifier {
+		let plugins = plugins.clone();
+		let paths = paths.clone();
+		let id = self.id.to_string();
+		let side = self.get_side();
+
+		LaunchArgsModifier(Arc::new(move |args| {
+			let arg = ModifyLaunchArgsArg {
+				id: id.clone(),
+				side: Some(side),
+				jvm_args: args.jvm_args,
+				game_args: args.game_args,
+			};
+			let arg = plugins
+				.call_hook_chained(
+					ModifyLaunchArgs,
+					arg,
+					|arg, result| {
+						if let Some(jvm_args) = result.jvm_args {
+							arg.jvm_args = jvm_args;
+						}
+						if let Some(game_args) = result.game_args {
+							arg.game_args = game_args;
+						}
+					},
+					&paths,
+					&mut NoOp,
+				)
+				.context("Failed to call modify launch args hook")?;
+
+			Ok(LaunchArgs {
+				jvm_args: arg.jvm_args,
+				game_args: arg.game_args,
+			})
+		}))
+	}
+
 	/// Removes the paper server jar file from a server instance
 	pub fn remove_paper(&mut self, paths: &Paths, paper_file_name: String) -> anyhow::Result<()> {
 		self.ensure_dirs(paths)?;
@@ -291,28 +376,85 @@ pub struct InstanceDirs {
 	pub inst_dir: PathBuf,
 	/// The game directory, such as .minecraft, relative to the instance directory
 	pub game_dir: PathBuf,
+	/// Whether the game directory is a custom one from the config
+	pub is_game_dir_custom: bool,
 }
 
 impl InstanceDirs {
-	/// Create a new InstanceDirs
-	pub fn new(paths: &Paths, instance_id: &str, side: &Side) -> Self {
-		let inst_dir = paths.project.data_dir().join("instances").join(instance_id);
-
-		let game_dir = match side {
-			Side::Client => inst_dir.join(".minecraft"),
-			Side::Server => inst_dir.clone(),
+	/// Create a new InstanceDirs. The game directory override replaces the default game directory
+	pub fn new(
+		paths: &Paths,
+		instance_id: &str,
+		side: &Side,
+		game_dir_override: Option<&Path>,
+	) -> Self {
+		let inst_dir = paths.instances.join(instance_id);
+
+		let game_dir = match (game_dir_override, side) {
+			(Some(game_dir), _) => game_dir.to_path_buf(),
+			(None, Side::Client) => inst_dir.join(".minecraft"),
+			(None, Side::Server) => inst_dir.clone(),
 		};
 
-		Self { inst_dir, game_dir }
+		Self {
+			inst_dir,
+			game_dir,
+			is_game_dir_custom: game_dir_override.is_some(),
+		}
 	}
 
-	/// Make sure the directories exist
+	/// Make sure the directories exist. A custom game directory is not created and has to exist already
 	pub fn ensure_exist(&self) -> anyhow::Result<()> {
 		std::fs::create_dir_all(&self.inst_dir).context("Failed to create instance directory")?;
-		std::fs::create_dir_all(&self.game_dir)
-			.context("Failed to create instance game directory")?;
+		if self.is_game_dir_custom {
+			if !self.game_dir.is_dir() {
+				bail!(
+					"Custom game directory {} does not exist or is not a directory",
+					self.game_dir.display()
+				);
+			}
+		} else {
+			std::fs::create_dir_all(&self.game_dir)
+				.context("Failed to create instance game directory")?;
+		}
 		Ok(())
 	}
+
+	/// Copy the contents of the game directory to another instance, skipping the excluded files.
+	/// This is used to exclude addons so that they can be installed fresh on the other instance
+	pub fn copy_game_dir(
+		&self,
+		dest: &InstanceDirs,
+		exclude: &HashSet<PathBuf>,
+	) -> anyhow::Result<()> {
+		dest.ensure_exist()?;
+		copy_dir_excluding(&self.game_dir, &dest.game_dir, exclude)
+	}
+}
+
+/// Recursively copy a directory, skipping any paths in the exclude set
+pub(crate) fn copy_dir_excluding(
+	src: &Path,
+	dest: &Path,
+	exclude: &HashSet<PathBuf>,
+) -> anyhow::Result<()> {
+	fs::create_dir_all(dest).context("Failed to create destination directory")?;
+	for entry in src.read_dir().context("Failed to read directory")? {
+		let entry = entry?;
+		let src_path = entry.path();
+		if exclude.contains(&src_path) {
+			continue;
+		}
+		let dest_path = dest.join(entry.file_name());
+		if entry.file_type()?.is_dir() {
+			copy_dir_excluding(&src_path, &dest_path, exclude)?;
+		} else {
+			fs::copy(&src_path, &dest_path)
+				.with_context(|| format!("Failed to copy file {}", src_path.to_string_lossy()))?;
+		}
+	}
+
+	Ok(())
 }
 
 /// Things that modifications for an instance change when creating it
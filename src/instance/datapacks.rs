@@ -0,0 +1,111 @@
+use anyhow::{bail, Context};
+use mcvm_net::modrinth::{Loader, ProjectType, Version};
+use mcvm_shared::output::MCVMOutput;
+use reqwest::Client;
+
+use crate::io::lock::{Lockfile, LockfileDatapack};
+use crate::io::paths::Paths;
+
+use super::pack_files::{get_modrinth_project, remove_pack_file, PackFile, PackSource};
+use super::Instance;
+
+/// The name of this kind of pack, used in messages
+const KIND: &str = "datapack";
+
+impl Instance {
+	/// Install a datapack on this instance and record it in the lockfile. Returns the ID
+	/// that the datapack was installed with
+	pub async fn add_datapack(
+		&mut self,
+		source: &PackSource,
+		selected_worlds: &[String],
+		lock: &mut Lockfile,
+		paths: &Paths,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		let dirs = self
+			.get_datapack_dirs(selected_worlds, paths)
+			.context("Failed to get datapack directories")?;
+		if dirs.is_empty() {
+			bail!("There are no worlds to install the datapack to");
+		}
+
+		let pack = match source {
+			PackSource::File(path) => PackFile::read_local(path, KIND)?,
+			PackSource::Modrinth(project_id) => {
+				let Some(minecraft_version) = lock.get_instance_version(&self.id) else {
+					bail!("Instance has not been created yet. Update it before adding datapacks");
+				};
+				let (project, versions) = get_modrinth_project(
+					project_id,
+					|x| {
+						matches!(x.project_type, ProjectType::Datapack)
+							|| x.loaders.iter().any(is_datapack_loader)
+					},
+					KIND,
+					paths,
+					client,
+					o,
+				)
+				.await?;
+				let version = select_version(&versions, minecraft_version).with_context(|| {
+					format!("No version of '{project_id}' supports Minecraft {minecraft_version}")
+				})?;
+				PackFile::download_modrinth(project_id, &project, version, KIND, client).await?
+			}
+		};
+		let id = pack.id.clone();
+
+		// Clear out the old files if this datapack is being replaced
+		if let Some(previous) = lock.remove_datapack(&self.id, &id) {
+			remove_datapack_files(&previous)?;
+		}
+
+		let mut files = Vec::with_capacity(dirs.len());
+		for dir in dirs {
+			let path = pack.write(&dir, KIND)?;
+			files.push(path.to_string_lossy().to_string());
+		}
+
+		let datapack = LockfileDatapack {
+			source: pack.source,
+			version: pack.version,
+			files,
+		};
+		lock.add_datapack(&self.id, &id, datapack);
+
+		Ok(id)
+	}
+
+	/// Remove a datapack that was installed on this instance
+	pub fn remove_datapack(&mut self, id: &str, lock: &mut Lockfile) -> anyhow::Result<()> {
+		let Some(datapack) = lock.remove_datapack(&self.id, id) else {
+			bail!("Datapack '{id}' is not installed on this instance");
+		};
+		remove_datapack_files(&datapack)
+	}
+}
+
+/// Remove the files of an installed datapack
+fn remove_datapack_files(datapack: &LockfileDatapack) -> anyhow::Result<()> {
+	for file in &datapack.files {
+		remove_pack_file(file, KIND)?;
+	}
+
+	Ok(())
+}
+
+/// Checks if a Modrinth loader is the datapack loader
+fn is_datapack_loader(loader: &Loader) -> bool {
+	matches!(loader, Loader::Unknown(loader) if loader == "datapack")
+}
+
+/// Select the newest datapack version that supports a Minecraft version
+fn select_version<'a>(versions: &'a [Version], minecraft_version: &str) -> Option<&'a Version> {
+	versions
+		.iter()
+		.filter(|x| x.game_versions.iter().any(|x| x == minecraft_version))
+		.filter(|x| x.loaders.is_empty() || x.loaders.iter().any(is_datapack_loader))
+		.max_by(|a, b| a.date_published.cmp(&b.date_published))
+}
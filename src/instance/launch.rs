@@ -1,10 +1,15 @@
 use std::collections::HashMap;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
 
-use anyhow::Context;
-use mcvm_core::auth_crate::mc::ClientId;
+use anyhow::{bail, Context};
 use mcvm_core::io::java::args::MemoryNum;
 use mcvm_core::io::java::install::JavaInstallationKind;
+use mcvm_core::io::java::JavaMajorVersion;
+use mcvm_core::net::game_files::mirror::Mirrors;
 use mcvm_core::user::UserManager;
+use mcvm_core::util::versions::MinecraftVersion;
+use mcvm_options::server::ServerProperties;
 use mcvm_plugin::hooks::{
 	HookHandle, InstanceLaunchArg, OnInstanceLaunch, OnInstanceStop, WhileInstanceLaunch,
 };
@@ -16,14 +21,39 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::update::manager::UpdateManager;
-use crate::config::instance::QuickPlay;
+use crate::config::instance::{deserialize_args, QuickPlay};
 use crate::config::plugin::PluginManager;
+use crate::io::lock::Lockfile;
 use crate::io::paths::Paths;
+use crate::io::pid::{get_stop_file_path, is_process_running, read_pidfile, write_pidfile};
 
-use super::Instance;
+use super::{InstKind, Instance};
 
 impl Instance {
-	/// Launch the instance process
+	/// Get the version to launch the instance with. Versions that resolve to the latest one
+	/// are pinned to the version from the last update, so that a newer version is not
+	/// launched without the instance files being updated for it
+	fn get_launch_version(
+		&self,
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<MinecraftVersion> {
+		if !self.config.version.is_latest() {
+			return Ok(self.config.version.clone());
+		}
+
+		let lock = Lockfile::open(paths, o).context("Failed to open lockfile")?;
+		let version = match lock.get_instance_version(&self.id) {
+			Some(version) => MinecraftVersion::Version(version.into()),
+			None => self.config.version.clone(),
+		};
+
+		Ok(version)
+	}
+
+	/// Launch the instance process. This returns as soon as the game has started, without waiting for it
+	/// to exit. Use `InstanceHandle::wait` to block until it exits, or `try_wait` and `kill` to keep track
+	/// of it and stop it, calling `finish` once it has exited
 	pub async fn launch(
 		&mut self,
 		paths: &Paths,
@@ -37,11 +67,26 @@ impl Instance {
 			MessageLevel::Important,
 		);
 
+		if settings.detach {
+			if !matches!(self.kind, InstKind::Server { .. }) {
+				bail!("Only server instances can be launched detached");
+			}
+			if let Some(pid) = read_pidfile(paths, &self.id)? {
+				if is_process_running(pid)? {
+					bail!("Instance is already running with PID {pid}");
+				}
+			}
+		}
+
 		let mut manager = UpdateManager::new(false, true);
-		let client = Client::new();
-		manager.set_version(&self.config.version);
+		let client = settings.client;
+		let version = self
+			.get_launch_version(paths, o)
+			.context("Failed to get the version to launch")?;
+		manager.set_version(&version);
 		manager.add_requirements(self.get_requirements());
-		manager.set_client_id(settings.ms_client_id);
+		manager.set_download_concurrency(settings.download_concurrency);
+		manager.set_mirrors(settings.mirrors);
 		if settings.offline_auth {
 			manager.offline_auth();
 		}
@@ -50,13 +95,31 @@ impl Instance {
 			.await
 			.context("Update failed")?;
 
+		let version = manager.version_info.get().version.clone();
+		let manifest = manager
+			.core
+			.get_mut()
+			.get_version_manifest(o)
+			.await
+			.context("Failed to get version manifest")?;
+		if manifest.manifest.is_snapshot(&version) {
+			o.display(
+				MessageContents::Warning(translate!(
+					o,
+					SnapshotLaunchWarning,
+					"version" = &version
+				)),
+				MessageLevel::Important,
+			);
+		}
+
 		let result = self
 			.create(&mut manager, plugins, paths, users, &client, o)
 			.await
 			.context("Failed to update instance")?;
 		manager.add_result(result);
 
-		let hook_arg = InstanceLaunchArg {
+		let mut hook_arg = InstanceLaunchArg {
 			id: self.id.to_string(),
 			side: Some(self.get_side()),
 			dir: self.dirs.get().inst_dir.to_string_lossy().into(),
@@ -64,6 +127,7 @@ impl Instance {
 			version_info: manager.version_info.get_clone(),
 			custom_config: self.config.plugin_config.clone(),
 			pid: None,
+			exit_code: None,
 		};
 
 		let mut installed_version = manager
@@ -72,7 +136,14 @@ impl Instance {
 			.context("Failed to get core version")?;
 
 		let mut instance = self
-			.create_core_instance(&mut installed_version, paths, o)
+			.create_core_instance(
+				&mut installed_version,
+				paths,
+				plugins,
+				settings.output_prefix,
+				settings.detach,
+				o,
+			)
 			.await
 			.context("Failed to create core instance")?;
 
@@ -89,7 +160,17 @@ impl Instance {
 			.call_hook(OnInstanceLaunch, &hook_arg, paths, o)
 			.context("Failed to call on launch hook")?;
 		for result in results {
-			result.result(o)?;
+			result.result_async(o).await?;
+		}
+
+		// Run the pre-launch command
+		if let Some(command) = &self.config.launch.pre_launch {
+			let status = run_launch_command(command, &hook_arg)
+				.await
+				.context("Failed to run pre-launch command")?;
+			if !status.success() {
+				bail!("Pre-launch command returned a non-zero exit code");
+			}
 		}
 
 		// Launch the instance using core
@@ -98,7 +179,14 @@ impl Instance {
 			.await
 			.context("Failed to launch core instance")?;
 
+		// Record the PID of detached instances so that they can be stopped later
+		if settings.detach {
+			write_pidfile(paths, &self.id, handle.get_pid())
+				.context("Failed to write instance pidfile")?;
+		}
+
 		// Run while_instance_launch hooks alongside
+		hook_arg.pid = Some(handle.get_pid());
 		let hook_handles = plugins
 			.call_hook(WhileInstanceLaunch, &hook_arg, paths, o)
 			.context("Failed to call while launch hook")?;
@@ -106,18 +194,36 @@ impl Instance {
 			inner: handle,
 			hook_handles,
 			hook_arg,
+			post_launch: self.config.launch.post_launch.clone(),
 		};
 
+		// Detached instances outlive this handle, so what to run when they stop is saved
+		// for `finish_detached`
+		if settings.detach {
+			handle
+				.write_stop_file(paths)
+				.context("Failed to write instance stop file")?;
+		}
+
 		Ok(handle)
 	}
 }
 
 /// Settings for launch provided to the instance launch function
 pub struct LaunchSettings {
-	/// The Microsoft client ID to use
-	pub ms_client_id: ClientId,
 	/// Whether to do offline auth
 	pub offline_auth: bool,
+	/// A prefix to put before every line of the game output
+	pub output_prefix: Option<String>,
+	/// Whether to detach the game process so that it keeps running after the launcher exits.
+	/// Only works for servers
+	pub detach: bool,
+	/// The HTTP client to use for downloads
+	pub client: Client,
+	/// The maximum number of files to download at the same time, overriding the default
+	pub download_concurrency: Option<usize>,
+	/// Mirrors to download game files from instead of the official servers
+	pub mirrors: Mirrors,
 }
 
 /// Options for launching after conversion from the deserialized version
@@ -125,6 +231,10 @@ pub struct LaunchSettings {
 pub struct LaunchOptions {
 	/// Java kind
 	pub java: JavaInstallationKind,
+	/// Major Java version override
+	pub java_version: Option<JavaMajorVersion>,
+	/// Java architecture override
+	pub java_arch: Option<String>,
 	/// JVM arguments
 	pub jvm_args: Vec<String>,
 	/// Game arguments
@@ -137,10 +247,22 @@ pub struct LaunchOptions {
 	pub env: HashMap<String, String>,
 	/// Wrapper command
 	pub wrapper: Option<WrapperCommand>,
+	/// Command to run before launching
+	pub pre_launch: Option<String>,
+	/// Command to run after the game exits
+	pub post_launch: Option<String>,
 	/// Quick Play options
 	pub quick_play: QuickPlay,
 	/// Whether or not to use the Log4J configuration
 	pub use_log4j_config: bool,
+	/// Whether to mitigate the Log4Shell vulnerability when launching affected client versions
+	pub patch_log4j: bool,
+	/// The maximum number of old game output logs to keep
+	pub max_log_files: usize,
+	/// Whether to restart a server automatically when it crashes
+	pub restart_on_crash: bool,
+	/// The maximum number of times to restart a crashed server within the restart window
+	pub max_restarts: u32,
 }
 
 /// A wrapper command
@@ -149,7 +271,9 @@ pub struct LaunchOptions {
 pub struct WrapperCommand {
 	/// The command to run
 	pub cmd: String,
-	/// The command's arguments
+	/// The command's arguments, either as a list or a string that is split like a shell would
+	#[serde(deserialize_with = "deserialize_args")]
+	#[cfg_attr(feature = "schema", schemars(with = "crate::config::instance::Args"))]
 	pub args: Vec<String>,
 }
 
@@ -161,48 +285,104 @@ pub struct InstanceHandle {
 	hook_handles: Vec<HookHandle<WhileInstanceLaunch>>,
 	/// Arg to pass to the stop hook when the instance is stopped
 	hook_arg: InstanceLaunchArg,
+	/// Command to run after the game exits
+	post_launch: Option<String>,
 }
 
 impl InstanceHandle {
 	/// Waits for the process to complete
-	pub fn wait(
+	pub async fn wait(
 		mut self,
 		plugins: &PluginManager,
 		paths: &Paths,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<std::process::ExitStatus> {
 		let result = self.inner.wait()?;
-		// Kill any sibling processes now that the main one is complete
-		for handle in self.hook_handles {
-			handle
-				.kill(o)
-				.context("Failed to kill plugin sibling process")?;
+		self.hook_arg.exit_code = result.code();
+		self.finish(plugins, paths, o).await?;
+
+		Ok(result)
+	}
+
+	/// Checks if the process has exited without blocking, returning its exit status if it has.
+	/// Once the process has exited, you must call `finish` to clean up
+	pub fn try_wait(&mut self) -> anyhow::Result<Option<std::process::ExitStatus>> {
+		let status = self
+			.inner
+			.try_wait()
+			.context("Failed to check instance process status")?;
+		if let Some(status) = &status {
+			self.hook_arg.exit_code = status.code();
 		}
 
-		Self::call_stop_hooks(&self.hook_arg, plugins, paths, o)?;
+		Ok(status)
+	}
 
-		Ok(result)
+	/// Waits for the process to complete for at most the given amount of time, returning its
+	/// exit status if it exited in time. Once the process has exited, you must call `finish` to clean up
+	pub fn wait_timeout(&mut self, timeout: Duration) -> anyhow::Result<Option<ExitStatus>> {
+		let status = self
+			.inner
+			.wait_timeout(timeout)
+			.context("Failed to wait for instance process")?;
+		if let Some(status) = &status {
+			self.hook_arg.exit_code = status.code();
+		}
+
+		Ok(status)
+	}
+
+	/// Cleans up after the process has exited, running any stop hooks
+	pub async fn finish(
+		self,
+		plugins: &PluginManager,
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
+		// Kill any sibling processes now that the main one is complete
+		let kill_result = Self::kill_hook_handles(self.hook_handles, o);
+		let stop_result = Self::run_stop_actions(
+			&self.hook_arg,
+			self.post_launch.as_deref(),
+			plugins,
+			paths,
+			o,
+		)
+		.await;
+
+		kill_result.and(stop_result)
 	}
 
-	/// Kills the process early
-	pub fn kill(
+	/// Stops the process early, giving it some time to shut down gracefully first.
+	/// Stop hooks and the post-launch command are still run
+	pub async fn kill(
 		mut self,
 		plugins: &PluginManager,
 		paths: &Paths,
 		o: &mut impl MCVMOutput,
 	) -> anyhow::Result<()> {
-		for handle in self.hook_handles {
-			handle
-				.kill(o)
-				.context("Failed to kill plugin sibling process")?;
-		}
-		self.inner
+		let kill_result = Self::kill_hook_handles(self.hook_handles, o);
+		let status = self
+			.inner
 			.kill()
 			.context("Failed to kill inner instance handle")?;
+		self.hook_arg.exit_code = status.code();
 
-		Self::call_stop_hooks(&self.hook_arg, plugins, paths, o)?;
+		let stop_result = Self::run_stop_actions(
+			&self.hook_arg,
+			self.post_launch.as_deref(),
+			plugins,
+			paths,
+			o,
+		)
+		.await;
 
-		Ok(())
+		kill_result.and(stop_result)
+	}
+
+	/// Gets the process ID of the game
+	pub fn get_pid(&self) -> u32 {
+		self.inner.get_pid()
 	}
 
 	/// Gets the internal child process for the game, consuming the
@@ -211,6 +391,53 @@ impl InstanceHandle {
 		self.inner.get_process()
 	}
 
+	/// Saves the stop hook argument and post-launch command so that they can be run by
+	/// `finish_detached` once a detached instance has stopped
+	fn write_stop_file(&self, paths: &Paths) -> anyhow::Result<()> {
+		let info = DetachedStopInfo {
+			hook_arg: &self.hook_arg,
+			post_launch: self.post_launch.as_deref(),
+		};
+		let path = get_stop_file_path(paths, &self.hook_arg.id);
+		let contents = serde_json::to_string(&info).context("Failed to serialize stop info")?;
+		std::fs::write(path, contents).context("Failed to write stop file")?;
+
+		Ok(())
+	}
+
+	/// Kills the processes of hooks running alongside the instance. All of them are killed
+	/// even if one fails, and the first error is returned
+	fn kill_hook_handles(
+		handles: Vec<HookHandle<WhileInstanceLaunch>>,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
+		let mut result = Ok(());
+		for handle in handles {
+			let kill_result = handle
+				.kill(o)
+				.map(|_| ())
+				.context("Failed to kill plugin sibling process");
+			result = result.and(kill_result);
+		}
+
+		result
+	}
+
+	/// Calls the stop hooks and then runs the post-launch command. The post-launch command
+	/// is run even if the stop hooks fail, and their error is returned afterwards
+	async fn run_stop_actions(
+		arg: &InstanceLaunchArg,
+		post_launch: Option<&str>,
+		plugins: &PluginManager,
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
+		let hook_result = Self::call_stop_hooks(arg, plugins, paths, o);
+		Self::run_post_launch(post_launch, arg, o).await?;
+
+		hook_result
+	}
+
 	/// Calls on stop hooks
 	fn call_stop_hooks(
 		arg: &InstanceLaunchArg,
@@ -226,4 +453,274 @@ impl InstanceHandle {
 		}
 		Ok(())
 	}
+
+	/// Runs the post-launch command, if there is one
+	async fn run_post_launch(
+		command: Option<&str>,
+		arg: &InstanceLaunchArg,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<()> {
+		if let Some(command) = command {
+			let status = run_launch_command(command, arg)
+				.await
+				.context("Failed to run post-launch command")?;
+			if !status.success() {
+				o.display(
+					MessageContents::Warning(
+						"Post-launch command returned a non-zero exit code".into(),
+					),
+					MessageLevel::Important,
+				);
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Runs the stop hooks and post-launch command of a detached instance after its process has
+/// stopped, since the launcher that started it was not around to do so
+pub async fn finish_detached(
+	instance_id: &str,
+	plugins: &PluginManager,
+	paths: &Paths,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<()> {
+	let path = get_stop_file_path(paths, instance_id);
+	if !path.exists() {
+		return Ok(());
+	}
+	let contents = std::fs::read_to_string(&path).context("Failed to read stop file")?;
+	std::fs::remove_file(&path).context("Failed to remove stop file")?;
+	let info: DetachedStopInfoDeser =
+		serde_json::from_str(&contents).context("Failed to deserialize stop info")?;
+
+	InstanceHandle::run_stop_actions(
+		&info.hook_arg,
+		info.post_launch.as_deref(),
+		plugins,
+		paths,
+		o,
+	)
+	.await
+}
+
+/// What to run when a detached instance stops
+#[derive(Serialize)]
+struct DetachedStopInfo<'a> {
+	hook_arg: &'a InstanceLaunchArg,
+	post_launch: Option<&'a str>,
+}
+
+/// Deserialized version of DetachedStopInfo
+#[derive(Deserialize)]
+struct DetachedStopInfoDeser {
+	hook_arg: InstanceLaunchArg,
+	post_launch: Option<String>,
+}
+
+/// The default maximum number of times to restart a crashed server within the restart window
+pub const DEFAULT_MAX_RESTARTS: u32 = 3;
+/// The period of time that restarts of a crashed server are counted in
+const RESTART_WINDOW: Duration = Duration::from_secs(10 * 60);
+/// The delay before the first restart of a crashed server, which doubles with every restart in the window
+const RESTART_BASE_DELAY: Duration = Duration::from_secs(5);
+/// The maximum delay before restarting a crashed server
+const RESTART_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Decides when to restart a server instance that has crashed
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+	/// The maximum number of restarts within the restart window
+	max_restarts: u32,
+	/// The times of the recent restarts
+	restarts: Vec<Instant>,
+}
+
+impl RestartPolicy {
+	/// Create a new RestartPolicy
+	pub fn new(max_restarts: u32) -> Self {
+		Self {
+			max_restarts,
+			restarts: Vec::new(),
+		}
+	}
+
+	/// Record a crash that happened at the given time. Returns how long to wait before restarting,
+	/// or None if the server has been restarted too many times recently and should stay stopped
+	pub fn on_crash(&mut self, now: Instant) -> Option<Duration> {
+		self.restarts
+			.retain(|x| now.saturating_duration_since(*x) < RESTART_WINDOW);
+		if self.restarts.len() >= self.max_restarts as usize {
+			return None;
+		}
+
+		let delay = RESTART_BASE_DELAY
+			.saturating_mul(2u32.saturating_pow(self.restarts.len() as u32))
+			.min(RESTART_MAX_DELAY);
+		self.restarts.push(now);
+
+		Some(delay)
+	}
+
+	/// Get the number of restarts within the restart window, including the one that was just scheduled
+	pub fn get_restart_count(&self) -> usize {
+		self.restarts.len()
+	}
+
+	/// Get the maximum number of restarts within the restart window
+	pub fn get_max_restarts(&self) -> u32 {
+		self.max_restarts
+	}
+}
+
+impl Instance {
+	/// Get the policy for restarting this instance when it crashes,
+	/// if it is a server that has restarts enabled
+	pub fn get_restart_policy(&self) -> Option<RestartPolicy> {
+		if matches!(self.kind, InstKind::Server { .. }) && self.config.launch.restart_on_crash {
+			Some(RestartPolicy::new(self.config.launch.max_restarts))
+		} else {
+			None
+		}
+	}
+
+	/// Check whether this instance is a server in online mode, which only lets in players that
+	/// are signed in. Configured properties take precedence over the server.properties file,
+	/// and servers are in online mode by default. Returns None if this is not a server
+	pub fn is_online_mode(&self) -> Option<bool> {
+		let InstKind::Server { properties, .. } = &self.kind else {
+			return None;
+		};
+		if let Some(value) = properties.get("online-mode") {
+			return Some(value.trim() != "false");
+		}
+
+		let file = self.dirs.is_full().then(|| {
+			ServerProperties::open(&self.dirs.get().game_dir.join("server.properties")).ok()
+		});
+		let value = file
+			.flatten()
+			.and_then(|file| file.get("online-mode"))
+			.map(|value| value.trim() != "false");
+
+		Some(value.unwrap_or(true))
+	}
+}
+
+/// Whether the exit status of the game process means that it crashed. Processes that were
+/// stopped on purpose, like from Ctrl+C or `instance stop`, did not crash
+pub fn is_crash(status: &ExitStatus) -> bool {
+	#[cfg(target_family = "unix")]
+	{
+		use std::os::unix::process::ExitStatusExt;
+		if let Some(signal) = status.signal() {
+			return !STOP_SIGNALS.contains(&signal);
+		}
+	}
+
+	match status.code() {
+		Some(code) => code != 0 && !STOP_EXIT_CODES.contains(&code),
+		None => false,
+	}
+}
+
+/// Signals that are sent to stop the process on purpose: SIGHUP, SIGINT, and SIGTERM
+#[cfg(target_family = "unix")]
+const STOP_SIGNALS: [i32; 3] = [1, 2, 15];
+
+/// Exit codes that Java uses when it shuts down after being sent SIGINT or SIGTERM
+const STOP_EXIT_CODES: [i32; 2] = [130, 143];
+
+/// Runs a pre-launch or post-launch command in the shell from the instance directory
+async fn run_launch_command(command: &str, arg: &InstanceLaunchArg) -> anyhow::Result<ExitStatus> {
+	#[cfg(target_family = "windows")]
+	let mut cmd = {
+		let mut cmd = tokio::process::Command::new("cmd");
+		cmd.arg("/C");
+		cmd
+	};
+	#[cfg(not(target_family = "windows"))]
+	let mut cmd = {
+		let mut cmd = tokio::process::Command::new("sh");
+		cmd.arg("-c");
+		cmd
+	};
+	cmd.arg(command);
+	cmd.current_dir(&arg.dir);
+	cmd.env("MCVM_INSTANCE_ID", &arg.id);
+	cmd.env("MCVM_GAME_DIR", &arg.game_dir);
+
+	let status = cmd.status().await.context("Failed to run command")?;
+
+	Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(not(target_family = "windows"))]
+	#[test]
+	fn test_run_launch_command() {
+		let dir = std::env::temp_dir().join("mcvm_test_run_launch_command");
+		std::fs::create_dir_all(&dir).unwrap();
+		let arg = InstanceLaunchArg {
+			id: "foo".into(),
+			dir: dir.to_string_lossy().to_string(),
+			game_dir: "/game".into(),
+			..Default::default()
+		};
+
+		let runtime = tokio::runtime::Runtime::new().unwrap();
+		let status = runtime
+			.block_on(run_launch_command(
+				"test \"$MCVM_INSTANCE_ID\" = foo && test \"$MCVM_GAME_DIR\" = /game && touch ran",
+				&arg,
+			))
+			.unwrap();
+		assert!(status.success());
+		assert!(dir.join("ran").exists());
+
+		let status = runtime
+			.block_on(run_launch_command("exit 3", &arg))
+			.unwrap();
+		assert_eq!(status.code(), Some(3));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_restart_policy() {
+		let mut policy = RestartPolicy::new(3);
+		let start = Instant::now();
+		assert_eq!(policy.on_crash(start), Some(Duration::from_secs(5)));
+		assert_eq!(policy.on_crash(start), Some(Duration::from_secs(10)));
+		assert_eq!(policy.on_crash(start), Some(Duration::from_secs(20)));
+		assert_eq!(policy.on_crash(start), None);
+
+		// Restarts outside of the window don't count anymore
+		let later = start + RESTART_WINDOW;
+		assert_eq!(policy.on_crash(later), Some(Duration::from_secs(5)));
+		assert_eq!(policy.get_restart_count(), 1);
+	}
+
+	#[test]
+	#[cfg(target_family = "unix")]
+	fn test_is_crash() {
+		use std::os::unix::process::ExitStatusExt;
+
+		assert!(!is_crash(&ExitStatus::from_raw(0)));
+		assert!(is_crash(&ExitStatus::from_raw(1 << 8)));
+		assert!(!is_crash(&ExitStatus::from_raw(130 << 8)));
+		assert!(!is_crash(&ExitStatus::from_raw(143 << 8)));
+		// Stopped by SIGHUP, SIGINT, and SIGTERM
+		assert!(!is_crash(&ExitStatus::from_raw(1)));
+		assert!(!is_crash(&ExitStatus::from_raw(2)));
+		assert!(!is_crash(&ExitStatus::from_raw(15)));
+		// Killed by SIGABRT, SIGKILL, and SIGSEGV
+		assert!(is_crash(&ExitStatus::from_raw(6)));
+		assert!(is_crash(&ExitStatus::from_raw(9)));
+		assert!(is_crash(&ExitStatus::from_raw(11)));
+	}
 }
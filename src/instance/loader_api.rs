@@ -0,0 +1,211 @@
+use std::fmt::Display;
+
+use mcvm_net::modrinth::{self, KnownLoader, Loader, Version};
+use mcvm_shared::modifications::Modloader;
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::translate;
+use reqwest::Client;
+
+use crate::config::package::{PackageConfig, PackageConfigSource};
+use crate::io::paths::Paths;
+
+use super::pack_files::get_modrinth_cache_dir;
+use super::Instance;
+
+/// A library mod that almost every mod for a loader depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoaderApi {
+	/// The Fabric API
+	FabricApi,
+	/// The Quilt Standard Libraries
+	Qsl,
+}
+
+impl LoaderApi {
+	/// Get the API that goes with a modloader, if it has one
+	fn from_modloader(modloader: &Modloader) -> Option<Self> {
+		match modloader {
+			Modloader::Fabric => Some(Self::FabricApi),
+			Modloader::Quilt => Some(Self::Qsl),
+			_ => None,
+		}
+	}
+
+	/// Get the ID of the Modrinth project for this API
+	fn modrinth_project(&self) -> &'static str {
+		match self {
+			Self::FabricApi => "fabric-api",
+			Self::Qsl => "qsl",
+		}
+	}
+
+	/// Checks if a version of this API supports a Minecraft version
+	fn supports(&self, version: &Version, mc_version: &str) -> bool {
+		version.game_versions.iter().any(|x| x == mc_version)
+			&& version.loaders.iter().any(|x| {
+				matches!(
+					(self, x),
+					(Self::FabricApi, Loader::Known(KnownLoader::Fabric))
+						| (Self::Qsl, Loader::Known(KnownLoader::Quilt))
+				)
+			})
+	}
+}
+
+impl Display for LoaderApi {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{}",
+			match self {
+				Self::FabricApi => "Fabric API",
+				Self::Qsl => "QSL",
+			}
+		)
+	}
+}
+
+impl Instance {
+	/// Check that the Fabric API or QSL requested by the include_loader_api option supports the
+	/// Minecraft version. If it doesn't, a warning is shown and the API isn't requested for this
+	/// update, instead of failing to resolve the packages of the instance
+	pub async fn check_loader_api(
+		&mut self,
+		mc_version: &str,
+		paths: &Paths,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) {
+		let is_requested = self
+			.config
+			.packages
+			.iter()
+			.any(|x| x.source == PackageConfigSource::LoaderApi);
+		let modloader = self.config.modifications.get_modloader(self.get_side());
+		let Some(api) = LoaderApi::from_modloader(&modloader).filter(|_| is_requested) else {
+			return;
+		};
+
+		let cache_dir = get_modrinth_cache_dir(paths);
+		match modrinth::get_project_versions_cached(api.modrinth_project(), &cache_dir, client, o)
+			.await
+		{
+			Ok(versions) => {
+				remove_unsupported_loader_api(
+					&mut self.config.packages,
+					api,
+					&versions,
+					mc_version,
+					o,
+				);
+			}
+			// Leave the package to be resolved normally, which will report any real problem
+			Err(e) => o.display(
+				MessageContents::Error(format!("{e:?}")),
+				MessageLevel::Debug,
+			),
+		}
+	}
+}
+
+/// Remove the implicitly requested loader API package and warn if no version of the API
+/// supports the Minecraft version. Returns whether it was removed
+fn remove_unsupported_loader_api(
+	packages: &mut Vec<PackageConfig>,
+	api: LoaderApi,
+	versions: &[Version],
+	mc_version: &str,
+	o: &mut impl MCVMOutput,
+) -> bool {
+	if versions.iter().any(|x| api.supports(x, mc_version)) {
+		return false;
+	}
+
+	o.display(
+		MessageContents::Warning(translate!(
+			o,
+			NoCompatibleLoaderApi,
+			"api" = &api.to_string(),
+			"version" = mc_version
+		)),
+		MessageLevel::Important,
+	);
+	packages.retain(|x| x.source != PackageConfigSource::LoaderApi);
+
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use mcvm_shared::output::Message;
+	use mcvm_shared::pkg::PackageID;
+
+	use super::*;
+
+	#[derive(Default)]
+	struct WarningCounter(usize);
+
+	impl MCVMOutput for WarningCounter {
+		fn display_text(&mut self, _: String, _: MessageLevel) {}
+
+		fn display_message(&mut self, message: Message) {
+			if let MessageContents::Warning(..) = message.contents {
+				self.0 += 1;
+			}
+		}
+	}
+
+	fn create_version(loader: &str, game_version: &str) -> Version {
+		serde_json::from_value(serde_json::json!({
+			"id": "abc",
+			"project_id": "P7dR8mSH",
+			"name": "Fabric API",
+			"version_number": "0.92.0",
+			"version_type": "release",
+			"loaders": [loader],
+			"files": [],
+			"game_versions": [game_version],
+			"dependencies": [],
+			"featured": false,
+			"date_published": "2024-01-01T00:00:00Z"
+		}))
+		.unwrap()
+	}
+
+	fn create_packages() -> Vec<PackageConfig> {
+		let mut api = PackageConfig::from_id(PackageID::from("fabriclike-api"));
+		api.source = PackageConfigSource::LoaderApi;
+		vec![PackageConfig::from_id(PackageID::from("sodium")), api]
+	}
+
+	#[test]
+	fn test_no_compatible_loader_api() {
+		let versions = [
+			create_version("fabric", "1.20.1"),
+			create_version("quilt", "1.20.4"),
+		];
+
+		let mut packages = create_packages();
+		let mut o = WarningCounter::default();
+		assert!(!remove_unsupported_loader_api(
+			&mut packages,
+			LoaderApi::FabricApi,
+			&versions,
+			"1.20.1",
+			&mut o
+		));
+		assert_eq!(packages.len(), 2);
+		assert_eq!(o.0, 0);
+
+		assert!(remove_unsupported_loader_api(
+			&mut packages,
+			LoaderApi::FabricApi,
+			&versions,
+			"1.20.4",
+			&mut o
+		));
+		assert_eq!(packages.len(), 1);
+		assert_eq!(packages[0].id, PackageID::from("sodium"));
+		assert_eq!(o.0, 1);
+	}
+}
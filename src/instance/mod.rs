@@ -2,15 +2,30 @@
 mod addons;
 /// Creation of instance contents
 pub mod create;
+/// Installing datapacks on an instance
+pub mod datapacks;
 /// Launching an instance
 pub mod launch;
+/// Checking the Fabric API or QSL that is requested for an instance
+mod loader_api;
+/// Installing pack files from Modrinth or the local filesystem
+pub mod pack_files;
 /// Managing and installing packages on an instance
 pub mod packages;
+/// Installing resource packs on an instance
+pub mod resource_packs;
+/// Installing shader packs on an instance
+pub mod shader_packs;
+/// Snapshots of instance files
+pub mod snapshot;
 /// Import and export of instances to other formats
 pub mod transfer;
 /// Updating an instance
 pub mod update;
 
+use std::collections::HashMap;
+use std::path::PathBuf;
+
 use mcvm_core::util::versions::MinecraftVersion;
 use mcvm_shared::later::Later;
 use mcvm_shared::pkg::PackageStability;
@@ -51,6 +66,8 @@ pub enum InstKind {
 	Server {
 		/// The new world name if it is changed by the options
 		world_name: Option<String>,
+		/// Properties to set in server.properties over the generated ones
+		properties: HashMap<String, String>,
 	},
 }
 
@@ -61,8 +78,11 @@ impl InstKind {
 	}
 
 	/// Create a new server InstKind
-	pub fn server() -> Self {
-		Self::Server { world_name: None }
+	pub fn server(properties: HashMap<String, String>) -> Self {
+		Self::Server {
+			world_name: properties.get("level-name").cloned(),
+			properties,
+		}
 	}
 
 	/// Convert to the Side enum
@@ -87,6 +107,10 @@ pub struct InstanceStoredConfig {
 	pub launch: LaunchOptions,
 	/// The instance's global datapack folder
 	pub datapack_folder: Option<String>,
+	/// A custom game directory that is used instead of the one inside the instance directory
+	pub game_dir_override: Option<PathBuf>,
+	/// Configuration for snapshots of the instance
+	pub snapshots: snapshot::Config,
 	/// The packages on the instance, consolidated from all parent sources
 	pub packages: Vec<PackageConfig>,
 	/// Default stability for packages
@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use mcvm_core::net::download;
+use mcvm_net::modrinth::{self, Project, Version};
+use mcvm_shared::output::MCVMOutput;
+use reqwest::Client;
+
+use crate::io::paths::Paths;
+
+/// Where to install a datapack, resource pack, or shader pack from
+#[derive(Debug, PartialEq)]
+pub enum PackSource {
+	/// A project on Modrinth, by ID or slug
+	Modrinth(String),
+	/// A pack zip file on the local filesystem
+	File(PathBuf),
+}
+
+impl PackSource {
+	/// Parse a pack source. Existing paths are treated as local files and
+	/// anything else is treated as a Modrinth project
+	pub fn parse(string: &str) -> anyhow::Result<Self> {
+		let path = PathBuf::from(string);
+		if path.exists() {
+			if !path.is_file() {
+				bail!("Folders are not supported. Please zip the pack first");
+			}
+			Ok(Self::File(path))
+		} else if string.ends_with(".zip") {
+			bail!("Pack file '{string}' does not exist");
+		} else {
+			Ok(Self::Modrinth(string.into()))
+		}
+	}
+}
+
+/// A pack that has been read or downloaded and is ready to be installed
+pub(super) struct PackFile {
+	/// The ID that the pack is installed with
+	pub id: String,
+	/// The name of the file to install the pack as
+	pub file_name: String,
+	/// The contents of the pack file
+	pub contents: Vec<u8>,
+	/// Where the pack came from, as recorded in the lockfile
+	pub source: String,
+	/// The version of the pack, if it came from Modrinth
+	pub version: Option<String>,
+}
+
+impl PackFile {
+	/// Read a pack from a file on the local filesystem
+	pub fn read_local(path: &Path, kind: &str) -> anyhow::Result<Self> {
+		let id = path
+			.file_stem()
+			.with_context(|| format!("Path to the {kind} has no file name"))?
+			.to_string_lossy()
+			.to_string();
+		let file_name = path
+			.file_name()
+			.with_context(|| format!("Path to the {kind} has no file name"))?
+			.to_string_lossy()
+			.to_string();
+		let contents =
+			std::fs::read(path).with_context(|| format!("Failed to read {kind} file"))?;
+		let path = path.canonicalize().unwrap_or(path.into());
+
+		Ok(Self {
+			id,
+			file_name,
+			contents,
+			source: path.to_string_lossy().to_string(),
+			version: None,
+		})
+	}
+
+	/// Download a version of a Modrinth project
+	pub async fn download_modrinth(
+		project_id: &str,
+		project: &Project,
+		version: &Version,
+		kind: &str,
+		client: &Client,
+	) -> anyhow::Result<Self> {
+		let download = version.get_primary_download()?;
+		let contents = download::bytes(&download.url, client)
+			.await
+			.with_context(|| format!("Failed to download {kind}"))?;
+
+		Ok(Self {
+			id: project_id.into(),
+			file_name: download.filename.clone(),
+			contents: contents.to_vec(),
+			source: format!("modrinth:{}", project.id),
+			version: Some(version.version_number.clone()),
+		})
+	}
+
+	/// Write the pack file into a directory, returning the path it was written to
+	pub fn write(&self, dir: &Path, kind: &str) -> anyhow::Result<PathBuf> {
+		std::fs::create_dir_all(dir)
+			.with_context(|| format!("Failed to create {kind} directory"))?;
+		let path = dir.join(&self.file_name);
+		std::fs::write(&path, &self.contents)
+			.with_context(|| format!("Failed to write {kind} file"))?;
+
+		Ok(path)
+	}
+}
+
+/// Get a Modrinth project and all of its versions, checking that it is the right kind of pack.
+/// Responses are cached so that they only need to be downloaded again when they change
+pub(super) async fn get_modrinth_project(
+	project_id: &str,
+	is_kind: impl FnOnce(&Project) -> bool,
+	kind: &str,
+	paths: &Paths,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<(Project, Vec<Version>)> {
+	let cache_dir = get_modrinth_cache_dir(paths);
+	let project = modrinth::get_project_cached(project_id, &cache_dir, client, o).await?;
+	if !is_kind(&project) {
+		bail!("Modrinth project '{project_id}' is not a {kind}");
+	}
+
+	let versions = modrinth::get_project_versions_cached(&project.id, &cache_dir, client, o)
+		.await
+		.context("Failed to get project versions")?;
+
+	Ok((project, versions))
+}
+
+/// Get the directory where responses from the Modrinth API are cached
+pub(super) fn get_modrinth_cache_dir(paths: &Paths) -> PathBuf {
+	paths.core.api_cache.join("modrinth")
+}
+
+/// Remove an installed pack file, if it still exists
+pub(super) fn remove_pack_file(file: &str, kind: &str) -> anyhow::Result<()> {
+	let path = PathBuf::from(file);
+	if path.is_file() {
+		std::fs::remove_file(path).with_context(|| format!("Failed to remove {kind} file"))?;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_pack_source_parse() {
+		assert_eq!(
+			PackSource::parse("terralith").unwrap(),
+			PackSource::Modrinth("terralith".into())
+		);
+		assert!(PackSource::parse("does_not_exist.zip").is_err());
+	}
+}
@@ -1,16 +1,18 @@
 use anyhow::{bail, Context};
+use mcvm_shared::addon::Addon;
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
-use mcvm_shared::pkg::ArcPkgReq;
+use mcvm_shared::pkg::{ArcPkgReq, PackageAddonOptionalHashes};
 use mcvm_shared::translate;
 use mcvm_shared::versions::VersionInfo;
 use reqwest::Client;
 
-use crate::addon::AddonExt;
+use crate::addon::{AddonExt, AddonLocation, AddonRequest};
 use crate::config::plugin::PluginManager;
-use crate::io::lock::{Lockfile, LockfileAddon};
+use crate::io::lock::{HashedFileInfo, Lockfile, LockfileAddon};
 use crate::io::paths::Paths;
 use crate::pkg::eval::{EvalData, EvalInput, Routine};
 use crate::pkg::reg::PkgRegistry;
+use crate::util::hash::hash_file_sha256;
 
 use super::Instance;
 use crate::config::package::PackageConfig;
@@ -70,22 +72,48 @@ impl Instance {
 		EvalData<'a>,
 		HashMap<String, impl Future<Output = anyhow::Result<()>> + Send + 'static>,
 	)> {
+		let (eval, requests) = self
+			.get_package_addon_requests(pkg, eval_input, reg, paths, force, client, plugins, o)
+			.await?;
+
+		let mut tasks = HashMap::new();
+		for addon in requests {
+			let task = addon
+				.get_acquire_task(paths, &self.id, client)
+				.context("Failed to get task for acquiring addon")?;
+			tasks.insert(addon.get_unique_id(&self.id), task);
+		}
+
+		Ok((eval, tasks))
+	}
+
+	/// Evaluates a package and gets the addons that need to be acquired,
+	/// without acquiring them or changing any files
+	#[allow(clippy::too_many_arguments)]
+	pub async fn get_package_addon_requests<'a>(
+		&mut self,
+		pkg: &ArcPkgReq,
+		eval_input: EvalInput<'a>,
+		reg: &mut PkgRegistry,
+		paths: &'a Paths,
+		force: bool,
+		client: &Client,
+		plugins: &'a PluginManager,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<(EvalData<'a>, Vec<AddonRequest>)> {
 		let eval = reg
 			.eval(pkg, paths, Routine::Install, eval_input, client, plugins, o)
 			.await
 			.context("Failed to evaluate package")?;
 
-		let mut tasks = HashMap::new();
-		for addon in eval.addon_reqs.iter() {
-			if addon.addon.should_update(paths, &self.id) || force {
-				let task = addon
-					.get_acquire_task(paths, &self.id, client)
-					.context("Failed to get task for acquiring addon")?;
-				tasks.insert(addon.get_unique_id(&self.id), task);
-			}
-		}
+		let requests = eval
+			.addon_reqs
+			.iter()
+			.filter(|x| x.addon.should_update(paths, &self.id) || force)
+			.cloned()
+			.collect();
 
-		Ok((eval, tasks))
+		Ok((eval, requests))
 	}
 
 	/// Install the EvalData resulting from evaluating a package onto this instance
@@ -119,13 +147,25 @@ impl Instance {
 			.addon_reqs
 			.iter()
 			.map(|x| {
-				Ok(LockfileAddon::from_addon(
+				let mut addon = LockfileAddon::from_addon(
 					&x.addon,
 					self.get_linked_addon_paths(&x.addon, &pkg_config.worlds, paths, version_info)?
 						.iter()
 						.map(|y| y.join(x.addon.file_name.clone()))
 						.collect(),
-				))
+				);
+				if let Some((hashes, file)) = self
+					.get_computed_addon_hashes(&x.addon, &pkg.id, paths, lock)
+					.with_context(|| format!("Failed to verify addon '{}'", x.addon.id))?
+				{
+					addon.set_hashes(hashes);
+					addon.set_hashed_file(file);
+				}
+				if let AddonLocation::Remote(url) = x.get_location() {
+					addon.set_url(url.clone());
+				}
+
+				Ok(addon)
 			})
 			.collect::<anyhow::Result<Vec<LockfileAddon>>>()
 			.context("Failed to convert addons to the lockfile format")?;
@@ -147,6 +187,51 @@ impl Instance {
 		Ok(())
 	}
 
+	/// Computes hashes for an addon that does not declare any so that they can be stored in the lockfile.
+	/// If the same version of the addon already has a stored hash, the new file is validated against it
+	/// and removed if it does not match. Files that have not changed since they were last hashed reuse the stored hash
+	fn get_computed_addon_hashes(
+		&self,
+		addon: &Addon,
+		pkg_id: &str,
+		paths: &Paths,
+		lock: &Lockfile,
+	) -> anyhow::Result<Option<(PackageAddonOptionalHashes, HashedFileInfo)>> {
+		if !addon.hashes.is_empty() {
+			return Ok(None);
+		}
+		let path = addon.get_path(paths, &self.id);
+		if !path.exists() {
+			return Ok(None);
+		}
+
+		let file = HashedFileInfo::from_file(&path)?;
+		let previous = lock
+			.get_addon(&self.id, pkg_id, &addon.id)
+			.filter(|x| addon.version.is_some() && x.get_version() == addon.version.as_deref());
+
+		let hash = if let Some(hash) = previous.and_then(|x| x.get_unchanged_sha256(&file)) {
+			hash.to_string()
+		} else {
+			let hash = hash_file_sha256(&path).context("Failed to hash addon file")?;
+			if let Some(previous_hash) = previous.and_then(|x| x.get_hashes().sha256.as_ref()) {
+				if previous_hash != &hash {
+					std::fs::remove_file(&path).context("Failed to remove addon file")?;
+					bail!("Checksum for addon file does not match the one stored in the lockfile");
+				}
+			}
+			hash
+		};
+
+		Ok(Some((
+			PackageAddonOptionalHashes {
+				sha256: Some(hash),
+				sha512: None,
+			},
+			file,
+		)))
+	}
+
 	/// Gets all of the configured packages for this instance
 	pub fn get_configured_packages(&self) -> &Vec<PackageConfig> {
 		&self.config.packages
@@ -0,0 +1,336 @@
+use std::io::{Cursor, Read};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use mcvm_net::modrinth::{ProjectType, Version};
+use mcvm_options::client::{read_options_txt, write_options_txt};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::io::lock::{Lockfile, LockfileResourcePack};
+use crate::io::paths::Paths;
+
+use super::pack_files::{get_modrinth_project, remove_pack_file, PackFile, PackSource};
+use super::{InstKind, Instance};
+
+/// The name of this kind of pack, used in messages
+const KIND: &str = "resource pack";
+
+/// The key in the options.txt for the list of enabled resource packs
+const RESOURCE_PACKS_KEY: &str = "resourcePacks";
+
+impl Instance {
+	/// Install a resource pack on this instance and record it in the lockfile. If enable is set,
+	/// the resource pack will also be added to the enabled packs in the options.txt.
+	/// Returns the ID that the resource pack was installed with
+	pub async fn add_resource_pack(
+		&mut self,
+		source: &PackSource,
+		enable: bool,
+		lock: &mut Lockfile,
+		paths: &Paths,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		if !matches!(self.kind, InstKind::Client { .. }) {
+			bail!("Resource packs can only be added to client instances");
+		}
+		let minecraft_version = lock.get_instance_version(&self.id).map(String::from);
+
+		let pack = match source {
+			PackSource::File(path) => PackFile::read_local(path, KIND)?,
+			PackSource::Modrinth(project_id) => {
+				let Some(minecraft_version) = &minecraft_version else {
+					bail!(
+						"Instance has not been created yet. Update it before adding resource packs"
+					);
+				};
+				let (project, versions) = get_modrinth_project(
+					project_id,
+					|x| matches!(x.project_type, ProjectType::ResourcePack),
+					KIND,
+					paths,
+					client,
+					o,
+				)
+				.await?;
+				let version = select_version(&versions, minecraft_version).with_context(|| {
+					format!("No version of '{project_id}' supports Minecraft {minecraft_version}")
+				})?;
+				PackFile::download_modrinth(project_id, &project, version, KIND, client).await?
+			}
+		};
+		let id = pack.id.clone();
+
+		if let Some(minecraft_version) = &minecraft_version {
+			check_pack_format(&pack.contents, minecraft_version, &id, o);
+		}
+
+		self.ensure_dirs(paths)?;
+		let game_dir = self.dirs.get().game_dir.clone();
+
+		// Clear out the old file if this resource pack is being replaced
+		if let Some(previous) = lock.remove_resource_pack(&self.id, &id) {
+			remove_resource_pack_file(&previous, &game_dir)?;
+		}
+
+		let path = pack.write(&game_dir.join("resourcepacks"), KIND)?;
+
+		if enable {
+			update_enabled_packs(&game_dir, |packs| {
+				let name = get_options_pack_name(&pack.file_name);
+				if !packs.contains(&name) {
+					packs.push(name);
+				}
+			})
+			.context("Failed to enable resource pack in options.txt")?;
+		}
+
+		let resource_pack = LockfileResourcePack {
+			source: pack.source,
+			version: pack.version,
+			file: path.to_string_lossy().to_string(),
+			enabled: enable,
+		};
+		lock.add_resource_pack(&self.id, &id, resource_pack);
+
+		Ok(id)
+	}
+
+	/// Remove a resource pack that was installed on this instance
+	pub fn remove_resource_pack(
+		&mut self,
+		id: &str,
+		lock: &mut Lockfile,
+		paths: &Paths,
+	) -> anyhow::Result<()> {
+		let Some(resource_pack) = lock.remove_resource_pack(&self.id, id) else {
+			bail!("Resource pack '{id}' is not installed on this instance");
+		};
+		self.ensure_dirs(paths)?;
+		remove_resource_pack_file(&resource_pack, &self.dirs.get().game_dir)
+	}
+}
+
+/// Remove the file of an installed resource pack, and disable it in the options.txt
+/// if it was enabled when it was installed
+fn remove_resource_pack_file(
+	resource_pack: &LockfileResourcePack,
+	game_dir: &Path,
+) -> anyhow::Result<()> {
+	remove_pack_file(&resource_pack.file, KIND)?;
+
+	if resource_pack.enabled {
+		if let Some(file_name) = Path::new(&resource_pack.file).file_name() {
+			let name = get_options_pack_name(&file_name.to_string_lossy());
+			update_enabled_packs(game_dir, |packs| packs.retain(|x| x != &name))
+				.context("Failed to disable resource pack in options.txt")?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Get the name that the game uses for a resource pack file in the options.txt
+fn get_options_pack_name(file_name: &str) -> String {
+	format!("file/{file_name}")
+}
+
+/// Modify the list of enabled resource packs in the options.txt of a game directory
+fn update_enabled_packs(game_dir: &Path, f: impl FnOnce(&mut Vec<String>)) -> anyhow::Result<()> {
+	let path = game_dir.join("options.txt");
+	let options = read_options_txt(&path)?;
+	let mut packs = match options.get(RESOURCE_PACKS_KEY) {
+		Some(value) => serde_json::from_str(value).context("Failed to parse resource pack list")?,
+		None => vec!["vanilla".to_string()],
+	};
+	f(&mut packs);
+
+	let value = serde_json::to_string(&packs).context("Failed to serialize resource pack list")?;
+	let keys = [(RESOURCE_PACKS_KEY.to_string(), value)].into();
+	write_options_txt(keys, &path, &None)
+}
+
+/// Warn if a resource pack doesn't support the pack format of a Minecraft version
+fn check_pack_format(contents: &[u8], minecraft_version: &str, id: &str, o: &mut impl MCVMOutput) {
+	let Some(expected) = get_pack_format(minecraft_version) else {
+		return;
+	};
+	let supported = match read_supported_formats(contents) {
+		Ok(supported) => supported,
+		Err(e) => {
+			o.display(
+				MessageContents::Warning(format!(
+					"Could not check the pack format of resource pack '{id}': {e:#}"
+				)),
+				MessageLevel::Important,
+			);
+			return;
+		}
+	};
+
+	if !supported.contains(&expected) {
+		o.display(
+			MessageContents::Warning(format!(
+				"Resource pack '{id}' supports pack formats {} to {}, but Minecraft {minecraft_version} uses pack format {expected}. It may not work correctly",
+				supported.start(),
+				supported.end()
+			)),
+			MessageLevel::Important,
+		);
+	}
+}
+
+/// Read the range of pack formats that a resource pack zip supports from its pack.mcmeta
+fn read_supported_formats(contents: &[u8]) -> anyhow::Result<RangeInclusive<u32>> {
+	let mut zip = zip::ZipArchive::new(Cursor::new(contents)).context("Failed to open zip file")?;
+	let mut meta = String::new();
+	zip.by_name("pack.mcmeta")
+		.context("Resource pack is missing a pack.mcmeta file")?
+		.read_to_string(&mut meta)
+		.context("Failed to read pack.mcmeta")?;
+	let meta: Value = serde_json::from_str(&meta).context("Failed to parse pack.mcmeta")?;
+	let pack = meta
+		.get("pack")
+		.context("pack.mcmeta is missing the pack field")?;
+
+	// Newer versions use a minimum and maximum format, which can have minor versions
+	let get_major = |value: &Value| match value {
+		Value::Array(version) => version.first().and_then(Value::as_u64),
+		other => other.as_u64(),
+	};
+	if let (Some(min), Some(max)) = (
+		pack.get("min_format").and_then(get_major),
+		pack.get("max_format").and_then(get_major),
+	) {
+		return Ok(min as u32..=max as u32);
+	}
+
+	let format = pack
+		.get("pack_format")
+		.and_then(Value::as_u64)
+		.context("pack.mcmeta is missing the pack format")? as u32;
+	let supported = match pack.get("supported_formats") {
+		Some(Value::Number(format)) => format.as_u64().map(|x| x..=x),
+		Some(Value::Array(range)) => match range.as_slice() {
+			[min, max] => min.as_u64().zip(max.as_u64()).map(|(min, max)| min..=max),
+			_ => None,
+		},
+		Some(Value::Object(range)) => range
+			.get("min_inclusive")
+			.and_then(Value::as_u64)
+			.zip(range.get("max_inclusive").and_then(Value::as_u64))
+			.map(|(min, max)| min..=max),
+		_ => None,
+	};
+
+	Ok(match supported {
+		Some(range) => (*range.start() as u32).min(format)..=(*range.end() as u32).max(format),
+		None => format..=format,
+	})
+}
+
+/// The first release of each resource pack format, ordered from oldest to newest
+const PACK_FORMATS: &[((u32, u32), u32)] = &[
+	((6, 1), 1),
+	((9, 0), 2),
+	((11, 0), 3),
+	((13, 0), 4),
+	((15, 0), 5),
+	((16, 2), 6),
+	((17, 0), 7),
+	((18, 0), 8),
+	((19, 0), 9),
+	((19, 3), 12),
+	((19, 4), 13),
+	((20, 0), 15),
+	((20, 2), 18),
+	((20, 3), 22),
+	((20, 5), 32),
+	((21, 0), 34),
+	((21, 2), 42),
+	((21, 4), 46),
+	((21, 5), 55),
+	((21, 6), 63),
+	((21, 7), 64),
+];
+
+/// The newest release that the pack format table is known to be correct for
+const NEWEST_KNOWN_RELEASE: (u32, u32) = (21, 8);
+
+/// Get the resource pack format for a release of Minecraft. Returns None for snapshots and
+/// versions that aren't in the table
+fn get_pack_format(minecraft_version: &str) -> Option<u32> {
+	let mut parts = minecraft_version.split('.');
+	if parts.next()? != "1" {
+		return None;
+	}
+	let minor: u32 = parts.next()?.parse().ok()?;
+	let patch: u32 = match parts.next() {
+		Some(patch) => patch.parse().ok()?,
+		None => 0,
+	};
+	if parts.next().is_some() || (minor, patch) > NEWEST_KNOWN_RELEASE {
+		return None;
+	}
+
+	PACK_FORMATS
+		.iter()
+		.rev()
+		.find(|(release, ..)| (minor, patch) >= *release)
+		.map(|x| x.1)
+}
+
+/// Select the newest resource pack version that supports a Minecraft version
+fn select_version<'a>(versions: &'a [Version], minecraft_version: &str) -> Option<&'a Version> {
+	versions
+		.iter()
+		.filter(|x| x.game_versions.iter().any(|x| x == minecraft_version))
+		.max_by(|a, b| a.date_published.cmp(&b.date_published))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Write;
+
+	use super::*;
+
+	#[test]
+	fn test_pack_format() {
+		assert_eq!(get_pack_format("1.20.1"), Some(15));
+		assert_eq!(get_pack_format("1.20"), Some(15));
+		assert_eq!(get_pack_format("1.16.1"), Some(5));
+		assert_eq!(get_pack_format("1.16.5"), Some(6));
+		assert_eq!(get_pack_format("1.21.1"), Some(34));
+		assert_eq!(get_pack_format("1.5.2"), None);
+		assert_eq!(get_pack_format("24w14a"), None);
+		assert_eq!(get_pack_format("1.20.5-pre1"), None);
+	}
+
+	#[test]
+	fn test_read_supported_formats() {
+		let create_pack = |meta: &str| {
+			let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+			zip.start_file("pack.mcmeta", zip::write::SimpleFileOptions::default())
+				.unwrap();
+			zip.write_all(meta.as_bytes()).unwrap();
+			zip.finish().unwrap().into_inner()
+		};
+
+		let pack = create_pack(r#"{"pack": {"pack_format": 15, "description": ""}}"#);
+		assert_eq!(read_supported_formats(&pack).unwrap(), 15..=15);
+
+		let pack = create_pack(r#"{"pack": {"pack_format": 15, "supported_formats": [13, 22]}}"#);
+		assert_eq!(read_supported_formats(&pack).unwrap(), 13..=22);
+
+		let pack = create_pack(
+			r#"{"pack": {"pack_format": 34, "supported_formats": {"min_inclusive": 32, "max_inclusive": 46}}}"#,
+		);
+		assert_eq!(read_supported_formats(&pack).unwrap(), 32..=46);
+
+		let pack = create_pack(r#"{"pack": {"min_format": [64, 0], "max_format": 69}}"#);
+		assert_eq!(read_supported_formats(&pack).unwrap(), 64..=69);
+	}
+}
@@ -0,0 +1,134 @@
+use anyhow::{bail, Context};
+use mcvm_net::modrinth::{KnownLoader, Loader, ProjectType, Version};
+use mcvm_shared::output::MCVMOutput;
+use reqwest::Client;
+
+use crate::io::lock::{Lockfile, LockfileShaderPack};
+use crate::io::paths::Paths;
+
+use super::pack_files::{get_modrinth_project, remove_pack_file, PackFile, PackSource};
+use super::{InstKind, Instance};
+
+/// The name of this kind of pack, used in messages
+const KIND: &str = "shader pack";
+
+/// A mod that is able to load shader packs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShaderLoader {
+	/// Iris, or one of its ports
+	Iris,
+	/// OptiFine
+	Optifine,
+}
+
+impl ShaderLoader {
+	/// Get the shader loader that a package provides, if any
+	fn from_package(package: &str) -> Option<Self> {
+		match package {
+			"iris" | "oculus" => Some(Self::Iris),
+			"optifine" | "optifine-support" => Some(Self::Optifine),
+			_ => None,
+		}
+	}
+
+	/// Checks if a Modrinth loader is this shader loader
+	fn matches_loader(&self, loader: &Loader) -> bool {
+		match self {
+			Self::Iris => matches!(loader, Loader::Known(KnownLoader::Iris)),
+			Self::Optifine => matches!(loader, Loader::Known(KnownLoader::Optifine)),
+		}
+	}
+}
+
+impl Instance {
+	/// Install a shader pack on this instance and record it in the lockfile.
+	/// Returns the ID that the shader pack was installed with
+	pub async fn add_shader_pack(
+		&mut self,
+		source: &PackSource,
+		lock: &mut Lockfile,
+		paths: &Paths,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		if !matches!(self.kind, InstKind::Client { .. }) {
+			bail!("Shader packs can only be added to client instances");
+		}
+		let Some(loader) = self.get_shader_loader(lock) else {
+			bail!("Instance does not have a shader loader. Add the 'iris' or 'optifine' package to it and update it first");
+		};
+
+		let pack = match source {
+			PackSource::File(path) => PackFile::read_local(path, KIND)?,
+			PackSource::Modrinth(project_id) => {
+				let Some(minecraft_version) = lock.get_instance_version(&self.id) else {
+					bail!(
+						"Instance has not been created yet. Update it before adding shader packs"
+					);
+				};
+				let (project, versions) = get_modrinth_project(
+					project_id,
+					|x| matches!(x.project_type, ProjectType::Shader),
+					KIND,
+					paths,
+					client,
+					o,
+				)
+				.await?;
+				let version =
+					select_version(&versions, minecraft_version, loader).with_context(|| {
+						format!("No version of '{project_id}' supports Minecraft {minecraft_version} with {loader:?}")
+					})?;
+				PackFile::download_modrinth(project_id, &project, version, KIND, client).await?
+			}
+		};
+		let id = pack.id.clone();
+
+		// Clear out the old file if this shader pack is being replaced
+		if let Some(previous) = lock.remove_shader_pack(&self.id, &id) {
+			remove_pack_file(&previous.file, KIND)?;
+		}
+
+		self.ensure_dirs(paths)?;
+		let path = pack.write(&self.dirs.get().game_dir.join("shaderpacks"), KIND)?;
+
+		let shader_pack = LockfileShaderPack {
+			source: pack.source,
+			version: pack.version,
+			file: path.to_string_lossy().to_string(),
+		};
+		lock.add_shader_pack(&self.id, &id, shader_pack);
+
+		Ok(id)
+	}
+
+	/// Remove a shader pack that was installed on this instance
+	pub fn remove_shader_pack(&mut self, id: &str, lock: &mut Lockfile) -> anyhow::Result<()> {
+		let Some(shader_pack) = lock.remove_shader_pack(&self.id, id) else {
+			bail!("Shader pack '{id}' is not installed on this instance");
+		};
+		remove_pack_file(&shader_pack.file, KIND)
+	}
+
+	/// Get the shader loader installed on this instance, looking at both the packages that were
+	/// installed in the last update and the ones in the config
+	pub fn get_shader_loader(&self, lock: &Lockfile) -> Option<ShaderLoader> {
+		lock.get_instance_packages(&self.id)
+			.into_iter()
+			.chain(self.config.packages.iter().map(|x| x.id.as_ref()))
+			.find_map(ShaderLoader::from_package)
+	}
+}
+
+/// Select the newest shader pack version that supports a Minecraft version and shader loader
+fn select_version<'a>(
+	versions: &'a [Version],
+	minecraft_version: &str,
+	loader: ShaderLoader,
+) -> Option<&'a Version> {
+	versions
+		.iter()
+		.filter(|x| x.game_versions.iter().any(|x| x == minecraft_version))
+		.filter(|x| x.loaders.is_empty() || x.loaders.iter().any(|x| loader.matches_loader(x)))
+		.max_by(|a, b| a.date_published.cmp(&b.date_published))
+}
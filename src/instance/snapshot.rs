@@ -0,0 +1,517 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use mcvm_core::io::{json_from_file, json_to_file};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::util::{is_valid_identifier, DefaultExt};
+#[cfg(feature = "schema")]
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::io::paths::Paths;
+use crate::util::get_size;
+
+use super::create::copy_dir_excluding;
+use super::Instance;
+
+/// Extension for snapshot directories that have not finished being written
+const PARTIAL_EXTENSION: &str = "partial";
+/// Extension for compressed snapshot archives
+const ARCHIVE_EXTENSION: &str = "tar.zst";
+
+/// Configuration for the snapshots of an instance
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(default)]
+pub struct Config {
+	/// The maximum number of snapshots to keep. The oldest ones will be removed first
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_snapshots: Option<u32>,
+	/// The maximum age of snapshots to keep, in days
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_age: Option<u32>,
+	/// Paths relative to the game directory to include in snapshots.
+	/// If empty, the whole game directory will be included
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub paths: Vec<String>,
+	/// How to compress snapshots
+	#[serde(skip_serializing_if = "DefaultExt::is_default")]
+	pub compression: SnapshotCompression,
+}
+
+/// Compression for snapshots
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "schema", derive(JsonSchema))]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum SnapshotCompression {
+	/// Store snapshots as plain copies of the files
+	#[default]
+	None,
+	/// Store snapshots as a single Zstandard-compressed tar archive
+	Zstd {
+		/// The compression level, from 1 to 22
+		#[serde(default = "default_zstd_level")]
+		level: i32,
+	},
+}
+
+/// The default Zstandard compression level
+fn default_zstd_level() -> i32 {
+	zstd::DEFAULT_COMPRESSION_LEVEL
+}
+
+impl Config {
+	/// Merge with another config, with the other one taking precedence
+	pub fn merge(&mut self, other: Self) {
+		self.max_snapshots = other.max_snapshots.or(self.max_snapshots);
+		self.max_age = other.max_age.or(self.max_age);
+		if !other.paths.is_empty() {
+			self.paths = other.paths;
+		}
+		if other.compression != SnapshotCompression::default() {
+			self.compression = other.compression;
+		}
+	}
+
+	/// Get the maximum age of snapshots as a duration
+	pub fn get_max_age(&self) -> Option<Duration> {
+		self.max_age
+			.map(|x| Duration::from_secs(x as u64 * 60 * 60 * 24))
+	}
+}
+
+/// Index of all the snapshots of an instance
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct Index {
+	/// The snapshots, ordered from oldest to newest
+	pub snapshots: Vec<Entry>,
+}
+
+/// A single snapshot in the index
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Entry {
+	/// The ID of the snapshot
+	pub id: String,
+	/// When the snapshot was created, as a UNIX timestamp in seconds
+	pub date: u64,
+	/// Whether the snapshot is stored as a compressed archive. Snapshots made before
+	/// compression was supported don't have this field and are stored as plain directories
+	#[serde(default)]
+	pub compressed: bool,
+}
+
+impl Entry {
+	/// Get the path to the data of this snapshot in a snapshot directory
+	pub fn get_data_path(&self, dir: &Path) -> PathBuf {
+		if self.compressed {
+			dir.join(format!("{}.{ARCHIVE_EXTENSION}", self.id))
+		} else {
+			dir.join(&self.id)
+		}
+	}
+}
+
+impl Index {
+	/// Open the index in a snapshot directory
+	pub fn open(dir: &Path) -> anyhow::Result<Self> {
+		let path = Self::get_path(dir);
+		if !path.exists() {
+			return Ok(Self::default());
+		}
+		let mut out: Self = json_from_file(path).context("Failed to read snapshot index")?;
+		// Ignore entries that don't have their data anymore
+		out.snapshots.retain(|x| x.get_data_path(dir).exists());
+
+		Ok(out)
+	}
+
+	/// Write the index to a snapshot directory. The index is written to a temporary
+	/// file first so that it is never left half-written
+	pub fn write(&self, dir: &Path) -> anyhow::Result<()> {
+		let path = Self::get_path(dir);
+		let tmp_path = path.with_extension("json.tmp");
+		json_to_file(&tmp_path, self).context("Failed to write snapshot index")?;
+		fs::rename(tmp_path, path).context("Failed to replace snapshot index")?;
+
+		Ok(())
+	}
+
+	/// Get the path to the index file
+	fn get_path(dir: &Path) -> PathBuf {
+		dir.join("index.json")
+	}
+
+	/// Get the IDs of snapshots that should be removed according to the config.
+	/// Snapshots in the keep list will never be removed
+	fn get_expired(&self, config: &Config, now: u64, keep: &[&str]) -> Vec<String> {
+		let mut out = Vec::new();
+		let mut remaining = self.snapshots.len();
+		for entry in &self.snapshots {
+			if keep.contains(&entry.id.as_str()) {
+				continue;
+			}
+			let too_many = config
+				.max_snapshots
+				.is_some_and(|max| remaining > max as usize);
+			let too_old = config
+				.get_max_age()
+				.is_some_and(|max| now.saturating_sub(entry.date) > max.as_secs());
+			if too_many || too_old {
+				out.push(entry.id.clone());
+				remaining -= 1;
+			}
+		}
+
+		out
+	}
+}
+
+impl Instance {
+	/// Get the directory where this instance's snapshots are stored
+	pub fn get_snapshot_dir(&self, paths: &Paths) -> PathBuf {
+		paths.snapshots.join(self.id.to_string())
+	}
+
+	/// Get the snapshots of this instance, ordered from oldest to newest
+	pub fn get_snapshots(&self, paths: &Paths) -> anyhow::Result<Vec<Entry>> {
+		let index = Index::open(&self.get_snapshot_dir(paths))?;
+		Ok(index.snapshots)
+	}
+
+	/// Create a new snapshot of this instance, and remove old ones according to the retention
+	/// policy. If an ID isn't given, the current time will be used. Returns the ID of the new snapshot
+	pub fn create_snapshot(
+		&mut self,
+		id: Option<String>,
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		self.create_snapshot_impl(id, &[], paths, o)
+	}
+
+	/// Restores a snapshot over the game directory of this instance. A snapshot of the current
+	/// state will be made first so that the restore can be undone. Returns the ID of that snapshot
+	pub fn restore_snapshot(
+		&mut self,
+		id: &str,
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		let snapshot_dir = self.get_snapshot_dir(paths);
+		let index = Index::open(&snapshot_dir)?;
+		let Some(entry) = index.snapshots.iter().find(|x| x.id == id) else {
+			bail!("Snapshot '{id}' does not exist");
+		};
+
+		let backup_id = format!("before-restore-{}", now());
+		self.create_snapshot_impl(Some(backup_id.clone()), &[id], paths, o)
+			.context("Failed to back up the current state of the instance")?;
+
+		// Compressed snapshots are extracted next to the archive first so that
+		// they can be restored the same way as uncompressed ones
+		let data_path = entry.get_data_path(&snapshot_dir);
+		let data_dir = if entry.compressed {
+			let extract_dir = snapshot_dir.join(format!("{id}.restore.{PARTIAL_EXTENSION}"));
+			remove_path(&extract_dir)?;
+			extract_archive(&data_path, &extract_dir)
+				.context("Failed to extract snapshot archive")?;
+			extract_dir
+		} else {
+			data_path
+		};
+
+		let game_dir = self.dirs.get().game_dir.clone();
+		for path in get_included_paths(&data_dir, &self.config.snapshots)? {
+			let target = game_dir.join(&path);
+			remove_path(&target)?;
+			copy_path(&data_dir.join(&path), &target)
+				.with_context(|| format!("Failed to restore {}", path.display()))?;
+		}
+
+		if entry.compressed {
+			remove_path(&data_dir)?;
+		}
+
+		Ok(backup_id)
+	}
+
+	/// Removes a snapshot of this instance
+	pub fn remove_snapshot(&mut self, id: &str, paths: &Paths) -> anyhow::Result<()> {
+		let snapshot_dir = self.get_snapshot_dir(paths);
+		let mut index = Index::open(&snapshot_dir)?;
+		let Some(pos) = index.snapshots.iter().position(|x| x.id == id) else {
+			bail!("Snapshot '{id}' does not exist");
+		};
+		let entry = index.snapshots.remove(pos);
+		// Update the index first so that it never points to missing data
+		index.write(&snapshot_dir)?;
+		remove_path(&entry.get_data_path(&snapshot_dir))?;
+
+		Ok(())
+	}
+
+	/// Creates a snapshot without removing the snapshots in the keep list
+	fn create_snapshot_impl(
+		&mut self,
+		id: Option<String>,
+		keep: &[&str],
+		paths: &Paths,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<String> {
+		self.ensure_dirs(paths)?;
+		let date = now();
+		let id = id.unwrap_or_else(|| date.to_string());
+		if id.is_empty() || id.starts_with('.') || !is_valid_identifier(&id) {
+			bail!("Invalid snapshot ID '{id}'");
+		}
+
+		let snapshot_dir = self.get_snapshot_dir(paths);
+		fs::create_dir_all(&snapshot_dir).context("Failed to create snapshot directory")?;
+		let mut index = Index::open(&snapshot_dir)?;
+		if index.snapshots.iter().any(|x| x.id == id) {
+			bail!("A snapshot with the ID '{id}' already exists");
+		}
+		remove_partial_snapshots(&snapshot_dir)?;
+
+		let entry = Entry {
+			id: id.clone(),
+			date,
+			compressed: self.config.snapshots.compression != SnapshotCompression::None,
+		};
+
+		// Write to a temporary location and then move it into place, so that a failure
+		// partway through never leaves a broken snapshot
+		let game_dir = &self.dirs.get().game_dir;
+		let included = get_included_paths(game_dir, &self.config.snapshots)?;
+		let data_path = entry.get_data_path(&snapshot_dir);
+		let mut partial_name = data_path.file_name().unwrap_or_default().to_owned();
+		partial_name.push(format!(".{PARTIAL_EXTENSION}"));
+		let partial_path = snapshot_dir.join(partial_name);
+		match &self.config.snapshots.compression {
+			SnapshotCompression::None => {
+				fs::create_dir_all(&partial_path)
+					.context("Failed to create snapshot data directory")?;
+				for path in &included {
+					copy_path(&game_dir.join(path), &partial_path.join(path))
+						.with_context(|| format!("Failed to copy {}", path.display()))?;
+				}
+			}
+			SnapshotCompression::Zstd { level } => {
+				create_archive(game_dir, &included, &partial_path, *level)
+					.context("Failed to create snapshot archive")?;
+
+				let original_size = included
+					.iter()
+					.map(|x| get_size(&game_dir.join(x)))
+					.sum::<u64>();
+				let compressed_size = get_size(&partial_path);
+				let ratio = if original_size == 0 {
+					100.0
+				} else {
+					compressed_size as f64 / original_size as f64 * 100.0
+				};
+				o.display(
+					MessageContents::Simple(format!(
+						"Compressed snapshot to {ratio:.1}% of its original size ({compressed_size} / {original_size} bytes)"
+					)),
+					MessageLevel::Important,
+				);
+			}
+		}
+		fs::rename(&partial_path, &data_path).context("Failed to move snapshot into place")?;
+
+		index.snapshots.push(entry);
+
+		// Enforce the retention policy
+		let mut keep = keep.to_vec();
+		keep.push(&id);
+		let expired = index.get_expired(&self.config.snapshots, date, &keep);
+		let mut removed = Vec::new();
+		index.snapshots.retain(|x| {
+			let is_expired = expired.contains(&x.id);
+			if is_expired {
+				removed.push(x.get_data_path(&snapshot_dir));
+			}
+			!is_expired
+		});
+		index.write(&snapshot_dir)?;
+		for path in removed {
+			remove_path(&path)?;
+		}
+
+		Ok(id)
+	}
+}
+
+/// Write files from a directory into a Zstandard-compressed tar archive
+fn create_archive(dir: &Path, included: &[PathBuf], dest: &Path, level: i32) -> anyhow::Result<()> {
+	let file = BufWriter::new(File::create(dest).context("Failed to create archive file")?);
+	let encoder = zstd::Encoder::new(file, level).context("Failed to create encoder")?;
+	let mut builder = tar::Builder::new(encoder);
+	for path in included {
+		let src = dir.join(path);
+		if src.is_dir() {
+			builder.append_dir_all(path, &src)
+		} else {
+			builder.append_path_with_name(&src, path)
+		}
+		.with_context(|| format!("Failed to add {} to archive", path.display()))?;
+	}
+	let encoder = builder.into_inner().context("Failed to finish archive")?;
+	encoder.finish().context("Failed to finish compression")?;
+
+	Ok(())
+}
+
+/// Extract a Zstandard-compressed tar archive into a directory
+fn extract_archive(path: &Path, dest: &Path) -> anyhow::Result<()> {
+	let file = File::open(path).context("Failed to open archive file")?;
+	let decoder = zstd::Decoder::new(file).context("Failed to create decoder")?;
+	tar::Archive::new(decoder)
+		.unpack(dest)
+		.context("Failed to unpack archive")?;
+
+	Ok(())
+}
+
+/// Get the paths relative to a directory that should be included in a snapshot
+fn get_included_paths(dir: &Path, config: &Config) -> anyhow::Result<Vec<PathBuf>> {
+	if config.paths.is_empty() {
+		let mut out = Vec::new();
+		if dir.exists() {
+			for entry in dir.read_dir().context("Failed to read directory")? {
+				out.push(PathBuf::from(entry?.file_name()));
+			}
+		}
+		Ok(out)
+	} else {
+		Ok(config
+			.paths
+			.iter()
+			.map(PathBuf::from)
+			.filter(|x| dir.join(x).exists())
+			.collect())
+	}
+}
+
+/// Copy a file or directory
+fn copy_path(src: &Path, dest: &Path) -> anyhow::Result<()> {
+	if src.is_dir() {
+		copy_dir_excluding(src, dest, &HashSet::new())
+	} else {
+		if let Some(parent) = dest.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		fs::copy(src, dest)?;
+		Ok(())
+	}
+}
+
+/// Remove a file or directory if it exists
+fn remove_path(path: &Path) -> anyhow::Result<()> {
+	if path.is_dir() {
+		fs::remove_dir_all(path)
+			.with_context(|| format!("Failed to remove directory {}", path.display()))?;
+	} else if path.exists() {
+		fs::remove_file(path)
+			.with_context(|| format!("Failed to remove file {}", path.display()))?;
+	}
+
+	Ok(())
+}
+
+/// Remove leftover snapshots that were interrupted while being created
+fn remove_partial_snapshots(dir: &Path) -> anyhow::Result<()> {
+	for entry in dir
+		.read_dir()
+		.context("Failed to read snapshot directory")?
+	{
+		let path = entry?.path();
+		if path.extension().is_some_and(|x| x == PARTIAL_EXTENSION) {
+			remove_path(&path)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Get the current UNIX timestamp in seconds
+fn now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(id: &str, date: u64) -> Entry {
+		Entry {
+			id: id.into(),
+			date,
+			compressed: false,
+		}
+	}
+
+	#[test]
+	fn test_snapshot_retention() {
+		let index = Index {
+			snapshots: vec![entry("a", 0), entry("b", 100), entry("c", 200)],
+		};
+
+		let config = Config {
+			max_snapshots: Some(2),
+			..Default::default()
+		};
+		assert_eq!(index.get_expired(&config, 200, &[]), vec!["a".to_string()]);
+		assert_eq!(
+			index.get_expired(&config, 200, &["a"]),
+			vec!["b".to_string()]
+		);
+
+		let config = Config {
+			max_age: Some(1),
+			..Default::default()
+		};
+		let day = 60 * 60 * 24;
+		assert_eq!(
+			index.get_expired(&config, day + 150, &[]),
+			vec!["a".to_string(), "b".to_string()]
+		);
+	}
+
+	#[test]
+	fn test_snapshot_archive() {
+		let dir = std::env::temp_dir().join("mcvm_test_snapshot_archive");
+		let _ = fs::remove_dir_all(&dir);
+		let game_dir = dir.join("game");
+		fs::create_dir_all(game_dir.join("world/region")).unwrap();
+		fs::write(game_dir.join("world/region/r.0.0.mca"), "region data").unwrap();
+		fs::write(game_dir.join("options.txt"), "fov:1.0").unwrap();
+
+		let archive = dir.join("snapshot.tar.zst");
+		let included = [PathBuf::from("world"), PathBuf::from("options.txt")];
+		create_archive(&game_dir, &included, &archive, 3).unwrap();
+
+		let extracted = dir.join("extracted");
+		extract_archive(&archive, &extracted).unwrap();
+		assert_eq!(
+			fs::read_to_string(extracted.join("world/region/r.0.0.mca")).unwrap(),
+			"region data"
+		);
+		assert_eq!(
+			fs::read_to_string(extracted.join("options.txt")).unwrap(),
+			"fov:1.0"
+		);
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
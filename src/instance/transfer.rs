@@ -1,15 +1,21 @@
-use std::{collections::HashMap, path::Path};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
+use mcvm_core::io::json_from_file;
+use mcvm_mods::fabric_quilt::{self, FabricQuiltMeta};
 use mcvm_plugin::hooks::{
 	AddInstanceTransferFormat, ExportInstance, ExportInstanceArg, InstanceTransferFeatureSupport,
 	InstanceTransferFormat, InstanceTransferFormatDirection,
 };
 use mcvm_shared::lang::translate::TranslationKey;
+use mcvm_shared::modifications::Modloader;
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm_shared::translate;
 
-use crate::{config::plugin::PluginManager, io::paths::Paths};
+use crate::config::plugin::PluginManager;
+use crate::io::lock::Lockfile;
+use crate::io::paths::Paths;
 
 use super::Instance;
 
@@ -17,8 +23,7 @@ impl Instance {
 	/// Export this instance using the given format
 	pub fn export(
 		&mut self,
-		format: &str,
-		result_path: &Path,
+		settings: ExportSettings,
 		formats: &Formats,
 		plugins: &PluginManager,
 		paths: &Paths,
@@ -27,7 +32,7 @@ impl Instance {
 		// Get and print info about the format
 		let format = formats
 			.formats
-			.get(format)
+			.get(&settings.format)
 			.context("Transfer format does not exist")?;
 
 		let export_info = format
@@ -38,10 +43,20 @@ impl Instance {
 
 		output_support_warnings(export_info, o);
 
-		// TODO: Ensure that the instance has been created once from lockfile before allowing export
+		// The resolved version is only known once the instance has been updated
+		let lock = Lockfile::open(paths, o).context("Failed to open lockfile")?;
+		let Some(minecraft_version) = lock.get_instance_version(&self.id) else {
+			bail!("Instance has not been created yet. Update it before exporting");
+		};
+		let minecraft_version = minecraft_version.to_string();
+
 		self.ensure_dirs(paths)
 			.context("Failed to ensure instance directories")?;
 
+		let modloader = self.config.modifications.get_modloader(self.get_side());
+		let modloader_version = get_modloader_version(&modloader, &minecraft_version, paths)
+			.context("Failed to get modloader version")?;
+
 		// Export using the plugin
 		let arg = ExportInstanceArg {
 			id: self.id.to_string(),
@@ -49,7 +64,11 @@ impl Instance {
 			name: self.config.name.clone(),
 			side: Some(self.get_side()),
 			game_dir: self.dirs.get().game_dir.to_string_lossy().to_string(),
-			result_path: result_path.to_string_lossy().to_string(),
+			result_path: settings.result_path.to_string_lossy().to_string(),
+			minecraft_version: Some(minecraft_version),
+			modloader: Some(modloader),
+			modloader_version,
+			exclude_worlds: settings.exclude_worlds,
 		};
 		let result = plugins
 			.call_hook_on_plugin(ExportInstance, &format.plugin, &arg, paths, o)
@@ -68,6 +87,37 @@ impl Instance {
 	}
 }
 
+/// Settings for exporting an instance
+pub struct ExportSettings {
+	/// The ID of the transfer format to use
+	pub format: String,
+	/// The path to write the exported instance to
+	pub result_path: PathBuf,
+	/// Whether to leave out world saves from the export
+	pub exclude_worlds: bool,
+}
+
+/// Get the version of the installed modloader from cached metadata, if it is known
+fn get_modloader_version(
+	modloader: &Modloader,
+	minecraft_version: &str,
+	paths: &Paths,
+) -> anyhow::Result<Option<String>> {
+	let mode = match modloader {
+		Modloader::Fabric => fabric_quilt::Mode::Fabric,
+		Modloader::Quilt => fabric_quilt::Mode::Quilt,
+		_ => return Ok(None),
+	};
+	let path = fabric_quilt::get_meta_path(minecraft_version, &mode, &paths.core);
+	if !path.exists() {
+		return Ok(None);
+	}
+	let meta: Vec<FabricQuiltMeta> =
+		json_from_file(path).context("Failed to read cached loader metadata")?;
+
+	Ok(meta.first().and_then(|x| x.loader.get_version()))
+}
+
 /// Load transfer formats from plugins
 pub fn load_formats(
 	plugins: &PluginManager,
@@ -5,14 +5,15 @@ use std::sync::Arc;
 use anyhow::Context;
 use mcvm_core::auth_crate::mc::ClientId;
 use mcvm_core::config::BrandingProperties;
+use mcvm_core::net::game_files::mirror::Mirrors;
 use mcvm_core::user::UserManager;
 use mcvm_core::util::versions::MinecraftVersion;
 use mcvm_core::version::InstalledVersion;
 use mcvm_core::MCVMCore;
 use mcvm_plugin::hooks::{AddVersions, HandleAuth, HandleAuthArg};
 use mcvm_shared::later::Later;
-use mcvm_shared::output::MCVMOutput;
 use mcvm_shared::output::NoOp;
+use mcvm_shared::output::{MCVMOutput, UpdatePhase, UpdatePhaseEvent};
 use mcvm_shared::versions::VersionInfo;
 use mcvm_shared::Side;
 use reqwest::Client;
@@ -37,8 +38,16 @@ pub struct UpdateSettings {
 	pub force: bool,
 	/// Whether we will prioritize local files instead of remote ones
 	pub allow_offline: bool,
+	/// Whether to check the hashes of existing game files
+	pub verify_hashes: bool,
 	/// Whether to do offline authentication
 	pub offline_auth: bool,
+	/// The maximum number of files to download at the same time, overriding the default
+	pub download_concurrency: Option<usize>,
+	/// Mirrors to download game files from instead of the official servers
+	pub mirrors: Mirrors,
+	/// Whether to only plan the update without downloading or changing any files
+	pub dry_run: bool,
 }
 
 /// Manager for when we are updating profile files.
@@ -68,7 +77,11 @@ impl UpdateManager {
 		let settings = UpdateSettings {
 			force,
 			allow_offline,
+			verify_hashes: false,
 			offline_auth: false,
+			download_concurrency: None,
+			mirrors: Mirrors::default(),
+			dry_run: false,
 		};
 
 		Self {
@@ -88,6 +101,27 @@ impl UpdateManager {
 		self.settings.offline_auth = true;
 	}
 
+	/// Set the maximum number of files to download at the same time
+	pub fn set_download_concurrency(&mut self, concurrency: Option<usize>) {
+		self.settings.download_concurrency = concurrency;
+	}
+
+	/// Set the mirrors to download game files from
+	pub fn set_mirrors(&mut self, mirrors: Mirrors) {
+		self.settings.mirrors = mirrors;
+	}
+
+	/// Check the hashes of existing game files so that corrupt ones are downloaded again.
+	/// This is slower, so it is only done for explicit updates and not when launching
+	pub fn set_verify_hashes(&mut self) {
+		self.settings.verify_hashes = true;
+	}
+
+	/// Make this a dry run, where only metadata is fetched and no game files are downloaded or changed
+	pub fn set_dry_run(&mut self) {
+		self.settings.dry_run = true;
+	}
+
 	/// Set the MS client ID
 	pub fn set_client_id(&mut self, id: ClientId) {
 		self.ms_client_id = Some(id);
@@ -118,9 +152,11 @@ impl UpdateManager {
 		self.add_files(result.files_updated);
 	}
 
-	/// Whether a file needs to be updated
+	/// Whether a file needs to be updated. Files are never updated in a dry run
 	pub fn should_update_file(&self, file: &Path) -> bool {
-		if self.settings.force {
+		if self.settings.dry_run {
+			false
+		} else if self.settings.force {
 			!self.files.contains(file) || !file.exists()
 		} else {
 			!file.exists()
@@ -155,6 +191,16 @@ impl UpdateManager {
 			return Ok(());
 		}
 
+		// Dry runs only need the version info, which doesn't install the version
+		if self.settings.dry_run {
+			let version_info = self
+				.get_dry_run_version_info(o)
+				.await
+				.context("Failed to get version info")?;
+			self.version_info.fill(version_info);
+			return Ok(());
+		}
+
 		let version = self
 			.get_core_version(o)
 			.await
@@ -187,6 +233,7 @@ impl UpdateManager {
 		let mut core_config = mcvm_core::ConfigBuilder::new()
 			.allow_offline(self.settings.allow_offline)
 			.force_reinstall(self.settings.force)
+			.verify_hashes(self.settings.verify_hashes)
 			.branding(BrandingProperties::new(
 				"mcvm".into(),
 				crate::VERSION.into(),
@@ -194,6 +241,13 @@ impl UpdateManager {
 		if let Some(client_id) = &self.ms_client_id {
 			core_config = core_config.ms_client_id(client_id.clone());
 		}
+		if let Some(concurrency) = self.settings.download_concurrency {
+			core_config = core_config.download_concurrency(concurrency);
+		}
+		core_config = core_config.mirrors(self.settings.mirrors.clone());
+		if let Later::Full(MinecraftVersion::Version(version)) = &self.mc_version {
+			core_config = core_config.installed_version(Some(version.to_string()));
+		}
 		let core_config = core_config.build();
 		let mut core = MCVMCore::with_config(core_config).context("Failed to initialize core")?;
 
@@ -231,7 +285,7 @@ impl UpdateManager {
 			.call_hook(AddVersions, &(), paths, o)
 			.context("Failed to call add_versions hook")?;
 		for result in results {
-			let result = result.result(o)?;
+			let result = result.result_async(o).await?;
 			core.add_additional_versions(result);
 		}
 
@@ -244,7 +298,7 @@ impl UpdateManager {
 	pub async fn get_core_version(
 		&mut self,
 		o: &mut impl MCVMOutput,
-	) -> anyhow::Result<InstalledVersion> {
+	) -> anyhow::Result<InstalledVersion<'_, '_>> {
 		let version = self
 			.core
 			.get_mut()
@@ -255,6 +309,25 @@ impl UpdateManager {
 		Ok(version)
 	}
 
+	/// Get the version info for the Minecraft version without installing it
+	async fn get_dry_run_version_info(
+		&mut self,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<VersionInfo> {
+		let core = self.core.get_mut();
+		let manifest = core
+			.get_version_manifest(o)
+			.await
+			.context("Failed to get version manifest")?;
+		let version = self
+			.mc_version
+			.get()
+			.get_version(&manifest.manifest)
+			.context("Version does not exist")?;
+
+		core.get_version_info(version.to_string()).await
+	}
+
 	/// Update Fabric or Quilt if it is required
 	async fn update_fabric_quilt(
 		&mut self,
@@ -279,6 +352,7 @@ impl UpdateManager {
 
 		// Update Fabric / Quilt
 		if required {
+			o.display_update_phase(UpdatePhase::FabricQuilt, UpdatePhaseEvent::Start);
 			for req in self.requirements.iter() {
 				if let UpdateRequirement::FabricQuilt(mode, side) = req {
 					if self.fq_meta.is_empty() {
@@ -315,6 +389,7 @@ impl UpdateManager {
 					.context("Failed to download {mode} files for {side}")?;
 				}
 			}
+			o.display_update_phase(UpdatePhase::FabricQuilt, UpdatePhaseEvent::End);
 		}
 
 		Ok(())
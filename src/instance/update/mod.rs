@@ -2,30 +2,45 @@
 pub mod manager;
 /// Updating packages on a profile
 pub mod packages;
+/// Reports of planned updates for dry runs
+pub mod plan;
+/// Summaries of the changes made by updates
+pub mod summary;
 
 use crate::config::plugin::PluginManager;
 use crate::config::preferences::ConfigPreferences;
 #[cfg(not(feature = "disable_profile_update_packages"))]
 use crate::pkg::eval::EvalConstants;
 use mcvm_core::user::UserManager;
+use mcvm_core::util::versions::MinecraftVersion;
 use mcvm_shared::translate;
+use mcvm_shared::util::ARCH_STRING;
 #[cfg(not(feature = "disable_profile_update_packages"))]
 use packages::print_package_support_messages;
-use packages::update_instance_packages;
+use packages::{install_locked_packages, update_instance_packages};
 #[cfg(not(feature = "disable_profile_update_packages"))]
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use mcvm_mods::paper;
 use mcvm_shared::modifications::ServerType;
 use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::pkg::PackageStability;
+use mcvm_shared::Side;
 use reqwest::Client;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
+use crate::config::package::PackageConfig;
+use crate::config::profile::GameModifications;
 use crate::io::lock::Lockfile;
 use crate::io::paths::Paths;
 use crate::pkg::reg::PkgRegistry;
 
 use manager::UpdateManager;
+use mcvm_core::io::persistent::PersistentData;
+use mcvm_core::net::game_files::client_meta;
+use plan::{PlannedJava, UpdatePlan};
 
 use super::Instance;
 
@@ -47,20 +62,27 @@ pub struct InstanceUpdateContext<'a, O: MCVMOutput> {
 	pub client: &'a Client,
 	/// The output object
 	pub output: &'a mut O,
+	/// Whether to install packages with elevated permissions without asking the user to confirm
+	pub accept_permissions: bool,
 }
 
 impl Instance {
-	/// Update this instance
+	/// Update this instance. If backup is enabled, the instance will be snapshotted
+	/// before its packages are changed
 	pub async fn update<'a, O: MCVMOutput>(
 		&mut self,
 		update_packages: bool,
 		force: bool,
+		backup: bool,
 		ctx: &mut InstanceUpdateContext<'a, O>,
 	) -> anyhow::Result<()> {
 		#[cfg(feature = "disable_profile_update_packages")]
-		let _update_packages = update_packages;
+		let _ = (update_packages, backup);
 
 		let mut manager = UpdateManager::new(force, false);
+		manager.set_verify_hashes();
+		manager.set_download_concurrency(ctx.prefs.download_concurrency);
+		manager.set_mirrors(ctx.prefs.mirrors.clone());
 
 		ctx.output.display(
 			MessageContents::Header(translate!(
@@ -86,6 +108,8 @@ impl Instance {
 		check_instance_version_change(self, &mc_version, paper_properties.clone(), ctx)
 			.await
 			.context("Failed to check for a profile version update")?;
+		ctx.lock
+			.update_instance_loader(&self.id, &self.get_loader_name());
 
 		check_instance_paper_update(self, paper_properties, ctx)
 			.await
@@ -111,6 +135,9 @@ impl Instance {
 			{
 				let mut all_packages = HashSet::new();
 
+				self.check_loader_api(&mc_version, ctx.paths, ctx.client, ctx.output)
+					.await;
+
 				ctx.output.display(
 					MessageContents::Header(translate!(ctx.output, StartUpdatingPackages)),
 					MessageLevel::Important,
@@ -125,7 +152,8 @@ impl Instance {
 				};
 
 				let packages =
-					update_instance_packages(&mut [self], &constants, ctx, force).await?;
+					update_instance_packages(&mut [self], &constants, ctx, force, backup, None)
+						.await?;
 
 				ctx.output.display(
 					MessageContents::Success(translate!(ctx.output, FinishUpdatingPackages)),
@@ -134,6 +162,9 @@ impl Instance {
 
 				all_packages.extend(packages);
 
+				if let Some(fingerprint) = self.get_update_fingerprint() {
+					ctx.lock.update_instance_fingerprint(&self.id, &fingerprint);
+				}
 				ctx.lock
 					.finish(ctx.paths)
 					.context("Failed to finish using lockfile")?;
@@ -149,6 +180,246 @@ impl Instance {
 	}
 }
 
+impl Instance {
+	/// Update this instance using exactly what is recorded in the lockfile, without resolving
+	/// any packages. The Minecraft version from the last update is used and the recorded addon
+	/// files are installed, failing if any of them can't be downloaded or don't match their hashes
+	pub async fn update_frozen<'a, O: MCVMOutput>(
+		&mut self,
+		ctx: &mut InstanceUpdateContext<'a, O>,
+	) -> anyhow::Result<()> {
+		let Some(version) = ctx.lock.get_instance_version(&self.id) else {
+			bail!(
+				"Instance '{}' is not in the lockfile yet. Update it without --frozen first",
+				self.id
+			);
+		};
+		let version = MinecraftVersion::Version(version.into());
+
+		let mut manager = UpdateManager::new(false, false);
+		manager.set_verify_hashes();
+		manager.set_download_concurrency(ctx.prefs.download_concurrency);
+		manager.set_mirrors(ctx.prefs.mirrors.clone());
+
+		ctx.output.display(
+			MessageContents::Header(translate!(
+				ctx.output,
+				StartUpdatingInstance,
+				"inst" = &self.id
+			)),
+			MessageLevel::Important,
+		);
+
+		manager.set_version(&version);
+		manager.add_requirements(self.get_requirements());
+		manager
+			.fulfill_requirements(ctx.users, ctx.plugins, ctx.paths, ctx.client, ctx.output)
+			.await
+			.context("Failed to fulfill update manager")?;
+
+		self.create(
+			&mut manager,
+			ctx.plugins,
+			ctx.paths,
+			ctx.users,
+			ctx.client,
+			ctx.output,
+		)
+		.await
+		.context("Failed to create instance")?;
+
+		ctx.output.display(
+			MessageContents::Header(translate!(ctx.output, StartUpdatingPackages)),
+			MessageLevel::Important,
+		);
+		install_locked_packages(self, manager.version_info.get(), ctx)
+			.await
+			.context("Failed to install packages from the lockfile")?;
+		ctx.output.display(
+			MessageContents::Success(translate!(ctx.output, FinishUpdatingPackages)),
+			MessageLevel::Important,
+		);
+
+		Ok(())
+	}
+
+	/// Get a fingerprint of the configuration that affects updating this instance and its packages.
+	/// Returns None if the Minecraft version is resolved from the latest versions, as it can't be
+	/// known without fetching the version manifest
+	pub fn get_update_fingerprint(&self) -> Option<String> {
+		if self.config.version.is_latest() {
+			return None;
+		}
+
+		// The packages are consolidated through a map, so their order isn't stable
+		let mut packages: Vec<_> = self.config.packages.iter().collect();
+		packages.sort_by(|a, b| a.id.cmp(&b.id));
+		let fingerprint = UpdateFingerprint {
+			side: self.kind.to_side(),
+			version: self.config.version.to_string(),
+			modifications: &self.config.modifications,
+			datapack_folder: self.config.datapack_folder.as_deref(),
+			package_stability: self.config.package_stability,
+			packages,
+		};
+		let config = serde_json::to_vec(&fingerprint).ok()?;
+		Some(hex::encode(Sha256::digest(config)))
+	}
+
+	/// Whether the configuration of this instance is the same as when its packages were last updated,
+	/// so that updating it again can be skipped
+	pub fn is_unchanged_since_update(&self, lock: &Lockfile) -> bool {
+		match (
+			self.get_update_fingerprint(),
+			lock.get_instance_fingerprint(&self.id),
+		) {
+			(Some(current), Some(stored)) => current == stored,
+			_ => false,
+		}
+	}
+
+	/// Plan an update of this instance without downloading game files, installing packages,
+	/// or changing the lockfile. Only metadata needed to make the plan is fetched
+	pub async fn plan_update<'a, O: MCVMOutput>(
+		&mut self,
+		update_packages: bool,
+		force: bool,
+		ctx: &mut InstanceUpdateContext<'a, O>,
+	) -> anyhow::Result<UpdatePlan> {
+		#[cfg(feature = "disable_profile_update_packages")]
+		let _ = update_packages;
+
+		let mut manager = UpdateManager::new(force, false);
+		manager.set_dry_run();
+		manager.set_download_concurrency(ctx.prefs.download_concurrency);
+		manager.set_mirrors(ctx.prefs.mirrors.clone());
+
+		manager.set_version(&self.config.version);
+		manager.add_requirements(self.get_requirements());
+		manager
+			.fulfill_requirements(ctx.users, ctx.plugins, ctx.paths, ctx.client, ctx.output)
+			.await
+			.context("Failed to fulfill update manager")?;
+		let mc_version = manager.version_info.get().version.clone();
+
+		let mut plan = UpdatePlan {
+			instance: self.id.to_string(),
+			current_version: ctx.lock.get_instance_version(&self.id).map(String::from),
+			first_update: !ctx.lock.has_instance_done_first_update(&self.id),
+			new_version: mc_version.clone(),
+			..Default::default()
+		};
+
+		plan.java = plan_java(self, &mc_version, &mut manager, ctx)
+			.await
+			.context("Failed to check Java installation")?;
+
+		if update_packages {
+			#[cfg(not(feature = "disable_profile_update_packages"))]
+			{
+				self.check_loader_api(&mc_version, ctx.paths, ctx.client, ctx.output)
+					.await;
+
+				let constants = EvalConstants {
+					version: mc_version.to_string(),
+					modifications: self.config.modifications.clone(),
+					version_list: manager.version_info.get().versions.clone(),
+					language: ctx.prefs.language,
+					profile_stability: self.config.package_stability,
+				};
+
+				let mut plans = HashMap::new();
+				update_instance_packages(
+					&mut [self],
+					&constants,
+					ctx,
+					force,
+					false,
+					Some(&mut plans),
+				)
+				.await?;
+				if let Some(package_plan) = plans.remove(&self.id) {
+					plan.packages_to_install = package_plan.packages_to_install;
+					plan.packages_to_remove = package_plan.packages_to_remove;
+					plan.addons_to_download = package_plan.addons_to_download;
+					plan.files_to_remove = package_plan.files_to_remove;
+				}
+			}
+		}
+
+		Ok(plan)
+	}
+}
+
+/// The configuration of an instance that affects updating it, which is hashed to get
+/// its update fingerprint
+#[derive(Serialize)]
+struct UpdateFingerprint<'a> {
+	side: Side,
+	version: String,
+	modifications: &'a GameModifications,
+	datapack_folder: Option<&'a str>,
+	package_stability: PackageStability,
+	packages: Vec<&'a PackageConfig>,
+}
+
+/// Get the Java installation that would need to be downloaded to update an instance
+async fn plan_java<'a, O: MCVMOutput>(
+	instance: &Instance,
+	mc_version: &str,
+	manager: &mut UpdateManager,
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) -> anyhow::Result<Option<PlannedJava>> {
+	let core = manager.core.get_mut();
+	let manifest = core
+		.get_version_manifest(ctx.output)
+		.await
+		.context("Failed to get version manifest")?
+		.manifest
+		.clone();
+	let client_meta = client_meta::get(
+		mc_version,
+		&manifest,
+		&ctx.paths.core,
+		core.get_update_manager(),
+		ctx.client,
+		ctx.output,
+	)
+	.await
+	.context("Failed to get client meta")?;
+
+	let persistent =
+		PersistentData::open(&ctx.paths.core).context("Failed to open persistent data")?;
+	let java_info = &client_meta.java_info;
+	let major_version = instance
+		.config
+		.launch
+		.java_version
+		.unwrap_or(java_info.major_version);
+	// The runtime component from the game only applies to the major version it asks for
+	let component = if major_version == java_info.major_version {
+		java_info.component.as_deref()
+	} else {
+		None
+	};
+	let missing = instance.config.launch.java.get_missing_installation(
+		&major_version,
+		component,
+		instance
+			.config
+			.launch
+			.java_arch
+			.as_deref()
+			.unwrap_or(ARCH_STRING),
+		&persistent,
+	);
+
+	Ok(missing.map(|installation| PlannedJava {
+		installation: installation.to_string(),
+		major_version: major_version.to_string(),
+	}))
+}
+
 /// Update an instance when the Minecraft version has changed
 async fn check_instance_version_change<'a, O: MCVMOutput>(
 	instance: &mut Instance,
@@ -183,13 +454,19 @@ async fn get_paper_properties<'a, O: MCVMOutput>(
 	ctx: &mut InstanceUpdateContext<'a, O>,
 ) -> anyhow::Result<Option<(u16, String)>> {
 	let out = if let ServerType::Paper = instance.config.modifications.server_type {
-		let build_num = paper::get_newest_build(paper::Mode::Paper, mc_version, ctx.client)
-			.await
-			.context("Failed to get the newest Paper build number")?;
-		let paper_file_name =
-			paper::get_jar_file_name(paper::Mode::Paper, mc_version, build_num, ctx.client)
+		let build_num =
+			paper::get_newest_build(paper::Mode::Paper, mc_version, &ctx.paths.core, ctx.client)
 				.await
-				.context("Failed to get the name of the Paper Jar file")?;
+				.context("Failed to get the newest Paper build number")?;
+		let paper_file_name = paper::get_jar_file_name(
+			paper::Mode::Paper,
+			mc_version,
+			build_num,
+			&ctx.paths.core,
+			ctx.client,
+		)
+		.await
+		.context("Failed to get the name of the Paper Jar file")?;
 		Some((build_num, paper_file_name))
 	} else {
 		None
@@ -218,3 +495,74 @@ async fn check_instance_paper_update<'a, O: MCVMOutput>(
 
 	Ok(())
 }
+
+/// Display a single list item of an update plan or summary
+fn list_item(text: String, o: &mut impl MCVMOutput) {
+	o.display(
+		MessageContents::ListItem(Box::new(MessageContents::Simple(text))),
+		MessageLevel::Important,
+	);
+}
+
+#[cfg(test)]
+mod tests {
+	use mcvm_shared::id::InstanceID;
+	use mcvm_shared::output::NoOp;
+
+	use crate::config::instance::{read_instance_config, InstanceConfig};
+
+	use super::*;
+
+	fn build_instance(packages: &[&str]) -> Instance {
+		let config: InstanceConfig = serde_json::from_value(serde_json::json!({
+			"type": "client",
+			"version": "1.20.1",
+			"modloader": "fabric",
+			"packages": packages,
+		}))
+		.unwrap();
+		read_instance_config(
+			InstanceID::from("test"),
+			config,
+			&HashMap::new(),
+			&PluginManager::new(),
+			&Paths::new_no_create().unwrap(),
+			&mut NoOp,
+		)
+		.unwrap()
+	}
+
+	#[test]
+	fn test_update_fingerprint_is_stable() {
+		let packages = [
+			"sodium",
+			"lithium",
+			"iris",
+			"modmenu",
+			"fabric-api",
+			"ferrite-core",
+			"entityculling",
+			"lambdynamiclights",
+		];
+		let fingerprint = build_instance(&packages).get_update_fingerprint();
+		assert!(fingerprint.is_some());
+		for _ in 0..10 {
+			assert_eq!(
+				build_instance(&packages).get_update_fingerprint(),
+				fingerprint
+			);
+		}
+
+		let mut reversed = packages;
+		reversed.reverse();
+		assert_eq!(
+			build_instance(&reversed).get_update_fingerprint(),
+			fingerprint
+		);
+
+		assert_ne!(
+			build_instance(&packages[1..]).get_update_fingerprint(),
+			fingerprint
+		);
+	}
+}
@@ -1,34 +1,54 @@
 use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use itertools::Itertools;
+use mcvm_core::io::persistent::PersistentData;
 use mcvm_core::net::download::get_transfer_limit;
 use mcvm_pkg::repo::PackageFlag;
 use mcvm_pkg::PkgRequest;
-use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use mcvm_shared::output::{
+	MCVMOutput, MessageContents, MessageLevel, UpdatePhase, UpdatePhaseEvent,
+};
 use mcvm_shared::pkg::{ArcPkgReq, PackageID};
 use mcvm_shared::translate;
 use mcvm_shared::versions::VersionInfo;
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 
+use crate::addon::{
+	check_addon_hashes, remove_unused_stored_addons, AddonExt, AddonLocation, AddonRequest,
+};
 use crate::instance::Instance;
-use crate::pkg::eval::{resolve, EvalConstants, EvalInput, EvalParameters};
+use crate::pkg::eval::{
+	resolve, EvalConstants, EvalData, EvalInput, EvalParameters, EvalPermissions,
+};
+use crate::util::hash::hash_sha256;
 use crate::util::select_random_n_items_from_list;
 use mcvm_shared::id::InstanceID;
 
+use super::plan::{PlannedAddon, UpdatePlan};
 use super::InstanceUpdateContext;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 
-/// Install packages on multiple instances. Returns a set of all unique packages
+/// Install packages on multiple instances. Returns a set of all unique packages.
+/// If backup is enabled, a snapshot of each affected instance will be taken before
+/// changing any of its addons, which can be rolled back to if the update fails.
+/// If plans are given, this is a dry run: the planned changes are added to the plans
+/// and no addons are downloaded, installed, or removed
 pub async fn update_instance_packages<'a, O: MCVMOutput>(
 	instances: &mut [&mut Instance],
 	constants: &EvalConstants,
 	ctx: &mut InstanceUpdateContext<'a, O>,
 	force: bool,
+	backup: bool,
+	mut plans: Option<&mut HashMap<InstanceID, UpdatePlan>>,
 ) -> anyhow::Result<HashSet<ArcPkgReq>> {
+	ctx.output
+		.display_update_phase(UpdatePhase::Packages, UpdatePhaseEvent::Start);
+
 	// Resolve dependencies
 	ctx.output.start_process();
 	ctx.output.display(
@@ -63,6 +83,9 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 
 		// Install the package on it's instances
 		let mut notices = Vec::new();
+		let mut privileged_operations = Vec::new();
+		// The instances that the package is elevated on, along with what it does on each
+		let mut elevated_instances = Vec::new();
 		for instance_id in package_instances {
 			let instance = instances
 				.iter_mut()
@@ -71,24 +94,76 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 
 			let mut params = EvalParameters::new(instance.kind.to_side());
 			params.stability = instance.config.package_stability;
+			if let Some(config) = instance.get_package_config(&package.id) {
+				params.perms = config.permissions;
+			}
+			let perms = params.perms;
 
 			let input = EvalInput { constants, params };
-			let (eval, new_tasks) = instance
-				.get_package_addon_tasks(
-					package,
-					input,
-					ctx.packages,
-					ctx.paths,
-					force,
-					ctx.client,
-					ctx.plugins,
-					ctx.output,
-				)
-				.await
-				.with_context(|| {
-					format!("Failed to get addon install tasks for package '{package}' on instance")
-				})?;
-			tasks.extend(new_tasks);
+			let eval = if let Some(plans) = &mut plans {
+				let (eval, requests) = instance
+					.get_package_addon_requests(
+						package,
+						input,
+						ctx.packages,
+						ctx.paths,
+						force,
+						ctx.client,
+						ctx.plugins,
+						ctx.output,
+					)
+					.await
+					.with_context(|| {
+						format!("Failed to evaluate package '{package}' on instance")
+					})?;
+				let plan = get_plan(plans, instance_id);
+				// Packages that are already installed the same way would not change
+				let addons: Vec<_> = eval.addon_reqs.iter().map(|x| &x.addon).collect();
+				if ctx
+					.lock
+					.is_package_changed(instance_id, &package.id, &addons)
+				{
+					plan.packages_to_install.push(package.to_string());
+				}
+				plan.addons_to_download
+					.extend(requests.into_iter().map(|x| PlannedAddon {
+						package: package.to_string(),
+						id: x.addon.id.clone(),
+						location: x.get_location().to_string(),
+					}));
+				eval
+			} else {
+				let (eval, new_tasks) = instance
+					.get_package_addon_tasks(
+						package,
+						input,
+						ctx.packages,
+						ctx.paths,
+						force,
+						ctx.client,
+						ctx.plugins,
+						ctx.output,
+					)
+					.await
+					.with_context(|| {
+						format!(
+							"Failed to get addon install tasks for package '{package}' on instance"
+						)
+					})?;
+				tasks.extend(new_tasks);
+				eval
+			};
+
+			if let EvalPermissions::Elevated = perms {
+				let mut operations = Vec::new();
+				add_privileged_operations(&eval, &mut operations);
+				for operation in &operations {
+					if !privileged_operations.contains(operation) {
+						privileged_operations.push(operation.clone());
+					}
+				}
+				elevated_instances.push((instance_id, operations));
+			}
 
 			// Add any notices to the list
 			notices.extend(
@@ -112,10 +187,61 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 				MessageLevel::Important,
 			);
 		}
+
+		// Nothing is run during a dry run, so there is nothing to confirm
+		if !elevated_instances.is_empty() && plans.is_none() {
+			let script = ctx
+				.packages
+				.load(package, ctx.paths, ctx.client, ctx.output)
+				.await
+				.with_context(|| format!("Failed to load package '{package}'"))?;
+			let script_hash = hash_sha256(script.as_bytes());
+			// Approvals are only kept in local data and never read from the lockfile
+			let mut persistent =
+				PersistentData::open(&ctx.paths.core).context("Failed to open persistent data")?;
+			// Only ask again when the package is new or what it does has changed
+			let is_approved = elevated_instances.iter().all(|(instance, operations)| {
+				persistent.is_elevated_package_approved(
+					instance,
+					&package.id,
+					&script_hash,
+					operations,
+				)
+			});
+			if !is_approved && !ctx.accept_permissions {
+				confirm_elevated_package(package, &privileged_operations, ctx)?;
+			}
+			for (instance, operations) in &elevated_instances {
+				persistent.approve_elevated_package(
+					instance,
+					&package.id,
+					&script_hash,
+					operations,
+				);
+			}
+			persistent
+				.dump(&ctx.paths.core)
+				.await
+				.context("Failed to write persistent data")?;
+		}
+	}
+
+	if let Some(plans) = plans {
+		plan_package_removals(&resolved_packages, plans, ctx);
+
+		let mut out = HashSet::new();
+		out.extend(resolved_packages.package_to_instances.keys().cloned());
+		ctx.output
+			.display_update_phase(UpdatePhase::Packages, UpdatePhaseEvent::End);
+		return Ok(out);
 	}
 
 	// Run the acquire tasks
-	run_addon_tasks(tasks, ctx.output)
+	let concurrency = ctx
+		.prefs
+		.download_concurrency
+		.unwrap_or_else(get_transfer_limit);
+	run_addon_tasks(tasks, concurrency, ctx.output)
 		.await
 		.context("Failed to acquire addons")?;
 
@@ -124,6 +250,146 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 		MessageLevel::Important,
 	);
 
+	// Back up the affected instances before their addons are changed
+	let mut backups = Vec::new();
+	if backup {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+		for instance_id in resolved_packages.instance_to_packages.keys().sorted() {
+			let instance = instances
+				.iter_mut()
+				.find(|x| &x.id == instance_id)
+				.expect("Instance should exist");
+			let snapshot = instance
+				.create_snapshot(
+					Some(format!("before-update-{timestamp}")),
+					ctx.paths,
+					ctx.output,
+				)
+				.with_context(|| {
+					format!("Failed to back up instance '{instance_id}' before updating")
+				})?;
+			backups.push((instance_id.clone(), snapshot));
+		}
+	}
+
+	let result =
+		install_and_remove_packages(instances, &resolved_packages, &evals, constants, ctx).await;
+	if let Err(e) = result {
+		if !backups.is_empty() {
+			offer_rollback(instances, &backups, ctx).context("Failed to roll back update")?;
+		}
+		return Err(e);
+	}
+
+	// Get the set of unique packages
+	let mut out = HashSet::new();
+	out.extend(resolved_packages.package_to_instances.keys().cloned());
+	ctx.output
+		.display_update_phase(UpdatePhase::Packages, UpdatePhaseEvent::End);
+
+	Ok(out)
+}
+
+/// Install the exact addons that are recorded in the lockfile for an instance, without resolving
+/// any packages. Addons are downloaded again from their recorded URLs if they aren't stored already.
+/// Fails if an addon has no recorded hash or URL, can't be downloaded, or doesn't match its hash
+pub async fn install_locked_packages<'a, O: MCVMOutput>(
+	instance: &mut Instance,
+	version_info: &VersionInfo,
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) -> anyhow::Result<()> {
+	ctx.output
+		.display_update_phase(UpdatePhase::Packages, UpdatePhaseEvent::Start);
+	let addons: Vec<_> = ctx
+		.lock
+		.get_instance_addons(&instance.id)
+		.into_iter()
+		.map(|(package, locked)| {
+			let addon = locked
+				.to_addon(PackageID::from(package))
+				.with_context(|| format!("Invalid addon in lockfile for package '{package}'"))?;
+			Ok((addon, locked.get_url().map(String::from)))
+		})
+		.collect::<anyhow::Result<_>>()?;
+
+	// Get the addons that aren't stored with the correct contents already
+	ctx.output.display(
+		MessageContents::StartProcess(translate!(ctx.output, StartAcquiringAddons)),
+		MessageLevel::Important,
+	);
+	let mut tasks = HashMap::new();
+	for (addon, url) in &addons {
+		let pkg = &addon.pkg_id;
+		if addon.hashes.is_empty() {
+			bail!(
+				"Addon '{}' of package '{pkg}' has no hash in the lockfile, so it can't be installed exactly. Update the instance without --frozen to record it",
+				addon.id
+			);
+		}
+		let path = addon.get_path(ctx.paths, &instance.id);
+		if path.exists() && check_addon_hashes(addon, &path).is_ok() {
+			continue;
+		}
+		let Some(url) = url else {
+			bail!(
+				"Addon '{}' of package '{pkg}' is not stored and has no URL in the lockfile to download it from",
+				addon.id
+			);
+		};
+
+		let request = AddonRequest::new(addon.clone(), AddonLocation::Remote(url.clone()));
+		let task = request.get_acquire_task(ctx.paths, &instance.id, ctx.client)?;
+		let context = format!(
+			"Failed to install addon '{}' of package '{pkg}' from {url} as it is recorded in the lockfile",
+			addon.id
+		);
+		tasks.insert(request.get_unique_id(&instance.id), async move {
+			task.await.context(context)
+		});
+	}
+	let concurrency = ctx
+		.prefs
+		.download_concurrency
+		.unwrap_or_else(get_transfer_limit);
+	run_addon_tasks(tasks, concurrency, ctx.output)
+		.await
+		.context("Failed to acquire addons")?;
+	ctx.output.display(
+		MessageContents::Success(translate!(ctx.output, FinishAcquiringAddons)),
+		MessageLevel::Important,
+	);
+
+	// Link the addons into this instance. The files recorded in the lockfile aren't trusted as
+	// destinations, since the lockfile may have come from somewhere else
+	for (addon, _) in &addons {
+		let worlds = instance
+			.config
+			.packages
+			.iter()
+			.find(|x| x.id == addon.pkg_id)
+			.map(|x| x.worlds.clone())
+			.unwrap_or_default();
+		instance
+			.create_addon(addon, &worlds, ctx.paths, version_info)
+			.with_context(|| format!("Failed to install addon '{}'", addon.id))?;
+	}
+	ctx.output
+		.display_update_phase(UpdatePhase::Packages, UpdatePhaseEvent::End);
+
+	Ok(())
+}
+
+/// Installs resolved packages on their instances and removes the ones that are no longer used
+async fn install_and_remove_packages<'a, O: MCVMOutput>(
+	instances: &mut [&mut Instance],
+	resolved_packages: &ResolvedPackages,
+	evals: &HashMap<(&ArcPkgReq, &InstanceID), EvalData<'_>>,
+	constants: &EvalConstants,
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) -> anyhow::Result<()> {
 	// Install each package one after another onto all of its instances
 	ctx.output.display(
 		MessageContents::StartProcess(translate!(ctx.output, StartInstallingPackages)),
@@ -174,16 +440,16 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 	}
 
 	// Use the instance-package map to remove unused packages and addons
-	for (instance_id, packages) in resolved_packages.instance_to_packages {
+	for (instance_id, packages) in &resolved_packages.instance_to_packages {
 		let instance = instances
 			.iter()
-			.find(|x| x.id == instance_id)
+			.find(|x| &x.id == instance_id)
 			.expect("Instance should exist");
 
 		let files_to_remove = ctx
 			.lock
 			.remove_unused_packages(
-				&instance_id,
+				instance_id,
 				&packages
 					.iter()
 					.map(|x| x.id.clone())
@@ -203,22 +469,155 @@ pub async fn update_instance_packages<'a, O: MCVMOutput>(
 		}
 	}
 
-	// Get the set of unique packages
-	let mut out = HashSet::new();
-	out.extend(resolved_packages.package_to_instances.keys().cloned());
+	// Stored addons can be shared, so only remove them once no instance uses them
+	remove_unused_stored_addons(ctx.paths, ctx.lock)
+		.context("Failed to remove unused stored addons")?;
 
-	Ok(out)
+	Ok(())
+}
+
+/// Adds the packages and addon files that would be removed from each instance to their plans
+fn plan_package_removals<'a, O: MCVMOutput>(
+	resolved_packages: &ResolvedPackages,
+	plans: &mut HashMap<InstanceID, UpdatePlan>,
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) {
+	for (instance_id, packages) in &resolved_packages.instance_to_packages {
+		let used: Vec<PackageID> = packages.iter().map(|x| x.id.clone()).collect();
+		let plan = get_plan(plans, instance_id);
+		for (package, files) in ctx.lock.get_unused_packages(instance_id, &used) {
+			plan.packages_to_remove.push(package.to_string());
+			plan.files_to_remove.extend(files);
+		}
+	}
+}
+
+/// Adds the operations from an evaluation that need elevated permissions or could be abused
+/// to the list, so that they can be shown to the user before the package is installed
+fn add_privileged_operations(eval: &EvalData, operations: &mut Vec<String>) {
+	let new_operations = eval
+		.addon_reqs
+		.iter()
+		.map(|x| match x.get_location() {
+			AddonLocation::Remote(url) => format!("Download '{}' from {url}", x.addon.id),
+			AddonLocation::Local(path) => {
+				format!("Copy '{}' from {}", x.addon.id, path.display())
+			}
+		})
+		.chain(
+			eval.commands
+				.iter()
+				.map(|x| format!("Run the command '{}'", x.join(" "))),
+		);
+	for operation in new_operations {
+		if !operations.contains(&operation) {
+			operations.push(operation);
+		}
+	}
+}
+
+/// Shows the user what a package with elevated permissions will do and asks them to confirm
+/// installing it, failing if they decline
+fn confirm_elevated_package<'a, O: MCVMOutput>(
+	package: &ArcPkgReq,
+	operations: &[String],
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) -> anyhow::Result<()> {
+	ctx.output.display(
+		format_package_update_message(
+			package,
+			None,
+			MessageContents::Warning(
+				"This package is configured with elevated permissions and will:".into(),
+			),
+		),
+		MessageLevel::Important,
+	);
+	if operations.is_empty() {
+		ctx.output.display(
+			MessageContents::ListItem(Box::new(MessageContents::Simple(
+				"Not do anything that needs elevated permissions".into(),
+			))),
+			MessageLevel::Important,
+		);
+	}
+	for operation in operations {
+		ctx.output.display(
+			MessageContents::ListItem(Box::new(MessageContents::Simple(operation.clone()))),
+			MessageLevel::Important,
+		);
+	}
+
+	let accepted = ctx.output.prompt_yes_no(
+		false,
+		MessageContents::Simple(format!("Install package '{package}'?")),
+	)?;
+	if !accepted {
+		bail!("Installation of package '{package}' was cancelled");
+	}
+
+	Ok(())
+}
+
+/// Gets the plan for an instance, creating it if it doesn't exist
+fn get_plan<'p>(
+	plans: &'p mut HashMap<InstanceID, UpdatePlan>,
+	instance_id: &InstanceID,
+) -> &'p mut UpdatePlan {
+	plans
+		.entry(instance_id.clone())
+		.or_insert_with(|| UpdatePlan {
+			instance: instance_id.to_string(),
+			..Default::default()
+		})
+}
+
+/// Asks the user whether to restore the snapshots that were taken before a failed update
+fn offer_rollback<'a, O: MCVMOutput>(
+	instances: &mut [&mut Instance],
+	backups: &[(InstanceID, String)],
+	ctx: &mut InstanceUpdateContext<'a, O>,
+) -> anyhow::Result<()> {
+	let rollback = ctx.output.prompt_yes_no(
+		true,
+		MessageContents::Simple(
+			"Updating packages failed. Restore the instances to how they were before the update?"
+				.into(),
+		),
+	)?;
+	if !rollback {
+		return Ok(());
+	}
+
+	for (instance_id, snapshot) in backups {
+		let instance = instances
+			.iter_mut()
+			.find(|x| &x.id == instance_id)
+			.expect("Instance should exist");
+		instance
+			.restore_snapshot(snapshot, ctx.paths, ctx.output)
+			.with_context(|| format!("Failed to restore instance '{instance_id}'"))?;
+		ctx.output.display(
+			MessageContents::Success(format!(
+				"Restored instance '{instance_id}' from snapshot '{snapshot}'"
+			)),
+			MessageLevel::Important,
+		);
+	}
+
+	Ok(())
 }
 
 /// Evaluates addon acquire tasks efficiently with a progress display to the user
 async fn run_addon_tasks(
 	tasks: HashMap<String, impl Future<Output = anyhow::Result<()>> + Send + 'static>,
+	concurrency: usize,
 	o: &mut impl MCVMOutput,
 ) -> anyhow::Result<()> {
 	let total_count = tasks.len();
 	let mut task_set = JoinSet::new();
 
-	let sem = Arc::new(Semaphore::new(get_transfer_limit()));
+	let sem = Arc::new(Semaphore::new(concurrency));
 	for task in tasks.into_values() {
 		let permit = sem.clone().acquire_owned().await;
 		let task = async move {
@@ -236,10 +635,13 @@ async fn run_addon_tasks(
 			.context("Failed to acquire addon")?;
 
 		// Update progress bar
-		let progress = MessageContents::Progress {
-			current: (total_count - task_set.len()) as u32,
-			total: total_count as u32,
-		};
+		let current = (total_count - task_set.len()) as u32;
+		let total = total_count as u32;
+		o.display_update_phase(
+			UpdatePhase::Packages,
+			UpdatePhaseEvent::Progress { current, total },
+		);
+		let progress = MessageContents::Progress { current, total };
 
 		o.display(progress, MessageLevel::Important);
 	}
@@ -259,10 +661,13 @@ async fn resolve_and_batch<'a, O: MCVMOutput>(
 ) -> anyhow::Result<ResolvedPackages> {
 	let mut batched: HashMap<ArcPkgReq, Vec<InstanceID>> = HashMap::new();
 	let mut resolved = HashMap::new();
+	let mut conflicts = Vec::new();
 
 	for instance in instances {
 		let mut params = EvalParameters::new(instance.kind.to_side());
 		params.stability = instance.config.package_stability;
+		params.allow_conflicts = ctx.prefs.allow_package_conflicts;
+		let allow_conflicts = params.allow_conflicts;
 
 		let instance_pkgs = instance.get_configured_packages();
 		let instance_resolved = resolve(
@@ -282,6 +687,22 @@ async fn resolve_and_batch<'a, O: MCVMOutput>(
 				instance.id
 			)
 		})?;
+
+		for conflict in &instance_resolved.conflicts {
+			let message = format!(
+				"Package '{}' conflicts with package '{}' in instance '{}'",
+				conflict.package.debug_sources(),
+				conflict.conflict,
+				instance.id
+			);
+			if allow_conflicts {
+				ctx.output
+					.display(MessageContents::Warning(message), MessageLevel::Important);
+			} else {
+				conflicts.push(message);
+			}
+		}
+
 		for package in &instance_resolved.packages {
 			if let Some(entry) = batched.get_mut(package) {
 				entry.push(instance.id.clone());
@@ -292,6 +713,10 @@ async fn resolve_and_batch<'a, O: MCVMOutput>(
 		resolved.insert(instance.id.clone(), instance_resolved.packages);
 	}
 
+	if !conflicts.is_empty() {
+		bail!("Conflicting packages were found:\n{}", conflicts.join("\n"));
+	}
+
 	Ok(ResolvedPackages {
 		package_to_instances: batched,
 		instance_to_packages: resolved,
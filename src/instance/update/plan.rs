@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use serde::Serialize;
+
+use super::list_item;
+
+/// The actions that an instance update would take, collected during a dry run
+/// instead of being performed
+#[derive(Debug, Default, Serialize)]
+pub struct UpdatePlan {
+	/// The ID of the instance
+	pub instance: String,
+	/// The Minecraft version that the instance was last updated to
+	pub current_version: Option<String>,
+	/// The Minecraft version that the instance would be updated to
+	pub new_version: String,
+	/// Whether the instance has not been updated before and would be created
+	pub first_update: bool,
+	/// The Java installation that would be downloaded, if any
+	pub java: Option<PlannedJava>,
+	/// Packages that would be installed or updated
+	pub packages_to_install: Vec<String>,
+	/// Packages that would be removed because they are no longer used
+	pub packages_to_remove: Vec<String>,
+	/// Addon files that would be downloaded or copied
+	pub addons_to_download: Vec<PlannedAddon>,
+	/// Addon files that would be removed from the instance
+	pub files_to_remove: Vec<PathBuf>,
+}
+
+/// A Java installation that would be downloaded
+#[derive(Debug, Serialize)]
+pub struct PlannedJava {
+	/// The kind of the installation
+	pub installation: String,
+	/// The major version of Java
+	pub major_version: String,
+}
+
+/// An addon that would be downloaded
+#[derive(Debug, Serialize)]
+pub struct PlannedAddon {
+	/// The package that the addon is from
+	pub package: String,
+	/// The ID of the addon
+	pub id: String,
+	/// The URL or local path that the addon would be acquired from
+	pub location: String,
+}
+
+impl UpdatePlan {
+	/// Whether the update would change the Minecraft version of the instance
+	pub fn changes_version(&self) -> bool {
+		self.current_version
+			.as_ref()
+			.is_some_and(|x| x != &self.new_version)
+	}
+
+	/// Whether the update would not do anything
+	pub fn is_empty(&self) -> bool {
+		!self.first_update
+			&& !self.changes_version()
+			&& self.java.is_none()
+			&& self.packages_to_install.is_empty()
+			&& self.packages_to_remove.is_empty()
+			&& self.addons_to_download.is_empty()
+			&& self.files_to_remove.is_empty()
+	}
+
+	/// Display the plan as a report
+	pub fn display(&self, o: &mut impl MCVMOutput) {
+		o.display(
+			MessageContents::Header(format!("Planned update for instance '{}'", self.instance)),
+			MessageLevel::Important,
+		);
+
+		if self.first_update {
+			list_item(
+				format!("Create the instance with Minecraft {}", self.new_version),
+				o,
+			);
+		} else if let (true, Some(current)) = (self.changes_version(), &self.current_version) {
+			list_item(
+				format!(
+					"Change the Minecraft version from {current} to {} and remove the old game files",
+					self.new_version
+				),
+				o,
+			);
+		}
+		if let Some(java) = &self.java {
+			list_item(
+				format!("Download {} Java {}", java.installation, java.major_version),
+				o,
+			);
+		}
+		for package in &self.packages_to_install {
+			list_item(format!("Install package {package}"), o);
+		}
+		for package in &self.packages_to_remove {
+			list_item(format!("Remove package {package}"), o);
+		}
+		for addon in &self.addons_to_download {
+			list_item(
+				format!(
+					"Download addon '{}' from package {} ({})",
+					addon.id, addon.package, addon.location
+				),
+				o,
+			);
+		}
+		for file in &self.files_to_remove {
+			list_item(format!("Remove file {}", file.display()), o);
+		}
+
+		if self.is_empty() {
+			o.display(
+				MessageContents::Success("Everything is up to date".into()),
+				MessageLevel::Important,
+			);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_empty_plan() {
+		let mut plan = UpdatePlan {
+			instance: "test".into(),
+			current_version: Some("1.20.1".into()),
+			new_version: "1.20.1".into(),
+			..Default::default()
+		};
+		assert!(plan.is_empty());
+
+		plan.new_version = "1.21".into();
+		assert!(plan.changes_version());
+		assert!(!plan.is_empty());
+
+		plan.new_version = "1.20.1".into();
+		plan.packages_to_remove.push("sodium".into());
+		assert!(!plan.is_empty());
+	}
+}
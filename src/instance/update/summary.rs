@@ -0,0 +1,217 @@
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
+use serde::Serialize;
+
+use crate::instance::{InstKind, Instance};
+use crate::io::lock::Lockfile;
+
+use super::list_item;
+
+/// The state of an instance in the lockfile, used to find what an update changed
+#[derive(Debug, Default, Clone)]
+pub struct InstanceState {
+	/// The Minecraft version of the instance
+	pub version: Option<String>,
+	/// The client or server type of the instance
+	pub loader: Option<String>,
+	/// The installed packages, sorted
+	pub packages: Vec<String>,
+}
+
+impl InstanceState {
+	/// Read the state of an instance from the lockfile
+	pub fn read(instance: &str, lock: &Lockfile) -> Self {
+		Self {
+			version: lock.get_instance_version(instance).map(String::from),
+			loader: lock.get_instance_loader(instance).map(String::from),
+			packages: lock
+				.get_instance_packages(instance)
+				.into_iter()
+				.map(String::from)
+				.collect(),
+		}
+	}
+}
+
+/// A summary of what an update changed on an instance
+#[derive(Debug, Serialize)]
+pub struct UpdateSummary {
+	/// The ID of the instance
+	pub instance: String,
+	/// The Minecraft version before the update
+	pub old_version: Option<String>,
+	/// The Minecraft version after the update
+	pub new_version: Option<String>,
+	/// The client or server type before the update
+	pub old_loader: Option<String>,
+	/// The client or server type after the update
+	pub new_loader: Option<String>,
+	/// Packages that were installed by the update
+	pub added_packages: Vec<String>,
+	/// Packages that were removed by the update
+	pub removed_packages: Vec<String>,
+	/// Whether the update was skipped because the instance hadn't changed
+	pub skipped: bool,
+}
+
+impl UpdateSummary {
+	/// Compare the states of an instance from before and after an update
+	pub fn new(instance: &str, before: InstanceState, after: InstanceState) -> Self {
+		let added_packages = after
+			.packages
+			.iter()
+			.filter(|x| !before.packages.contains(x))
+			.cloned()
+			.collect();
+		let removed_packages = before
+			.packages
+			.iter()
+			.filter(|x| !after.packages.contains(x))
+			.cloned()
+			.collect();
+
+		Self {
+			instance: instance.to_string(),
+			old_version: before.version,
+			new_version: after.version,
+			old_loader: before.loader,
+			new_loader: after.loader,
+			added_packages,
+			removed_packages,
+			skipped: false,
+		}
+	}
+
+	/// Create the summary for an instance that wasn't updated because it hadn't changed
+	pub fn skipped(instance: &str, state: InstanceState) -> Self {
+		Self {
+			skipped: true,
+			..Self::new(instance, state.clone(), state)
+		}
+	}
+
+	/// Whether the update changed the Minecraft version of an instance that already existed
+	pub fn changes_version(&self) -> bool {
+		self.old_version.is_some() && self.old_version != self.new_version
+	}
+
+	/// Whether the update changed the loader of an instance that already had one
+	pub fn changes_loader(&self) -> bool {
+		self.old_loader.is_some() && self.old_loader != self.new_loader
+	}
+
+	/// Whether the update didn't change anything
+	pub fn is_empty(&self) -> bool {
+		self.old_version.is_some()
+			&& !self.changes_version()
+			&& !self.changes_loader()
+			&& self.added_packages.is_empty()
+			&& self.removed_packages.is_empty()
+	}
+
+	/// Display the summary as a report
+	pub fn display(&self, o: &mut impl MCVMOutput) {
+		o.display(
+			MessageContents::Header(format!("Changes to instance '{}'", self.instance)),
+			MessageLevel::Important,
+		);
+
+		let new_version = self.new_version.as_deref().unwrap_or("unknown");
+		match &self.old_version {
+			None => list_item(format!("Created with Minecraft {new_version}"), o),
+			Some(old_version) if self.changes_version() => list_item(
+				format!("Changed the Minecraft version from {old_version} to {new_version}"),
+				o,
+			),
+			_ => {}
+		}
+		if let (true, Some(old_loader), Some(new_loader)) =
+			(self.changes_loader(), &self.old_loader, &self.new_loader)
+		{
+			list_item(
+				format!("Changed the loader from {old_loader} to {new_loader}"),
+				o,
+			);
+		}
+		for package in &self.added_packages {
+			list_item(format!("Added package {package}"), o);
+		}
+		for package in &self.removed_packages {
+			list_item(format!("Removed package {package}"), o);
+		}
+
+		if self.changes_version() {
+			o.display(
+				MessageContents::Warning(format!(
+					"The Minecraft version of instance '{}' changed. Back up your worlds before playing, and check that your mods and other addons support the new version",
+					self.instance
+				)),
+				MessageLevel::Important,
+			);
+		} else if self.skipped {
+			o.display(
+				MessageContents::Success(
+					"Skipped, as the instance hasn't changed since it was last updated".into(),
+				),
+				MessageLevel::Important,
+			);
+		} else if self.is_empty() {
+			o.display(
+				MessageContents::Success("Nothing changed".into()),
+				MessageLevel::Important,
+			);
+		}
+	}
+}
+
+impl Instance {
+	/// Get the name of the client or server type of this instance
+	pub fn get_loader_name(&self) -> String {
+		match &self.kind {
+			InstKind::Client { .. } => self.config.modifications.client_type.to_string(),
+			InstKind::Server { .. } => self.config.modifications.server_type.to_string(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_update_summary() {
+		let before = InstanceState {
+			version: Some("1.20.1".into()),
+			loader: Some("fabric".into()),
+			packages: vec!["lithium".into(), "sodium".into()],
+		};
+		let after = InstanceState {
+			version: Some("1.21".into()),
+			loader: Some("fabric".into()),
+			packages: vec!["iris".into(), "sodium".into()],
+		};
+		let summary = UpdateSummary::new("test", before.clone(), after);
+		assert!(summary.changes_version());
+		assert!(!summary.changes_loader());
+		assert_eq!(summary.added_packages, vec!["iris".to_string()]);
+		assert_eq!(summary.removed_packages, vec!["lithium".to_string()]);
+
+		let summary = UpdateSummary::new("test", before.clone(), before.clone());
+		assert!(summary.is_empty());
+
+		// Creating an instance is a change, but not a version change
+		let summary = UpdateSummary::new(
+			"test",
+			InstanceState::default(),
+			InstanceState {
+				version: Some("1.21".into()),
+				..Default::default()
+			},
+		);
+		assert!(!summary.changes_version());
+		assert!(!summary.is_empty());
+
+		let summary = UpdateSummary::skipped("test", before);
+		assert!(summary.skipped);
+		assert!(summary.is_empty());
+	}
+}
@@ -1,10 +1,10 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 
 use anyhow::{anyhow, bail, Context};
 use mcvm_core::io::{json_from_file, json_to_file_pretty};
-use mcvm_shared::output::{MCVMOutput, MessageContents};
+use mcvm_shared::output::{MCVMOutput, MessageContents, MessageLevel};
 use mcvm_shared::translate;
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +12,7 @@ use mcvm_shared::addon::{Addon, AddonKind};
 use mcvm_shared::pkg::{PackageAddonOptionalHashes, PackageID};
 
 use super::paths::Paths;
+use crate::addon::get_addon_store_key;
 
 /// A file that remembers important info like what files and packages are currently installed
 #[derive(Debug)]
@@ -19,13 +20,26 @@ pub struct Lockfile {
 	contents: LockfileContents,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
+/// The current version of the lockfile format
+const CURRENT_LOCKFILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
 #[serde(default)]
 struct LockfileContents {
+	/// The format version of the lockfile. Lockfiles from before
+	/// versioning was added will not have this field and are treated as version 0
+	#[serde(default)]
+	version: u32,
 	packages: HashMap<String, HashMap<String, LockfilePackage>>,
 	instances: HashMap<String, LockfileInstance>,
 	/// Instances that have done their first update
 	created_instances: HashSet<String>,
+	/// Datapacks installed on instances outside of packages
+	datapacks: HashMap<String, HashMap<String, LockfileDatapack>>,
+	/// Resource packs installed on instances outside of packages
+	resource_packs: HashMap<String, HashMap<String, LockfileResourcePack>>,
+	/// Shader packs installed on instances outside of packages
+	shader_packs: HashMap<String, HashMap<String, LockfileShaderPack>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -34,6 +48,51 @@ struct LockfileInstance {
 	version: String,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	paper_build: Option<u16>,
+	/// The client or server type that the instance was last updated with
+	#[serde(skip_serializing_if = "Option::is_none")]
+	loader: Option<String>,
+	/// A fingerprint of the configuration that the instance's packages were last updated with
+	#[serde(skip_serializing_if = "Option::is_none")]
+	fingerprint: Option<String>,
+}
+
+/// A datapack that was installed on an instance directly, stored in the lockfile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockfileDatapack {
+	/// Where the datapack came from, such as a Modrinth project or a local file
+	pub source: String,
+	/// The version of the datapack, if it is known
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	/// The paths to the datapack files in the instance
+	pub files: Vec<String>,
+}
+
+/// A resource pack that was installed on an instance directly, stored in the lockfile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockfileResourcePack {
+	/// Where the resource pack came from, such as a Modrinth project or a local file
+	pub source: String,
+	/// The version of the resource pack, if it is known
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	/// The path to the resource pack file in the instance
+	pub file: String,
+	/// Whether the resource pack was enabled in the options.txt when it was installed
+	#[serde(default)]
+	pub enabled: bool,
+}
+
+/// A shader pack that was installed on an instance directly, stored in the lockfile
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LockfileShaderPack {
+	/// Where the shader pack came from, such as a Modrinth project or a local file
+	pub source: String,
+	/// The version of the shader pack, if it is known
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+	/// The path to the shader pack file in the instance
+	pub file: String,
 }
 
 /// Package stored in the lockfile
@@ -56,6 +115,40 @@ pub struct LockfileAddon {
 	#[serde(default)]
 	#[serde(skip_serializing_if = "PackageAddonOptionalHashes::is_empty")]
 	hashes: PackageAddonOptionalHashes,
+	/// The URL that the addon was downloaded from, if it is remote
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	url: Option<String>,
+	/// The state of the addon file when its hashes were computed
+	#[serde(default)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	hashed_file: Option<HashedFileInfo>,
+}
+
+/// The size and modification time of a file when it was hashed, used to skip
+/// hashing the file again if it has not changed since
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct HashedFileInfo {
+	/// The size of the file in bytes
+	pub size: u64,
+	/// The modification time of the file, in milliseconds since the Unix epoch
+	pub modified: u64,
+}
+
+impl HashedFileInfo {
+	/// Get the info for a file on disk
+	pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+		let meta = fs::metadata(path).context("Failed to get file metadata")?;
+		let modified = meta
+			.modified()
+			.context("Failed to get file modification time")?
+			.duration_since(std::time::UNIX_EPOCH)
+			.unwrap_or_default();
+		Ok(Self {
+			size: meta.len(),
+			modified: modified.as_millis() as u64,
+		})
+	}
 }
 
 impl LockfileAddon {
@@ -76,25 +169,81 @@ impl LockfileAddon {
 			kind: addon.kind.to_string(),
 			version: addon.version.clone(),
 			hashes: addon.hashes.clone(),
+			url: None,
+			hashed_file: None,
 		}
 	}
 
 	/// Converts this LockfileAddon to an Addon
 	pub fn to_addon(&self, pkg_id: PackageID) -> anyhow::Result<Addon> {
+		let file_name = self
+			.file_name
+			.clone()
+			.expect("Filename should have been filled in by migration");
+		// The file name is joined onto instance directories, so it can't be allowed to leave them
+		let mut components = Path::new(&file_name).components();
+		let is_plain = matches!(components.next(), Some(Component::Normal(x)) if x == file_name.as_str())
+			&& components.next().is_none();
+		if !is_plain {
+			bail!("Invalid addon file name '{file_name}'");
+		}
+
 		Ok(Addon {
 			kind: AddonKind::parse_from_str(&self.kind)
 				.ok_or(anyhow!("Invalid addon kind '{}'", self.kind))?,
 			id: self.id.clone(),
-			file_name: self
-				.file_name
-				.clone()
-				.expect("Filename should have been filled in or fixed"),
+			file_name,
 			pkg_id,
 			version: self.version.clone(),
 			hashes: self.hashes.clone(),
 		})
 	}
 
+	/// Get the version of this addon
+	pub fn get_version(&self) -> Option<&str> {
+		self.version.as_deref()
+	}
+
+	/// Get the hashes stored for this addon
+	pub fn get_hashes(&self) -> &PackageAddonOptionalHashes {
+		&self.hashes
+	}
+
+	/// Set the hashes stored for this addon
+	pub fn set_hashes(&mut self, hashes: PackageAddonOptionalHashes) {
+		self.hashes = hashes;
+	}
+
+	/// Get the stored SHA-256 hash of this addon, but only if the file has not
+	/// changed since it was hashed
+	pub fn get_unchanged_sha256(&self, file: &HashedFileInfo) -> Option<&str> {
+		if self.hashed_file.as_ref() == Some(file) {
+			self.hashes.sha256.as_deref()
+		} else {
+			None
+		}
+	}
+
+	/// Set the state of the addon file that the stored hashes were computed from
+	pub fn set_hashed_file(&mut self, file: HashedFileInfo) {
+		self.hashed_file = Some(file);
+	}
+
+	/// Get the URL that this addon was downloaded from
+	pub fn get_url(&self) -> Option<&str> {
+		self.url.as_deref()
+	}
+
+	/// Set the URL that this addon was downloaded from
+	pub fn set_url(&mut self, url: String) {
+		self.url = Some(url);
+	}
+
+	/// Get the paths to the files of this addon in the instance
+	pub fn get_files(&self) -> &[String] {
+		&self.files
+	}
+
 	/// Remove this addon
 	pub fn remove(&self) -> anyhow::Result<()> {
 		for file in self.files.iter() {
@@ -108,15 +257,85 @@ impl LockfileAddon {
 	}
 }
 
+impl Default for LockfileContents {
+	fn default() -> Self {
+		Self {
+			version: CURRENT_LOCKFILE_VERSION,
+			packages: HashMap::new(),
+			instances: HashMap::new(),
+			created_instances: HashSet::new(),
+			datapacks: HashMap::new(),
+			resource_packs: HashMap::new(),
+			shader_packs: HashMap::new(),
+		}
+	}
+}
+
+/// A single migration step that upgrades the lockfile format by one version
+type LockfileMigration = fn(&mut LockfileContents);
+
+/// Reads the contents of the lockfile, falling back to the backup if the lockfile is missing or corrupt
+fn read_lockfile_contents(
+	path: &Path,
+	backup_path: &Path,
+	o: &mut impl MCVMOutput,
+) -> anyhow::Result<LockfileContents> {
+	if path.exists() {
+		match json_from_file(path) {
+			Ok(contents) => Ok(contents),
+			Err(e) if backup_path.exists() => {
+				o.display(
+					MessageContents::Warning(translate!(
+						o,
+						CorruptLockfileWarning,
+						"error" = &format!("{e:#}")
+					)),
+					MessageLevel::Important,
+				);
+				json_from_file(backup_path)
+					.with_context(|| format!("Failed to open lockfile: {e:#}"))
+					.context("Failed to open lockfile backup")
+			}
+			Err(e) => Err(e).context("Failed to open lockfile"),
+		}
+	} else if backup_path.exists() {
+		json_from_file(backup_path).context("Failed to open lockfile backup")
+	} else {
+		Ok(LockfileContents::default())
+	}
+}
+
+/// Migration steps for the lockfile, where the step at index N upgrades from version N to N + 1
+const LOCKFILE_MIGRATIONS: [LockfileMigration; CURRENT_LOCKFILE_VERSION as usize] =
+	[migrate_v0_to_v1];
+
 impl LockfileContents {
-	/// Fix changes in lockfile format
-	pub fn fix(&mut self) {
-		for (.., instance) in &mut self.packages {
-			for (.., package) in instance {
-				for addon in &mut package.addons {
-					if addon.file_name.is_none() {
-						addon.file_name = Some(addon.id.clone())
-					}
+	/// Migrate the lockfile from the stored version to the current version
+	pub fn migrate(&mut self) -> anyhow::Result<()> {
+		if self.version > CURRENT_LOCKFILE_VERSION {
+			bail!(
+				"Lockfile version {} is newer than the supported version {}. Please update mcvm",
+				self.version,
+				CURRENT_LOCKFILE_VERSION
+			);
+		}
+
+		for migration in &LOCKFILE_MIGRATIONS[self.version as usize..] {
+			migration(self);
+			self.version += 1;
+		}
+
+		Ok(())
+	}
+}
+
+/// Fills in addon filenames, which were not stored before version 1
+fn migrate_v0_to_v1(contents: &mut LockfileContents) {
+	for (.., instance) in &mut contents.packages {
+		for (.., package) in instance {
+			for addon in &mut package.addons {
+				if addon.file_name.is_none() {
+					addon.file_name = Some(addon.id.clone())
 				}
 			}
 		}
@@ -124,15 +343,13 @@ impl LockfileContents {
 }
 
 impl Lockfile {
-	/// Open the lockfile
-	pub fn open(paths: &Paths) -> anyhow::Result<Self> {
-		let path = Self::get_path(paths);
-		let mut contents = if path.exists() {
-			json_from_file(path).context("Failed to open lockfile")?
-		} else {
-			LockfileContents::default()
-		};
-		contents.fix();
+	/// Open the lockfile. If it is corrupt, the backup copy is loaded instead
+	pub fn open(paths: &Paths, o: &mut impl MCVMOutput) -> anyhow::Result<Self> {
+		let mut contents =
+			read_lockfile_contents(&Self::get_path(paths), &Self::get_backup_path(paths), o)?;
+		contents
+			.migrate()
+			.context("Failed to migrate lockfile to the current version")?;
 		Ok(Self { contents })
 	}
 
@@ -141,10 +358,23 @@ impl Lockfile {
 		paths.internal.join("lock.json")
 	}
 
-	/// Finish using the lockfile and write to the disk
+	/// Get the path to the backup copy of the lockfile
+	pub fn get_backup_path(paths: &Paths) -> PathBuf {
+		paths.internal.join("lock.json.bak")
+	}
+
+	/// Finish using the lockfile and write to the disk.
+	/// The contents are written to a temporary file first and then moved over the
+	/// real lockfile so that an interrupted write can't leave it truncated
 	pub fn finish(&mut self, paths: &Paths) -> anyhow::Result<()> {
-		json_to_file_pretty(Self::get_path(paths), &self.contents)
-			.context("Failed to write to lockfile")?;
+		let path = Self::get_path(paths);
+		let tmp_path = paths.internal.join("lock.json.tmp");
+		json_to_file_pretty(&tmp_path, &self.contents)
+			.context("Failed to write to temporary lockfile")?;
+		fs::rename(&tmp_path, &path).context("Failed to move temporary lockfile into place")?;
+
+		// Keep a backup of the last successful write
+		fs::copy(&path, Self::get_backup_path(paths)).context("Failed to back up lockfile")?;
 
 		Ok(())
 	}
@@ -234,6 +464,44 @@ impl Lockfile {
 		Ok(files_to_remove)
 	}
 
+	/// Gets an addon of a package installed on an instance
+	pub fn get_addon(&self, instance: &str, package: &str, addon: &str) -> Option<&LockfileAddon> {
+		self.contents
+			.packages
+			.get(instance)?
+			.get(package)?
+			.addons
+			.iter()
+			.find(|x| x.id == addon)
+	}
+
+	/// Checks if the addons of a package are different from the ones that are installed
+	/// on an instance, or if the package is not installed on it at all
+	pub fn is_package_changed(&self, instance: &str, package: &str, addons: &[&Addon]) -> bool {
+		let Some(pkg) = self
+			.contents
+			.packages
+			.get(instance)
+			.and_then(|x| x.get(package))
+		else {
+			return true;
+		};
+		if pkg.addons.len() != addons.len() {
+			return true;
+		}
+
+		addons.iter().any(|addon| {
+			let Some(current) = pkg.addons.iter().find(|x| x.id == addon.id) else {
+				return true;
+			};
+			// Hashes are only compared when the package gives them
+			current.file_name.as_deref() != Some(addon.file_name.as_str())
+				|| current.kind != addon.kind.to_string()
+				|| current.version != addon.version
+				|| (!addon.hashes.is_empty() && current.hashes != addon.hashes)
+		})
+	}
+
 	/// Remove any unused packages for an instance.
 	/// Returns any addon files that need to be removed from the instance.
 	pub fn remove_unused_packages(
@@ -241,27 +509,53 @@ impl Lockfile {
 		instance: &str,
 		used_packages: &[PackageID],
 	) -> anyhow::Result<Vec<PathBuf>> {
-		if let Some(inst) = self.contents.packages.get_mut(instance) {
-			let mut pkgs_to_remove = Vec::new();
-			for (pkg, ..) in inst.iter() {
-				if !used_packages.contains(&PackageID::from(pkg.clone())) {
-					pkgs_to_remove.push(pkg.clone());
-				}
-			}
+		let unused: Vec<String> = self
+			.get_unused_packages(instance, used_packages)
+			.into_iter()
+			.map(|(pkg, ..)| pkg.to_string())
+			.collect();
+		let Some(inst) = self.contents.packages.get_mut(instance) else {
+			return Ok(vec![]);
+		};
 
-			let mut files_to_remove = Vec::new();
-			for pkg_id in pkgs_to_remove {
-				if let Some(pkg) = inst.remove(&pkg_id) {
-					for addon in pkg.addons {
-						files_to_remove.extend(addon.files.iter().map(PathBuf::from));
-					}
+		let mut files_to_remove = Vec::new();
+		for pkg_id in unused {
+			if let Some(pkg) = inst.remove(&pkg_id) {
+				for addon in pkg.addons {
+					files_to_remove.extend(addon.files.iter().map(PathBuf::from));
 				}
 			}
-
-			Ok(files_to_remove)
-		} else {
-			Ok(vec![])
 		}
+
+		Ok(files_to_remove)
+	}
+
+	/// Get the packages installed on an instance that are not in the list of used packages,
+	/// along with the addon files that removing them would remove, without changing anything
+	pub fn get_unused_packages(
+		&self,
+		instance: &str,
+		used_packages: &[PackageID],
+	) -> Vec<(&str, Vec<PathBuf>)> {
+		let Some(inst) = self.contents.packages.get(instance) else {
+			return Vec::new();
+		};
+
+		let mut out: Vec<_> = inst
+			.iter()
+			.filter(|(pkg, ..)| !used_packages.contains(&PackageID::from(pkg.to_string())))
+			.map(|(pkg, locked)| {
+				let files = locked
+					.addons
+					.iter()
+					.flat_map(|x| x.files.iter().map(PathBuf::from))
+					.collect();
+				(pkg.as_str(), files)
+			})
+			.collect();
+		out.sort_by_key(|x| x.0);
+
+		out
 	}
 
 	/// Updates an instance in the lockfile. Returns true if the version has changed.
@@ -279,6 +573,8 @@ impl Lockfile {
 				LockfileInstance {
 					version: version.to_owned(),
 					paper_build: None,
+					loader: None,
+					fingerprint: None,
 				},
 			);
 
@@ -286,6 +582,44 @@ impl Lockfile {
 		}
 	}
 
+	/// Get the version of an instance that was stored when it was last updated
+	pub fn get_instance_version(&self, instance: &str) -> Option<&str> {
+		self.contents
+			.instances
+			.get(instance)
+			.map(|x| x.version.as_str())
+	}
+
+	/// Updates the loader that an instance was updated with
+	pub fn update_instance_loader(&mut self, instance: &str, loader: &str) {
+		if let Some(instance) = self.contents.instances.get_mut(instance) {
+			instance.loader = Some(loader.to_owned());
+		}
+	}
+
+	/// Get the loader of an instance that was stored when it was last updated
+	pub fn get_instance_loader(&self, instance: &str) -> Option<&str> {
+		self.contents
+			.instances
+			.get(instance)
+			.and_then(|x| x.loader.as_deref())
+	}
+
+	/// Updates the fingerprint of the configuration that an instance's packages were updated with
+	pub fn update_instance_fingerprint(&mut self, instance: &str, fingerprint: &str) {
+		if let Some(instance) = self.contents.instances.get_mut(instance) {
+			instance.fingerprint = Some(fingerprint.to_owned());
+		}
+	}
+
+	/// Get the fingerprint of the configuration that an instance's packages were last updated with
+	pub fn get_instance_fingerprint(&self, instance: &str) -> Option<&str> {
+		self.contents
+			.instances
+			.get(instance)
+			.and_then(|x| x.fingerprint.as_deref())
+	}
+
 	/// Updates an instance with a new Paper build. Returns true if the version has changed.
 	pub fn update_instance_paper_build(&mut self, instance: &str, build_num: u16) -> bool {
 		if let Some(instance) = self.contents.instances.get_mut(instance) {
@@ -305,6 +639,11 @@ impl Lockfile {
 		}
 	}
 
+	/// Get the Paper build that an instance was last updated with
+	pub fn get_instance_paper_build(&self, instance: &str) -> Option<u16> {
+		self.contents.instances.get(instance)?.paper_build
+	}
+
 	/// Check whether an instance has done its first update successfully
 	pub fn has_instance_done_first_update(&mut self, instance: &str) -> bool {
 		self.contents.created_instances.contains(instance)
@@ -314,4 +653,631 @@ impl Lockfile {
 	pub fn update_instance_has_done_first_update(&mut self, instance: &str) {
 		self.contents.created_instances.insert(instance.to_string());
 	}
+
+	/// Get the paths of all of the addon files installed on an instance
+	pub fn get_instance_addon_files(&self, instance: &str) -> Vec<PathBuf> {
+		let Some(packages) = self.contents.packages.get(instance) else {
+			return Vec::new();
+		};
+		packages
+			.values()
+			.flat_map(|x| x.addons.iter())
+			.flat_map(|x| x.files.iter().map(PathBuf::from))
+			.collect()
+	}
+
+	/// Get the addon store keys of all of the addons used by every instance
+	pub fn get_stored_addon_keys(&self) -> HashSet<String> {
+		self.contents
+			.packages
+			.values()
+			.flat_map(|x| x.values())
+			.flat_map(|x| x.addons.iter())
+			.filter_map(|x| get_addon_store_key(&x.hashes))
+			.collect()
+	}
+
+	/// Get the IDs of all of the instances that have entries in the lockfile, sorted
+	pub fn get_instance_ids(&self) -> Vec<&str> {
+		let mut out: Vec<&str> = self
+			.contents
+			.packages
+			.keys()
+			.chain(self.contents.instances.keys())
+			.chain(self.contents.created_instances.iter())
+			.chain(self.contents.datapacks.keys())
+			.chain(self.contents.resource_packs.keys())
+			.chain(self.contents.shader_packs.keys())
+			.map(|x| x.as_str())
+			.collect();
+		out.sort();
+		out.dedup();
+		out
+	}
+
+	/// Remove all of the entries for an instance, such as when it is deleted
+	pub fn remove_instance(&mut self, instance: &str) {
+		self.contents.packages.remove(instance);
+		self.contents.instances.remove(instance);
+		self.contents.created_instances.remove(instance);
+		self.contents.datapacks.remove(instance);
+		self.contents.resource_packs.remove(instance);
+		self.contents.shader_packs.remove(instance);
+	}
+
+	/// Move all of the entries for an instance to a new ID. Stored file paths
+	/// inside the old instance directory are changed to point to the new one
+	pub fn rename_instance(
+		&mut self,
+		instance: &str,
+		new_id: &str,
+		old_dir: &Path,
+		new_dir: &Path,
+	) {
+		fn rename_key<V>(map: &mut HashMap<String, V>, old: &str, new: &str) {
+			if let Some(value) = map.remove(old) {
+				map.insert(new.to_string(), value);
+			}
+		}
+
+		rename_key(&mut self.contents.packages, instance, new_id);
+		rename_key(&mut self.contents.instances, instance, new_id);
+		rename_key(&mut self.contents.datapacks, instance, new_id);
+		rename_key(&mut self.contents.resource_packs, instance, new_id);
+		rename_key(&mut self.contents.shader_packs, instance, new_id);
+		if self.contents.created_instances.remove(instance) {
+			self.contents.created_instances.insert(new_id.to_string());
+		}
+
+		self.rewrite_instance_paths(new_id, old_dir, new_dir);
+	}
+
+	/// Change the stored file paths of every instance that are inside of the old
+	/// instances directory to point to the new one, after the instances have been moved
+	pub fn relocate_instances(&mut self, old_dir: &Path, new_dir: &Path) {
+		let instances: HashSet<String> = (self.contents.packages.keys())
+			.chain(self.contents.datapacks.keys())
+			.chain(self.contents.resource_packs.keys())
+			.chain(self.contents.shader_packs.keys())
+			.cloned()
+			.collect();
+		for instance in instances {
+			self.rewrite_instance_paths(&instance, old_dir, new_dir);
+		}
+	}
+
+	/// Change the stored file paths of an instance that are inside of the old directory to point to the new one
+	fn rewrite_instance_paths(&mut self, instance: &str, old_dir: &Path, new_dir: &Path) {
+		let rewrite = |file: &mut String| {
+			if let Ok(rest) = Path::new(file.as_str()).strip_prefix(old_dir) {
+				*file = new_dir.join(rest).to_string_lossy().to_string();
+			}
+		};
+		if let Some(packages) = self.contents.packages.get_mut(instance) {
+			for addon in packages.values_mut().flat_map(|x| x.addons.iter_mut()) {
+				addon.files.iter_mut().for_each(rewrite);
+			}
+		}
+		if let Some(datapacks) = self.contents.datapacks.get_mut(instance) {
+			for datapack in datapacks.values_mut() {
+				datapack.files.iter_mut().for_each(rewrite);
+			}
+		}
+		if let Some(resource_packs) = self.contents.resource_packs.get_mut(instance) {
+			for resource_pack in resource_packs.values_mut() {
+				rewrite(&mut resource_pack.file);
+			}
+		}
+		if let Some(shader_packs) = self.contents.shader_packs.get_mut(instance) {
+			for shader_pack in shader_packs.values_mut() {
+				rewrite(&mut shader_pack.file);
+			}
+		}
+	}
+
+	/// Get the IDs of the packages installed on an instance, sorted
+	pub fn get_instance_packages(&self, instance: &str) -> Vec<&str> {
+		let Some(packages) = self.contents.packages.get(instance) else {
+			return Vec::new();
+		};
+		let mut out: Vec<_> = packages.keys().map(|x| x.as_str()).collect();
+		out.sort();
+		out
+	}
+
+	/// Get the locked version of a package installed on an instance, which is the version
+	/// of the first of its addons that has one
+	pub fn get_package_version(&self, instance: &str, package: &str) -> Option<&str> {
+		self.contents
+			.packages
+			.get(instance)?
+			.get(package)?
+			.addons
+			.iter()
+			.find_map(|x| x.get_version())
+	}
+
+	/// Get the addons of all of the packages installed on an instance, along with their package IDs.
+	/// The addons are sorted by their package
+	pub fn get_instance_addons(&self, instance: &str) -> Vec<(&str, &LockfileAddon)> {
+		let Some(packages) = self.contents.packages.get(instance) else {
+			return Vec::new();
+		};
+		let mut out: Vec<_> = packages
+			.iter()
+			.flat_map(|(id, pkg)| pkg.addons.iter().map(move |x| (id.as_str(), x)))
+			.collect();
+		out.sort_by_key(|x| x.0);
+		out
+	}
+
+	/// Get the datapacks installed on an instance, sorted by their IDs
+	pub fn get_datapacks(&self, instance: &str) -> Vec<(&str, &LockfileDatapack)> {
+		let Some(datapacks) = self.contents.datapacks.get(instance) else {
+			return Vec::new();
+		};
+		let mut out: Vec<_> = datapacks.iter().map(|(k, v)| (k.as_str(), v)).collect();
+		out.sort_by_key(|x| x.0);
+		out
+	}
+
+	/// Add a datapack to an instance, replacing any datapack that has the same ID.
+	/// Returns the replaced datapack
+	pub fn add_datapack(
+		&mut self,
+		instance: &str,
+		id: &str,
+		datapack: LockfileDatapack,
+	) -> Option<LockfileDatapack> {
+		self.contents
+			.datapacks
+			.entry(instance.to_string())
+			.or_default()
+			.insert(id.to_string(), datapack)
+	}
+
+	/// Remove a datapack from an instance, returning it if it existed
+	pub fn remove_datapack(&mut self, instance: &str, id: &str) -> Option<LockfileDatapack> {
+		let datapacks = self.contents.datapacks.get_mut(instance)?;
+		let out = datapacks.remove(id);
+		if datapacks.is_empty() {
+			self.contents.datapacks.remove(instance);
+		}
+		out
+	}
+
+	/// Get the resource packs installed on an instance, sorted by their IDs
+	pub fn get_resource_packs(&self, instance: &str) -> Vec<(&str, &LockfileResourcePack)> {
+		let Some(resource_packs) = self.contents.resource_packs.get(instance) else {
+			return Vec::new();
+		};
+		let mut out: Vec<_> = resource_packs
+			.iter()
+			.map(|(k, v)| (k.as_str(), v))
+			.collect();
+		out.sort_by_key(|x| x.0);
+		out
+	}
+
+	/// Add a resource pack to an instance, replacing any resource pack that has the same ID.
+	/// Returns the replaced resource pack
+	pub fn add_resource_pack(
+		&mut self,
+		instance: &str,
+		id: &str,
+		resource_pack: LockfileResourcePack,
+	) -> Option<LockfileResourcePack> {
+		self.contents
+			.resource_packs
+			.entry(instance.to_string())
+			.or_default()
+			.insert(id.to_string(), resource_pack)
+	}
+
+	/// Remove a resource pack from an instance, returning it if it existed
+	pub fn remove_resource_pack(
+		&mut self,
+		instance: &str,
+		id: &str,
+	) -> Option<LockfileResourcePack> {
+		let resource_packs = self.contents.resource_packs.get_mut(instance)?;
+		let out = resource_packs.remove(id);
+		if resource_packs.is_empty() {
+			self.contents.resource_packs.remove(instance);
+		}
+		out
+	}
+
+	/// Get the shader packs installed on an instance, sorted by their IDs
+	pub fn get_shader_packs(&self, instance: &str) -> Vec<(&str, &LockfileShaderPack)> {
+		let Some(shader_packs) = self.contents.shader_packs.get(instance) else {
+			return Vec::new();
+		};
+		let mut out: Vec<_> = shader_packs.iter().map(|(k, v)| (k.as_str(), v)).collect();
+		out.sort_by_key(|x| x.0);
+		out
+	}
+
+	/// Add a shader pack to an instance, replacing any shader pack that has the same ID.
+	/// Returns the replaced shader pack
+	pub fn add_shader_pack(
+		&mut self,
+		instance: &str,
+		id: &str,
+		shader_pack: LockfileShaderPack,
+	) -> Option<LockfileShaderPack> {
+		self.contents
+			.shader_packs
+			.entry(instance.to_string())
+			.or_default()
+			.insert(id.to_string(), shader_pack)
+	}
+
+	/// Remove a shader pack from an instance, returning it if it existed
+	pub fn remove_shader_pack(&mut self, instance: &str, id: &str) -> Option<LockfileShaderPack> {
+		let shader_packs = self.contents.shader_packs.get_mut(instance)?;
+		let out = shader_packs.remove(id);
+		if shader_packs.is_empty() {
+			self.contents.shader_packs.remove(instance);
+		}
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mcvm_shared::output::NoOp;
+
+	use super::*;
+
+	#[test]
+	fn test_migrate_v0_to_v1() {
+		let mut contents: LockfileContents = serde_json::from_str(
+			r#"{
+				"packages": {
+					"inst": {
+						"pkg": {
+							"addons": [
+								{ "id": "addon", "files": [], "kind": "mod" }
+							]
+						}
+					}
+				}
+			}"#,
+		)
+		.unwrap();
+		assert_eq!(contents.version, 0);
+
+		migrate_v0_to_v1(&mut contents);
+		let addon = &contents.packages["inst"]["pkg"].addons[0];
+		assert_eq!(addon.file_name.as_deref(), Some("addon"));
+	}
+
+	#[test]
+	fn test_migrate_to_current() {
+		let mut contents: LockfileContents = serde_json::from_str("{}").unwrap();
+		assert_eq!(contents.version, 0);
+		contents.migrate().unwrap();
+		assert_eq!(contents.version, CURRENT_LOCKFILE_VERSION);
+	}
+
+	#[test]
+	fn test_migrate_newer_version() {
+		let mut contents = LockfileContents {
+			version: CURRENT_LOCKFILE_VERSION + 1,
+			..Default::default()
+		};
+		assert!(contents.migrate().is_err());
+	}
+
+	#[test]
+	fn test_is_package_changed() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		let addon = Addon {
+			kind: AddonKind::Mod,
+			id: "addon".into(),
+			file_name: "addon.jar".into(),
+			pkg_id: "pkg".into(),
+			version: Some("1".into()),
+			hashes: PackageAddonOptionalHashes::default(),
+		};
+		assert!(lock.is_package_changed("inst", "pkg", &[&addon]));
+
+		let lock_addon = LockfileAddon::from_addon(&addon, Vec::new());
+		lock.update_package("pkg", "inst", &[lock_addon], &mut NoOp)
+			.unwrap();
+		assert!(!lock.is_package_changed("inst", "pkg", &[&addon]));
+		assert!(lock.is_package_changed("inst", "pkg", &[]));
+
+		let updated = Addon {
+			version: Some("2".into()),
+			..addon.clone()
+		};
+		assert!(lock.is_package_changed("inst", "pkg", &[&updated]));
+	}
+
+	#[test]
+	fn test_remove_instance() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		lock.update_instance_version("foo", "1.20.1");
+		lock.update_instance_version("bar", "1.19.4");
+		lock.update_instance_has_done_first_update("foo");
+		lock.contents.packages.insert("foo".into(), HashMap::new());
+
+		lock.add_datapack(
+			"foo",
+			"pack",
+			LockfileDatapack {
+				source: "pack.zip".into(),
+				version: None,
+				files: Vec::new(),
+			},
+		);
+
+		lock.add_resource_pack(
+			"foo",
+			"pack",
+			LockfileResourcePack {
+				source: "pack.zip".into(),
+				version: None,
+				file: "pack.zip".into(),
+				enabled: false,
+			},
+		);
+
+		lock.add_shader_pack(
+			"foo",
+			"shaders",
+			LockfileShaderPack {
+				source: "shaders.zip".into(),
+				version: None,
+				file: "shaders.zip".into(),
+			},
+		);
+
+		lock.remove_instance("foo");
+		assert!(lock.get_datapacks("foo").is_empty());
+		assert!(lock.get_resource_packs("foo").is_empty());
+		assert!(lock.get_shader_packs("foo").is_empty());
+		assert!(!lock.contents.instances.contains_key("foo"));
+		assert!(!lock.contents.packages.contains_key("foo"));
+		assert!(!lock.has_instance_done_first_update("foo"));
+		assert!(lock.contents.instances.contains_key("bar"));
+	}
+
+	#[test]
+	fn test_instance_version_change() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		// The first update doesn't count as a change
+		assert!(!lock.update_instance_version("foo", "24w14a"));
+		assert!(!lock.update_instance_version("foo", "24w14a"));
+		// Moving to a newer snapshot does
+		assert!(lock.update_instance_version("foo", "24w18a"));
+		assert_eq!(lock.get_instance_version("foo"), Some("24w18a"));
+	}
+
+	#[test]
+	fn test_rename_instance() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		lock.update_instance_version("foo", "1.20.1");
+		lock.update_instance_has_done_first_update("foo");
+		lock.add_datapack(
+			"foo",
+			"pack",
+			LockfileDatapack {
+				source: "pack.zip".into(),
+				version: None,
+				files: vec!["/instances/foo/world/datapacks/pack.zip".into()],
+			},
+		);
+
+		lock.rename_instance(
+			"foo",
+			"bar",
+			Path::new("/instances/foo"),
+			Path::new("/instances/bar"),
+		);
+		assert_eq!(lock.get_instance_version("foo"), None);
+		assert_eq!(lock.get_instance_version("bar"), Some("1.20.1"));
+		assert!(lock.has_instance_done_first_update("bar"));
+		assert!(!lock.has_instance_done_first_update("foo"));
+		let datapacks = lock.get_datapacks("bar");
+		assert_eq!(
+			datapacks[0].1.files,
+			vec![PathBuf::from("/instances/bar/world/datapacks/pack.zip")
+				.to_string_lossy()
+				.to_string()]
+		);
+	}
+
+	#[test]
+	fn test_relocate_instances() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		lock.add_datapack(
+			"foo",
+			"pack",
+			LockfileDatapack {
+				source: "pack.zip".into(),
+				version: None,
+				files: vec![
+					"/instances/foo/world/datapacks/pack.zip".into(),
+					"/elsewhere/pack.zip".into(),
+				],
+			},
+		);
+
+		lock.relocate_instances(Path::new("/instances"), Path::new("/mnt/games"));
+		let datapacks = lock.get_datapacks("foo");
+		assert_eq!(
+			datapacks[0].1.files,
+			vec![
+				PathBuf::from("/mnt/games/foo/world/datapacks/pack.zip")
+					.to_string_lossy()
+					.to_string(),
+				"/elsewhere/pack.zip".to_string()
+			]
+		);
+	}
+
+	#[test]
+	fn test_corrupt_lockfile_backup() {
+		#[derive(Default)]
+		struct WarningCounter(usize);
+
+		impl MCVMOutput for WarningCounter {
+			fn display_text(&mut self, _: String, _: MessageLevel) {}
+
+			fn display_message(&mut self, message: mcvm_shared::output::Message) {
+				if let MessageContents::Warning(..) = message.contents {
+					self.0 += 1;
+				}
+			}
+		}
+
+		let dir = std::env::temp_dir().join("mcvm_test_corrupt_lockfile");
+		fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("lock.json");
+		let backup_path = dir.join("lock.json.bak");
+		fs::write(&path, r#"{"version": 1, "packages": {"#).unwrap();
+		fs::write(
+			&backup_path,
+			r#"{"version": 1, "created_instances": ["foo"]}"#,
+		)
+		.unwrap();
+
+		let mut o = WarningCounter::default();
+		let contents = read_lockfile_contents(&path, &backup_path, &mut o).unwrap();
+		assert!(contents.created_instances.contains("foo"));
+		assert_eq!(o.0, 1);
+
+		fs::remove_file(&backup_path).unwrap();
+		assert!(read_lockfile_contents(&path, &backup_path, &mut o).is_err());
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_addon_hashes() {
+		let addon = Addon {
+			kind: AddonKind::Mod,
+			id: "mod".into(),
+			file_name: "mod.jar".into(),
+			pkg_id: PackageID::from("pkg"),
+			version: None,
+			hashes: PackageAddonOptionalHashes::default(),
+		};
+		let mut locked = LockfileAddon::from_addon(&addon, Vec::new());
+		assert!(locked.get_hashes().is_empty());
+
+		let hashes = PackageAddonOptionalHashes {
+			sha256: Some("abc".into()),
+			sha512: None,
+		};
+		locked.set_hashes(hashes.clone());
+		assert_eq!(locked.get_hashes(), &hashes);
+
+		let file = HashedFileInfo {
+			size: 10,
+			modified: 1000,
+		};
+		assert_eq!(locked.get_unchanged_sha256(&file), None);
+		locked.set_hashed_file(file.clone());
+		assert_eq!(locked.get_unchanged_sha256(&file), Some("abc"));
+		let changed = HashedFileInfo {
+			modified: 2000,
+			..file
+		};
+		assert_eq!(locked.get_unchanged_sha256(&changed), None);
+	}
+
+	#[test]
+	fn test_hashed_file_info() {
+		let path = std::env::temp_dir().join("mcvm_test_hashed_file_info");
+		fs::write(&path, "Hello").unwrap();
+		let info = HashedFileInfo::from_file(&path).unwrap();
+		assert_eq!(info.size, 5);
+		assert_eq!(HashedFileInfo::from_file(&path).unwrap(), info);
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn test_instance_addons() {
+		let mut lock = Lockfile {
+			contents: LockfileContents::default(),
+		};
+		let addon = Addon {
+			kind: AddonKind::Mod,
+			id: "mod".into(),
+			file_name: "mod.jar".into(),
+			pkg_id: PackageID::from("sodium"),
+			version: Some("1.0".into()),
+			hashes: PackageAddonOptionalHashes::default(),
+		};
+		let mut locked = LockfileAddon::from_addon(&addon, vec![PathBuf::from("mods/mod.jar")]);
+		locked.set_url("https://example.com/mod.jar".into());
+		lock.update_package("sodium", "foo", &[locked], &mut mcvm_shared::output::NoOp)
+			.unwrap();
+
+		let addons = lock.get_instance_addons("foo");
+		assert_eq!(addons.len(), 1);
+		assert_eq!(addons[0].0, "sodium");
+		assert_eq!(addons[0].1.get_url(), Some("https://example.com/mod.jar"));
+		assert_eq!(addons[0].1.get_files(), ["mods/mod.jar".to_string()]);
+		assert!(lock.get_instance_addons("bar").is_empty());
+	}
+
+	#[test]
+	fn test_hostile_lockfile_addons() {
+		let contents: LockfileContents = serde_json::from_str(
+			r#"{
+				"version": 1,
+				"packages": {
+					"inst": {
+						"escape": {
+							"addons": [{
+								"id": "addon",
+								"file_name": "../../../.bashrc",
+								"files": ["/home/user/.bashrc"],
+								"kind": "mod"
+							}]
+						},
+						"absolute": {
+							"addons": [{
+								"id": "addon",
+								"file_name": "/home/user/.bashrc",
+								"files": [],
+								"kind": "mod"
+							}]
+						},
+						"valid": {
+							"addons": [{
+								"id": "addon",
+								"file_name": "mod.jar",
+								"files": ["/home/user/.bashrc"],
+								"kind": "mod"
+							}]
+						}
+					}
+				}
+			}"#,
+		)
+		.unwrap();
+		let lock = Lockfile { contents };
+
+		let addons: HashMap<_, _> = lock.get_instance_addons("inst").into_iter().collect();
+		assert!(addons["escape"].to_addon("escape".into()).is_err());
+		assert!(addons["absolute"].to_addon("absolute".into()).is_err());
+		// The recorded files are never used as destinations, only the plain file name
+		let addon = addons["valid"].to_addon("valid".into()).unwrap();
+		assert_eq!(addon.file_name, "mod.jar");
+	}
 }
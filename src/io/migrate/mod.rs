@@ -0,0 +1,94 @@
+/// Importing from Prism Launcher and MultiMC
+pub mod prism;
+
+use std::path::Path;
+
+use mcvm_shared::id::InstanceID;
+use serde::Serialize;
+
+use crate::config::instance::{validate_instance_id, InstanceConfig};
+use crate::io::paths::Paths;
+
+/// An instance that was imported from another launcher
+#[derive(Serialize)]
+pub struct MigratedInstance {
+	/// The name of the instance in the other launcher
+	pub name: String,
+	/// The ID that the instance was imported as, if it was imported
+	pub id: Option<InstanceID>,
+	/// How the import went
+	pub status: MigrationStatus,
+	/// The number of mods that were copied into the instance as files that mcvm doesn't manage
+	pub unmanaged_mods: usize,
+	/// The config to add for the instance, if it was imported
+	#[serde(skip)]
+	pub config: Option<InstanceConfig>,
+}
+
+impl MigratedInstance {
+	/// Create a MigratedInstance for an instance that could not be imported
+	pub fn failed(name: String, error: String) -> Self {
+		Self {
+			name,
+			id: None,
+			status: MigrationStatus::Failed(error),
+			unmanaged_mods: 0,
+			config: None,
+		}
+	}
+}
+
+/// Removes the instance directories that were created for imported instances, such as when
+/// their configs could not be written
+pub fn remove_migrated_instances(migrated: &[MigratedInstance], paths: &Paths) {
+	for id in migrated.iter().filter_map(|x| x.id.as_ref()) {
+		let dir = paths.instances.join(&**id);
+		if dir.exists() {
+			let _ = std::fs::remove_dir_all(dir);
+		}
+	}
+}
+
+/// How importing an instance went
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationStatus {
+	/// The instance was imported without any problems
+	Clean,
+	/// The instance was imported, but some of its setup has to be finished by hand
+	NeedsAttention(Vec<String>),
+	/// The instance could not be imported
+	Failed(String),
+}
+
+/// Create an instance ID from the name of an instance in another launcher. The ID is made unique
+/// against the taken IDs, ignoring case since IDs are used as directory names, and against
+/// directories that already exist in the instances directory
+pub fn make_instance_id(name: &str, taken: &[InstanceID], instances_dir: &Path) -> InstanceID {
+	let mut base = String::with_capacity(name.len());
+	for c in name.chars() {
+		if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+			base.push(c);
+		} else if !base.ends_with('-') {
+			base.push('-');
+		}
+	}
+	let base = base.trim_matches(|x| x == '-' || x == '.');
+	let base = if base.is_empty() || validate_instance_id(base).is_err() {
+		"imported"
+	} else {
+		base
+	};
+
+	let is_taken = |id: &str| {
+		taken.iter().any(|x| x.eq_ignore_ascii_case(id)) || instances_dir.join(id).exists()
+	};
+	let mut id = base.to_string();
+	let mut i = 2;
+	while is_taken(&id) {
+		id = format!("{base}-{i}");
+		i += 1;
+	}
+
+	InstanceID::from(id)
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use mcvm_core::util::versions::MinecraftVersionDeser;
+use mcvm_shared::id::InstanceID;
+use mcvm_shared::modifications::{ClientType, Modloader};
+use mcvm_shared::Side;
+use serde::Deserialize;
+
+use crate::config::instance::{
+	Args, CommonInstanceConfig, InstanceConfig, LaunchArgs, LaunchConfig, LaunchMemory,
+};
+use crate::config::profile::can_install_client_type;
+use crate::io::paths::Paths;
+use crate::util::copy_dir_recursive;
+
+use super::{make_instance_id, MigratedInstance, MigrationStatus};
+
+/// The component UID that Prism uses for the game itself
+const MINECRAFT_UID: &str = "net.minecraft";
+
+/// Imports all of the instances from a Prism Launcher or MultiMC data directory. The game directory
+/// of each instance is copied into a new instance directory, and the config for each imported
+/// instance is returned so that it can be added to the config file. Mods are copied as files that
+/// are not managed by mcvm.
+pub fn migrate(
+	prism_dir: &Path,
+	existing_ids: &[InstanceID],
+	paths: &Paths,
+) -> anyhow::Result<Vec<MigratedInstance>> {
+	let instances_dir = get_instances_dir(prism_dir);
+	if !instances_dir.is_dir() {
+		bail!(
+			"Could not find an instances directory at {}",
+			instances_dir.display()
+		);
+	}
+
+	let mut dirs = Vec::new();
+	for entry in instances_dir
+		.read_dir()
+		.context("Failed to read instances directory")?
+	{
+		let entry = entry?;
+		let path = entry.path();
+		// Prism keeps temporary directories for instances that are being created in here
+		let is_hidden = entry.file_name().to_string_lossy().starts_with(['.', '_']);
+		if !is_hidden && path.join("instance.cfg").is_file() {
+			dirs.push(path);
+		}
+	}
+	dirs.sort();
+
+	let mut taken = existing_ids.to_vec();
+	let mut out = Vec::with_capacity(dirs.len());
+	for dir in dirs {
+		let migrated = migrate_instance(&dir, &taken, paths);
+		if let Some(id) = &migrated.id {
+			taken.push(id.clone());
+		}
+		out.push(migrated);
+	}
+
+	Ok(out)
+}
+
+/// Gets the directory that Prism stores instances in, which can be changed in its settings
+fn get_instances_dir(prism_dir: &Path) -> PathBuf {
+	for file_name in ["prismlauncher.cfg", "multimc.cfg"] {
+		let Ok(contents) = std::fs::read_to_string(prism_dir.join(file_name)) else {
+			continue;
+		};
+		if let Some(dir) = parse_cfg(&contents).remove("InstanceDir") {
+			return prism_dir.join(dir);
+		}
+	}
+
+	prism_dir.join("instances")
+}
+
+/// Imports a single instance from its directory
+fn migrate_instance(dir: &Path, taken: &[InstanceID], paths: &Paths) -> MigratedInstance {
+	let dir_name = dir.file_name().unwrap_or_default().to_string_lossy();
+	let instance = match read_instance(dir) {
+		Ok(instance) => instance,
+		Err(e) => return MigratedInstance::failed(dir_name.to_string(), format!("{e:#}")),
+	};
+	let name = instance.name.clone().unwrap_or(dir_name.to_string());
+
+	let Some(version) = instance.version.clone() else {
+		return MigratedInstance::failed(
+			name,
+			"Could not find the Minecraft version of the instance".into(),
+		);
+	};
+
+	let mut notes = Vec::new();
+	let modloader = instance.loader.as_ref().map(|(uid, version)| {
+		let modloader = get_modloader(uid);
+		let client_type = get_client_type(&modloader);
+		if !can_install_client_type(&client_type) {
+			notes.push(format!(
+				"{modloader} {version} can't be installed by mcvm yet, so it will have to be installed by hand"
+			));
+		}
+		modloader
+	});
+	if instance.custom_java {
+		notes.push("The instance used a custom Java installation, which was not imported".into());
+	}
+
+	let id = make_instance_id(&name, taken, &paths.instances);
+	let mut unmanaged_mods = 0;
+	if let Some(game_dir) = &instance.game_dir {
+		let dest = paths.instances.join(&*id).join(".minecraft");
+		if let Err(e) = copy_dir_recursive(game_dir, &dest) {
+			// Don't leave a partial copy behind
+			let _ = std::fs::remove_dir_all(paths.instances.join(&*id));
+			return MigratedInstance::failed(name, format!("Failed to copy game directory: {e:#}"));
+		}
+		unmanaged_mods = count_mods(&dest.join("mods"));
+	} else {
+		notes.push("The instance has no game directory, so no files were copied".into());
+	}
+
+	let config = InstanceConfig {
+		side: Some(Side::Client),
+		name: Some(name.clone()),
+		common: CommonInstanceConfig {
+			version: Some(MinecraftVersionDeser::Version(version.into())),
+			modloader,
+			launch: LaunchConfig {
+				memory: instance.memory,
+				args: LaunchArgs {
+					jvm: instance.jvm_args.map(Args::String).unwrap_or_default(),
+					..Default::default()
+				},
+				..Default::default()
+			},
+			..Default::default()
+		},
+		window: Default::default(),
+		properties: Default::default(),
+	};
+
+	MigratedInstance {
+		name,
+		id: Some(id),
+		status: if notes.is_empty() {
+			MigrationStatus::Clean
+		} else {
+			MigrationStatus::NeedsAttention(notes)
+		},
+		unmanaged_mods,
+		config: Some(config),
+	}
+}
+
+/// The information about a Prism instance that mcvm can use
+#[derive(Debug, Default, PartialEq)]
+struct PrismInstance {
+	/// The display name
+	name: Option<String>,
+	/// The Minecraft version
+	version: Option<String>,
+	/// The UID and version of the modloader component
+	loader: Option<(String, String)>,
+	/// Overridden memory settings
+	memory: LaunchMemory,
+	/// Overridden JVM arguments
+	jvm_args: Option<String>,
+	/// Whether the instance overrides the Java installation
+	custom_java: bool,
+	/// The game directory
+	game_dir: Option<PathBuf>,
+}
+
+/// Reads a Prism instance from its directory
+fn read_instance(dir: &Path) -> anyhow::Result<PrismInstance> {
+	let cfg =
+		std::fs::read_to_string(dir.join("instance.cfg")).context("Failed to read instance.cfg")?;
+	let pack_path = dir.join("mmc-pack.json");
+	let pack = if pack_path.exists() {
+		let contents =
+			std::fs::read_to_string(pack_path).context("Failed to read mmc-pack.json")?;
+		Some(serde_json::from_str(&contents).context("Failed to parse mmc-pack.json")?)
+	} else {
+		None
+	};
+
+	let mut instance = parse_instance(&parse_cfg(&cfg), pack.as_ref());
+	instance.game_dir = [".minecraft", "minecraft"]
+		.into_iter()
+		.map(|x| dir.join(x))
+		.find(|x| x.is_dir());
+
+	Ok(instance)
+}
+
+/// Gets the instance information from its parsed instance.cfg and mmc-pack.json
+fn parse_instance(cfg: &HashMap<String, String>, pack: Option<&MmcPack>) -> PrismInstance {
+	let is_enabled = |key: &str| cfg.get(key).is_some_and(|x| x == "true");
+	let get_nonempty = |key: &str| cfg.get(key).filter(|x| !x.is_empty()).cloned();
+
+	let mut version = None;
+	let mut loader = None;
+	if let Some(pack) = pack {
+		for component in &pack.components {
+			let component_version = component
+				.version
+				.clone()
+				.or(component.cached_version.clone())
+				.unwrap_or_default();
+			if component.uid == MINECRAFT_UID {
+				version = Some(component_version);
+			} else if is_loader_uid(&component.uid) {
+				loader = Some((component.uid.clone(), component_version));
+			}
+		}
+	}
+	// Old MultiMC instances put the version in the instance.cfg instead
+	let version = version
+		.filter(|x| !x.is_empty())
+		.or(get_nonempty("IntendedVersion"));
+
+	let memory = if is_enabled("OverrideMemory") {
+		match (get_nonempty("MinMemAlloc"), get_nonempty("MaxMemAlloc")) {
+			(Some(min), Some(max)) => LaunchMemory::Both {
+				min: format!("{min}M"),
+				max: format!("{max}M"),
+			},
+			(Some(mem), None) | (None, Some(mem)) => LaunchMemory::Single(format!("{mem}M")),
+			(None, None) => LaunchMemory::None,
+		}
+	} else {
+		LaunchMemory::None
+	};
+
+	let jvm_args = if is_enabled("OverrideJavaArgs") {
+		get_nonempty("JvmArgs")
+	} else {
+		None
+	};
+
+	PrismInstance {
+		name: get_nonempty("name"),
+		version,
+		loader,
+		memory,
+		jvm_args,
+		custom_java: is_enabled("OverrideJavaLocation") && get_nonempty("JavaPath").is_some(),
+		game_dir: None,
+	}
+}
+
+/// The mmc-pack.json file, which lists the components of an instance
+#[derive(Deserialize, Debug)]
+struct MmcPack {
+	components: Vec<MmcComponent>,
+}
+
+/// A single component of an instance, such as the game or a modloader
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MmcComponent {
+	uid: String,
+	#[serde(default)]
+	version: Option<String>,
+	#[serde(default)]
+	cached_version: Option<String>,
+}
+
+/// Checks if a component UID is for a modloader
+fn is_loader_uid(uid: &str) -> bool {
+	matches!(
+		uid,
+		"net.fabricmc.fabric-loader"
+			| "org.quiltmc.quilt-loader"
+			| "net.minecraftforge"
+			| "net.neoforged"
+			| "com.mumfrey.liteloader"
+	)
+}
+
+/// Gets the modloader for a modloader component UID
+fn get_modloader(uid: &str) -> Modloader {
+	match uid {
+		"net.fabricmc.fabric-loader" => Modloader::Fabric,
+		"org.quiltmc.quilt-loader" => Modloader::Quilt,
+		"net.minecraftforge" => Modloader::Forge,
+		"net.neoforged" => Modloader::NeoForged,
+		"com.mumfrey.liteloader" => Modloader::LiteLoader,
+		other => Modloader::Unknown(other.into()),
+	}
+}
+
+/// Gets the client type that installs a modloader
+fn get_client_type(modloader: &Modloader) -> ClientType {
+	match modloader {
+		Modloader::Vanilla => ClientType::Vanilla,
+		Modloader::Forge => ClientType::Forge,
+		Modloader::NeoForged => ClientType::NeoForged,
+		Modloader::Fabric => ClientType::Fabric,
+		Modloader::Quilt => ClientType::Quilt,
+		Modloader::LiteLoader => ClientType::LiteLoader,
+		Modloader::Risugamis => ClientType::Risugamis,
+		Modloader::Rift => ClientType::Rift,
+		Modloader::Unknown(other) => ClientType::Unknown(other.clone()),
+	}
+}
+
+/// Parses a Prism config file, which is an INI file written by Qt. Sections are ignored
+/// since the settings mcvm needs are all in the general section
+fn parse_cfg(contents: &str) -> HashMap<String, String> {
+	let mut out = HashMap::new();
+	for line in contents.lines() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with(['[', ';', '#']) {
+			continue;
+		}
+		let Some((key, value)) = line.split_once('=') else {
+			continue;
+		};
+		let value = value.trim();
+		// Qt quotes values that have special characters in them
+		let value = match value.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+			Some(value) => value.replace("\\\"", "\"").replace("\\\\", "\\"),
+			None => value.to_string(),
+		};
+		out.insert(key.trim().to_string(), value);
+	}
+
+	out
+}
+
+/// Counts the mod files in a mods directory
+fn count_mods(dir: &Path) -> usize {
+	let Ok(entries) = dir.read_dir() else {
+		return 0;
+	};
+	entries
+		.filter_map(|x| x.ok())
+		.filter(|x| {
+			let path = x.path();
+			path.is_file() && path.extension().is_some_and(|x| x == "jar" || x == "zip")
+		})
+		.count()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_instance() {
+		let cfg = parse_cfg(
+			"[General]\nInstanceType=OneSix\nname=\"My \\\"Cool\\\" Pack\"\nOverrideMemory=true\nMinMemAlloc=512\nMaxMemAlloc=4096\nOverrideJavaArgs=false\nJvmArgs=-Xss4M\n",
+		);
+		let pack: MmcPack = serde_json::from_str(
+			r#"{
+				"components": [
+					{ "uid": "org.lwjgl3", "version": "3.3.1" },
+					{ "uid": "net.minecraft", "version": "1.20.1" },
+					{ "uid": "net.fabricmc.intermediary", "version": "1.20.1" },
+					{ "uid": "net.fabricmc.fabric-loader", "cachedVersion": "0.14.21" }
+				],
+				"formatVersion": 1
+			}"#,
+		)
+		.unwrap();
+
+		let instance = parse_instance(&cfg, Some(&pack));
+		assert_eq!(
+			instance,
+			PrismInstance {
+				name: Some("My \"Cool\" Pack".into()),
+				version: Some("1.20.1".into()),
+				loader: Some(("net.fabricmc.fabric-loader".into(), "0.14.21".into())),
+				memory: LaunchMemory::Both {
+					min: "512M".into(),
+					max: "4096M".into()
+				},
+				jvm_args: None,
+				custom_java: false,
+				game_dir: None,
+			}
+		);
+
+		// Old MultiMC instances have no pack file
+		let cfg = parse_cfg("IntendedVersion=1.7.10\nname=Old");
+		let instance = parse_instance(&cfg, None);
+		assert_eq!(instance.version.as_deref(), Some("1.7.10"));
+		assert_eq!(instance.loader, None);
+	}
+
+	#[test]
+	fn test_make_instance_id() {
+		let dir = Path::new("/nonexistent/instances");
+		assert_eq!(
+			&*make_instance_id("All the Mods 9 (1.20.1)", &[], dir),
+			"All-the-Mods-9-1.20.1"
+		);
+		assert_eq!(&*make_instance_id("Über!", &[], dir), "ber");
+		assert_eq!(&*make_instance_id("???", &[], dir), "imported");
+		let taken = vec!["vanilla".into(), "Vanilla-2".into()];
+		assert_eq!(&*make_instance_id("Vanilla", &taken, dir), "Vanilla-3");
+	}
+}
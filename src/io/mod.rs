@@ -1,4 +1,8 @@
 /// Use of the lockfile for persistent data
 pub mod lock;
+/// Importing instances from other launchers
+pub mod migrate;
 /// Standard paths for MCVM
 pub mod paths;
+/// Pidfiles for tracking detached instances
+pub mod pid;
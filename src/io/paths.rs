@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Context};
+use anyhow::Context;
 use directories::{BaseDirs, ProjectDirs};
 
 use std::path::PathBuf;
@@ -16,6 +16,8 @@ pub struct Paths {
 	pub data: PathBuf,
 	/// Holds internal data
 	pub internal: PathBuf,
+	/// Holds the directories of instances
+	pub instances: PathBuf,
 	/// Holds addons
 	pub addons: PathBuf,
 	/// Holds cached package scripts
@@ -70,36 +72,41 @@ impl Paths {
 		Ok(())
 	}
 
+	/// Change where the directories of instances are stored. The rest of the paths stay the same
+	pub fn set_instances_dir(&mut self, dir: PathBuf) {
+		self.core.instances = dir.clone();
+		self.instances = dir;
+	}
+
 	/// Create the paths without creating any directories
 	pub fn new_no_create() -> anyhow::Result<Self> {
-		let base = BaseDirs::new().ok_or(anyhow!("Base directories failed"))?;
-		let project =
-			ProjectDirs::from("", "mcvm", "mcvm").ok_or(anyhow!("Base directories failed"))?;
+		// The base directories are shared with core so that the two can't disagree
+		let core_paths =
+			mcvm_core::Paths::new_no_create().context("Failed to create core paths")?;
+		let base = core_paths.base.clone();
+		let project = core_paths.project.clone();
 
-		let data = project.data_dir().to_owned();
-		let internal = data.join("internal");
+		let data = core_paths.data.clone();
+		let internal = core_paths.internal.clone();
+		let instances = core_paths.instances.clone();
 		let addons = internal.join("addons");
 		let pkg_cache = project.cache_dir().join("pkg");
 		let pkg_index_cache = pkg_cache.join("index");
-		let logs = data.join("logs");
-		let launch_logs = logs.join("launch");
-		let run = project
-			.runtime_dir()
-			.map(|x| x.to_path_buf())
-			.unwrap_or(internal.join("run"));
+		let logs = core_paths.logs.clone();
+		let launch_logs = core_paths.launch_logs.clone();
+		let run = core_paths.run.clone();
 		let snapshots = internal.join("snapshots");
 		let fabric_quilt = internal.join("fabric_quilt");
 		let proxy = data.join("proxy");
 		let plugins = data.join("plugins");
 
-		let core_paths = mcvm_core::Paths::new().context("Failed to create core paths")?;
-
 		Ok(Paths {
 			base,
 			project,
 			core: core_paths,
 			data,
 			internal,
+			instances,
 			addons,
 			pkg_cache,
 			pkg_index_cache,
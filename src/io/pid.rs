@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use anyhow::Context;
+
+use super::paths::Paths;
+
+/// Get the path to the pidfile for a detached instance
+pub fn get_pidfile_path(paths: &Paths, instance_id: &str) -> PathBuf {
+	paths.run.join(format!("{instance_id}.pid"))
+}
+
+/// Get the path to the file that stores what to run when a detached instance stops
+pub fn get_stop_file_path(paths: &Paths, instance_id: &str) -> PathBuf {
+	paths.run.join(format!("{instance_id}.stop.json"))
+}
+
+/// Write the PID of a detached instance to its pidfile
+pub fn write_pidfile(paths: &Paths, instance_id: &str, pid: u32) -> anyhow::Result<()> {
+	let path = get_pidfile_path(paths, instance_id);
+	std::fs::create_dir_all(&paths.run).context("Failed to create runtime directory")?;
+	std::fs::write(path, pid.to_string()).context("Failed to write pidfile")?;
+
+	Ok(())
+}
+
+/// Read the PID of a detached instance from its pidfile, if it exists
+pub fn read_pidfile(paths: &Paths, instance_id: &str) -> anyhow::Result<Option<u32>> {
+	let path = get_pidfile_path(paths, instance_id);
+	if !path.exists() {
+		return Ok(None);
+	}
+	let contents = std::fs::read_to_string(path).context("Failed to read pidfile")?;
+	let pid = contents
+		.trim()
+		.parse()
+		.context("Pidfile does not contain a valid PID")?;
+
+	Ok(Some(pid))
+}
+
+/// Remove the pidfile of a detached instance
+pub fn remove_pidfile(paths: &Paths, instance_id: &str) -> anyhow::Result<()> {
+	let path = get_pidfile_path(paths, instance_id);
+	if path.exists() {
+		std::fs::remove_file(path).context("Failed to remove pidfile")?;
+	}
+
+	Ok(())
+}
+
+/// Check whether a process with the given PID is still running
+pub fn is_process_running(pid: u32) -> anyhow::Result<bool> {
+	#[cfg(target_os = "windows")]
+	{
+		let output = Command::new("tasklist")
+			.args(["/FI", &format!("PID eq {pid}"), "/NH"])
+			.stderr(Stdio::null())
+			.output()
+			.context("Failed to run tasklist")?;
+		let output = String::from_utf8_lossy(&output.stdout);
+		Ok(output
+			.split_whitespace()
+			.any(|x| x == pid.to_string().as_str()))
+	}
+	#[cfg(not(target_os = "windows"))]
+	{
+		let status = Command::new("kill")
+			.args(["-0", &pid.to_string()])
+			.stderr(Stdio::null())
+			.status()
+			.context("Failed to run kill")?;
+		Ok(status.success())
+	}
+}
+
+/// Ask a process to shut down gracefully. On Unix this sends SIGTERM, and on Windows the
+/// process is stopped forcefully
+pub fn terminate_process(pid: u32) -> anyhow::Result<()> {
+	#[cfg(target_os = "windows")]
+	let mut cmd = {
+		let mut cmd = Command::new("taskkill");
+		// Detached processes have no window to close, so they have to be stopped forcefully
+		cmd.args(["/F", "/PID", &pid.to_string()]);
+		cmd
+	};
+	#[cfg(not(target_os = "windows"))]
+	let mut cmd = {
+		let mut cmd = Command::new("kill");
+		cmd.args(["-TERM", &pid.to_string()]);
+		cmd
+	};
+	let status = cmd
+		.stdout(Stdio::null())
+		.status()
+		.context("Failed to run command to stop process")?;
+	if !status.success() {
+		anyhow::bail!("Command to stop process returned a non-zero exit code");
+	}
+
+	Ok(())
+}
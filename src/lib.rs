@@ -9,12 +9,12 @@
 //! # Features
 //!
 //! - `arc`: MCVM uses Rc's in a couple places. Although these are more performant than Arc's, they
-//! may not be compatible with some async runtimes. With this feature enabled, these Rc's will be replaced with
-//! Arc's where possible.
+//!   may not be compatible with some async runtimes. With this feature enabled, these Rc's will be replaced with
+//!   Arc's where possible.
 //! - `builder`: Enable or disable the config builder system, which isn't needed if you are just deserializing the standard config.
 //! - `disable_profile_update_packages`: A workaround for `https://github.com/rust-lang/rust/issues/102211`. If you are
-//! getting higher-ranked lifetime errors when running the update_profiles function, try enabling this. When enabled, the
-//! update_profiles function will no longer update packages at all.
+//!   getting higher-ranked lifetime errors when running the update_profiles function, try enabling this. When enabled, the
+//!   update_profiles function will no longer update packages at all.
 //! - `schema`: Enable generation of JSON schemas using the `schemars` crate
 
 pub use mcvm_core as core;
@@ -31,6 +31,8 @@ pub mod config;
 pub mod instance;
 /// File and data format input / output
 pub mod io;
+/// Network protocols for communicating with the game
+pub mod net;
 /// Dealing with packages
 pub mod pkg;
 /// Configuration profiles for instances
@@ -0,0 +1,2 @@
+/// Client for the Source RCON protocol, used to send commands to running servers
+pub mod rcon;
@@ -0,0 +1,264 @@
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use mcvm_options::server::ServerProperties;
+
+/// The default port for RCON if it is not set in the server.properties
+pub const DEFAULT_RCON_PORT: u16 = 25575;
+
+/// Packet type for authenticating with the server
+const PACKET_AUTH: i32 = 3;
+/// Packet type for the response to an auth packet
+const PACKET_AUTH_RESPONSE: i32 = 2;
+/// Packet type for executing a command
+const PACKET_EXEC_COMMAND: i32 = 2;
+/// Packet type for a response to a command
+const PACKET_RESPONSE_VALUE: i32 = 0;
+
+/// The maximum length of a command body that the Minecraft server will accept
+const MAX_COMMAND_LENGTH: usize = 1446;
+/// The maximum length of an incoming packet that we will accept
+const MAX_PACKET_LENGTH: usize = 4096 + 10;
+/// How long to wait for the server to respond
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A client connected and authenticated to a server over RCON
+pub struct RconClient {
+	stream: TcpStream,
+	next_id: i32,
+}
+
+impl RconClient {
+	/// Connect to a server running on this machine and authenticate with the given password
+	pub fn connect(port: u16, password: &str) -> anyhow::Result<Self> {
+		let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+			.context("Failed to connect to the server. Is it running?")?;
+		stream.set_read_timeout(Some(TIMEOUT))?;
+		stream.set_write_timeout(Some(TIMEOUT))?;
+
+		let mut client = Self { stream, next_id: 1 };
+		client
+			.authenticate(password)
+			.context("Failed to authenticate")?;
+
+		Ok(client)
+	}
+
+	/// Runs a command on the server and returns the server's response
+	pub fn command(&mut self, command: &str) -> anyhow::Result<String> {
+		if command.len() > MAX_COMMAND_LENGTH {
+			bail!("Command is longer than the maximum of {MAX_COMMAND_LENGTH} bytes");
+		}
+
+		let id = self.next_id();
+		write_packet(&mut self.stream, id, PACKET_EXEC_COMMAND, command)
+			.context("Failed to send command")?;
+
+		// Long responses are split across multiple packets, and there is no way to tell
+		// which is the last one. To find the end, we send another packet after the command.
+		// Since the server handles packets in order, once we get the response to that one
+		// we know that every part of the command response has been received.
+		let end_id = self.next_id();
+		write_packet(&mut self.stream, end_id, PACKET_RESPONSE_VALUE, "")
+			.context("Failed to send end marker")?;
+
+		let mut out = String::new();
+		loop {
+			let packet = read_packet(&mut self.stream).context("Failed to read response")?;
+			if packet.id == end_id {
+				break;
+			}
+			if packet.id != id {
+				bail!("Received response with unexpected ID {}", packet.id);
+			}
+			out.push_str(&packet.body);
+		}
+
+		Ok(out)
+	}
+
+	/// Performs the authentication handshake
+	fn authenticate(&mut self, password: &str) -> anyhow::Result<()> {
+		let id = self.next_id();
+		write_packet(&mut self.stream, id, PACKET_AUTH, password)
+			.context("Failed to send auth packet")?;
+
+		// Some servers send an empty response value before the actual auth response
+		loop {
+			let packet = read_packet(&mut self.stream).context("Failed to read auth response")?;
+			if packet.kind != PACKET_AUTH_RESPONSE {
+				continue;
+			}
+			if packet.id == -1 {
+				bail!("Incorrect RCON password");
+			}
+			if packet.id != id {
+				bail!("Received auth response with unexpected ID {}", packet.id);
+			}
+			return Ok(());
+		}
+	}
+
+	/// Gets the next request ID to use
+	fn next_id(&mut self) -> i32 {
+		let id = self.next_id;
+		self.next_id = self.next_id.wrapping_add(1).max(1);
+		id
+	}
+}
+
+/// A single RCON packet
+#[derive(Debug, PartialEq)]
+struct Packet {
+	id: i32,
+	kind: i32,
+	body: String,
+}
+
+/// Writes a packet to a stream
+fn write_packet(writer: &mut impl Write, id: i32, kind: i32, body: &str) -> anyhow::Result<()> {
+	// ID, type, body, and the two null terminators
+	let length = 4 + 4 + body.len() + 2;
+	let mut buf = Vec::with_capacity(4 + length);
+	buf.extend_from_slice(&(length as i32).to_le_bytes());
+	buf.extend_from_slice(&id.to_le_bytes());
+	buf.extend_from_slice(&kind.to_le_bytes());
+	buf.extend_from_slice(body.as_bytes());
+	buf.extend_from_slice(&[0, 0]);
+	writer.write_all(&buf)?;
+	writer.flush()?;
+
+	Ok(())
+}
+
+/// Reads a packet from a stream
+fn read_packet(reader: &mut impl Read) -> anyhow::Result<Packet> {
+	let mut int_buf = [0u8; 4];
+	reader.read_exact(&mut int_buf)?;
+	let length = i32::from_le_bytes(int_buf);
+	let length = usize::try_from(length).context("Packet has a negative length")?;
+	if !(10..=MAX_PACKET_LENGTH).contains(&length) {
+		bail!("Packet has an invalid length of {length}");
+	}
+
+	let mut buf = vec![0u8; length];
+	reader.read_exact(&mut buf)?;
+	let id = i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+	let kind = i32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+	let body = String::from_utf8_lossy(&buf[8..length - 2]).to_string();
+
+	Ok(Packet { id, kind, body })
+}
+
+/// RCON settings read from a server
+#[derive(Debug, PartialEq)]
+pub struct RconSettings {
+	/// Whether RCON is enabled
+	pub enabled: bool,
+	/// The port RCON is listening on
+	pub port: u16,
+	/// The RCON password
+	pub password: String,
+}
+
+/// Reads the RCON settings from the server.properties file in a server's game directory
+pub fn read_rcon_settings(game_dir: &Path) -> anyhow::Result<RconSettings> {
+	let path = game_dir.join("server.properties");
+	let contents = std::fs::read_to_string(path)
+		.context("Failed to read server.properties. Has the server been launched yet?")?;
+	parse_rcon_settings(&contents)
+}
+
+/// Parses RCON settings from the contents of a server.properties file
+fn parse_rcon_settings(contents: &str) -> anyhow::Result<RconSettings> {
+	let properties = ServerProperties::parse(contents);
+	let port = match properties.get("rcon.port") {
+		Some(port) => port
+			.trim()
+			.parse()
+			.context("Invalid rcon.port in server.properties")?,
+		None => DEFAULT_RCON_PORT,
+	};
+
+	Ok(RconSettings {
+		enabled: properties
+			.get("enable-rcon")
+			.is_some_and(|x| x.trim() == "true"),
+		port,
+		password: properties.get("rcon.password").unwrap_or_default(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::net::TcpListener;
+
+	use super::*;
+
+	#[test]
+	fn test_packet_round_trip() {
+		let mut buf = Vec::new();
+		write_packet(&mut buf, 7, PACKET_EXEC_COMMAND, "list").unwrap();
+		assert_eq!(buf.len(), 4 + 4 + 4 + 4 + 2);
+		let packet = read_packet(&mut buf.as_slice()).unwrap();
+		assert_eq!(
+			packet,
+			Packet {
+				id: 7,
+				kind: PACKET_EXEC_COMMAND,
+				body: "list".into()
+			}
+		);
+	}
+
+	#[test]
+	fn test_multi_packet_response() {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+		let port = listener.local_addr().unwrap().port();
+		let server = std::thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+			let auth = read_packet(&mut stream).unwrap();
+			assert_eq!(auth.body, "secret");
+			write_packet(&mut stream, auth.id, PACKET_RESPONSE_VALUE, "").unwrap();
+			write_packet(&mut stream, auth.id, PACKET_AUTH_RESPONSE, "").unwrap();
+
+			let command = read_packet(&mut stream).unwrap();
+			let end = read_packet(&mut stream).unwrap();
+			write_packet(&mut stream, command.id, PACKET_RESPONSE_VALUE, "foo").unwrap();
+			write_packet(&mut stream, command.id, PACKET_RESPONSE_VALUE, "bar").unwrap();
+			write_packet(
+				&mut stream,
+				end.id,
+				PACKET_RESPONSE_VALUE,
+				"Unknown request 0",
+			)
+			.unwrap();
+		});
+
+		let mut client = RconClient::connect(port, "secret").unwrap();
+		assert_eq!(client.command("list").unwrap(), "foobar");
+		server.join().unwrap();
+	}
+
+	#[test]
+	fn test_rcon_settings() {
+		let contents = "#Minecraft server properties\nenable-rcon=true\nrcon.port=25580\nrcon.password=hello=world\n";
+		let settings = parse_rcon_settings(contents).unwrap();
+		assert_eq!(
+			settings,
+			RconSettings {
+				enabled: true,
+				port: 25580,
+				password: "hello=world".into()
+			}
+		);
+
+		let contents = "enable-rcon=true\nrcon.password=a\\=b\\:c\\\\d\n";
+		let settings = parse_rcon_settings(contents).unwrap();
+		assert_eq!(settings.port, DEFAULT_RCON_PORT);
+		assert_eq!(settings.password, "a=b:c\\d");
+	}
+}
@@ -142,6 +142,8 @@ pub struct EvalParameters {
 	pub stability: PackageStability,
 	/// Requested worlds to put addons in
 	pub worlds: Vec<String>,
+	/// Whether to only warn about conflicting packages instead of failing
+	pub allow_conflicts: bool,
 }
 
 impl EvalParameters {
@@ -154,6 +156,7 @@ impl EvalParameters {
 			perms: EvalPermissions::default(),
 			stability: PackageStability::default(),
 			worlds: Vec::new(),
+			allow_conflicts: false,
 		}
 	}
 }
@@ -555,6 +558,10 @@ impl<'a> PackageEvaluatorTrait<'a> for PackageEvaluator<'a> {
 			.await?;
 		Ok(properties)
 	}
+
+	fn allow_conflicts(&self, input: &Self::EvalInput<'a>) -> bool {
+		input.params.allow_conflicts
+	}
 }
 
 /// Resolve package dependencies
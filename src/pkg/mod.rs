@@ -6,6 +6,8 @@ pub mod eval;
 pub mod reg;
 /// Interacting with package repositories
 pub mod repo;
+/// Validating packages for package authors
+pub mod validate;
 
 use crate::io::paths::Paths;
 use mcvm_core::net::download;
@@ -20,6 +22,7 @@ use std::future::Future;
 use std::path::PathBuf;
 
 use self::core::get_core_package;
+use self::repo::{map_auth_error, RepoToken};
 use anyhow::{anyhow, bail, Context};
 use mcvm_parse::parse::{lex_and_parse, Parsed};
 use mcvm_pkg::metadata::{eval_metadata, PackageMetadata};
@@ -53,6 +56,8 @@ pub enum PkgLocation {
 		url: Option<String>,
 		/// The ID of the repository this package is from
 		repo_id: String,
+		/// The token to authenticate with the repository
+		token: Option<RepoToken>,
 	},
 	/// Included in the binary
 	Core,
@@ -170,14 +175,24 @@ impl Package {
 					self.data
 						.fill(PkgData::new(&tokio::fs::read_to_string(path).await?));
 				}
-				PkgLocation::Remote { url, .. } => {
+				PkgLocation::Remote {
+					url,
+					repo_id,
+					token,
+				} => {
 					let path = self.cached_path(paths);
 					if !force && path.exists() {
 						self.data
 							.fill(PkgData::new(&tokio::fs::read_to_string(path).await?));
 					} else {
 						let url = url.as_ref().expect("URL for remote package missing");
-						let text = download::text(url, client).await?;
+						let token = token.as_ref().map(RepoToken::get).transpose()?;
+						let text = download::download_with_token(url, token.as_deref(), client)
+							.await
+							.map_err(|e| map_auth_error(e, repo_id))?
+							.text()
+							.await
+							.context("Failed to convert download to text")?;
 						tokio::fs::write(&path, &text).await?;
 						self.data.fill(PkgData::new(&text));
 					}
@@ -200,15 +215,33 @@ impl Package {
 		force: bool,
 		client: &Client,
 	) -> Option<impl Future<Output = anyhow::Result<()>> + 'static> {
-		if let PkgLocation::Remote { url, .. } = &self.location {
+		if let PkgLocation::Remote {
+			url,
+			repo_id,
+			token,
+		} = &self.location
+		{
 			let path = self.cached_path(paths);
 			if force || !path.exists() {
 				let url = url
 					.as_ref()
 					.expect("URL for remote package missing")
 					.clone();
+				let repo_id = repo_id.clone();
+				let token = token.clone();
 				let client = client.clone();
-				return Some(async move { download::file(url, path, &client).await });
+				return Some(async move {
+					let token = token.as_ref().map(RepoToken::get).transpose()?;
+					let bytes = download::download_with_token(url, token.as_deref(), &client)
+						.await
+						.map_err(|e| map_auth_error(e, &repo_id))?
+						.bytes()
+						.await
+						.context("Failed to convert download to raw bytes")?;
+					tokio::fs::write(&path, bytes)
+						.await
+						.context("Failed to write package file")
+				});
 			}
 		}
 
@@ -300,6 +333,7 @@ mod tests {
 			PkgLocation::Remote {
 				url: None,
 				repo_id: String::new(),
+				token: None,
 			},
 			PackageContentType::Script,
 			HashSet::new(),
@@ -311,6 +345,7 @@ mod tests {
 			PkgLocation::Remote {
 				url: None,
 				repo_id: String::new(),
+				token: None,
 			},
 			PackageContentType::Declarative,
 			HashSet::new(),
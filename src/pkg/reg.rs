@@ -74,7 +74,7 @@ impl PkgRegistry {
 			.await
 			.context("Failed to query remote repositories")?;
 		if let Some(result) = query {
-			return Ok(self.insert(
+			Ok(self.insert(
 				req.clone(),
 				Package::new(
 					req.id.clone(),
@@ -82,7 +82,7 @@ impl PkgRegistry {
 					result.content_type,
 					result.flags,
 				),
-			));
+			))
 		} else {
 			Err(anyhow!("Package '{req}' does not exist"))
 		}
@@ -227,7 +227,7 @@ impl PkgRegistry {
 	}
 
 	/// Get the content type of a package
-	pub async fn content_type<'a>(
+	pub async fn content_type(
 		&mut self,
 		req: &ArcPkgReq,
 		paths: &Paths,
@@ -1,5 +1,7 @@
+use crate::config::secret::{LazySecret, SecretSource};
 use crate::io::paths::Paths;
-use mcvm_core::net::download;
+use mcvm_core::io::{json_from_file, json_to_file};
+use mcvm_core::net::download::{self, CacheValidators};
 use mcvm_pkg::repo::{
 	get_api_url, get_index_url, PackageFlag, RepoIndex, RepoMetadata, RepoPkgEntry,
 };
@@ -13,10 +15,11 @@ use reqwest::Client;
 
 use std::borrow::Cow;
 use std::collections::HashSet;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
 use std::fs::File;
 use std::io::{BufReader, Cursor};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use super::core::{
 	get_all_core_packages, get_core_package_content_type, get_core_package_count, is_core_package,
@@ -30,6 +33,47 @@ pub struct PkgRepo {
 	pub id: String,
 	location: PkgRepoLocation,
 	index: Later<RepoIndex>,
+	/// The ID of the plugin that provided this repository, if any
+	plugin: Option<String>,
+	/// The token to authenticate with the repository, if any
+	token: Option<RepoToken>,
+}
+
+/// A bearer token used to authenticate with a private repository.
+/// It is never shown in debug output so that it doesn't end up in logs
+#[derive(Clone)]
+pub struct RepoToken(LazySecret);
+
+impl RepoToken {
+	/// Create a new RepoToken
+	pub fn new(token: &str) -> Self {
+		Self(LazySecret::new(SecretSource::Plain(token.into())))
+	}
+
+	/// Create a new RepoToken from a secret that is resolved when the token is first used
+	pub fn from_secret(secret: LazySecret) -> Self {
+		Self(secret)
+	}
+
+	/// Get the secret contents of the token
+	pub fn get(&self) -> anyhow::Result<Arc<str>> {
+		self.0.get().context("Failed to get repository token")
+	}
+}
+
+impl Debug for RepoToken {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "RepoToken(***)")
+	}
+}
+
+/// The result from syncing a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncResult {
+	/// The index was downloaded again because it changed
+	Updated,
+	/// The index hasn't changed since the last sync
+	UpToDate,
 }
 
 /// Location for a PkgRepo
@@ -60,9 +104,34 @@ impl PkgRepo {
 			id: id.to_owned(),
 			location,
 			index: Later::new(),
+			plugin: None,
+			token: None,
 		}
 	}
 
+	/// Set the token used to authenticate with this repository
+	pub fn with_token(mut self, token: RepoToken) -> Self {
+		self.token = Some(token);
+		self
+	}
+
+	/// Checks whether this repository sends a token to authenticate
+	pub fn is_authenticated(&self) -> bool {
+		self.token.is_some()
+	}
+
+	/// Create a new PkgRepo provided by a plugin
+	pub fn from_plugin(id: &str, location: PkgRepoLocation, plugin: &str) -> Self {
+		let mut out = Self::new(id, location);
+		out.plugin = Some(plugin.to_string());
+		out
+	}
+
+	/// Get the ID of the plugin that provided this repository, if any
+	pub fn get_plugin(&self) -> Option<&str> {
+		self.plugin.as_deref()
+	}
+
 	/// Create the core repository
 	pub fn core() -> Self {
 		Self::new("core", PkgRepoLocation::Core)
@@ -106,29 +175,79 @@ impl PkgRepo {
 		Ok(())
 	}
 
+	/// The cached path of the validators for the index, which are used
+	/// to check if the index has changed
+	pub fn get_validators_path(&self, paths: &Paths) -> PathBuf {
+		paths
+			.pkg_index_cache
+			.join(format!("{}.validators.json", &self.id))
+	}
+
 	/// Update the currently cached index file
-	pub async fn sync(&mut self, paths: &Paths, client: &Client) -> anyhow::Result<()> {
+	pub async fn sync(
+		&mut self,
+		paths: &Paths,
+		client: &Client,
+		o: &mut impl MCVMOutput,
+	) -> anyhow::Result<SyncResult> {
 		match &self.location {
 			PkgRepoLocation::Local(path) => {
 				let bytes = tokio::fs::read(path).await?;
 				tokio::fs::write(self.get_path(paths), &bytes).await?;
 				let mut cursor = Cursor::new(&bytes);
 				self.set_index(&mut cursor).context("Failed to set index")?;
+				Ok(SyncResult::Updated)
 			}
 			PkgRepoLocation::Remote(url) => {
-				let bytes = download::bytes(get_index_url(url), client)
+				let url = get_index_url(url);
+				let index_path = self.get_path(paths);
+				let validators_path = self.get_validators_path(paths);
+				// The validators are useless without the cached index that they describe
+				let validators = if index_path.exists() && validators_path.exists() {
+					json_from_file(&validators_path).unwrap_or_default()
+				} else {
+					CacheValidators::default()
+				};
+
+				let token = self.token.as_ref().map(RepoToken::get).transpose()?;
+				let result =
+					download::bytes_if_modified(&url, &validators, token.as_deref(), client, o)
+						.await
+						.map_err(|e| map_auth_error(e, &self.id))
+						.context("Failed to download index")?;
+				if result.is_none() {
+					let file = File::open(&index_path).context("Failed to open cached index")?;
+					if self.set_index(&mut BufReader::new(file)).is_ok() {
+						return Ok(SyncResult::UpToDate);
+					}
+				}
+
+				// Download the whole index again if it changed or the cached one is invalid
+				let (bytes, validators) = match result {
+					Some(result) => result,
+					None => download::bytes_if_modified(
+						&url,
+						&CacheValidators::default(),
+						token.as_deref(),
+						client,
+						o,
+					)
 					.await
-					.context("Failed to download index")?;
-				tokio::fs::write(self.get_path(paths), &bytes)
+					.map_err(|e| map_auth_error(e, &self.id))
+					.context("Failed to download index")?
+					.context("Server did not return the index")?,
+				};
+				tokio::fs::write(&index_path, &bytes)
 					.await
 					.context("Failed to write index to cached file")?;
+				json_to_file(&validators_path, &validators)
+					.context("Failed to write index validators to cached file")?;
 				let mut cursor = Cursor::new(&bytes);
 				self.set_index(&mut cursor).context("Failed to set index")?;
+				Ok(SyncResult::Updated)
 			}
-			PkgRepoLocation::Core => {}
+			PkgRepoLocation::Core => Ok(SyncResult::UpToDate),
 		}
-
-		Ok(())
 	}
 
 	/// Make sure that the repository index is downloaded
@@ -151,13 +270,13 @@ impl PkgRepo {
 				match self.set_index(&mut file) {
 					Ok(..) => {}
 					Err(..) => {
-						self.sync(paths, client)
+						self.sync(paths, client, o)
 							.await
 							.context("Failed to sync index")?;
 					}
 				};
 			} else {
-				self.sync(paths, client)
+				self.sync(paths, client, o)
 					.await
 					.context("Failed to sync index")?;
 			}
@@ -213,8 +332,9 @@ impl PkgRepo {
 			self.ensure_index(paths, client, o).await?;
 			let index = self.index.get();
 			if let Some(entry) = index.packages.get(id) {
-				let location = get_package_location(entry, &self.location, &self.id)
-					.context("Failed to get location of package")?;
+				let location =
+					get_package_location(entry, &self.location, &self.id, self.token.as_ref())
+						.context("Failed to get location of package")?;
 				return Ok(Some(RepoQueryResult {
 					location,
 					content_type: get_content_type(entry).await,
@@ -268,7 +388,7 @@ impl PkgRepo {
 		paths: &Paths,
 		client: &Client,
 		o: &mut impl MCVMOutput,
-	) -> anyhow::Result<Cow<RepoMetadata>> {
+	) -> anyhow::Result<Cow<'_, RepoMetadata>> {
 		self.ensure_index(paths, client, o).await?;
 
 		if let PkgRepoLocation::Core = &self.location {
@@ -351,16 +471,42 @@ pub async fn get_content_type(entry: &RepoPkgEntry) -> PackageContentType {
 	}
 }
 
+/// Replace an error from a rejected download with a clearer one about the repository's authentication
+pub fn map_auth_error(error: anyhow::Error, repo_id: &str) -> anyhow::Error {
+	if download::is_unauthorized(&error) {
+		error.context(format!(
+			"Repository authentication failed for '{repo_id}'. Check the token in its configuration"
+		))
+	} else {
+		error
+	}
+}
+
+/// Checks if two URLs have the same scheme, host, and port
+fn is_same_origin(url1: &str, url2: &str) -> bool {
+	match (reqwest::Url::parse(url1), reqwest::Url::parse(url2)) {
+		(Ok(url1), Ok(url2)) => url1.origin() == url2.origin(),
+		_ => false,
+	}
+}
+
 /// Gets the location of a package from it's repository entry in line with url and path rules
 pub fn get_package_location(
 	entry: &RepoPkgEntry,
 	repo_location: &PkgRepoLocation,
 	repo_id: &str,
+	token: Option<&RepoToken>,
 ) -> anyhow::Result<PkgLocation> {
 	if let Some(url) = &entry.url {
+		// Don't leak the token to other servers that packages are hosted on
+		let token = match repo_location {
+			PkgRepoLocation::Remote(repo_url) if is_same_origin(url, repo_url) => token.cloned(),
+			_ => None,
+		};
 		Ok(PkgLocation::Remote {
 			url: Some(url.clone()),
 			repo_id: repo_id.to_string(),
+			token,
 		})
 	} else if let Some(path) = &entry.path {
 		let path = PathBuf::from(path);
@@ -382,6 +528,7 @@ pub fn get_package_location(
 					Ok(PkgLocation::Remote {
 						url: Some(url.to_owned() + trimmed),
 						repo_id: repo_id.to_string(),
+						token: token.cloned(),
 					})
 				} else {
 					bail!("Package path on remote repository is non-relative")
@@ -403,3 +550,46 @@ pub fn get_package_location(
 		bail!("Neither url nor path entry present in package")
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_package_location_token() {
+		let repo = PkgRepoLocation::Remote("https://example.com/repo".into());
+		let token = RepoToken::new("secret");
+		let entry = |url: &str| RepoPkgEntry {
+			url: Some(url.into()),
+			path: None,
+			content_type: None,
+			flags: HashSet::new(),
+		};
+
+		let location = get_package_location(
+			&entry("https://example.com/repo/pkg.json"),
+			&repo,
+			"repo",
+			Some(&token),
+		)
+		.unwrap();
+		assert!(matches!(
+			location,
+			PkgLocation::Remote {
+				token: Some(..),
+				..
+			}
+		));
+
+		let location = get_package_location(
+			&entry("https://other.example.com/pkg.json"),
+			&repo,
+			"repo",
+			Some(&token),
+		)
+		.unwrap();
+		assert!(matches!(location, PkgLocation::Remote { token: None, .. }));
+
+		assert_eq!(format!("{token:?}"), "RepoToken(***)");
+	}
+}
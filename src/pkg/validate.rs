@@ -0,0 +1,472 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use mcvm_core::net::download;
+use mcvm_parse::instruction::InstrKind;
+use mcvm_parse::lex::{lex, PosError, Side, Token, TokenAndPos};
+use mcvm_parse::parse::parse;
+use mcvm_parse::vars::Value;
+use mcvm_pkg::declarative::{deserialize_declarative_package, DeclarativePackageRelations};
+use mcvm_pkg::metadata::eval_metadata;
+use mcvm_pkg::properties::eval_properties;
+use mcvm_pkg::{PackageContentType, PkgRequest, PkgRequestSource};
+use mcvm_shared::output::MCVMOutput;
+use reqwest::Client;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::reg::PkgRegistry;
+use crate::io::paths::Paths;
+
+/// A problem found while validating a package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationProblem {
+	/// The line of the package that the problem is on, if it is known
+	pub line: Option<usize>,
+	/// A description of the problem
+	pub message: String,
+}
+
+impl ValidationProblem {
+	fn new(line: Option<usize>, message: impl Into<String>) -> Self {
+		Self {
+			line,
+			message: message.into(),
+		}
+	}
+}
+
+impl Display for ValidationProblem {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if let Some(line) = self.line {
+			write!(f, "Line {line}: {}", self.message)
+		} else {
+			write!(f, "{}", self.message)
+		}
+	}
+}
+
+/// The results of checking the contents of a package without using the network
+#[derive(Debug, Default)]
+pub struct ContentsCheck {
+	/// The problems that were found
+	pub problems: Vec<ValidationProblem>,
+	/// The IDs of the packages that this package has relations with, along with the lines they are on
+	pub relations: Vec<(String, Option<usize>)>,
+	/// The URLs of the addons of this package, along with the lines they are on
+	pub addon_urls: Vec<(String, Option<usize>)>,
+}
+
+/// Checks the contents of a package. Every problem is collected instead of stopping at the first one.
+/// Relations and addon URLs that are written literally are gathered so that they can be checked
+/// further, but ones that come from variables can't be known until the package is evaluated
+pub fn check_package_contents(contents: &str, content_type: PackageContentType) -> ContentsCheck {
+	let mut out = ContentsCheck::default();
+	match content_type {
+		PackageContentType::Script => check_script(contents, &mut out),
+		PackageContentType::Declarative => check_declarative(contents, &mut out),
+	}
+
+	out
+}
+
+/// Fully validates a package. On top of checking its contents, this makes sure that the packages
+/// it has relations with exist in the configured repositories and that its addon URLs are reachable
+pub async fn validate_package(
+	contents: &str,
+	content_type: PackageContentType,
+	reg: &mut PkgRegistry,
+	paths: &Paths,
+	client: &Client,
+	o: &mut impl MCVMOutput,
+) -> Vec<ValidationProblem> {
+	let check = check_package_contents(contents, content_type);
+	let mut problems = check.problems;
+
+	let mut checked = HashSet::new();
+	for (relation, line) in check.relations {
+		if !checked.insert(relation.clone()) {
+			continue;
+		}
+		let req = Arc::new(PkgRequest::parse(&relation, PkgRequestSource::Repository));
+		if let Err(e) = reg.ensure_package(&req, paths, client, o).await {
+			problems.push(ValidationProblem::new(
+				line,
+				format!("Related package '{relation}' could not be found: {e:#}"),
+			));
+		}
+	}
+
+	problems.extend(check_addon_urls(check.addon_urls, client).await);
+	problems.sort_by_key(|x| x.line);
+
+	problems
+}
+
+/// Checks that addon URLs are reachable, with a few requests at a time
+async fn check_addon_urls(
+	urls: Vec<(String, Option<usize>)>,
+	client: &Client,
+) -> Vec<ValidationProblem> {
+	let mut checked = HashSet::new();
+	let mut join = JoinSet::new();
+	let sem = Arc::new(Semaphore::new(download::get_transfer_limit()));
+	for (url, line) in urls {
+		if !checked.insert(url.clone()) {
+			continue;
+		}
+		let client = client.clone();
+		let sem = sem.clone();
+		join.spawn(async move {
+			let _permit = sem.acquire().await;
+			download::check_url_reachable(&url, &client)
+				.await
+				.err()
+				.map(|e| {
+					ValidationProblem::new(line, format!("Addon URL {url} is not reachable: {e:#}"))
+				})
+		});
+	}
+
+	let mut out = Vec::new();
+	while let Some(result) = join.join_next().await {
+		match result {
+			Ok(Some(problem)) => out.push(problem),
+			Ok(None) => {}
+			Err(e) => out.push(ValidationProblem::new(
+				None,
+				format!("Failed to check addon URL: {e}"),
+			)),
+		}
+	}
+
+	out
+}
+
+/// Checks the contents of a package script
+fn check_script(contents: &str, out: &mut ContentsCheck) {
+	let tokens = match lex(contents) {
+		Ok(tokens) => tokens,
+		Err(e) => {
+			out.problems.push(ValidationProblem::new(
+				get_error_line(&e),
+				format!("Syntax error: {e:#}"),
+			));
+			return;
+		}
+	};
+
+	let bracket_problems = check_brackets(&tokens);
+	let brackets_balanced = bracket_problems.is_empty();
+	out.problems.extend(bracket_problems);
+
+	let parsed = match parse(tokens.iter()) {
+		Ok(parsed) => parsed,
+		Err(e) => {
+			// Unbalanced brackets make the parser fail with confusing errors, so they are reported on their own
+			if brackets_balanced {
+				out.problems.push(ValidationProblem::new(
+					get_error_line(&e),
+					format!("Syntax error: {e:#}"),
+				));
+			}
+			return;
+		}
+	};
+
+	if let Err(e) = eval_metadata(&parsed) {
+		out.problems.push(ValidationProblem::new(
+			None,
+			format!("Invalid metadata: {e:#}"),
+		));
+	}
+	if let Err(e) = eval_properties(&parsed) {
+		out.problems.push(ValidationProblem::new(
+			None,
+			format!("Invalid properties: {e:#}"),
+		));
+	}
+
+	for block in parsed.blocks.values() {
+		for instr in &block.contents {
+			let line = Some(*instr.pos.row());
+			let mut add_relation = |value: &Value| {
+				if let Value::Literal(id) = value {
+					out.relations.push((id.clone(), line));
+				}
+			};
+			match &instr.kind {
+				InstrKind::Require(groups) => {
+					for package in groups.iter().flatten() {
+						add_relation(&package.value);
+					}
+				}
+				InstrKind::Refuse(package)
+				| InstrKind::Recommend(_, package)
+				| InstrKind::Bundle(package)
+				| InstrKind::Extend(package) => add_relation(package),
+				InstrKind::Compat(package, compat) => {
+					add_relation(package);
+					add_relation(compat);
+				}
+				InstrKind::Addon { url, hashes, .. } => {
+					if let Value::Literal(url) = url {
+						out.addon_urls.push((url.clone(), line));
+					}
+					for (hash, kind, len) in [
+						(&hashes.sha256, "SHA-256", 64),
+						(&hashes.sha512, "SHA-512", 128),
+					] {
+						if let Value::Literal(hash) = hash {
+							if let Some(problem) = check_hash(hash, kind, len) {
+								out.problems.push(ValidationProblem::new(line, problem));
+							}
+						}
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+}
+
+/// Gets the line that an error from lexing or parsing happened on
+fn get_error_line(error: &anyhow::Error) -> Option<usize> {
+	error.downcast_ref::<PosError>().map(|x| *x.pos().row())
+}
+
+/// Finds brackets that are never closed, or are closed by the wrong kind of bracket
+fn check_brackets(tokens: &[TokenAndPos]) -> Vec<ValidationProblem> {
+	let mut out = Vec::new();
+	let mut open = Vec::new();
+	for (tok, pos) in tokens {
+		let (kind, side) = match tok {
+			Token::Curly(side) => ('{', side),
+			Token::Square(side) => ('[', side),
+			Token::Paren(side) => ('(', side),
+			_ => continue,
+		};
+		let line = Some(*pos.row());
+		match side {
+			Side::Left => open.push((kind, line)),
+			Side::Right => {
+				let closing = tok.as_string();
+				// A mismatched bracket leaves the open one alone so that its real closing bracket still matches
+				match open.last() {
+					Some((opening, _)) if *opening == kind => {
+						open.pop();
+					}
+					Some((opening, opening_line)) => {
+						out.push(ValidationProblem::new(
+							line,
+							format!(
+								"'{closing}' does not match the '{opening}' opened on line {}",
+								opening_line.unwrap_or_default()
+							),
+						));
+					}
+					None => {
+						out.push(ValidationProblem::new(
+							line,
+							format!("'{closing}' was never opened"),
+						));
+					}
+				}
+			}
+		}
+	}
+	for (opening, line) in open {
+		out.push(ValidationProblem::new(
+			line,
+			format!("'{opening}' is never closed"),
+		));
+	}
+
+	out
+}
+
+/// Checks the contents of a declarative package
+fn check_declarative(contents: &str, out: &mut ContentsCheck) {
+	let pkg = match deserialize_declarative_package(contents) {
+		Ok(pkg) => pkg,
+		Err(e) => {
+			let line = e.downcast_ref::<serde_json::Error>().map(|x| x.line());
+			out.problems.push(ValidationProblem::new(
+				line,
+				format!("Invalid package: {e:#}"),
+			));
+			return;
+		}
+	};
+
+	if let Err(e) = pkg.meta.check_validity() {
+		out.problems.push(ValidationProblem::new(
+			None,
+			format!("Invalid metadata: {e:#}"),
+		));
+	}
+	if let Err(e) = pkg.properties.check_validity() {
+		out.problems.push(ValidationProblem::new(
+			None,
+			format!("Invalid properties: {e:#}"),
+		));
+	}
+
+	add_declarative_relations(&pkg.relations, out);
+	for rule in &pkg.conditional_rules {
+		add_declarative_relations(&rule.properties.relations, out);
+	}
+
+	let mut addons: Vec<_> = pkg.addons.iter().collect();
+	addons.sort_by_key(|x| x.0);
+	for (addon_id, addon) in addons {
+		for (i, version) in addon.versions.iter().enumerate() {
+			let name = match &version.version {
+				Some(version) => format!("Version '{version}' of addon '{addon_id}'"),
+				None => format!("Version {} of addon '{addon_id}'", i + 1),
+			};
+			add_declarative_relations(&version.relations, out);
+			if version.url.is_none() && version.path.is_none() {
+				out.problems.push(ValidationProblem::new(
+					None,
+					format!("{name} has neither a URL nor a path"),
+				));
+			}
+			if let Some(url) = &version.url {
+				out.addon_urls.push((url.clone(), None));
+			}
+			for (hash, kind, len) in [
+				(&version.hashes.sha256, "SHA-256", 64),
+				(&version.hashes.sha512, "SHA-512", 128),
+			] {
+				if let Some(problem) = hash.as_ref().and_then(|x| check_hash(x, kind, len)) {
+					out.problems
+						.push(ValidationProblem::new(None, format!("{name}: {problem}")));
+				}
+			}
+		}
+	}
+}
+
+/// Adds the packages in declarative relations to the ones that need to be checked
+fn add_declarative_relations(relations: &DeclarativePackageRelations, out: &mut ContentsCheck) {
+	let ids = relations
+		.dependencies
+		.iter()
+		.chain(relations.explicit_dependencies.iter())
+		.chain(relations.conflicts.iter())
+		.chain(relations.extensions.iter())
+		.chain(relations.bundled.iter())
+		.cloned()
+		.chain(
+			relations
+				.compats
+				.iter()
+				.flat_map(|(package, compat)| [package.clone(), compat.clone()]),
+		)
+		.chain(
+			relations
+				.recommendations
+				.iter()
+				.map(|x| x.value.to_string()),
+		);
+	out.relations.extend(ids.map(|x| (x, None)));
+}
+
+/// Checks that a hash is a hex string with the right length for its kind
+fn check_hash(hash: &str, kind: &str, len: usize) -> Option<String> {
+	if hash.len() != len || !hash.chars().all(|x| x.is_ascii_hexdigit()) {
+		Some(format!(
+			"{kind} hash '{hash}' is not valid. It should be {len} hexadecimal characters"
+		))
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_script_brackets() {
+		let check = check_package_contents(
+			"@install {\n\trequire <\"foo\">;\n\tif side client {\n}\n",
+			PackageContentType::Script,
+		);
+		assert_eq!(
+			check.problems,
+			vec![ValidationProblem::new(Some(1), "'{' is never closed")]
+		);
+
+		let check = check_package_contents("@install {\n)\n}", PackageContentType::Script);
+		assert_eq!(
+			check.problems,
+			vec![ValidationProblem::new(
+				Some(2),
+				"')' does not match the '{' opened on line 1"
+			)]
+		);
+	}
+
+	#[test]
+	fn test_script_syntax_errors() {
+		let check = check_package_contents("@install {\n\t%\n}", PackageContentType::Script);
+		assert_eq!(check.problems.len(), 1);
+		assert_eq!(check.problems[0].line, Some(2));
+
+		let check = check_package_contents("@install {\n\tif;\n}", PackageContentType::Script);
+		assert_eq!(check.problems.len(), 1);
+		assert_eq!(check.problems[0].line, Some(2));
+	}
+
+	#[test]
+	fn test_script_hashes_with_unbalanced_brackets() {
+		let contents = "@install {\n\taddon \"test\" \"test.jar\" (kind: mod, url: \"https://example.com/test.jar\", hash_sha256: \"abc\");\n\tif side client {\n}\n";
+		let check = check_package_contents(contents, PackageContentType::Script);
+		let lines: Vec<_> = check.problems.iter().map(|x| x.line).collect();
+		assert_eq!(lines, vec![Some(1), Some(2)]);
+	}
+
+	#[test]
+	fn test_declarative_check() {
+		let contents = r#"
+			{
+				"addons": {
+					"test": {
+						"kind": "mod",
+						"versions": [
+							{
+								"url": "https://example.com/test.jar",
+								"hashes": {
+									"sha256": "abc"
+								}
+							},
+							{
+								"version": "2"
+							}
+						]
+					}
+				},
+				"relations": {
+					"dependencies": "foo",
+					"compats": [[ "bar", "baz" ]]
+				}
+			}
+		"#;
+		let check = check_package_contents(contents, PackageContentType::Declarative);
+		assert_eq!(check.problems.len(), 2);
+		assert_eq!(
+			check.addon_urls,
+			vec![("https://example.com/test.jar".into(), None)]
+		);
+		let relations: Vec<_> = check.relations.into_iter().map(|x| x.0).collect();
+		assert_eq!(relations, vec!["foo", "bar", "baz"]);
+
+		let check = check_package_contents("{\n\"addons\": 5\n}", PackageContentType::Declarative);
+		assert_eq!(check.problems.len(), 1);
+		assert_eq!(check.problems[0].line, Some(2));
+	}
+}
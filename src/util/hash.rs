@@ -70,6 +70,20 @@ pub fn hash_file_with_best_hash(path: &Path, result: BestHashResult) -> anyhow::
 	Ok(matches)
 }
 
+/// Compute the SHA-256 hash of a file as a hex string
+pub fn hash_file_sha256(path: &Path) -> anyhow::Result<String> {
+	let file = File::open(path).context("Failed to open file for checksum")?;
+	let hash =
+		digest_reader::<Sha256, _>(BufReader::new(file)).context("Failed to compute file hash")?;
+
+	Ok(hex::encode(hash))
+}
+
+/// Compute the SHA-256 hash of some data as a hex string
+pub fn hash_sha256(data: &[u8]) -> String {
+	hex::encode(Sha256::digest(data))
+}
+
 /// Digest a reader into a hasher
 pub fn digest_reader<D: Digest, R: Read>(mut reader: R) -> anyhow::Result<Vec<u8>> {
 	let mut digest = D::new();
@@ -105,4 +119,18 @@ mod tests {
 				.unwrap()
 		);
 	}
+
+	#[test]
+	fn test_hash_file_sha256() {
+		let path = std::env::temp_dir().join("mcvm_test_hash_file_sha256");
+		std::fs::write(&path, "Hello").unwrap();
+		let hash = hash_file_sha256(&path).unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		assert_eq!(
+			hash,
+			"185f8db32271fe25f561a6fc938b2e264306ec304eda518007d1764826381969"
+		);
+		assert_eq!(hash, hash_sha256(b"Hello"));
+	}
 }
@@ -1,7 +1,11 @@
 /// Utilities for working with hashes and checksums
 pub mod hash;
 
+use std::path::Path;
+
+use anyhow::Context;
 use rand::Rng;
+use sysinfo::Disks;
 
 /// Selects a random set of n elements from a list. The return slice will not necessarily be of n length
 pub fn select_random_n_items_from_list<T>(list: &[T], n: usize) -> Vec<&T> {
@@ -20,3 +24,155 @@ pub fn select_random_n_items_from_list<T>(list: &[T], n: usize) -> Vec<&T> {
 
 	chosen
 }
+
+/// Fuzzy-matches a query against some text, returning a score if it matches.
+/// Higher scores are better matches. Exact and substring matches are always preferred over
+/// matches where the characters of the query are only found in order
+pub fn fuzzy_match_score(query: &str, text: &str) -> Option<u32> {
+	let query = query.to_lowercase();
+	let text = text.to_lowercase();
+	if query.is_empty() {
+		return None;
+	}
+
+	if text == query {
+		return Some(100);
+	}
+	if text.starts_with(&query) {
+		return Some(90);
+	}
+	if text.contains(&query) {
+		return Some(75);
+	}
+
+	// Check if the query is a subsequence of the text, penalizing gaps between characters
+	let mut text_chars = text.chars();
+	let mut gaps = 0;
+	for query_char in query.chars() {
+		loop {
+			let text_char = text_chars.next()?;
+			if text_char == query_char {
+				break;
+			}
+			gaps += 1;
+		}
+	}
+
+	Some(50u32.saturating_sub(gaps).max(1))
+}
+
+/// Get the total size of a file or directory in bytes
+pub fn get_size(path: &Path) -> u64 {
+	if path.is_dir() {
+		let Ok(entries) = path.read_dir() else {
+			return 0;
+		};
+		entries
+			.filter_map(|x| x.ok())
+			.map(|x| get_size(&x.path()))
+			.sum()
+	} else {
+		path.metadata().map(|x| x.len()).unwrap_or_default()
+	}
+}
+
+/// Get the space in bytes that is available on the disk that a path is on,
+/// or None if the disk can't be found
+pub fn get_available_space(path: &Path) -> Option<u64> {
+	let disks = Disks::new_with_refreshed_list();
+	// The disk with the longest mount point that contains the path is the one it is on
+	disks
+		.iter()
+		.filter(|x| path.starts_with(x.mount_point()))
+		.max_by_key(|x| x.mount_point().as_os_str().len())
+		.map(|x| x.available_space())
+}
+
+/// Move a directory, copying it and removing the original if it can't be renamed,
+/// such as when moving to another disk. If copying fails, the partial copy is removed
+pub fn move_dir(src: &Path, dest: &Path) -> anyhow::Result<()> {
+	if std::fs::rename(src, dest).is_ok() {
+		return Ok(());
+	}
+
+	let dest_existed = dest.exists();
+	if let Err(e) = copy_dir_recursive(src, dest) {
+		// Don't leave a partial copy behind
+		if !dest_existed {
+			let _ = std::fs::remove_dir_all(dest);
+		}
+		return Err(e)
+			.with_context(|| format!("Failed to copy {} to {}", src.display(), dest.display()));
+	}
+	std::fs::remove_dir_all(src)
+		.with_context(|| format!("Failed to remove {} after copying it", src.display()))?;
+
+	Ok(())
+}
+
+/// Copy a directory and all of its contents
+pub fn copy_dir_recursive(src: &Path, dest: &Path) -> anyhow::Result<()> {
+	std::fs::create_dir_all(dest)?;
+	for entry in src.read_dir()? {
+		let entry = entry?;
+		let dest_path = dest.join(entry.file_name());
+		let file_type = entry.file_type()?;
+		// Keep links as links instead of copying what they point to
+		#[cfg(target_family = "unix")]
+		if file_type.is_symlink() {
+			std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &dest_path)?;
+			continue;
+		}
+		if file_type.is_dir() {
+			copy_dir_recursive(&entry.path(), &dest_path)?;
+		} else {
+			std::fs::copy(entry.path(), &dest_path)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Format a size in bytes to be human-readable, using binary units
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+	if bytes < 1024 {
+		return format!("{bytes} B");
+	}
+
+	let mut size = bytes as f64;
+	let mut unit = "B";
+	for next in UNITS {
+		if size < 1024.0 {
+			break;
+		}
+		size /= 1024.0;
+		unit = next;
+	}
+
+	format!("{size:.1} {unit}")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_format_size() {
+		assert_eq!(format_size(0), "0 B");
+		assert_eq!(format_size(1023), "1023 B");
+		assert_eq!(format_size(1024), "1.0 KiB");
+		assert_eq!(format_size(1536 * 1024), "1.5 MiB");
+		assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+	}
+
+	#[test]
+	fn test_fuzzy_match_score() {
+		assert_eq!(fuzzy_match_score("sodium", "sodium"), Some(100));
+		assert_eq!(fuzzy_match_score("sod", "sodium"), Some(90));
+		assert_eq!(fuzzy_match_score("API", "fabric-api"), Some(75));
+		assert_eq!(fuzzy_match_score("fapi", "fabric-api"), Some(44));
+		assert_eq!(fuzzy_match_score("xyz", "sodium"), None);
+		assert_eq!(fuzzy_match_score("", "sodium"), None);
+	}
+}